@@ -0,0 +1,23 @@
+use std::env;
+use std::process::Command;
+
+// embeds the commit being built as BATTLESNAKE_COMMIT for the /version route. a build outside a git checkout, like a
+// docker build without .git, can pass the commit in the same variable, otherwise it's left unset
+fn main() {
+    println!("cargo:rerun-if-env-changed=BATTLESNAKE_COMMIT");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    let commit = env::var("BATTLESNAKE_COMMIT").ok().or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "--short=12", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+    });
+    if let Some(commit) = commit.map(|commit| commit.trim().to_string()) {
+        if !commit.is_empty() {
+            println!("cargo:rustc-env=BATTLESNAKE_COMMIT={}", commit);
+        }
+    }
+}
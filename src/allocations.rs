@@ -0,0 +1,109 @@
+//! counts the allocations made on each thread, so a test can hold the move pipeline to an allocation budget. it's
+//! the test build's global allocator and otherwise hands everything to the system allocator
+
+use crate::fixtures::{self, Fixture};
+use crate::logic;
+use crate::search::tiles::{TileMap, TileSet};
+use crate::types::Coord;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // a thread being torn down has no counter left, its allocations aren't anyone's
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        return System.alloc(layout);
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static COUNTING: Counting = Counting;
+
+/// # allocations
+/// the allocations made on this thread while running a closure
+pub fn allocations<T>(run: impl FnOnce() -> T) -> (T, u64) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let value = run();
+    return (value, ALLOCATIONS.with(Cell::get) - before);
+}
+
+fn fixture(name: &str) -> Fixture {
+    return fixtures::load(Path::new(fixtures::FIXTURE_DIR))
+        .unwrap()
+        .into_iter()
+        .find(|fixture| fixture.name == name)
+        .unwrap();
+}
+
+#[test]
+fn escaping_a_box_allocates_little() {
+    let state = fixture("escape_from_box").state;
+    let (response, count) =
+        allocations(|| logic::get_move(&state.game, &state.turn, &state.board, &state.you));
+    assert_eq!(response["move"], "left");
+    // cloning the planned moves at every step of the searches took 521
+    assert!(count < 360, "{} allocations", count);
+}
+
+#[test]
+fn avoiding_poorly_connected_tiles_allocates_little() {
+    let state = fixture("poorly_connected").state;
+    let (response, count) =
+        allocations(|| logic::get_move(&state.game, &state.turn, &state.board, &state.you));
+    assert_eq!(response["move"], "down");
+    // a vector for every list of neighbours took 6721
+    assert!(count < 1000, "{} allocations", count);
+}
+
+#[test]
+fn visited_sets_allocate_once() {
+    let tiles: Vec<Coord> = (0..11)
+        .flat_map(|x| (0..11).map(move |y| Coord { x, y }))
+        .collect();
+    let (_, set) = allocations(|| {
+        let mut visited = TileSet::with_size(11, 11);
+        visited.extend(tiles.iter().copied());
+        return visited;
+    });
+    let (_, map) = allocations(|| {
+        let mut visited = TileMap::with_size(11, 11);
+        for tile in &tiles {
+            visited.insert(*tile, *tile);
+        }
+        return visited;
+    });
+    assert_eq!((set, map), (1, 1));
+    // a hashed set or map reallocates as it grows, a search visiting the whole board paid for each
+    let (_, hash_set) = allocations(|| {
+        let mut visited = HashSet::new();
+        for tile in &tiles {
+            visited.insert(*tile);
+        }
+        return visited;
+    });
+    let (_, hash_map) = allocations(|| {
+        let mut visited = HashMap::new();
+        for tile in &tiles {
+            visited.insert(*tile, *tile);
+        }
+        return visited;
+    });
+    assert!(
+        hash_set > set && hash_map > map,
+        "{} and {} allocations hashed",
+        hash_set,
+        hash_map
+    );
+}
@@ -0,0 +1,493 @@
+use crate::logic;
+use crate::phase::PhaseWeights;
+use crate::postmortem::{self, Death};
+use crate::search::minimax::SearchMode;
+use crate::types::{self, Coord, Ruleset};
+use clap::Parser;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::Instant;
+
+/// the timeout the arena tells the pipeline it has, short so the endgame solver doesn't dominate a game
+const ARENA_TIMEOUT: u32 = 100;
+
+/// how a snake in the arena picks its moves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// the full `get_move` pipeline the server plays with
+    Greedy,
+    /// the first move that doesn't hit a wall or a body
+    Safe,
+    /// a two turn alpha-beta search
+    Minimax,
+    /// a two turn search that averages the opponents' replies by how likely they are
+    Expectimax,
+    /// the full pipeline with one of `logic::PROFILES`, by name
+    Profile(&'static str),
+    /// the full pipeline with the default profile playing other phase weights, the weights `tune` tries out
+    Weighted(PhaseWeights),
+}
+
+impl Policy {
+    pub fn parse(name: &str) -> Option<Policy> {
+        return match name {
+            "greedy" => Some(Policy::Greedy),
+            "safe" => Some(Policy::Safe),
+            "minimax" => Some(Policy::Minimax),
+            "expectimax" => Some(Policy::Expectimax),
+            _ => logic::PROFILES
+                .into_iter()
+                .find(|(route, _)| *route == name)
+                .map(|(route, _)| Policy::Profile(route)),
+        };
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Policy::Greedy => "greedy",
+            Policy::Safe => "safe",
+            Policy::Minimax => "minimax",
+            Policy::Expectimax => "expectimax",
+            Policy::Profile(name) => name,
+            Policy::Weighted(_) => "weighted",
+        };
+    }
+
+    /// # choose
+    /// the move a snake plays
+    /// ## Returns:
+    /// the name of the move
+    fn choose(
+        &self,
+        game: &types::Game,
+        turn: u32,
+        board: &types::Board,
+        you: &types::Battlesnake,
+    ) -> String {
+        return match self {
+            Policy::Greedy => logic::get_move(game, &turn, board, you)["move"]
+                .as_str()
+                .unwrap_or("up")
+                .to_string(),
+            Policy::Profile(_) | Policy::Weighted(_) => {
+                let profile = match self {
+                    Policy::Weighted(phase_weights) => logic::StrategyProfile {
+                        phase_weights: *phase_weights,
+                        ..logic::StrategyProfile::DEFAULT
+                    },
+                    _ => logic::profile(self.name()).unwrap_or_default(),
+                };
+                let deadline =
+                    Instant::now() + logic::move_budget(game.timeout, logic::latency_buffer());
+                logic::get_move_profiled(game, &turn, board, you, &profile, deadline).response
+                    ["move"]
+                    .as_str()
+                    .unwrap_or("up")
+                    .to_string()
+            }
+            Policy::Safe => logic::fallback_move(board, you).to_string(),
+            Policy::Minimax | Policy::Expectimax => {
+                let search = if *self == Policy::Expectimax { SearchMode::Expectimax } else { SearchMode::Paranoid };
+                let profile = logic::StrategyProfile { search, ..logic::StrategyProfile::DEFAULT };
+                let mut search = profile.searcher(Ruleset::from(game));
+                let best_move = search.search(board, &you.id, turn, 2).best_move;
+                let name = best_move.and_then(|dir| {
+                    types::DIRECTIONS
+                        .entries()
+                        .find(|(_, unit)| **unit == dir)
+                        .map(|(name, _)| *name)
+                });
+                name.unwrap_or_else(|| logic::fallback_move(board, you))
+                    .to_string()
+            }
+        };
+    }
+}
+
+/// how to run a batch of games
+#[derive(Debug, Clone)]
+pub struct ArenaConfig {
+    pub games: u32,
+    pub size: u8,
+    /// games are drawn once this many turns have been played
+    pub turn_cap: u32,
+    pub seed: u64,
+    /// one snake per policy, the seats rotate every game so no policy keeps the best start
+    pub policies: Vec<Policy>,
+}
+
+impl Default for ArenaConfig {
+    fn default() -> Self {
+        return ArenaConfig {
+            games: 10,
+            size: 11,
+            turn_cap: 500,
+            seed: 0,
+            policies: vec![Policy::Greedy, Policy::Greedy],
+        };
+    }
+}
+
+/// how one policy fared over a batch of games
+#[derive(Debug, Default, Clone)]
+pub struct Record {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// how often each cause of death came up, keyed by its JSON name
+    pub deaths: BTreeMap<String, u32>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ArenaResult {
+    /// one record per seat, in the order of `ArenaConfig::policies`
+    pub records: Vec<Record>,
+    pub games: u32,
+    pub turns: u32,
+}
+
+impl ArenaResult {
+    pub fn average_length(&self) -> f32 {
+        if self.games == 0 {
+            return 0.0;
+        }
+        return self.turns as f32 / self.games as f32;
+    }
+}
+
+/// # starting_board
+/// a standard start: snakes stacked on spawn points picked at random, food next to every snake and in the center
+fn starting_board(size: u8, snakes: usize, rng: &mut StdRng) -> types::Board {
+    let (low, mid, high) = (1, size as i16 / 2, size as i16 - 2);
+    let mut spawns = vec![
+        Coord { x: low, y: low },
+        Coord { x: high, y: high },
+        Coord { x: low, y: high },
+        Coord { x: high, y: low },
+        Coord { x: mid, y: low },
+        Coord { x: mid, y: high },
+        Coord { x: low, y: mid },
+        Coord { x: high, y: mid },
+    ];
+    spawns.shuffle(rng);
+    let center = Coord { x: mid, y: mid };
+    let mut food = vec![center];
+    let mut battlesnakes = vec![];
+    for (i, spawn) in spawns.into_iter().take(snakes).enumerate() {
+        let toward_center = Coord {
+            x: spawn.x + (center.x - spawn.x).signum(),
+            y: spawn.y + (center.y - spawn.y).signum(),
+        };
+        if !food.contains(&toward_center) {
+            food.push(toward_center);
+        }
+        battlesnakes.push(types::Battlesnake {
+            id: format!("snake-{}", i),
+            name: format!("snake-{}", i),
+            health: 100,
+            body: vec![spawn; 3],
+            head: spawn,
+            length: 3,
+            shout: None,
+            squad: String::new(),
+            latency: None,
+        });
+    }
+    return types::Board {
+        height: size,
+        width: size,
+        food,
+        snakes: battlesnakes,
+        hazards: vec![],
+        hazard_odds: vec![],
+        hazard_walls: false,
+    };
+}
+
+/// # play
+/// plays a game to the end with `simulate_turn` as the referee
+/// ## Arguments:
+/// * game_id - the id the snakes are told, it seeds their randomness
+/// * seats - the policy of every snake, by board order
+/// * config - the board size and turn cap
+/// * rng - draws the start and the food spawns
+/// ## Returns:
+/// the winning seat if there is one, how each eliminated seat died, and the number of turns played
+fn play(
+    game_id: &str,
+    seats: &[Policy],
+    config: &ArenaConfig,
+    rng: &mut StdRng,
+) -> (Option<usize>, Vec<(usize, Death)>, u32) {
+    let game: types::Game = serde_json::from_value(json!({
+        "id": game_id,
+        "ruleset": { "name": "standard" },
+        "timeout": ARENA_TIMEOUT
+    }))
+    .unwrap();
+    let rules = Ruleset::from(&game);
+    let mut board = starting_board(config.size, seats.len(), rng);
+    let seat_of = |snake: &types::Battlesnake| -> usize {
+        return snake.id.trim_start_matches("snake-").parse().unwrap_or(0);
+    };
+    let mut deaths: Vec<(usize, Death)> = vec![];
+    let mut turn = 0;
+    while board.snakes.len() > 1 && turn < config.turn_cap {
+        let before = board.clone();
+        let chosen: Vec<String> = board
+            .snakes
+            .iter()
+            .map(|snake| seats[seat_of(snake)].choose(&game, turn, &board, snake))
+            .collect();
+        let moves: Vec<Coord> = chosen
+            .iter()
+            .map(|name| {
+                types::DIRECTIONS
+                    .get(name.as_str())
+                    .copied()
+                    .unwrap_or(types::DIRECTIONS["up"])
+            })
+            .collect();
+        turn += 1;
+        board.simulate_turn(&moves, turn, &rules);
+        board.spawn_food(&rules, rng);
+        for (snake, chosen) in before.snakes.iter().zip(&chosen) {
+            if board.snakes.iter().any(|survivor| survivor.id == snake.id) {
+                continue;
+            }
+            let last = postmortem::Turn {
+                turn: turn - 1,
+                board: before.clone(),
+                you: snake.clone(),
+                chosen: chosen.clone(),
+            };
+            deaths.push((
+                seat_of(snake),
+                postmortem::classify_death(&[last], &board, &rules),
+            ));
+        }
+    }
+    let winner = match board.snakes.as_slice() {
+        [survivor] => Some(seat_of(survivor)),
+        _ => None,
+    };
+    return (winner, deaths, turn);
+}
+
+/// # run_games
+/// plays a batch of games and tallies how every policy did
+pub fn run_games(config: &ArenaConfig) -> ArenaResult {
+    let seats = config.policies.len();
+    let mut result = ArenaResult {
+        records: vec![Record::default(); seats],
+        ..ArenaResult::default()
+    };
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    for game in 0..config.games {
+        // seat i is played by policy (i + game) % seats
+        let rotated: Vec<Policy> = (0..seats)
+            .map(|seat| config.policies[(seat + game as usize) % seats])
+            .collect();
+        let game_id = format!("arena-{}-{}", config.seed, game);
+        let (winner, deaths, turns) = play(&game_id, &rotated, config, &mut rng);
+        result.games += 1;
+        result.turns += turns;
+        for seat in 0..seats {
+            let record = &mut result.records[(seat + game as usize) % seats];
+            match winner {
+                Some(winner) if winner == seat => record.wins += 1,
+                Some(_) => record.losses += 1,
+                None => record.draws += 1,
+            }
+        }
+        for (seat, death) in deaths {
+            let record = &mut result.records[(seat + game as usize) % seats];
+            let cause = serde_json::to_value(&death)
+                .ok()
+                .and_then(|value| value["cause"].as_str().map(str::to_string))
+                .unwrap_or_else(|| "unknown".to_string());
+            *record.deaths.entry(cause).or_default() += 1;
+        }
+    }
+    return result;
+}
+
+/// the `arena` command's arguments
+#[derive(Parser, Debug)]
+#[command(name = "arena")]
+pub struct ArenaArgs {
+    /// the number of games to play
+    #[arg(long, default_value_t = 10)]
+    games: u32,
+    /// the width and height of the board
+    #[arg(long, default_value_t = 11, value_parser = clap::value_parser!(u8).range(5..))]
+    size: u8,
+    /// games are drawn once this many turns have been played
+    #[arg(long, default_value_t = 500)]
+    turns: u32,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// the policies to seat, comma separated: greedy, safe, minimax, expectimax or a strategy profile
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "greedy,greedy",
+        value_parser = |name: &str| Policy::parse(name).ok_or(format!("unknown policy {}", name))
+    )]
+    profiles: Vec<Policy>,
+}
+
+/// # run
+/// the `arena` command: plays the given policies against each other and prints how they did
+/// ## Arguments:
+/// * args - the parsed arguments
+/// * out - where the results are written
+/// ## Returns:
+/// the exit code, 2 when there are too few or too many policies
+pub fn run(args: &ArenaArgs, out: &mut impl Write) -> i32 {
+    if args.profiles.len() < 2 || args.profiles.len() > 8 {
+        let _ = writeln!(out, "the arena seats 2 to 8 policies");
+        return 2;
+    }
+    let config = ArenaConfig {
+        games: args.games,
+        size: args.size,
+        turn_cap: args.turns,
+        seed: args.seed,
+        policies: args.profiles.clone(),
+    };
+    let result = run_games(&config);
+    return match report(&config, &result, out) {
+        Ok(_) => 0,
+        Err(_) => 2,
+    };
+}
+
+/// # report
+/// prints a line per policy with its results and causes of death
+fn report(config: &ArenaConfig, result: &ArenaResult, out: &mut impl Write) -> io::Result<()> {
+    let names: Vec<&str> = config.policies.iter().map(Policy::name).collect();
+    writeln!(
+        out,
+        "{} games of {} on {}x{}, seed {}, {:.1} turns on average",
+        result.games,
+        names.join(" vs "),
+        config.size,
+        config.size,
+        config.seed,
+        result.average_length()
+    )?;
+    for (name, record) in names.iter().zip(&result.records) {
+        let deaths: Vec<String> = record
+            .deaths
+            .iter()
+            .map(|(cause, count)| format!("{} {}", cause, count))
+            .collect();
+        writeln!(
+            out,
+            "{}: {} won, {} drawn, {} lost, deaths: {}",
+            name,
+            record.wins,
+            record.draws,
+            record.losses,
+            if deaths.is_empty() {
+                "none".to_string()
+            } else {
+                deaths.join(", ")
+            }
+        )?;
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn small_games_play_to_completion() {
+        let config = ArenaConfig {
+            games: 4,
+            size: 7,
+            turn_cap: 150,
+            seed: 7,
+            policies: vec![Policy::Greedy, Policy::Safe],
+        };
+        let result = run_games(&config);
+        assert_eq!(result.games, 4);
+        for record in &result.records {
+            assert_eq!(record.wins + record.draws + record.losses, 4);
+        }
+        let wins: u32 = result.records.iter().map(|record| record.wins).sum();
+        let losses: u32 = result.records.iter().map(|record| record.losses).sum();
+        assert_eq!(wins, losses);
+        let deaths: u32 = result
+            .records
+            .iter()
+            .flat_map(|record| record.deaths.values())
+            .sum();
+        assert!(deaths >= losses);
+        assert!(result.average_length() > 0.0);
+    }
+
+    #[test]
+    fn seed_replays_the_same_games() {
+        // the greedy pipeline reads the clock, the search and the safe policy don't
+        let config = ArenaConfig {
+            games: 2,
+            size: 7,
+            turn_cap: 60,
+            seed: 11,
+            policies: vec![Policy::Minimax, Policy::Safe],
+        };
+        let first = run_games(&config);
+        let second = run_games(&config);
+        assert_eq!(first.turns, second.turns);
+        assert_eq!(first.records[0].wins, second.records[0].wins);
+    }
+
+    #[test]
+    fn command_reports_every_policy() {
+        let args = ArenaArgs::try_parse_from([
+            "arena",
+            "--games",
+            "2",
+            "--size",
+            "7",
+            "--turns",
+            "50",
+            "--seed",
+            "3",
+            "--profiles",
+            "safe,safe",
+        ])
+        .unwrap();
+        let mut out: Vec<u8> = vec![];
+        assert_eq!(run(&args, &mut out), 0);
+        let out = String::from_utf8(out).unwrap();
+        assert!(
+            out.starts_with("2 games of safe vs safe on 7x7, seed 3"),
+            "{}",
+            out
+        );
+        assert_eq!(out.lines().count(), 3);
+
+        assert!(ArenaArgs::try_parse_from(["arena", "--profiles", "fast,safe"]).is_err());
+        let alone = ArenaArgs::try_parse_from(["arena", "--profiles", "safe"]).unwrap();
+        assert_eq!(run(&alone, &mut vec![]), 2);
+    }
+
+    #[test]
+    fn strategy_profiles_take_seats() {
+        let args = ArenaArgs::try_parse_from(["arena", "--profiles", "defensive,greedy"]).unwrap();
+        assert_eq!(
+            args.profiles,
+            [Policy::Profile("defensive"), Policy::Greedy]
+        );
+        assert_eq!(args.games, ArenaConfig::default().games);
+    }
+}
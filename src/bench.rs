@@ -0,0 +1,159 @@
+use crate::fixtures;
+use crate::logic;
+use crate::types;
+use clap::Parser;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// the `bench` command's arguments
+#[derive(Parser, Debug)]
+#[command(name = "bench")]
+pub struct BenchArgs {
+    /// a directory of move requests, `<name>.json` each. the regression fixtures by default
+    #[arg(long, default_value = fixtures::FIXTURE_DIR)]
+    corpus: PathBuf,
+    /// how many times each position is played
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    runs: u32,
+}
+
+/// a position of the corpus and how long it took
+struct Timing {
+    name: String,
+    chosen: String,
+    /// every run, fastest first
+    runs: Vec<Duration>,
+}
+
+/// # load_corpus
+/// reads every move request in a directory, the `.expect.json` files next to the fixtures are skipped
+/// ## Returns:
+/// the positions by name, or what couldn't be read
+fn load_corpus(dir: &Path) -> Result<Vec<(String, types::GameState)>, String> {
+    let entries = fs::read_dir(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    let mut corpus = vec![];
+    for entry in entries {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if !name.ends_with(".expect.json") => name.strip_suffix(".json"),
+            _ => None,
+        };
+        let Some(name) = name else {
+            continue;
+        };
+        let state = fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| serde_json::from_str(&contents).map_err(|err| err.to_string()))
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        corpus.push((name.to_string(), state));
+    }
+    corpus.sort_by(|a, b| a.0.cmp(&b.0));
+    return Ok(corpus);
+}
+
+/// # time
+/// plays a position with the game's own time budget
+fn time(name: &str, state: &types::GameState, runs: u32) -> Timing {
+    let mut timing = Timing {
+        name: name.to_string(),
+        chosen: String::new(),
+        runs: vec![],
+    };
+    for _ in 0..runs {
+        let started = Instant::now();
+        let deadline = started + logic::move_budget(state.game.timeout, logic::latency_buffer());
+        let decision =
+            logic::get_move_until(&state.game, &state.turn, &state.board, &state.you, deadline);
+        timing.runs.push(started.elapsed());
+        timing.chosen = decision.response["move"]
+            .as_str()
+            .unwrap_or("none")
+            .to_string();
+    }
+    timing.runs.sort();
+    return timing;
+}
+
+/// # run
+/// the `bench` command: times the move pipeline on every position of a corpus and prints the median and slowest run
+/// of each, then of the whole corpus
+/// ## Arguments:
+/// * args - the parsed arguments
+/// * out - where the timings are written
+/// ## Returns:
+/// the exit code, 2 when the corpus can't be read or is empty
+pub fn run(args: &BenchArgs, out: &mut impl Write) -> i32 {
+    let corpus = match load_corpus(&args.corpus) {
+        Ok(corpus) if !corpus.is_empty() => corpus,
+        Ok(_) => {
+            let _ = writeln!(out, "{} has no positions", args.corpus.display());
+            return 2;
+        }
+        Err(err) => {
+            let _ = writeln!(out, "couldn't read the corpus: {}", err);
+            return 2;
+        }
+    };
+    let timings: Vec<Timing> = corpus
+        .iter()
+        .map(|(name, state)| time(name, state, args.runs))
+        .collect();
+    return match report(&timings, out) {
+        Ok(_) => 0,
+        Err(_) => 2,
+    };
+}
+
+/// # report
+/// prints a line per position and a summary over every run
+fn report(timings: &[Timing], out: &mut impl Write) -> io::Result<()> {
+    let millis = |duration: &Duration| duration.as_secs_f64() * 1000.0;
+    for timing in timings {
+        writeln!(
+            out,
+            "{}: {}, median {:.1}ms, max {:.1}ms",
+            timing.name,
+            timing.chosen,
+            millis(&timing.runs[timing.runs.len() / 2]),
+            millis(timing.runs.last().unwrap())
+        )?;
+    }
+    let mut all: Vec<&Duration> = timings.iter().flat_map(|timing| &timing.runs).collect();
+    all.sort();
+    writeln!(
+        out,
+        "{} positions, {} runs: median {:.1}ms, p95 {:.1}ms, max {:.1}ms",
+        timings.len(),
+        all.len(),
+        millis(all[all.len() / 2]),
+        millis(all[(all.len() * 95 / 100).min(all.len() - 1)]),
+        millis(all[all.len() - 1])
+    )?;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bench_times_the_fixtures() {
+        let args = BenchArgs::try_parse_from(["bench", "--runs", "1"]).unwrap();
+        let mut out: Vec<u8> = vec![];
+        assert_eq!(run(&args, &mut out), 0);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("corner: right, median "), "{}", out);
+        let fixtures = fixtures::load(Path::new(fixtures::FIXTURE_DIR)).unwrap();
+        let summary = format!(
+            "\n{} positions, {} runs: median ",
+            fixtures.len(),
+            fixtures.len()
+        );
+        assert!(out.contains(&summary), "{}", out);
+
+        let missing = BenchArgs::try_parse_from(["bench", "--corpus", "testdata/missing"]).unwrap();
+        assert_eq!(run(&missing, &mut vec![]), 2);
+    }
+}
@@ -0,0 +1,200 @@
+//! how much of the timeout to keep back for the network, learned from the latency the engine reports for our own
+//! moves. the engine measures the whole round trip and sends it back in `you.latency` with the next request, so the
+//! difference from the time we took to answer is the network's share, which we can't see from here. the buffer grows to
+//! cover that share with a margin, and when every recent turn finished well inside the timeout it shrinks, handing the
+//! time back to the searches. the buffer a game ends with is kept per region, see `results::ResultLog::learned_buffer`,
+//! so the next game starts from it
+
+use crate::types;
+use log::info;
+use std::collections::VecDeque;
+use std::env;
+use std::time::Duration;
+
+/// the region or host the server answers from, the buffer is learned separately for each. the host name when it isn't
+/// set
+pub const REGION_ENV: &str = "BATTLESNAKE_REGION";
+
+/// how many of the latest turns the buffer is worked out over
+pub const BUDGET_WINDOW: usize = 5;
+
+/// what the buffer keeps on top of the network's share of the latency
+pub const BUFFER_MARGIN: Duration = Duration::from_millis(30);
+
+/// the time to spare on every turn in the window that has the buffer shrink
+pub const SHRINK_SPARE: Duration = Duration::from_millis(300);
+
+/// how much the buffer shrinks by on a turn with time to spare
+pub const SHRINK_STEP: Duration = Duration::from_millis(10);
+
+/// the smallest buffer kept whatever the engine reports
+pub const MIN_BUFFER: Duration = Duration::from_millis(20);
+
+/// the largest buffer kept, past it the network is too slow for any budget to help
+pub const MAX_BUFFER: Duration = Duration::from_millis(400);
+
+/// # region
+/// the region the server answers from, `REGION_ENV`, the host name or `local`
+pub fn region() -> String {
+    return env::var(REGION_ENV)
+        .or_else(|_| env::var("HOSTNAME"))
+        .ok()
+        .filter(|region| !region.trim().is_empty())
+        .unwrap_or_else(|| "local".to_string());
+}
+
+/// what a reported latency said about a turn
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sample {
+    /// the latency less the time we took to answer
+    network: Duration,
+    /// the timeout less the latency
+    spare: Duration,
+}
+
+/// the buffer a game is played with, kept with the game's memory
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BudgetTracker {
+    /// none until the game's first move, which starts from the buffer learned for the region
+    buffer: Option<Duration>,
+    /// the turn we answered last and the time it took us
+    answered: Option<(u32, Duration)>,
+    /// the latest `BUDGET_WINDOW` turns, oldest first
+    samples: VecDeque<Sample>,
+}
+
+impl BudgetTracker {
+    /// # buffer_for
+    /// the buffer to answer a move request with, after learning from the latency it reports for our previous move
+    /// ## Arguments:
+    /// * state - the move request
+    /// * starting - the buffer the game starts from, asked for on its first move
+    /// ## Returns:
+    /// the time to keep back from the timeout
+    pub fn buffer_for(&mut self, state: &types::GameState, starting: impl FnOnce() -> Duration) -> Duration {
+        let buffer = *self.buffer.get_or_insert_with(starting);
+        let answered = self.answered.take();
+        // the latency is for the move before this one, a turn we didn't answer tells us nothing
+        let Some((latency, elapsed)) = state
+            .you
+            .latency
+            .zip(answered.filter(|(turn, _)| turn + 1 == state.turn).map(|(_, elapsed)| elapsed))
+        else {
+            return buffer;
+        };
+        let latency = Duration::from_millis(latency as u64);
+        self.samples.push_back(Sample {
+            network: latency.saturating_sub(elapsed),
+            spare: Duration::from_millis(state.game.timeout as u64).saturating_sub(latency),
+        });
+        if self.samples.len() > BUDGET_WINDOW {
+            self.samples.pop_front();
+        }
+        let needed = self.samples.iter().map(|sample| sample.network).max().unwrap_or_default() + BUFFER_MARGIN;
+        let adjusted = if needed > buffer {
+            // halfway there on each turn, a single slow round trip doesn't take the whole budget with it
+            let short = needed - buffer;
+            buffer + (short / 2).max(Duration::from_millis(1)).min(short)
+        } else if self.samples.len() == BUDGET_WINDOW && self.samples.iter().all(|sample| sample.spare >= SHRINK_SPARE)
+        {
+            buffer.saturating_sub(SHRINK_STEP).max(needed)
+        } else {
+            buffer
+        };
+        let adjusted = adjusted.clamp(MIN_BUFFER, MAX_BUFFER);
+        if adjusted != buffer {
+            info!("TURN {}: the latency buffer went from {:?} to {:?}", state.turn, buffer, adjusted);
+        }
+        self.buffer = Some(adjusted);
+        return adjusted;
+    }
+
+    /// # answered
+    /// records how long we took to answer a turn, measured here, for the next request's latency to be set against
+    pub fn answered(&mut self, turn: u32, elapsed: Duration) {
+        self.answered = Some((turn, elapsed));
+    }
+
+    /// # buffer
+    /// the buffer the game is playing with, none before its first move
+    pub fn buffer(&self) -> Option<Duration> {
+        return self.buffer;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::logic;
+
+    const STARTING: Duration = logic::DEFAULT_LATENCY_BUFFER;
+
+    fn state(turn: u32, latency: Option<u32>) -> types::GameState {
+        let mut state: types::GameState =
+            serde_json::from_str(include_str!("../testdata/fixtures/corner.json")).unwrap();
+        state.turn = turn;
+        state.game.timeout = 500;
+        state.you.latency = latency;
+        return state;
+    }
+
+    /// plays the turns through a tracker, each answered in `elapsed` ms and reported back with `network` ms on top
+    fn play(tracker: &mut BudgetTracker, turns: std::ops::Range<u32>, elapsed: u64, network: u64) -> Vec<Duration> {
+        return turns
+            .map(|turn| {
+                let latency = tracker.answered.map(|(_, elapsed)| (elapsed.as_millis() as u64 + network) as u32);
+                let buffer = tracker.buffer_for(&state(turn, latency), || STARTING);
+                tracker.answered(turn, Duration::from_millis(elapsed));
+                return buffer;
+            })
+            .collect();
+    }
+
+    #[test]
+    fn a_slow_network_grows_the_buffer_until_it_covers_it() {
+        let mut tracker = BudgetTracker::default();
+        let needed = Duration::from_millis(170) + BUFFER_MARGIN;
+        // 350ms of compute reported as 520ms, 170ms on the network
+        let buffers = play(&mut tracker, 0..12, 350, 170);
+        assert_eq!(buffers[0], STARTING);
+        assert!(buffers.windows(2).all(|pair| pair[1] > pair[0] || pair[1] == needed), "{:?}", buffers);
+        let last = *buffers.last().unwrap();
+        assert_eq!(last, needed);
+        // and the pipeline's deadline comes that much sooner
+        let budget = logic::move_budget(500, last);
+        assert!(budget < logic::move_budget(500, STARTING));
+        assert!(budget <= Duration::from_millis(500) - Duration::from_millis(170));
+    }
+
+    #[test]
+    fn time_to_spare_shrinks_the_buffer() {
+        let mut tracker = BudgetTracker::default();
+        // 100ms of compute, 20ms on the network and 380ms to spare
+        let buffers = play(&mut tracker, 0..30, 100, 20);
+        // the window fills before it starts shrinking
+        assert!(buffers[..BUDGET_WINDOW].iter().all(|buffer| *buffer == STARTING), "{:?}", buffers);
+        assert_eq!(buffers[BUDGET_WINDOW], STARTING - SHRINK_STEP);
+        // down to what the network needs and no further
+        assert_eq!(buffers.last(), Some(&(Duration::from_millis(20) + BUFFER_MARGIN)));
+
+        // a turn that comes close to the timeout holds it where it is
+        let mut tracker = BudgetTracker::default();
+        play(&mut tracker, 0..5, 100, 20);
+        let buffers = play(&mut tracker, 5..10, 400, 20);
+        assert!(buffers.iter().all(|buffer| *buffer == buffers[0]), "{:?}", buffers);
+    }
+
+    #[test]
+    fn only_the_latency_of_our_last_answer_counts() {
+        let mut tracker = BudgetTracker::default();
+        assert_eq!(tracker.buffer(), None);
+        assert_eq!(tracker.buffer_for(&state(3, Some(480)), || Duration::from_millis(60)), Duration::from_millis(60));
+        // no latency reported, or one for a turn we didn't answer
+        tracker.answered(3, Duration::from_millis(100));
+        assert_eq!(tracker.buffer_for(&state(4, None), || STARTING), Duration::from_millis(60));
+        tracker.answered(4, Duration::from_millis(100));
+        assert_eq!(tracker.buffer_for(&state(6, Some(480)), || STARTING), Duration::from_millis(60));
+        tracker.answered(6, Duration::from_millis(100));
+        assert!(tracker.buffer_for(&state(7, Some(480)), || STARTING) > Duration::from_millis(60));
+    }
+}
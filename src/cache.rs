@@ -0,0 +1,133 @@
+use crate::logic::Alongside;
+use crate::types::Coord;
+use std::any::Any;
+use std::collections::HashMap;
+
+/// a value cached on a turn
+#[derive(Debug)]
+#[allow(dead_code)] // the strategies that carry work across turns will read the slots
+pub struct Cached<T> {
+    pub turn: u32,
+    pub value: T,
+}
+
+impl<T> Cached<T> {
+    /// # fresh
+    /// whether the value is from this turn or the one before it
+    fn fresh(&self, turn: u32) -> bool {
+        return self.turn + 1 >= turn;
+    }
+}
+
+/// the move weights predicted for each opponent, by snake id
+pub(crate) type OpponentPredictions = HashMap<String, Vec<(Coord, f32)>>;
+
+/// what the strategies work out on one turn and want again on the next, kept with the game's memory so it goes when
+/// the game does. every value is tagged with the turn it was cached on, anything older than the previous turn is
+/// dropped as the game moves on
+#[derive(Debug, Default)]
+pub struct GameCache {
+    /// the latest turn the game has been seen at
+    turn: u32,
+    /// the A* path we were following
+    pub planned_path: Option<Cached<Vec<Coord>>>,
+    /// the board's empty regions, largest first
+    pub last_components: Option<Cached<Vec<Vec<Coord>>>>,
+    pub opponent_policy_state: Option<Cached<OpponentPredictions>>,
+    /// the moves left of the way out of a pin we committed to
+    pub escape_plan: Option<Cached<Vec<Coord>>>,
+    /// everything without a slot of its own, by the turn it was cached on and its name
+    entries: HashMap<(u32, &'static str), Box<dyn Any + Send>>,
+}
+
+#[allow(dead_code)] // the strategies that carry work across turns will cache through this
+impl GameCache {
+    /// # advance
+    /// moves the cache on to a turn, dropping everything older than the turn before it. going back a turn, as a
+    /// replayed request does, drops nothing
+    pub fn advance(&mut self, turn: u32) {
+        if turn <= self.turn {
+            return;
+        }
+        self.turn = turn;
+        self.entries.retain(|(cached, _), _| cached + 1 >= turn);
+        keep_fresh(&mut self.planned_path, turn);
+        keep_fresh(&mut self.last_components, turn);
+        keep_fresh(&mut self.opponent_policy_state, turn);
+        keep_fresh(&mut self.escape_plan, turn);
+    }
+
+    /// # insert
+    /// caches a value without a slot of its own
+    /// ## Arguments:
+    /// * turn - the turn the value was worked out on
+    /// * name - what it is, a value cached under the same turn and name is replaced
+    /// * value - the value
+    pub fn insert<T: Any + Send>(&mut self, turn: u32, name: &'static str, value: T) {
+        self.entries.insert((turn, name), Box::new(value));
+    }
+
+    /// # get
+    /// ## Returns:
+    /// the value cached under a turn and name, none if there isn't one of that type or it's been dropped
+    pub fn get<T: Any>(&self, turn: u32, name: &'static str) -> Option<&T> {
+        return self.entries.get(&(turn, name))?.downcast_ref::<T>();
+    }
+
+    /// # escape_for
+    /// the moves left of the way out of a pin we committed to on the turn before
+    /// ## Returns:
+    /// the moves to play from `turn` on, empty when we didn't commit to one
+    pub fn escape_for(&self, turn: u32) -> Vec<Coord> {
+        return match &self.escape_plan {
+            Some(cached) if cached.turn + 1 == turn => cached.value.clone(),
+            _ => vec![],
+        };
+    }
+
+    /// # commit_escape
+    /// remembers the moves left of the way out of a pin after the move played on a turn, none forgets it
+    pub fn commit_escape(&mut self, turn: u32, rest: &[Coord]) {
+        self.escape_plan = (!rest.is_empty()).then(|| Cached {
+            turn,
+            value: rest.to_vec(),
+        });
+    }
+
+    /// # alongside_for
+    /// the longer snake that was running alongside us on the turn before, none if nobody was
+    pub fn alongside_for(&self, turn: u32) -> Option<Alongside> {
+        return self.get::<Alongside>(turn.checked_sub(1)?, "alongside").cloned();
+    }
+
+    /// # commit_alongside
+    /// remembers the longer snake running alongside us on a turn, none forgets it
+    pub fn commit_alongside(&mut self, turn: u32, alongside: Option<Alongside>) {
+        match alongside {
+            Some(alongside) => self.insert(turn, "alongside", alongside),
+            None => _ = self.entries.remove(&(turn, "alongside")),
+        }
+    }
+
+    /// # len
+    /// the number of values cached, slots included
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        return self.entries.len()
+            + self.planned_path.is_some() as usize
+            + self.last_components.is_some() as usize
+            + self.opponent_policy_state.is_some() as usize
+            + self.escape_plan.is_some() as usize;
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+}
+
+fn keep_fresh<T>(slot: &mut Option<Cached<T>>, turn: u32) {
+    if slot.as_ref().is_some_and(|cached| !cached.fresh(turn)) {
+        *slot = None;
+    }
+}
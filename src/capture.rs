@@ -0,0 +1,196 @@
+//! the last turns of every game we didn't win, written out as fixtures. a loss is the best regression test there is,
+//! each capture is a directory of move requests in the same form as `testdata/fixtures`, ready to be given an expect
+//! file and moved into the corpus
+
+use crate::postmortem::{GameResult, PostMortem};
+use crate::store::GameMemory;
+use log::{info, warn};
+use serde_json::json;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// set to a directory, `captured_fixtures` for example, to capture the games we don't win there
+pub const CAPTURE_DIR_ENV: &str = "BATTLESNAKE_CAPTURE_DIR";
+
+/// the most megabytes of captures to keep, `DEFAULT_CAPTURE_MB` when it isn't set
+pub const CAPTURE_MB_ENV: &str = "BATTLESNAKE_CAPTURE_MB";
+
+pub const DEFAULT_CAPTURE_MB: u64 = 100;
+
+/// how many of the last turns we remember are captured
+pub const CAPTURE_TURNS: usize = 5;
+
+/// writes the games we don't win to `<dir>/<date>_<game_id>/`, a `turn_<n>.json` for each of the last turns and a
+/// `capture.json` with the moves we chose and the post-mortem
+pub struct Capture {
+    dir: Option<PathBuf>,
+    /// no more games are captured once the directory holds this many bytes
+    max_bytes: u64,
+}
+
+impl Capture {
+    /// # from_env
+    /// captures to `CAPTURE_DIR_ENV` up to `CAPTURE_MB_ENV` megabytes, or nothing when the directory isn't set
+    pub fn from_env() -> Self {
+        let max_mb = std::env::var(CAPTURE_MB_ENV)
+            .ok()
+            .and_then(|mb| mb.parse().ok())
+            .unwrap_or(DEFAULT_CAPTURE_MB);
+        return match std::env::var(CAPTURE_DIR_ENV) {
+            Ok(dir) => Capture::new(PathBuf::from(dir), max_mb * 1024 * 1024),
+            Err(_) => Capture::disabled(),
+        };
+    }
+
+    /// # disabled
+    /// captures nothing
+    pub fn disabled() -> Self {
+        return Capture {
+            dir: None,
+            max_bytes: 0,
+        };
+    }
+
+    /// # new
+    /// captures to a directory until it holds `max_bytes`
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        return Capture {
+            dir: Some(dir),
+            max_bytes,
+        };
+    }
+
+    /// # game_over
+    /// captures a game we didn't win
+    /// ## Arguments:
+    /// * route - the personality that played it
+    /// * memory - the turns we remember
+    /// * postmortem - how the game ended and why
+    pub fn game_over(&self, route: &str, memory: &GameMemory, postmortem: &PostMortem) {
+        let Some(dir) = &self.dir else {
+            return;
+        };
+        if postmortem.result == GameResult::Win || memory.states.is_empty() {
+            return;
+        }
+        let used = disk_usage(dir);
+        if used >= self.max_bytes {
+            warn!("not capturing {}, {} already holds {} bytes", postmortem.game_id, dir.display(), used);
+            return;
+        }
+        let name = format!("{}_{}", date(SystemTime::now()), file_safe(&postmortem.game_id));
+        let capture = dir.join(&name);
+        match write(&capture, route, memory, postmortem) {
+            Ok(()) => info!("captured {} to {}", postmortem.game_id, capture.display()),
+            Err(err) => warn!("couldn't capture {} to {}: {}", postmortem.game_id, capture.display(), err),
+        }
+    }
+}
+
+fn write(capture: &Path, route: &str, memory: &GameMemory, postmortem: &PostMortem) -> io::Result<()> {
+    fs::create_dir_all(capture)?;
+    let skip = memory.states.len().saturating_sub(CAPTURE_TURNS);
+    let mut moves = serde_json::Map::new();
+    for (state, chosen) in memory.states.iter().zip(&memory.moves).skip(skip) {
+        fs::write(capture.join(format!("turn_{}.json", state.turn)), serde_json::to_string_pretty(state)?)?;
+        moves.insert(state.turn.to_string(), json!(chosen));
+    }
+    let summary = json!({ "route": route, "moves": moves, "postmortem": postmortem });
+    fs::write(capture.join("capture.json"), serde_json::to_string_pretty(&summary)?)?;
+    return Ok(());
+}
+
+/// # disk_usage
+/// the bytes in the files of a directory and the directories in it, nothing when it isn't there
+fn disk_usage(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    return entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => disk_usage(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum();
+}
+
+/// # file_safe
+/// a game id with anything that isn't a letter, a digit, a dash or an underscore replaced, for a directory name
+fn file_safe(game_id: &str) -> String {
+    return game_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+}
+
+/// # date
+/// the UTC date of a time as `YYYY-MM-DD`
+fn date(time: SystemTime) -> String {
+    let days = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() / 86_400) as i64;
+    // days to the proleptic Gregorian calendar, from Howard Hinnant's `civil_from_days`
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    return format!("{:04}-{:02}-{:02}", year, month, day);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::postmortem;
+    use crate::store::GameStore;
+    use crate::types;
+    use std::time::Duration;
+
+    #[test]
+    fn dates_and_names() {
+        assert_eq!(date(SystemTime::UNIX_EPOCH), "1970-01-01");
+        assert_eq!(date(SystemTime::UNIX_EPOCH + Duration::from_secs(951_782_400)), "2000-02-29");
+        assert_eq!(date(SystemTime::UNIX_EPOCH + Duration::from_secs(1_792_195_200)), "2026-10-17");
+        assert_eq!(file_safe("../a b/c-d_e"), "___a_b_c-d_e");
+    }
+
+    #[test]
+    fn captures_stop_at_the_cap() {
+        let dir = std::env::temp_dir().join(format!("battlesnake-capped-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let state: types::GameState =
+            serde_json::from_str(&fs::read_to_string("testdata/fixtures/pinned_against_the_wall.json").unwrap())
+                .unwrap();
+        let store = GameStore::new(Duration::from_secs(60));
+        let game_over = |game_id: &str| {
+            let mut state = state.clone();
+            state.game.id = game_id.to_string();
+            store.start("", game_id);
+            store.record("", &state, "down");
+            let memory = store.end("", game_id).unwrap();
+            // nobody's left on the board, a draw isn't a win either
+            let mut board = state.board.clone();
+            board.snakes.clear();
+            let record = postmortem::analyse(&state.game, state.turn + 1, &board, &state.you, &memory.history());
+            return (memory, record);
+        };
+        // a capture takes a few kilobytes, the second one finds the cap reached
+        let capture = Capture::new(dir.clone(), 1);
+        let (memory, record) = game_over("first");
+        capture.game_over("", &memory, &record);
+        let (memory, record) = game_over("second");
+        capture.game_over("", &memory, &record);
+        let captured = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(record.result, GameResult::Draw);
+        assert_eq!(captured, 1);
+    }
+}
@@ -0,0 +1,326 @@
+//! the strategy tunables, read from a TOML file at startup so they can be changed without a rebuild. every field is
+//! optional and falls back to the value the snake has always played with:
+//!
+//! ```toml
+//! connection_threshold = 0.5
+//! degree_threshold = 2
+//! box_threshold = 0.3
+//! box_margin = 14
+//! hazard_cost = 16
+//! endgame_share = 0.25
+//! parallel_fills = false
+//! lookahead_depth = 0
+//! quiescence_plies = 2
+//! shrink = "pessimistic"
+//!
+//! [phase_weights.late]
+//! space = 10
+//! length = 30
+//! health = 1
+//! degree = 20
+//! food = 0
+//! center = 1
+//! ```
+
+use crate::logic::{self, StrategyProfile};
+use crate::phase::PhaseWeights;
+use crate::types::ShrinkMode;
+use log::info;
+use serde::Deserialize;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// the config file, `config.toml` in the working directory when it isn't set
+pub const CONFIG_ENV: &str = "BATTLESNAKE_CONFIG";
+pub const DEFAULT_CONFIG: &str = "config.toml";
+/// the deepest `lookahead_depth` goes, deeper searches don't finish in a move's time on a full board
+pub const MAX_LOOKAHEAD_DEPTH: u8 = 4;
+/// the most `quiescence_plies` goes, a head to head is settled within two turns of the heads closing in
+pub const MAX_QUIESCENCE_PLIES: u8 = 2;
+
+/// how the snake at the root plays. the personalities keep their own thresholds and share the rest
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StrategyConfig {
+    /// the share of the free tiles, 0 to 1, a tile has to stay connected to for A* to path through it. higher keeps
+    /// us out of narrow gaps but gives up food behind them
+    pub connection_threshold: f32,
+    /// the free neighbours, 0 to 4, a tile needs for A* to path through it. higher avoids corridors
+    pub degree_threshold: u8,
+    /// we go looking for a way out once we can reach less than this share of the free tiles, 0 to 1. higher escapes
+    /// earlier and more often
+    pub box_threshold: f32,
+    /// the tiles past our length, at most 100, a box has to hold for us to keep playing in it rather than head for the
+    /// way out. higher escapes from roomier boxes
+    pub box_margin: u16,
+    /// what a step onto a hazard costs A*, 1 to 100 against 1 for any other step. higher paths around hazards for
+    /// longer
+    pub hazard_cost: u16,
+    /// the share of the game's timeout, above 0 and at most 1, the endgame solver may spend proving a small duel
+    pub endgame_share: f32,
+    /// whether to flood fill the candidate moves side by side on large boards, worth it on a host with cores to spare
+    pub parallel_fills: bool,
+    /// the turns, at most `MAX_LOOKAHEAD_DEPTH`, the lookahead search looks ahead to second-guess the heuristics' move,
+    /// 0 leaves it out. deeper searches catch more traps but take the time the endgame solver could have had
+    pub lookahead_depth: u8,
+    /// the turns, at most `MAX_QUIESCENCE_PLIES`, the lookahead search goes past its depth while snake heads are close
+    /// enough to meet, 0 stops it at its depth
+    pub quiescence_plies: u8,
+    /// how lookahead models the royale shrink: `pessimistic` shrinks every side at once, `expected` covers every side
+    /// with the odds the engine picks it
+    pub shrink: ShrinkMode,
+    /// the weights moves are scored with in each phase, 0 to 1000 each. a phase left out keeps its defaults but a phase
+    /// that's there needs every weight, `tune` writes them out whole
+    pub phase_weights: PhaseWeights,
+}
+
+impl Default for StrategyConfig {
+    fn default() -> Self {
+        let profile = StrategyProfile::DEFAULT;
+        return StrategyConfig {
+            connection_threshold: profile.connection_threshold,
+            degree_threshold: profile.degree_threshold,
+            box_threshold: profile.box_threshold,
+            box_margin: profile.box_margin,
+            hazard_cost: profile.hazard_cost,
+            endgame_share: profile.endgame_share,
+            parallel_fills: profile.parallel_fills,
+            lookahead_depth: profile.lookahead_depth,
+            quiescence_plies: profile.quiescence_plies,
+            shrink: profile.shrink,
+            phase_weights: profile.phase_weights,
+        };
+    }
+}
+
+impl StrategyConfig {
+    /// # load
+    /// reads and validates a config file, a file that doesn't exist leaves every value at its default
+    /// ## Arguments:
+    /// * path - the config file
+    /// ## Returns:
+    /// the config, or why it couldn't be used
+    pub fn load(path: &Path) -> Result<StrategyConfig, String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                info!("No config at {}, playing with the defaults", path.display());
+                return Ok(StrategyConfig::default());
+            }
+            Err(err) => return Err(format!("couldn't read {}: {}", path.display(), err)),
+        };
+        let config: StrategyConfig = toml::from_str(&contents)
+            .map_err(|err| format!("couldn't parse {}: {}", path.display(), err))?;
+        config
+            .validate()
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        return Ok(config);
+    }
+
+    /// # validate
+    /// checks every value is in its range
+    /// ## Returns:
+    /// the first value out of its range
+    pub fn validate(&self) -> Result<(), String> {
+        let shares = [
+            ("connection_threshold", self.connection_threshold),
+            ("box_threshold", self.box_threshold),
+        ];
+        for (name, value) in shares {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(format!("{} must be between 0 and 1, got {}", name, value));
+            }
+        }
+        if self.degree_threshold > 4 {
+            return Err(format!(
+                "degree_threshold must be at most 4, got {}",
+                self.degree_threshold
+            ));
+        }
+        if self.box_margin > 100 {
+            return Err(format!("box_margin must be at most 100, got {}", self.box_margin));
+        }
+        if !(1..=100).contains(&self.hazard_cost) {
+            return Err(format!(
+                "hazard_cost must be between 1 and 100, got {}",
+                self.hazard_cost
+            ));
+        }
+        if self.lookahead_depth > MAX_LOOKAHEAD_DEPTH {
+            return Err(format!(
+                "lookahead_depth must be at most {}, got {}",
+                MAX_LOOKAHEAD_DEPTH, self.lookahead_depth
+            ));
+        }
+        if self.quiescence_plies > MAX_QUIESCENCE_PLIES {
+            return Err(format!(
+                "quiescence_plies must be at most {}, got {}",
+                MAX_QUIESCENCE_PLIES, self.quiescence_plies
+            ));
+        }
+        let mut phase_weights = self.phase_weights;
+        for (phase, weights) in phase_weights.phases_mut() {
+            for (name, value) in weights.named_mut() {
+                if !(0..=1000).contains(value) {
+                    return Err(format!(
+                        "phase_weights.{}.{} must be between 0 and 1000, got {}",
+                        phase, name, value
+                    ));
+                }
+            }
+        }
+        if !(self.endgame_share > 0.0 && self.endgame_share <= 1.0) {
+            return Err(format!(
+                "endgame_share must be above 0 and at most 1, got {}",
+                self.endgame_share
+            ));
+        }
+        return Ok(());
+    }
+
+    /// # profile
+    /// the profile a personality plays with under this config
+    /// ## Arguments:
+    /// * name - `default` for the snake at the root, or one of `logic::PROFILES`
+    /// ## Returns:
+    /// the profile, none if there's no personality by that name
+    pub fn profile(&self, name: &str) -> Option<StrategyProfile> {
+        let thresholds = match name {
+            "default" => StrategyProfile {
+                connection_threshold: self.connection_threshold,
+                degree_threshold: self.degree_threshold,
+                box_threshold: self.box_threshold,
+                ..StrategyProfile::DEFAULT
+            },
+            _ => logic::profile(name)?,
+        };
+        return Some(StrategyProfile {
+            box_margin: self.box_margin,
+            hazard_cost: self.hazard_cost,
+            endgame_share: self.endgame_share,
+            parallel_fills: self.parallel_fills,
+            lookahead_depth: self.lookahead_depth,
+            quiescence_plies: self.quiescence_plies,
+            shrink: self.shrink,
+            phase_weights: self.phase_weights,
+            ..thresholds
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+    use std::path::PathBuf;
+
+    fn write(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!(
+            "battlesnake-config-{}-{}.toml",
+            name,
+            std::process::id()
+        ));
+        fs::write(&path, contents).unwrap();
+        return path;
+    }
+
+    #[test]
+    fn missing_file_plays_the_defaults() {
+        let config = StrategyConfig::load(Path::new("testdata/missing.toml")).unwrap();
+        assert_eq!(config, StrategyConfig::default());
+        assert_eq!(config.profile("default"), Some(StrategyProfile::default()));
+        assert_eq!(config.profile("defensive"), logic::profile("defensive"));
+    }
+
+    #[test]
+    fn checked_in_file_lists_the_defaults() {
+        assert_eq!(StrategyConfig::load(Path::new(DEFAULT_CONFIG)), Ok(StrategyConfig::default()));
+    }
+
+    #[test]
+    fn partial_file_keeps_the_other_defaults() {
+        let path = write(
+            "partial",
+            concat!(
+                "box_threshold = 0.45\nhazard_cost = 40\nparallel_fills = true\n",
+                "lookahead_depth = 2\nquiescence_plies = 1\nshrink = \"expected\"\n",
+            ),
+        );
+        let config = StrategyConfig::load(&path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(config.box_threshold, 0.45);
+        assert_eq!(config.hazard_cost, 40);
+        assert_eq!(config.connection_threshold, 0.5);
+        assert_eq!(config.degree_threshold, 2);
+
+        // the personalities keep their thresholds but share the hazard cost
+        let defensive = config.profile("defensive").unwrap();
+        assert_eq!(defensive.box_threshold, 0.4);
+        assert_eq!(defensive.hazard_cost, 40);
+        assert!(defensive.parallel_fills);
+        assert_eq!(defensive.lookahead_depth, 2);
+        assert_eq!(defensive.quiescence_plies, 1);
+        assert_eq!(defensive.shrink, ShrinkMode::Expected);
+        assert_eq!(config.profile("reckless"), None);
+    }
+
+    #[test]
+    fn phase_weights_are_read_a_phase_at_a_time() {
+        let late = "[phase_weights.late]\nspace = 12\nlength = 30\nhealth = 1\ndegree = 25\nfood = 0\ncenter = 2\n";
+        let path = write("phase-weights", late);
+        let config = StrategyConfig::load(&path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(config.phase_weights.early, PhaseWeights::DEFAULT.early);
+        assert_eq!(config.phase_weights.late.degree, 25);
+        assert_eq!(config.profile("aggressive").unwrap().phase_weights.late.space, 12);
+
+        // a phase that's there has to be whole
+        let path = write("partial-phase", "[phase_weights.mid]\nfood = 4\n");
+        let err = StrategyConfig::load(&path).unwrap_err();
+        fs::remove_file(path).unwrap();
+        assert!(err.contains("missing field `space`"), "{}", err);
+    }
+
+    #[test]
+    fn invalid_values_are_refused() {
+        let cases = [
+            (
+                "connection_threshold = 1.5",
+                "connection_threshold must be between 0 and 1, got 1.5",
+            ),
+            (
+                "box_threshold = -0.1",
+                "box_threshold must be between 0 and 1, got -0.1",
+            ),
+            (
+                "degree_threshold = 5",
+                "degree_threshold must be at most 4, got 5",
+            ),
+            ("box_margin = 101", "box_margin must be at most 100, got 101"),
+            ("lookahead_depth = 5", "lookahead_depth must be at most 4, got 5"),
+            ("quiescence_plies = 3", "quiescence_plies must be at most 2, got 3"),
+            (
+                "hazard_cost = 0",
+                "hazard_cost must be between 1 and 100, got 0",
+            ),
+            (
+                "endgame_share = 0.0",
+                "endgame_share must be above 0 and at most 1, got 0",
+            ),
+            (
+                "[phase_weights.early]\nspace = 10\nlength = 30\nhealth = 1\ndegree = 10\nfood = -6\ncenter = 1",
+                "phase_weights.early.food must be between 0 and 1000, got -6",
+            ),
+            ("box_treshold = 0.3", "unknown field `box_treshold`"),
+            ("hazard_cost = \"high\"", "invalid type"),
+        ];
+        for (contents, message) in cases {
+            let path = write("invalid", contents);
+            let err = StrategyConfig::load(&path).unwrap_err();
+            fs::remove_file(&path).unwrap();
+            assert!(err.contains(message), "{}: {}", contents, err);
+            assert!(err.contains(&path.display().to_string()), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! what a move works out about its board once and every step of the pipeline reads, instead of each of them working
+//! it out again from the board, the game board and our snake
+
+use crate::logic::{self, StrategyProfile};
+use crate::phase::Phase;
+use crate::search::eval::Weights;
+use crate::search::connectivity::Connectivity;
+use crate::types::{self, Coord, FastSet, Ruleset};
+
+/// a turn as the heuristics see it
+pub struct TurnContext<'a> {
+    /// the board the heuristics play on, the opponents' lengths as we perceive them
+    pub board: &'a types::Board,
+    /// the hashmap representation of the game board
+    pub game_board: &'a types::FastMap<Coord, types::Flags>,
+    pub you: &'a types::Battlesnake,
+    /// the tiles without a snake or a hazard on them, see `logic::num_free_tiles`
+    pub free_tiles: u16,
+    /// the heads of the snakes at least as long as us and the tiles next to them, where we could lose a head to head
+    pub threats: FastSet<Coord>,
+    /// the moves from our head a longer snake is herding us into a corner with, see `logic::shadowed_moves`
+    pub shadowed: logic::AdjTiles,
+    /// the moves that keep us in a lane a longer snake is running us down against a wall, none unless we're in one, see
+    /// `logic::sandwich_lane`
+    pub lane: Option<logic::AdjTiles>,
+    /// the moves that keep us beside a longer snake that's been running alongside us, none unless one has for long
+    /// enough, see `logic::alongside_moves`. the pipeline fills it in from the game's memory
+    pub alongside: Option<logic::AdjTiles>,
+    /// how hungry we are for the length the profile wants us to be, see `logic::LengthPolicy`
+    pub hunger: logic::Hunger,
+    /// the phase of the game we're playing in, the midgame until the pipeline says otherwise
+    pub phase: Phase,
+    /// the open tiles by region, labelled the first time a search asks
+    pub connectivity: Connectivity<'a>,
+    /// the hazards stacked on each tile and how deep they'll be by the turn we could reach them
+    pub hazards: types::HazardStacks,
+    /// the food that's all a starving opponent has left, we make for it however full we are, see
+    /// `starvation::Outlook::denial`. the pipeline fills it in
+    pub denials: Vec<Coord>,
+    pub profile: StrategyProfile,
+    pub rules: Ruleset,
+}
+
+impl<'a> TurnContext<'a> {
+    /// # new
+    /// works out a turn's context
+    /// ## Arguments:
+    /// * board - the board the heuristics play on
+    /// * game_board - the hashmap representation of the game board
+    /// * you - your battlesnake
+    /// * profile - how cautiously to play
+    /// * rules - the game's rules
+    pub fn new(
+        board: &'a types::Board,
+        game_board: &'a types::FastMap<Coord, types::Flags>,
+        you: &'a types::Battlesnake,
+        profile: StrategyProfile,
+        rules: Ruleset,
+    ) -> Self {
+        let mut threats = FastSet::default();
+        for snake in &board.snakes {
+            if snake != you && snake.length >= you.length {
+                threats.insert(snake.head);
+                threats.extend(types::DIRECTIONS.values().map(|dir| snake.head + *dir));
+            }
+        }
+        let mut ctx = TurnContext {
+            board,
+            game_board,
+            you,
+            free_tiles: logic::num_free_tiles(board),
+            threats,
+            shadowed: logic::AdjTiles::new(),
+            lane: None,
+            alongside: None,
+            hunger: profile.length.hunger(board, you),
+            phase: Phase::Mid,
+            connectivity: Connectivity::new(board, game_board, you),
+            hazards: types::HazardStacks::new(board, &rules),
+            denials: vec![],
+            profile,
+            rules,
+        };
+        ctx.shadowed = logic::shadowed_moves(&ctx);
+        ctx.lane = logic::sandwich_lane(&ctx);
+        return ctx;
+    }
+
+    /// # sample
+    /// a context with the default profile and rules
+    #[cfg(test)]
+    pub fn sample(
+        board: &'a types::Board,
+        game_board: &'a types::FastMap<Coord, types::Flags>,
+        you: &'a types::Battlesnake,
+    ) -> Self {
+        return TurnContext::new(
+            board,
+            game_board,
+            you,
+            StrategyProfile::default(),
+            Ruleset::default(),
+        );
+    }
+
+    /// # with_full_fills
+    /// the same context with every connectivity question left to the flood fill
+    #[cfg(test)]
+    pub fn with_full_fills(mut self) -> Self {
+        self.connectivity = Connectivity::full_fills(self.board, self.game_board, self.you);
+        return self;
+    }
+
+    /// # herded
+    /// whether a longer snake is herding us along with a move from our head, into a corner, down a lane or beside it
+    pub fn herded(&self, tile: &Coord) -> bool {
+        return self.shadowed.contains(tile)
+            || self.lane.as_ref().is_some_and(|lane| lane.contains(tile))
+            || self.alongside.as_ref().is_some_and(|alongside| alongside.contains(tile));
+    }
+
+    /// # weights
+    /// the weights the profile scores moves with in the phase we're in
+    pub fn weights(&self) -> Weights {
+        return self.profile.phase_weights.weights(self.phase);
+    }
+
+    /// # wants_food
+    /// whether A* should make for a food, food a snake at least our length is as close to is left to it unless we're
+    /// urgently hungry, and we only make for food when we're full if we're starving, which `hunger` already rules out.
+    /// food we're denying a starving opponent is always worth making for
+    /// ## Arguments:
+    /// * food - the food in question
+    pub fn wants_food(&self, food: &Coord) -> bool {
+        if self.denials.contains(food) {
+            return true;
+        }
+        return match self.hunger {
+            logic::Hunger::Urgent => true,
+            logic::Hunger::Normal => !self.board.snakes.iter().any(|snake| {
+                snake.id != self.you.id
+                    && snake.length >= self.you.length
+                    && snake.head.steps(food) <= self.you.head.steps(food)
+            }),
+            logic::Hunger::Full => false,
+        };
+    }
+
+    /// # can_move
+    /// `logic::can_move_board` for our snake, with the head to head threats looked up
+    /// ## Arguments:
+    /// * tile - the tile in question
+    /// * options - whether to avoid tiles adjacent to the heads of larger snakes
+    /// ## Returns:
+    /// true if we can safely move onto tile
+    pub fn can_move(&self, tile: &Coord, options: &logic::MoveOptions) -> bool {
+        return logic::tile_is_open(tile, self.board, self.game_board)
+            && !(options.avoids_snake_heads() && self.threats.contains(tile));
+    }
+}
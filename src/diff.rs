@@ -0,0 +1,421 @@
+//! the `diff-corpus` command: which turns of the replays and fixtures we have would be decided differently by two
+//! ways of deciding them, the current pipeline under two profiles or the current pipeline against the moves we logged
+
+use crate::config::StrategyConfig;
+use crate::logic::{self, StrategyProfile};
+use crate::replay::{self, ReplayEntry};
+use crate::types;
+use clap::Parser;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// one of the two ways of deciding a turn
+#[derive(Debug, Clone)]
+pub enum Side {
+    /// the moves and traces the replays logged, positions without a logged move are left out
+    Recorded,
+    /// the current pipeline with a profile, under the name it's reported by
+    Profile(String, Box<StrategyProfile>),
+}
+
+impl Side {
+    /// # parse
+    /// a side by name: `recorded`, `default` or a personality, or the path of a strategy config for the default
+    /// profile to play with
+    pub fn parse(spec: &str) -> Result<Side, String> {
+        if spec == "recorded" {
+            return Ok(Side::Recorded);
+        }
+        if let Some(profile) = logic::profile(spec) {
+            return Ok(Side::Profile(spec.to_string(), Box::new(profile)));
+        }
+        // a missing config would quietly play the defaults
+        if !Path::new(spec).is_file() {
+            return Err(format!("{} is neither recorded, a profile nor a config file", spec));
+        }
+        let config = StrategyConfig::load(Path::new(spec))?;
+        return Ok(Side::Profile(spec.to_string(), Box::new(config.profile("default").unwrap_or_default())));
+    }
+
+    pub fn name(&self) -> &str {
+        return match self {
+            Side::Recorded => "recorded",
+            Side::Profile(name, _) => name,
+        };
+    }
+
+    /// # decide
+    /// how this side answers a move request
+    /// ## Returns:
+    /// the answer, none when it's the recorded side and nothing was logged for the request
+    fn decide(&self, entry: &ReplayEntry) -> Option<Answer> {
+        let (chosen, trace) = match self {
+            Side::Recorded => {
+                let chosen = entry.response.as_ref()?["move"].as_str()?.to_string();
+                (chosen, entry.trace.clone())
+            }
+            Side::Profile(_, profile) => {
+                let state = &entry.state;
+                let deadline = Instant::now() + logic::move_budget(state.game.timeout, logic::latency_buffer());
+                let decision =
+                    logic::get_move_profiled(&state.game, &state.turn, &state.board, &state.you, profile, deadline);
+                let chosen = decision.response["move"].as_str().unwrap_or("none").to_string();
+                (chosen, serde_json::to_value(&decision.trace).ok())
+            }
+        };
+        let branch = trace
+            .as_ref()
+            .and_then(|trace| trace["branch"].as_str())
+            .map(str::to_string);
+        return Some(Answer { chosen, branch, trace });
+    }
+}
+
+/// a side's answer to a turn
+#[derive(Debug, Clone, PartialEq)]
+pub struct Answer {
+    pub chosen: String,
+    /// the part of the pipeline that settled on the move, none when the trace doesn't say
+    pub branch: Option<String>,
+    pub trace: Option<Value>,
+}
+
+/// a turn the two sides decided differently
+#[derive(Debug, Clone)]
+pub struct TurnDiff {
+    pub turn: u32,
+    pub board: String,
+    pub base: Answer,
+    pub against: Answer,
+}
+
+/// how a game of the corpus came out
+#[derive(Debug, Clone, Default)]
+pub struct GameDiff {
+    /// the file the game was read from
+    pub name: String,
+    /// the turns both sides answered
+    pub compared: u32,
+    pub changed: Vec<TurnDiff>,
+    /// turns logged after we were eliminated, there's nothing to decide
+    pub dead: Vec<u32>,
+    /// turns between the first and last logged that weren't logged at all
+    pub missing: Vec<u32>,
+    /// turns a side had no answer for, the recorded side on a turn without a logged move
+    pub unanswered: Vec<u32>,
+}
+
+/// how the whole corpus came out
+#[derive(Debug, Clone, Default)]
+pub struct CorpusDiff {
+    pub games: Vec<GameDiff>,
+    /// the changed turns by the branch each side decided them in, as `base -> against`
+    pub branches: BTreeMap<String, u32>,
+}
+
+impl CorpusDiff {
+    pub fn compared(&self) -> u32 {
+        return self.games.iter().map(|game| game.compared).sum();
+    }
+
+    pub fn changed(&self) -> usize {
+        return self.games.iter().map(|game| game.changed.len()).sum();
+    }
+}
+
+/// a game of the corpus, its move requests in the order they were logged
+#[derive(Debug, Clone)]
+pub struct CorpusGame {
+    pub name: String,
+    pub frames: Vec<ReplayEntry>,
+}
+
+/// # parse_frames
+/// the move requests of a log, either a replay of requests and our answers or the bare positions of a game
+fn parse_frames(contents: &str) -> serde_json::Result<Vec<ReplayEntry>> {
+    if let Ok(entries) = replay::load(contents) {
+        return Ok(entries.into_iter().filter(|entry| entry.request == "move").collect());
+    }
+    return contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            return serde_json::from_str(line).map(|state| ReplayEntry {
+                request: "move".to_string(),
+                state,
+                response: None,
+                trace: None,
+            });
+        })
+        .collect();
+}
+
+/// # load_corpus
+/// reads every game under the given paths: `.jsonl` logs are games, a `.json` position such as a fixture is a game of
+/// one turn, and directories are read through in name order
+/// ## Returns:
+/// the games, or the first file that couldn't be read
+pub fn load_corpus(paths: &[PathBuf]) -> Result<Vec<CorpusGame>, String> {
+    let mut games = vec![];
+    for path in paths {
+        if !path.exists() {
+            return Err(format!("{}: no such file or directory", path.display()));
+        }
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = fs::read_dir(path)
+                .map_err(|err| format!("{}: {}", path.display(), err))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect();
+            entries.sort();
+            games.extend(load_corpus(&entries)?);
+            continue;
+        }
+        let name = path.display().to_string();
+        let frames = if name.ends_with(".jsonl") {
+            let contents = fs::read_to_string(path).map_err(|err| format!("{}: {}", name, err))?;
+            parse_frames(&contents).map_err(|err| format!("{}: {}", name, err))?
+        } else if name.ends_with(".json") && !name.ends_with(".expect.json") {
+            let contents = fs::read_to_string(path).map_err(|err| format!("{}: {}", name, err))?;
+            let state: types::GameState = serde_json::from_str(&contents).map_err(|err| format!("{}: {}", name, err))?;
+            vec![ReplayEntry {
+                request: "move".to_string(),
+                state,
+                response: None,
+                trace: None,
+            }]
+        } else {
+            continue;
+        };
+        games.push(CorpusGame { name, frames });
+    }
+    return Ok(games);
+}
+
+/// # align
+/// lines a game's frames up by turn: a turn logged twice, as a retried request is, counts once, and turns we were no
+/// longer on the board for are set aside
+/// ## Returns:
+/// the frames to decide in turn order, the dead turns and the turns missing between the first and the last
+fn align(frames: &[ReplayEntry]) -> (Vec<&ReplayEntry>, Vec<u32>, Vec<u32>) {
+    let mut by_turn: BTreeMap<u32, &ReplayEntry> = BTreeMap::new();
+    for frame in frames {
+        by_turn.entry(frame.state.turn).or_insert(frame);
+    }
+    let missing = match (by_turn.keys().next(), by_turn.keys().next_back()) {
+        (Some(first), Some(last)) => (*first..*last).filter(|turn| !by_turn.contains_key(turn)).collect(),
+        _ => vec![],
+    };
+    let (alive, dead): (Vec<&ReplayEntry>, Vec<&ReplayEntry>) = by_turn.into_values().partition(|frame| {
+        let state = &frame.state;
+        return state.you.health > 0 && state.board.snakes.iter().any(|snake| snake.id == state.you.id);
+    });
+    return (alive, dead.iter().map(|frame| frame.state.turn).collect(), missing);
+}
+
+/// # diff_game
+/// decides every live turn of a game both ways
+fn diff_game(game: &CorpusGame, base: &Side, against: &Side) -> GameDiff {
+    let (frames, dead, missing) = align(&game.frames);
+    let mut diff = GameDiff {
+        name: game.name.clone(),
+        dead,
+        missing,
+        ..GameDiff::default()
+    };
+    for frame in frames {
+        let turn = frame.state.turn;
+        let (Some(base), Some(against)) = (base.decide(frame), against.decide(frame)) else {
+            diff.unanswered.push(turn);
+            continue;
+        };
+        diff.compared += 1;
+        if base.chosen != against.chosen {
+            diff.changed.push(TurnDiff {
+                turn,
+                board: frame.state.board.render(),
+                base,
+                against,
+            });
+        }
+    }
+    return diff;
+}
+
+/// # diff_corpus
+/// decides every turn of the corpus both ways and tallies the branches behind the turns that changed
+/// ## Arguments:
+/// * games - the corpus, see `load_corpus`
+/// * base - the side the changes are measured from
+/// * against - the side they're measured to
+pub fn diff_corpus(games: &[CorpusGame], base: &Side, against: &Side) -> CorpusDiff {
+    let mut diff = CorpusDiff {
+        games: games.iter().map(|game| diff_game(game, base, against)).collect(),
+        ..CorpusDiff::default()
+    };
+    for turn in diff.games.iter().flat_map(|game| &game.changed) {
+        let branch = |answer: &Answer| answer.branch.clone().unwrap_or_else(|| "unknown".to_string());
+        let transition = format!("{} -> {}", branch(&turn.base), branch(&turn.against));
+        *diff.branches.entry(transition).or_default() += 1;
+    }
+    return diff;
+}
+
+/// the `diff-corpus` command's arguments
+#[derive(Parser, Debug)]
+#[command(name = "diff-corpus")]
+pub struct DiffArgs {
+    /// replays, game logs, fixtures and directories of them
+    #[arg(default_values = ["testdata/replays", "testdata/fixtures"])]
+    corpus: Vec<PathBuf>,
+    /// the side the changes are measured from: `recorded`, a profile or a strategy config
+    #[arg(long, default_value = "recorded", value_parser = Side::parse)]
+    base: Side,
+    /// the side they're measured to
+    #[arg(long, default_value = "default", value_parser = Side::parse)]
+    against: Side,
+    /// exit with 1 when any turn changed, for CI
+    #[arg(long)]
+    fail_on_diff: bool,
+}
+
+/// # run
+/// the `diff-corpus` command: decides every turn of the corpus both ways and prints a line per game, every turn that
+/// changed with its board and both traces, and the branches the changed turns were decided in
+/// ## Arguments:
+/// * args - the parsed arguments
+/// * out - where the report is written
+/// ## Returns:
+/// the exit code, 1 when a turn changed under `--fail-on-diff` and 2 when the corpus can't be read
+pub fn run(args: &DiffArgs, out: &mut impl Write) -> i32 {
+    let games = match load_corpus(&args.corpus) {
+        Ok(games) => games,
+        Err(err) => {
+            let _ = writeln!(out, "couldn't read the corpus: {}", err);
+            return 2;
+        }
+    };
+    let diff = diff_corpus(&games, &args.base, &args.against);
+    return match report(&diff, &args.base, &args.against, out) {
+        Ok(_) if args.fail_on_diff && diff.changed() > 0 => 1,
+        Ok(_) => 0,
+        Err(_) => 2,
+    };
+}
+
+/// # turns
+/// a list of turns for the report, `none` when it's empty
+fn turns(turns: &[u32]) -> String {
+    if turns.is_empty() {
+        return "none".to_string();
+    }
+    return turns.iter().map(u32::to_string).collect::<Vec<String>>().join(", ");
+}
+
+/// # report
+/// prints the corpus diff game by game, then the totals
+fn report(diff: &CorpusDiff, base: &Side, against: &Side, out: &mut impl Write) -> io::Result<()> {
+    let answer = |answer: &Answer| {
+        return format!("{} ({})", answer.chosen, answer.branch.as_deref().unwrap_or("unknown"));
+    };
+    for game in &diff.games {
+        writeln!(
+            out,
+            "{}: {} of {} turns changed, dead: {}, missing: {}, unanswered: {}",
+            game.name,
+            game.changed.len(),
+            game.compared,
+            turns(&game.dead),
+            turns(&game.missing),
+            turns(&game.unanswered)
+        )?;
+        for turn in &game.changed {
+            writeln!(
+                out,
+                "turn {}: {} {}, {} {}",
+                turn.turn,
+                base.name(),
+                answer(&turn.base),
+                against.name(),
+                answer(&turn.against)
+            )?;
+            writeln!(out, "{}", turn.board)?;
+            for (side, answer) in [(base, &turn.base), (against, &turn.against)] {
+                let trace = answer.trace.as_ref().map_or("none".to_string(), Value::to_string);
+                writeln!(out, "{} trace: {}", side.name(), trace)?;
+            }
+        }
+    }
+    let changed_games = diff.games.iter().filter(|game| !game.changed.is_empty()).count();
+    writeln!(
+        out,
+        "{} of {} turns changed in {} of {} games",
+        diff.changed(),
+        diff.compared(),
+        changed_games,
+        diff.games.len()
+    )?;
+    let branches: Vec<String> = diff
+        .branches
+        .iter()
+        .map(|(transition, count)| format!("{} {}", transition, count))
+        .collect();
+    writeln!(out, "branches: {}", if branches.is_empty() { "none".to_string() } else { branches.join(", ") })?;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    fn diff(args: &[&str]) -> (i32, String) {
+        let args = DiffArgs::try_parse_from(["diff-corpus"].iter().chain(args)).unwrap();
+        let mut out: Vec<u8> = vec![];
+        let code = run(&args, &mut out);
+        return (code, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn flipped_weight_changes_one_turn() {
+        let dir = env::temp_dir().join(format!("battlesnake-diff-corpus-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // the sample replay with turn 2 lost, turn 3 logged twice and a turn after we'd been eliminated
+        let mut entries = replay::load(&fs::read_to_string("testdata/replays/standard.jsonl").unwrap()).unwrap();
+        entries.retain(|entry| entry.request == "move" && entry.state.turn != 2);
+        entries.insert(3, entries[2].clone());
+        let mut dead = entries.last().unwrap().clone();
+        dead.state.turn += 1;
+        dead.state.board.snakes.retain(|snake| snake.id != dead.state.you.id);
+        entries.push(dead);
+        let lines: Vec<String> = entries.iter().map(|entry| serde_json::to_string(entry).unwrap()).collect();
+        fs::write(dir.join("game.jsonl"), lines.join("\n")).unwrap();
+        fs::copy("testdata/fixtures/ahead_by_three.json", dir.join("ahead_by_three.json")).unwrap();
+        // the midgame without any weight on the room next to a move
+        let flipped = dir.join("flipped.toml");
+        let mid = "[phase_weights.mid]\nspace = 10\nlength = 30\nhealth = 1\ndegree = 0\nfood = 3\ncenter = 1\n";
+        fs::write(&flipped, mid).unwrap();
+        let (corpus, flipped) = (dir.to_str().unwrap(), flipped.to_str().unwrap());
+
+        let (code, out) = diff(&[corpus, "--base", "default", "--against", flipped, "--fail-on-diff"]);
+        assert_eq!(code, 1, "{}", out);
+        assert!(out.contains("ahead_by_three.json: 1 of 1 turns changed"), "{}", out);
+        assert!(out.contains(&format!("turn 30: default up (survival), {} down (survival)\n", flipped)), "{}", out);
+        assert!(out.contains("game.jsonl: 0 of 5 turns changed, dead: 6, missing: 2, unanswered: none\n"), "{}", out);
+        assert!(out.contains("default trace: {"), "{}", out);
+        assert!(out.ends_with("1 of 6 turns changed in 1 of 2 games\nbranches: survival -> survival 1\n"), "{}", out);
+
+        // the logged moves still hold, and the fixture has none to compare with
+        let (code, out) = diff(&[corpus, "--fail-on-diff"]);
+        assert_eq!(code, 0, "{}", out);
+        assert!(out.contains("ahead_by_three.json: 0 of 0 turns changed, dead: none, missing: none, unanswered: 30\n"));
+        assert!(out.ends_with("0 of 5 turns changed in 0 of 2 games\nbranches: none\n"), "{}", out);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(diff(&["testdata/missing"]).0, 2);
+        assert!(DiffArgs::try_parse_from(["diff-corpus", "--against", "reckless"]).is_err());
+    }
+}
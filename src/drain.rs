@@ -0,0 +1,111 @@
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use log::info;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Request, Shutdown};
+use subtle::ConstantTimeEq;
+
+use crate::store::GameStore;
+
+/// how long, in seconds, a drain waits for the games in flight to end before shutting down anyway
+pub const DRAIN_SECS_ENV: &str = "BATTLESNAKE_DRAIN_SECS";
+pub const DEFAULT_DRAIN_PERIOD: Duration = Duration::from_secs(120);
+
+/// the token `POST /admin/drain` wants as `Authorization: Bearer <token>`, the route is off when it isn't set
+pub const ADMIN_TOKEN_ENV: &str = "BATTLESNAKE_ADMIN_TOKEN";
+
+/// how often a drain checks whether the games in flight have ended
+const DRAIN_POLL: Duration = Duration::from_millis(250);
+
+/// whether we're draining before a restart. while draining new games are refused and the games in flight are played
+/// out, then the server shuts down
+pub struct Drain {
+    draining: AtomicBool,
+    period: Duration,
+    token: Option<String>,
+}
+
+impl Drain {
+    pub fn new(period: Duration, token: Option<String>) -> Self {
+        return Drain {
+            draining: AtomicBool::new(false),
+            period,
+            token: token.filter(|token| !token.is_empty()),
+        };
+    }
+
+    /// # from_env
+    /// a drain with the period from `DRAIN_SECS_ENV` and the token from `ADMIN_TOKEN_ENV`
+    pub fn from_env() -> Self {
+        let period = env::var(DRAIN_SECS_ENV)
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .map_or(DEFAULT_DRAIN_PERIOD, Duration::from_secs);
+        return Drain::new(period, env::var(ADMIN_TOKEN_ENV).ok());
+    }
+
+    pub fn is_draining(&self) -> bool {
+        return self.draining.load(Ordering::Relaxed);
+    }
+
+    /// # begin
+    /// starts draining, the server shuts down once every game in the store has ended or the drain period is up
+    /// ## Returns:
+    /// false if we were already draining
+    pub fn begin(&self, store: Arc<GameStore>, shutdown: Shutdown) -> bool {
+        if self.draining.swap(true, Ordering::Relaxed) {
+            return false;
+        }
+        let period = self.period;
+        info!(
+            "draining {} games for up to {:?} before shutting down",
+            store.in_flight(),
+            period
+        );
+        rocket::tokio::spawn(async move {
+            let started = Instant::now();
+            while store.in_flight() > 0 && started.elapsed() < period {
+                rocket::tokio::time::sleep(DRAIN_POLL).await;
+            }
+            info!(
+                "drained with {} games still in flight, shutting down",
+                store.in_flight()
+            );
+            shutdown.notify();
+        });
+        return true;
+    }
+}
+
+/// proof a request carries the admin token
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = req
+            .rocket()
+            .state::<Drain>()
+            .and_then(|drain| drain.token.as_deref());
+        let offered = req
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "));
+        // async_trait rewrites the body, an explicit return here trips clippy
+        match (token, offered) {
+            // without a token there's no admin, the route might as well not exist
+            (None, _) => Outcome::Failure((Status::NotFound, ())),
+            // compared in constant time, how long the check takes says nothing about how much of the token matched
+            (Some(token), Some(offered)) if bool::from(token.as_bytes().ct_eq(offered.as_bytes())) => {
+                Outcome::Success(Admin)
+            }
+            _ => Outcome::Failure((Status::Unauthorized, ())),
+        }
+    }
+}
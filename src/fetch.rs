@@ -0,0 +1,457 @@
+//! pulls a finished game out of the engine's archive and writes it in the replay format, so the ranked games we didn't
+//! log ourselves can still go through `replay` and the simulator checks
+
+use crate::replay::{ReplayEntry, REPLAY_DIR_ENV};
+use crate::types;
+use clap::Parser;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+pub const ENGINE_URL: &str = "https://engine.battlesnake.com";
+
+/// the frames asked for at a time, the engine pages them
+const PAGE: usize = 100;
+
+/// the `fetch-game` command's arguments
+#[derive(Parser, Debug)]
+#[command(name = "fetch-game")]
+pub struct FetchArgs {
+    /// the game to download
+    game_id: String,
+    /// our snake in the game, by name or id
+    #[arg(long)]
+    snake: String,
+    /// where the replay goes, as `<dir>/<game_id>.jsonl`
+    #[arg(long, env = REPLAY_DIR_ENV, default_value = "replays")]
+    out: PathBuf,
+    /// the engine the game was played on
+    #[arg(long, default_value = ENGINE_URL)]
+    engine: String,
+}
+
+/// the engine's names for things are capitalised, and its ids shouted
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct GameResponse {
+    game: EngineGame,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct EngineGame {
+    #[serde(rename = "ID")]
+    id: String,
+    width: u8,
+    height: u8,
+    /// every setting as a string, `"foodSpawnChance": "15"`
+    #[serde(default)]
+    ruleset: HashMap<String, Value>,
+    #[serde(alias = "Timeout", default = "default_timeout")]
+    snake_timeout: u32,
+    #[serde(default)]
+    map: Option<String>,
+}
+
+fn default_timeout() -> u32 {
+    return 500;
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct FramesPage {
+    #[serde(default)]
+    frames: Vec<Frame>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+struct Frame {
+    turn: u32,
+    #[serde(default)]
+    snakes: Vec<EngineSnake>,
+    #[serde(default)]
+    food: Vec<Point>,
+    #[serde(default)]
+    hazards: Vec<Point>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+struct EngineSnake {
+    #[serde(rename = "ID")]
+    id: String,
+    name: String,
+    body: Vec<Point>,
+    health: u8,
+    /// set from the turn the snake died on, the dead stay in the frames that follow
+    death: Option<Value>,
+    #[serde(default)]
+    shout: String,
+    #[serde(default)]
+    squad: String,
+    #[serde(default, deserialize_with = "types::reported_latency")]
+    latency: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "PascalCase")]
+struct Point {
+    x: i16,
+    y: i16,
+}
+
+impl From<Point> for types::Coord {
+    fn from(point: Point) -> Self {
+        return types::Coord {
+            x: point.x,
+            y: point.y,
+        };
+    }
+}
+
+/// # get
+/// downloads and parses a JSON document
+fn get<T: DeserializeOwned>(url: &str) -> Result<T, String> {
+    let body = reqwest::blocking::get(url)
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.text())
+        .map_err(|err| format!("{}: {}", url, err))?;
+    return serde_json::from_str(&body).map_err(|err| format!("{}: {}", url, err));
+}
+
+/// # paged
+/// gathers every frame a page at a time, until a page comes back short
+/// ## Arguments:
+/// * limit - the frames asked for at a time
+/// * page - fetches the page of frames starting at an offset
+fn paged(
+    limit: usize,
+    mut page: impl FnMut(usize, usize) -> Result<Vec<Frame>, String>,
+) -> Result<Vec<Frame>, String> {
+    let mut frames = vec![];
+    loop {
+        let next = page(frames.len(), limit)?;
+        let last = next.len() < limit;
+        frames.extend(next);
+        if last {
+            return Ok(frames);
+        }
+    }
+}
+
+/// # download
+/// the game's settings and every frame of it, in turn order
+fn download(engine: &str, game_id: &str) -> Result<(EngineGame, Vec<Frame>), String> {
+    let engine = engine.trim_end_matches('/');
+    let game: GameResponse = get(&format!("{}/games/{}", engine, game_id))?;
+    let mut frames = paged(PAGE, |offset, limit| {
+        let url = format!(
+            "{}/games/{}/frames?offset={}&limit={}",
+            engine, game_id, offset, limit
+        );
+        return get::<FramesPage>(&url).map(|page| page.frames);
+    })?;
+    frames.sort_by_key(|frame| frame.turn);
+    frames.dedup_by_key(|frame| frame.turn);
+    return Ok((game.game, frames));
+}
+
+/// the engine's squad switches, flat in its ruleset and under `settings.squad` in a move request
+const SQUAD_SETTINGS: [&str; 4] = ["allowBodyCollisions", "sharedElimination", "sharedHealth", "sharedLength"];
+
+/// # ruleset
+/// the engine's ruleset in the shape the move requests carry it: the name at the top, the numbers and switches under
+/// `settings`, the royale shrink under `settings.royale` and the squad switches under `settings.squad`
+fn ruleset(engine: &HashMap<String, Value>) -> HashMap<String, Value> {
+    let mut settings = Map::new();
+    let mut royale = Map::new();
+    let mut squad = Map::new();
+    for (key, value) in engine {
+        if key == "name" {
+            continue;
+        }
+        let value = match value {
+            Value::String(text) => text
+                .parse::<i64>()
+                .map(Value::from)
+                .or_else(|_| text.parse::<bool>().map(Value::from))
+                .unwrap_or_else(|_| value.clone()),
+            _ => value.clone(),
+        };
+        if key == "shrinkEveryNTurns" {
+            royale.insert(key.clone(), value);
+        } else if SQUAD_SETTINGS.contains(&key.as_str()) {
+            squad.insert(key.clone(), value);
+        } else {
+            settings.insert(key.clone(), value);
+        }
+    }
+    settings.insert("royale".to_string(), Value::Object(royale));
+    settings.insert("squad".to_string(), Value::Object(squad));
+    let name = engine.get("name").cloned().unwrap_or(json!("standard"));
+    return HashMap::from([
+        ("name".to_string(), name),
+        ("settings".to_string(), Value::Object(settings)),
+    ]);
+}
+
+fn snake(snake: &EngineSnake) -> types::Battlesnake {
+    let body: Vec<types::Coord> = snake.body.iter().copied().map(types::Coord::from).collect();
+    return types::Battlesnake {
+        id: snake.id.clone(),
+        name: snake.name.clone(),
+        health: snake.health,
+        head: body.first().copied().unwrap_or(types::Coord { x: 0, y: 0 }),
+        length: body.len() as u32,
+        body,
+        shout: Some(snake.shout.clone()),
+        squad: snake.squad.clone(),
+        latency: snake.latency,
+    };
+}
+
+/// # convert
+/// turns a game's frames into the requests the engine sent our snake: a `start` on the first frame, a `move` on every
+/// frame we were alive in before the last, answered with the way our head went next, and an `end` on the frame we died
+/// in or the last one. the board only holds the snakes still alive on a frame
+/// ## Arguments:
+/// * game - the game's settings
+/// * frames - every frame, in turn order
+/// * ours - our snake's name or id
+/// ## Returns:
+/// the replay entries, or why there aren't any
+fn convert(game: &EngineGame, frames: &[Frame], ours: &str) -> Result<Vec<ReplayEntry>, String> {
+    let is_ours = |snake: &&EngineSnake| snake.id == ours || snake.name == ours;
+    let first = frames
+        .first()
+        .ok_or_else(|| format!("game {} has no frames", game.id))?;
+    let mut you = first
+        .snakes
+        .iter()
+        .find(is_ours)
+        .map(snake)
+        .ok_or_else(|| format!("{} isn't in game {}", ours, game.id))?;
+    let game_info = types::Game {
+        id: game.id.clone(),
+        ruleset: ruleset(&game.ruleset),
+        timeout: game.snake_timeout,
+        map: game.map.clone(),
+    };
+    let state = |frame: &Frame, you: &types::Battlesnake| types::GameState {
+        game: game_info.clone(),
+        turn: frame.turn,
+        board: types::Board {
+            height: game.height,
+            width: game.width,
+            food: frame.food.iter().copied().map(types::Coord::from).collect(),
+            hazards: frame
+                .hazards
+                .iter()
+                .copied()
+                .map(types::Coord::from)
+                .collect(),
+            snakes: frame
+                .snakes
+                .iter()
+                .filter(|snake| snake.death.is_none())
+                .map(snake)
+                .collect(),
+            hazard_odds: vec![],
+            hazard_walls: false,
+        },
+        you: you.clone(),
+    };
+    let entry = |request: &str, state: types::GameState, response: Option<Value>| ReplayEntry {
+        request: request.to_string(),
+        state,
+        response,
+        trace: None,
+    };
+
+    let mut entries = vec![entry("start", state(first, &you), None)];
+    for (i, frame) in frames.iter().enumerate() {
+        // a snake that's gone from a late frame died before it, the engine doesn't always keep the dead around
+        let seen = frame.snakes.iter().find(is_ours);
+        if let Some(seen) = seen {
+            you = snake(seen);
+        }
+        let alive = seen.is_some_and(|snake| snake.death.is_none());
+        let next = frames.get(i + 1);
+        if !alive || next.is_none() {
+            entries.push(entry("end", state(frame, &you), None));
+            break;
+        }
+        let response = next
+            .and_then(|next| next.snakes.iter().find(is_ours))
+            .and_then(|next| next.body.first())
+            .map(|head| types::Coord::from(*head) - you.head)
+            .and_then(|dir| types::DIRECTIONS.entries().find(|(_, unit)| **unit == dir))
+            .map(|(mv, _)| json!({ "move": mv }));
+        entries.push(entry("move", state(frame, &you), response));
+    }
+    return Ok(entries);
+}
+
+/// # run
+/// the `fetch-game` command: downloads a finished game from the engine and writes it where the replay logger would
+/// have, from our snake's side of the board
+/// ## Arguments:
+/// * args - the parsed arguments
+/// * out - where the progress is written
+/// ## Returns:
+/// the exit code, 2 when the game can't be downloaded, converted or written
+pub fn run(args: &FetchArgs, out: &mut impl Write) -> i32 {
+    let entries = download(&args.engine, &args.game_id)
+        .and_then(|(game, frames)| convert(&game, &frames, &args.snake));
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(err) => {
+            let _ = writeln!(out, "couldn't fetch game {}: {}", args.game_id, err);
+            return 2;
+        }
+    };
+    let path = args.out.join(format!("{}.jsonl", args.game_id));
+    let mut lines = String::new();
+    for entry in &entries {
+        lines.push_str(&serde_json::to_string(entry).unwrap_or_default());
+        lines.push('\n');
+    }
+    let written = fs::create_dir_all(&args.out).and_then(|_| fs::write(&path, lines));
+    if let Err(err) = written {
+        let _ = writeln!(out, "couldn't write {}: {}", path.display(), err);
+        return 2;
+    }
+    let moves = entries
+        .iter()
+        .filter(|entry| entry.request == "move")
+        .count();
+    let _ = writeln!(out, "wrote {} moves to {}", moves, path.display());
+    return 0;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{payload, replay};
+
+    fn sample() -> (EngineGame, Vec<Frame>) {
+        let game: GameResponse =
+            serde_json::from_str(&fs::read_to_string("testdata/engine/game.json").unwrap())
+                .unwrap();
+        let page: FramesPage =
+            serde_json::from_str(&fs::read_to_string("testdata/engine/frames.json").unwrap())
+                .unwrap();
+        return (game.game, page.frames);
+    }
+
+    #[test]
+    fn frames_become_our_requests() {
+        let (game, frames) = sample();
+        let entries = convert(&game, &frames, "our snake").unwrap();
+        let requests: Vec<(&str, u32)> = entries
+            .iter()
+            .map(|entry| (entry.request.as_str(), entry.state.turn))
+            .collect();
+        assert_eq!(
+            requests,
+            [
+                ("start", 0),
+                ("move", 0),
+                ("move", 1),
+                ("move", 2),
+                ("move", 3),
+                ("end", 4)
+            ]
+        );
+        let moves: Vec<&Value> = entries
+            .iter()
+            .filter_map(|entry| entry.response.as_ref())
+            .map(|response| &response["move"])
+            .collect();
+        assert_eq!(moves, ["up", "right", "right", "up"]);
+
+        for entry in &entries {
+            let problems = payload::validate(&entry.state);
+            assert!(
+                problems.is_empty(),
+                "turn {}: {:?}",
+                entry.state.turn,
+                problems
+            );
+            assert_eq!(entry.state.you.id, "gs_me");
+        }
+        // the other snake hit the wall on turn 3, it's dead there and gone after
+        let snakes = |turn: usize| entries[turn + 1].state.board.snakes.len();
+        assert_eq!([snakes(0), snakes(2), snakes(3), snakes(4)], [2, 2, 1, 1]);
+        assert_eq!(
+            entries[2].state.board.snakes[1].shout.as_deref(),
+            Some("up")
+        );
+        let you = &entries[5].state.you;
+        assert_eq!((you.length, you.health), (4, 100));
+
+        let rules = types::Ruleset::from(&entries[0].state.game);
+        assert_eq!(rules.hazard_damage, 14);
+        assert_eq!(rules.food_spawn_chance, 15);
+        assert!(rules.royale.is_none());
+        assert_eq!(entries[0].state.game.map.as_deref(), Some("standard"));
+        assert!(!rules.hazard_walls);
+        assert!(rules.squad.is_none());
+        // a squad game's switches come through under `settings.squad`
+        let engine = HashMap::from([
+            ("name".to_string(), json!("squad")),
+            ("sharedHealth".to_string(), json!("true")),
+        ]);
+        let squad = types::Game {
+            id: "squad".to_string(),
+            ruleset: ruleset(&engine),
+            timeout: 500,
+            map: None,
+        };
+        let squad = types::Ruleset::from(&squad).squad;
+        assert!(squad.is_some_and(|squad| squad.shared_health && !squad.shared_elimination));
+
+        // the file reads back as a replay, and our snake can be named by its id
+        let lines: String = entries
+            .iter()
+            .map(|entry| serde_json::to_string(entry).unwrap() + "\n")
+            .collect();
+        assert_eq!(replay::load_states(&lines).unwrap().len(), 4);
+        assert_eq!(
+            convert(&game, &frames, "gs_me").unwrap().len(),
+            entries.len()
+        );
+        assert!(convert(&game, &frames, "someone else").is_err());
+    }
+
+    #[test]
+    fn our_death_ends_the_replay() {
+        let (game, mut frames) = sample();
+        frames[2].snakes[0].death = Some(json!({ "Cause": "head-collision", "Turn": 2 }));
+        frames[3].snakes.remove(0);
+        let requests: Vec<String> = convert(&game, &frames, "our snake")
+            .unwrap()
+            .into_iter()
+            .map(|entry| format!("{} {}", entry.request, entry.state.turn))
+            .collect();
+        assert_eq!(requests, ["start 0", "move 0", "move 1", "end 2"]);
+    }
+
+    #[test]
+    fn frames_are_paged() {
+        let (_, frames) = sample();
+        let mut asked = vec![];
+        let paged = paged(2, |offset, limit| {
+            asked.push(offset);
+            return Ok(frames.iter().skip(offset).take(limit).cloned().collect());
+        })
+        .unwrap();
+        assert_eq!(paged.len(), frames.len());
+        assert_eq!(asked, [0, 2, 4]);
+    }
+}
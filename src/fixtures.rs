@@ -0,0 +1,318 @@
+use crate::config::StrategyConfig;
+use crate::logic::{self, Branch};
+use crate::trace::DecisionTrace;
+use crate::types;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::Instant;
+
+/// the positions we've been caught out by before, each `<name>.json` is a move request and `<name>.expect.json` says
+/// what we may answer it with
+pub const FIXTURE_DIR: &str = "testdata/fixtures";
+
+/// the move and branch `decide` answers every fixture with, any change to them has to be written in deliberately
+pub const GOLDEN_MOVES: &str = "testdata/golden_moves.toml";
+
+/// set to 1 to rewrite `GOLDEN_MOVES` from the moves the fixtures are answered with now
+pub const UPDATE_GOLDEN_ENV: &str = "UPDATE_GOLDEN";
+
+/// what a fixture expects of our move, the moves in `allowed` are the only right answers and the moves in `forbidden`
+/// are wrong ones
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Expectation {
+    pub allowed: Vec<String>,
+    pub forbidden: Vec<String>,
+    /// how hungry the trace has to say we were, any hunger will do when it's left out
+    pub hunger: Option<logic::Hunger>,
+    /// what went wrong here before
+    pub why: String,
+}
+
+impl Expectation {
+    /// # accepts
+    /// whether a move meets the expectation
+    pub fn accepts(&self, chosen: &str) -> bool {
+        return (self.allowed.is_empty() || self.allowed.iter().any(|mv| mv == chosen))
+            && !self.forbidden.iter().any(|mv| mv == chosen);
+    }
+}
+
+/// a position and what we should answer it with
+#[derive(Debug)]
+pub struct Fixture {
+    pub name: String,
+    pub state: types::GameState,
+    pub expect: Expectation,
+}
+
+/// how a fixture was answered
+pub struct Outcome {
+    pub chosen: String,
+    pub passed: bool,
+    pub trace: DecisionTrace,
+}
+
+impl Fixture {
+    /// # check
+    /// runs the move pipeline on the position with the game's own time budget
+    pub fn check(&self) -> Outcome {
+        let state = &self.state;
+        let deadline =
+            Instant::now() + logic::move_budget(state.game.timeout, logic::latency_buffer());
+        let decision =
+            logic::get_move_until(&state.game, &state.turn, &state.board, &state.you, deadline);
+        let chosen = decision.response["move"]
+            .as_str()
+            .unwrap_or("none")
+            .to_string();
+        return Outcome {
+            passed: self.expect.accepts(&chosen)
+                && self.expect.hunger.is_none_or(|hunger| hunger == decision.trace.hunger),
+            chosen,
+            trace: decision.trace,
+        };
+    }
+
+    /// # report
+    /// what a failed fixture wanted, the board and how we came to the wrong move
+    pub fn report(&self, outcome: &Outcome) -> String {
+        return format!(
+            "{}: answered {} {:?}, allowed {:?}, forbidden {:?}, hunger {:?}\n{}\n{}\ntrace: {}",
+            self.name,
+            outcome.chosen,
+            outcome.trace.hunger,
+            self.expect.allowed,
+            self.expect.forbidden,
+            self.expect.hunger,
+            self.expect.why,
+            self.state.board.render(),
+            serde_json::to_string(&outcome.trace).unwrap_or_default()
+        );
+    }
+}
+
+/// the move a fixture was answered with when the golden moves were last written
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct GoldenMove {
+    /// the moves that match, more than one where the fixture is settled by a tie broken at random
+    pub moves: Vec<String>,
+    pub branch: Branch,
+    /// the gist of the trace the move was written with, see `gist`, only there to show next to the new one
+    pub trace: String,
+}
+
+/// the golden move of each fixture, by name
+pub type GoldenMoves = BTreeMap<String, GoldenMove>;
+
+impl GoldenMove {
+    /// # record
+    /// the golden move for a move and the trace of how the pipeline came to it
+    pub fn record(trace: &DecisionTrace) -> Self {
+        return GoldenMove {
+            moves: vec![trace.chosen.clone()],
+            branch: trace.branch,
+            trace: gist(trace),
+        };
+    }
+
+    /// # matches
+    /// whether a move and the branch that settled on it are the golden ones
+    pub fn matches(&self, trace: &DecisionTrace) -> bool {
+        return self.moves.contains(&trace.chosen) && self.branch == trace.branch;
+    }
+}
+
+/// # gist
+/// a trace in a line: how hungry we were, the phase of the game, where A* was heading and whether the validator
+/// stepped in
+pub fn gist(trace: &DecisionTrace) -> String {
+    let name = |value: serde_json::Value| value.as_str().unwrap_or_default().to_string();
+    let heading = match &trace.a_star {
+        Some(path) => format!("A* to ({}, {}) in {}", path.goal.x, path.goal.y, path.length),
+        None => "no A* path".to_string(),
+    };
+    let mut gist = format!(
+        "{} hunger, {} phase, {}",
+        name(serde_json::json!(trace.hunger)),
+        name(serde_json::json!(trace.game_phase)),
+        heading
+    );
+    if let Some(replaced) = &trace.safety_override {
+        gist += &format!(", the validator replaced {}", replaced);
+    }
+    return gist;
+}
+
+/// # load_golden
+/// reads the golden moves, none when the file isn't there yet
+pub fn load_golden(path: &Path) -> Result<GoldenMoves, String> {
+    return match fs::read_to_string(path) {
+        Ok(contents) => toml::from_str(&contents).map_err(|err| format!("{}: {}", path.display(), err)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(GoldenMoves::new()),
+        Err(err) => Err(format!("{}: {}", path.display(), err)),
+    };
+}
+
+/// # write_golden
+/// writes the golden moves, fixture by fixture in name order
+pub fn write_golden(path: &Path, golden: &GoldenMoves) -> Result<(), String> {
+    let tables = toml::to_string(golden).map_err(|err| err.to_string())?;
+    let header = format!(
+        "# the move and branch `decide` answers each fixture with, rewritten with {}=1 cargo test --test fixtures.\n\
+         # list more than one move where a fixture is settled by a tie broken at random, a rewrite keeps the list as\n\
+         # long as the move is still in it\n\n",
+        UPDATE_GOLDEN_ENV
+    );
+    return fs::write(path, header + &tables).map_err(|err| format!("couldn't write {}: {}", path.display(), err));
+}
+
+/// # check_golden
+/// answers every fixture with `decide` under the default config and holds it to its golden move. when updating, a
+/// fixture that no longer matches gets its new move written in and the fixtures that are gone are dropped
+/// ## Arguments:
+/// * fixtures - the fixtures, see `load`
+/// * golden - the golden moves, see `load_golden`
+/// * update - whether to rewrite the golden moves instead of failing on them
+/// ## Returns:
+/// a report for every fixture that doesn't match, in name order
+pub fn check_golden(fixtures: &[Fixture], golden: &mut GoldenMoves, update: bool) -> Vec<String> {
+    let config = StrategyConfig::default();
+    let mut failures = vec![];
+    for fixture in fixtures {
+        let state = &fixture.state;
+        let deadline = Instant::now() + logic::move_budget(state.game.timeout, logic::latency_buffer());
+        let trace = crate::decide(state, &config, deadline).trace;
+        let recorded = golden.get(&fixture.name);
+        if recorded.is_some_and(|recorded| recorded.matches(&trace)) {
+            continue;
+        }
+        if update {
+            golden.insert(fixture.name.clone(), GoldenMove::record(&trace));
+            continue;
+        }
+        let was = match recorded {
+            Some(recorded) => {
+                format!("golden: {} by {}, {}", recorded.moves.join(" or "), recorded.branch.name(), recorded.trace)
+            }
+            None => format!("golden: none yet, run with {}=1 to write one", UPDATE_GOLDEN_ENV),
+        };
+        failures.push(format!(
+            "{}: answered {} by {}\n{}\n{}\nnow: {} by {}, {}\ntrace: {}",
+            fixture.name,
+            trace.chosen,
+            trace.branch.name(),
+            state.board.render(),
+            was,
+            trace.chosen,
+            trace.branch.name(),
+            gist(&trace),
+            serde_json::to_string(&trace).unwrap_or_default()
+        ));
+    }
+    if update {
+        golden.retain(|name, _| fixtures.iter().any(|fixture| fixture.name == *name));
+    }
+    return failures;
+}
+
+/// # load
+/// reads every fixture in a directory
+/// ## Arguments:
+/// * dir - the fixture directory
+/// ## Returns:
+/// the fixtures by name, or what couldn't be read. a position without an expectation is an error
+pub fn load(dir: &Path) -> Result<Vec<Fixture>, String> {
+    let read = |path: &Path| {
+        return fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err));
+    };
+    let entries = fs::read_dir(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    let mut fixtures = vec![];
+    for entry in entries {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if !name.ends_with(".expect.json") => name.strip_suffix(".json"),
+            _ => None,
+        };
+        let Some(name) = name else {
+            continue;
+        };
+        let state = serde_json::from_str(&read(&path)?)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        let sidecar = dir.join(format!("{}.expect.json", name));
+        let expect = serde_json::from_str(&read(&sidecar)?)
+            .map_err(|err| format!("{}: {}", sidecar.display(), err))?;
+        fixtures.push(Fixture {
+            name: name.to_string(),
+            state,
+            expect,
+        });
+    }
+    fixtures.sort_by(|a, b| a.name.cmp(&b.name));
+    return Ok(fixtures);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expectations() {
+        let allowed = Expectation {
+            allowed: vec!["up".to_string(), "left".to_string()],
+            ..Default::default()
+        };
+        assert!(allowed.accepts("left"));
+        assert!(!allowed.accepts("down"));
+        let forbidden = Expectation {
+            forbidden: vec!["up".to_string()],
+            ..Default::default()
+        };
+        assert!(forbidden.accepts("down"));
+        assert!(!forbidden.accepts("up"));
+    }
+
+    #[test]
+    fn golden_moves_match_any_of_their_moves() {
+        let golden: GoldenMoves = toml::from_str(
+            "[tie]\nmoves = [\"up\", \"left\"]\nbranch = \"survival\"\ntrace = \"\"\n",
+        )
+        .unwrap();
+        let mut trace = DecisionTrace::new("tie", 0);
+        trace.chosen = "left".to_string();
+        trace.branch = Branch::Survival;
+        assert!(golden["tie"].matches(&trace));
+        trace.branch = Branch::AStar;
+        assert!(!golden["tie"].matches(&trace));
+        trace.chosen = "down".to_string();
+        trace.branch = Branch::Survival;
+        assert!(!golden["tie"].matches(&trace));
+    }
+
+    #[test]
+    fn updating_keeps_the_golden_moves_that_still_match() {
+        let fixtures = load(Path::new(FIXTURE_DIR)).unwrap();
+        let fixtures = &fixtures[..1];
+        let name = &fixtures[0].name;
+        let mut golden = GoldenMoves::from([("gone".to_string(), GoldenMove::record(&DecisionTrace::new("gone", 0)))]);
+        assert!(check_golden(fixtures, &mut golden, true).is_empty());
+        assert_eq!(golden.keys().collect::<Vec<_>>(), [name]);
+
+        // a tie marked by hand survives a rewrite
+        let chosen = golden[name].moves[0].clone();
+        let other = ["up", "down", "left", "right"].into_iter().find(|mv| *mv != chosen).unwrap().to_string();
+        golden.get_mut(name).unwrap().moves = vec![other.clone(), chosen.clone()];
+        assert!(check_golden(fixtures, &mut golden, true).is_empty());
+        assert_eq!(golden[name].moves, [other.clone(), chosen]);
+
+        // and a move that changed is reported with the board
+        golden.get_mut(name).unwrap().moves = vec![other];
+        let failures = check_golden(fixtures, &mut golden, false);
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].contains(&fixtures[0].state.board.render()), "{}", failures[0]);
+    }
+}
@@ -0,0 +1,325 @@
+use crate::postmortem::PostMortem;
+use crate::trace::DecisionTrace;
+use crate::types;
+use log::warn;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::time::{Duration, Instant};
+use std::{env, thread};
+
+/// set to a directory, `logs` for example, to give every game its own log at `<dir>/<game_id>.log`, or at
+/// `<dir>/<route>/<game_id>.log` for the personalities mounted away from the root
+pub const GAME_LOG_DIR_ENV: &str = "BATTLESNAKE_GAME_LOG_DIR";
+
+/// set to `1` to keep the decision traces out of the global log while the game logs are on
+pub const GAME_LOG_ONLY_ENV: &str = "BATTLESNAKE_GAME_LOG_ONLY";
+
+enum Message {
+    /// start a game's log, closing the logs that have gone quiet for longer than the ttl
+    Open(String),
+    Line(String, String),
+    /// the game is over, flush and close its log
+    Close(String),
+    /// flush every open log, answered once every message sent before it has been written
+    Flush(Sender<()>),
+}
+
+/// writes every game's turns to its own file on a background thread, so the logs of concurrent games don't interleave
+/// and writing never holds up a response
+pub struct GameLogger {
+    sender: Option<Sender<Message>>,
+    /// whether the game logs replace the global log for decision traces
+    exclusive: bool,
+}
+
+struct GameLog {
+    file: BufWriter<File>,
+    touched: Instant,
+}
+
+impl GameLogger {
+    /// # from_env
+    /// a logger writing to `GAME_LOG_DIR_ENV`, or one that drops everything when it isn't set
+    pub fn from_env(ttl: Duration) -> Self {
+        return match env::var(GAME_LOG_DIR_ENV) {
+            Ok(dir) => {
+                let exclusive = env::var(GAME_LOG_ONLY_ENV).is_ok_and(|only| only == "1");
+                GameLogger::new(PathBuf::from(dir), ttl, exclusive)
+            }
+            Err(_) => GameLogger::disabled(),
+        };
+    }
+
+    /// # disabled
+    /// a logger that drops everything
+    pub fn disabled() -> Self {
+        return GameLogger {
+            sender: None,
+            exclusive: false,
+        };
+    }
+
+    /// # new
+    /// starts the writer thread for a directory of game logs
+    /// ## Arguments:
+    /// * dir - where the logs go
+    /// * ttl - how long a game's log stays open without hearing from the game, for games that never end
+    /// * exclusive - whether the game logs replace the global log for decision traces
+    pub fn new(dir: PathBuf, ttl: Duration, exclusive: bool) -> Self {
+        let (sender, receiver) = mpsc::channel::<Message>();
+        thread::spawn(move || {
+            let mut logs: HashMap<String, GameLog> = HashMap::new();
+            for message in receiver {
+                match message {
+                    Message::Open(name) => {
+                        logs.retain(|_, log| {
+                            let fresh = log.touched.elapsed() < ttl;
+                            if !fresh {
+                                let _ = log.file.flush();
+                            }
+                            return fresh;
+                        });
+                        match open(&dir, &name) {
+                            Ok(file) => {
+                                let touched = Instant::now();
+                                logs.insert(name, GameLog { file, touched });
+                            }
+                            Err(err) => warn!("couldn't open the game log {}: {}", name, err),
+                        }
+                    }
+                    Message::Line(name, line) => {
+                        // a game we missed the start of is logged from here
+                        let log = match logs.entry(name) {
+                            Entry::Occupied(entry) => entry.into_mut(),
+                            Entry::Vacant(entry) => match open(&dir, entry.key()) {
+                                Ok(file) => entry.insert(GameLog {
+                                    file,
+                                    touched: Instant::now(),
+                                }),
+                                Err(err) => {
+                                    warn!("couldn't open the game log {}: {}", entry.key(), err);
+                                    continue;
+                                }
+                            },
+                        };
+                        log.touched = Instant::now();
+                        if let Err(err) = writeln!(log.file, "{}", line) {
+                            warn!("couldn't write a game log: {}", err);
+                        }
+                    }
+                    Message::Close(name) => {
+                        if let Some(mut log) = logs.remove(&name) {
+                            if let Err(err) = log.file.flush() {
+                                warn!("couldn't flush the game log {}: {}", name, err);
+                            }
+                        }
+                    }
+                    Message::Flush(done) => {
+                        for (name, log) in logs.iter_mut() {
+                            if let Err(err) = log.file.flush() {
+                                warn!("couldn't flush the game log {}: {}", name, err);
+                            }
+                        }
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+        return GameLogger {
+            sender: Some(sender),
+            exclusive,
+        };
+    }
+
+    /// # logs_globally
+    /// whether decision traces still belong in the global log
+    pub fn logs_globally(&self) -> bool {
+        return self.sender.is_none() || !self.exclusive;
+    }
+
+    fn send(&self, message: Message) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(message);
+        }
+    }
+
+    /// # start
+    /// opens a game's log
+    /// ## Arguments:
+    /// * route - the personality playing, empty for the root
+    /// * state - the start request
+    pub fn start(&self, route: &str, state: &types::GameState) {
+        if self.sender.is_none() {
+            return;
+        }
+        let name = file_name(route, &state.game.id);
+        self.send(Message::Open(name.clone()));
+        self.send(Message::Line(
+            name,
+            format!(
+                "game {} started, {}x{} with {} snakes\n{}",
+                state.game.id,
+                state.board.width,
+                state.board.height,
+                state.board.snakes.len(),
+                state.board.render()
+            ),
+        ));
+    }
+
+    /// # turn
+    /// logs a turn's board, the shouts that changed and how we chose our move
+    /// ## Arguments:
+    /// * route - the personality playing, empty for the root
+    /// * state - the move request
+    /// * trace - how the move was chosen
+    pub fn turn(&self, route: &str, state: &types::GameState, trace: &DecisionTrace) {
+        if self.sender.is_none() {
+            return;
+        }
+        let shouts: String = trace
+            .shouts
+            .iter()
+            .map(|shout| format!("SHOUT {}: {}\n", shout.snake, shout.shout))
+            .collect();
+        let trace = serde_json::to_string(trace).unwrap_or_default();
+        self.send(Message::Line(
+            file_name(route, &state.game.id),
+            format!(
+                "turn {}\n{}\n{}DECISION {}",
+                state.turn,
+                state.board.render(),
+                shouts,
+                trace
+            ),
+        ));
+    }
+
+    /// # end
+    /// closes a game's log with its post-mortem
+    /// ## Arguments:
+    /// * route - the personality playing, empty for the root
+    /// * state - the end request
+    /// * record - the post-mortem of the game
+    pub fn end(&self, route: &str, state: &types::GameState, record: &PostMortem) {
+        if self.sender.is_none() {
+            return;
+        }
+        let name = file_name(route, &state.game.id);
+        let record = serde_json::to_string(record).unwrap_or_default();
+        self.send(Message::Line(
+            name.clone(),
+            format!(
+                "game over on turn {}\n{}\nPOSTMORTEM {}",
+                state.turn,
+                state.board.render(),
+                record
+            ),
+        ));
+        self.send(Message::Close(name));
+    }
+
+    /// # flush
+    /// waits until everything queued so far has been written and flushed, including the games still in progress
+    pub fn flush(&self) {
+        if let Some(sender) = &self.sender {
+            let (done, wait) = mpsc::channel();
+            if sender.send(Message::Flush(done)).is_ok() {
+                let _ = wait.recv();
+            }
+        }
+    }
+}
+
+/// # file_name
+/// where a personality's log of a game goes, relative to the log directory
+fn file_name(route: &str, game_id: &str) -> String {
+    if route.is_empty() {
+        return format!("{}.log", game_id);
+    }
+    return format!("{}/{}.log", route, game_id);
+}
+
+fn open(dir: &Path, name: &str) -> io::Result<BufWriter<File>> {
+    let path = dir.join(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    return Ok(BufWriter::new(file));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::postmortem;
+    use crate::store::GAME_TTL;
+    use serde_json::json;
+
+    fn state(game_id: &str, turn: u32) -> types::GameState {
+        let me = json!({
+            "id": "me",
+            "name": "me",
+            "health": 100 - turn,
+            "body": [{ "x": 1, "y": turn }, { "x": 1, "y": 0 }, { "x": 1, "y": 0 }],
+            "head": { "x": 1, "y": turn },
+            "length": 3,
+            "shout": ""
+        });
+        return serde_json::from_value(json!({
+            "game": { "id": game_id, "ruleset": { "name": "standard" }, "timeout": 500 },
+            "turn": turn,
+            "board": { "height": 7, "width": 7, "food": [], "snakes": [me], "hazards": [] },
+            "you": me
+        }))
+        .unwrap();
+    }
+
+    #[test]
+    fn interleaved_games_get_their_own_logs() {
+        let dir = env::temp_dir().join(format!("battlesnake-game-logs-{}", std::process::id()));
+        let logger = GameLogger::new(dir.clone(), GAME_TTL, true);
+        assert!(!logger.logs_globally());
+        for game_id in ["first", "second"] {
+            logger.start("", &state(game_id, 0));
+        }
+        for turn in 1..4 {
+            for game_id in ["first", "second"] {
+                let mut trace = DecisionTrace::new(game_id, turn);
+                trace.chosen = "up".to_string();
+                logger.turn("", &state(game_id, turn), &trace);
+            }
+        }
+        for game_id in ["first", "second"] {
+            let end = state(game_id, 4);
+            let record = postmortem::analyse(&end.game, end.turn, &end.board, &end.you, &[]);
+            logger.end("", &end, &record);
+        }
+        logger.flush();
+
+        for game_id in ["first", "second"] {
+            let log = fs::read_to_string(dir.join(format!("{}.log", game_id))).unwrap();
+            let turns: Vec<&str> = log
+                .lines()
+                .filter(|line| line.starts_with("turn "))
+                .collect();
+            assert_eq!(turns, ["turn 1", "turn 2", "turn 3"]);
+            let decisions: Vec<serde_json::Value> = log
+                .lines()
+                .filter_map(|line| line.strip_prefix("DECISION "))
+                .map(|trace| serde_json::from_str(trace).unwrap())
+                .collect();
+            assert_eq!(decisions.len(), 3);
+            assert!(decisions.iter().all(|trace| trace["game_id"] == game_id));
+            assert!(log.starts_with(&format!("game {} started", game_id)));
+            let last = log.lines().last().unwrap();
+            assert!(last.starts_with("POSTMORTEM "), "{}", last);
+            assert!(last.contains(&format!("\"game_id\":\"{}\"", game_id)));
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
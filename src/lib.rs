@@ -0,0 +1,84 @@
+//! the snake's move pipeline, the searches it runs and the simulator they play positions forward in, for the server
+//! and anything else that wants a move out of a game state without going through HTTP:
+//!
+//! ```
+//! use battlesnake::{decide, GameState, StrategyConfig};
+//! use std::time::{Duration, Instant};
+//!
+//! let state: GameState = serde_json::from_str(include_str!("../testdata/fixtures/corner.json")).unwrap();
+//! let decision = decide(&state, &StrategyConfig::default(), Instant::now() + Duration::from_millis(200));
+//! assert_eq!(decision.response["move"], "right");
+//! ```
+//!
+//! the api is `decide`, the game state types in `types` with `Board::play_turn` to play a game forward, and the
+//! recorded positions in `fixtures`. everything else is the pipeline's and the server's own, the binary starts the
+//! server and the command line tools through `run`
+
+#![allow(clippy::needless_return, clippy::too_many_arguments)]
+
+#[macro_use]
+extern crate rocket;
+
+#[cfg(test)]
+mod allocations;
+mod arena;
+mod bench;
+mod budget;
+mod cache;
+mod capture;
+mod config;
+mod context;
+mod diff;
+mod drain;
+mod fetch;
+pub mod fixtures;
+mod gamelog;
+mod live;
+mod logic;
+mod metrics;
+mod opponents;
+mod pace;
+mod payload;
+mod phase;
+mod pool;
+mod postmortem;
+#[cfg(test)]
+mod property;
+mod replay;
+mod results;
+mod search;
+mod seed;
+mod server;
+mod spans;
+mod stats;
+mod store;
+mod trace;
+mod tune;
+pub mod types;
+#[cfg(any(test, feature = "verify"))]
+mod verify;
+mod version;
+mod visualize;
+
+pub use config::StrategyConfig;
+pub use fixtures::Fixture;
+pub use logic::{Branch, Decision, DecisionError};
+pub use server::run;
+pub use trace::DecisionTrace;
+pub use types::GameState;
+
+use std::time::Instant;
+
+/// # decide
+/// the move the snake at the root of the server would answer a request with, the move pipeline run with the
+/// config's default profile against opponents we don't know anything about
+/// ## Arguments:
+/// * state - the move request
+/// * config - the strategy tunables
+/// * deadline - when the move has to be ready
+/// ## Returns:
+/// the move and the trace of how the pipeline came to it
+pub fn decide(state: &GameState, config: &StrategyConfig, deadline: Instant) -> Decision {
+    let profile = config.profile("default").unwrap_or_default();
+    return logic::get_move_profiled(&state.game, &state.turn, &state.board, &state.you, &profile, deadline);
+}
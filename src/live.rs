@@ -0,0 +1,95 @@
+//! a live feed of our decisions, to watch a test game being played instead of tailing the logs. every move's trace and
+//! board go out as server-sent events on `GET /live`, `GET /live?game=<id>` follows a single game
+
+use crate::trace::DecisionTrace;
+use crate::types;
+use rocket::tokio::sync::broadcast::{self, Receiver, Sender};
+use serde::Serialize;
+use std::env;
+use std::sync::Arc;
+
+/// set to `1` to serve the feed
+pub const LIVE_ENV: &str = "BATTLESNAKE_LIVE";
+
+/// the turns kept for a client that's behind, a client further behind than this misses the oldest
+pub const CAPACITY: usize = 64;
+
+/// one of our moves as the feed sends it
+#[derive(Serialize, Debug)]
+pub struct LiveTurn {
+    pub game_id: String,
+    /// the personality that answered, empty for the root
+    pub route: String,
+    pub turn: u32,
+    pub trace: DecisionTrace,
+    /// the board as text, as the game logs draw it
+    pub board: String,
+}
+
+/// hands every move to the connected clients. publishing never waits on a client: the channel is bounded and a client
+/// that falls behind loses its oldest turns, so a stalled viewer can't hold up a move
+pub struct LiveFeed {
+    sender: Option<Sender<Arc<LiveTurn>>>,
+}
+
+impl LiveFeed {
+    /// # from_env
+    /// a feed when `LIVE_ENV` is set to `1`, otherwise one that's off
+    pub fn from_env() -> Self {
+        return match env::var(LIVE_ENV) {
+            Ok(live) if live == "1" => LiveFeed::new(CAPACITY),
+            _ => LiveFeed::disabled(),
+        };
+    }
+
+    /// # disabled
+    /// a feed that's off, nothing is published and `/live` isn't served
+    pub fn disabled() -> Self {
+        return LiveFeed { sender: None };
+    }
+
+    /// # new
+    /// a feed keeping up to `capacity` turns for each client
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        return LiveFeed {
+            sender: Some(sender),
+        };
+    }
+
+    /// # enabled
+    /// whether the feed is served
+    pub fn enabled(&self) -> bool {
+        return self.sender.is_some();
+    }
+
+    /// # publish
+    /// sends a move to every connected client, nothing is rendered when nobody is watching
+    /// ## Arguments:
+    /// * route - the personality that answered, empty for the root
+    /// * state - the move request
+    /// * trace - how the move was decided
+    pub fn publish(&self, route: &str, state: &types::GameState, trace: &DecisionTrace) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        if sender.receiver_count() == 0 {
+            return;
+        }
+        let turn = LiveTurn {
+            game_id: state.game.id.clone(),
+            route: route.to_string(),
+            turn: state.turn,
+            trace: trace.clone(),
+            board: state.board.render(),
+        };
+        // the only error is every client having gone since the count
+        let _ = sender.send(Arc::new(turn));
+    }
+
+    /// # subscribe
+    /// a client's view of the feed, from the next move on. none when the feed is off
+    pub fn subscribe(&self) -> Option<Receiver<Arc<LiveTurn>>> {
+        return self.sender.as_ref().map(Sender::subscribe);
+    }
+}
@@ -1,81 +1,276 @@
 use std::{
     cmp::Ordering,
-    collections::{HashMap, HashSet, VecDeque},
+    env,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 use crate::{
-    board_tile_is_free, get_board_tile,
-    search::graph,
-    types::{self, Coord},
+    context::TurnContext,
+    metrics,
+    opponents::{self, Announced, Stances},
+    pace::{self, Pace},
+    phase::{self, Phase, PhaseWeights},
+    pool::ComputePool,
+    postmortem,
+    search::{endgame, escape, graph, safety},
+    search::eval::{MoveScore, WIN_SCORE},
+    search::starvation::{self, Outlook},
+    search::minimax::{self, AlphaBeta, SearchMode, SearchResult},
+    search::opponent::{GreedyPolicy, SoftmaxPolicy},
+    seed, spans,
+    store::GameStore,
+    trace::{CrossingTrace, DecisionTrace, PathTrace},
+    types::{self, board_tile_is_free, get_board_tile, Coord},
 };
-use log::info;
+use log::{error, info, warn};
+use rocket::tokio::sync::oneshot;
+use rand::{seq::SliceRandom, Rng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use smallvec::SmallVec;
+use tracing::field::Empty;
+
+/// set to the milliseconds of the timeout to keep back for the response to reach the engine
+pub(crate) const LATENCY_BUFFER_ENV: &str = "BATTLESNAKE_LATENCY_BUFFER_MS";
+pub(crate) const DEFAULT_LATENCY_BUFFER: Duration = Duration::from_millis(120);
+
+pub(crate) const SNAKE_COLOR_ENV: &str = "SNAKE_COLOR";
+pub(crate) const SNAKE_HEAD_ENV: &str = "SNAKE_HEAD";
+pub(crate) const SNAKE_TAIL_ENV: &str = "SNAKE_TAIL";
+pub(crate) const SNAKE_AUTHOR_ENV: &str = "SNAKE_AUTHOR";
+const DEFAULT_COLOR: &str = "#c76d0c";
+const DEFAULT_HEAD: &str = "chicken";
+const DEFAULT_TAIL: &str = "mlh-gene";
+const DEFAULT_AUTHOR: &str = "tofurky";
+
+/// # snake_info
+/// the appearance the `SNAKE_*` variables ask for, anything unset keeps its default
+/// ## Arguments:
+/// * var - looks up a variable
+/// ## Returns:
+/// the appearance, with the default color and a warning if the color asked for isn't `#rrggbb`
+pub(crate) fn snake_info(var: impl Fn(&str) -> Option<String>) -> types::SnakeInfo {
+    let or_default = |key: &str, default: &str| var(key).unwrap_or_else(|| default.to_string());
+    let default_color = types::Color::parse(DEFAULT_COLOR).unwrap();
+    let color = match var(SNAKE_COLOR_ENV) {
+        Some(value) => types::Color::parse(&value).unwrap_or_else(|| {
+            warn!(
+                "{} {:?} isn't a #rrggbb color, using {}",
+                SNAKE_COLOR_ENV, value, default_color
+            );
+            default_color
+        }),
+        None => default_color,
+    };
+    return types::SnakeInfo {
+        apiversion: "1".to_string(),
+        author: or_default(SNAKE_AUTHOR_ENV, DEFAULT_AUTHOR),
+        color,
+        head: or_default(SNAKE_HEAD_ENV, DEFAULT_HEAD),
+        tail: or_default(SNAKE_TAIL_ENV, DEFAULT_TAIL),
+    };
+}
+
+/// # personality_info
+/// the appearance of a hosted personality, its `SNAKE_<ROUTE>_*` variables override the shared `SNAKE_*` ones
+/// ## Arguments:
+/// * route - the path the personality is mounted at, empty for the root which only reads the shared variables
+/// * var - looks up a variable
+pub(crate) fn personality_info(route: &str, var: impl Fn(&str) -> Option<String>) -> types::SnakeInfo {
+    if route.is_empty() {
+        return snake_info(var);
+    }
+    let prefix = format!("SNAKE_{}_", route.to_uppercase());
+    return snake_info(|key| var(&key.replacen("SNAKE_", &prefix, 1)).or_else(|| var(key)));
+}
 
 // info is called when you create your Battlesnake on play.battlesnake.com
 // and controls your Battlesnake's appearance
 // TIP: If you open your Battlesnake URL in a browser you should see this data
-pub fn info() -> Value {
+pub(crate) fn info(appearance: &types::SnakeInfo) -> types::SnakeInfo {
     info!("INFO");
 
-    return json!({
-        "apiversion": "1",
-        "author": "tofurky", // TODO: Your types::Battlesnake Username
-        "color": "#c76d0c", // TODO: Choose color
-        "head": "chicken", // TODO: Choose head
-        "tail": "mlh-gene", // TODO: Choose tail
-    });
+    return appearance.clone();
 }
 
 // start is called when your types::Battlesnake begins a game
-pub fn start(_game: &types::Game, _turn: &u32, _board: &types::Board, _you: &types::Battlesnake) {
+pub(crate) fn start(_game: &types::Game, _turn: &u32, _board: &types::Board, _you: &types::Battlesnake) {
     info!("GAME START");
 }
 
 // end is called when your types::Battlesnake finishes a game
-pub fn end(_game: &types::Game, _turn: &u32, _board: &types::Board, _you: &types::Battlesnake) {
+pub(crate) fn end(
+    game: &types::Game,
+    turn: &u32,
+    board: &types::Board,
+    you: &types::Battlesnake,
+    history: &[postmortem::Turn],
+) -> postmortem::PostMortem {
     info!("GAME OVER");
+    let record = postmortem::analyse(game, *turn, board, you, history);
+    postmortem::save(&record);
+    return record;
 }
 
-pub fn get_snake_from_tile<'a>(
+/// # get_snake_from_tile
+/// finds the snake on a tile. it reads every body to answer, anything asking about more than one tile should build
+/// `types::TileOwners` once instead
+/// ## Arguments:
+/// * tile - the tile in question
+/// * snakes - the snakes on the board
+/// ## Returns:
+/// the first snake in board order with a segment on the tile
+#[allow(dead_code)]
+pub(crate) fn get_snake_from_tile<'a>(
     tile: &types::Coord,
-    snakes: &'a Vec<types::Battlesnake>,
+    snakes: &'a [types::Battlesnake],
 ) -> Option<&'a types::Battlesnake> {
-    for snake in snakes {
-        for cell in &snake.body {
-            if cell == tile {
-                return Some(snake);
-            }
-        }
+    return types::TileOwners::new(snakes)
+        .get(tile)
+        .map(|owner| &snakes[owner.snake]);
+}
+
+/// the tiles next to a tile, kept inline since there are never more than four
+pub(crate) type AdjTiles = SmallVec<[types::Coord; 4]>;
+
+/// a move `get_scored_adj_tiles` found safe, with what it worked out about the move on the way
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ScoredTile {
+    pub coord: types::Coord,
+    /// the share of the free tiles the move is connected to, only worked out to order moves that diverge
+    pub connectivity: Option<f32>,
+    /// the safe tiles next to the move, when the moves were ordered by degree
+    pub degree: Option<u8>,
+    /// whether the move could lead somewhere the others don't, and had to clear the thresholds for it
+    pub diverged: bool,
+}
+
+impl ScoredTile {
+    /// # unscored
+    /// a move there was nothing to order against, so nothing was worked out about it
+    fn unscored(coord: types::Coord, diverged: bool) -> Self {
+        return ScoredTile {
+            coord,
+            connectivity: None,
+            degree: None,
+            diverged,
+        };
+    }
+}
+
+/// the scored tiles next to a tile, kept inline like `AdjTiles`
+pub(crate) type ScoredTiles = SmallVec<[ScoredTile; 4]>;
+
+/// which tiles the move helpers count as moves and how they order them. the defaults avoid the tiles next to the heads
+/// of snakes at least as long as us, order the moves by degree, don't take evasive action, exclude nothing, have no
+/// connectivity or degree threshold and only look for longer heads one move out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct MoveOptions<'a> {
+    avoid_snake_heads: bool,
+    apply_degree: bool,
+    evasive: bool,
+    exclude: &'a [types::Coord],
+    threshold: f32,
+    degree_threshold: u8,
+    head_radius: u8,
+}
+
+impl Default for MoveOptions<'_> {
+    fn default() -> Self {
+        return MoveOptions {
+            avoid_snake_heads: true,
+            apply_degree: true,
+            evasive: false,
+            exclude: &[],
+            threshold: 0.0,
+            degree_threshold: 0,
+            head_radius: 1,
+        };
+    }
+}
+
+impl<'a> MoveOptions<'a> {
+    /// # avoid_snake_heads
+    /// whether to avoid the tiles next to the heads of snakes at least as long as us, on by default
+    pub fn avoid_snake_heads(mut self, avoid: bool) -> Self {
+        self.avoid_snake_heads = avoid;
+        return self;
+    }
+
+    /// # apply_degree
+    /// whether to order the moves by their degree, and hold the diverging ones to the degree threshold, on by default
+    pub fn apply_degree(mut self, apply: bool) -> Self {
+        self.apply_degree = apply;
+        return self;
+    }
+
+    /// # evasive
+    /// whether to order the moves by how far they get us from food instead, useful if we're about to go head to head
+    /// with a larger snake. off by default
+    pub fn evasive(mut self, evasive: bool) -> Self {
+        self.evasive = evasive;
+        return self;
+    }
+
+    /// # exclude
+    /// tiles to count as blocked, the path we've planned for example, none by default
+    pub fn exclude(mut self, tiles: &'a [types::Coord]) -> Self {
+        self.exclude = tiles;
+        return self;
+    }
+
+    /// # head_radius
+    /// how many moves out to look for longer heads, 1 by default. at 2 a move every way on from which a snake at least
+    /// as long as us could meet our head is marked down, see `cut_off`, anything further is taken as 2
+    pub fn head_radius(mut self, radius: u8) -> Self {
+        self.head_radius = radius;
+        return self;
+    }
+
+    /// # avoids_snake_heads
+    /// whether the tiles next to the heads of snakes at least as long as us are avoided
+    pub fn avoids_snake_heads(&self) -> bool {
+        return self.avoid_snake_heads;
+    }
+
+    /// # connection_threshold
+    /// the share of the free tiles a diverging move has to be connected to
+    pub fn connection_threshold(&self) -> f32 {
+        return self.threshold;
+    }
+
+    /// # degree_threshold
+    /// the safe tiles a diverging move needs next to it
+    pub fn degree_threshold(&self) -> u8 {
+        return self.degree_threshold;
+    }
+
+    /// # thresholds
+    /// the share of the free tiles a diverging move has to be connected to and the safe tiles it needs next to it, none
+    /// by default
+    pub fn thresholds(mut self, threshold: f32, degree_threshold: u8) -> Self {
+        self.threshold = threshold;
+        self.degree_threshold = degree_threshold;
+        return self;
     }
-    return None;
 }
 
 /// # get_adj_tiles
 /// gets the tiles adjacent to a given tile that are safe to move on
 /// ## Arguments:
 /// * tile - the tile in question
-/// * board - the battlesnake game board
-/// * game_board - the hashmap representation of the game board
-/// * you - your battlesnake
-/// * avoid_snake_heads_option - option to avoid tiles adjacent to the heads of larger snakes
-/// * current_planned_moves_option - option to exclude a set of tiles from search
+/// * ctx - the turn's context
+/// * options - whether to avoid the tiles next to larger heads and the tiles to exclude
 /// ## Returns:
 /// vector of tiles adjacent to the given tile that the snake can move to
-pub fn get_adj_tiles(
-    tile: &types::Coord,
-    board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
-    you: &types::Battlesnake,
-    avoid_snake_heads_option: Option<bool>,
-    current_planned_moves_option: Option<Vec<types::Coord>>,
-) -> Vec<types::Coord> {
-    let current_planned_moves = current_planned_moves_option.unwrap_or(vec![]);
-    let mut adj: Vec<types::Coord> = vec![];
+pub(crate) fn get_adj_tiles(tile: &types::Coord, ctx: &TurnContext, options: &MoveOptions) -> AdjTiles {
+    let mut adj = AdjTiles::new();
     for (.., dir) in types::DIRECTIONS.into_iter() {
         let new_point = *dir + *tile;
-        if can_move_board(&new_point, board, game_board, you, avoid_snake_heads_option)
-            && !current_planned_moves.contains(&new_point)
-        {
+        if ctx.can_move(&new_point, options) && !options.exclude.contains(&new_point) {
             adj.push(new_point)
         }
     }
@@ -90,8 +285,8 @@ pub fn get_adj_tiles(
 /// * game_board - the hashmap representation of the game board
 /// ## Returns:
 /// vector of tiles adjacent to the given tile that are not out of bounds
-pub fn get_all_adj_tiles(tile: &types::Coord, board: &types::Board) -> Vec<types::Coord> {
-    let mut adj: Vec<types::Coord> = vec![];
+pub(crate) fn get_all_adj_tiles(tile: &types::Coord, board: &types::Board) -> AdjTiles {
+    let mut adj = AdjTiles::new();
     for (.., dir) in types::DIRECTIONS.into_iter() {
         let new_point = *dir + *tile;
         if new_point.x >= 0
@@ -107,85 +302,85 @@ pub fn get_all_adj_tiles(tile: &types::Coord, board: &types::Board) -> Vec<types
 
 /// # num_free_tiles  
 /// returns the number of free tiles on a board.  
-/// We need to count the occupied tiles using a hashset because some tiles can multiple board entities. (ie: overlapping snake bodies, hazard and food, etc)
+/// We need to mark the occupied tiles on a bitboard because some tiles can multiple board entities. (ie: overlapping snake bodies, hazard and food, etc)
 /// ## Arguments:
 /// * board - reference to board object
 /// ## Returns:
 /// The number of free tiles on the board
-pub fn num_free_tiles(board: &types::Board) -> u16 {
-    let mut occupied_tiles: HashSet<types::Coord> = HashSet::new();
-    for snake in &board.snakes {
-        occupied_tiles.extend(&snake.body);
+pub(crate) fn num_free_tiles(board: &types::Board) -> u16 {
+    #[cfg(test)]
+    FREE_TILE_COUNTS.with(|counts| counts.set(counts.get() + 1));
+    let mut occupied_tiles = board.to_occupancy();
+    for hazard in &board.hazards {
+        occupied_tiles.set(*hazard);
     }
+    return occupied_tiles.count_free();
+}
+
+/// # free_tiles_at
+/// the tiles `num_free_tiles` would count `k` turns from now as far as the bodies we can see go: every tail moved up
+/// `k` tiles, a body shorter than `k` gone altogether, and the opponents that could eat by then frozen where they are.
+/// the tiles the heads move onto aren't known, so they aren't taken
+/// ## Arguments:
+/// * board - the battlesnake game board
+/// * you - our snake
+/// * k - the turns from now, 0 for now
+#[allow(dead_code)] // the stall planning, space filling and post-eat pocket checks will count through this
+pub(crate) fn free_tiles_at(board: &types::Board, you: &types::Battlesnake, k: u16) -> u16 {
+    let mut occupied_tiles = types::TimedOccupancy::new(board, &you.id).occupancy_at(board.width, board.height, k);
     for hazard in &board.hazards {
-        occupied_tiles.insert(*hazard);
+        occupied_tiles.set(*hazard);
     }
-    return board.height as u16 * board.width as u16 - occupied_tiles.len() as u16;
+    return occupied_tiles.count_free();
+}
+
+#[cfg(test)]
+thread_local! {
+    /// the times `num_free_tiles` ran on this thread, for the tests to count
+    static FREE_TILE_COUNTS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
 }
 
 /// # num_connected_tiles
-/// gets the number of tiles connected to the first element in the frontier
+/// gets the number of tiles connected to a tile: the open tiles a flood fill from its neighbours reaches, with the tile
+/// itself and the fill's empty frontier counted on top, see `graph::flood_fill`
 /// ## Arguments:
-/// * board - the battlesnake game board
-/// * game_board - the hashmap representation of the game board
-/// * you - your battlesnake
-/// * frontier - used to track tiles on the edge of our explored set
-/// * visited - used to track the tiles that we've already visited and their parents
+/// * tile - the tile in question
+/// * ctx - the turn's context
 /// * exclude_tiles - list of tiles to exclude from flood fill, useful when we want to calculate connectivity of a tile given a snake's future position
+/// * limit - the count to stop at, the fill doesn't go further once it's reached
 /// ## Returns:
-/// the number of tiles connected to a supplied tile in the frontier
-fn num_connected_tiles(
-    board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
-    you: &types::Battlesnake,
-    frontier: &mut VecDeque<types::Coord>,
-    visited: &mut HashSet<types::Coord>,
-    exclude_tiles: &Vec<types::Coord>,
-) -> u8 {
-    if frontier.len() <= 0 {
-        return 1;
-    }
-    let current_tile = frontier.pop_front().unwrap();
-    let adj_tiles: Vec<types::Coord> =
-        get_adj_tiles(&current_tile, board, game_board, you, None, None)
-            .into_iter()
-            .filter(|adj| visited.get(adj).is_none() && !exclude_tiles.contains(adj))
-            .collect();
-    visited.extend(adj_tiles.clone());
-    let mut adj_deque = VecDeque::from(adj_tiles);
-    frontier.append(&mut adj_deque);
-    return 1 + num_connected_tiles(board, game_board, you, frontier, visited, exclude_tiles);
+/// the number of tiles connected to the tile, at most `limit`
+fn num_connected_tiles(tile: &types::Coord, ctx: &TurnContext, exclude_tiles: &[types::Coord], limit: u32) -> u32 {
+    let options = graph::FillOptions::default()
+        .exclude(exclude_tiles)
+        .budget(limit.saturating_sub(2))
+        .count_only(true);
+    let fill = graph::flood_fill(ctx, &get_all_adj_tiles(tile, ctx.board), &options);
+    return (2 + fill.count).min(limit.max(1));
 }
 
 /// # percent_connected
-/// gets the percentage of game tiles connected to the first element in the frontier
+/// gets the percentage of game tiles connected to the first element in the frontier. the turn's connectivity answers
+/// for no excluded tile or a single one without a flood fill
 /// ## Arguments:
 /// * tile - the tile in question
-/// * board - the battlesnake game board
-/// * game_board - the hashmap representation of the game board
-/// * you - your battlesnake
+/// * ctx - the turn's context
 /// * exclude_tiles - list of tiles to exclude from flood fill, useful when we want to calculate connectivity of a tile given a snake's future position
 /// ## Returns:
 /// the total percentage of tiles connected to a given tile
-fn percent_connected(
-    tile: &types::Coord,
-    board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
-    you: &types::Battlesnake,
-    exclude_tiles: &Vec<types::Coord>,
-) -> f32 {
-    let free_tiles = num_free_tiles(board);
-
-    let mut frontier = VecDeque::from([*tile]);
-    let mut visited: HashSet<types::Coord> = HashSet::new();
-    let connected_tiles = num_connected_tiles(
-        board,
-        game_board,
-        you,
-        &mut frontier,
-        &mut visited,
-        exclude_tiles,
-    );
+fn percent_connected(tile: &types::Coord, ctx: &TurnContext, exclude_tiles: &[types::Coord]) -> f32 {
+    let free_tiles = ctx.free_tiles;
+
+    let connected_tiles = match ctx.connectivity.connected_tiles(tile, exclude_tiles) {
+        Some(connected_tiles) => connected_tiles,
+        None => {
+            let connected_tiles = num_connected_tiles(tile, ctx, exclude_tiles, u32::MAX);
+            ctx.connectivity.record_fill(connected_tiles);
+            connected_tiles
+        }
+    };
+    #[cfg(feature = "verify")]
+    crate::verify::check_connected(ctx, tile, exclude_tiles, connected_tiles, u32::MAX);
 
     if free_tiles == 0 {
         return 0.0;
@@ -194,6 +389,60 @@ fn percent_connected(
     }
 }
 
+/// # tiles_needed
+/// the fewest connected tiles `percent_connected` puts at or above a threshold
+/// ## Arguments:
+/// * threshold - the percentage of the free tiles
+/// * free_tiles - the free tiles on the board
+/// ## Returns:
+/// the count, `u32::MAX` if no count clears the threshold
+pub(crate) fn tiles_needed(threshold: f32, free_tiles: u16) -> u32 {
+    if free_tiles == 0 {
+        return if threshold <= 0.0 { 0 } else { u32::MAX };
+    }
+    let clears = |connected: u32| connected as f32 / free_tiles as f32 >= threshold;
+    let mut needed = (threshold * free_tiles as f32).ceil().max(0.0) as u32;
+    // the product can round to either side of the count the division clears at
+    while needed > 0 && clears(needed - 1) {
+        needed -= 1;
+    }
+    while !clears(needed) && needed < u32::MAX {
+        needed += 1;
+    }
+    return needed;
+}
+
+/// # connected_at_least
+/// whether a tile is connected to at least a number of tiles, counted the way `percent_connected` counts them. the
+/// flood fill stops as soon as it's seen enough, on an open board that's long before it's filled the board
+/// ## Arguments:
+/// * tile - the tile in question
+/// * needed_tiles - the connected tiles the tile needs, see `tiles_needed`
+/// * ctx - the turn's context
+/// * exclude_tiles - list of tiles to exclude from flood fill
+/// ## Returns:
+/// true if the tile is connected to `needed_tiles` tiles or more
+pub(crate) fn connected_at_least(
+    tile: &types::Coord,
+    needed_tiles: u32,
+    ctx: &TurnContext,
+    exclude_tiles: &[types::Coord],
+) -> bool {
+    if needed_tiles == 0 {
+        return true;
+    }
+    if let Some(connected_tiles) = ctx.connectivity.connected_tiles(tile, exclude_tiles) {
+        #[cfg(feature = "verify")]
+        crate::verify::check_connected(ctx, tile, exclude_tiles, connected_tiles, u32::MAX);
+        return connected_tiles >= needed_tiles;
+    }
+    let connected_tiles = num_connected_tiles(tile, ctx, exclude_tiles, needed_tiles);
+    ctx.connectivity.record_fill(connected_tiles);
+    #[cfg(feature = "verify")]
+    crate::verify::check_connected(ctx, tile, exclude_tiles, connected_tiles, needed_tiles);
+    return connected_tiles >= needed_tiles;
+}
+
 /// # coords_diverge
 /// determines if two tiles, adjacent to the head of the snake may be disconnected
 /// ## Arguments:
@@ -205,7 +454,7 @@ fn percent_connected(
 fn coords_diverge(
     tile: &types::Coord,
     unit_coords: (&types::Coord, &types::Coord),
-    game_board: &HashMap<types::Coord, types::Flags>,
+    game_board: &types::FastMap<types::Coord, types::Flags>,
 ) -> bool {
     let (unit_coord1, unit_coord2) = unit_coords;
     let unit_vec = *unit_coord1 + *unit_coord2;
@@ -215,76 +464,75 @@ fn coords_diverge(
 }
 
 /// # favourable_divergent_coords
-/// given that two tiles may not be connected, determine the most connected tile
+/// given that two tiles may not be connected, find the ones connected enough to move onto
 /// ## Arguments:
 /// * tiles - the two tiles to test
-/// * board - the battlesnake game board
-/// * game_board - the hashmap representation of the game board
-/// * you - your battlesnake
+/// * ctx - the turn's context
 /// * exclude_tiles - list of tiles to exclude from flood fill, useful when we want to calculate connectivity of a tile given a snake's future position
-/// * threshold - the percentage of total free tiles you want to be connected to
 /// * degree_threshold - the minimum number of adjacent tiles that a given tile must have to be considered valid
+/// * needed_tiles - the connected tiles a tile needs, see `tiles_needed`
 /// ## Returns:
-/// if strict is true it returns a reference to all the provided tiles that are connected above the threshold, otherwise it returns an array of
-/// tiles and their corresponding connectivity index sorted in order from least connected to most
+/// the provided tiles that are connected above the threshold, in the order they were provided
 fn favourable_divergent_coords<'a>(
     tiles: [&'a types::Coord; 2],
-    board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
-    you: &types::Battlesnake,
-    exclude_tiles: &Vec<types::Coord>,
+    ctx: &TurnContext,
+    exclude_tiles: &[types::Coord],
     degree_threshold: u8,
-    threshold: f32,
-    avoid_snake_heads_option: Option<bool>,
-    apply_degree: Option<bool>,
-    evasive_action_option: Option<bool>,
-) -> Vec<(&'a types::Coord, f32)> {
-    let connected_unit_moves: Vec<(&types::Coord, f32)> = tiles
-        .into_iter()
-        .map(|tile| {
-            (
-                tile,
-                percent_connected(tile, board, game_board, you, exclude_tiles),
-            )
-        })
-        .collect();
-    let mut connected_unit_moves_filtered: Vec<(&types::Coord, f32)> = connected_unit_moves
-        .clone()
+    needed_tiles: u32,
+) -> Vec<&'a types::Coord> {
+    let options = MoveOptions::default().exclude(exclude_tiles);
+    let favourable = map_candidates(&tiles, ctx, |tile| {
+        return get_adj_tiles(tile, ctx, &options).len() as u8 >= degree_threshold
+            && connected_at_least(tile, needed_tiles, ctx, exclude_tiles);
+    });
+    return tiles
         .into_iter()
-        .filter(|(&tile, conn)| {
-            *conn >= threshold
-                && get_adj_tiles(
-                    &tile,
-                    board,
-                    game_board,
-                    you,
-                    None,
-                    Some(exclude_tiles.to_vec()),
-                )
-                .len() as u8
-                    >= degree_threshold
-        })
+        .zip(favourable)
+        .filter_map(|(tile, favourable)| favourable.then_some(tile))
         .collect();
+}
 
-    connected_unit_moves_filtered.sort_by(|(a, a_conn), (b, b_conn)| {
-        let order = (*a_conn).partial_cmp(b_conn).unwrap();
-        if order == Ordering::Equal {
-            return compare_moves(
-                a,
-                b,
-                board,
-                game_board,
-                you,
-                exclude_tiles,
-                avoid_snake_heads_option,
-                apply_degree,
-                evasive_action_option,
-            );
-        } else {
-            return order;
-        }
+/// the tiles on a board, 200 is between an 11x11 board and a 19x19 one, from which the divergence analysis may fill
+/// its candidates' regions side by side. on a smaller board a fill is over before a thread would pick it up
+pub(crate) const PARALLEL_FILL_TILES: u16 = 200;
+
+/// # map_candidates
+/// asks the same question of every candidate move, side by side on the rayon pool when the profile allows it and the
+/// board is big enough to be worth it. the answers come back in the candidates' order either way
+/// ## Arguments:
+/// * tiles - the candidates
+/// * ctx - the turn's context
+/// * question - what to ask of a candidate
+fn map_candidates<'a, T: Send>(
+    tiles: &[&'a types::Coord],
+    ctx: &TurnContext,
+    question: impl Fn(&'a types::Coord) -> T + Sync,
+) -> Vec<T> {
+    let board_tiles = ctx.board.width as u16 * ctx.board.height as u16;
+    if ctx.profile.parallel_fills && tiles.len() > 1 && board_tiles >= PARALLEL_FILL_TILES {
+        return tiles.par_iter().map(|tile| question(tile)).collect();
+    }
+    return tiles.iter().map(|tile| question(tile)).collect();
+}
+
+/// # sort_by_connectivity
+/// sorts tiles from least connected to most, the ties from least favourable to most. the only place the divergence
+/// analysis needs to know exactly how connected a tile is, and it's only asked when there's more than one tile
+/// ## Arguments:
+/// * tiles - the tiles to sort
+/// * ctx - the turn's context
+/// * options - how to key the moves, its excluded tiles are left out of the flood fill
+/// ## Returns:
+/// the tiles sorted and scored, each marked as having diverged
+fn sort_by_connectivity(tiles: &[types::Coord], ctx: &TurnContext, options: &MoveOptions) -> ScoredTiles {
+    if tiles.len() < 2 {
+        return tiles.iter().map(|tile| ScoredTile::unscored(*tile, true)).collect();
+    }
+    let candidates: Vec<&types::Coord> = tiles.iter().collect();
+    let keys = map_candidates(&candidates, ctx, |tile| {
+        return MoveKey::new(tile, ctx, options).with_connectivity(percent_connected(tile, ctx, options.exclude));
     });
-    return connected_unit_moves_filtered;
+    return score_keyed(tiles, ctx.profile.move_order, keys, true);
 }
 
 /// # distance_to_center
@@ -302,138 +550,228 @@ fn distance_to_center(tile: &types::Coord, board: &types::Board) -> f32 {
     return tile.distance(&center);
 }
 
+/// what `compare_moves` orders a move by, worked out once for each move being sorted instead of once for each
+/// comparison it's in
+#[derive(Debug, Clone, Copy)]
+struct MoveKey {
+    /// whether every way on from the move could meet a longer head, see `cut_off`
+    cut_off: bool,
+    /// whether a longer snake is herding us along with the move, see `TurnContext::herded`
+    herded: bool,
+    /// whether the move eats a food while we're full
+    feeds: bool,
+    /// the percentage of the free tiles the move is connected to, when the moves are sorted by it first
+    connected: Option<f32>,
+    /// the distance to the closest food, only when taking evasive action
+    food: Option<f32>,
+    /// the safe tiles next to the move, only when ordering by degree
+    degree: Option<usize>,
+    center: f32,
+}
+
+impl MoveKey {
+    /// # new
+    /// the key of a move
+    /// ## Arguments:
+    /// * tile - the move
+    /// * ctx - the turn's context
+    /// * options - whether to key by degree or by the distance to food, its excluded tiles aren't counted in the degree
+    fn new(tile: &types::Coord, ctx: &TurnContext, options: &MoveOptions) -> Self {
+        let feeds = ctx.hunger == Hunger::Full && ctx.board.food.contains(tile) && !ctx.denials.contains(tile);
+        if options.evasive && !ctx.board.food.is_empty() {
+            return MoveKey {
+                cut_off: cut_off(tile, ctx, options),
+                herded: ctx.herded(tile),
+                feeds,
+                connected: None,
+                food: graph::closest_food(tile, ctx.board),
+                degree: None,
+                center: 0.0,
+            };
+        }
+        let degree = options.apply_degree.then(|| {
+            #[cfg(test)]
+            MOVE_DEGREES.with(|degrees| degrees.set(degrees.get() + 1));
+            return get_adj_tiles(tile, ctx, options).len();
+        });
+        return MoveKey {
+            cut_off: cut_off(tile, ctx, options),
+            herded: ctx.herded(tile),
+            feeds,
+            connected: None,
+            food: None,
+            degree,
+            center: distance_to_center(tile, ctx.board),
+        };
+    }
+
+    /// # with_connectivity
+    /// the same key, sorted by the move's connectivity before anything else
+    fn with_connectivity(mut self, connected: f32) -> Self {
+        self.connected = Some(connected);
+        return self;
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// the times a move's degree was worked out for `compare_moves` on this thread, for the tests to count
+    static MOVE_DEGREES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// one thing moves are ordered by. a profile orders moves by a chain of them, each one only settling the ties the ones
+/// before it left
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveCriterion {
+    /// a move every way on from which could meet a longer head is the least favourable, see `cut_off`
+    CutOff,
+    /// then a move a longer snake is herding us along
+    Herded,
+    /// the least connected, when connectivity is part of the keys
+    Connectivity,
+    /// a move that eats while we're full
+    Feeds,
+    /// the closest to food, when taking evasive action
+    FoodDistance,
+    /// the lowest degree, when it counts
+    Degree,
+    /// the furthest from the center
+    Center,
+}
+
+impl MoveCriterion {
+    /// the order moves have always been sorted in
+    pub const DEFAULT_ORDER: &'static [MoveCriterion] = &[
+        MoveCriterion::CutOff,
+        MoveCriterion::Herded,
+        MoveCriterion::Connectivity,
+        MoveCriterion::Feeds,
+        MoveCriterion::FoodDistance,
+        MoveCriterion::Degree,
+        MoveCriterion::Center,
+    ];
+
+    /// # compare
+    /// the ordering that sorts two moves from least favourable to most by this criterion alone
+    fn compare(&self, a: &MoveKey, b: &MoveKey) -> Ordering {
+        return match self {
+            MoveCriterion::CutOff => b.cut_off.cmp(&a.cut_off),
+            MoveCriterion::Herded => b.herded.cmp(&a.herded),
+            MoveCriterion::Connectivity => match (a.connected, b.connected) {
+                (Some(a_conn), Some(b_conn)) => a_conn.total_cmp(&b_conn),
+                _ => Ordering::Equal,
+            },
+            MoveCriterion::Feeds => b.feeds.cmp(&a.feeds),
+            MoveCriterion::FoodDistance => match (a.food, b.food) {
+                (Some(a_food), Some(b_food)) => a_food.total_cmp(&b_food),
+                _ => Ordering::Equal,
+            },
+            MoveCriterion::Degree => a.degree.cmp(&b.degree),
+            MoveCriterion::Center => b.center.total_cmp(&a.center),
+        };
+    }
+}
+
 /// # compare_moves
-/// given two moves are equally connected, return the ordering that sorts the tiles from least favourable to most
+/// return the ordering that sorts the tiles from least favourable to most, by the first criterion of the chain that
+/// tells them apart
+/// * order - the criteria, see `MoveCriterion::DEFAULT_ORDER`
 /// * a - one move for comparison
 /// * b - the other move to compare
-/// * degree_order_option - whether or not to take into account the degree of the tile in the comparison
-/// * evasive_action_option - whether to get as far away from food as possible (useful if we're about to go head to head with larger snake)
-fn compare_moves(
-    a: &types::Coord,
-    b: &types::Coord,
-    board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
-    you: &types::Battlesnake,
-    current_planned_moves: &Vec<types::Coord>,
-    avoid_snake_heads_option: Option<bool>,
-    degree_order_option: Option<bool>,
-    evasive_action_option: Option<bool>,
-) -> Ordering {
-    let evasive_action = evasive_action_option.unwrap_or(false);
-    let degree_order = degree_order_option.unwrap_or(true);
-
-    if evasive_action && board.food.len() > 0 {
-        return graph::closest_food(a, board)
-            .unwrap()
-            .partial_cmp(&graph::closest_food(b, board).unwrap())
-            .unwrap();
-    }
+fn compare_moves(order: &[MoveCriterion], a: &MoveKey, b: &MoveKey) -> Ordering {
+    return order
+        .iter()
+        .map(|criterion| criterion.compare(a, b))
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal);
+}
 
-    let adj_a: Vec<types::Coord> = get_adj_tiles(
-        a,
-        board,
-        game_board,
-        you,
-        avoid_snake_heads_option,
-        Some(current_planned_moves.to_vec()),
-    )
-    .into_iter()
-    .filter(|item| !current_planned_moves.contains(item))
-    .collect();
-    let adj_b: Vec<types::Coord> = get_adj_tiles(
-        b,
-        board,
-        game_board,
-        you,
-        avoid_snake_heads_option,
-        Some(current_planned_moves.to_vec()),
-    )
-    .into_iter()
-    .filter(|item| !current_planned_moves.contains(item))
-    .collect();
-    let conn_order = adj_a.len().cmp(&adj_b.len());
-    if conn_order == Ordering::Equal || !degree_order {
-        return distance_to_center(b, board)
-            .partial_cmp(&distance_to_center(a, board))
-            .unwrap();
-    } else {
-        return conn_order;
+/// # score_moves
+/// sorts moves from least favourable to most by `compare_moves`, with each move's key worked out just once and kept
+/// with the move
+/// ## Arguments:
+/// * tiles - the moves to sort
+/// * order - the criteria to sort them by
+/// * key - the key of a move
+fn score_moves(
+    tiles: &[types::Coord],
+    order: &[MoveCriterion],
+    key: impl Fn(&types::Coord) -> MoveKey,
+) -> ScoredTiles {
+    if tiles.len() < 2 {
+        return tiles.iter().map(|tile| ScoredTile::unscored(*tile, false)).collect();
     }
+    let keys = tiles.iter().map(key).collect();
+    return score_keyed(tiles, order, keys, false);
+}
+
+/// # score_keyed
+/// sorts moves from least favourable to most by keys already worked out, one for each move in the same order
+/// ## Arguments:
+/// * tiles - the moves to sort
+/// * order - the criteria to sort them by
+/// * keys - the key of each move
+/// * diverged - whether the moves went through the divergence analysis
+fn score_keyed(tiles: &[types::Coord], order: &[MoveCriterion], keys: Vec<MoveKey>, diverged: bool) -> ScoredTiles {
+    let mut keyed: SmallVec<[(types::Coord, MoveKey); 4]> = tiles.iter().copied().zip(keys).collect();
+    keyed.sort_by(|(_, a), (_, b)| compare_moves(order, a, b));
+    return keyed
+        .into_iter()
+        .map(|(coord, key)| ScoredTile {
+            coord,
+            connectivity: key.connected,
+            degree: key.degree.map(|degree| degree as u8),
+            diverged,
+        })
+        .collect();
 }
 
 /// # get_adj_tiles_connected
-/// gets the tiles adjacent to a given tile that are safe to move on and are sufficiently connected
+/// gets the tiles adjacent to a given tile that are safe to move on and are sufficiently connected, see
+/// `get_scored_adj_tiles` for what was worked out about them on the way
 /// ## Arguments:
 /// * tile - the tile in question
-/// * board - the battlesnake game board
-/// * game_board - the hashmap representation of the game board
-/// * you - your battlesnake
-/// * theshold - the desired connectedness of any adjacent tiles
-/// * degree_threshold - the minimum number of adjacent tiles that a given tile must have to be considered valid
-/// * apply_degree - whether or not to apply the degree threshold / sorting
-/// * avoid_snake_heads_option - option to avoid tiles adjacent to the heads of larger snakes
-/// * current_planned_moves_option - option to avoid the provided tiles
+/// * ctx - the turn's context
+/// * options - the connectedness and degree thresholds, how to order the moves and the tiles to avoid
 /// ## Returns:
-/// if strict is true then ot returns all adjacent tiles that pass the connectedness threshold,
-/// else it returns all adjacent tiles in order of least to most connected
-pub fn get_adj_tiles_connected(
-    tile: &types::Coord,
-    board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
-    you: &types::Battlesnake,
-    threshold: f32,
-    degree_threshold: u8,
-    apply_degree: Option<bool>,
-    evasive_action_option: Option<bool>,
-    avoid_snake_heads_option: Option<bool>,
-    current_planned_moves_option: Option<Vec<types::Coord>>,
-) -> Vec<types::Coord> {
-    let current_planned_moves: Vec<types::Coord> = current_planned_moves_option.unwrap_or(vec![]);
+/// the adjacent tiles that pass the thresholds in order of least to most favourable
+pub(crate) fn get_adj_tiles_connected(tile: &types::Coord, ctx: &TurnContext, options: &MoveOptions) -> AdjTiles {
+    return get_scored_adj_tiles(tile, ctx, options).into_iter().map(|scored| scored.coord).collect();
+}
 
+/// # get_scored_adj_tiles
+/// gets the tiles adjacent to a given tile that are safe to move on and are sufficiently connected, with the
+/// connectivity and degree they were ordered by
+/// ## Arguments:
+/// * tile - the tile in question
+/// * ctx - the turn's context
+/// * options - the connectedness and degree thresholds, how to order the moves and the tiles to avoid
+/// ## Returns:
+/// the adjacent tiles that pass the thresholds in order of least to most favourable. when they diverge only the ones
+/// connected enough are left, ordered by their connectivity first
+pub(crate) fn get_scored_adj_tiles(tile: &types::Coord, ctx: &TurnContext, options: &MoveOptions) -> ScoredTiles {
+    let current_planned_moves = options.exclude;
+    let degree_threshold = options.degree_threshold;
     // get adjacent moves if they don't loop back on the same path
-    let mut moves: Vec<types::Coord> = get_adj_tiles(
-        tile,
-        board,
-        game_board,
-        you,
-        avoid_snake_heads_option,
-        Some((&current_planned_moves).to_vec()),
-    )
-    .into_iter()
-    .filter(|item| !current_planned_moves.contains(item))
-    .collect();
+    let adj_tiles = get_adj_tiles(tile, ctx, options);
     // if connectivity is equal, if evasive_action is enabled: move away from closest food, else: sort moves by degree, if degree is equal, sort by distance to center
-    moves.sort_by(|a, b| {
-        compare_moves(
-            a,
-            b,
-            board,
-            game_board,
-            you,
-            &current_planned_moves,
-            avoid_snake_heads_option,
-            apply_degree,
-            evasive_action_option,
-        )
-    });
-    let unit_moves: Vec<types::Coord> = (&moves).into_iter().map(|adj| *adj - *tile).collect();
+    let moves = score_moves(&adj_tiles, ctx.profile.move_order, |mv| MoveKey::new(mv, ctx, options));
+    let unit_moves: Vec<types::Coord> = moves.iter().map(|adj| adj.coord - *tile).collect();
+    let needed_tiles = tiles_needed(options.threshold, ctx.free_tiles);
     if unit_moves.len() == 2 {
-        if coords_diverge(tile, (&unit_moves[0], &unit_moves[1]), game_board) {
-            return favourable_divergent_coords(
-                [&moves[0], &moves[1]],
-                board,
-                game_board,
-                you,
-                &current_planned_moves,
+        if coords_diverge(tile, (&unit_moves[0], &unit_moves[1]), ctx.game_board) {
+            let favourable_moves: AdjTiles = favourable_divergent_coords(
+                [&moves[0].coord, &moves[1].coord],
+                ctx,
+                current_planned_moves,
                 degree_threshold,
-                threshold,
-                avoid_snake_heads_option,
-                apply_degree,
-                evasive_action_option,
+                needed_tiles,
             )
             .into_iter()
-            .map(|(mv, _)| *mv)
+            .copied()
             .collect();
+            return sort_by_connectivity(&favourable_moves, ctx, options);
         } else {
             return moves;
         }
@@ -444,12 +782,12 @@ pub fn get_adj_tiles_connected(
             .filter(|mv| *mv != forward_unit_vec)
             .collect();
         if side_unit_moves.len() != 2 {
-            return vec![];
+            return ScoredTiles::new();
         }
 
         // if none of the coords take a divergent path then they are all equally connected, skip calculations
-        if !(coords_diverge(tile, (&forward_unit_vec, &side_unit_moves[0]), game_board)
-            || coords_diverge(tile, (&forward_unit_vec, &side_unit_moves[1]), game_board))
+        if !(coords_diverge(tile, (&forward_unit_vec, &side_unit_moves[0]), ctx.game_board)
+            || coords_diverge(tile, (&forward_unit_vec, &side_unit_moves[1]), ctx.game_board))
         {
             return moves;
         }
@@ -460,59 +798,30 @@ pub fn get_adj_tiles_connected(
             .collect();
         let forward_vec = forward_unit_vec + *tile;
         //find the best connected moves on one side of the head
-        let mut favouravble_moves_1 = favourable_divergent_coords(
+        let favouravble_moves_1 = favourable_divergent_coords(
             [&forward_vec, &side_moves[0]],
-            board,
-            game_board,
-            you,
-            &current_planned_moves,
+            ctx,
+            current_planned_moves,
             degree_threshold,
-            threshold,
-            avoid_snake_heads_option,
-            apply_degree,
-            evasive_action_option,
+            needed_tiles,
         );
         //find the best connected moves on the other side of the head
-        let mut favouravble_moves_2 = favourable_divergent_coords(
+        let favouravble_moves_2 = favourable_divergent_coords(
             [&forward_vec, &side_moves[1]],
-            board,
-            game_board,
-            you,
-            &current_planned_moves,
+            ctx,
+            current_planned_moves,
             degree_threshold,
-            threshold,
-            avoid_snake_heads_option,
-            apply_degree,
-            evasive_action_option,
-        )
-        .into_iter()
-        .filter(|&item| !favouravble_moves_1.contains(&item))
-        .collect();
-        let mut favourable_moves = Vec::new();
-        favourable_moves.append(&mut favouravble_moves_1);
-        favourable_moves.append(&mut favouravble_moves_2);
-
-        // sort by most connected
-        favourable_moves.sort_by(|&(a, a_conn), &(b, b_conn)| {
-            let order = a_conn.partial_cmp(&b_conn).unwrap();
-            if order == Ordering::Equal {
-                return compare_moves(
-                    a,
-                    b,
-                    board,
-                    game_board,
-                    you,
-                    &current_planned_moves,
-                    avoid_snake_heads_option,
-                    apply_degree,
-                    evasive_action_option,
-                );
-            } else {
-                return order;
+            needed_tiles,
+        );
+        let mut favourable_moves = AdjTiles::new();
+        for mv in favouravble_moves_1.into_iter().chain(favouravble_moves_2) {
+            if !favourable_moves.contains(mv) {
+                favourable_moves.push(*mv);
             }
-        });
+        }
 
-        return favourable_moves.into_iter().map(|(mv, _)| *mv).collect();
+        // sort by most connected
+        return sort_by_connectivity(&favourable_moves, ctx, options);
     }
     return moves;
 }
@@ -530,9 +839,10 @@ fn adj_to_bigger_snake(
     board: &types::Board,
     you: &types::Battlesnake,
 ) -> bool {
-    // calculate distance to other snake heads to see if we are adjacent to snakes with higher health
+    // calculate distance to other snake heads to see if we are adjacent to snakes with higher health. a snake without
+    // a body has no head to fear, and on a frame that contradicts itself our copy on the board can differ from `you`
     for snake in &board.snakes {
-        if snake != you {
+        if snake.id != you.id && !snake.body.is_empty() {
             let distance = tile.distance(&snake.head);
             if distance <= 1.0 && snake.length >= you.length {
                 return true;
@@ -542,8 +852,294 @@ fn adj_to_bigger_snake(
     return false;
 }
 
+/// how many steps from our head a longer snake can be and still be herding us, see `shadowed_moves`
+pub(crate) const SHADOW_RANGE: i16 = 3;
+
+/// what A* adds to a first step a longer snake is herding us along, enough for any other first step to win unless it's
+/// a much longer way to food
+pub(crate) const HERDING_PENALTY: u16 = 16;
+
+/// what A* adds to a first step that's `cut_off`, well over `HERDING_PENALTY`: a squeeze can still be got out of, a
+/// head to head we'd lose can't
+pub(crate) const CUT_OFF_PENALTY: u16 = 4 * HERDING_PENALTY;
+
+/// # cut_off
+/// whether a move leaves us nowhere to go next that a snake at least as long as us couldn't get to by then. the move
+/// itself can be out of reach of every longer head, as `TurnContext::can_move` checks, and still have each way on from
+/// it a head to head we'd lose. only looked for with a `MoveOptions::head_radius` of 2
+/// ## Arguments:
+/// * tile - the move
+/// * ctx - the turn's context
+/// * options - how far out to look for longer heads
+/// ## Returns:
+/// true if every legal move on from the tile is within two moves of a longer head, false for a dead end as well,
+/// that's for the flood fills to see
+pub(crate) fn cut_off(tile: &types::Coord, ctx: &TurnContext, options: &MoveOptions) -> bool {
+    if options.head_radius < 2 {
+        return false;
+    }
+    let anywhere = MoveOptions::default().avoid_snake_heads(false);
+    let next = get_adj_tiles(tile, ctx, &anywhere.exclude(&[ctx.you.head]));
+    if next.is_empty() {
+        return false;
+    }
+    // the tiles each longer head could be on in one move and in two
+    let mut reach = types::FastSet::default();
+    for snake in ctx.board.snakes.iter().filter(|snake| snake.id != ctx.you.id && snake.length >= ctx.you.length) {
+        for first in get_all_adj_tiles(&snake.head, ctx.board) {
+            if !tile_is_open(&first, ctx.board, ctx.game_board) {
+                continue;
+            }
+            reach.insert(first);
+            reach.extend(
+                get_all_adj_tiles(&first, ctx.board)
+                    .into_iter()
+                    .filter(|second| tile_is_open(second, ctx.board, ctx.game_board)),
+            );
+        }
+    }
+    return next.iter().all(|next| reach.contains(next));
+}
+
+/// the widest lane, in tiles across counting ours, we count as being run down between a wall and a longer snake
+pub(crate) const SANDWICH_CLEARANCE: i16 = 2;
+
+/// # nearest_corner
+/// the corner of the board fewest steps from a tile
+fn nearest_corner(tile: &types::Coord, board: &types::Board) -> types::Coord {
+    let (right, top) = (board.width as i16 - 1, board.height as i16 - 1);
+    return [(0, 0), (right, 0), (0, top), (right, top)]
+        .into_iter()
+        .map(|(x, y)| Coord { x, y })
+        .min_by_key(|corner| corner.steps(tile))
+        .unwrap();
+}
+
+/// # escape_routes
+/// counts the ways out of a tile: its safe neighbours. a tile next to the head of a snake at least as long as us isn't
+/// safe, so a snake alongside closes the routes on its side the same way a wall does
+/// ## Arguments:
+/// * tile - the tile in question
+/// * ctx - the turn's context
+/// * exclude_tiles - tiles to count as blocked, the tile we'd be leaving for one
+/// ## Returns:
+/// the number of safe neighbours
+pub(crate) fn escape_routes(tile: &types::Coord, ctx: &TurnContext, exclude_tiles: &[types::Coord]) -> usize {
+    return get_adj_tiles(tile, ctx, &MoveOptions::default().exclude(exclude_tiles)).len();
+}
+
+/// # shadowed_moves
+/// finds the moves a longer snake is herding us into a corner with. a move is shadowed when it takes us closer to our
+/// nearest corner and leaves us fewer escape routes than we have now, while a longer snake within `SHADOW_RANGE` of
+/// our head is further from that corner than we are and heading toward it as well. once the flood fills see the trap
+/// it's already shut, so these moves are marked down while the board still looks connected
+/// ## Arguments:
+/// * ctx - the turn's context
+/// ## Returns:
+/// our moves into the corner, empty when nobody is shadowing us
+pub(crate) fn shadowed_moves(ctx: &TurnContext) -> AdjTiles {
+    let head = ctx.you.head;
+    let corner = nearest_corner(&head, ctx.board);
+    let shadowed = ctx.board.snakes.iter().any(|snake| {
+        snake != ctx.you
+            && snake.length > ctx.you.length
+            && snake.head.steps(&head) <= SHADOW_RANGE
+            && snake.head.steps(&corner) > head.steps(&corner)
+            && snake
+                .heading()
+                .is_some_and(|dir| (snake.head + dir).steps(&corner) < snake.head.steps(&corner))
+    });
+    #[cfg(test)]
+    let shadowed = shadowed && HERDING.with(|herding| herding.get());
+    if !shadowed {
+        return AdjTiles::new();
+    }
+    let routes = escape_routes(&head, ctx, &[]);
+    return get_adj_tiles(&head, ctx, &MoveOptions::default().avoid_snake_heads(false))
+        .into_iter()
+        .filter(|mv| mv.steps(&corner) < head.steps(&corner) && escape_routes(mv, ctx, &[head]) < routes)
+        .collect();
+}
+
+/// what a walk across a lane ran into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LaneSide {
+    /// the edge of the board or a hazard
+    Wall,
+    /// the body of the snake at this index on the board
+    Body(usize),
+    /// nothing within `SANDWICH_CLEARANCE`
+    Open,
+}
+
+/// # lane_side
+/// walks from a tile in a direction until something stops it, no further than the lane is wide
+/// ## Returns:
+/// what stopped the walk and the open tiles it passed on the way
+fn lane_side(tile: &types::Coord, dir: &types::Coord, ctx: &TurnContext) -> (LaneSide, i16) {
+    let mut next = *tile + *dir;
+    for open in 0..SANDWICH_CLEARANCE {
+        if !ctx.board.in_bounds(&next) || ctx.board.hazards.contains(&next) {
+            return (LaneSide::Wall, open);
+        }
+        if let Some(snake) = ctx.board.snakes.iter().position(|snake| snake.body.contains(&next)) {
+            return (LaneSide::Body(snake), open);
+        }
+        next = next + *dir;
+    }
+    return (LaneSide::Open, SANDWICH_CLEARANCE);
+}
+
+/// # sandwich_lane
+/// checks whether a longer snake is running us down a lane against a wall. we're sandwiched when the line across our
+/// head, or across the tile ahead of it, has a wall or hazard on one side and a longer snake's body on the other with
+/// no more than `SANDWICH_CLEARANCE` tiles between them, and that snake's head is further along the lane than ours.
+/// the lane only gets narrower while the fills still see the board it opens onto, so the moves that keep us in it are
+/// marked down while we can still break out sideways
+/// ## Arguments:
+/// * ctx - the turn's context
+/// ## Returns:
+/// the moves that keep us in the lane, racing down it or stepping across it toward the wall, none when we aren't in one
+pub(crate) fn sandwich_lane(ctx: &TurnContext) -> Option<AdjTiles> {
+    #[cfg(test)]
+    if !HERDING.with(|herding| herding.get()) {
+        return None;
+    }
+    let head = ctx.you.head;
+    let heading = ctx.you.heading()?;
+    let across = Coord { x: heading.y, y: heading.x };
+    let back = Coord { x: -across.x, y: -across.y };
+    let ahead = head + heading;
+    for line in [head, ahead] {
+        if line == ahead && !ctx.can_move(&ahead, &MoveOptions::default().avoid_snake_heads(false)) {
+            continue;
+        }
+        for (wall_dir, body_dir) in [(across, back), (back, across)] {
+            let (wall, wall_open) = lane_side(&line, &wall_dir, ctx);
+            let (body, body_open) = lane_side(&line, &body_dir, ctx);
+            let snake = match (wall, body) {
+                (LaneSide::Wall, LaneSide::Body(snake)) if 1 + wall_open + body_open <= SANDWICH_CLEARANCE => {
+                    &ctx.board.snakes[snake]
+                }
+                _ => continue,
+            };
+            let lead = snake.head - head;
+            if snake != ctx.you && snake.length > ctx.you.length && lead.x * heading.x + lead.y * heading.y > 0 {
+                return Some(
+                    get_adj_tiles(&head, ctx, &MoveOptions::default().avoid_snake_heads(false))
+                        .into_iter()
+                        .filter(|mv| *mv == ahead || *mv == head + wall_dir)
+                        .collect(),
+                );
+            }
+        }
+    }
+    return None;
+}
+
+/// how many lanes over from ours a longer snake can be and still be running alongside us, see `running_alongside`
+pub(crate) const ALONGSIDE_OFFSET: i16 = 2;
+
+/// how far ahead of ours a longer snake's head can be and still be running alongside us
+pub(crate) const ALONGSIDE_LEAD: i16 = 2;
+
+/// the turns in a row a longer snake has to have run alongside us before we break away, for a turn or two snakes are
+/// side by side all the time without one steering the other
+pub(crate) const ALONGSIDE_TURNS: u32 = 3;
+
+/// a longer snake running alongside us, see `running_alongside`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Alongside {
+    /// the snake's id
+    pub snake: String,
+    /// the turns in a row it's been alongside us, this one included
+    pub turns: u32,
+}
+
+/// # running_alongside
+/// finds a longer snake running alongside us: heading the way we are, a lane or two over and level with us or a little
+/// ahead. out in the open every most connected move keeps us beside it until it turns in and cuts us off, the squeeze
+/// `sandwich_lane` sees against a wall without the wall to see it by
+/// ## Arguments:
+/// * board - the battlesnake game board
+/// * you - our snake
+/// * before - the snake that was alongside us on the turn before, none if nobody was
+/// ## Returns:
+/// the snake alongside us and the turns in a row it's been there, none when nobody is
+pub(crate) fn running_alongside(
+    board: &types::Board,
+    you: &types::Battlesnake,
+    before: Option<&Alongside>,
+) -> Option<Alongside> {
+    #[cfg(test)]
+    if !HERDING.with(|herding| herding.get()) {
+        return None;
+    }
+    let heading = you.heading()?;
+    let snake = board.snakes.iter().find(|snake| {
+        let offset = snake.head - you.head;
+        // how far along our heading their head is from ours, and how many lanes across
+        let lead = offset.x * heading.x + offset.y * heading.y;
+        let across = (offset.x * heading.y - offset.y * heading.x).abs();
+        return snake.id != you.id
+            && snake.length > you.length
+            && snake.heading() == Some(heading)
+            && (0..=ALONGSIDE_LEAD).contains(&lead)
+            && (1..=ALONGSIDE_OFFSET).contains(&across);
+    })?;
+    let turns = match before {
+        Some(before) if before.snake == snake.id => before.turns + 1,
+        _ => 1,
+    };
+    return Some(Alongside {
+        snake: snake.id.clone(),
+        turns,
+    });
+}
+
+/// # alongside_moves
+/// the moves that keep us beside a snake that has run alongside us for `ALONGSIDE_TURNS`: every move but the one
+/// turning away from it, which drops us in behind its tail. none while it hasn't been there that long, or when turning
+/// away doesn't leave us the room the profile wants of a move, breaking away into a pocket is no better
+/// ## Arguments:
+/// * ctx - the turn's context
+/// * alongside - the snake alongside us
+pub(crate) fn alongside_moves(ctx: &TurnContext, alongside: &Alongside) -> Option<AdjTiles> {
+    if alongside.turns < ALONGSIDE_TURNS || ctx.lane.is_some() {
+        return None;
+    }
+    let snake = ctx.board.snakes.iter().find(|snake| snake.id == alongside.snake)?;
+    let heading = ctx.you.heading()?;
+    let offset = snake.head - ctx.you.head;
+    let side = Coord { x: heading.y, y: heading.x };
+    let away = match offset.x * side.x + offset.y * side.y > 0 {
+        true => Coord { x: -side.x, y: -side.y },
+        false => side,
+    };
+    let peel = ctx.you.head + away;
+    let room = tiles_needed(ctx.profile.connection_threshold, ctx.free_tiles);
+    if !ctx.can_move(&peel, &MoveOptions::default()) || !connected_at_least(&peel, room, ctx, &[]) {
+        return None;
+    }
+    return Some(
+        get_adj_tiles(&ctx.you.head, ctx, &MoveOptions::default().avoid_snake_heads(false))
+            .into_iter()
+            .filter(|mv| *mv != peel)
+            .collect(),
+    );
+}
+
+#[cfg(test)]
+thread_local! {
+    /// off for contexts made on this thread to ignore snakes herding us, into a corner, down a lane or alongside us,
+    /// for the tests to compare
+    static HERDING: std::cell::Cell<bool> = const { std::cell::Cell::new(true) };
+}
+
 /// # can_move_on_tail
-/// determines if it is safe to move on another snake's tail
+/// determines if it is safe to move on another snake's tail: every segment on the tile is the tip of a tail that will
+/// move. a tail stacked on the segment before it, on the first turn or after a squadmate shared its length, leaves a
+/// segment behind
 /// ## Arguments:
 /// * snakes - array of battlesnakes
 /// * coord - the tile in question
@@ -551,10 +1147,14 @@ fn adj_to_bigger_snake(
 /// true if we can safely move to coord
 macro_rules! can_move_on_tail {
     ($snakes:ident, $coord:ident) => {
-        $snakes
-            .into_iter()
-            .find(|snake| snake.health < 100 && snake.body[snake.body.len() - 1] == *$coord)
-            .is_some()
+        $snakes.into_iter().all(|snake| {
+            let tail = snake.body.len().saturating_sub(1);
+            snake
+                .body
+                .iter()
+                .enumerate()
+                .all(|(i, segment)| segment != $coord || (i == tail && snake.health < 100))
+        })
     };
 }
 
@@ -565,17 +1165,34 @@ macro_rules! can_move_on_tail {
 /// * board - the battlesnake game board
 /// * game_board - the hashmap representation of the game board
 /// * you - your battlesnake
-/// * avoid_snake_heads_option - option to avoid tiles adjacent to the heads of larger snakes
+/// * options - whether to avoid tiles adjacent to the heads of larger snakes
 /// ## Returns:
 /// true if we can safely move onto tile
-pub fn can_move_board(
+pub(crate) fn can_move_board(
     tile: &types::Coord,
     board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
+    game_board: &types::FastMap<types::Coord, types::Flags>,
     you: &types::Battlesnake,
-    avoid_snake_heads_option: Option<bool>,
+    options: &MoveOptions,
+) -> bool {
+    // if tile is adjacent to head, only return true if we can't move anywhere else
+    return tile_is_open(tile, board, game_board)
+        && !(options.avoid_snake_heads && adj_to_bigger_snake(tile, board, you));
+}
+
+/// # tile_is_open
+/// determines if a tile is on the board and nothing stops a snake moving onto it, whoever's heads are next to it
+/// ## Arguments:
+/// * tile - the tile in question
+/// * board - the battlesnake game board
+/// * game_board - the hashmap representation of the game board
+/// ## Returns:
+/// true if the tile is free or the tip of a tail that will move
+pub(crate) fn tile_is_open(
+    tile: &types::Coord,
+    board: &types::Board,
+    game_board: &types::FastMap<types::Coord, types::Flags>,
 ) -> bool {
-    let avoid_snake_heads = avoid_snake_heads_option.unwrap_or(true);
     if tile.x as u8 >= board.width || tile.y as u8 >= board.height || tile.x < 0 || tile.y < 0 {
         return false;
     }
@@ -583,67 +1200,57 @@ pub fn can_move_board(
     // if tile is free: Food | Ally | Empty
     let board_tile = get_board_tile!(game_board, tile.x, tile.y);
     let snakes = &board.snakes;
-    if board_tile_is_free!(board_tile)
-        || (board_tile == types::Flags::SNAKE && can_move_on_tail!(snakes, tile))
-    {
-        // if tile is adjacent to head, only return true if we can't move anywhere else
-        if adj_to_bigger_snake(tile, board, you) && avoid_snake_heads {
-            return false;
-        }
-        return true;
-    }
-    return false;
+    return board_tile_is_free!(board_tile)
+        || (board_tile == types::Flags::SNAKE && can_move_on_tail!(snakes, tile));
 }
 
 /// # get_rand_moves
 /// gets the most favourable moves, shuffling them if they are equally favourable
 /// ## Arguments:
 /// * from_point - the tile we want to move from
-/// * board - the battlesnake game board
-/// * game_board - the hashmap representation of the game board
-/// * you - your battlesnake
-/// * theshold - the connectedness theshold we want of a tile to be considered favourable
-/// * degree_threshold - the degree (number of adj tiles) threshold we want of a tile to be considered favourable
-/// * apply_degree_option - whether or not to apply the degree threshold/sorting
+/// * ctx - the turn's context
+/// * options - the connectedness and degree thresholds we want of a tile to be considered favourable, when nothing
+///   clears them the moves are taken evasively from every tile we can move on
+/// * rng - the random number generator used to shuffle equally favourable moves
 /// ## Returns:
 /// an array of move options
 fn get_rand_moves(
     from_point: &types::Coord,
-    board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
-    you: &types::Battlesnake,
-    threshold: f32,
-    degree_threshold: u8,
-    apply_degree_option: Option<bool>,
+    ctx: &TurnContext,
+    options: &MoveOptions,
+    rng: &mut impl Rng,
 ) -> Vec<&'static str> {
-    let mut safe_moves = get_adj_tiles_connected(
-        from_point,
-        board,
-        game_board,
-        you,
-        threshold,
-        degree_threshold,
-        apply_degree_option,
-        None,
-        None,
-        None,
-    );
-    if safe_moves.len() <= 0 {
-        safe_moves = get_adj_tiles_connected(
-            from_point,
-            board,
-            game_board,
-            you,
-            0.0,
-            0,
-            apply_degree_option,
-            Some(true),
-            Some(false),
-            None,
-        );
+    let mut safe_moves = get_scored_adj_tiles(from_point, ctx, options);
+    if safe_moves.is_empty() {
+        let desperate = options.thresholds(0.0, 0).evasive(true).avoid_snake_heads(false).head_radius(1);
+        safe_moves = get_scored_adj_tiles(from_point, ctx, &desperate);
+    }
+    // the most favourable moves are at the end, the ones that tie with the very best go by their score with the
+    // phase's weights and the ones that still tie are shuffled. the moves that diverged come with their connectivity
+    if let Some(best) = safe_moves.last() {
+        let key = |mv: &ScoredTile| {
+            let connected = mv.connectivity.unwrap_or_else(|| percent_connected(&mv.coord, ctx, options.exclude));
+            return MoveKey::new(&mv.coord, ctx, options).with_connectivity(connected);
+        };
+        let best_key = key(best);
+        let ties = safe_moves
+            .iter()
+            .rev()
+            .take_while(|mv| compare_moves(ctx.profile.move_order, &key(mv), &best_key) == Ordering::Equal)
+            .count();
+        let len = safe_moves.len();
+        let tied = &mut safe_moves[len - ties..];
+        let weights = ctx.weights();
+        let score = |mv: &ScoredTile| {
+            return MoveScore::new(ctx.board, ctx.game_board, ctx.you, &(mv.coord - ctx.you.head)).value(&weights);
+        };
+        tied.sort_by_cached_key(score);
+        let best_score = score(&tied[ties - 1]);
+        let top = tied.iter().rev().take_while(|mv| score(mv) == best_score).count();
+        tied[ties - top..].shuffle(rng);
     }
     let unit_moves: Vec<types::Coord> =
-        safe_moves.into_iter().map(|item| item - you.head).collect();
+        safe_moves.into_iter().map(|item| item.coord - ctx.you.head).collect();
     let move_words = dirs_to_moves(unit_moves);
 
     return move_words;
@@ -661,8 +1268,8 @@ fn dirs_to_moves(dirs: Vec<types::Coord>) -> Vec<&'static str> {
                 }
             },
         );
-        if mv.is_some() {
-            moves.push(mv.unwrap());
+        if let Some(mv) = mv {
+            moves.push(mv);
         }
     }
     return moves;
@@ -671,87 +1278,865 @@ fn dirs_to_moves(dirs: Vec<types::Coord>) -> Vec<&'static str> {
 // move is called on every turn and returns your next move
 // Valid moves are "up", "down", "left", or "right"
 // See https://docs.battlesnake.com/api/example-move for available data
-pub fn get_move(
+// the handler goes through move_in_time with its own deadline, the replay command calls this directly
+pub(crate) fn get_move(
     game: &types::Game,
     turn: &u32,
     board: &types::Board,
     you: &types::Battlesnake,
 ) -> Value {
+    let deadline = Instant::now() + move_budget(game.timeout, latency_buffer());
+    return get_move_until(game, turn, board, you, deadline).response;
+}
+
+/// the part of the pipeline that settled on a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Branch {
+    /// the path out of a box we're inside
+    Escape,
+    /// the first move left of the way out of a lane a longer snake pinned us in
+    Pinned,
+    /// the first step of the A* path
+    AStar,
+    /// the best of the moves that keep the most space connected
+    Survival,
+    /// the lookahead search found the heuristics' move loses and this one doesn't
+    Lookahead,
+    /// the best move of a duel the endgame solver proved isn't lost
+    Endgame,
+    /// the safety check replaced the move
+    Validator,
+    /// the pipeline ran out of time or gave up on the frame and the handler answered for it, see `DecisionError`
+    Fallback,
+}
+
+impl Branch {
+    pub const ALL: [Branch; 8] = [
+        Branch::Escape,
+        Branch::Pinned,
+        Branch::AStar,
+        Branch::Survival,
+        Branch::Lookahead,
+        Branch::Endgame,
+        Branch::Validator,
+        Branch::Fallback,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Branch::Escape => "escape",
+            Branch::Pinned => "pinned",
+            Branch::AStar => "a_star",
+            Branch::Survival => "survival",
+            Branch::Lookahead => "lookahead",
+            Branch::Endgame => "endgame",
+            Branch::Validator => "validator",
+            Branch::Fallback => "fallback",
+        };
+    }
+}
+
+/// a move and how the pipeline came to it
+#[derive(Debug, Clone)]
+pub struct Decision {
+    pub response: Value,
+    pub trace: DecisionTrace,
+}
+
+impl Decision {
+    /// # new
+    /// answers with the move a trace settled on
+    pub(crate) fn new(trace: DecisionTrace) -> Self {
+        return Decision {
+            response: json!({ "move": trace.chosen }),
+            trace,
+        };
+    }
+
+    /// # fallback
+    /// answers a request with the fallback move, for when the pipeline didn't
+    pub(crate) fn fallback(state: &types::GameState, fallback: &str) -> Self {
+        let mut trace = DecisionTrace::new(&state.game.id, state.turn);
+        trace.branch = Branch::Fallback;
+        trace.chosen = fallback.to_string();
+        return Decision::new(trace);
+    }
+
+    /// # failed
+    /// answers with the fallback move for a pipeline that gave up on the frame, the error is kept in the trace
+    pub(crate) fn failed(game_id: &str, turn: u32, fallback: &str, error: DecisionError) -> Self {
+        let mut trace = DecisionTrace::new(game_id, turn);
+        trace.branch = Branch::Fallback;
+        trace.chosen = fallback.to_string();
+        trace.error = Some(error);
+        return Decision::new(trace);
+    }
+}
+
+/// why the move pipeline gave up on a frame, the handler answers it with the fallback move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, thiserror::Error)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DecisionError {
+    /// the frame has no snake of ours to play, see `intact`
+    #[error("no snake of ours to play")]
+    NoSnake,
+    /// every tile is taken, the share of the free tiles a move stays connected to has nothing to be a share of
+    #[error("no free tiles to measure connectivity against")]
+    NoFreeTiles,
+    /// the searches ran out of tiles before any of them took a first step
+    #[error("every search ran out of tiles without a first move")]
+    EmptyFrontier,
+    /// a branch settled on a path that doesn't start with a move from our head
+    #[error("the {} branch settled on a path without a first move", branch.name())]
+    EmptyPath { branch: Branch },
+}
+
+/// how cautiously the move pipeline plays, every personality the server hosts has its own
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct StrategyProfile {
+    /// the share of the free tiles a tile has to stay connected to for a path to go through it
+    pub connection_threshold: f32,
+    /// the free neighbours a tile needs for a path to go through it
+    pub degree_threshold: u8,
+    /// we look for a way out once we can reach less than this share of the free tiles
+    pub box_threshold: f32,
+    /// the tiles past our length a box has to hold for us to keep playing in it, see `cramped`
+    pub box_margin: u16,
+    /// what a step onto a hazard costs A*, every other step costs 1
+    pub hazard_cost: u16,
+    /// the share of the game's timeout the endgame solver gets
+    pub endgame_share: f32,
+    /// the deepest the endgame solver's iterative deepening goes
+    pub endgame_depth: u8,
+    /// whether the divergence analysis fills the candidates' regions side by side on boards of `PARALLEL_FILL_TILES`
+    pub parallel_fills: bool,
+    /// how long we want to be next to the longest opponent, and how hungry that leaves us
+    pub length: LengthPolicy,
+    /// the weights moves are scored with in each phase of the game
+    pub phase_weights: PhaseWeights,
+    /// when a path across the hazards is worth the damage
+    pub hazards: HazardPolicy,
+    /// the turns the lookahead search looks ahead to check the heuristics' move doesn't lose, 0 leaves the search out
+    pub lookahead_depth: u8,
+    /// the most turns the lookahead search goes past its depth while snake heads are close enough to meet, 0 stops it
+    /// at its depth
+    pub quiescence_plies: u8,
+    /// how the lookahead search expects the opponents to reply
+    pub search: SearchMode,
+    /// how simulation models the royale shrink, whose side the engine picks at random
+    pub shrink: types::ShrinkMode,
+    /// the criteria moves are ordered by, the first to tell two moves apart settles it
+    pub move_order: &'static [MoveCriterion],
+}
+
+impl StrategyProfile {
+    pub const DEFAULT: StrategyProfile = StrategyProfile {
+        connection_threshold: 0.5,
+        degree_threshold: 2,
+        box_threshold: 0.3,
+        box_margin: 14,
+        hazard_cost: 16,
+        endgame_share: 0.25,
+        endgame_depth: endgame::MAX_DEPTH,
+        parallel_fills: false,
+        length: LengthPolicy::DEFAULT,
+        phase_weights: PhaseWeights::DEFAULT,
+        hazards: HazardPolicy::DEFAULT,
+        lookahead_depth: 0,
+        quiescence_plies: 2,
+        search: SearchMode::Paranoid,
+        shrink: types::ShrinkMode::Pessimistic,
+        move_order: MoveCriterion::DEFAULT_ORDER,
+    };
+
+    /// # searcher
+    /// the lookahead search the profile plays with, going up to `quiescence_plies` past its depth while heads are about
+    /// to meet and ending every turn in a chance node over the food the game's rules spawn. paranoid search tries the
+    /// opponents' replies in the order a `GreedyPolicy` expects them, expectimax weighs them with a `SoftmaxPolicy`,
+    /// which never rules out a plausible one
+    /// ## Arguments:
+    /// * rules - the game's rules
+    pub fn searcher(&self, rules: types::Ruleset) -> AlphaBeta {
+        let mut search = AlphaBeta::new(self.phase_weights.mid, rules, true)
+            .with_quiescence(self.quiescence_plies, minimax::QUIESCENCE_BUDGET);
+        if rules.food_spawn_chance > 0 || rules.minimum_food > 0 {
+            search = search.with_food_spawn(minimax::FOOD_SPAWN_SAMPLES, minimax::FOOD_SPAWN_SEED);
+        }
+        return match self.search {
+            SearchMode::Paranoid => search.with_opponent_policy(Box::new(GreedyPolicy::default())),
+            SearchMode::Expectimax => search.with_expectimax(Box::new(SoftmaxPolicy::default())),
+        };
+    }
+
+    /// # paced
+    /// the profile adjusted to how hard the opponents are thinking. slow opponents leave the endgame solver more of
+    /// the timeout and `pace::SLOW_EXTRA_DEPTH` more depth, fast ones may be searching deeper than we are so their
+    /// replies are assumed to be the worst for us
+    /// ## Arguments:
+    /// * pace - the opponents' pace, see `pace::PaceTracker`
+    pub fn paced(&self, pace: Pace) -> StrategyProfile {
+        // a profile already giving the solver more than the cap keeps its share
+        let slow_share = (self.endgame_share * pace::SLOW_SHARE_FACTOR).min(pace::MAX_SLOW_SHARE);
+        return match pace {
+            Pace::Slow => StrategyProfile {
+                endgame_share: slow_share.max(self.endgame_share),
+                endgame_depth: self.endgame_depth.saturating_add(pace::SLOW_EXTRA_DEPTH),
+                ..*self
+            },
+            Pace::Fast => StrategyProfile {
+                search: SearchMode::Paranoid,
+                ..*self
+            },
+            Pace::Unread => *self,
+        };
+    }
+}
+
+impl Default for StrategyProfile {
+    fn default() -> Self {
+        return StrategyProfile::DEFAULT;
+    }
+}
+
+/// how hungry we are, from how far we are off the length we want to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Hunger {
+    /// far enough behind the longest opponent that we go for any food, even food a snake our length is as close to
+    Urgent,
+    /// we go for the food no snake our length is as close to
+    Normal,
+    /// long enough that more length only chokes our own space, we only eat to keep from starving
+    Full,
+}
+
+/// the length we play for: a little longer than the longest opponent so head to heads go our way, but not so long
+/// we run out of room
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LengthPolicy {
+    /// how much longer than the longest opponent we want to be
+    pub lead: u32,
+    /// we're urgently hungry once we're this much shorter than the length we want
+    pub urgent_deficit: u32,
+    /// we're full once we're this much longer than the length we want
+    pub full_surplus: u32,
+    /// the health at or below which we eat even when we're full
+    pub starving_health: u8,
+}
+
+impl LengthPolicy {
+    /// behind the longest opponent by 2 or more is urgent, ahead of it by 3 or more is full
+    pub const DEFAULT: LengthPolicy = LengthPolicy {
+        lead: 1,
+        urgent_deficit: 3,
+        full_surplus: 2,
+        starving_health: 30,
+    };
+
+    /// # length_target
+    /// the length we want to be
+    /// ## Arguments:
+    /// * board - the battlesnake game board
+    /// * you - your battlesnake
+    /// ## Returns:
+    /// the longest opponent's length and our lead, our own length when we have the board to ourselves
+    pub fn length_target(&self, board: &types::Board, you: &types::Battlesnake) -> u32 {
+        let longest = board.snakes.iter().filter(|snake| snake.id != you.id).map(|snake| snake.length).max();
+        return match longest {
+            Some(longest) => longest + self.lead,
+            None => you.length,
+        };
+    }
+
+    /// # hunger
+    /// how hungry we are for the length we want to be
+    /// ## Arguments:
+    /// * board - the battlesnake game board
+    /// * you - your battlesnake
+    pub fn hunger(&self, board: &types::Board, you: &types::Battlesnake) -> Hunger {
+        let surplus = you.length as i64 - self.length_target(board, you) as i64;
+        if surplus <= -(self.urgent_deficit as i64) {
+            return Hunger::Urgent;
+        }
+        if surplus >= self.full_surplus as i64 && you.health > self.starving_health {
+            return Hunger::Full;
+        }
+        return Hunger::Normal;
+    }
+}
+
+/// when we take the short way to food across the hazards instead of the long way around them
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct HazardPolicy {
+    /// the health a turn saved is worth, the turns a crossing saves have to make up for its extra damage
+    pub turn_worth: u16,
+    /// the health we want left on the food after crossing
+    pub reserve: u8,
+}
+
+impl HazardPolicy {
+    /// six turns saved pay for two tiles at the engine's default damage
+    pub const DEFAULT: HazardPolicy = HazardPolicy {
+        turn_worth: 5,
+        reserve: 15,
+    };
+}
+
+/// the personalities hosted next to the default one, by the path they're mounted at
+pub(crate) const PROFILES: [(&str, StrategyProfile); 2] = [
+    (
+        "aggressive",
+        StrategyProfile {
+            connection_threshold: 0.2,
+            degree_threshold: 1,
+            box_threshold: 0.2,
+            // plays for a bigger lead and fights for food a length sooner
+            length: LengthPolicy {
+                lead: 2,
+                urgent_deficit: 2,
+                ..LengthPolicy::DEFAULT
+            },
+            ..StrategyProfile::DEFAULT
+        },
+    ),
+    (
+        "defensive",
+        StrategyProfile {
+            connection_threshold: 0.8,
+            degree_threshold: 3,
+            box_threshold: 0.4,
+            ..StrategyProfile::DEFAULT
+        },
+    ),
+];
+
+/// # profile
+/// looks a profile up by name, `default` or one of `PROFILES`
+pub(crate) fn profile(name: &str) -> Option<StrategyProfile> {
+    if name == "default" {
+        return Some(StrategyProfile::default());
+    }
+    return PROFILES.iter().find(|(route, _)| *route == name).map(|(_, profile)| *profile);
+}
+
+/// # path_damage
+/// the hazard damage we'd take following a path, each tile's stacks as deep as they'll be when we step onto it
+/// ## Arguments:
+/// * path - the path, our head left out
+/// * ctx - the turn's context
+fn path_damage(path: &[types::Coord], ctx: &TurnContext) -> i32 {
+    return path
+        .iter()
+        .enumerate()
+        .map(|(step, tile)| ctx.hazards.damage_on(tile, step as u16 + 1, ctx.rules.hazard_damage) as i32)
+        .sum();
+}
+
+/// # weigh_crossing
+/// chooses between the path A* found around the hazards and the one with the fewest turns across them. crossing is
+/// only worth it when the turns it saves, at the profile's `HazardPolicy::turn_worth` each, pay for the extra damage
+/// and we'd still have `HazardPolicy::reserve` health on the food
+/// ## Arguments:
+/// * ctx - the turn's context
+/// * detour - what A* found with hazard steps at the profile's flat cost, an empty path if there isn't one
+/// * shortcut - what A* found with hazard steps at the cost of any other, an empty path if there isn't one
+/// * trace - where the tradeoff is recorded when there was one to make
+/// ## Returns:
+/// the path to follow
+fn weigh_crossing(
+    ctx: &TurnContext,
+    detour: graph::PathResult,
+    shortcut: graph::PathResult,
+    trace: &mut DecisionTrace,
+) -> graph::PathResult {
+    let damage = path_damage(&shortcut.path, ctx);
+    let extra_damage = damage - path_damage(&detour.path, ctx);
+    if shortcut.path.is_empty() || extra_damage <= 0 {
+        return detour;
+    }
+    let policy = ctx.profile.hazards;
+    // no way around is as long a way around as there is
+    let turns_saved = if detour.path.is_empty() {
+        i32::MAX
+    } else {
+        detour.path.len() as i32 - shortcut.path.len() as i32
+    };
+    // the health A* worked out we'd reach the food with, the steps and the hazards taken off
+    let affordable = shortcut.health_at_goal > policy.reserve;
+    let crossed = turns_saved > 0 && turns_saved.saturating_mul(policy.turn_worth as i32) >= extra_damage && affordable;
+    trace.crossing = Some(CrossingTrace {
+        turns_saved,
+        damage,
+        crossed,
+    });
+    if crossed {
+        return shortcut;
+    }
+    return detour;
+}
+
+/// # get_move_until
+/// the full move pipeline with the default profile
+pub(crate) fn get_move_until(
+    game: &types::Game,
+    turn: &u32,
+    board: &types::Board,
+    you: &types::Battlesnake,
+    deadline: Instant,
+) -> Decision {
+    return get_move_profiled(game, turn, board, you, &StrategyProfile::default(), deadline);
+}
+
+/// # get_move_profiled
+/// the full move pipeline against opponents we don't know anything about, a frame it gives up on is answered with
+/// `fallback_move`
+pub(crate) fn get_move_profiled(
+    game: &types::Game,
+    turn: &u32,
+    board: &types::Board,
+    you: &types::Battlesnake,
+    profile: &StrategyProfile,
+    deadline: Instant,
+) -> Decision {
+    let (stances, announced) = (Stances::new(), Announced::new());
+    return get_move_against(game, turn, board, you, profile, &stances, &announced, None, &[], None, deadline)
+        .unwrap_or_else(|error| {
+            warn!("MOVE {}: {}, answering with the fallback\n{}", turn, error, board.render());
+            return Decision::failed(&game.id, *turn, fallback_move(board, you), error);
+        });
+}
+
+/// # cramped
+/// whether a box is too small to keep playing in. our tail only starts opening space behind us once the body has
+/// moved through, so the box has to hold us for the turns until the tile under our head clears, and our slack past
+/// that, the room to turn around in while it does, has to come to the profile's margin. a short snake in a pocket it
+/// fits in plays on as normal
+/// ## Arguments:
+/// * region - the tiles we can reach in the box, see `graph::inside_box`
+/// * ctx - the turn we're playing
+/// ## Returns:
+/// true if it's worth planning the way out
+pub(crate) fn cramped(region: u16, ctx: &TurnContext) -> bool {
+    let timed = types::TimedOccupancy::new(ctx.board, &ctx.you.id);
+    let slack = region as i32 - timed.clears_in(&ctx.you.head) as i32;
+    return slack < ctx.profile.box_margin as i32;
+}
+
+/// # get_move_against
+/// the full move pipeline, the parts that search for as long as they're allowed stop at the deadline
+/// ## Arguments:
+/// * game - the game the move is for
+/// * turn - the turn number
+/// * board - the battlesnake game board
+/// * you - your battlesnake
+/// * profile - how cautiously to play
+/// * stances - how to play around the opponents we recognised at the start
+/// * announced - the moves the opponents we can read announced this turn
+/// * phase - the phase the game's memory settled on, none to go by what the position looks like on its own
+/// * plan - the rest of the way out of a pin we committed to on the turn before, empty for none
+/// * alongside - the longer snake that was running alongside us on the turn before, see `running_alongside`
+/// * deadline - when the answer has to be ready
+/// ## Returns:
+/// the move response and the trace of how it was chosen, or why the pipeline gave up on the frame
+pub(crate) fn get_move_against(
+    game: &types::Game,
+    turn: &u32,
+    board: &types::Board,
+    you: &types::Battlesnake,
+    profile: &StrategyProfile,
+    stances: &Stances,
+    announced: &Announced,
+    phase: Option<Phase>,
+    plan: &[Coord],
+    alongside: Option<&Alongside>,
+    deadline: Instant,
+) -> Result<Decision, DecisionError> {
+    let turn_span = tracing::info_span!("turn", game = %game.id, turn = *turn);
+    let _turn = turn_span.enter();
+    let rules = types::Ruleset::from(game).with_shrink(profile.shrink);
+    // on a maze the hazards are walls, every step from here on sees them as such
+    let walled = board.with_rules(&rules);
+    let board = walled.as_ref();
+    if !intact(board, you) {
+        return Err(DecisionError::NoSnake);
+    }
     let game_board = board.to_game_board();
+    // the heuristics play around the opponents' habits, the endgame and the validator stick to the real board
+    let mut perceived = opponents::perceive(board, you, stances, announced);
+    // an opponent starving whatever it does is no threat, and the one food that could still save it is ours to take
+    let outlooks = starvation::outlooks(board, you, &rules);
+    let written_off = starvation::write_off(&mut perceived, you, &outlooks);
+    let mut ctx = TurnContext::new(&perceived, &game_board, you, *profile, rules);
+    ctx.denials = outlooks.values().filter_map(Outlook::denial).collect();
+    #[cfg(feature = "verify")]
+    crate::verify::check_open_tiles(&ctx);
+    if ctx.free_tiles == 0 {
+        return Err(DecisionError::NoFreeTiles);
+    }
+    ctx.phase = phase.unwrap_or_else(|| phase::classify(board, *turn, ctx.free_tiles));
+    let alongside = running_alongside(ctx.board, you, alongside);
+    ctx.alongside = alongside.as_ref().and_then(|alongside| alongside_moves(&ctx, alongside));
 
     let mut safe_moves: Vec<&str> = vec![];
+    let mut trace = DecisionTrace::new(&game.id, *turn);
+    trace.game_phase = ctx.phase;
+    trace.score_candidates(board, &game_board, you, &ctx.weights());
+    // the moves A* may start with, looking a move further ahead for longer heads than the fallbacks do
+    let options = MoveOptions::default()
+        .thresholds(ctx.profile.connection_threshold, ctx.profile.degree_threshold)
+        .head_radius(2);
+    let connectivity = tracing::debug_span!("connectivity", candidates = Empty, tiles_filled = Empty).entered();
+    let scored = get_scored_adj_tiles(&you.head, &ctx, &options);
+    connectivity.record("candidates", scored.len());
+    connectivity.record("tiles_filled", ctx.connectivity.visits());
+    connectivity.exit();
+    trace.score_connectivity(you.head, &scored);
+    trace.sandwiched = ctx.lane.is_some();
+    trace.alongside = alongside;
+    trace.length_target = ctx.profile.length.length_target(board, you);
+    trace.hunger = ctx.hunger;
+    trace.written_off = written_off;
+    trace.denials = ctx.denials.clone();
+    let mut rng = seed::rng(&game.id, *turn);
     let game_mode = game.ruleset.get("name").unwrap_or(&json!("")).to_string();
 
     // check and see if we're trapped in a box unless we're in constrictor mode
-    if game_mode != "\"constrictor\"" && graph::inside_box(you, board, &game_board, 0.3) {
+    let phase = Instant::now();
+    let boxed = graph::inside_box(&ctx, ctx.profile.box_threshold).is_some_and(|region| cramped(region, &ctx));
+    if game_mode != "\"constrictor\"" && boxed {
         // find square to escape from
-        let escape_tile_res = graph::find_key_hole(board, &game_board, you);
-        if escape_tile_res.is_some() {
-            let escape_tile = escape_tile_res.unwrap();
-            let path = graph::dfs_long(&escape_tile, board, &game_board, you, 0.0, 0);
-            let next_move = path.first();
+        let escape_tile_res = graph::find_key_hole(&ctx);
+        if let Some(escape_tile) = escape_tile_res {
+            let path = graph::dfs_long(&escape_tile, &ctx, 0.0, 0);
+
+            //because we're asking it to move to an occupied tile it will sometimes suggest an occupied tile as the next move
+            if let Some(next_move) = path.first() {
+                if ctx.can_move(next_move, &MoveOptions::default().avoid_snake_heads(false)) {
+                    let unit_move = *next_move - you.head;
+                    safe_moves.append(&mut dirs_to_moves(vec![unit_move]));
+                    trace.branch = Branch::Escape;
+                }
+            }
+        }
+    }
+    trace.phase("escape", phase);
+    // pinned in a lane by a longer snake, keep to the way out we committed to or look for one
+    let phase = Instant::now();
+    if safe_moves.is_empty() {
+        let anywhere = MoveOptions::default().avoid_snake_heads(false);
+        let keeping_to_plan = plan
+            .first()
+            .is_some_and(|dir| can_move_board(&(you.head + *dir), board, &game_board, you, &anywhere));
+        let way_out = if keeping_to_plan {
+            Some(plan.to_vec())
+        } else if ctx.lane.is_some() {
+            escape::solve(board, &you.id, *turn, &ctx.rules, escape::ESCAPE_DEPTH)
+        } else {
+            None
+        };
+        if let Some(way_out) = way_out {
+            let Some(first) = way_out.first().and_then(|dir| dirs_to_moves(vec![*dir]).pop()) else {
+                return Err(DecisionError::EmptyPath { branch: Branch::Pinned });
+            };
+            safe_moves.push(first);
+            trace.escape = way_out;
+            trace.branch = Branch::Pinned;
+        }
+    }
+    trace.phase("pinned", phase);
+    let phase = Instant::now();
+    if safe_moves.is_empty() {
+        // otherwise look for food or other stuff
+        // be less hungry, try to control the center if we have high health and are sufficiently long
+        let found = if ctx.hunger == Hunger::Full && ctx.denials.is_empty() {
+            graph::PathResult::default()
+        } else {
+            let detour = graph::a_star(&ctx, &options, ctx.profile.hazard_cost);
+            if board.hazards.is_empty() {
+                detour
+            } else {
+                // the path with the fewest turns, whatever hazards it crosses
+                let shortcut = graph::a_star(&ctx, &options, 1);
+                weigh_crossing(&ctx, detour, shortcut, &mut trace)
+            }
+        };
+        let path = &found.path;
+
+        if let Some(goal) = path.last() {
+            trace.a_star = Some(PathTrace {
+                goal: *goal,
+                length: path.len(),
+                cost: found.cost,
+                health_at_goal: found.health_at_goal,
+                nodes_expanded: found.nodes_expanded,
+            });
+        }
+        if let Some(first) = path.first() {
+            let Some(dir) = dirs_to_moves(vec![*first - you.head]).pop() else {
+                return Err(DecisionError::EmptyPath { branch: Branch::AStar });
+            };
+            safe_moves.push(dir);
+            trace.branch = Branch::AStar;
+        } else {
+            let mut rand_moves = get_rand_moves(&you.head, &ctx, &options.apply_degree(false), &mut rng);
+            safe_moves.append(&mut rand_moves);
+        }
+    }
+
+    trace.phase("a_star", phase);
+
+    let Some(&(mut chosen)) = safe_moves.last() else {
+        return Err(DecisionError::EmptyFrontier);
+    };
+    let rules = ctx.rules;
 
-            //because we're asking it to move to an occupied tile it will sometimes suggest an occupied tile as the next move
-            if next_move.is_some()
-                && can_move_board(next_move.unwrap(), board, &game_board, you, Some(false))
-            {
-                let unit_move = *next_move.unwrap() - you.head;
-                safe_moves.append(&mut dirs_to_moves(vec![unit_move]));
+    // the lookahead search second-guesses the heuristics with half the time left: a move it finds loses is swapped for
+    // the best one it finds that doesn't, our root moves searched side by side. a search the deadline cut short doesn't
+    // get a say
+    let phase = Instant::now();
+    if ctx.profile.lookahead_depth > 0 {
+        let search_by = phase + deadline.saturating_duration_since(phase) / 2;
+        let depth = ctx.profile.lookahead_depth;
+        let searcher = || ctx.profile.searcher(rules).with_deadline(search_by);
+        let lost = |found: &SearchResult| found.complete && found.score < -WIN_SCORE / 2;
+        let heuristic =
+            searcher().with_first_move(types::DIRECTIONS[chosen]).search_parallel(board, &you.id, *turn, depth);
+        if lost(&heuristic) {
+            let found = searcher().search_parallel(board, &you.id, *turn, depth);
+            let searched = found.best_move.and_then(|dir| dirs_to_moves(vec![dir]).pop());
+            if let Some(searched) = searched.filter(|_| found.complete && !lost(&found)) {
+                info!("MOVE {}: lookahead found {} loses, playing {}", turn, chosen, searched);
+                chosen = searched;
+                trace.branch = Branch::Lookahead;
             }
         }
     }
-    if safe_moves.len() <= 0 {
-        // otherwise look for food or other stuff
-        let tile_connection_threshold = 0.5;
-        let degree_threshold: u8 = 2;
-
-        // be less hungry, try to control the center if we have high health and are sufficiently long
-        let path: Vec<types::Coord> = graph::a_star(
-            board,
-            &game_board,
-            &you,
-            tile_connection_threshold,
-            degree_threshold,
-        );
+    trace.phase("lookahead", phase);
 
-        if path.len() > 0 {
-            let dir_vector = path[0] - you.head;
-            let dir = types::DIRECTIONS.into_iter().find_map(|(key, &val)| {
-                if val == dir_vector {
-                    Some(key)
-                } else {
-                    None
+    // a duel squeezed into a small region can be solved outright, a proven loss is left to the heuristics in case the
+    // other snake slips up
+    let phase = Instant::now();
+    if endgame::is_endgame(board, &you.id, endgame::ENDGAME_TILES) {
+        let endgame_budget = Duration::from_millis(game.timeout as u64).mul_f32(ctx.profile.endgame_share);
+        let solve_by = deadline.min(Instant::now() + endgame_budget);
+        let max_depth = ctx.profile.endgame_depth;
+        if let Some(solution) = endgame::solve(board, &you.id, *turn, &rules, max_depth, solve_by) {
+            if solution.outcome != endgame::Outcome::Loss {
+                if let Some(solved) = dirs_to_moves(vec![solution.best_move]).first() {
+                    info!(
+                        "MOVE {}: endgame solved as {:?} in {} turns",
+                        turn, solution.outcome, solution.depth
+                    );
+                    chosen = *solved;
+                    trace.branch = Branch::Endgame;
                 }
-            });
-            if dir.is_some() {
-                safe_moves.push(dir.unwrap());
             }
-        } else {
-            let mut rand_moves = get_rand_moves(
-                &you.head,
-                board,
-                &game_board,
-                you,
-                tile_connection_threshold,
-                degree_threshold,
-                Some(false),
-            );
-            safe_moves.append(&mut rand_moves);
         }
     }
+    trace.phase("endgame", phase);
 
-    let chosen = safe_moves.last().unwrap_or(&"up");
+    // whatever produced the move, make sure it doesn't walk into a death we could have avoided
+    let phase = Instant::now();
+    let select = tracing::debug_span!("select", candidates = safe_moves.len(), chosen = chosen).entered();
+    let chosen_dir = types::DIRECTIONS[chosen];
+    let validated_dir = safety::validate_move(board, &you.id, &chosen_dir, turn + 1, &rules);
+    if validated_dir != chosen_dir {
+        if let Some(validated) = dirs_to_moves(vec![validated_dir]).first() {
+            info!(
+                "MOVE {}: validator overrode {} with {}",
+                turn, chosen, validated
+            );
+            trace.safety_override = Some(chosen.to_string());
+            chosen = *validated;
+            trace.branch = Branch::Validator;
+        }
+    }
+    select.exit();
+    trace.phase("validator", phase);
 
     // TODO: Step 4 - Move towards food instead of random, to regain health and survive longer
     // let food = &board.food;
 
     info!("MOVE {}: {} len:{:?}", turn, chosen, safe_moves);
-    return json!({ "move": chosen });
+    // the endgame or the validator went another way, there's no way out to keep to
+    if trace.branch != Branch::Pinned {
+        trace.escape.clear();
+    }
+    trace.chosen = chosen.to_string();
+    trace.spans = spans::closed_in(&turn_span);
+    return Ok(Decision::new(trace));
+}
+
+/// # intact
+/// whether a frame has a snake of ours to play: on the board, alive, and with its head at the front of its body and
+/// every part of it inside the board's bounds. the engine shouldn't send anything else, but the lenient parsing lets
+/// it through, the searches all start from a body that's there and the tile sets they fill panic on a tile off the
+/// board. a frame without one is answered with `fallback_move`
+/// ## Arguments:
+/// * board - the battlesnake game board
+/// * you - your battlesnake
+/// ## Returns:
+/// true if the move pipeline can play the frame
+pub(crate) fn intact(board: &types::Board, you: &types::Battlesnake) -> bool {
+    return you.health > 0
+        && you.body.first() == Some(&you.head)
+        && [&you.head].into_iter().chain(&you.body).all(|tile| board.in_bounds(tile))
+        && board.snakes.iter().any(|snake| snake.id == you.id);
+}
+
+/// # latency_buffer
+/// how much of the timeout is kept back for the response to travel, `LATENCY_BUFFER_ENV` overrides the default
+pub(crate) fn latency_buffer() -> Duration {
+    return env::var(LATENCY_BUFFER_ENV)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .map_or(DEFAULT_LATENCY_BUFFER, Duration::from_millis);
+}
+
+/// # move_budget
+/// the time the pipeline gets to compute a move
+/// ## Arguments:
+/// * timeout - the game's timeout in milliseconds
+/// * buffer - the time kept back for the network
+pub(crate) fn move_budget(timeout: u32, buffer: Duration) -> Duration {
+    return Duration::from_millis(timeout as u64).saturating_sub(buffer);
+}
+
+/// # fallback_move
+/// the move to answer with when the pipeline can't: one that doesn't run into a wall or a body, away from the heads of
+/// snakes as long as us if it can be and otherwise the one with the most free tiles around it. it reads the board as
+/// sent and allocates nothing, so it's ready before the real pipeline starts. our own body counts even when the board
+/// left us out. a board where every move is fatal is answered with up
+pub(crate) fn fallback_move(board: &types::Board, you: &types::Battlesnake) -> &'static str {
+    let free = |tile: &Coord| {
+        return board.in_bounds(tile)
+            && board.snakes.iter().chain([you]).all(|snake| {
+                // a tail moves out of the way unless the snake has just eaten
+                let tail = snake.body.len().saturating_sub(1);
+                return snake
+                    .body
+                    .iter()
+                    .enumerate()
+                    .all(|(i, segment)| segment != tile || (i == tail && snake.health < 100));
+            });
+    };
+    let mut best: Option<(&'static str, (bool, usize))> = None;
+    for mv in ["up", "right", "down", "left"] {
+        let tile = you.head + types::DIRECTIONS[mv];
+        if !free(&tile) {
+            continue;
+        }
+        let room = types::DIRECTIONS.values().filter(|dir| free(&(tile + **dir))).count();
+        let rank = (!adj_to_bigger_snake(&tile, board, you), room);
+        if best.is_none_or(|(_, best)| rank > best) {
+            best = Some((mv, rank));
+        }
+    }
+    return best.map_or("up", |(mv, _)| mv);
+}
+
+/// what the move handler answered and how it got there
+#[derive(Debug)]
+pub(crate) struct Answer {
+    pub response: Value,
+    /// the branch is `Branch::Fallback` when the pipeline missed the deadline or couldn't be queued
+    pub trace: DecisionTrace,
+    pub elapsed: Duration,
+}
+
+/// # move_in_time
+/// runs the move pipeline on the compute pool and answers with its move if it's ready by the deadline, otherwise with
+/// the fallback move, which also answers a pipeline that panics or gives up with an error. a worker that overruns is
+/// left to finish on its own, its answer is dropped. waiting doesn't block, so the server's workers stay free for the
+/// other games while the pool is busy
+/// ## Arguments:
+/// * state - the move request
+/// * fallback - the move to answer with when the pipeline can't, worked out before anything else
+/// * store - the memory of the games in progress, the request and our answer are recorded in it
+/// * route - the personality answering, its memory of the game is kept apart from the others'
+/// * buffer - the time kept back from the timeout for the network
+/// * pool - where the pipeline runs, the fallback answers straight away when it's saturated
+/// * strategy - the pipeline, given the request and its deadline, an error is logged with the board it gave up on
+/// ## Returns:
+/// the answer to send
+pub(crate) async fn move_in_time<F>(
+    state: Arc<types::GameState>,
+    fallback: &'static str,
+    store: &GameStore,
+    route: &str,
+    buffer: Duration,
+    pool: &ComputePool,
+    strategy: F,
+) -> Answer
+where
+    F: FnOnce(&types::GameState, Instant) -> Result<Decision, DecisionError> + Send + 'static,
+{
+    let start = Instant::now();
+    let deadline = start + move_budget(state.game.timeout, buffer);
+    let worker_state = Arc::clone(&state);
+    let (sender, receiver) = oneshot::channel();
+    let queued = pool.try_run(move || {
+        // a move that waited in the queue past its deadline isn't worth starting
+        if Instant::now() < deadline {
+            // a panicking pipeline is answered with the fallback straight away instead of being waited on
+            let decision = panic::catch_unwind(AssertUnwindSafe(|| strategy(&worker_state, deadline)));
+            // the receiver is gone if we already answered with the fallback
+            let _ = sender.send(decision.ok());
+        }
+    });
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    let decision = if !queued {
+        warn!("MOVE {}: the compute pool is saturated, falling back to {}", state.turn, fallback);
+        metrics::DEADLINE_DEGRADATIONS.add("saturated", 1);
+        None
+    } else {
+        match rocket::tokio::time::timeout(remaining, receiver).await {
+            Ok(Ok(Some(Ok(decision)))) => Some(decision),
+            Ok(Ok(Some(Err(failure)))) => {
+                error!(
+                    "MOVE {}: the pipeline gave up in game {}: {}, falling back to {}\n{}",
+                    state.turn,
+                    state.game.id,
+                    failure,
+                    fallback,
+                    state.board.render()
+                );
+                Some(Decision::failed(&state.game.id, state.turn, fallback, failure))
+            }
+            Ok(Ok(None)) => {
+                error!(
+                    "MOVE {}: the pipeline panicked in game {}, falling back to {}",
+                    state.turn, state.game.id, fallback
+                );
+                metrics::PIPELINE_PANICS.add("pipeline", 1);
+                None
+            }
+            // the worker dropped the move, it started too late
+            Ok(Err(_)) | Err(_) => {
+                warn!("MOVE {}: out of time, falling back to {}", state.turn, fallback);
+                metrics::DEADLINE_DEGRADATIONS.add("pipeline", 1);
+                None
+            }
+        }
+    };
+    let decision = decision.unwrap_or_else(|| Decision::fallback(&state, fallback));
+    let shouts = store.record(route, &state, decision.response["move"].as_str().unwrap_or(fallback));
+    let elapsed = start.elapsed();
+    let mut trace = decision.trace;
+    trace.shouts = shouts;
+    trace.elapsed_ms = elapsed.as_millis() as u64;
+    return Answer {
+        response: decision.response,
+        trace,
+        elapsed,
+    };
 }
 
 #[cfg(test)]
-mod tests {
+pub mod tests {
     use crate::types::{self, Coord};
+    use std::collections::{HashMap, HashSet};
 
     use super::*;
 
@@ -837,7 +2222,7 @@ mod tests {
         let game_board = board.to_game_board();
         let point = Coord { x: 5, y: 11 };
 
-        assert!(!can_move_board(&point, &board, &game_board, &you, None));
+        assert!(!can_move_board(&point, &board, &game_board, &you, &MoveOptions::default()));
     }
 
     #[test]
@@ -984,17 +2369,37 @@ mod tests {
             &board,
             &game_board,
             &you,
-            None
+            &MoveOptions::default()
         ));
         assert!(can_move_board(
             &Coord { x: 4, y: 6 },
             &board,
             &game_board,
             &you,
-            None
+            &MoveOptions::default()
         ));
     }
 
+    #[test]
+    fn a_stacked_tail_stays_put() {
+        // the first turn: the tail still has a segment stacked on it though the snake hasn't eaten
+        let board: types::Board = serde_json::from_value(json!({
+            "width": 7, "height": 7, "food": [], "hazards": [],
+            "snakes": [
+                { "id": "me", "name": "me", "health": 99, "head": { "x": 1, "y": 2 }, "length": 3,
+                  "body": [{ "x": 1, "y": 2 }, { "x": 1, "y": 1 }, { "x": 1, "y": 1 }] },
+                { "id": "them", "name": "them", "health": 99, "head": { "x": 5, "y": 5 }, "length": 3,
+                  "body": [{ "x": 5, "y": 5 }, { "x": 5, "y": 4 }, { "x": 5, "y": 3 }] },
+            ],
+        }))
+        .unwrap();
+        let game_board = board.to_game_board();
+        let you = &board.snakes[0];
+        assert!(!tile_is_open(&Coord { x: 1, y: 1 }, &board, &game_board));
+        assert!(tile_is_open(&Coord { x: 5, y: 3 }, &board, &game_board));
+        assert!(can_move_board(&Coord { x: 5, y: 3 }, &board, &game_board, you, &MoveOptions::default()));
+    }
+
     #[test]
     fn avoid_head_to_head() {
         const BOARD_DATA: &str = r#"
@@ -1117,14 +2522,14 @@ mod tests {
             &board,
             &game_board,
             &you,
-            None
+            &MoveOptions::default()
         ));
         assert!(can_move_board(
             &Coord { x: 6, y: 4 },
             &board,
             &game_board,
             &you,
-            None
+            &MoveOptions::default()
         ));
     }
     #[test]
@@ -1217,35 +2622,35 @@ mod tests {
         let board: types::Board = serde_json::from_str(BOARD_DATA).unwrap();
         let game_board = board.to_game_board();
         let you: &types::Battlesnake = &board.snakes[0];
+        let ctx = TurnContext::sample(&board, &game_board, you);
         let mut connected_tiles = get_adj_tiles_connected(
             &you.head,
-            &board,
-            &game_board,
-            you,
-            0.8,
-            0,
-            Some(false),
-            None,
-            Some(true),
-            None,
+            &ctx,
+            &MoveOptions::default().thresholds(0.8, 0).apply_degree(false).avoid_snake_heads(true),
         );
         assert!(connected_tiles[0] == Coord { x: 4, y: 4 });
         connected_tiles = get_adj_tiles_connected(
             &you.head,
-            &board,
-            &game_board,
-            you,
-            0.01,
-            0,
-            Some(false),
-            None,
-            Some(true),
-            None,
+            &ctx,
+            &MoveOptions::default().thresholds(0.01, 0).apply_degree(false).avoid_snake_heads(true),
         );
         assert!(
             connected_tiles.len() == 3
                 && connected_tiles[connected_tiles.len() - 1] == Coord { x: 4, y: 4 }
         );
+        // the scores the moves were ordered by: ten tiles in the pocket our body wraps, a hundred outside it
+        let options = MoveOptions::default().thresholds(0.01, 0).apply_degree(false).avoid_snake_heads(true);
+        let scored = get_scored_adj_tiles(&you.head, &ctx, &options);
+        let coords: Vec<Coord> = scored.iter().map(|tile| tile.coord).collect();
+        assert_eq!(coords, connected_tiles.to_vec());
+        assert!(scored.iter().all(|tile| tile.diverged && tile.degree.is_none()));
+        let connectivity: Vec<f32> = scored.iter().map(|tile| tile.connectivity.unwrap()).collect();
+        assert_eq!(connectivity, [10.0 / 106.0, 10.0 / 106.0, 100.0 / 106.0]);
+        // the only move connected enough has nothing to be ordered against
+        let options = options.thresholds(0.8, 0);
+        let scored = get_scored_adj_tiles(&you.head, &ctx, &options);
+        assert_eq!(scored.len(), 1);
+        assert_eq!((scored[0].coord, scored[0].connectivity, scored[0].diverged), (Coord { x: 4, y: 4 }, None, true));
     }
 
     #[test]
@@ -1361,7 +2766,912 @@ mod tests {
         let board: types::Board = serde_json::from_str(BOARD_DATA).unwrap();
         let game_board = board.to_game_board();
         let you: &types::Battlesnake = &board.snakes[0];
-        let moves = get_rand_moves(&you.head, &board, &game_board, you, 0.3, 2, None);
+        let moves = get_rand_moves(
+            &you.head,
+            &TurnContext::sample(&board, &game_board, you),
+            &MoveOptions::default().thresholds(0.3, 2),
+            &mut seed::rng("test", 0),
+        );
         assert_eq!(*moves.last().unwrap(), "down");
     }
+
+    // a lone snake in the middle of an empty board, every way it can go is as good as the others
+    static SYMMETRIC_DATA: &str = r#"
+      {
+        "game": {
+          "id": "symmetric",
+          "ruleset": { "name": "standard", "version": "v1.0.0" },
+          "timeout": 500
+        },
+        "turn": 3,
+        "board": {
+          "height": 11,
+          "width": 11,
+          "food": [],
+          "snakes": [
+            {
+              "id": "me",
+              "name": "me",
+              "health": 97,
+              "body": [
+                { "x": 5, "y": 5 },
+                { "x": 5, "y": 4 },
+                { "x": 5, "y": 3 }
+              ],
+              "latency": 0,
+              "head": { "x": 5, "y": 5 },
+              "length": 3,
+              "shout": ""
+            }
+          ],
+          "hazards": []
+        },
+        "you": {
+          "id": "me",
+          "name": "me",
+          "health": 97,
+          "body": [
+            { "x": 5, "y": 5 },
+            { "x": 5, "y": 4 },
+            { "x": 5, "y": 3 }
+          ],
+          "latency": 0,
+          "head": { "x": 5, "y": 5 },
+          "length": 3,
+          "shout": ""
+        }
+      }
+    "#;
+
+    #[test]
+    fn same_game_and_turn_gives_same_move() {
+        let state: types::GameState = serde_json::from_str(SYMMETRIC_DATA).unwrap();
+        let first = get_move(&state.game, &state.turn, &state.board, &state.you);
+        for _ in 0..5 {
+            let again = get_move(&state.game, &state.turn, &state.board, &state.you);
+            assert_eq!(first, again);
+        }
+    }
+
+    #[test]
+    fn game_ids_diverge_on_symmetric_board() {
+        let mut state: types::GameState = serde_json::from_str(SYMMETRIC_DATA).unwrap();
+        let mut moves: HashSet<String> = HashSet::new();
+        for i in 0..16 {
+            state.game.id = format!("game-{}", i);
+            let response = get_move(&state.game, &state.turn, &state.board, &state.you);
+            moves.insert(response["move"].to_string());
+        }
+        assert!(moves.len() > 1);
+    }
+
+    #[test]
+    fn fallback_move_keeps_away_from_longer_heads() {
+        let snake = |id: &str, body: &[(i16, i16)]| {
+            let body: Vec<Coord> = body.iter().map(|&(x, y)| Coord { x, y }).collect();
+            let head = body.first().copied().unwrap_or(Coord { x: 0, y: 0 });
+            return json!({
+                "id": id, "name": id, "health": 90, "body": body, "head": head, "length": body.len(),
+            });
+        };
+        let you = snake("us", &[(2, 2), (2, 1), (2, 0)]);
+        let board: types::Board = serde_json::from_value(json!({
+            "width": 5, "height": 5, "food": [], "hazards": [],
+            "snakes": [you, snake("them", &[(3, 3), (3, 4), (4, 4), (4, 3)])],
+        }))
+        .unwrap();
+        let you: types::Battlesnake = serde_json::from_value(you).unwrap();
+        // up and right could meet their head, down is our neck
+        assert_eq!(fallback_move(&board, &you), "left");
+
+        // with nowhere to go it still answers
+        let you = snake("us", &[(0, 0), (0, 0), (0, 0)]);
+        let board: types::Board = serde_json::from_value(json!({
+            "width": 1, "height": 1, "food": [], "hazards": [], "snakes": [you, snake("empty", &[])],
+        }))
+        .unwrap();
+        let you: types::Battlesnake = serde_json::from_value(you).unwrap();
+        assert_eq!(fallback_move(&board, &you), "up");
+    }
+
+    #[test]
+    fn frames_the_pipeline_gives_up_on_get_the_fallback() {
+        let corner: types::GameState =
+            serde_json::from_str(include_str!("../testdata/fixtures/corner.json")).unwrap();
+        let frame = |size: u8, body: &[(i16, i16)]| {
+            let body: Vec<Coord> = body.iter().map(|&(x, y)| Coord { x, y }).collect();
+            let you: types::Battlesnake = serde_json::from_value(json!({
+                "id": "us", "name": "us", "health": 90, "body": body, "head": body[0], "length": body.len(),
+            }))
+            .unwrap();
+            let mut state = corner.clone();
+            (state.board.width, state.board.height) = (size, size);
+            state.board.snakes = vec![you.clone()];
+            state.you = you;
+            return state;
+        };
+        let deadline = || Instant::now() + Duration::from_secs(1);
+        // every tile is ours, the share of the free tiles a move keeps is 0 / 0, but the tail moves out of the way
+        let full = frame(2, &[(0, 0), (0, 1), (1, 1), (1, 0)]);
+        // walled in by our own body, no search gets a first step
+        let boxed = frame(3, &[(0, 0), (1, 0), (1, 1), (0, 1), (0, 2)]);
+        // a row above the board, the tile sets the searches fill have no room for it
+        let above = frame(3, &[(0, 3), (1, 3), (2, 3)]);
+        for (state, error) in [
+            (&full, DecisionError::NoFreeTiles),
+            (&boxed, DecisionError::EmptyFrontier),
+            (&above, DecisionError::NoSnake),
+        ] {
+            let decision = get_move_until(&state.game, &state.turn, &state.board, &state.you, deadline());
+            assert_eq!(decision.trace.error, Some(error));
+            assert_eq!(decision.trace.branch, Branch::Fallback);
+            assert_eq!(decision.response["move"], fallback_move(&state.board, &state.you));
+        }
+        let decision = get_move_until(&full.game, &full.turn, &full.board, &full.you, deadline());
+        assert_eq!(decision.response["move"], "right");
+        let decision = get_move_until(&above.game, &above.turn, &above.board, &above.you, deadline());
+        assert_eq!(decision.response["move"], "down");
+
+        // a way out two tiles long for a single move, its first step isn't a move we can make
+        let stale = get_move_against(
+            &corner.game, &corner.turn, &corner.board, &corner.you, &StrategyProfile::default(), &Stances::new(),
+            &Announced::new(), None, &[Coord { x: 2, y: 0 }], None, deadline(),
+        );
+        assert_eq!(stale.unwrap_err(), DecisionError::EmptyPath { branch: Branch::Pinned });
+    }
+
+    #[test]
+    fn lookahead_swaps_a_move_that_loses() {
+        let mut state: types::GameState =
+            serde_json::from_str(include_str!("../testdata/fixtures/corner.json")).unwrap();
+        // our body walls in the corner below our head, once in there our neck and our body are all around us
+        let body: Vec<Coord> = [(0, 1), (1, 1), (1, 0), (2, 0), (3, 0)].iter().map(|&(x, y)| Coord { x, y }).collect();
+        let you: types::Battlesnake = serde_json::from_value(json!({
+            "id": "us", "name": "us", "health": 90, "body": body, "head": body[0], "length": body.len(),
+        }))
+        .unwrap();
+        (state.board.snakes, state.board.food, state.you) = (vec![you.clone()], vec![], you);
+        // a way out we committed to on the turn before that now leads into the corner
+        let plan = [Coord { x: 0, y: -1 }];
+        let decide = |lookahead_depth: u8| {
+            let profile = StrategyProfile {
+                lookahead_depth,
+                ..StrategyProfile::default()
+            };
+            let deadline = Instant::now() + Duration::from_secs(1);
+            return get_move_against(
+                &state.game, &state.turn, &state.board, &state.you, &profile, &Stances::new(), &Announced::new(),
+                None, &plan, None, deadline,
+            )
+            .unwrap();
+        };
+        let planned = decide(0);
+        assert_eq!((planned.trace.branch, planned.response["move"].as_str()), (Branch::Pinned, Some("down")));
+        let searched = decide(2);
+        assert_eq!((searched.trace.branch, searched.response["move"].as_str()), (Branch::Lookahead, Some("up")));
+        assert!(searched.trace.escape.is_empty());
+    }
+
+    /// # random_board
+    /// an 11x11 board with our snake and another whose body is scattered over it as walls, its health and length
+    /// decide whether its tail can be moved onto and whether its head is avoided
+    pub fn random_board(rng: &mut impl Rng) -> types::Board {
+        return random_board_of(rng, 11);
+    }
+
+    /// # random_board_of
+    /// a `random_board` of any size, with as many walls for its area
+    pub fn random_board_of(rng: &mut impl Rng, size: i16) -> types::Board {
+        let tile = |rng: &mut dyn rand::RngCore| Coord {
+            x: rng.gen_range(0..size),
+            y: rng.gen_range(0..size),
+        };
+        let snake = |id: &str, body: Vec<Coord>, health: u8, length: usize| {
+            return json!({
+                "id": id, "name": id, "health": health, "head": body[0], "body": body, "length": length,
+            });
+        };
+        let you: Vec<Coord> = (0..3).map(|_| tile(rng)).collect();
+        let area = size as usize * size as usize;
+        let walls: Vec<Coord> = (0..rng.gen_range(area / 12..area / 2)).map(|_| tile(rng)).collect();
+        let wall_health = rng.gen_range(90..=100);
+        let wall_length = rng.gen_range(2..6);
+        return serde_json::from_value(json!({
+            "width": size, "height": size, "food": [], "hazards": [],
+            "snakes": [snake("us", you, 90, 3), snake("walls", walls, wall_health, wall_length)],
+        }))
+        .unwrap();
+    }
+
+    #[test]
+    fn incremental_connectivity_matches_the_flood_fill() {
+        let mut rng = seed::rng("connectivity", 0);
+        for _ in 0..10 {
+            let board = random_board(&mut rng);
+            let game_board = board.to_game_board();
+            let you = &board.snakes[0];
+            let labelled = TurnContext::sample(&board, &game_board, you);
+            let filled = TurnContext::sample(&board, &game_board, you).with_full_fills();
+            for x in 0..11 {
+                for y in 0..11 {
+                    let tile = Coord { x, y };
+                    let random = Coord {
+                        x: rng.gen_range(0..11),
+                        y: rng.gen_range(0..11),
+                    };
+                    let mut exclusions = vec![vec![], vec![tile], vec![random], vec![random, tile]];
+                    exclusions.extend(get_all_adj_tiles(&tile, &board).into_iter().map(|adj| vec![adj]));
+                    for exclude in exclusions {
+                        assert_eq!(
+                            percent_connected(&tile, &labelled, &exclude),
+                            percent_connected(&tile, &filled, &exclude),
+                            "from {:?} excluding {:?} on\n{}",
+                            tile,
+                            exclude,
+                            board.render()
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn divergence_analysis_visits_fewer_tiles() {
+        let state: types::GameState =
+            serde_json::from_str(&std::fs::read_to_string("testdata/fixtures/three_way_squeeze.json").unwrap())
+                .unwrap();
+        let board = &state.board;
+        let game_board = board.to_game_board();
+        let you = &state.you;
+        let labelled = TurnContext::sample(board, &game_board, you);
+        let filled = TurnContext::sample(board, &game_board, you).with_full_fills();
+        // every step A* could take, with the tile it came from excluded
+        for x in 0..board.width as i16 {
+            for y in 0..board.height as i16 {
+                let tile = Coord { x, y };
+                for from in get_all_adj_tiles(&tile, board) {
+                    let moves = |ctx: &TurnContext| {
+                        get_adj_tiles_connected(&tile, ctx, &MoveOptions::default().thresholds(0.5, 0).exclude(&[from]))
+                    };
+                    assert_eq!(moves(&labelled), moves(&filled));
+                }
+            }
+        }
+        assert!(
+            labelled.connectivity.visits() * 10 < filled.connectivity.visits(),
+            "{} visits labelled, {} filling",
+            labelled.connectivity.visits(),
+            filled.connectivity.visits()
+        );
+    }
+
+    #[test]
+    fn move_order_comes_from_the_profile() {
+        // a profile that only cares about not being cut off and then heads for the center
+        const CENTER_FIRST: &[MoveCriterion] = &[MoveCriterion::CutOff, MoveCriterion::Center, MoveCriterion::Degree];
+        let state: types::GameState =
+            serde_json::from_str(&std::fs::read_to_string("testdata/fixtures/ahead_by_three.json").unwrap()).unwrap();
+        let game_board = state.board.to_game_board();
+        let mut ctx = TurnContext::sample(&state.board, &game_board, &state.you);
+        let head = state.you.head;
+        // the best move comes last, up by default and right once the center comes ahead of the degree
+        let by_degree = get_adj_tiles_connected(&head, &ctx, &MoveOptions::default());
+        assert_eq!(by_degree.to_vec(), [Coord { x: 4, y: 5 }, Coord { x: 3, y: 4 }, Coord { x: 3, y: 6 }]);
+        ctx.profile.move_order = CENTER_FIRST;
+        let by_center = get_adj_tiles_connected(&head, &ctx, &MoveOptions::default());
+        assert_eq!(by_center.to_vec(), [Coord { x: 3, y: 4 }, Coord { x: 3, y: 6 }, Coord { x: 4, y: 5 }]);
+    }
+
+    #[test]
+    fn moves_are_keyed_once_a_sort() {
+        let state: types::GameState =
+            serde_json::from_str(&std::fs::read_to_string("testdata/fixtures/three_way_squeeze.json").unwrap())
+                .unwrap();
+        let board = &state.board;
+        let game_board = board.to_game_board();
+        let ctx = TurnContext::sample(board, &game_board, &state.you);
+        for x in 0..board.width as i16 {
+            for y in 0..board.height as i16 {
+                let tile = Coord { x, y };
+                let moves = get_adj_tiles(&tile, &ctx, &MoveOptions::default());
+                MOVE_DEGREES.with(|degrees| degrees.set(0));
+                score_moves(&moves, MoveCriterion::DEFAULT_ORDER, |mv| MoveKey::new(mv, &ctx, &MoveOptions::default()));
+                let keyed = if moves.len() < 2 { 0 } else { moves.len() };
+                assert_eq!(MOVE_DEGREES.with(|degrees| degrees.get()), keyed);
+                // one sort of the moves and at most one more of the ones that diverge
+                MOVE_DEGREES.with(|degrees| degrees.set(0));
+                get_adj_tiles_connected(&tile, &ctx, &MoveOptions::default());
+                assert!(MOVE_DEGREES.with(|degrees| degrees.get()) <= 2 * moves.len());
+            }
+        }
+    }
+
+    #[test]
+    fn connected_at_least_agrees_with_the_percentage() {
+        for fixture in crate::fixtures::load(std::path::Path::new(crate::fixtures::FIXTURE_DIR)).unwrap() {
+            let board = &fixture.state.board;
+            let game_board = board.to_game_board();
+            let you = &fixture.state.you;
+            let labelled = TurnContext::sample(board, &game_board, you);
+            let filled = TurnContext::sample(board, &game_board, you).with_full_fills();
+            for x in 0..board.width as i16 {
+                for y in 0..board.height as i16 {
+                    let tile = Coord { x, y };
+                    let adj = get_all_adj_tiles(&tile, board);
+                    let exclusions = [vec![], adj[..1].to_vec(), adj[..2].to_vec()];
+                    for threshold in [0.0, 0.1, 0.3, 0.5, 0.8, 1.0] {
+                        let needed = tiles_needed(threshold, labelled.free_tiles);
+                        for exclude in &exclusions {
+                            let clears = percent_connected(&tile, &filled, exclude) >= threshold;
+                            assert_eq!(
+                                connected_at_least(&tile, needed, &labelled, exclude),
+                                clears,
+                                "{:?} at {} excluding {:?} in {}",
+                                tile,
+                                threshold,
+                                exclude,
+                                fixture.name
+                            );
+                            assert_eq!(connected_at_least(&tile, needed, &filled, exclude), clears);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn connected_at_least_stops_at_the_threshold() {
+        let board: types::Board = serde_json::from_value(json!({
+            "width": 19, "height": 19, "food": [], "hazards": [],
+            "snakes": [{
+                "id": "us", "name": "us", "health": 90, "length": 3,
+                "head": {"x": 9, "y": 9}, "body": [{"x": 9, "y": 9}, {"x": 9, "y": 8}, {"x": 9, "y": 7}],
+            }],
+        }))
+        .unwrap();
+        let game_board = board.to_game_board();
+        let you = &board.snakes[0];
+        let tile = Coord { x: 9, y: 10 };
+        let ctx = TurnContext::sample(&board, &game_board, you).with_full_fills();
+        assert!(connected_at_least(&tile, you.length, &ctx, &[]));
+        assert_eq!(ctx.connectivity.visits(), you.length as u64);
+        // half the board is as far as the fill goes for the usual threshold, the percentage fills all of it
+        let half = TurnContext::sample(&board, &game_board, you).with_full_fills();
+        let needed = tiles_needed(0.5, half.free_tiles);
+        assert!(connected_at_least(&tile, needed, &half, &[]));
+        assert_eq!(half.connectivity.visits(), needed as u64);
+        let whole = TurnContext::sample(&board, &game_board, you).with_full_fills();
+        percent_connected(&tile, &whole, &[]);
+        assert!(whole.connectivity.visits() >= whole.free_tiles as u64);
+    }
+
+    /// the moves every tile of a board would be given, and A*'s path, with the fills run side by side or not
+    fn divergence_answers(board: &types::Board, parallel_fills: bool) -> (Vec<AdjTiles>, Vec<Coord>, u64) {
+        let game_board = board.to_game_board();
+        let profile = StrategyProfile {
+            parallel_fills,
+            ..StrategyProfile::DEFAULT
+        };
+        let ctx = TurnContext::new(board, &game_board, &board.snakes[0], profile, types::Ruleset::default())
+            .with_full_fills();
+        let mut answers = vec![];
+        for x in 0..board.width as i16 {
+            for y in 0..board.height as i16 {
+                let tile = Coord { x, y };
+                let from = get_all_adj_tiles(&tile, board);
+                for exclude in [&[][..], &from[..1]] {
+                    let options = MoveOptions::default().exclude(exclude);
+                    answers.push(get_adj_tiles_connected(&tile, &ctx, &options.thresholds(0.3, 2)));
+                    answers.push(get_adj_tiles_connected(&tile, &ctx, &options.evasive(true)));
+                }
+            }
+        }
+        let path = graph::a_star(&ctx, &MoveOptions::default().thresholds(0.5, 2), 16);
+        return (answers, path.path, ctx.connectivity.visits());
+    }
+
+    #[test]
+    fn parallel_fills_match_sequential() {
+        let mut rng = seed::rng("parallel_fills", 0);
+        let mut boards: Vec<types::Board> = crate::fixtures::load(std::path::Path::new(crate::fixtures::FIXTURE_DIR))
+            .unwrap()
+            .into_iter()
+            .map(|fixture| fixture.state.board)
+            .collect();
+        boards.extend([15, 19, 25].map(|size| random_board_of(&mut rng, size)));
+        assert!(boards.iter().any(|board| board.width as u16 * board.height as u16 >= PARALLEL_FILL_TILES));
+        for board in &boards {
+            assert_eq!(divergence_answers(board, true), divergence_answers(board, false), "on\n{}", board.render());
+        }
+    }
+
+    #[test]
+    fn parallel_fills_dont_slow_a_large_board() {
+        let board = random_board_of(&mut seed::rng("parallel_fills_timing", 0), 19);
+        let time = |parallel_fills| {
+            let started = Instant::now();
+            divergence_answers(&board, parallel_fills);
+            return started.elapsed();
+        };
+        // warm the pool and the scratches up first
+        time(true);
+        let (sequential, parallel) = (time(false), time(true));
+        assert!(parallel < sequential * 2, "{:?} side by side, {:?} one after another", parallel, sequential);
+    }
+
+    #[test]
+    fn free_tiles_are_counted_once_a_turn() {
+        let state: types::GameState =
+            serde_json::from_str(&std::fs::read_to_string("testdata/fixtures/poorly_connected.json").unwrap())
+                .unwrap();
+        FREE_TILE_COUNTS.with(|counts| counts.set(0));
+        get_move(&state.game, &state.turn, &state.board, &state.you);
+        // the box check and every connectivity question of the searches read it off the turn's context
+        assert_eq!(FREE_TILE_COUNTS.with(|counts| counts.get()), 1);
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        return move |key| vars.get(key).cloned();
+    }
+
+    #[test]
+    fn snake_info_defaults() {
+        let info = snake_info(vars(&[]));
+        assert_eq!(
+            serde_json::to_value(&info).unwrap(),
+            json!({
+                "apiversion": "1",
+                "author": "tofurky",
+                "color": "#c76d0c",
+                "head": "chicken",
+                "tail": "mlh-gene",
+            })
+        );
+    }
+
+    #[test]
+    fn snake_info_overridden() {
+        let info = snake_info(vars(&[
+            (SNAKE_COLOR_ENV, "#1A2b3c"),
+            (SNAKE_HEAD_ENV, "beluga"),
+            (SNAKE_TAIL_ENV, "curled"),
+            (SNAKE_AUTHOR_ENV, "someone"),
+        ]));
+        assert_eq!(info.color.to_string(), "#1A2b3c");
+        assert_eq!(info.head, "beluga");
+        assert_eq!(info.tail, "curled");
+        assert_eq!(info.author, "someone");
+    }
+
+    #[test]
+    fn invalid_snake_color_falls_back() {
+        for color in ["red", "#12345", "#1234567", "c76d0c", "#gggggg"] {
+            let info = snake_info(vars(&[(SNAKE_COLOR_ENV, color), (SNAKE_HEAD_ENV, "beluga")]));
+            assert_eq!(info.color.to_string(), DEFAULT_COLOR, "{}", color);
+            assert_eq!(info.head, "beluga");
+        }
+    }
+
+    #[test]
+    fn personality_info_prefers_its_own_variables() {
+        let var = vars(&[
+            (SNAKE_COLOR_ENV, "#111111"),
+            (SNAKE_HEAD_ENV, "beluga"),
+            ("SNAKE_AGGRESSIVE_COLOR", "#ff0000"),
+        ]);
+        let aggressive = personality_info("aggressive", &var);
+        assert_eq!(aggressive.color.to_string(), "#ff0000");
+        assert_eq!(aggressive.head, "beluga");
+        assert_eq!(personality_info("", &var).color.to_string(), "#111111");
+    }
+
+    #[test]
+    fn shadowing_turns_us_out_of_the_corner_early() {
+        let state: types::GameState = serde_json::from_str(
+            &std::fs::read_to_string("testdata/fixtures/shadowed_into_corner.json").unwrap(),
+        )
+        .unwrap();
+        let game_board = state.board.to_game_board();
+        let ctx = TurnContext::sample(&state.board, &game_board, &state.you);
+        let down = state.you.head + types::DIRECTIONS["down"];
+        let left = state.you.head + types::DIRECTIONS["left"];
+        assert_eq!(ctx.shadowed.iter().collect::<HashSet<_>>(), HashSet::from([&down, &left]));
+        // the corner's still open, nothing but the shadow keeps us from heading into it
+        assert!(connected_at_least(&down, tiles_needed(0.5, ctx.free_tiles), &ctx, &[state.you.head]));
+
+        HERDING.with(|herding| herding.set(false));
+        let unaware = get_move(&state.game, &state.turn, &state.board, &state.you);
+        HERDING.with(|herding| herding.set(true));
+        let aware = get_move(&state.game, &state.turn, &state.board, &state.you);
+        assert_eq!(unaware["move"], "down");
+        assert_ne!(aware["move"], "down");
+    }
+
+    #[test]
+    fn only_a_longer_snake_heading_for_the_corner_shadows_us() {
+        let mut state: types::GameState = serde_json::from_str(
+            &std::fs::read_to_string("testdata/fixtures/shadowed_into_corner.json").unwrap(),
+        )
+        .unwrap();
+        let shadowed = |state: &types::GameState| {
+            let game_board = state.board.to_game_board();
+            return TurnContext::sample(&state.board, &game_board, &state.you).shadowed.len();
+        };
+        assert_eq!(shadowed(&state), 2);
+        // the same snake at our length
+        state.board.snakes[1].length = state.you.length;
+        assert_eq!(shadowed(&state), 0);
+        // longer again but turned back up the board, its body reversed
+        state.board.snakes[1].length = state.you.length + 2;
+        state.board.snakes[1].body.reverse();
+        state.board.snakes[1].head = state.board.snakes[1].body[0];
+        assert_eq!(shadowed(&state), 0);
+    }
+
+    #[test]
+    fn sandwiched_snakes_break_out_sideways() {
+        let mut state: types::GameState = serde_json::from_str(
+            &std::fs::read_to_string("testdata/fixtures/sandwiched_on_the_wall.json").unwrap(),
+        )
+        .unwrap();
+        let deadline = || Instant::now() + Duration::from_secs(1);
+        HERDING.with(|herding| herding.set(false));
+        let unaware = get_move_until(&state.game, &state.turn, &state.board, &state.you, deadline());
+        HERDING.with(|herding| herding.set(true));
+        let aware = get_move_until(&state.game, &state.turn, &state.board, &state.you, deadline());
+        // the food at the end of the lane keeps us racing down it
+        assert_eq!(unaware.response["move"], "down");
+        assert!(!unaware.trace.sandwiched);
+        assert_eq!(aware.response["move"], "right");
+        assert!(aware.trace.sandwiched);
+
+        // a snake our own length alongside is no reason to leave
+        state.board.snakes[1].length = state.you.length;
+        let game_board = state.board.to_game_board();
+        assert_eq!(TurnContext::sample(&state.board, &game_board, &state.you).lane, None);
+    }
+
+    #[test]
+    fn hunger_follows_our_length_against_the_longest_opponent() {
+        let mut state: types::GameState = serde_json::from_str(
+            &std::fs::read_to_string("testdata/fixtures/level_with_the_longest.json").unwrap(),
+        )
+        .unwrap();
+        let policy = LengthPolicy::DEFAULT;
+        assert_eq!(policy.length_target(&state.board, &state.you), 6);
+        let hunger = |state: &types::GameState, length: u32| {
+            let mut you = state.you.clone();
+            you.length = length;
+            return policy.hunger(&state.board, &you);
+        };
+        let hungers: Vec<Hunger> = (2..=9).map(|length| hunger(&state, length)).collect();
+        use Hunger::*;
+        assert_eq!(hungers, [Urgent, Urgent, Normal, Normal, Normal, Normal, Full, Full]);
+
+        // starving, we eat however long we are
+        state.you.health = policy.starving_health;
+        assert_eq!(hunger(&state, 9), Normal);
+        // on our own there's nobody to measure up to
+        state.board.snakes.retain(|snake| snake.id == state.you.id);
+        assert_eq!(policy.length_target(&state.board, &state.you), state.you.length);
+        assert_eq!(policy.hunger(&state.board, &state.you), Normal);
+    }
+
+    #[test]
+    fn the_phase_weights_break_the_ties() {
+        // our long snake in the middle of the board heading up, full next to a short snake up at the top. up, left
+        // and right are as roomy and as central as each other, the food is two tiles to the right
+        let body = |tiles: &[(i16, i16)]| tiles.iter().map(|&(x, y)| Coord { x, y }).collect::<Vec<Coord>>();
+        let you: types::Battlesnake = serde_json::from_value(json!({
+            "id": "us", "name": "us", "health": 90, "length": 6, "head": { "x": 5, "y": 5 },
+            "body": body(&[(5, 5), (5, 4), (5, 3), (5, 2), (5, 1), (5, 0)]),
+        }))
+        .unwrap();
+        let board: types::Board = serde_json::from_value(json!({
+            "width": 11, "height": 11, "food": [{ "x": 8, "y": 5 }], "hazards": [],
+            "snakes": [you, {
+                "id": "them", "name": "them", "health": 90, "length": 2, "head": { "x": 5, "y": 10 },
+                "body": body(&[(5, 10), (5, 9)]),
+            }],
+        }))
+        .unwrap();
+        let game: types::Game =
+            serde_json::from_value(json!({ "id": "phased", "ruleset": {}, "timeout": 500 })).unwrap();
+        let chosen = |turn: u32, phase: Phase| {
+            let deadline = Instant::now() + Duration::from_secs(1);
+            let decision = get_move_against(
+                &game, &turn, &board, &you, &StrategyProfile::default(), &Stances::new(), &Announced::new(),
+                Some(phase), &[], None, deadline,
+            ).unwrap();
+            assert_eq!(decision.trace.hunger, Hunger::Full);
+            assert_eq!(decision.trace.game_phase, phase);
+            return decision.response["move"].as_str().unwrap().to_string();
+        };
+        let early: HashSet<String> = (0..20).map(|turn| chosen(turn, Phase::Early)).collect();
+        let late: HashSet<String> = (0..20).map(|turn| chosen(turn, Phase::Late)).collect();
+        // the early game leans toward the food, the late game doesn't mind it and leaves the tie to chance
+        assert_eq!(early, HashSet::from(["right".to_string()]));
+        assert!(late.len() > 1, "{:?}", late);
+    }
+
+    #[test]
+    fn moves_cut_off_by_a_longer_head_are_avoided() {
+        let state: types::GameState =
+            serde_json::from_str(&std::fs::read_to_string("testdata/fixtures/cut_off_on_the_wall.json").unwrap())
+                .unwrap();
+        let game_board = state.board.to_game_board();
+        let ctx = TurnContext::sample(&state.board, &game_board, &state.you);
+        let right = Coord { x: 3, y: 0 };
+        // a move out of the longer head's reach this turn, with both ways on from it in its reach the next
+        assert!(ctx.can_move(&right, &MoveOptions::default()));
+        assert!(!cut_off(&right, &ctx, &MoveOptions::default()));
+        assert!(cut_off(&right, &ctx, &MoveOptions::default().head_radius(2)));
+        assert!(!cut_off(&Coord { x: 2, y: 1 }, &ctx, &MoveOptions::default().head_radius(2)));
+        // the short way to the food goes through it, unless A* looks that far
+        let options = MoveOptions::default().thresholds(0.5, 2);
+        assert_eq!(graph::a_star(&ctx, &options, 16).path.first(), Some(&right));
+        assert_eq!(graph::a_star(&ctx, &options.head_radius(2), 16).path.first(), Some(&Coord { x: 2, y: 1 }));
+    }
+
+    #[test]
+    fn roomy_boxes_are_played_in() {
+        let state: types::GameState =
+            serde_json::from_str(&std::fs::read_to_string("testdata/fixtures/roomy_pocket.json").unwrap()).unwrap();
+        let game_board = state.board.to_game_board();
+        let ctx = TurnContext::sample(&state.board, &game_board, &state.you);
+        // boxed in by the share of the free tiles, but the pocket holds our five tiles with room to spare
+        let region = graph::inside_box(&ctx, ctx.profile.box_threshold).unwrap();
+        assert_eq!(region, 24);
+        assert!(!cramped(region, &ctx));
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let decision = get_move_until(&state.game, &state.turn, &state.board, &state.you, deadline);
+        assert_ne!(decision.trace.branch, Branch::Escape);
+
+        // a margin a snake three times our length would need has us plan the way out
+        let wary = StrategyProfile {
+            box_margin: 40,
+            ..StrategyProfile::default()
+        };
+        let decision = get_move_profiled(&state.game, &state.turn, &state.board, &state.you, &wary, deadline);
+        assert_eq!(decision.trace.branch, Branch::Escape);
+    }
+
+    #[test]
+    fn maze_walls_are_routed_around() {
+        let state: types::GameState =
+            serde_json::from_str(&std::fs::read_to_string("testdata/fixtures/maze_corridor.json").unwrap()).unwrap();
+        let rules = types::Ruleset::from(&state.game);
+        assert!(rules.hazard_walls);
+        let board = state.board.with_rules(&rules);
+        let game_board = board.to_game_board();
+        let ctx = TurnContext::new(&board, &game_board, &state.you, StrategyProfile::default(), rules);
+        let head = state.you.head;
+        let right = Coord { x: 2, y: 2 };
+        let adj = get_adj_tiles(&head, &ctx, &MoveOptions::default());
+        assert!(adj.len() == 2 && adj.contains(&right), "{:?}", adj);
+        // the corridors are the free tiles, and a fill from the move right covers them and a tail tip that moves on
+        assert_eq!(ctx.free_tiles, 105);
+        assert_eq!(percent_connected(&right, &ctx, &[]), 106.0 / 105.0);
+        // seen as hazards the walls would count toward a fill but not toward the free tiles
+        let plain = state.board.to_game_board();
+        let unwalled = TurnContext::sample(&state.board, &plain, &state.you);
+        assert_eq!(percent_connected(&right, &unwalled, &[]), 116.0 / 105.0);
+
+        // two steps up across the wall, twenty along the corridor and through the gap without a point of damage
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let decision = get_move_until(&state.game, &state.turn, &state.board, &state.you, deadline);
+        assert_eq!(decision.response["move"], "right");
+        let a_star = decision.trace.a_star.unwrap();
+        assert_eq!((a_star.length, a_star.health_at_goal), (20, 70));
+    }
+
+    #[test]
+    fn snail_trails_are_crossed_where_they_have_decayed() {
+        let state: types::GameState =
+            serde_json::from_str(&std::fs::read_to_string("testdata/fixtures/snail_trails.json").unwrap()).unwrap();
+        let rules = types::Ruleset::from(&state.game);
+        assert!(rules.snail);
+        let game_board = state.board.to_game_board();
+        let ctx = TurnContext::new(&state.board, &game_board, &state.you, StrategyProfile::default(), rules);
+        // straight up we'd step onto all five stacks of the fresh trail, the old trail is down to two when we reach it
+        let up = [(5, 4), (5, 5), (5, 6), (5, 7)].map(|(x, y)| Coord { x, y });
+        assert_eq!(path_damage(&up, &ctx), 70);
+        let left = [(4, 3), (4, 4), (4, 5), (4, 6), (4, 7), (5, 7)].map(|(x, y)| Coord { x, y });
+        assert_eq!(path_damage(&left, &ctx), 28);
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let decision = get_move_until(&state.game, &state.turn, &state.board, &state.you, deadline);
+        assert_eq!(decision.response["move"], "left");
+        // three tiles along, the old trail has lost its last stack by the turn we cross it
+        let a_star = decision.trace.a_star.unwrap();
+        assert_eq!((a_star.length, a_star.cost, a_star.health_at_goal), (10, 10, 70));
+    }
+
+    #[test]
+    fn sauce_is_crossed_when_we_can_afford_it() {
+        let crossing = |name: &str| {
+            let state: types::GameState = serde_json::from_str(
+                &std::fs::read_to_string(format!("testdata/fixtures/{}.json", name)).unwrap(),
+            )
+            .unwrap();
+            let deadline = Instant::now() + Duration::from_secs(1);
+            return get_move_until(&state.game, &state.turn, &state.board, &state.you, deadline).trace.crossing;
+        };
+        // three turns across two tiles of sauce against eleven around them
+        let shortcut = CrossingTrace {
+            turns_saved: 8,
+            damage: 28,
+            crossed: true,
+        };
+        assert_eq!(crossing("sauce_shortcut"), Some(shortcut.clone()));
+        assert_eq!(crossing("sauce_detour"), Some(CrossingTrace { crossed: false, ..shortcut }));
+    }
+
+    #[test]
+    fn pinned_snakes_keep_to_the_way_out() {
+        let state: types::GameState = serde_json::from_str(
+            &std::fs::read_to_string("testdata/fixtures/pinned_against_the_wall.json").unwrap(),
+        )
+        .unwrap();
+        let (down, right) = (Coord { x: 0, y: -1 }, Coord { x: 1, y: 0 });
+        let decide = |plan: &[Coord]| {
+            let deadline = Instant::now() + Duration::from_secs(1);
+            return get_move_against(
+                &state.game, &state.turn, &state.board, &state.you, &StrategyProfile::default(), &Stances::new(),
+                &Announced::new(), None, plan, None, deadline,
+            ).unwrap();
+        };
+        HERDING.with(|herding| herding.set(false));
+        let unaware = decide(&[]);
+        // a plan we committed to is kept to even once we no longer see the lane
+        let committed = decide(&[down, down, right]);
+        HERDING.with(|herding| herding.set(true));
+        let pinned = decide(&[]);
+        // move by move, the open tile to the right looks best, and the longer snake closes it off
+        assert_eq!(unaware.response["move"], "right");
+        assert_eq!(unaware.trace.escape, vec![]);
+        assert_eq!(pinned.response["move"], "down");
+        assert_eq!(pinned.trace.branch, Branch::Pinned);
+        assert_eq!(pinned.trace.escape, vec![down, down, down, right]);
+        assert_eq!(committed.response["move"], "down");
+        assert_eq!(committed.trace.escape, vec![down, down, right]);
+    }
+
+    #[test]
+    fn free_tiles_count_down_the_turns() {
+        let snake = |id: &str, health: u8, body: &[(i16, i16)]| {
+            let body: Vec<Coord> = body.iter().map(|&(x, y)| Coord { x, y }).collect();
+            return json!({
+                "id": id, "name": id, "health": health, "body": body, "head": body[0], "length": body.len(),
+            });
+        };
+        let you = snake("us", 90, &[(0, 0), (1, 0), (2, 0)]);
+        // one that just ate, with its tail stacked, that can eat again by turn 2, and one shorter than the turns asked
+        // about that can't eat before it's gone
+        let board: types::Board = serde_json::from_value(json!({
+            "width": 5, "height": 5, "food": [{"x": 0, "y": 2}], "hazards": [],
+            "snakes": [you, snake("fed", 100, &[(0, 4), (1, 4), (2, 4), (2, 4)]), snake("short", 50, &[(4, 0), (4, 1)])],
+        }))
+        .unwrap();
+        let you: types::Battlesnake = serde_json::from_value(you).unwrap();
+        let free: Vec<u16> = (0..=5).map(|k| free_tiles_at(&board, &you, k)).collect();
+        assert_eq!(free, vec![17, 19, 22, 23, 23, 23]);
+        assert_eq!(free[0], num_free_tiles(&board));
+        // a tile that's cleared stays clear
+        assert!(free.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", free);
+
+        let timed = types::TimedOccupancy::new(&board, "us");
+        let stacked = Coord { x: 2, y: 4 };
+        assert!(timed.occupied_at(&stacked, 1) && !timed.occupied_at(&stacked, 2));
+        // frozen where it is from the turn it could eat, with the tail it has left by then
+        assert!([Coord { x: 0, y: 4 }, Coord { x: 1, y: 4 }].iter().all(|tile| timed.occupied_at(tile, 9)));
+        // gone at turn 2, before it could have eaten
+        let short = Coord { x: 4, y: 0 };
+        assert!(timed.occupied_at(&short, 1) && !timed.occupied_at(&short, 2) && !timed.occupied_at(&short, 9));
+        // our own body is never frozen
+        assert!(!timed.occupied_at(&Coord { x: 0, y: 0 }, 3));
+
+        // at full health without a stacked tail it grows this turn and keeps its tail
+        let board: types::Board = serde_json::from_value(json!({
+            "width": 5, "height": 5, "food": [], "hazards": [], "snakes": [snake("fed", 100, &[(0, 4), (1, 4), (2, 4)])],
+        }))
+        .unwrap();
+        let timed = types::TimedOccupancy::new(&board, "us");
+        assert!(timed.occupied_at(&stacked, 1) && !timed.occupied_at(&stacked, 2));
+        assert_eq!(timed.clears_in(&Coord { x: 0, y: 4 }), 4);
+    }
+
+    #[test]
+    fn default_move_options_are_the_old_defaults() {
+        // what leaving every option out used to mean
+        let spelled_out = MoveOptions::default()
+            .avoid_snake_heads(true)
+            .apply_degree(true)
+            .evasive(false)
+            .exclude(&[])
+            .thresholds(0.0, 0);
+        assert_eq!(MoveOptions::default(), spelled_out);
+        // what get_adj_tiles_connected answered with every option left out before they were a builder, for every tile
+        // of every fixture a row at a time from the bottom, the moves in the order they came. the old code panicked on
+        // the snake without a body, so it isn't there
+        let recorded: HashMap<String, Vec<String>> =
+            serde_json::from_str(include_str!("../testdata/adj_tiles_connected.json")).unwrap();
+        let step = |mv: char| types::DIRECTIONS[["up", "right", "down", "left"]["urdl".find(mv).unwrap()]];
+        let mut compared = HashSet::new();
+        for fixture in crate::fixtures::load(std::path::Path::new(crate::fixtures::FIXTURE_DIR)).unwrap() {
+            let board = &fixture.state.board;
+            let game_board = board.to_game_board();
+            let you = &fixture.state.you;
+            let ctx = TurnContext::sample(board, &game_board, you);
+            for x in 0..board.width as i16 {
+                for y in 0..board.height as i16 {
+                    let tile = Coord { x, y };
+                    let open: AdjTiles = get_all_adj_tiles(&tile, board)
+                        .into_iter()
+                        .filter(|adj| tile_is_open(adj, board, &game_board) && !ctx.threats.contains(adj))
+                        .collect();
+                    let adj = get_adj_tiles(&tile, &ctx, &MoveOptions::default());
+                    assert_eq!(adj.iter().collect::<HashSet<_>>(), open.iter().collect(), "{} {:?}", fixture.name, tile);
+                    assert_eq!(
+                        can_move_board(&tile, board, &game_board, you, &MoveOptions::default()),
+                        tile_is_open(&tile, board, &game_board) && !adj_to_bigger_snake(&tile, board, you),
+                    );
+                    let Some(rows) = recorded.get(&fixture.name) else {
+                        continue;
+                    };
+                    let old: AdjTiles = rows[y as usize]
+                        .split(' ')
+                        .nth(x as usize)
+                        .unwrap()
+                        .chars()
+                        .map(|mv| tile + step(mv))
+                        .collect();
+                    let adj = get_adj_tiles_connected(&tile, &ctx, &MoveOptions::default());
+                    assert_eq!(adj, old, "{} {:?}", fixture.name, tile);
+                    compared.insert(fixture.name.clone());
+                }
+            }
+        }
+        assert_eq!(compared, recorded.keys().cloned().collect());
+    }
+
+    #[test]
+    fn longer_snakes_running_alongside_are_broken_away_from() {
+        let turns: Vec<types::GameState> = std::fs::read_to_string("testdata/sequences/running_alongside.jsonl")
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        // the turns as the server plays them, each one told who was alongside us on the turn before
+        let play = || {
+            let mut alongside = None;
+            let mut moves = vec![];
+            for state in &turns {
+                let deadline = Instant::now() + Duration::from_millis(300);
+                let decision = get_move_against(
+                    &state.game, &state.turn, &state.board, &state.you, &StrategyProfile::default(), &Stances::new(),
+                    &Announced::new(), None, &[], alongside.as_ref(), deadline,
+                ).unwrap();
+                alongside = decision.trace.alongside.clone();
+                moves.push(decision.response["move"].as_str().unwrap().to_string());
+            }
+            return (moves, alongside);
+        };
+        HERDING.with(|herding| herding.set(false));
+        let (unaware, _) = play();
+        HERDING.with(|herding| herding.set(true));
+        let (aware, alongside) = play();
+        // straight on looks as good as ever, a turn or two side by side isn't enough to go by
+        assert_eq!(unaware, ["right", "right", "right"]);
+        assert_eq!(aware[..2], ["right", "right"]);
+        // by the third they're steering us, we turn away from them while there's room below
+        assert_eq!(aware[2], "down");
+        assert_eq!(alongside, Some(Alongside { snake: "them".to_string(), turns: 3 }));
+    }
 }
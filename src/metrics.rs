@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Write};
+use std::sync::{LazyLock, Mutex};
+
+/// a counter the strategy code can bump without knowing how it's exported. every label value it can take is listed up
+/// front so an exporter shows the whole family from the first scrape
+pub struct Counter {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub label: &'static str,
+    pub values: &'static [&'static str],
+}
+
+/// the nodes the searches visited
+pub const SEARCH_NODES: Counter = Counter {
+    name: "battlesnake_search_nodes_total",
+    help: "Nodes visited by the searches.",
+    label: "search",
+    values: &["endgame", "minimax"],
+};
+
+/// the times a deadline cut something short, from a search giving up early to the handler answering for the pipeline,
+/// `saturated` when the compute pool was too busy to even queue the move
+pub const DEADLINE_DEGRADATIONS: Counter = Counter {
+    name: "battlesnake_deadline_degradations_total",
+    help: "Times a deadline cut the move pipeline or one of its searches short, or the pipeline was skipped for it.",
+    label: "stage",
+    values: &["pipeline", "saturated", "endgame", "minimax"],
+};
+
+/// the moves answered with the fallback because something panicked, in the pipeline on the compute pool or in the move
+/// handler around it
+pub const PIPELINE_PANICS: Counter = Counter {
+    name: "battlesnake_pipeline_panics_total",
+    help: "Moves answered with the fallback because the pipeline or the move handler panicked.",
+    label: "stage",
+    values: &["pipeline", "handler"],
+};
+
+/// every counter, in the order they're exported
+pub const COUNTERS: [&Counter; 3] = [&SEARCH_NODES, &DEADLINE_DEGRADATIONS, &PIPELINE_PANICS];
+
+/// the counts, keyed by the counter's name and label value. they start over whenever the server restarts
+static REGISTRY: LazyLock<Mutex<HashMap<(&'static str, &'static str), u64>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+impl Counter {
+    /// # add
+    /// ## Arguments:
+    /// * value - the label value, one of `values`
+    /// * by - how much to count
+    pub fn add(&self, value: &'static str, by: u64) {
+        debug_assert!(
+            self.values.contains(&value),
+            "{} has no {}",
+            self.name,
+            value
+        );
+        let mut registry = REGISTRY
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *registry.entry((self.name, value)).or_default() += by;
+    }
+
+    pub fn get(&self, value: &'static str) -> u64 {
+        let registry = REGISTRY
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        return registry.get(&(self.name, value)).copied().unwrap_or(0);
+    }
+}
+
+/// a scrape in the Prometheus text format
+pub struct Exposition {
+    text: String,
+}
+
+impl Default for Exposition {
+    fn default() -> Self {
+        return Exposition::new();
+    }
+}
+
+impl Exposition {
+    pub fn new() -> Self {
+        return Exposition {
+            text: String::new(),
+        };
+    }
+
+    /// # family
+    /// starts a metric family, its samples follow
+    /// ## Arguments:
+    /// * name - the family's name
+    /// * kind - `counter`, `gauge` or `histogram`
+    /// * help - what it measures
+    pub fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.text, "# HELP {} {}", name, help);
+        let _ = writeln!(self.text, "# TYPE {} {}", name, kind);
+    }
+
+    /// # sample
+    /// ## Arguments:
+    /// * name - the sample's name, the family's name with a suffix for histograms
+    /// * labels - the label names and values
+    /// * value - the sample
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        self.text.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, escape(value)))
+                .collect();
+            let _ = write!(self.text, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.text, " {}", value);
+    }
+
+    /// # counters
+    /// adds every counter the strategy code records
+    pub fn counters(&mut self) {
+        for counter in COUNTERS {
+            self.family(counter.name, "counter", counter.help);
+            for value in counter.values {
+                self.sample(counter.name, &[(counter.label, value)], counter.get(value));
+            }
+        }
+    }
+
+    pub fn finish(self) -> String {
+        return self.text;
+    }
+}
+
+/// # escape
+/// a label value as the text format quotes it
+fn escape(value: &str) -> String {
+    return value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+}
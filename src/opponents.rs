@@ -0,0 +1,606 @@
+//! the snakes we keep meeting in ranked play, recognised by name. every game adds what each opponent did to a table
+//! on disk, and at `start()` the opponents with a clear habit get a stance the move pipeline plays around. a known
+//! opponent whose profile names a shout format has its shouts read every turn for the move it announces
+
+use crate::types::{self, Coord};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// set to a file, `opponents.json` for example, to remember the opponents between games. without it every opponent
+/// is played as an unknown
+pub const OPPONENTS_ENV: &str = "BATTLESNAKE_OPPONENTS";
+
+/// the chances a snake needs to have had before its rate says anything about it
+const MIN_CHANCES: u32 = 5;
+
+/// a snake that moves toward our head at least this often is played as aggressive
+const AGGRESSIVE_RATE: f32 = 0.5;
+
+/// a snake that takes the food we could have reached at most this often is played as passive
+const PASSIVE_RATE: f32 = 0.25;
+
+/// how we play around an opponent
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Stance {
+    #[default]
+    Neutral,
+    /// it goes for heads, we keep further from its head
+    Aggressive,
+    /// it backs off, we contest the food next to its head
+    Passive,
+}
+
+/// the stance for each opponent in a game, by snake id
+pub type Stances = HashMap<String, Stance>;
+
+/// reads the move a snake says it's about to make out of its shout. a strategy that knows how a snake announces its
+/// moves registers a format for it with `OpponentTable::with_shout_format`, and the snake's profile names it
+pub trait ShoutFormat: Send + Sync + Debug {
+    /// # announced_move
+    /// ## Returns:
+    /// the unit direction a shout announces, none if it doesn't announce one
+    fn announced_move(&self, shout: &str) -> Option<Coord>;
+}
+
+/// a shout that names the move, `left` or `going up!`. the first move named is the one announced
+#[derive(Debug)]
+pub struct NamedMove;
+
+impl ShoutFormat for NamedMove {
+    fn announced_move(&self, shout: &str) -> Option<Coord> {
+        return shout
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .find_map(|word| types::DIRECTIONS.get(word.to_ascii_lowercase().as_str()))
+            .copied();
+    }
+}
+
+/// how to read the shouts of the opponents in a game that announce their moves, by snake id
+pub type ShoutReaders = HashMap<String, Arc<dyn ShoutFormat>>;
+
+/// the unit direction each opponent announced this turn, by snake id
+pub type Announced = HashMap<String, Coord>;
+
+/// what an opponent has done when it had the chance, over every game we've seen it in
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct OpponentProfile {
+    pub games: u32,
+    /// turns its head was two steps or less from ours
+    pub head_chances: u32,
+    /// the ones it moved closer to our head on
+    pub head_approaches: u32,
+    /// turns a food next to its head was two steps or less from our head
+    pub food_chances: u32,
+    /// the ones it ate on
+    pub food_contests: u32,
+    /// the format it announces its moves in, set by hand in the table, `named_move` for example
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shout_format: Option<String>,
+}
+
+impl OpponentProfile {
+    /// # aggression
+    /// how often it moved toward our head when it was close, none until it's had `MIN_CHANCES`
+    pub fn aggression(&self) -> Option<f32> {
+        return rate(self.head_approaches, self.head_chances);
+    }
+
+    /// # food_contest_rate
+    /// how often it took the food we could have reached, none until it's had `MIN_CHANCES`
+    pub fn food_contest_rate(&self) -> Option<f32> {
+        return rate(self.food_contests, self.food_chances);
+    }
+
+    pub fn stance(&self) -> Stance {
+        if self
+            .aggression()
+            .is_some_and(|rate| rate >= AGGRESSIVE_RATE)
+        {
+            return Stance::Aggressive;
+        }
+        if self
+            .food_contest_rate()
+            .is_some_and(|rate| rate <= PASSIVE_RATE)
+        {
+            return Stance::Passive;
+        }
+        return Stance::Neutral;
+    }
+
+    fn add(&mut self, other: &OpponentProfile) {
+        self.games += other.games;
+        self.head_chances += other.head_chances;
+        self.head_approaches += other.head_approaches;
+        self.food_chances += other.food_chances;
+        self.food_contests += other.food_contests;
+    }
+}
+
+fn rate(taken: u32, chances: u32) -> Option<f32> {
+    if chances < MIN_CHANCES {
+        return None;
+    }
+    return Some(taken as f32 / chances as f32);
+}
+
+fn steps(a: &Coord, b: &Coord) -> i16 {
+    return (a.x - b.x).abs() + (a.y - b.y).abs();
+}
+
+/// # observe
+/// adds what every opponent did between two consecutive requests of a game to what it's done so far this game
+/// ## Arguments:
+/// * before - the earlier request
+/// * after - the request a turn later
+/// * habits - what each opponent has done this game, by name
+pub fn observe(
+    before: &types::GameState,
+    after: &types::GameState,
+    habits: &mut HashMap<String, OpponentProfile>,
+) {
+    if after.turn != before.turn + 1 {
+        return;
+    }
+    let ours = before.you.head;
+    for snake in before
+        .board
+        .snakes
+        .iter()
+        .filter(|snake| snake.id != before.you.id)
+    {
+        // a snake that died on the move tells us nothing about where it meant to go
+        let Some(moved) = after.board.snakes.iter().find(|moved| moved.id == snake.id) else {
+            continue;
+        };
+        let habit = habits.entry(snake.name.clone()).or_default();
+        habit.games = 1;
+        if steps(&snake.head, &ours) <= 2 {
+            habit.head_chances += 1;
+            if steps(&moved.head, &ours) < steps(&snake.head, &ours) {
+                habit.head_approaches += 1;
+            }
+        }
+        let contested = before
+            .board
+            .food
+            .iter()
+            .any(|food| steps(food, &snake.head) == 1 && steps(food, &ours) <= 2);
+        if contested {
+            habit.food_chances += 1;
+            if before.board.food.contains(&moved.head) {
+                habit.food_contests += 1;
+            }
+        }
+    }
+}
+
+/// # announced
+/// reads the move every opponent with a shout format announced this turn
+/// ## Arguments:
+/// * board - the battlesnake game board
+/// * readers - how to read each opponent's shouts, by snake id
+pub fn announced(board: &types::Board, readers: &ShoutReaders) -> Announced {
+    return board
+        .snakes
+        .iter()
+        .filter_map(|snake| {
+            let reader = readers.get(&snake.id)?;
+            let dir = reader.announced_move(snake.shout.as_deref()?)?;
+            return Some((snake.id.clone(), dir));
+        })
+        .collect();
+}
+
+/// # perceive
+/// the board as the heuristics should see it: an aggressive opponent looks a segment longer, so we keep off the tiles
+/// next to its head even when we're as long, and a passive one a segment shorter, so we take the food next to its head
+/// when it's only as long as us. an announced move is trusted as little: a snake that says it's moving onto a tile we
+/// could move to looks a segment longer and one that says it's moving elsewhere a segment shorter, so a snake that's
+/// really longer stays dangerous whatever it says. the real board is still what the endgame solver and the validator
+/// see
+/// ## Arguments:
+/// * board - the battlesnake game board
+/// * you - your battlesnake
+/// * stances - how to play around the opponents we recognised at the start
+/// * announced - the moves the opponents announced this turn
+pub fn perceive(
+    board: &types::Board,
+    you: &types::Battlesnake,
+    stances: &Stances,
+    announced: &Announced,
+) -> types::Board {
+    let mut perceived = board.clone();
+    for snake in perceived.snakes.iter_mut() {
+        match stances.get(&snake.id) {
+            Some(Stance::Aggressive) => snake.length += 1,
+            Some(Stance::Passive) => snake.length = snake.length.saturating_sub(1),
+            _ => {}
+        }
+        if let Some(dir) = announced.get(&snake.id) {
+            if steps(&(snake.head + *dir), &you.head) == 1 {
+                snake.length += 1;
+            } else {
+                snake.length = snake.length.saturating_sub(1);
+            }
+        }
+    }
+    return perceived;
+}
+
+/// the opponents we've met, by name, kept in a JSON file
+pub struct OpponentTable {
+    path: Option<PathBuf>,
+    profiles: Mutex<BTreeMap<String, OpponentProfile>>,
+    /// the shout formats a profile can name, by name
+    formats: HashMap<String, Arc<dyn ShoutFormat>>,
+}
+
+impl OpponentTable {
+    /// # from_env
+    /// the table in `OPPONENTS_ENV`, or one that knows nobody and learns nothing when it isn't set
+    pub fn from_env() -> Self {
+        return match std::env::var(OPPONENTS_ENV) {
+            Ok(path) => OpponentTable::load(PathBuf::from(path)),
+            Err(_) => OpponentTable::disabled(),
+        };
+    }
+
+    /// # disabled
+    /// a table that knows nobody and learns nothing
+    pub fn disabled() -> Self {
+        return OpponentTable {
+            path: None,
+            profiles: Mutex::new(BTreeMap::new()),
+            formats: default_formats(),
+        };
+    }
+
+    /// # load
+    /// reads the table from a file, a file that isn't there yet starts an empty table that's written there
+    pub fn load(path: PathBuf) -> Self {
+        let profiles = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+                warn!("couldn't parse {}, starting over: {}", path.display(), err);
+                return BTreeMap::new();
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        return OpponentTable {
+            path: Some(path),
+            profiles: Mutex::new(profiles),
+            formats: default_formats(),
+        };
+    }
+
+    /// # with_shout_format
+    /// lets the profiles name another shout format
+    /// ## Arguments:
+    /// * name - what the profiles call it
+    /// * format - how to read it
+    #[allow(dead_code)] // the strategies that know an opponent's own format register it through this
+    pub fn with_shout_format(mut self, name: &str, format: impl ShoutFormat + 'static) -> Self {
+        self.formats.insert(name.to_string(), Arc::new(format));
+        return self;
+    }
+
+    fn profiles(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, OpponentProfile>> {
+        return self
+            .profiles
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+
+    /// # recognize
+    /// looks up the opponents of a game
+    /// ## Returns:
+    /// the stance for every opponent with a habit, by snake id
+    pub fn recognize(&self, state: &types::GameState) -> Stances {
+        let profiles = self.profiles();
+        let mut stances = Stances::new();
+        for snake in state
+            .board
+            .snakes
+            .iter()
+            .filter(|snake| snake.id != state.you.id)
+        {
+            let stance = profiles
+                .get(&snake.name)
+                .map_or(Stance::Neutral, OpponentProfile::stance);
+            if stance != Stance::Neutral {
+                info!(
+                    "GAME {}: {} is known, playing it as {:?}",
+                    state.game.id, snake.name, stance
+                );
+                stances.insert(snake.id.clone(), stance);
+            }
+        }
+        return stances;
+    }
+
+    /// # shout_readers
+    /// looks up how to read the shouts of a game's opponents, only the opponents we know with a format we know are read
+    /// ## Returns:
+    /// the shout format of every opponent that announces its moves, by snake id
+    pub fn shout_readers(&self, state: &types::GameState) -> ShoutReaders {
+        let profiles = self.profiles();
+        let mut readers = ShoutReaders::new();
+        for snake in state
+            .board
+            .snakes
+            .iter()
+            .filter(|snake| snake.id != state.you.id)
+        {
+            let Some(name) = profiles
+                .get(&snake.name)
+                .and_then(|profile| profile.shout_format.as_ref())
+            else {
+                continue;
+            };
+            match self.formats.get(name) {
+                Some(format) => {
+                    readers.insert(snake.id.clone(), Arc::clone(format));
+                }
+                None => warn!("{} shouts in {}, a format we can't read", snake.name, name),
+            }
+        }
+        return readers;
+    }
+
+    /// # learn
+    /// adds a finished game's habits to the table and writes it out
+    /// ## Arguments:
+    /// * habits - what each opponent did in the game, by name
+    pub fn learn(&self, habits: &HashMap<String, OpponentProfile>) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if habits.is_empty() {
+            return;
+        }
+        let contents = {
+            let mut profiles = self.profiles();
+            for (name, habit) in habits {
+                profiles.entry(name.clone()).or_default().add(habit);
+            }
+            serde_json::to_string_pretty(&*profiles)
+        };
+        // written beside the table and renamed over it, a crash mid write leaves the old table
+        let written = path.with_extension("json.tmp");
+        let saved = contents
+            .map_err(|err| err.to_string())
+            .and_then(|contents| fs::write(&written, contents).map_err(|err| err.to_string()))
+            .and_then(|_| fs::rename(&written, path).map_err(|err| err.to_string()));
+        if let Err(err) = saved {
+            warn!("couldn't save the opponents to {}: {}", path.display(), err);
+        }
+    }
+}
+
+fn default_formats() -> HashMap<String, Arc<dyn ShoutFormat>> {
+    let named: Arc<dyn ShoutFormat> = Arc::new(NamedMove);
+    return HashMap::from([("named_move".to_string(), named)]);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::logic::{self, StrategyProfile};
+    use std::env;
+    use std::time::{Duration, Instant};
+
+    const CONTESTED_FOOD: &str = "testdata/fixtures/contested_food.json";
+
+    fn contested_food() -> types::GameState {
+        return serde_json::from_str(&fs::read_to_string(CONTESTED_FOOD).unwrap()).unwrap();
+    }
+
+    fn chosen(state: &types::GameState, stances: &Stances) -> String {
+        return chosen_announced(state, stances, &Announced::new());
+    }
+
+    fn chosen_announced(
+        state: &types::GameState,
+        stances: &Stances,
+        announced: &Announced,
+    ) -> String {
+        let deadline = Instant::now() + Duration::from_millis(300);
+        let decision = logic::get_move_against(
+            &state.game,
+            &state.turn,
+            &state.board,
+            &state.you,
+            &StrategyProfile::default(),
+            stances,
+            announced,
+            None,
+            &[],
+            None,
+            deadline,
+        )
+        .unwrap();
+        return decision.response["move"].as_str().unwrap().to_string();
+    }
+
+    #[test]
+    fn known_passive_snake_changes_the_move() {
+        let state = contested_food();
+        // neutral, the food above is next to the head of a snake as long as us
+        assert_ne!(
+            chosen(&state, &OpponentTable::disabled().recognize(&state)),
+            "up"
+        );
+
+        let path =
+            env::temp_dir().join(format!("battlesnake-opponents-{}.json", std::process::id()));
+        let passive = OpponentProfile {
+            games: 10,
+            head_chances: 10,
+            head_approaches: 1,
+            food_chances: 10,
+            food_contests: 1,
+            ..Default::default()
+        };
+        let table = BTreeMap::from([("snake mTOl1", passive)]);
+        fs::write(&path, serde_json::to_string(&table).unwrap()).unwrap();
+        let table = OpponentTable::load(path.clone());
+        fs::remove_file(&path).unwrap();
+        let stances = table.recognize(&state);
+        assert_eq!(stances.get("mTOl1"), Some(&Stance::Passive));
+        assert_eq!(chosen(&state, &stances), "up");
+    }
+
+    #[test]
+    fn habits_are_learnt_and_saved() {
+        let before = contested_food();
+        // the other snake takes the food on its left toward our head
+        let mut after = before.clone();
+        after.turn += 1;
+        let other = &mut after.board.snakes[0];
+        other.body.insert(0, Coord { x: 5, y: 5 });
+        other.head = Coord { x: 5, y: 5 };
+        after.board.food.clear();
+        after.board.snakes[1].head = Coord { x: 6, y: 4 };
+
+        let mut habits = HashMap::new();
+        observe(&before, &after, &mut habits);
+        let expected = OpponentProfile {
+            games: 1,
+            head_chances: 1,
+            head_approaches: 1,
+            food_chances: 1,
+            food_contests: 1,
+            ..Default::default()
+        };
+        assert_eq!(habits["snake mTOl1"], expected);
+        // a request that isn't the next turn is skipped
+        observe(&before, &before, &mut habits);
+        assert_eq!(habits["snake mTOl1"], expected);
+
+        let path = env::temp_dir().join(format!(
+            "battlesnake-opponents-learnt-{}.json",
+            std::process::id()
+        ));
+        let table = OpponentTable::load(path.clone());
+        for _ in 0..MIN_CHANCES {
+            table.learn(&habits);
+        }
+        let reloaded = OpponentTable::load(path.clone());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(reloaded.profiles()["snake mTOl1"].games, MIN_CHANCES);
+        assert_eq!(
+            reloaded.recognize(&before).get("mTOl1"),
+            Some(&Stance::Aggressive)
+        );
+    }
+
+    #[test]
+    fn stances_need_enough_chances() {
+        let mut profile = OpponentProfile {
+            head_chances: MIN_CHANCES - 1,
+            head_approaches: MIN_CHANCES - 1,
+            ..Default::default()
+        };
+        assert_eq!(profile.stance(), Stance::Neutral);
+        profile.head_chances += 1;
+        assert_eq!(profile.stance(), Stance::Aggressive);
+        profile.head_approaches = 0;
+        profile.food_chances = MIN_CHANCES;
+        assert_eq!(profile.stance(), Stance::Passive);
+        profile.food_contests = MIN_CHANCES;
+        assert_eq!(profile.stance(), Stance::Neutral);
+    }
+
+    /// a snake that announces its moves as `>U`, `>R`, `>D` or `>L`
+    #[derive(Debug)]
+    struct Arrow;
+
+    impl ShoutFormat for Arrow {
+        fn announced_move(&self, shout: &str) -> Option<Coord> {
+            let mv = match shout.strip_prefix('>')? {
+                "U" => "up",
+                "R" => "right",
+                "D" => "down",
+                "L" => "left",
+                _ => return None,
+            };
+            return types::DIRECTIONS.get(mv).copied();
+        }
+    }
+
+    #[test]
+    fn announced_move_shifts_the_head_danger() {
+        let path = env::temp_dir().join(format!(
+            "battlesnake-opponents-announcing-{}.json",
+            std::process::id()
+        ));
+        let announcer = OpponentProfile {
+            games: 1,
+            shout_format: Some("arrow".to_string()),
+            ..Default::default()
+        };
+        fs::write(
+            &path,
+            serde_json::to_string(&BTreeMap::from([("snake mTOl1", announcer)])).unwrap(),
+        )
+        .unwrap();
+        let table = OpponentTable::load(path.clone()).with_shout_format("arrow", Arrow);
+        fs::remove_file(&path).unwrap();
+
+        let mut state = contested_food();
+        let readers = table.shout_readers(&state);
+        assert!(readers.contains_key("mTOl1"));
+        let stances = table.recognize(&state);
+        assert!(stances.is_empty());
+        let turn = |state: &types::GameState| {
+            return chosen_announced(state, &stances, &announced(&state.board, &readers));
+        };
+
+        // it says it's going up, away from the food next to both our heads
+        state.board.snakes[0].shout = Some(">U".to_string());
+        assert_eq!(
+            announced(&state.board, &readers)["mTOl1"],
+            types::DIRECTIONS["up"]
+        );
+        assert_eq!(turn(&state), "up");
+        // it says it's taking the food
+        state.board.snakes[0].shout = Some(">R".to_string());
+        assert_ne!(turn(&state), "up");
+        // a shout it doesn't announce anything with
+        state.board.snakes[0].shout = Some("gg".to_string());
+        assert!(announced(&state.board, &readers).is_empty());
+        assert_ne!(turn(&state), "up");
+
+        // a snake we don't know isn't read, and neither is a format nobody registered
+        assert!(OpponentTable::disabled().shout_readers(&state).is_empty());
+        let unregistered = OpponentTable::load(path.clone());
+        unregistered.profiles().insert(
+            "snake mTOl1".to_string(),
+            OpponentProfile {
+                shout_format: Some("arrow".to_string()),
+                ..Default::default()
+            },
+        );
+        assert!(unregistered.shout_readers(&state).is_empty());
+    }
+
+    #[test]
+    fn named_moves_are_read() {
+        assert_eq!(
+            NamedMove.announced_move("left"),
+            Some(types::DIRECTIONS["left"])
+        );
+        assert_eq!(
+            NamedMove.announced_move("going UP!"),
+            Some(types::DIRECTIONS["up"])
+        );
+        assert_eq!(NamedMove.announced_move("downright rude"), None);
+        assert_eq!(NamedMove.announced_move(""), None);
+    }
+}
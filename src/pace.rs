@@ -0,0 +1,211 @@
+//! how hard the opponents are thinking, read off the latency the engine reports for each of them. an opponent that
+//! keeps answering near the timeout has no time left to out-search us, so against a table of them the endgame solver
+//! can take more of our own timeout and look further. one answering in a few milliseconds either searches nothing or
+//! searches very fast, and the only safe assumption is the second, so against them we search paranoid
+
+use crate::types;
+use log::info;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+
+/// how many of an opponent's latest latencies its average is taken over
+pub const LATENCY_WINDOW: usize = 10;
+
+/// the latencies an opponent has to have reported before its average counts
+pub const MIN_SAMPLES: usize = 3;
+
+/// an opponent whose average latency is at least this is slow
+pub const SLOW_LATENCY_MS: f32 = 400.0;
+
+/// an opponent whose average latency is at most this is fast
+pub const FAST_LATENCY_MS: f32 = 50.0;
+
+/// what the endgame solver's share of the timeout is multiplied by against slow opponents
+pub const SLOW_SHARE_FACTOR: f32 = 1.5;
+
+/// the most of the timeout the endgame solver gets against slow opponents
+pub const MAX_SLOW_SHARE: f32 = 0.5;
+
+/// how much deeper the endgame solver goes against slow opponents
+pub const SLOW_EXTRA_DEPTH: u8 = 32;
+
+/// how hard the opponents still in the game are thinking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pace {
+    /// too few reports to tell, or a mix of slow and middling opponents
+    #[default]
+    Unread,
+    /// every opponent answers near its limit
+    Slow,
+    /// at least one opponent answers in next to no time
+    Fast,
+}
+
+/// the latencies each opponent has reported, kept with the game's memory
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaceTracker {
+    /// the latest `LATENCY_WINDOW` latencies of each opponent, oldest first, by snake id
+    latencies: HashMap<String, VecDeque<u32>>,
+    pace: Pace,
+    /// the latest turn observed
+    turn: Option<u32>,
+}
+
+impl Default for PaceTracker {
+    fn default() -> Self {
+        return PaceTracker {
+            latencies: HashMap::new(),
+            pace: Pace::default(),
+            turn: None,
+        };
+    }
+}
+
+impl PaceTracker {
+    /// # observe
+    /// records the latency each opponent reported on a turn. a turn already seen, as a replayed request is, changes
+    /// nothing
+    /// ## Arguments:
+    /// * board - the battlesnake game board
+    /// * you_id - the id of our snake
+    /// * turn - the turn number
+    /// ## Returns:
+    /// the pace of the opponents still on the board
+    pub fn observe(&mut self, board: &types::Board, you_id: &str, turn: u32) -> Pace {
+        if self.turn.is_some_and(|seen| turn <= seen) {
+            return self.pace;
+        }
+        self.turn = Some(turn);
+        let opponents: Vec<&types::Battlesnake> = board.snakes.iter().filter(|snake| snake.id != you_id).collect();
+        for snake in &opponents {
+            if let Some(latency) = snake.latency {
+                let window = self.latencies.entry(snake.id.clone()).or_default();
+                window.push_back(latency);
+                if window.len() > LATENCY_WINDOW {
+                    window.pop_front();
+                }
+            }
+        }
+        let averages: Vec<Option<f32>> = opponents.iter().map(|snake| self.average(&snake.id)).collect();
+        let slow = |average: &Option<f32>| average.is_some_and(|ms| ms >= SLOW_LATENCY_MS);
+        let pace = if averages.iter().flatten().any(|average| *average <= FAST_LATENCY_MS) {
+            Pace::Fast
+        } else if !averages.is_empty() && averages.iter().all(slow) {
+            Pace::Slow
+        } else {
+            Pace::Unread
+        };
+        if pace != self.pace {
+            info!("TURN {}: the opponents' pace went from {:?} to {:?}", turn, self.pace, pace);
+            self.pace = pace;
+        }
+        return self.pace;
+    }
+
+    /// # average
+    /// an opponent's average latency over its latest `LATENCY_WINDOW` reports
+    /// ## Returns:
+    /// the average in milliseconds, none until the opponent has reported `MIN_SAMPLES` latencies
+    pub fn average(&self, id: &str) -> Option<f32> {
+        let window = self.latencies.get(id).filter(|window| window.len() >= MIN_SAMPLES)?;
+        return Some(window.iter().sum::<u32>() as f32 / window.len() as f32);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::logic::StrategyProfile;
+    use crate::search::endgame;
+    use crate::search::minimax::SearchMode;
+    use serde_json::{json, Value};
+
+    /// a board with us and an opponent for each latency, `null` for one that didn't report any
+    fn board(latencies: &[Value]) -> types::Board {
+        let mut snakes = vec![json!({
+            "id": "me", "name": "me", "health": 90, "head": { "x": 0, "y": 0 }, "body": [{ "x": 0, "y": 0 }],
+            "length": 1, "latency": "12",
+        })];
+        for (i, latency) in latencies.iter().enumerate() {
+            snakes.push(json!({
+                "id": format!("s{}", i), "name": "s", "health": 90, "head": { "x": i + 1, "y": 5 },
+                "body": [{ "x": i + 1, "y": 5 }], "length": 1, "latency": latency,
+            }));
+        }
+        return serde_json::from_value(json!({
+            "width": 11, "height": 11, "food": [], "hazards": [], "snakes": snakes,
+        }))
+        .unwrap();
+    }
+
+    /// plays the turns through a tracker, each turn's latencies given by `latencies`
+    fn play(turns: std::ops::Range<u32>, latencies: impl Fn(u32) -> Vec<Value>) -> (PaceTracker, Vec<Pace>) {
+        let mut tracker = PaceTracker::default();
+        let paces = turns.map(|turn| tracker.observe(&board(&latencies(turn)), "me", turn)).collect();
+        return (tracker, paces);
+    }
+
+    #[test]
+    fn latency_is_read_from_strings_and_numbers() {
+        let board = board(&[json!("452"), json!(37), json!(""), json!("0"), Value::Null]);
+        let latencies: Vec<_> = board.snakes.iter().map(|snake| snake.latency).collect();
+        assert_eq!(latencies, [Some(12), Some(452), Some(37), None, None, None]);
+        let without: types::Battlesnake = serde_json::from_value(json!({
+            "id": "s", "name": "s", "health": 90, "head": { "x": 0, "y": 0 }, "body": [], "length": 0,
+        }))
+        .unwrap();
+        assert_eq!(without.latency, None);
+    }
+
+    #[test]
+    fn slow_opponents_get_a_deeper_endgame() {
+        let (tracker, paces) = play(0..12, |turn| vec![json!(format!("{}", 420 + turn * 3)), json!(480)]);
+        // the first turns are too few to go on
+        assert_eq!(paces[..MIN_SAMPLES - 1], [Pace::Unread, Pace::Unread]);
+        assert!(paces[MIN_SAMPLES - 1..].iter().all(|pace| *pace == Pace::Slow));
+        // the average only covers the latest reports
+        assert_eq!(tracker.average("s0"), Some(420.0 + 3.0 * 6.5));
+
+        let profile = StrategyProfile::DEFAULT.paced(Pace::Slow);
+        assert_eq!(profile.endgame_depth, endgame::MAX_DEPTH + SLOW_EXTRA_DEPTH);
+        assert!(profile.endgame_share > StrategyProfile::DEFAULT.endgame_share);
+        assert!(profile.endgame_share <= MAX_SLOW_SHARE);
+        assert_eq!(StrategyProfile::DEFAULT.paced(Pace::Unread), StrategyProfile::DEFAULT);
+    }
+
+    #[test]
+    fn fast_opponents_get_paranoid_search() {
+        // one quick opponent among slow ones is enough
+        let (_, paces) = play(0..8, |_| vec![json!("20"), json!("450")]);
+        assert_eq!(paces.last(), Some(&Pace::Fast));
+
+        let expectimax = StrategyProfile {
+            search: SearchMode::Expectimax,
+            ..StrategyProfile::DEFAULT
+        };
+        let profile = expectimax.paced(Pace::Fast);
+        assert_eq!(profile.search, SearchMode::Paranoid);
+        assert_eq!(profile.endgame_depth, endgame::MAX_DEPTH);
+        assert_eq!(profile.endgame_share, expectimax.endgame_share);
+    }
+
+    #[test]
+    fn the_pace_follows_the_opponents_still_playing() {
+        let mut tracker = PaceTracker::default();
+        for turn in 0..5 {
+            tracker.observe(&board(&[json!(500), json!(30)]), "me", turn);
+        }
+        // a slow opponent that speeds up is only counted fast once its whole window is
+        for turn in 5..20 {
+            let pace = tracker.observe(&board(&[json!(500)]), "me", turn);
+            assert_eq!(pace, Pace::Slow, "turn {}", turn);
+        }
+        let paces: Vec<_> = (20..30).map(|turn| tracker.observe(&board(&[json!(10)]), "me", turn)).collect();
+        assert_eq!(paces.iter().position(|pace| *pace == Pace::Fast), Some(LATENCY_WINDOW - 1));
+        // a replayed turn isn't counted twice
+        let before = tracker.average("s0");
+        tracker.observe(&board(&[json!(10)]), "me", 29);
+        assert_eq!(tracker.average("s0"), before);
+    }
+}
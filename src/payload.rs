@@ -0,0 +1,281 @@
+use crate::types;
+use log::warn;
+use rocket::data::{self, ByteUnit, Data, FromData};
+use rocket::http::Status;
+use rocket::Request;
+use serde_json::{json, Value};
+
+/// how much of a request we couldn't read goes in the log
+const LOGGED_BODY_LEN: usize = 500;
+
+/// the most of a request body that's read, far more than the largest board needs
+const BODY_LIMIT: ByteUnit = ByteUnit::Mebibyte(1);
+
+/// the timeout assumed when a request doesn't say
+const DEFAULT_TIMEOUT: u32 = 500;
+
+/// a request body as it arrived, whatever its content type. bytes that aren't utf-8 are replaced so a garbled body
+/// fails to parse instead of failing the request
+pub struct RawBody(pub String);
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for RawBody {
+    type Error = std::io::Error;
+
+    async fn from_data(_req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        // async_trait rewrites the body, an explicit return here trips clippy
+        match data.open(BODY_LIMIT).into_bytes().await {
+            Ok(bytes) => {
+                data::Outcome::Success(RawBody(String::from_utf8_lossy(&bytes).into_owned()))
+            }
+            Err(err) => data::Outcome::Failure((Status::BadRequest, err)),
+        }
+    }
+}
+
+/// # parse
+/// reads a request from the engine, first as the API describes it and then leniently, filling in what's missing
+/// and coercing what has the wrong type. a request that reads but doesn't add up is kept, with a warning
+/// ## Arguments:
+/// * request - `start`, `move` or `end`, for the log
+/// * body - the raw request body
+/// ## Returns:
+/// the request, none if it couldn't be read at all
+pub fn parse(request: &str, body: &str) -> Option<types::GameState> {
+    let state = match serde_json::from_str::<types::GameState>(body) {
+        Ok(state) => state,
+        Err(strict) => match lenient(body) {
+            Some(state) => {
+                warn!("{} request read leniently: {}", request, strict);
+                state
+            }
+            None => {
+                warn!(
+                    "{} request couldn't be read: {}, body: {}",
+                    request,
+                    strict,
+                    truncate(body, LOGGED_BODY_LEN)
+                );
+                return None;
+            }
+        },
+    };
+    for problem in validate(&state) {
+        warn!(
+            "{} request for game {}: {}",
+            request, state.game.id, problem
+        );
+    }
+    return Some(state);
+}
+
+/// # validate
+/// the ways a request contradicts itself
+/// ## Returns:
+/// a description of each problem, empty when there are none
+pub fn validate(state: &types::GameState) -> Vec<String> {
+    let mut problems = vec![];
+    let board = &state.board;
+    let on_board = |tile: &types::Coord| {
+        return tile.x >= 0
+            && tile.y >= 0
+            && tile.x < board.width as i16
+            && tile.y < board.height as i16;
+    };
+    for snake in board.snakes.iter().chain([&state.you]) {
+        match snake.body.first() {
+            None => problems.push(format!("snake {} has no body", snake.id)),
+            Some(first) if *first != snake.head => problems.push(format!(
+                "snake {} has its head away from its body",
+                snake.id
+            )),
+            Some(_) => {}
+        }
+        if snake.length as usize != snake.body.len() {
+            problems.push(format!(
+                "snake {} has length {} but {} body parts",
+                snake.id,
+                snake.length,
+                snake.body.len()
+            ));
+        }
+        if !snake.body.iter().all(on_board) {
+            problems.push(format!("snake {} is partly off the board", snake.id));
+        }
+    }
+    if !board.snakes.iter().any(|snake| snake.id == state.you.id) {
+        problems.push("we're not among the snakes on the board".to_string());
+    }
+    if !board.food.iter().chain(&board.hazards).all(on_board) {
+        problems.push("food or hazards are off the board".to_string());
+    }
+    return problems;
+}
+
+/// # lenient
+/// rebuilds a request from whatever parts of it make sense, only the board size and our body are required
+fn lenient(body: &str) -> Option<types::GameState> {
+    let value: Value = serde_json::from_str(body).ok()?;
+    let game = &value["game"];
+    let board = &value["board"];
+    let coords = |value: &Value| -> Vec<Value> {
+        return value
+            .as_array()
+            .map(|tiles| tiles.iter().filter_map(coord).collect())
+            .unwrap_or_default();
+    };
+    let state = json!({
+        "game": {
+            "id": text(&game["id"]).unwrap_or_default(),
+            "ruleset": game["ruleset"].as_object().cloned().unwrap_or_default(),
+            "timeout": number(&game["timeout"]).map_or(DEFAULT_TIMEOUT as i64, |timeout| timeout.max(0)),
+        },
+        "turn": number(&value["turn"]).unwrap_or(0).max(0),
+        "board": {
+            "height": size(&board["height"])?,
+            "width": size(&board["width"])?,
+            "food": coords(&board["food"]),
+            "snakes": board["snakes"]
+                .as_array()
+                .map(|snakes| snakes.iter().filter_map(snake).collect::<Vec<_>>())
+                .unwrap_or_default(),
+            "hazards": coords(&board["hazards"]),
+        },
+        "you": snake(&value["you"])?,
+    });
+    return serde_json::from_value(state).ok();
+}
+
+/// a number that might have been sent as a float or a string
+fn number(value: &Value) -> Option<i64> {
+    return match value {
+        Value::Number(number) => number
+            .as_i64()
+            .or_else(|| number.as_f64().map(|float| float.round() as i64)),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    };
+}
+
+/// a string that might have been sent as a number
+fn text(value: &Value) -> Option<String> {
+    return match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    };
+}
+
+fn size(value: &Value) -> Option<u8> {
+    return number(value).and_then(|size| u8::try_from(size).ok());
+}
+
+fn coord(value: &Value) -> Option<Value> {
+    let x = i16::try_from(number(&value["x"])?).ok()?;
+    let y = i16::try_from(number(&value["y"])?).ok()?;
+    return Some(json!({ "x": x, "y": y }));
+}
+
+/// # snake
+/// rebuilds a snake, only its body is required
+fn snake(value: &Value) -> Option<Value> {
+    let body: Vec<Value> = value["body"].as_array()?.iter().filter_map(coord).collect();
+    if body.is_empty() {
+        return None;
+    }
+    let head = coord(&value["head"]).unwrap_or_else(|| body[0].clone());
+    let id = text(&value["id"]).unwrap_or_default();
+    let length = number(&value["length"]).unwrap_or(body.len() as i64);
+    return Some(json!({
+        "id": id,
+        "name": text(&value["name"]).unwrap_or_else(|| id.clone()),
+        "health": number(&value["health"]).unwrap_or(100).clamp(0, 100),
+        "head": head,
+        "length": length.max(0),
+        "body": body,
+        "shout": value["shout"].as_str(),
+    }));
+}
+
+/// # truncate
+/// the start of a string, cut on a character boundary
+fn truncate(text: &str, len: usize) -> &str {
+    return match text.char_indices().nth(len) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const STATE: &str = r#"{
+        "game": { "id": "g", "ruleset": { "name": "standard" }, "timeout": 500 },
+        "turn": 3,
+        "board": {
+            "height": 7,
+            "width": 7,
+            "food": [{ "x": 1, "y": 1 }],
+            "snakes": [{
+                "id": "me", "name": "me", "health": 90, "length": 3, "shout": "",
+                "head": { "x": 3, "y": 3 },
+                "body": [{ "x": 3, "y": 3 }, { "x": 3, "y": 2 }, { "x": 3, "y": 1 }]
+            }],
+            "hazards": []
+        },
+        "you": {
+            "id": "me", "name": "me", "health": 90, "length": 3, "shout": "",
+            "head": { "x": 3, "y": 3 },
+            "body": [{ "x": 3, "y": 3 }, { "x": 3, "y": 2 }, { "x": 3, "y": 1 }]
+        }
+    }"#;
+
+    #[test]
+    fn well_formed_request_is_valid() {
+        let state = parse("move", STATE).unwrap();
+        assert_eq!(state.turn, 3);
+        assert!(validate(&state).is_empty());
+    }
+
+    #[test]
+    fn wrong_types_and_missing_fields_read_leniently() {
+        let mut value: Value = serde_json::from_str(STATE).unwrap();
+        value["turn"] = json!("3");
+        value["you"]["health"] = json!(89.6);
+        value["you"]["id"] = json!(7);
+        value["board"]["snakes"][0]["head"] = Value::Null;
+        value["board"]["snakes"][0]["length"] = Value::Null;
+        value["board"].as_object_mut().unwrap().remove("hazards");
+        value.as_object_mut().unwrap().remove("game");
+        assert!(serde_json::from_value::<types::GameState>(value.clone()).is_err());
+
+        let state = parse("move", &value.to_string()).unwrap();
+        assert_eq!(state.turn, 3);
+        assert_eq!(state.you.health, 90);
+        assert_eq!(state.you.id, "7");
+        assert_eq!(state.board.snakes[0].head, types::Coord { x: 3, y: 3 });
+        assert_eq!(state.board.snakes[0].length, 3);
+        assert_eq!(state.game.timeout, DEFAULT_TIMEOUT);
+        // our id was mangled so we no longer match our snake on the board
+        assert_eq!(
+            validate(&state),
+            ["we're not among the snakes on the board"]
+        );
+    }
+
+    #[test]
+    fn unreadable_requests_are_rejected() {
+        assert!(parse("move", "").is_none());
+        assert!(parse("move", &STATE[..STATE.len() / 2]).is_none());
+        let mut value: Value = serde_json::from_str(STATE).unwrap();
+        value.as_object_mut().unwrap().remove("you");
+        assert!(parse("move", &value.to_string()).is_none());
+    }
+
+    #[test]
+    fn truncate_keeps_whole_characters() {
+        assert_eq!(truncate("héllo", 2), "hé");
+        assert_eq!(truncate("hi", 5), "hi");
+    }
+}
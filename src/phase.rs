@@ -0,0 +1,250 @@
+//! where a game is: the early game while everyone's short and the board is open, the midgame of jockeying for space
+//! and the late game of two snakes in tight space. each wants its own move weights, so the profile carries a set for
+//! each phase and the game's memory settles on the phase a game is in, holding it until a new one has lasted
+
+use crate::logic;
+use crate::search::eval::Weights;
+use crate::types;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+/// the game is early until this turn
+pub const EARLY_TURNS: u32 = 30;
+
+/// the game is early while at least this share of the board is free
+pub const EARLY_FREE_SHARE: f32 = 0.8;
+
+/// the game is late once it's down to two snakes and at most this share of the board is free
+pub const LATE_FREE_SHARE: f32 = 0.75;
+
+/// the turns in a row a position has to look like a new phase before the game moves to it
+pub const PHASE_HOLD: u32 = 3;
+
+/// the part of the game we're in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// everyone's short and food is plentiful
+    Early,
+    /// jockeying for space
+    Mid,
+    /// two snakes left in tight space
+    Late,
+}
+
+/// # classify
+/// the phase a position looks like on its own
+/// ## Arguments:
+/// * board - the battlesnake game board
+/// * turn - the turn number
+/// * free_tiles - the tiles without a snake or a hazard on them, see `logic::num_free_tiles`
+pub fn classify(board: &types::Board, turn: u32, free_tiles: u16) -> Phase {
+    let free_share = free_tiles as f32 / (board.width as f32 * board.height as f32);
+    if turn < EARLY_TURNS && free_share >= EARLY_FREE_SHARE {
+        return Phase::Early;
+    }
+    if board.snakes.len() <= 2 && free_share <= LATE_FREE_SHARE {
+        return Phase::Late;
+    }
+    return Phase::Mid;
+}
+
+/// the move weights for each phase, a phase left out of a config keeps its default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhaseWeights {
+    pub early: Weights,
+    pub mid: Weights,
+    pub late: Weights,
+}
+
+impl PhaseWeights {
+    /// the early game leans toward food, the late game only on the room a move leaves
+    pub const DEFAULT: PhaseWeights = PhaseWeights {
+        early: Weights {
+            food: 6,
+            ..Weights::DEFAULT
+        },
+        mid: Weights::DEFAULT,
+        late: Weights {
+            degree: 20,
+            food: 0,
+            ..Weights::DEFAULT
+        },
+    };
+
+    /// # phases_mut
+    /// the weights of every phase with the phase's name, early to late
+    pub fn phases_mut(&mut self) -> [(&'static str, &mut Weights); 3] {
+        return [("early", &mut self.early), ("mid", &mut self.mid), ("late", &mut self.late)];
+    }
+
+    /// # weights
+    /// the weights a phase plays with
+    pub fn weights(&self, phase: Phase) -> Weights {
+        return match phase {
+            Phase::Early => self.early,
+            Phase::Mid => self.mid,
+            Phase::Late => self.late,
+        };
+    }
+}
+
+impl Default for PhaseWeights {
+    fn default() -> Self {
+        return PhaseWeights::DEFAULT;
+    }
+}
+
+/// the phase a game is in, kept with its memory
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseTracker {
+    phase: Phase,
+    /// the phase the latest turns looked like when it isn't `phase`, and for how many turns in a row
+    pending: Option<(Phase, u32)>,
+    /// the latest turn observed
+    turn: Option<u32>,
+}
+
+impl Default for PhaseTracker {
+    fn default() -> Self {
+        return PhaseTracker {
+            phase: Phase::Early,
+            pending: None,
+            turn: None,
+        };
+    }
+}
+
+impl PhaseTracker {
+    /// # observe
+    /// moves the game on to a turn. the first turn seen sets the phase outright, after that a position has to look
+    /// like a new phase for `PHASE_HOLD` turns in a row before the game moves to it. a turn already seen, as a replayed
+    /// request is, changes nothing
+    /// ## Arguments:
+    /// * board - the battlesnake game board
+    /// * turn - the turn number
+    /// ## Returns:
+    /// the phase the game is in
+    pub fn observe(&mut self, board: &types::Board, turn: u32) -> Phase {
+        if self.turn.is_some_and(|seen| turn <= seen) {
+            return self.phase;
+        }
+        let looks = classify(board, turn, logic::num_free_tiles(board));
+        if self.turn.is_none() {
+            self.turn = Some(turn);
+            self.phase = looks;
+            return self.phase;
+        }
+        self.turn = Some(turn);
+        if looks == self.phase {
+            self.pending = None;
+            return self.phase;
+        }
+        let streak = match self.pending {
+            Some((pending, streak)) if pending == looks => streak + 1,
+            _ => 1,
+        };
+        if streak < PHASE_HOLD {
+            self.pending = Some((looks, streak));
+            return self.phase;
+        }
+        info!("TURN {}: the game moved from the {:?} phase to the {:?} phase", turn, self.phase, looks);
+        self.phase = looks;
+        self.pending = None;
+        return self.phase;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    /// an 11x11 board with `snakes` snakes of a length each, up to 22, laid along their own pair of rows
+    fn board(snakes: usize, length: usize) -> types::Board {
+        let snakes: Vec<_> = (0..snakes)
+            .map(|i| {
+                let body: Vec<_> =
+                    (0..length as i64).map(|k| json!({ "x": k % 11, "y": i as i64 * 3 + k / 11 })).collect();
+                return json!({
+                    "id": format!("s{}", i), "name": "s", "health": 90, "head": body[0], "body": body,
+                    "length": length,
+                });
+            })
+            .collect();
+        return serde_json::from_value(json!({
+            "width": 11, "height": 11, "food": [], "hazards": [], "snakes": snakes,
+        }))
+        .unwrap();
+    }
+
+    /// a game of four snakes that grow a tile every ten turns until two of them are out at turn 60, the two left
+    /// grow a tile every five
+    fn synthetic(turn: u32) -> types::Board {
+        if turn < 60 {
+            return board(4, 3 + turn as usize / 10);
+        }
+        return board(2, 3 + turn as usize / 5);
+    }
+
+    fn classify_synthetic(turn: u32) -> Phase {
+        let board = synthetic(turn);
+        return classify(&board, turn, logic::num_free_tiles(&board));
+    }
+
+    #[test]
+    fn classifies_by_turn_snakes_and_space() {
+        assert_eq!(classify_synthetic(0), Phase::Early);
+        assert_eq!(classify_synthetic(EARLY_TURNS - 1), Phase::Early);
+        // still short but past the opening
+        assert_eq!(classify_synthetic(EARLY_TURNS), Phase::Mid);
+        // down to two snakes of 15, 91 of the 121 tiles are still free
+        assert_eq!(classify_synthetic(60), Phase::Mid);
+        // two snakes of 16 leave less than three quarters free
+        assert_eq!(classify_synthetic(65), Phase::Late);
+        // early turns on a crowded board are already the midgame
+        let crowded = board(4, 7);
+        assert_eq!(classify(&crowded, 0, logic::num_free_tiles(&crowded)), Phase::Mid);
+    }
+
+    #[test]
+    fn a_synthetic_game_moves_through_every_phase() {
+        let mut tracker = PhaseTracker::default();
+        let mut transitions: Vec<(u32, Phase)> = vec![];
+        for turn in 0..100 {
+            let phase = tracker.observe(&synthetic(turn), turn);
+            if transitions.last().is_none_or(|(_, before)| *before != phase) {
+                transitions.push((turn, phase));
+            }
+        }
+        // after the first turn, each phase is taken up on the third turn in a row the board looks like it
+        assert_eq!(
+            transitions,
+            [(0, Phase::Early), (EARLY_TURNS + PHASE_HOLD - 1, Phase::Mid), (65 + PHASE_HOLD - 1, Phase::Late)]
+        );
+    }
+
+    #[test]
+    fn a_flapping_position_holds_its_phase() {
+        let mut tracker = PhaseTracker::default();
+        assert_eq!(tracker.observe(&board(4, 3), 40), Phase::Mid);
+        // late and mid by turns, never late long enough to move
+        for turn in 41..=60 {
+            let board = if turn % 3 == 0 { board(4, 3) } else { board(2, 20) };
+            assert_eq!(tracker.observe(&board, turn), Phase::Mid, "turn {}", turn);
+        }
+        // a replayed turn doesn't count toward the streak
+        let late = board(2, 20);
+        assert_eq!(tracker.observe(&late, 61), Phase::Mid);
+        assert_eq!(tracker.observe(&late, 61), Phase::Mid);
+        assert_eq!(tracker.observe(&late, 62), Phase::Mid);
+        assert_eq!(tracker.observe(&late, 63), Phase::Late);
+    }
+
+    #[test]
+    fn a_game_picked_up_midway_starts_in_its_phase() {
+        let mut tracker = PhaseTracker::default();
+        assert_eq!(tracker.observe(&board(2, 20), 150), Phase::Late);
+    }
+}
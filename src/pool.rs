@@ -0,0 +1,75 @@
+use log::warn;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::{env, thread};
+
+/// the number of threads computing moves, the machine's parallelism when it isn't set
+pub const COMPUTE_THREADS_ENV: &str = "BATTLESNAKE_COMPUTE_THREADS";
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// a fixed set of threads for the move pipeline, so slow turns queue up among themselves instead of tying up the
+/// server's workers. the queue is bounded, a move that doesn't fit is answered with the fallback right away
+pub struct ComputePool {
+    sender: SyncSender<Job>,
+}
+
+impl ComputePool {
+    /// # new
+    /// starts the pool's threads
+    /// ## Arguments:
+    /// * threads - how many moves are computed at once
+    /// * queue - how many more wait for a thread before the pool counts as saturated
+    pub fn new(threads: usize, queue: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Job>(queue);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for worker in 0..threads.max(1) {
+            let receiver = Arc::clone(&receiver);
+            thread::Builder::new()
+                .name(format!("compute-{}", worker))
+                .spawn(move || work(&receiver))
+                .expect("couldn't start a compute thread");
+        }
+        return ComputePool { sender };
+    }
+
+    /// # from_env
+    /// a pool with `COMPUTE_THREADS_ENV` threads and as many places in the queue
+    pub fn from_env() -> Self {
+        let threads = env::var(COMPUTE_THREADS_ENV)
+            .ok()
+            .and_then(|threads| threads.parse().ok())
+            .unwrap_or_else(|| thread::available_parallelism().map_or(4, |threads| threads.get()));
+        return ComputePool::new(threads, threads);
+    }
+
+    /// # try_run
+    /// queues a job for the next free thread
+    /// ## Returns:
+    /// false if the queue is full and the job was dropped
+    pub fn try_run<F>(&self, job: F) -> bool
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        return self.sender.try_send(Box::new(job)).is_ok();
+    }
+}
+
+fn work(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = receiver
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .recv();
+        let job = match job {
+            Ok(job) => job,
+            // the pool is gone
+            Err(_) => return,
+        };
+        // a panicking move is answered with the fallback, the thread carries on
+        if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+            warn!("a move computation panicked");
+        }
+    }
+}
@@ -0,0 +1,452 @@
+use crate::types::{self, Coord, Ruleset};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::{env, fs, path::Path};
+
+/// set to a directory to save a JSON post-mortem of every game there
+pub(crate) const POSTMORTEM_DIR_ENV: &str = "BATTLESNAKE_POSTMORTEM_DIR";
+
+/// a turn we played: the position we were sent and the move we answered with
+#[derive(Clone, Debug)]
+pub struct Turn {
+    pub turn: u32,
+    pub board: types::Board,
+    pub you: types::Battlesnake,
+    pub chosen: String,
+}
+
+/// why our snake was eliminated
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "cause", rename_all = "snake_case")]
+pub enum Death {
+    /// moved onto the same tile as a snake at least as long
+    HeadToHead {
+        opponent: String,
+        opponent_length: u32,
+    },
+    /// ran into a wall or a body after being shut into less space than our length
+    SelfTrap {
+        since_turn: u32,
+        space: usize,
+        length: u32,
+    },
+    Starvation,
+    /// the hazard damage on the last move took the rest of our health
+    HazardDrain,
+    Wall,
+    /// ran into a body, `snake` is our own id when it was ours
+    Body {
+        snake: String,
+    },
+    /// nothing in the history explains it, usually because the history is empty
+    Unknown,
+}
+
+impl Death {
+    /// # cause
+    /// the name of what killed us, as the `cause` tag serializes it
+    pub fn cause(&self) -> &'static str {
+        return match self {
+            Death::HeadToHead { .. } => "head_to_head",
+            Death::SelfTrap { .. } => "self_trap",
+            Death::Starvation => "starvation",
+            Death::HazardDrain => "hazard_drain",
+            Death::Wall => "wall",
+            Death::Body { .. } => "body",
+            Death::Unknown => "unknown",
+        };
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GameResult {
+    Win,
+    /// we went out on the same turn as the last of the other snakes
+    Draw,
+    Loss,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PostMortem {
+    pub game_id: String,
+    pub turn: u32,
+    pub result: GameResult,
+    /// set when we didn't survive
+    pub death: Option<Death>,
+    /// how much longer we were than the longest other snake when it was last seen, set when we survived
+    pub margin: Option<i32>,
+    /// the final board, drawn by `Board::render`
+    pub board: String,
+}
+
+/// # reachable_space
+/// counts the tiles our head can reach without crossing a body, treating every body as if it stays put
+fn reachable_space(board: &types::Board, you: &types::Battlesnake) -> usize {
+    let bodies: HashSet<Coord> = board
+        .snakes
+        .iter()
+        .flat_map(|snake| snake.body.iter().copied())
+        .collect();
+    let mut visited: HashSet<Coord> = HashSet::new();
+    let mut frontier: VecDeque<Coord> = VecDeque::from([you.head]);
+    while let Some(tile) = frontier.pop_front() {
+        for dir in types::DIRECTIONS.values() {
+            let adj = tile + *dir;
+            if board.in_bounds(&adj) && !bodies.contains(&adj) && visited.insert(adj) {
+                frontier.push_back(adj);
+            }
+        }
+    }
+    return visited.len();
+}
+
+/// # segments_after_move
+/// the parts of a body still there after the snake moves, the tail stays only if the snake just ate
+fn segments_after_move(snake: &types::Battlesnake) -> &[Coord] {
+    let len = snake.body.len();
+    let stacked = len > 1 && snake.body[len - 1] == snake.body[len - 2];
+    if stacked {
+        return &snake.body;
+    }
+    return &snake.body[..len.saturating_sub(1)];
+}
+
+/// # classify_death
+/// works out what killed us from the turns leading up to it
+/// ## Arguments:
+/// * history - the latest turns of the game, oldest first
+/// * final_board - the board sent with `end()`
+/// * rules - the game's ruleset
+/// ## Returns:
+/// the cause of death
+pub(crate) fn classify_death(history: &[Turn], final_board: &types::Board, rules: &Ruleset) -> Death {
+    let last = match history.last() {
+        Some(last) => last,
+        None => return Death::Unknown,
+    };
+    let you = &last.you;
+    let dir = match types::DIRECTIONS.get(last.chosen.as_str()) {
+        Some(dir) => *dir,
+        None => return Death::Unknown,
+    };
+    let target = you.head + dir;
+
+    let immediate = if !last.board.in_bounds(&target) {
+        Death::Wall
+    } else {
+        let on_food = last.board.food.contains(&target);
+        let moved_health = you.health.saturating_sub(1);
+        let hazard = !on_food && last.board.hazards.contains(&target);
+        if !on_food && moved_health == 0 {
+            Death::Starvation
+        } else if hazard && moved_health <= rules.hazard_damage {
+            Death::HazardDrain
+        } else if let Some(death) = head_to_head(last, &target, final_board) {
+            death
+        } else {
+            match last
+                .board
+                .snakes
+                .iter()
+                .find(|snake| segments_after_move(snake).contains(&target))
+            {
+                Some(snake) => Death::Body {
+                    snake: snake.id.clone(),
+                },
+                None => Death::Unknown,
+            }
+        }
+    };
+
+    // running into something is only the symptom when we'd already shut ourselves in
+    if !matches!(immediate, Death::Wall | Death::Body { .. }) {
+        return immediate;
+    }
+    let mut trap: Option<(u32, usize)> = None;
+    for turn in history.iter().rev() {
+        let space = reachable_space(&turn.board, &turn.you);
+        if space >= turn.you.length as usize {
+            break;
+        }
+        trap = Some((turn.turn, trap.map_or(space, |(_, last_space)| last_space)));
+    }
+    return match trap {
+        Some((since_turn, space)) => Death::SelfTrap {
+            since_turn,
+            space,
+            length: you.length,
+        },
+        None => immediate,
+    };
+}
+
+/// # head_to_head
+/// a snake that was a move away from the tile we moved onto either ended up there, or went out with us
+fn head_to_head(last: &Turn, target: &Coord, final_board: &types::Board) -> Option<Death> {
+    let contested = last.board.snakes.iter().filter(|snake| {
+        snake.id != last.you.id
+            && snake.length >= last.you.length
+            && (snake.head.x - target.x).abs() + (snake.head.y - target.y).abs() == 1
+    });
+    for snake in contested {
+        let won = final_board
+            .snakes
+            .iter()
+            .any(|survivor| survivor.id == snake.id && survivor.head == *target);
+        let traded = !final_board
+            .snakes
+            .iter()
+            .any(|survivor| survivor.id == snake.id)
+            && snake.length == last.you.length;
+        if won || traded {
+            return Some(Death::HeadToHead {
+                opponent: snake.id.clone(),
+                opponent_length: snake.length,
+            });
+        }
+    }
+    return None;
+}
+
+/// # analyse
+/// writes up how a game went for us
+/// ## Arguments:
+/// * game - the game sent with `end()`
+/// * turn - the final turn
+/// * board - the final board
+/// * you - our snake as it was last seen
+/// * history - the latest turns of the game, oldest first
+/// ## Returns:
+/// the post-mortem record
+pub(crate) fn analyse(
+    game: &types::Game,
+    turn: u32,
+    board: &types::Board,
+    you: &types::Battlesnake,
+    history: &[Turn],
+) -> PostMortem {
+    let survived = board.snakes.iter().any(|snake| snake.id == you.id);
+    let (result, death, margin) = if survived {
+        // the other snakes are gone from the final board, the last turn they were on tells how close it was
+        let longest_other = history
+            .iter()
+            .rev()
+            .find_map(|turn| {
+                turn.board
+                    .snakes
+                    .iter()
+                    .filter(|snake| snake.id != you.id)
+                    .map(|snake| snake.length)
+                    .max()
+            })
+            .unwrap_or(0);
+        let length = board
+            .snakes
+            .iter()
+            .find(|snake| snake.id == you.id)
+            .map_or(you.length, |snake| snake.length);
+        (
+            GameResult::Win,
+            None,
+            Some(length as i32 - longest_other as i32),
+        )
+    } else {
+        let result = if board.snakes.is_empty() {
+            GameResult::Draw
+        } else {
+            GameResult::Loss
+        };
+        let death = classify_death(history, board, &Ruleset::from(game));
+        (result, Some(death), None)
+    };
+    return PostMortem {
+        game_id: game.id.clone(),
+        turn,
+        result,
+        death,
+        margin,
+        board: board.render(),
+    };
+}
+
+/// # save
+/// logs the post-mortem, and writes it to `POSTMORTEM_DIR_ENV` when that's set
+pub(crate) fn save(record: &PostMortem) {
+    let json = match serde_json::to_string(record) {
+        Ok(json) => json,
+        Err(err) => {
+            warn!("couldn't serialize the post-mortem: {}", err);
+            return;
+        }
+    };
+    info!("POSTMORTEM {}", json);
+    if let Ok(dir) = env::var(POSTMORTEM_DIR_ENV) {
+        let path = Path::new(&dir).join(format!("{}.json", record.game_id));
+        if let Err(err) = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, json)) {
+            warn!("couldn't write {}: {}", path.display(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn snake(id: &str, health: u8, body: &[(i16, i16)]) -> types::Battlesnake {
+        let body: Vec<Coord> = body.iter().map(|(x, y)| Coord { x: *x, y: *y }).collect();
+        return types::Battlesnake {
+            id: id.to_string(),
+            name: id.to_string(),
+            health,
+            head: body[0],
+            length: body.len() as u32,
+            body,
+            shout: None,
+            squad: String::new(),
+            latency: None,
+        };
+    }
+
+    fn board(
+        snakes: Vec<types::Battlesnake>,
+        food: &[(i16, i16)],
+        hazards: &[(i16, i16)],
+    ) -> types::Board {
+        let coords =
+            |tiles: &[(i16, i16)]| tiles.iter().map(|(x, y)| Coord { x: *x, y: *y }).collect();
+        return types::Board {
+            height: 7,
+            width: 7,
+            food: coords(food),
+            snakes,
+            hazards: coords(hazards),
+            hazard_odds: vec![],
+            hazard_walls: false,
+        };
+    }
+
+    fn turn(turn: u32, board: types::Board, chosen: &str) -> Turn {
+        let you = board
+            .snakes
+            .iter()
+            .find(|snake| snake.id == "me")
+            .unwrap()
+            .clone();
+        return Turn {
+            turn,
+            board,
+            you,
+            chosen: chosen.to_string(),
+        };
+    }
+
+    fn game() -> types::Game {
+        return serde_json::from_value(json!({
+            "id": "postmortem",
+            "ruleset": { "name": "standard", "settings": { "hazardDamagePerTurn": 14 } },
+            "timeout": 500
+        }))
+        .unwrap();
+    }
+
+    #[test]
+    fn head_to_head_with_longer_snake() {
+        let me = snake("me", 80, &[(2, 3), (1, 3), (0, 3)]);
+        let them = snake("them", 80, &[(4, 3), (4, 4), (4, 5), (4, 6)]);
+        let history = vec![turn(10, board(vec![me, them], &[], &[]), "right")];
+        let winner = snake("them", 79, &[(3, 3), (4, 3), (4, 4), (4, 5)]);
+        let final_board = board(vec![winner], &[], &[]);
+        let record = analyse(&game(), 11, &final_board, &history[0].you, &history);
+        assert_eq!(record.result, GameResult::Loss);
+        assert_eq!(
+            record.death,
+            Some(Death::HeadToHead {
+                opponent: "them".to_string(),
+                opponent_length: 4
+            })
+        );
+    }
+
+    #[test]
+    fn self_trap_below_length() {
+        // a turn after leaving the open side our head is walled into the corner by our own body, three tiles for a
+        // seven long snake
+        let me = snake(
+            "me",
+            80,
+            &[(1, 0), (2, 0), (2, 1), (2, 2), (1, 2), (0, 2), (0, 3)],
+        );
+        let earlier = snake(
+            "me",
+            80,
+            &[(2, 0), (2, 1), (2, 2), (1, 2), (0, 2), (0, 3), (0, 4)],
+        );
+        let history = vec![
+            turn(20, board(vec![earlier], &[], &[]), "left"),
+            turn(21, board(vec![me.clone()], &[], &[]), "down"),
+        ];
+        let death = classify_death(&history, &board(vec![], &[], &[]), &Ruleset::default());
+        assert_eq!(
+            death,
+            Death::SelfTrap {
+                since_turn: 21,
+                space: 3,
+                length: 7
+            }
+        );
+    }
+
+    #[test]
+    fn starvation() {
+        let me = snake("me", 1, &[(3, 3), (3, 2), (3, 1)]);
+        let history = vec![turn(99, board(vec![me], &[(0, 0)], &[]), "up")];
+        let death = classify_death(&history, &board(vec![], &[], &[]), &Ruleset::default());
+        assert_eq!(death, Death::Starvation);
+    }
+
+    #[test]
+    fn hazard_drain() {
+        let me = snake("me", 12, &[(3, 3), (3, 2), (3, 1)]);
+        let history = vec![turn(40, board(vec![me], &[], &[(3, 4)]), "up")];
+        let death = classify_death(&history, &board(vec![], &[], &[]), &Ruleset::default());
+        assert_eq!(death, Death::HazardDrain);
+    }
+
+    #[test]
+    fn wall_and_body_collisions() {
+        let me = snake("me", 80, &[(6, 3), (5, 3), (4, 3)]);
+        let history = vec![turn(5, board(vec![me], &[], &[]), "right")];
+        let death = classify_death(&history, &board(vec![], &[], &[]), &Ruleset::default());
+        assert_eq!(death, Death::Wall);
+
+        let me = snake("me", 80, &[(3, 3), (2, 3), (1, 3)]);
+        let them = snake("them", 80, &[(5, 4), (4, 4), (3, 4), (2, 4)]);
+        let history = vec![turn(5, board(vec![me, them.clone()], &[], &[]), "up")];
+        let death = classify_death(&history, &board(vec![them], &[], &[]), &Ruleset::default());
+        assert_eq!(
+            death,
+            Death::Body {
+                snake: "them".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn win_records_margin() {
+        let me = snake("me", 60, &[(3, 3), (3, 2), (3, 1), (3, 0)]);
+        let them = snake("them", 60, &[(5, 5), (5, 4)]);
+        let history = vec![turn(30, board(vec![me.clone(), them], &[], &[]), "up")];
+        let final_board = board(vec![me.clone()], &[], &[]);
+        let record = analyse(&game(), 31, &final_board, &me, &history);
+        assert_eq!(record.result, GameResult::Win);
+        assert_eq!(record.death, None);
+        assert_eq!(record.margin, Some(2));
+
+        let json: serde_json::Value = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["result"], "win");
+        assert_eq!(json["board"].as_str().unwrap().lines().count(), 7);
+    }
+}
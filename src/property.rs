@@ -0,0 +1,344 @@
+//! a property test over random boards: the move we choose never dies against every reply when another move would have
+//! survived one. a board that breaks it is shrunk to the smallest board that still does and written out for triage,
+//! along with the board it was found on
+
+use crate::logic;
+use crate::search::safety;
+use crate::types::{self, Coord, Ruleset};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde_json::json;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use std::{env, fs};
+
+/// the number of boards checked, kept low by default so the suite stays quick
+pub const CASES_ENV: &str = "BATTLESNAKE_PROPERTY_CASES";
+const DEFAULT_CASES: u64 = 40;
+
+/// where the boards that break the property are written, the temp directory when it isn't set
+pub const FAILURE_DIR_ENV: &str = "BATTLESNAKE_PROPERTY_DIR";
+
+/// the seed the boards are drawn from, case `n` is drawn from `SEED + n` so a failure can be looked at on its own
+const SEED: u64 = 0x5eed;
+
+/// the time the pipeline gets per board, less than a real turn to keep the cases cheap
+const BUDGET: Duration = Duration::from_millis(150);
+
+const MOVES: [&str; 4] = ["up", "right", "down", "left"];
+
+/// the engine never has a snake shorter than this, its tail stacks up instead
+const MIN_LENGTH: usize = 3;
+
+/// the share of boards sent with our snake missing from the board, dead or without a body, see `break_you`
+const BROKEN_SHARE: f64 = 0.05;
+
+/// # generate
+/// a random board a game could reach: 7 to 19 tiles a side, one to four snakes with contiguous bodies of at least
+/// `MIN_LENGTH` that don't overlap, food on free tiles and sometimes hazards, which may lie under snakes. we're always the first snake.
+/// now and then it's a frame the engine shouldn't send instead, see `break_you`
+fn generate(rng: &mut StdRng, case: u64) -> types::GameState {
+    let width = rng.gen_range(7..=19);
+    let height = rng.gen_range(7..=19);
+    let mut taken: Vec<Coord> = vec![];
+    let free_tile = |rng: &mut StdRng, taken: &Vec<Coord>| -> Option<Coord> {
+        for _ in 0..100 {
+            let tile = Coord {
+                x: rng.gen_range(0..width as i16),
+                y: rng.gen_range(0..height as i16),
+            };
+            if !taken.contains(&tile) {
+                return Some(tile);
+            }
+        }
+        return None;
+    };
+
+    let mut snakes = vec![];
+    for i in 0..rng.gen_range(1..=4) {
+        let Some(head) = free_tile(rng, &taken) else {
+            break;
+        };
+        let mut body = vec![head];
+        taken.push(head);
+        for _ in 1..rng.gen_range(3..=12) {
+            let last = *body.last().unwrap();
+            let mut next: Vec<Coord> = types::DIRECTIONS
+                .values()
+                .map(|dir| last + *dir)
+                .filter(|tile| tile.x >= 0 && tile.y >= 0)
+                .filter(|tile| tile.x < width as i16 && tile.y < height as i16)
+                .filter(|tile| !taken.contains(tile))
+                .collect();
+            next.sort_by_key(|tile| (tile.x, tile.y));
+            let Some(tile) = next.choose(rng).copied() else {
+                break;
+            };
+            body.push(tile);
+            taken.push(tile);
+        }
+        // a snake boxed in before it got its length has its tail stacked, as after eating or at the start
+        while body.len() < MIN_LENGTH {
+            body.push(*body.last().unwrap());
+        }
+        let id = if i == 0 {
+            "me".to_string()
+        } else {
+            format!("snake-{}", i)
+        };
+        snakes.push(json!({
+            "id": id,
+            "name": id,
+            "health": rng.gen_range(1..=100),
+            "body": body,
+            "head": head,
+            "length": body.len(),
+            "shout": "",
+        }));
+    }
+
+    let mut food = vec![];
+    for _ in 0..rng.gen_range(0..=6) {
+        if let Some(tile) = free_tile(rng, &taken) {
+            food.push(tile);
+            taken.push(tile);
+        }
+    }
+    let mut hazards = vec![];
+    if rng.gen_bool(0.3) {
+        for _ in 0..rng.gen_range(1..=(width as usize * height as usize / 6)) {
+            let tile = Coord {
+                x: rng.gen_range(0..width as i16),
+                y: rng.gen_range(0..height as i16),
+            };
+            if !hazards.contains(&tile) {
+                hazards.push(tile);
+            }
+        }
+    }
+
+    let mut state: types::GameState = serde_json::from_value(json!({
+        "game": {
+            "id": format!("property-{}", case),
+            "ruleset": { "name": "standard", "settings": { "hazardDamagePerTurn": 14 } },
+            "timeout": 500,
+        },
+        "turn": rng.gen_range(0..300),
+        "board": { "width": width, "height": height, "food": food, "snakes": snakes, "hazards": hazards },
+        "you": snakes[0],
+    }))
+    .unwrap();
+    if rng.gen_bool(BROKEN_SHARE) {
+        break_you(rng, &mut state);
+    }
+    return state;
+}
+
+/// # break_you
+/// leaves our snake off the board, kills it, takes its body away or moves it past the board's edge, on the board as well
+fn break_you(rng: &mut StdRng, state: &mut types::GameState) {
+    match rng.gen_range(0..4) {
+        0 => {
+            state.board.snakes.remove(0);
+        }
+        1 => {
+            state.board.snakes[0].health = 0;
+            state.you.health = 0;
+        }
+        2 => {
+            for you in [&mut state.board.snakes[0], &mut state.you] {
+                you.body.clear();
+                you.length = 0;
+            }
+        }
+        _ => {
+            let past = Coord { x: state.board.width as i16, y: 0 };
+            for you in [&mut state.board.snakes[0], &mut state.you] {
+                you.head = you.head + past;
+                you.body.iter_mut().for_each(|segment| *segment = *segment + past);
+            }
+        }
+    }
+}
+
+/// # survives
+/// whether a move lives through at least one combination of the opponents' plausible replies
+fn survives(state: &types::GameState, mv: &str) -> bool {
+    let rules = Ruleset::from(&state.game);
+    let dir = types::DIRECTIONS[mv];
+    return safety::survival_rate(&state.board, &state.you.id, &dir, state.turn + 1, &rules) > 0.0;
+}
+
+/// # violation
+/// runs the pipeline on a board
+/// ## Returns:
+/// the fatal move we chose and the moves that would have survived, none if the property holds. a board where every
+/// move dies holds, there was nothing better to do, and a board without a snake of ours only has to be answered
+fn violation(state: &types::GameState) -> Option<(String, Vec<&'static str>)> {
+    let deadline = Instant::now() + BUDGET;
+    let decision =
+        logic::get_move_until(&state.game, &state.turn, &state.board, &state.you, deadline);
+    let chosen = decision.response["move"]
+        .as_str()
+        .unwrap_or("none")
+        .to_string();
+    if !logic::intact(&state.board, &state.you) {
+        return (!MOVES.contains(&chosen.as_str())).then(|| (chosen, vec![]));
+    }
+    if MOVES.contains(&chosen.as_str()) && survives(state, &chosen) {
+        return None;
+    }
+    let safe: Vec<&str> = MOVES.into_iter().filter(|mv| survives(state, mv)).collect();
+    if safe.is_empty() {
+        return None;
+    }
+    return Some((chosen, safe));
+}
+
+/// # simpler
+/// every board one step simpler than a board: a snake, food or hazard fewer, a snake longer than `MIN_LENGTH` a segment
+/// shorter, or the board a row or column smaller when nothing is on its edge. our snake is kept
+fn simpler(state: &types::GameState) -> Vec<types::GameState> {
+    let mut boards = vec![];
+    let with = |change: &dyn Fn(&mut types::GameState)| {
+        let mut simpler = state.clone();
+        change(&mut simpler);
+        simpler.you = simpler.board.snakes[0].clone();
+        return simpler;
+    };
+    for i in 1..state.board.snakes.len() {
+        boards.push(with(&|state| {
+            state.board.snakes.remove(i);
+        }));
+    }
+    for i in 0..state.board.food.len() {
+        boards.push(with(&|state| {
+            state.board.food.remove(i);
+        }));
+    }
+    for i in 0..state.board.hazards.len() {
+        boards.push(with(&|state| {
+            state.board.hazards.remove(i);
+        }));
+    }
+    for i in 0..state.board.snakes.len() {
+        if state.board.snakes[i].body.len() > MIN_LENGTH {
+            boards.push(with(&|state| {
+                let snake = &mut state.board.snakes[i];
+                snake.body.pop();
+                snake.length -= 1;
+            }));
+        }
+    }
+    let tiles = || {
+        return state
+            .board
+            .snakes
+            .iter()
+            .flat_map(|snake| snake.body.iter())
+            .chain(&state.board.food)
+            .chain(&state.board.hazards);
+    };
+    if state.board.width > 1 && tiles().all(|tile| tile.x < state.board.width as i16 - 1) {
+        boards.push(with(&|state| state.board.width -= 1));
+    }
+    if state.board.height > 1 && tiles().all(|tile| tile.y < state.board.height as i16 - 1) {
+        boards.push(with(&|state| state.board.height -= 1));
+    }
+    return boards;
+}
+
+/// # shrink
+/// simplifies a board that breaks the property for as long as the simpler board still breaks it
+fn shrink(mut state: types::GameState) -> types::GameState {
+    while let Some(simpler) = simpler(&state)
+        .into_iter()
+        .find(|simpler| violation(simpler).is_some())
+    {
+        state = simpler;
+    }
+    return state;
+}
+
+/// # write_failure
+/// writes a board that broke the property and its shrunk form for triage
+/// ## Returns:
+/// where it was written
+fn write_failure(case: u64, found: &types::GameState, shrunk: &types::GameState) -> PathBuf {
+    let dir = env::var(FAILURE_DIR_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| env::temp_dir().join("battlesnake-property"));
+    let path = dir.join(format!("case-{}.json", case));
+    let failure = json!({ "seed": SEED + case, "shrunk": shrunk, "found": found });
+    let written = fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(&path, serde_json::to_string_pretty(&failure).unwrap()));
+    if let Err(err) = written {
+        eprintln!("couldn't write {}: {}", path.display(), err);
+    }
+    return path;
+}
+
+#[test]
+fn never_choose_an_avoidable_death() {
+    let cases = env::var(CASES_ENV)
+        .ok()
+        .and_then(|cases| cases.parse().ok())
+        .unwrap_or(DEFAULT_CASES);
+    let mut failures = vec![];
+    for case in 0..cases {
+        let state = generate(&mut StdRng::seed_from_u64(SEED + case), case);
+        if violation(&state).is_none() {
+            continue;
+        }
+        let shrunk = shrink(state.clone());
+        let (chosen, safe) = violation(&shrunk).unwrap();
+        let path = write_failure(case, &state, &shrunk);
+        failures.push(format!(
+            "case {}: chose {} when {:?} survived, written to {}\n{}",
+            case,
+            chosen,
+            safe,
+            path.display(),
+            shrunk.board.render()
+        ));
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
+
+#[test]
+fn generated_boards_are_valid() {
+    let mut broken = 0;
+    for case in 0..200 {
+        let state = generate(&mut StdRng::seed_from_u64(SEED + case), case);
+        if !logic::intact(&state.board, &state.you) {
+            broken += 1;
+            continue;
+        }
+        let problems = crate::payload::validate(&state);
+        assert!(problems.is_empty(), "case {}: {:?}", case, problems);
+        // a stacked tail repeats its tile, every other tile belongs to one snake or piece of food
+        let mut tiles: Vec<&Coord> = vec![];
+        for snake in &state.board.snakes {
+            assert!(snake.body.len() >= MIN_LENGTH, "case {}", case);
+            for pair in snake.body.windows(2) {
+                let step = pair[0] - pair[1];
+                assert!(step.x.abs() + step.y.abs() <= 1, "case {}", case);
+            }
+            let mut body: Vec<&Coord> = snake.body.iter().collect();
+            body.dedup();
+            tiles.extend(body);
+        }
+        tiles.extend(&state.board.food);
+        for (i, tile) in tiles.iter().enumerate() {
+            assert!(
+                !tiles[i + 1..].contains(tile),
+                "case {}: {:?} is taken twice",
+                case,
+                tile
+            );
+        }
+    }
+    // a few, but not so many the property goes unchecked
+    assert!((1..20).contains(&broken), "{} broken boards", broken);
+}
@@ -0,0 +1,477 @@
+use crate::fixtures;
+use crate::logic;
+use crate::types;
+use clap::Parser;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::{env, thread};
+
+/// set to a directory, `replays` for example, to log every request of every game to `<dir>/<game_id>.jsonl`, or to
+/// `<dir>/<route>/<game_id>.jsonl` for the personalities mounted away from the root
+pub const REPLAY_DIR_ENV: &str = "BATTLESNAKE_REPLAY_DIR";
+
+/// one request of a game and our answer to it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReplayEntry {
+    /// `start`, `move` or `end`
+    pub request: String,
+    pub state: types::GameState,
+    /// the move we answered with, only set for moves
+    pub response: Option<Value>,
+    /// a summary of how the move was decided, only set for moves
+    pub trace: Option<Value>,
+}
+
+enum Message {
+    /// an entry and the file it goes in
+    Entry(String, Box<ReplayEntry>),
+    /// the game is over, flush and close its file
+    Finish(String),
+    /// answered once every message sent before it has been written
+    Sync(Sender<()>),
+    /// flush every open file, answered once they are
+    Flush(Sender<()>),
+}
+
+/// appends replay entries on a background thread so logging never holds up a response. every game has its own
+/// buffered file, so concurrent games don't interleave
+pub struct ReplayLogger {
+    sender: Option<Sender<Message>>,
+}
+
+impl ReplayLogger {
+    /// # from_env
+    /// a logger writing to `REPLAY_DIR_ENV`, or one that drops everything when it isn't set
+    pub fn from_env() -> Self {
+        return match env::var(REPLAY_DIR_ENV) {
+            Ok(dir) => ReplayLogger::new(PathBuf::from(dir)),
+            Err(_) => ReplayLogger::disabled(),
+        };
+    }
+
+    /// # disabled
+    /// a logger that drops everything
+    pub fn disabled() -> Self {
+        return ReplayLogger { sender: None };
+    }
+
+    /// # new
+    /// starts the writer thread for a directory of replays
+    pub fn new(dir: PathBuf) -> Self {
+        let (sender, receiver) = mpsc::channel::<Message>();
+        thread::spawn(move || {
+            let mut files: HashMap<String, BufWriter<File>> = HashMap::new();
+            for message in receiver {
+                match message {
+                    Message::Entry(name, entry) => {
+                        if let Err(err) = append(&dir, &mut files, &name, &entry) {
+                            warn!("couldn't log the replay {}: {}", name, err);
+                        }
+                    }
+                    Message::Finish(name) => {
+                        if let Some(mut file) = files.remove(&name) {
+                            if let Err(err) = file.flush() {
+                                warn!("couldn't flush the replay {}: {}", name, err);
+                            }
+                        }
+                    }
+                    Message::Sync(done) => {
+                        let _ = done.send(());
+                    }
+                    Message::Flush(done) => {
+                        for (name, file) in files.iter_mut() {
+                            if let Err(err) = file.flush() {
+                                warn!("couldn't flush the replay {}: {}", name, err);
+                            }
+                        }
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+        return ReplayLogger {
+            sender: Some(sender),
+        };
+    }
+
+    /// # log
+    /// queues a request and our answer to be written
+    /// ## Arguments:
+    /// * route - the personality that answered, empty for the root
+    /// * request - `start`, `move` or `end`
+    /// * state - the request
+    /// * response - the move we answered with
+    /// * trace - a summary of how the move was decided
+    pub fn log(
+        &self,
+        route: &str,
+        request: &str,
+        state: &types::GameState,
+        response: Option<&Value>,
+        trace: Option<Value>,
+    ) {
+        if let Some(sender) = &self.sender {
+            let entry = ReplayEntry {
+                request: request.to_string(),
+                state: state.clone(),
+                response: response.cloned(),
+                trace,
+            };
+            let name = file_name(route, &state.game.id);
+            let _ = sender.send(Message::Entry(name, Box::new(entry)));
+        }
+    }
+
+    /// # finish
+    /// flushes and closes a game's replay once everything queued for it is written
+    pub fn finish(&self, route: &str, game_id: &str) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(Message::Finish(file_name(route, game_id)));
+        }
+    }
+
+    /// # sync
+    /// waits until everything queued so far has been written
+    #[allow(dead_code)] // the handlers never wait on the writer, only tests need to
+    pub fn sync(&self) {
+        self.wait(Message::Sync);
+    }
+
+    /// # flush
+    /// waits until everything queued so far has been written and flushed, including the games still in progress
+    pub fn flush(&self) {
+        self.wait(Message::Flush);
+    }
+
+    fn wait(&self, message: fn(Sender<()>) -> Message) {
+        if let Some(sender) = &self.sender {
+            let (done, wait) = mpsc::channel();
+            if sender.send(message(done)).is_ok() {
+                let _ = wait.recv();
+            }
+        }
+    }
+}
+
+/// # file_name
+/// where a personality's replay of a game goes, relative to the replay directory
+fn file_name(route: &str, game_id: &str) -> String {
+    if route.is_empty() {
+        return format!("{}.jsonl", game_id);
+    }
+    return format!("{}/{}.jsonl", route, game_id);
+}
+
+/// # append
+/// writes an entry to its file, opening the file on the game's first entry
+fn append(
+    dir: &Path,
+    files: &mut HashMap<String, BufWriter<File>>,
+    name: &str,
+    entry: &ReplayEntry,
+) -> io::Result<()> {
+    if !files.contains_key(name) {
+        let path = dir.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        files.insert(name.to_string(), BufWriter::new(file));
+    }
+    let file = files.get_mut(name).unwrap();
+    serde_json::to_writer(&mut *file, entry)?;
+    file.write_all(b"\n")?;
+    return Ok(());
+}
+
+/// # load
+/// parses a replay file
+/// ## Returns:
+/// every entry in the order they were logged
+pub fn load(contents: &str) -> serde_json::Result<Vec<ReplayEntry>> {
+    return contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect();
+}
+
+/// # load_states
+/// the positions we were asked to move in, in turn order
+#[allow(dead_code)] // the replay command reads whole entries to compare the moves
+pub fn load_states(contents: &str) -> serde_json::Result<Vec<types::GameState>> {
+    return Ok(load(contents)?
+        .into_iter()
+        .filter(|entry| entry.request == "move")
+        .map(|entry| entry.state)
+        .collect());
+}
+
+/// the `replay` command's arguments
+#[derive(Parser, Debug)]
+#[command(name = "replay")]
+pub struct ReplayArgs {
+    /// the replay log of a game
+    #[arg(required_unless_present = "fixtures")]
+    file: Option<PathBuf>,
+    /// only look at this turn, it's printed even when it matches
+    #[arg(long)]
+    turn: Option<u32>,
+    /// exit with 1 when any move changed, for CI
+    #[arg(long)]
+    fail_on_diff: bool,
+    /// check the regression fixtures in a directory instead of a game
+    #[arg(long, num_args = 0..=1, default_missing_value = fixtures::FIXTURE_DIR, conflicts_with = "file")]
+    fixtures: Option<PathBuf>,
+}
+
+/// # run
+/// the `replay` command: re-runs the logged moves of a game through the current `get_move` and compares them with the
+/// moves we sent. turns that changed are printed with the board and the logged trace, `--turn N` looks at a single
+/// turn and `--fail-on-diff` turns any change into a failure for CI. `--fixtures` checks the regression fixtures
+/// instead, failing if any of them doesn't hold
+/// ## Arguments:
+/// * args - the parsed arguments
+/// * out - where the comparison is written
+/// ## Returns:
+/// the exit code, 1 when a move changed under `--fail-on-diff` and 2 when the file is bad
+pub fn run(args: &ReplayArgs, out: &mut impl Write) -> i32 {
+    if let Some(dir) = &args.fixtures {
+        return check_fixtures(dir, out);
+    }
+    let Some(path) = &args.file else {
+        let _ = writeln!(out, "a replay file is required");
+        return 2;
+    };
+    let entries = match fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| load(&contents).map_err(|err| err.to_string()))
+    {
+        Ok(entries) => entries,
+        Err(err) => {
+            let _ = writeln!(out, "couldn't read {}: {}", path.display(), err);
+            return 2;
+        }
+    };
+    return match compare(&entries, args.turn, out) {
+        Ok(changed) if args.fail_on_diff && changed > 0 => 1,
+        Ok(_) => 0,
+        Err(_) => 2,
+    };
+}
+
+/// # check_fixtures
+/// runs every fixture in a directory, the ones that don't hold are printed with their board and trace
+/// ## Returns:
+/// the exit code, 1 when a fixture doesn't hold and 2 when the fixtures can't be read
+fn check_fixtures(dir: &Path, out: &mut impl Write) -> i32 {
+    let fixtures = match fixtures::load(dir) {
+        Ok(fixtures) => fixtures,
+        Err(err) => {
+            let _ = writeln!(out, "couldn't read the fixtures: {}", err);
+            return 2;
+        }
+    };
+    let mut failed = 0;
+    for fixture in &fixtures {
+        let outcome = fixture.check();
+        if outcome.passed {
+            let _ = writeln!(out, "{}: {}", fixture.name, outcome.chosen);
+        } else {
+            failed += 1;
+            let _ = writeln!(out, "{}", fixture.report(&outcome));
+        }
+    }
+    let _ = writeln!(out, "{} of {} fixtures failed", failed, fixtures.len());
+    return if failed > 0 { 1 } else { 0 };
+}
+
+/// # compare
+/// prints the recorded and fresh move of every logged move request
+/// ## Returns:
+/// the number of moves that changed
+fn compare(entries: &[ReplayEntry], turn: Option<u32>, out: &mut impl Write) -> io::Result<usize> {
+    let mut checked = 0;
+    let mut changed = 0;
+    let moves = entries.iter().filter(|entry| {
+        entry.request == "move" && turn.is_none_or(|turn| entry.state.turn == turn)
+    });
+    for entry in moves {
+        let state = &entry.state;
+        let recorded = entry
+            .response
+            .as_ref()
+            .and_then(|response| response["move"].as_str())
+            .unwrap_or("none");
+        let response = logic::get_move(&state.game, &state.turn, &state.board, &state.you);
+        let fresh = response["move"].as_str().unwrap_or("none");
+        checked += 1;
+        if recorded == fresh {
+            writeln!(out, "turn {}: {}", state.turn, fresh)?;
+            if turn.is_none() {
+                continue;
+            }
+        } else {
+            changed += 1;
+            writeln!(
+                out,
+                "turn {}: recorded {}, now {}",
+                state.turn, recorded, fresh
+            )?;
+        }
+        writeln!(out, "{}", state.board.render())?;
+        if let Some(trace) = &entry.trace {
+            writeln!(out, "trace: {}", trace)?;
+        }
+    }
+    writeln!(out, "{} of {} moves changed", changed, checked)?;
+    return Ok(changed);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn state(game_id: &str, turn: u32) -> types::GameState {
+        let me = json!({
+            "id": "me",
+            "name": "me",
+            "health": 100 - turn,
+            "body": [{ "x": 1, "y": turn }, { "x": 1, "y": 0 }, { "x": 1, "y": 0 }],
+            "head": { "x": 1, "y": turn },
+            "length": 3,
+            "shout": ""
+        });
+        return serde_json::from_value(json!({
+            "game": { "id": game_id, "ruleset": { "name": "standard" }, "timeout": 500 },
+            "turn": turn,
+            "board": { "height": 11, "width": 11, "food": [], "snakes": [me], "hazards": [] },
+            "you": me
+        }))
+        .unwrap();
+    }
+
+    #[test]
+    fn short_game_round_trips() {
+        let dir = env::temp_dir().join(format!("battlesnake-replays-{}", std::process::id()));
+        let logger = ReplayLogger::new(dir.clone());
+        // two games logged at once each get their own file
+        for game_id in ["first", "second"] {
+            logger.log("", "start", &state(game_id, 0), None, None);
+        }
+        for turn in 0..3 {
+            for game_id in ["first", "second"] {
+                let response = json!({ "move": "up" });
+                let trace = json!({ "branch": "a_star", "elapsed_ms": 1 });
+                logger.log(
+                    "",
+                    "move",
+                    &state(game_id, turn),
+                    Some(&response),
+                    Some(trace),
+                );
+            }
+        }
+        for game_id in ["first", "second"] {
+            logger.log("", "end", &state(game_id, 3), None, None);
+            logger.finish("", game_id);
+        }
+        logger.sync();
+
+        let contents = fs::read_to_string(dir.join("first.jsonl")).unwrap();
+        let entries = load(&contents).unwrap();
+        let requests: Vec<&str> = entries.iter().map(|entry| entry.request.as_str()).collect();
+        assert_eq!(requests, ["start", "move", "move", "move", "end"]);
+        assert!(entries.iter().all(|entry| entry.state.game.id == "first"));
+        assert_eq!(entries[1].response, Some(json!({ "move": "up" })));
+
+        let states = load_states(&contents).unwrap();
+        let turns: Vec<u32> = states.iter().map(|state| state.turn).collect();
+        assert_eq!(turns, [0, 1, 2]);
+        assert_eq!(states[2].you.head, types::Coord { x: 1, y: 2 });
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn disabled_logger_writes_nothing() {
+        let logger = ReplayLogger::disabled();
+        logger.log("", "start", &state("ignored", 0), None, None);
+        logger.finish("", "ignored");
+        logger.sync();
+    }
+
+    const SAMPLE: &str = "testdata/replays/standard.jsonl";
+
+    fn replay(args: &[&str]) -> (i32, String) {
+        let mut out: Vec<u8> = vec![];
+        // bad arguments exit with clap's usage code, 2
+        let code = match ReplayArgs::try_parse_from(["replay"].iter().chain(args)) {
+            Ok(args) => run(&args, &mut out),
+            Err(err) => {
+                write!(out, "{}", err).unwrap();
+                err.exit_code()
+            }
+        };
+        return (code, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn replay_sample_has_no_changes() {
+        let (code, out) = replay(&[SAMPLE, "--fail-on-diff"]);
+        assert_eq!(code, 0, "{}", out);
+        assert!(out.ends_with("0 of 6 moves changed\n"), "{}", out);
+
+        // a single turn is shown with its board and trace even when it matches
+        let (code, out) = replay(&[SAMPLE, "--turn", "4"]);
+        assert_eq!(code, 0);
+        assert!(out.starts_with("turn 4: down\n"), "{}", out);
+        assert!(out.contains("trace: "));
+        assert!(out.ends_with("0 of 1 moves changed\n"));
+    }
+
+    #[test]
+    fn replay_flags_changed_moves() {
+        let contents = fs::read_to_string(SAMPLE).unwrap();
+        let mut entries = load(&contents).unwrap();
+        // pretend we once answered the first move differently
+        entries[1].response = Some(json!({ "move": "left" }));
+        let path = env::temp_dir().join(format!("battlesnake-replay-{}.jsonl", std::process::id()));
+        let lines: Vec<String> = entries
+            .iter()
+            .map(|entry| serde_json::to_string(entry).unwrap())
+            .collect();
+        fs::write(&path, lines.join("\n")).unwrap();
+        let path = path.to_str().unwrap();
+
+        let (code, out) = replay(&[path]);
+        assert_eq!(code, 0);
+        assert!(out.contains("turn 0: recorded left, now up\n"), "{}", out);
+        assert!(out.ends_with("1 of 6 moves changed\n"));
+        assert_eq!(replay(&[path, "--fail-on-diff"]).0, 1);
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(replay(&[]).0, 2);
+        assert_eq!(replay(&[SAMPLE, "--turn"]).0, 2);
+        assert_eq!(replay(&["missing.jsonl"]).0, 2);
+    }
+
+    #[test]
+    fn replay_checks_the_fixtures() {
+        let (code, out) = replay(&["--fixtures"]);
+        assert_eq!(code, 0, "{}", out);
+        assert!(out.contains("corner: right\n"), "{}", out);
+        assert!(
+            out.ends_with(" fixtures failed\n") && out.contains("\n0 of "),
+            "{}",
+            out
+        );
+        assert_eq!(replay(&["--fixtures", "testdata/missing"]).0, 2);
+    }
+}
@@ -0,0 +1,331 @@
+//! every finished game's result, appended to a JSONL file that outlives the server, so a week of games can be added
+//! up across the deploys in it. `/stats` reports the totals next to the counters that start over on a restart, broken
+//! down by crate version to compare one deploy's strategy with the last. the latency buffer each game ended with is
+//! kept too, the next game in the same region starts from it
+
+use crate::budget;
+use crate::postmortem::{GameResult, PostMortem};
+use crate::types;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// set to a file, `results.jsonl` for example, to keep every game's result there
+pub const RESULTS_ENV: &str = "BATTLESNAKE_RESULTS";
+
+/// a line of the results file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GameRecord {
+    pub game_id: String,
+    /// the ruleset's name, `standard` or `royale` for example
+    pub mode: String,
+    /// the names of the other snakes that started the game
+    pub opponents: Vec<String>,
+    pub result: GameResult,
+    /// what killed us, see `postmortem::Death::cause`
+    pub death: Option<String>,
+    /// the crate version that played the game
+    pub version: String,
+    /// the route of the personality that played the game, empty for the root
+    pub profile: String,
+    /// where the server answered from, see `budget::region`
+    #[serde(default)]
+    pub region: String,
+    /// the latency buffer the game ended with in milliseconds, none for a game that never got to a move
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buffer_ms: Option<u64>,
+}
+
+impl GameRecord {
+    /// # new
+    /// the record of a finished game
+    /// ## Arguments:
+    /// * route - the personality that played it
+    /// * state - the end request
+    /// * starting - the board of the first turn we remember, for the snakes that didn't make it to the end
+    /// * postmortem - the game's post-mortem
+    pub fn new(route: &str, state: &types::GameState, starting: &types::Board, postmortem: &PostMortem) -> Self {
+        let opponents = starting
+            .snakes
+            .iter()
+            .filter(|snake| snake.id != state.you.id)
+            .map(|snake| snake.name.clone())
+            .collect();
+        let mode = state.game.ruleset.get("name").and_then(Value::as_str).unwrap_or("unknown");
+        return GameRecord {
+            game_id: state.game.id.clone(),
+            mode: mode.to_string(),
+            opponents,
+            result: postmortem.result,
+            death: postmortem.death.as_ref().map(|death| death.cause().to_string()),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            profile: route.to_string(),
+            region: budget::region(),
+            buffer_ms: None,
+        };
+    }
+}
+
+/// games won, drawn and lost
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+struct Tally {
+    wins: u64,
+    draws: u64,
+    losses: u64,
+}
+
+impl Tally {
+    fn add(&mut self, result: GameResult) {
+        match result {
+            GameResult::Win => self.wins += 1,
+            GameResult::Draw => self.draws += 1,
+            GameResult::Loss => self.losses += 1,
+        }
+    }
+
+    fn report(&self) -> Value {
+        let games = self.wins + self.draws + self.losses;
+        return json!({
+            "games": games,
+            "wins": self.wins,
+            "draws": self.draws,
+            "losses": self.losses,
+            "win_rate": if games == 0 { 0.0 } else { self.wins as f64 / games as f64 },
+        });
+    }
+}
+
+#[derive(Default)]
+struct Aggregates {
+    total: Tally,
+    by_version: BTreeMap<String, Tally>,
+    by_mode: BTreeMap<String, Tally>,
+    by_profile: BTreeMap<String, Tally>,
+    /// our deaths, by cause
+    deaths: BTreeMap<String, u64>,
+    /// the latency buffer the latest game in each region ended with, in milliseconds
+    buffers: BTreeMap<String, u64>,
+}
+
+impl Aggregates {
+    fn add(&mut self, record: &GameRecord) {
+        self.total.add(record.result);
+        self.by_version.entry(record.version.clone()).or_default().add(record.result);
+        self.by_mode.entry(record.mode.clone()).or_default().add(record.result);
+        self.by_profile.entry(record.profile.clone()).or_default().add(record.result);
+        if let Some(cause) = &record.death {
+            *self.deaths.entry(cause.clone()).or_default() += 1;
+        }
+        if let Some(buffer_ms) = record.buffer_ms {
+            self.buffers.insert(record.region.clone(), buffer_ms);
+        }
+    }
+}
+
+/// the results file and what's in it so far. without a file the totals are still kept for the latency buffers, they
+/// just aren't reported or saved
+pub struct ResultLog {
+    path: Option<PathBuf>,
+    /// where the server answers from, see `budget::region`
+    region: String,
+    /// the totals, only held to add a game or read them. the first move of every game reads the learned buffer, it
+    /// doesn't wait on the file
+    aggregates: Mutex<Aggregates>,
+    /// held while a line is written, so concurrent games can't interleave theirs. true while the file ends partway
+    /// through a line, the next one starts on a line of its own
+    torn: Mutex<bool>,
+}
+
+impl ResultLog {
+    /// # from_env
+    /// the results in `RESULTS_ENV`, or a log that keeps nothing when it isn't set
+    pub fn from_env() -> Self {
+        return match std::env::var(RESULTS_ENV) {
+            Ok(path) => ResultLog::load(PathBuf::from(path)),
+            Err(_) => ResultLog::disabled(),
+        };
+    }
+
+    /// # disabled
+    /// a log that keeps nothing
+    pub fn disabled() -> Self {
+        return ResultLog {
+            path: None,
+            region: budget::region(),
+            aggregates: Mutex::new(Aggregates::default()),
+            torn: Mutex::new(false),
+        };
+    }
+
+    /// # load
+    /// adds up the results already in a file, a file that isn't there yet is created with the first game. a line that
+    /// doesn't parse, as the last one can't after a crash mid write, is skipped
+    pub fn load(path: PathBuf) -> Self {
+        let mut aggregates = Aggregates::default();
+        let mut torn = false;
+        if let Ok(contents) = fs::read_to_string(&path) {
+            torn = !contents.is_empty() && !contents.ends_with('\n');
+            for (number, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+                match serde_json::from_str::<GameRecord>(line) {
+                    Ok(record) => aggregates.add(&record),
+                    Err(err) => warn!("skipping line {} of {}: {}", number + 1, path.display(), err),
+                }
+            }
+        }
+        return ResultLog {
+            path: Some(path),
+            region: budget::region(),
+            aggregates: Mutex::new(aggregates),
+            torn: Mutex::new(torn),
+        };
+    }
+
+    fn aggregates(&self) -> MutexGuard<'_, Aggregates> {
+        return self
+            .aggregates
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+
+    /// # record
+    /// appends a finished game to the file and the totals
+    pub fn record(&self, record: &GameRecord) {
+        self.aggregates().add(record);
+        let Some(path) = &self.path else {
+            return;
+        };
+        let mut torn = self.torn.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let line = match serde_json::to_string(record) {
+            Ok(line) if *torn => format!("\n{}\n", line),
+            Ok(line) => line + "\n",
+            Err(err) => return warn!("couldn't serialize the result of {}: {}", record.game_id, err),
+        };
+        // the whole line in one append, so another server sharing the file can't split it either
+        let written = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| OpenOptions::new().create(true).append(true).open(path))
+            .and_then(|mut file| file.write_all(line.as_bytes()));
+        match written {
+            Ok(()) => *torn = false,
+            Err(err) => warn!("couldn't save the result of {} to {}: {}", record.game_id, path.display(), err),
+        }
+    }
+
+    /// # learned_buffer
+    /// the latency buffer the latest game in this server's region ended with, none before the region's first game
+    pub fn learned_buffer(&self) -> Option<Duration> {
+        return self.aggregates().buffers.get(&self.region).map(|ms| Duration::from_millis(*ms));
+    }
+
+    /// # snapshot
+    /// the totals as the `/stats` route reports them, none when results aren't kept
+    pub fn snapshot(&self) -> Value {
+        if self.path.is_none() {
+            return Value::Null;
+        }
+        let aggregates = self.aggregates();
+        let breakdown = |tallies: &BTreeMap<String, Tally>| -> BTreeMap<String, Value> {
+            return tallies.iter().map(|(key, tally)| (key.clone(), tally.report())).collect();
+        };
+        return json!({
+            "total": aggregates.total.report(),
+            "by_version": breakdown(&aggregates.by_version),
+            "by_mode": breakdown(&aggregates.by_mode),
+            "by_profile": breakdown(&aggregates.by_profile),
+            "deaths": aggregates.deaths,
+            "buffers_ms": aggregates.buffers,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    fn record(game_id: &str, result: GameResult, death: Option<&str>, version: &str) -> GameRecord {
+        return GameRecord {
+            game_id: game_id.to_string(),
+            mode: "standard".to_string(),
+            opponents: vec!["them".to_string()],
+            result,
+            death: death.map(str::to_string),
+            version: version.to_string(),
+            profile: String::new(),
+            region: "local".to_string(),
+            buffer_ms: None,
+        };
+    }
+
+    #[test]
+    fn results_add_up_across_restarts() {
+        let path = env::temp_dir().join(format!("battlesnake-results-{}/results.jsonl", std::process::id()));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+        // the file and its directory are made with the first game
+        let results = ResultLog::load(path.clone());
+        assert_eq!(results.snapshot()["total"]["games"], 0);
+        results.record(&record("won", GameResult::Win, None, "0.1.0"));
+        results.record(&record("lost", GameResult::Loss, Some("head_to_head"), "0.1.0"));
+
+        // a restart picks up where it left off, a torn last line is skipped
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"game_id\":\"torn").unwrap();
+        drop(file);
+        let restarted = ResultLog::load(path.clone());
+        restarted.record(&record("upgraded", GameResult::Win, None, "0.2.0"));
+        let snapshot = restarted.snapshot();
+        let lines = fs::read_to_string(&path).unwrap().lines().count();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert_eq!(lines, 4);
+        assert_eq!(snapshot["total"], json!({ "games": 3, "wins": 2, "draws": 0, "losses": 1, "win_rate": 2.0 / 3.0 }));
+        assert_eq!(snapshot["by_version"]["0.1.0"]["win_rate"], 0.5);
+        assert_eq!(snapshot["by_version"]["0.2.0"]["games"], 1);
+        assert_eq!(snapshot["by_mode"]["standard"]["games"], 3);
+        assert_eq!(snapshot["deaths"], json!({ "head_to_head": 1 }));
+        assert_eq!(ResultLog::disabled().snapshot(), Value::Null);
+    }
+
+    #[test]
+    fn the_latest_buffer_of_the_region_is_learned() {
+        let path = env::temp_dir().join(format!("battlesnake-buffers-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let results = ResultLog::load(path.clone());
+        let region = results.region.clone();
+        assert_eq!(results.learned_buffer(), None);
+        let ended = |game_id: &str, region: &str, buffer_ms: Option<u64>| GameRecord {
+            region: region.to_string(),
+            buffer_ms,
+            ..record(game_id, GameResult::Win, None, "0.1.0")
+        };
+        results.record(&ended("first", &region, Some(150)));
+        results.record(&ended("second", &region, Some(180)));
+        // another region's and a game without a move don't count
+        results.record(&ended("elsewhere", &format!("{}-other", region), Some(40)));
+        results.record(&ended("no moves", &region, None));
+        assert_eq!(results.learned_buffer(), Some(Duration::from_millis(180)));
+        // a line being written doesn't hold up a first move
+        let writing = results.torn.lock().unwrap();
+        assert_eq!(results.learned_buffer(), Some(Duration::from_millis(180)));
+        drop(writing);
+
+        // the next server in the region starts from it
+        let restarted = ResultLog::load(path.clone());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(restarted.learned_buffer(), Some(Duration::from_millis(180)));
+        assert_eq!(restarted.snapshot()["buffers_ms"][&region], 180);
+
+        // and without a file it's only kept until the restart
+        let disabled = ResultLog::disabled();
+        disabled.record(&ended("first", &region, Some(90)));
+        assert_eq!(disabled.learned_buffer(), Some(Duration::from_millis(90)));
+    }
+}
@@ -0,0 +1,347 @@
+//! how the tiles we could move through on a turn hang together, so the divergence analysis can tell how much room is
+//! left behind a tile without flood filling the board again for every candidate. the open tiles are labelled by region
+//! once, with a depth first search that also finds the tiles a region can't lose without falling apart, and a question
+//! that excludes a single tile is answered from that
+//!
+//! the answers are the ones `logic`'s flood fill gives, a question it can't answer, excluding a few tiles at once, is
+//! left to the fill
+
+use crate::logic;
+use crate::types::{self, Coord};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+/// a tile's neighbours, in the order the search looks at them
+const STEPS: [(i16, i16); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+/// the part of a region a tile is in once a tile has been taken out of it
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Piece {
+    /// the excluded tile isn't in the region, it's whole
+    Region(usize),
+    /// the subtree below one of the excluded tile's children, cut off from the rest of the region
+    Below(usize),
+    /// whatever of the region is still joined to the tiles above the excluded one
+    Above(usize),
+}
+
+/// the open tiles labelled by region, and the depth first search over each region
+struct Labels {
+    /// the region of every tile, none for the tiles we can't move onto
+    region: Vec<Option<usize>>,
+    /// the number of tiles in each region
+    sizes: Vec<u32>,
+    /// the order the search reached each tile in, a tile's subtree is the tiles reached right after it
+    order: Vec<u32>,
+    /// the earliest tile in the order that each tile's subtree has an edge to
+    low: Vec<u32>,
+    /// the tiles in each tile's subtree, itself included
+    subtree: Vec<u32>,
+    /// the tile each tile was reached from, none for the first tile of a region
+    parent: Vec<Option<usize>>,
+}
+
+/// the connectivity of one board from one snake's point of view, labelled the first time it's asked about. it keeps
+/// count of the tiles visited to answer, its own and those of the fills it leaves questions to. the fills can run on
+/// several threads at once, so both are shared safely
+pub struct Connectivity<'a> {
+    board: &'a types::Board,
+    game_board: &'a types::FastMap<Coord, types::Flags>,
+    you: &'a types::Battlesnake,
+    /// off to leave every question to the fill
+    incremental: bool,
+    labels: OnceLock<Labels>,
+    visits: AtomicU64,
+}
+
+impl<'a> Connectivity<'a> {
+    /// # new
+    /// the connectivity of a board, nothing is worked out until it's asked about
+    /// ## Arguments:
+    /// * board - the battlesnake game board
+    /// * game_board - the hashmap representation of the game board
+    /// * you - your battlesnake, the tiles next to the heads of bigger snakes aren't open to it
+    pub fn new(
+        board: &'a types::Board,
+        game_board: &'a types::FastMap<Coord, types::Flags>,
+        you: &'a types::Battlesnake,
+    ) -> Self {
+        return Connectivity {
+            board,
+            game_board,
+            you,
+            incremental: true,
+            labels: OnceLock::new(),
+            visits: AtomicU64::new(0),
+        };
+    }
+
+    /// # full_fills
+    /// a connectivity that answers nothing itself, to compare the fills against
+    #[cfg(test)]
+    pub fn full_fills(
+        board: &'a types::Board,
+        game_board: &'a types::FastMap<Coord, types::Flags>,
+        you: &'a types::Battlesnake,
+    ) -> Self {
+        let mut connectivity = Connectivity::new(board, game_board, you);
+        connectivity.incremental = false;
+        return connectivity;
+    }
+
+    /// # connected_tiles
+    /// the tiles a flood fill from a tile would count: every tile joined to its open neighbours, with the tile itself
+    /// and the fill's empty frontier counted on top the way `num_connected_tiles` does
+    /// ## Arguments:
+    /// * tile - the tile the fill starts from
+    /// * exclude_tiles - the tiles the fill can't go through
+    /// ## Returns:
+    /// the count, none when more than one tile is excluded and it's up to the fill
+    pub fn connected_tiles(&self, tile: &Coord, exclude_tiles: &[Coord]) -> Option<u32> {
+        let excluded = match exclude_tiles {
+            _ if !self.incremental => return None,
+            [] => None,
+            [excluded] => Some(*excluded),
+            _ => return None,
+        };
+        let labels = self.labels();
+        // a tile we couldn't move onto anyway changes nothing
+        let excluded = excluded
+            .and_then(|excluded| self.index(&excluded))
+            .filter(|&excluded| labels.region[excluded].is_some());
+
+        let mut pieces: Vec<Piece> = vec![];
+        let mut connected = 2;
+        for neighbour in self.neighbours(tile) {
+            if Some(neighbour) == excluded || labels.region[neighbour].is_none() {
+                continue;
+            }
+            let (piece, size) = self.piece(labels, neighbour, excluded);
+            if !pieces.contains(&piece) {
+                pieces.push(piece);
+                connected += size;
+            }
+        }
+        return Some(connected);
+    }
+
+    /// # record_fill
+    /// counts the tiles a fill this connectivity left a question to visited
+    pub fn record_fill(&self, tiles: u32) {
+        self.visits.fetch_add(tiles as u64, Ordering::Relaxed);
+    }
+
+    /// # visits
+    /// the tiles visited answering the questions so far
+    pub fn visits(&self) -> u64 {
+        return self.visits.load(Ordering::Relaxed);
+    }
+
+    /// # piece
+    /// the part of its region an open tile is in with a tile taken out. a tile whose removal splits its region is an
+    /// articulation point: the subtrees below its children that have no edge above it are cut off, and their sizes
+    /// came out of the labelling, so neither side has to be filled to be counted
+    /// ## Arguments:
+    /// * tile - the open tile
+    /// * excluded - the open tile taken out, if any
+    /// ## Returns:
+    /// the piece and the number of tiles in it
+    fn piece(&self, labels: &Labels, tile: usize, excluded: Option<usize>) -> (Piece, u32) {
+        let region = labels.region[tile].unwrap_or_default();
+        let Some(excluded) = excluded.filter(|&excluded| labels.region[excluded] == Some(region))
+        else {
+            return (Piece::Region(region), labels.sizes[region]);
+        };
+        let mut cut_off = 0;
+        for child in self.neighbours_of(excluded) {
+            if labels.parent[child] != Some(excluded) || labels.low[child] < labels.order[excluded]
+            {
+                continue;
+            }
+            let below = labels.order[child]..labels.order[child] + labels.subtree[child];
+            if below.contains(&labels.order[tile]) {
+                return (Piece::Below(child), labels.subtree[child]);
+            }
+            cut_off += labels.subtree[child];
+        }
+        return (Piece::Above(region), labels.sizes[region] - 1 - cut_off);
+    }
+
+    fn labels(&self) -> &Labels {
+        return self.labels.get_or_init(|| self.label());
+    }
+
+    /// # label
+    /// labels the open tiles by region with an iterative depth first search, recording the order each tile was reached
+    /// in, the subtree below it and the earliest tile that subtree has an edge back to
+    fn label(&self) -> Labels {
+        let tiles = self.board.width as usize * self.board.height as usize;
+        let open: Vec<bool> = (0..tiles)
+            .map(|index| {
+                logic::can_move_board(
+                    &self.coord(index),
+                    self.board,
+                    self.game_board,
+                    self.you,
+                    &logic::MoveOptions::default(),
+                )
+            })
+            .collect();
+        let mut labels = Labels {
+            region: vec![None; tiles],
+            sizes: vec![],
+            order: vec![0; tiles],
+            low: vec![0; tiles],
+            subtree: vec![1; tiles],
+            parent: vec![None; tiles],
+        };
+        let mut reached = 0;
+        for root in 0..tiles {
+            if !open[root] || labels.region[root].is_some() {
+                continue;
+            }
+            let region = labels.sizes.len();
+            let first = reached;
+            labels.region[root] = Some(region);
+            labels.order[root] = reached;
+            labels.low[root] = reached;
+            reached += 1;
+            // each tile on the stack with the neighbours it has left to look at
+            let mut stack = vec![(root, self.neighbours_of(root))];
+            while let Some((tile, neighbours)) = stack.last_mut() {
+                let tile = *tile;
+                let Some(next) = neighbours.pop() else {
+                    stack.pop();
+                    if let Some(parent) = labels.parent[tile] {
+                        labels.low[parent] = labels.low[parent].min(labels.low[tile]);
+                        labels.subtree[parent] += labels.subtree[tile];
+                    }
+                    continue;
+                };
+                if !open[next] {
+                    continue;
+                }
+                if labels.region[next].is_some() {
+                    if labels.parent[tile] != Some(next) {
+                        labels.low[tile] = labels.low[tile].min(labels.order[next]);
+                    }
+                    continue;
+                }
+                labels.region[next] = Some(region);
+                labels.parent[next] = Some(tile);
+                labels.order[next] = reached;
+                labels.low[next] = reached;
+                reached += 1;
+                stack.push((next, self.neighbours_of(next)));
+            }
+            labels.sizes.push(reached - first);
+        }
+        self.visits.fetch_add(reached as u64, Ordering::Relaxed);
+        return labels;
+    }
+
+    fn coord(&self, index: usize) -> Coord {
+        let width = self.board.width as usize;
+        return Coord {
+            x: (index % width) as i16,
+            y: (index / width) as i16,
+        };
+    }
+
+    fn index(&self, tile: &Coord) -> Option<usize> {
+        if !self.board.in_bounds(tile) {
+            return None;
+        }
+        return Some(tile.y as usize * self.board.width as usize + tile.x as usize);
+    }
+
+    /// the tiles next to a tile on the board
+    fn neighbours(&self, tile: &Coord) -> Vec<usize> {
+        return STEPS
+            .iter()
+            .filter_map(|(x, y)| {
+                self.index(&Coord {
+                    x: tile.x + x,
+                    y: tile.y + y,
+                })
+            })
+            .collect();
+    }
+
+    fn neighbours_of(&self, index: usize) -> Vec<usize> {
+        return self.neighbours(&self.coord(index));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn articulation_points_split_their_region() {
+        // a wall across the board with a single gap, and a dead end off the open side
+        let mut board = types::Board {
+            height: 5,
+            width: 5,
+            food: vec![],
+            hazards: vec![],
+            snakes: vec![],
+            hazard_odds: vec![],
+            hazard_walls: false,
+        };
+        let wall: Vec<Coord> = [(0, 2), (1, 2), (3, 2), (4, 2), (4, 0)]
+            .iter()
+            .map(|&(x, y)| Coord { x, y })
+            .collect();
+        board.snakes.push(types::Battlesnake {
+            id: "wall".to_string(),
+            name: "wall".to_string(),
+            health: 100,
+            head: wall[0],
+            length: wall.len() as u32,
+            body: wall,
+            shout: None,
+            squad: String::new(),
+            latency: None,
+        });
+        let you = board.snakes[0].clone();
+        let game_board = board.to_game_board();
+        let connectivity = Connectivity::new(&board, &game_board, &you);
+
+        let gap = Coord { x: 2, y: 2 };
+        // nothing excluded: the whole region, less the five wall tiles
+        assert_eq!(
+            connectivity.connected_tiles(&Coord { x: 2, y: 4 }, &[]),
+            Some(2 + 20)
+        );
+        // the gap cuts the board in two, ten tiles above and nine below
+        assert_eq!(
+            connectivity.connected_tiles(&Coord { x: 2, y: 4 }, &[gap]),
+            Some(2 + 10)
+        );
+        assert_eq!(
+            connectivity.connected_tiles(&Coord { x: 2, y: 0 }, &[gap]),
+            Some(2 + 9)
+        );
+        // from the gap itself both sides are counted
+        assert_eq!(connectivity.connected_tiles(&gap, &[gap]), Some(2 + 19));
+        // a tile that doesn't split anything only takes itself away
+        assert_eq!(
+            connectivity.connected_tiles(&Coord { x: 0, y: 4 }, &[Coord { x: 1, y: 4 }]),
+            Some(2 + 19)
+        );
+        // the tile behind the wall's end only has one way out, taking it away cuts that tile off too
+        assert_eq!(
+            connectivity.connected_tiles(&Coord { x: 4, y: 1 }, &[Coord { x: 3, y: 1 }]),
+            Some(2)
+        );
+        assert_eq!(
+            connectivity.connected_tiles(&Coord { x: 2, y: 1 }, &[Coord { x: 3, y: 1 }]),
+            Some(2 + 18)
+        );
+        assert_eq!(
+            connectivity.connected_tiles(&gap, &[gap, Coord { x: 2, y: 3 }]),
+            None
+        );
+    }
+}
@@ -0,0 +1,410 @@
+use crate::metrics;
+use crate::search::minimax::{candidate_moves, MOVES};
+use crate::search::state::SearchBoard;
+use crate::types::{self, Coord, Flags, Ruleset};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Instant;
+
+/// the most free tiles a shared region can have for the endgame to be worth solving exactly
+pub const ENDGAME_TILES: usize = 20;
+
+/// how many nodes are searched between looks at the clock
+const CLOCK_INTERVAL: u64 = 1024;
+
+/// how deep the solver looks by default, snakes chasing their tails can go on until they starve
+pub const MAX_DEPTH: u8 = 64;
+
+const WIN: i32 = 1;
+const DRAW: i32 = 0;
+const LOSS: i32 = -1;
+
+/// how a solved endgame ends with best play from both snakes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Solution {
+    /// the unit direction to play
+    pub best_move: Coord,
+    pub outcome: Outcome,
+    /// the depth the outcome was proven at
+    pub depth: u8,
+}
+
+/// # shared_region
+/// flood fills the free tiles reachable from our head
+/// ## Returns:
+/// the free tiles of the region, none if the opponent's head doesn't border it
+fn shared_region(
+    board: &types::Board,
+    game_board: &types::FastMap<Coord, Flags>,
+    you: &types::Battlesnake,
+    them: &types::Battlesnake,
+) -> Option<HashSet<Coord>> {
+    let is_free = |tile: &Coord| {
+        board.in_bounds(tile)
+            && !game_board
+                .get(tile)
+                .is_some_and(|flags| flags.contains(Flags::SNAKE))
+    };
+    let mut region: HashSet<Coord> = HashSet::new();
+    let mut frontier: VecDeque<Coord> = VecDeque::from([you.head]);
+    while let Some(tile) = frontier.pop_front() {
+        for dir in types::DIRECTIONS.values() {
+            let adj = tile + *dir;
+            if !region.contains(&adj) && is_free(&adj) {
+                region.insert(adj);
+                frontier.push_back(adj);
+            }
+        }
+    }
+    let borders_them = types::DIRECTIONS
+        .values()
+        .any(|dir| region.contains(&(them.head + *dir)));
+    if !borders_them {
+        return None;
+    }
+    return Some(region);
+}
+
+/// # is_endgame
+/// a duel is an endgame when both snakes share one region of at most `max_tiles` free tiles
+/// ## Arguments:
+/// * board - the battlesnake game board
+/// * you_id - the id of our snake
+/// * max_tiles - the most free tiles the shared region can have
+pub fn is_endgame(board: &types::Board, you_id: &str, max_tiles: usize) -> bool {
+    if board.snakes.len() != 2 {
+        return false;
+    }
+    let (you, them) = match board.snakes.iter().position(|snake| snake.id == you_id) {
+        Some(0) => (&board.snakes[0], &board.snakes[1]),
+        Some(_) => (&board.snakes[1], &board.snakes[0]),
+        None => return false,
+    };
+    let game_board = board.to_game_board();
+    return shared_region(board, &game_board, you, them)
+        .is_some_and(|region| region.len() <= max_tiles);
+}
+
+/// exhaustive alpha-beta over a two snake endgame. positions are scored as won, drawn or lost, positions past the depth
+/// limit are scored as a loss to prove wins and as a win to prove losses
+struct Solver<'a> {
+    you_id: &'a str,
+    rules: Ruleset,
+    deadline: Instant,
+    /// exact values keyed by the position hash, the remaining depth and which way unknown positions were scored
+    table: HashMap<(u64, u8, i32), i32>,
+    nodes: u64,
+    timed_out: bool,
+}
+
+impl Solver<'_> {
+    fn out_of_time(&mut self) -> bool {
+        if !self.timed_out && self.nodes.is_multiple_of(CLOCK_INTERVAL) {
+            self.timed_out = Instant::now() >= self.deadline;
+        }
+        return self.timed_out;
+    }
+
+    fn max_node(
+        &mut self,
+        state: &mut SearchBoard,
+        depth: u8,
+        unknown: i32,
+        mut alpha: i32,
+        beta: i32,
+    ) -> (i32, Option<Coord>) {
+        self.nodes += 1;
+        let you = match state
+            .board
+            .snakes
+            .iter()
+            .find(|snake| snake.id == self.you_id)
+        {
+            Some(snake) => snake.clone(),
+            None => return (LOSS, None),
+        };
+        if depth == 0 || self.out_of_time() {
+            return (unknown, None);
+        }
+        let key = (state.hash, depth, unknown);
+        if let Some(value) = self.table.get(&key) {
+            return (*value, None);
+        }
+
+        let original_alpha = alpha;
+        let mut best = LOSS - 1;
+        let mut best_move = None;
+        for mv in candidate_moves(&you) {
+            let value = self.min_node(state, &mv, depth, unknown, alpha, beta);
+            if value > best {
+                best = value;
+                best_move = Some(mv);
+            }
+            alpha = alpha.max(value);
+            if alpha >= beta || self.timed_out {
+                break;
+            }
+        }
+        if !self.timed_out && best > original_alpha && best < beta {
+            self.table.insert(key, best);
+        }
+        return (best, best_move);
+    }
+
+    fn min_node(
+        &mut self,
+        state: &mut SearchBoard,
+        our_move: &Coord,
+        depth: u8,
+        unknown: i32,
+        alpha: i32,
+        mut beta: i32,
+    ) -> i32 {
+        self.nodes += 1;
+        let them = match state
+            .board
+            .snakes
+            .iter()
+            .find(|snake| snake.id != self.you_id)
+        {
+            Some(snake) => snake.clone(),
+            None => return WIN,
+        };
+        let mut best = WIN + 1;
+        for reply in candidate_moves(&them) {
+            let moves: Vec<Coord> = state
+                .board
+                .snakes
+                .iter()
+                .map(|snake| {
+                    if snake.id == self.you_id {
+                        *our_move
+                    } else {
+                        reply
+                    }
+                })
+                .collect();
+            let token = state.apply(&moves, &self.rules);
+            let you_alive = state
+                .board
+                .snakes
+                .iter()
+                .any(|snake| snake.id == self.you_id);
+            let them_alive = state.board.snakes.iter().any(|snake| snake.id == them.id);
+            let value = match (you_alive, them_alive) {
+                (false, false) => DRAW,
+                (false, true) => LOSS,
+                (true, false) => WIN,
+                (true, true) => self.max_node(state, depth - 1, unknown, alpha, beta).0,
+            };
+            state.undo(token);
+            best = best.min(value);
+            beta = beta.min(value);
+            if alpha >= beta || self.timed_out {
+                break;
+            }
+        }
+        return best;
+    }
+}
+
+/// # solve
+/// searches a two snake endgame to its end with iterative deepening, giving up at the deadline
+/// ## Arguments:
+/// * board - the position to solve
+/// * you_id - the id of our snake
+/// * turn - the turn number of the position
+/// * rules - the hazard damage and royale settings
+/// * max_depth - the deepest the iterative deepening goes before giving up, `MAX_DEPTH` unless the opponents leave
+///   us time to spare
+/// * deadline - when to give up
+/// ## Returns:
+/// the best move and its proven outcome, none if the outcome couldn't be proven in time or depth
+pub fn solve(
+    board: &types::Board,
+    you_id: &str,
+    turn: u32,
+    rules: &Ruleset,
+    max_depth: u8,
+    deadline: Instant,
+) -> Option<Solution> {
+    if board.snakes.len() != 2 || !board.snakes.iter().any(|snake| snake.id == you_id) {
+        return None;
+    }
+    let span = tracing::debug_span!("endgame", nodes = tracing::field::Empty, depth = tracing::field::Empty).entered();
+    let mut solver = Solver {
+        you_id,
+        rules: *rules,
+        deadline,
+        table: HashMap::new(),
+        nodes: 0,
+        timed_out: false,
+    };
+    let mut state = SearchBoard::new(board.clone(), turn);
+    let solution = deepen(&mut solver, &mut state, max_depth);
+    span.record("nodes", solver.nodes);
+    if let Some(solution) = &solution {
+        span.record("depth", solution.depth);
+    }
+    metrics::SEARCH_NODES.add("endgame", solver.nodes);
+    if solver.timed_out {
+        metrics::DEADLINE_DEGRADATIONS.add("endgame", 1);
+    }
+    return solution;
+}
+
+/// # deepen
+/// the iterative deepening behind `solve`, none once the solver times out or runs out of depth
+fn deepen(solver: &mut Solver, state: &mut SearchBoard, max_depth: u8) -> Option<Solution> {
+    for depth in 1..=max_depth {
+        // scoring the unknown as a loss only leaves proven wins winning, and the other way round for losses
+        let (pessimistic, win_move) = solver.max_node(state, depth, LOSS, LOSS - 1, WIN + 1);
+        if solver.timed_out {
+            return None;
+        }
+        if pessimistic == WIN {
+            return win_move.map(|best_move| Solution {
+                best_move,
+                outcome: Outcome::Win,
+                depth,
+            });
+        }
+        let (optimistic, best_move) = solver.max_node(state, depth, WIN, LOSS - 1, WIN + 1);
+        if solver.timed_out {
+            return None;
+        }
+        let outcome = match (pessimistic, optimistic) {
+            (_, LOSS) => Outcome::Loss,
+            (DRAW, DRAW) => Outcome::Draw,
+            _ => continue,
+        };
+        // a drawn line is only proven by the pessimistic search, every move loses when losing
+        let best_move = if outcome == Outcome::Draw {
+            win_move
+        } else {
+            best_move
+        };
+        return Some(Solution {
+            best_move: best_move.unwrap_or(MOVES[0]),
+            outcome,
+            depth,
+        });
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    // 5x4, we're longer and can pin the other snake against the bottom left corner: down takes the only tile that
+    // isn't the corner, then we follow it into the corner
+    const CORNER_DATA: &str = r#"
+      {
+        "food": [],
+        "snakes": [
+          {
+            "id": "me",
+            "name": "me",
+            "health": 90,
+            "body": [
+              { "x": 1, "y": 2 },
+              { "x": 2, "y": 2 },
+              { "x": 3, "y": 2 },
+              { "x": 4, "y": 2 },
+              { "x": 4, "y": 3 }
+            ],
+            "latency": 0,
+            "head": { "x": 1, "y": 2 },
+            "length": 5,
+            "shout": ""
+          },
+          {
+            "id": "them",
+            "name": "them",
+            "health": 90,
+            "body": [
+              { "x": 1, "y": 0 },
+              { "x": 2, "y": 0 },
+              { "x": 3, "y": 0 }
+            ],
+            "latency": 0,
+            "head": { "x": 1, "y": 0 },
+            "length": 3,
+            "shout": ""
+          }
+        ],
+        "width": 5,
+        "height": 4,
+        "hazards": []
+      }
+    "#;
+
+    #[test]
+    fn solves_small_endgame() {
+        let board: types::Board = serde_json::from_str(CORNER_DATA).unwrap();
+        assert!(is_endgame(&board, "me", ENDGAME_TILES));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let solution = solve(&board, "me", 100, &Ruleset::default(), MAX_DEPTH, deadline).unwrap();
+        assert_eq!(solution.outcome, Outcome::Win);
+        assert_eq!(solution.best_move, types::DIRECTIONS["down"]);
+        assert_eq!(solution.depth, 2);
+    }
+
+    #[test]
+    fn falls_back_on_large_region() {
+        const BOARD_DATA: &str = r#"
+          {
+            "food": [],
+            "snakes": [
+              {
+                "id": "me",
+                "name": "me",
+                "health": 90,
+                "body": [
+                  { "x": 3, "y": 5 },
+                  { "x": 3, "y": 4 },
+                  { "x": 3, "y": 3 }
+                ],
+                "latency": 0,
+                "head": { "x": 3, "y": 5 },
+                "length": 3,
+                "shout": ""
+              },
+              {
+                "id": "them",
+                "name": "them",
+                "health": 90,
+                "body": [
+                  { "x": 7, "y": 5 },
+                  { "x": 7, "y": 6 },
+                  { "x": 7, "y": 7 }
+                ],
+                "latency": 0,
+                "head": { "x": 7, "y": 5 },
+                "length": 3,
+                "shout": ""
+              }
+            ],
+            "width": 11,
+            "height": 11,
+            "hazards": []
+          }
+        "#;
+        let board: types::Board = serde_json::from_str(BOARD_DATA).unwrap();
+        assert!(!is_endgame(&board, "me", ENDGAME_TILES));
+
+        // forced to try anyway, the solver gives up at the deadline instead of guessing
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(50);
+        assert_eq!(solve(&board, "me", 10, &Ruleset::default(), MAX_DEPTH, deadline), None);
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::logic;
+use crate::search::eval;
+use crate::search::minimax::candidate_moves;
+use crate::search::opponent::{GreedyPolicy, OpponentPolicy};
+use crate::types::{self, Coord, Ruleset};
+
+/// the most moves the solver looks ahead for a way out
+pub const ESCAPE_DEPTH: u8 = 8;
+
+/// # opponent_move
+/// how the solver expects an opponent to move: straight on while it can, otherwise where its greedy policy would go
+fn opponent_move(
+    board: &types::Board,
+    game_board: &types::FastMap<Coord, types::Flags>,
+    snake: &types::Battlesnake,
+    policy: &GreedyPolicy,
+) -> Coord {
+    if let Some(heading) = snake.heading() {
+        let anywhere = logic::MoveOptions::default().avoid_snake_heads(false);
+        if logic::can_move_board(&(snake.head + heading), board, game_board, snake, &anywhere) {
+            return heading;
+        }
+    }
+    return policy
+        .predict(board, game_board, snake)
+        .or_else(|| snake.heading())
+        .unwrap_or(Coord { x: 0, y: 1 });
+}
+
+/// # in_open_space
+/// whether a snake's head is out in the open: every move but back onto its neck is free and there's room for twice its
+/// length behind them
+pub fn in_open_space(board: &types::Board, snake: &types::Battlesnake) -> bool {
+    let game_board = board.to_game_board();
+    let anywhere = logic::MoveOptions::default().avoid_snake_heads(false);
+    let free = candidate_moves(snake)
+        .into_iter()
+        .filter(|dir| logic::can_move_board(&(snake.head + *dir), board, &game_board, snake, &anywhere))
+        .count();
+    return free >= 3 && eval::reachable_tiles(&snake.head, board, &game_board) >= 2 * snake.length as i32;
+}
+
+/// searches our moves alone for a way out, the opponents move as `opponent_move` expects
+struct Solver<'a> {
+    you_id: &'a str,
+    rules: Ruleset,
+    policy: GreedyPolicy,
+    /// the moves made to reach the position being searched
+    plan: Vec<Coord>,
+}
+
+impl Solver<'_> {
+    /// # search
+    /// looks for a way out of the position within `depth` more moves, leaving the board as it was
+    /// ## Returns:
+    /// true with the way out in `plan` if there is one
+    fn search(&mut self, board: &mut types::Board, turn: u32, depth: u8) -> bool {
+        let Some(index) = board.snakes.iter().position(|snake| snake.id == self.you_id) else {
+            return false;
+        };
+        if !self.plan.is_empty() && in_open_space(board, &board.snakes[index]) {
+            return true;
+        }
+        if depth == 0 {
+            return false;
+        }
+        let game_board = board.to_game_board();
+        let mut moves: Vec<Coord> = board
+            .snakes
+            .iter()
+            .map(|snake| opponent_move(board, &game_board, snake, &self.policy))
+            .collect();
+        let you = &board.snakes[index];
+        let anywhere = logic::MoveOptions::default().avoid_snake_heads(false);
+        let legal: Vec<Coord> = candidate_moves(you)
+            .into_iter()
+            .filter(|dir| logic::can_move_board(&(you.head + *dir), board, &game_board, you, &anywhere))
+            .collect();
+        for dir in legal {
+            moves[index] = dir;
+            let token = board.simulate_turn(&moves, turn + 1, &self.rules);
+            self.plan.push(dir);
+            let found = self.search(board, turn + 1, depth - 1);
+            board.undo(token);
+            if found {
+                return true;
+            }
+            self.plan.pop();
+        }
+        return false;
+    }
+}
+
+/// # solve
+/// looks for the shortest sequence of our moves that gets our head out into the open, for when a longer snake has
+/// pinned us in a lane that greedy move by move choices can't see the way out of
+/// ## Arguments:
+/// * board - the battlesnake game board
+/// * you_id - the id of our snake
+/// * turn - the turn number the board is at
+/// * rules - the hazard damage and royale settings
+/// * depth - the most moves to look ahead
+/// ## Returns:
+/// the unit directions of the way out in the order they're played, none if there isn't one within `depth` moves
+pub fn solve(board: &types::Board, you_id: &str, turn: u32, rules: &Ruleset, depth: u8) -> Option<Vec<Coord>> {
+    let mut board = board.clone();
+    let mut solver = Solver {
+        you_id,
+        rules: *rules,
+        policy: GreedyPolicy::default(),
+        plan: vec![],
+    };
+    for limit in 1..=depth {
+        if solver.search(&mut board, turn, limit) {
+            return Some(solver.plan);
+        }
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pinned() -> types::GameState {
+        return serde_json::from_str(
+            &std::fs::read_to_string("testdata/fixtures/pinned_against_the_wall.json").unwrap(),
+        )
+        .unwrap();
+    }
+
+    /// every way out within `depth` moves, by brute force over the same moves the solver plays
+    fn ways_out(board: &mut types::Board, you_id: &str, turn: u32, depth: u8, plan: &mut Vec<Coord>) -> Vec<Vec<Coord>> {
+        let Some(index) = board.snakes.iter().position(|snake| snake.id == you_id) else {
+            return vec![];
+        };
+        if !plan.is_empty() && in_open_space(board, &board.snakes[index]) {
+            return vec![plan.clone()];
+        }
+        if depth == 0 {
+            return vec![];
+        }
+        let policy = GreedyPolicy::default();
+        let game_board = board.to_game_board();
+        let mut moves: Vec<Coord> =
+            board.snakes.iter().map(|snake| opponent_move(board, &game_board, snake, &policy)).collect();
+        let mut found = vec![];
+        for dir in types::DIRECTIONS.values() {
+            moves[index] = *dir;
+            let token = board.simulate_turn(&moves, turn + 1, &Ruleset::default());
+            plan.push(*dir);
+            found.extend(ways_out(board, you_id, turn + 1, depth - 1, plan));
+            plan.pop();
+            board.undo(token);
+        }
+        return found;
+    }
+
+    #[test]
+    fn finds_the_one_way_out_along_the_wall() {
+        let state = pinned();
+        let (down, right) = (Coord { x: 0, y: -1 }, Coord { x: 1, y: 0 });
+        let plan = solve(&state.board, &state.you.id, state.turn, &Ruleset::default(), ESCAPE_DEPTH);
+        assert_eq!(plan, Some(vec![down, down, down, right]));
+
+        // nothing shorter gets out, and nothing else as short
+        let mut board = state.board.clone();
+        let all = ways_out(&mut board, &state.you.id, state.turn, 4, &mut vec![]);
+        assert_eq!(all, vec![vec![down, down, down, right]]);
+    }
+
+    #[test]
+    fn gives_up_past_its_depth() {
+        let state = pinned();
+        assert_eq!(solve(&state.board, &state.you.id, state.turn, &Ruleset::default(), 3), None);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::search::scratch::with_scratch;
+use crate::types::{self, board_tile_is_free, get_board_tile, Coord};
+use serde::{Deserialize, Serialize};
+
+/// score of a position where we've won, positions where we lose score the negative of this
+pub const WIN_SCORE: i32 = 1_000_000;
+
+/// weights used to turn the features of a position (or of a single move) into a score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Weights {
+    /// per tile reachable from our head
+    pub space: i32,
+    /// per unit of length we have over the longest opponent
+    pub length: i32,
+    /// per point of health
+    pub health: i32,
+    /// per free tile adjacent to the tile a move lands on
+    pub degree: i32,
+    /// per tile of distance between a move and the closest food
+    pub food: i32,
+    /// per tile of distance between a move and the center of the board
+    pub center: i32,
+}
+
+impl Weights {
+    pub const DEFAULT: Weights = Weights {
+        space: 10,
+        length: 30,
+        health: 1,
+        degree: 10,
+        food: 3,
+        center: 1,
+    };
+
+    /// # named_mut
+    /// every weight with its name, in the order they're declared
+    pub fn named_mut(&mut self) -> [(&'static str, &mut i32); 6] {
+        return [
+            ("space", &mut self.space),
+            ("length", &mut self.length),
+            ("health", &mut self.health),
+            ("degree", &mut self.degree),
+            ("food", &mut self.food),
+            ("center", &mut self.center),
+        ];
+    }
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        return Weights::DEFAULT;
+    }
+}
+
+fn manhattan(a: &Coord, b: &Coord) -> i32 {
+    return ((a.x - b.x).abs() + (a.y - b.y).abs()) as i32;
+}
+
+/// # passable
+/// determines if a snake could occupy a tile next turn. tails are passable because they move out of the way
+fn passable(
+    tile: &Coord,
+    board: &types::Board,
+    game_board: &types::FastMap<Coord, types::Flags>,
+) -> bool {
+    if !board.in_bounds(tile) {
+        return false;
+    }
+    let board_tile = get_board_tile!(game_board, tile.x, tile.y);
+    return board_tile_is_free!(board_tile)
+        || board
+            .snakes
+            .iter()
+            .any(|snake| snake.body.last() == Some(tile) && snake.health < 100);
+}
+
+/// # reachable_tiles
+/// counts the tiles reachable from a tile without crossing a snake body
+pub fn reachable_tiles(
+    from: &Coord,
+    board: &types::Board,
+    game_board: &types::FastMap<Coord, types::Flags>,
+) -> i32 {
+    return with_scratch(board, |scratch| {
+        let (frontier, visited) = (&mut scratch.frontier, &mut scratch.visited);
+        frontier.push_back(*from);
+        visited.insert(*from);
+        while let Some(tile) = frontier.pop_front() {
+            for dir in types::DIRECTIONS.values() {
+                let adj = tile + *dir;
+                if !visited.contains(&adj) && passable(&adj, board, game_board) {
+                    visited.insert(adj);
+                    frontier.push_back(adj);
+                }
+            }
+        }
+        // the starting tile is our own head
+        return visited.len() as i32 - 1;
+    });
+}
+
+/// # evaluate
+/// statically evaluates a position from the perspective of one snake and its squad
+/// ## Arguments:
+/// * board - the position to evaluate
+/// * game_board - the hashmap representation of the game board
+/// * you_id - the id of the snake we're evaluating for
+/// * mates - the ids of our squadmates, a position is only as good as it is for the worst off of us and losing any of
+///   us loses it
+/// * weights - the feature weights
+/// ## Returns:
+/// the score of the position, higher is better for `you_id`
+pub fn evaluate(
+    board: &types::Board,
+    game_board: &types::FastMap<Coord, types::Flags>,
+    you_id: &str,
+    mates: &[String],
+    weights: &Weights,
+) -> i32 {
+    let mut squad: Vec<&types::Battlesnake> = vec![];
+    for id in std::iter::once(you_id).chain(mates.iter().map(String::as_str)) {
+        match board.snakes.iter().find(|snake| snake.id == id) {
+            Some(snake) => squad.push(snake),
+            None => return -WIN_SCORE,
+        }
+    }
+    let longest_opponent = board
+        .snakes
+        .iter()
+        .filter(|snake| !squad.contains(snake))
+        .map(|snake| snake.length as i32)
+        .max();
+
+    return squad
+        .into_iter()
+        .map(|snake| {
+            let space = reachable_tiles(&snake.head, board, game_board);
+            let longest_opponent = longest_opponent.unwrap_or(snake.length as i32);
+            space * weights.space
+                + (snake.length as i32 - longest_opponent) * weights.length
+                + snake.health as i32 * weights.health
+        })
+        .min()
+        .unwrap_or(-WIN_SCORE);
+}
+
+/// the static features of a single move, used to order moves before searching them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveScore {
+    /// number of passable tiles next to the tile we move onto
+    pub degree: i32,
+    /// manhattan distance from the tile we move onto to the closest food
+    pub food_distance: i32,
+    /// manhattan distance from the tile we move onto to the center of the board
+    pub center_distance: i32,
+}
+
+impl MoveScore {
+    /// # new
+    /// scores a move without searching it
+    /// ## Arguments:
+    /// * board - the battlesnake game board
+    /// * game_board - the hashmap representation of the game board
+    /// * snake - the snake making the move
+    /// * dir - the unit direction of the move
+    pub fn new(
+        board: &types::Board,
+        game_board: &types::FastMap<Coord, types::Flags>,
+        snake: &types::Battlesnake,
+        dir: &Coord,
+    ) -> MoveScore {
+        let tile = snake.head + *dir;
+        let degree = types::DIRECTIONS
+            .values()
+            .filter(|adj_dir| passable(&(tile + **adj_dir), board, game_board))
+            .count() as i32;
+        let food_distance = board
+            .food
+            .iter()
+            .map(|food| manhattan(&tile, food))
+            .min()
+            .unwrap_or(0);
+        let center = Coord {
+            x: board.width as i16 / 2,
+            y: board.height as i16 / 2,
+        };
+        return MoveScore {
+            degree,
+            food_distance,
+            center_distance: manhattan(&tile, &center),
+        };
+    }
+
+    pub fn value(&self, weights: &Weights) -> i32 {
+        return self.degree * weights.degree
+            - self.food_distance * weights.food
+            - self.center_distance * weights.center;
+    }
+}
@@ -1,66 +1,68 @@
-use crate::logic::{get_adj_tiles, get_all_adj_tiles};
-use crate::{get_board_tile, logic, types};
+use crate::context::TurnContext;
+use crate::logic::{get_adj_tiles, get_all_adj_tiles, AdjTiles, MoveOptions};
+use crate::types::{self, get_board_tile};
+use crate::logic;
 use ordered_float::OrderedFloat;
 use priority_queue::PriorityQueue;
-use std::cmp;
-use std::collections::{HashMap, HashSet, VecDeque};
+use crate::search::scratch::with_scratch;
+use crate::search::tiles::TileMap;
+use std::collections::VecDeque;
+use tracing::field::Empty;
 
 /// # dfs_long
 /// finds a long path to a specified coordinate. uses hueristic distance to approximate longest path
 /// ## Arguments
 /// * goal - the goal to search for
-/// * board - the game board object
-/// * game_board - the hash table representation of the game board (used for faster lookup)
-/// * you - our battlesnake
+/// * ctx - the turn's context
 /// * connection_threshold - the connectedness threshold we want tiles in the path to adhere to
 /// * degree_threshold - the minimum number of adjacent tiles that a given tile must have to be considered valid
 /// ## Returns:
 /// a path from our starting point to the goal
 pub fn dfs_long(
     goal: &types::Coord,
-    board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
-    you: &types::Battlesnake,
+    ctx: &TurnContext,
     connection_threshold: f32,
     degree_threshold: u8
 ) -> Vec<types::Coord> {
-    let mut visited: HashMap<types::Coord, types::Coord> = HashMap::new();
-    let success = depth_first_search_logic(
-        goal,
-        &you.head,
-        board,
-        game_board,
-        you,
-        &mut visited,
-        connection_threshold,
-        degree_threshold
-    );
-    return match success {
-        Some(tile) => backtrack(tile, &visited),
-        None => vec![],
-    };
+    let span = tracing::debug_span!("dfs_long", path_len = Empty).entered();
+    let path = with_scratch(ctx.board, |scratch| {
+        let success = depth_first_search_logic(
+            goal,
+            &ctx.you.head,
+            ctx,
+            &mut scratch.parents,
+            &mut scratch.path,
+            connection_threshold,
+            degree_threshold,
+        );
+        return match success {
+            Some(tile) => backtrack(tile, &scratch.parents),
+            None => vec![],
+        };
+    });
+    span.record("path_len", path.len());
+    return path;
 }
 
 /// # depth_first_search_logic
 /// Approximates the longest path to a specified coord using a priority queue
 /// ## Arguments
 /// * goal - the goal tile to search for
-/// * board - the game board object
-/// * game_board - the hash table representation of the game board (used for faster lookup)
-/// * you - our battlesnake
+/// * ctx - the turn's context
 /// * frontier - keeps track of the tiles we haven't visited yet in our search
 /// * visited - keeps track of the tiles we've already visited during our search and their parent nodes (values are the parent coords)
+/// * path - the tiles from our head to `from` with the head left out, the path `visited` leads back along
 /// * connection_threshold - the connectedness threshold we want tiles in the path to adhere to
 /// * degree_threshold - the minimum number of adjacent tiles that a given tile must have to be considered valid
 /// ## Returns:
 /// an option of a tile containing a food if a path is successfully found
+#[allow(clippy::only_used_in_recursion)]
 fn depth_first_search_logic(
     goal: &types::Coord,
     from: &types::Coord,
-    board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
-    you: &types::Battlesnake,
-    visited: &mut HashMap<types::Coord, types::Coord>,
+    ctx: &TurnContext,
+    visited: &mut TileMap<types::Coord>,
+    path: &mut Vec<types::Coord>,
     connection_threshold: f32,
     degree_threshold: u8,
 ) -> Option<types::Coord> {
@@ -69,44 +71,33 @@ fn depth_first_search_logic(
         return Some(*goal);
     }
 
-    // get current path so we make sure we don't intersect our own path
-    let current_path = backtrack(*from, visited);
-    let path_index =
-        usize::try_from(cmp::max(0, current_path.len() as i32 - you.length as i32)).unwrap_or(0);
-    let future_snake_positions: Vec<types::Coord> = current_path[path_index..].to_vec();
-
-    // get adj tiles if they haven't been visited before and they're not in the current path
-    let mut adj_tiles: Vec<types::Coord> = logic::get_adj_tiles_connected(
-        from,
-        board,
-        &game_board,
-        you,
-        0.0,
-        0,
-        Some(true),
-        None,
-        None,
-        Some(future_snake_positions),
-    )
-    .into_iter()
-    .filter(|item| visited.get(item).is_none())
-    .collect();
-
-    adj_tiles.sort_by(|a, b| goal.distance(b).partial_cmp(&goal.distance(a)).unwrap());
-
-    // mark adj tiles as visited and link the parent node
+    // the end of the current path is where our body will be, make sure we don't intersect it
+    let future_snake_positions = &path[path.len().saturating_sub(ctx.you.length as usize)..];
+
+    // get adj tiles if they haven't been visited before and they're not in the current path, or the head it started
+    // from, which has no parent to stop the backtrack at once it's open
+    let options = MoveOptions::default().exclude(future_snake_positions);
+    let mut adj_tiles: Vec<types::Coord> = logic::get_adj_tiles_connected(from, ctx, &options)
+        .into_iter()
+        .filter(|item| visited.get(item).is_none() && *item != ctx.you.head)
+        .collect();
+
+    adj_tiles.sort_by(|a, b| goal.distance(b).total_cmp(&goal.distance(a)));
+
+    // mark adj tiles as visited and link the parent node, the path follows the search down and back up
     for tile in &adj_tiles {
         visited.insert(*tile, *from);
+        path.push(*tile);
         let success = depth_first_search_logic(
             goal,
             tile,
-            board,
-            game_board,
-            you,
+            ctx,
             visited,
+            path,
             connection_threshold,
-            degree_threshold
+            degree_threshold,
         );
+        path.pop();
         if success.is_some() {
             return success;
         }
@@ -116,319 +107,567 @@ fn depth_first_search_logic(
     return None;
 }
 
-pub fn inside_box(
-    you: &types::Battlesnake,
-    board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
-    box_threshold: f32,
-) -> bool {
-    let mut frontier: VecDeque<types::Coord> = VecDeque::from([you.head]);
-    let mut visited: HashSet<types::Coord> = HashSet::new();
-    let num_free_tiles = logic::num_free_tiles(board);
-    return inside_box_logic(
-        you,
-        board,
-        game_board,
-        &mut frontier,
-        &mut visited,
-        num_free_tiles,
-        box_threshold,
-    );
+/// which tiles a flood fill goes through
+#[derive(Debug, Clone, Copy)]
+pub enum Passable<'a> {
+    /// the tiles we could move onto this turn, see `TurnContext::can_move`
+    Open,
+    /// every tile on the board, for the fills that stop at what they run into instead
+    Board,
+    /// the open tiles and the tiles under a body whose tail will have moved off by the time the fill gets there, a
+    /// fill's starts being a move away
+    #[allow(dead_code)] // the stall planning and post-eat pocket checks will fill through this
+    Decaying(&'a types::TimedOccupancy),
 }
 
-fn inside_box_logic(
-    you: &types::Battlesnake,
-    board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
-    frontier: &mut VecDeque<types::Coord>,
-    visited: &mut HashSet<types::Coord>,
-    num_free_tiles: u16,
-    box_threshold: f32,
-) -> bool {
-    if frontier.is_empty() {
-        return true;
+/// what a flood fill goes through, when it stops and what it keeps. the defaults go through the open tiles, exclude
+/// nothing, fill the whole region, stop at nothing and keep the tiles they reach
+#[derive(Clone, Copy)]
+pub struct FillOptions<'a> {
+    passable: Passable<'a>,
+    exclude: &'a [types::Coord],
+    budget: Option<u32>,
+    stop: Option<&'a dyn Fn(&types::Coord) -> bool>,
+    count_only: bool,
+}
+
+impl Default for FillOptions<'_> {
+    fn default() -> Self {
+        return FillOptions {
+            passable: Passable::Open,
+            exclude: &[],
+            budget: None,
+            stop: None,
+            count_only: false,
+        };
     }
+}
 
-    let current_tile = frontier.pop_front().unwrap();
+impl<'a> FillOptions<'a> {
+    /// # passable
+    /// which tiles the fill goes through, the open ones by default
+    pub fn passable(mut self, passable: Passable<'a>) -> Self {
+        self.passable = passable;
+        return self;
+    }
 
-    let adj_tiles: Vec<types::Coord> = get_adj_tiles(&current_tile, board, game_board, you, None, None)
-        .into_iter()
-        .filter(|item| visited.get(item).is_none())
-        .collect();
+    /// # exclude
+    /// the tiles the fill can't go through whatever `passable` says, the starts included
+    pub fn exclude(mut self, exclude: &'a [types::Coord]) -> Self {
+        self.exclude = exclude;
+        return self;
+    }
 
-    for adj in &adj_tiles {
-        visited.insert(*adj);
+    /// # budget
+    /// the tiles to reach before the fill stops early, it fills the whole region without one
+    pub fn budget(mut self, budget: u32) -> Self {
+        self.budget = Some(budget);
+        return self;
     }
 
-    if (visited.len() as f32 / num_free_tiles as f32) > box_threshold {
-        return false;
+    /// # stop
+    /// the tiles the fill reaches but doesn't go past, kept as its boundary
+    pub fn stop(mut self, stop: &'a dyn Fn(&types::Coord) -> bool) -> Self {
+        self.stop = Some(stop);
+        return self;
     }
 
-    frontier.append(&mut VecDeque::from(adj_tiles));
+    /// # count_only
+    /// whether to only count the tiles the fill goes through instead of keeping them as well
+    pub fn count_only(mut self, count_only: bool) -> Self {
+        self.count_only = count_only;
+        return self;
+    }
+}
 
-    return inside_box_logic(
-        you,
-        board,
-        game_board,
-        frontier,
-        visited,
-        num_free_tiles,
-        box_threshold,
-    );
+/// what a flood fill found
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FillResult {
+    /// the tiles the fill went through, the starts among them and the boundary left out, no more than its budget
+    pub count: u32,
+    /// whether the fill stopped at its budget instead of running out of tiles
+    pub cut_short: bool,
+    /// the tiles the fill went through in the order it reached them, empty when it only counted
+    pub tiles: Vec<types::Coord>,
+    /// the tiles the stop predicate held the fill at, in the order it reached them
+    pub boundary: Vec<types::Coord>,
 }
 
-fn find_blocking_tiles(
-    board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
-    frontier: &mut VecDeque<types::Coord>,
-    visited: &mut HashSet<types::Coord>,
-    blocking_tiles: &mut Vec<types::Coord>,
-) {
-    if frontier.is_empty() {
-        return;
+/// # flood_fill
+/// a breadth first fill from a set of tiles. every tile is marked the moment it's reached, the starts too, so none is
+/// counted twice, and the fill runs off a queue rather than the stack, however large the board
+/// ## Arguments:
+/// * ctx - the turn's context
+/// * starts - the tiles to fill from, each a move away from where we stand. the ones the fill couldn't go through
+///   are left out and the ones the stop predicate holds are boundary
+/// * options - what the fill goes through, when it stops and what it keeps
+/// ## Returns:
+/// the tiles the fill reached
+pub fn flood_fill(ctx: &TurnContext, starts: &[types::Coord], options: &FillOptions) -> FillResult {
+    let mut fill = FillResult::default();
+    if options.budget == Some(0) {
+        fill.cut_short = true;
+        return fill;
     }
-
-    let current_tile = frontier.pop_front().unwrap();
-
-    if !(get_board_tile!(game_board, current_tile.x, current_tile.y) & types::Flags::SNAKE)
-        .is_empty()
-    {
-        blocking_tiles.push(current_tile);
-    } else {
-        let adj_tiles: Vec<types::Coord> = get_all_adj_tiles(&current_tile, board)
-            .into_iter()
-            .filter(|item| visited.get(item).is_none())
-            .collect();
-        for adj in &adj_tiles {
-            visited.insert(*adj);
+    let open = MoveOptions::default();
+    let passable = |tile: &types::Coord, steps: u16| {
+        if options.exclude.contains(tile) {
+            return false;
+        }
+        return match options.passable {
+            Passable::Open => ctx.can_move(tile, &open),
+            Passable::Board => ctx.board.in_bounds(tile),
+            Passable::Decaying(timed) => {
+                ctx.can_move(tile, &open) || (timed.occupied_at(tile, 0) && !timed.occupied_at(tile, steps))
+            }
+        };
+    };
+    return with_scratch(ctx.board, |scratch| {
+        let visited = &mut scratch.visited;
+        // marks a tile reached, true once the fill has reached its budget
+        let mut reach = |tile: types::Coord, steps: u16, frontier: &mut VecDeque<types::Coord>, fill: &mut FillResult| {
+            if visited.contains(&tile) || !passable(&tile, steps) {
+                return false;
+            }
+            visited.insert(tile);
+            if options.stop.is_some_and(|stop| stop(&tile)) {
+                fill.boundary.push(tile);
+                return false;
+            }
+            fill.count += 1;
+            if !options.count_only {
+                fill.tiles.push(tile);
+            }
+            frontier.push_back(tile);
+            return options.budget.is_some_and(|budget| fill.count >= budget);
+        };
+        let frontier = &mut scratch.frontier;
+        for start in starts {
+            if reach(*start, 1, frontier, &mut fill) {
+                fill.cut_short = true;
+                return fill;
+            }
         }
-        let mut adj_tiles_deque = VecDeque::from(adj_tiles);
-        frontier.append(&mut adj_tiles_deque);
+        // a layer at a time, for the decaying bodies to know how many moves out the fill is
+        let mut steps = 1;
+        while !frontier.is_empty() {
+            steps += 1;
+            for _ in 0..frontier.len() {
+                let Some(tile) = frontier.pop_front() else {
+                    break;
+                };
+                for adj in get_all_adj_tiles(&tile, ctx.board) {
+                    if reach(adj, steps, frontier, &mut fill) {
+                        fill.cut_short = true;
+                        return fill;
+                    }
+                }
+            }
+        }
+        return fill;
+    });
+}
+
+/// # inside_box
+/// whether the tiles we can reach are fewer than a share of the free tiles, the fill stops as soon as they aren't
+/// ## Arguments:
+/// * ctx - the turn's context
+/// * box_threshold - the share of the free tiles we have to reach to not be boxed in
+/// ## Returns:
+/// the tiles in the box, none when we aren't in one
+pub fn inside_box(ctx: &TurnContext, box_threshold: f32) -> Option<u16> {
+    // without a body there's no head to fill from
+    if ctx.you.body.is_empty() {
+        return None;
     }
-    find_blocking_tiles(board, game_board, frontier, visited, blocking_tiles);
+    // the fewest tiles that are more than the share, past the size of the board there's no stopping early
+    let beyond = |tiles: u32| tiles as f32 / ctx.free_tiles as f32 > box_threshold;
+    let area = ctx.board.width as u32 * ctx.board.height as u32;
+    let mut budget = ((box_threshold * ctx.free_tiles as f32).floor().max(0.0) as u32).min(area + 1);
+    while budget > 0 && beyond(budget - 1) {
+        budget -= 1;
+    }
+    while !beyond(budget) && budget <= area {
+        budget += 1;
+    }
+    let options = FillOptions::default().budget(budget).count_only(true);
+    let fill = flood_fill(ctx, &get_all_adj_tiles(&ctx.you.head, ctx.board), &options);
+    return (!fill.cut_short).then_some(fill.count as u16);
 }
 
 /// # find_key_hole
 /// given that the snake it trapped in a small region, find the tile that is our best bet to leave the region
-pub fn find_key_hole(
-    board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
-    you: &types::Battlesnake,
-) -> Option<types::Coord> {
-    let mut frontier: VecDeque<types::Coord> =
-        VecDeque::from(get_adj_tiles(&you.head, board, game_board, you, None, None));
-    let mut visited: HashSet<types::Coord> = HashSet::new();
-    let mut blocking_tiles: Vec<types::Coord> = Vec::new();
-    find_blocking_tiles(
-        board,
-        game_board,
-        &mut frontier,
-        &mut visited,
-        &mut blocking_tiles,
-    );
+pub fn find_key_hole(ctx: &TurnContext) -> Option<types::Coord> {
+    // fill the region over everything but the bodies, the ones it runs into are what's keeping us in
+    let game_board = ctx.game_board;
+    let blocking = |tile: &types::Coord| {
+        return !(get_board_tile!(game_board, tile.x, tile.y) & types::Flags::SNAKE).is_empty();
+    };
+    let options = FillOptions::default().passable(Passable::Board).stop(&blocking).count_only(true);
+    let mut blocking_tiles =
+        flood_fill(ctx, &get_adj_tiles(&ctx.you.head, ctx, &MoveOptions::default()), &options).boundary;
 
     // make sure we don't try to move back on our own head
-    blocking_tiles = blocking_tiles.into_iter().filter(|tile| *tile != you.head).collect();
-
-    blocking_tiles.sort_by(|a, b| {
-        let index_a;
-        let index_b;
-        match logic::get_snake_from_tile(a, &board.snakes) {
-            Some(snake) => {
-                index_a =
-                    snake.body.len() - snake.body.iter().position(|item| item == a).unwrap_or(0)
-            }
-            None => index_a = 0,
-        }
-        match logic::get_snake_from_tile(b, &board.snakes) {
-            Some(snake) => {
-                index_b =
-                    snake.body.len() - snake.body.iter().position(|item| item == b).unwrap_or(0)
-            }
-            None => index_b = 0,
-        }
+    blocking_tiles.retain(|tile| *tile != ctx.you.head);
 
-        return index_a.cmp(&index_b);
-    });
+    sort_blocking_tiles(&mut blocking_tiles, &types::TimedOccupancy::new(ctx.board, &ctx.you.id));
 
-    if blocking_tiles.len() <= 0 {
+    if blocking_tiles.is_empty() {
         return None;
     }
     // find the blocking tile that is closest to the tail of it's snake
     return Some(blocking_tiles[0]);
 }
 
+/// # sort_blocking_tiles
+/// sorts blocking tiles by how many turns their snake's body keeps them blocked, the soonest to clear first
+/// ## Arguments:
+/// * blocking_tiles - the tiles to sort
+/// * timed - how long each body tile stays taken, worked out once instead of reading the bodies for every comparison
+fn sort_blocking_tiles(blocking_tiles: &mut [types::Coord], timed: &types::TimedOccupancy) {
+    blocking_tiles.sort_by_key(|tile| timed.clears_in(tile));
+}
+
+/// what a search knows about a tile it reached, at the least the tile it was reached from
+trait Reached: Copy {
+    fn parent(&self) -> types::Coord;
+}
+
+impl Reached for types::Coord {
+    fn parent(&self) -> types::Coord {
+        return *self;
+    }
+}
+
+impl Reached for NodeRecord {
+    fn parent(&self) -> types::Coord {
+        return self.parent;
+    }
+}
+
 /// # backtrack
 /// determines the path from the starting point to our goal
 /// ## Arguments:
 /// * tile - the goal tile
-/// * trace_tree - hashmap containing tiles as keys and thier parents as values
+/// * trace_tree - map from tiles to thier parents
 /// ## Returns:
 /// a path from our starting point to the goal
-fn backtrack(
+fn backtrack<T: Reached>(
     tile: types::Coord,
-    trace_tree: &HashMap<types::Coord, types::Coord>,
+    trace_tree: &TileMap<T>,
 ) -> Vec<types::Coord> {
-    let mut current_tile = &tile;
-    let mut path = vec![*current_tile];
+    let mut current_tile = tile;
+    let mut path = vec![current_tile];
     loop {
-        let parent_opt = trace_tree.get(current_tile);
+        let parent_opt = trace_tree.get(&current_tile);
         match parent_opt {
-            Some(tile) => {
-                path.push(*tile);
-                current_tile = tile;
+            Some(reached) => {
+                path.push(reached.parent());
+                current_tile = reached.parent();
             }
             None => break,
         }
     }
 
-    // return early if the path is empty
-    if path.len() <= 0 {
-        return path;
-    }
-
     // remove the root node, usually the head of the snake
-    let split_slice = path.split_last().unwrap().1;
+    let Some((_, split_slice)) = path.split_last() else {
+        return path;
+    };
     let mut cleaned_path = Vec::from(split_slice);
     cleaned_path.reverse();
 
     return cleaned_path;
 }
 
+/// # recent_path
+/// the end of the path `backtrack` would give, without walking the rest of it back to the root
+/// ## Arguments:
+/// * tile - the tile the path ends on
+/// * trace_tree - the parent of every tile reached
+/// * length - the number of tiles to keep
+/// * path - cleared and filled with the last `length` tiles of the path, the root left out, in any order
+fn recent_path<T: Reached>(
+    tile: types::Coord,
+    trace_tree: &TileMap<T>,
+    length: usize,
+    path: &mut Vec<types::Coord>,
+) {
+    path.clear();
+    let mut current_tile = tile;
+    while path.len() < length {
+        match trace_tree.get(&current_tile) {
+            Some(reached) => {
+                path.push(current_tile);
+                current_tile = reached.parent();
+            }
+            None => break,
+        }
+    }
+}
+
 pub fn closest_food(tile: &types::Coord, board: &types::Board) -> Option<f32> {
-    if board.food.len() <= 0 {
+    if board.food.is_empty() {
         return None;
     }
-    let mut distances: Vec<f32> = board.food.iter().map(|item| tile.distance(item)).collect();
-    distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
-    return Some(distances[0]);
+    #[cfg(test)]
+    FOOD_DISTANCES_MEASURED.with(|measured| measured.set(measured.get() + board.food.len()));
+    return board.food.iter().map(|item| tile.distance(item)).min_by(f32::total_cmp);
+}
+
+/// # food_distances
+/// the distance `closest_food` gives for every tile on the board, measured once for a search that would otherwise
+/// measure it again for every tile it reaches
+/// ## Arguments:
+/// * board - the battlesnake game board
+/// ## Returns:
+/// the distance from each tile to its closest food, no tile has one when there's no food
+fn food_distances(board: &types::Board) -> TileMap<f32> {
+    let mut distances = TileMap::new(board);
+    if board.food.is_empty() {
+        return distances;
+    }
+    for x in 0..board.width as i16 {
+        for y in 0..board.height as i16 {
+            let tile = types::Coord { x, y };
+            let closest = board.food.iter().map(|item| tile.distance(item)).fold(f32::INFINITY, f32::min);
+            distances.insert(tile, closest);
+        }
+    }
+    #[cfg(test)]
+    FOOD_DISTANCES_MEASURED.with(|measured| {
+        measured.set(measured.get() + board.width as usize * board.height as usize * board.food.len())
+    });
+    return distances;
+}
+
+#[cfg(test)]
+thread_local! {
+    /// the distances from a tile to a food measured on this thread, for the tests to count
+    static FOOD_DISTANCES_MEASURED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// # goal_clears
+/// whether a goal clears the connectivity and degree thresholds the first step has to, with our body along the path
+/// that reached it
+/// ## Arguments:
+/// * goal - the goal tile
+/// * ctx - the turn's context
+/// * visited - the record of every tile reached
+/// * future_snake_positions - filled with where our body would be on the goal
+/// * connection_threshold - the connectedness the goal needs
+/// * degree_threshold - the minimum number of adjacent tiles the goal must have
+fn goal_clears(
+    goal: &types::Coord,
+    ctx: &TurnContext,
+    visited: &TileMap<NodeRecord>,
+    future_snake_positions: &mut Vec<types::Coord>,
+    connection_threshold: f32,
+    degree_threshold: u8,
+) -> bool {
+    #[cfg(test)]
+    A_STAR_COSTS.with(|costs| costs.set((costs.get().0, costs.get().1 + 1)));
+    recent_path(*goal, visited, ctx.you.length as usize, future_snake_positions);
+    let options = MoveOptions::default().exclude(future_snake_positions);
+    return get_adj_tiles(goal, ctx, &options).len() as u8 >= degree_threshold
+        && logic::connected_at_least(
+            goal,
+            logic::tiles_needed(connection_threshold, ctx.free_tiles),
+            ctx,
+            future_snake_positions,
+        );
+}
+
+#[cfg(test)]
+thread_local! {
+    /// the tiles A* expanded on this thread and the connectivity questions it asked, for the tests to count
+    static A_STAR_COSTS: std::cell::Cell<(usize, usize)> = const { std::cell::Cell::new((0, 0)) };
+}
+
+/// what A* knows about a tile it reached
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NodeRecord {
+    /// the tile it was reached from
+    pub parent: types::Coord,
+    /// the cost of the cheapest path to it found so far
+    pub g: u16,
+    /// `g` with the distance on to the closest food, what the frontier is ordered by
+    pub f: f32,
+    /// our health on stepping onto it, before eating any food there. food eaten along the way fills it back up
+    pub health_after: u8,
+    /// the moves it takes to reach it, the turn we'd step onto it
+    pub turn: u16,
+}
+
+/// the path A* found and what the search knew about it
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PathResult {
+    /// the tiles from the first step to the goal, empty if there's no path
+    pub path: Vec<types::Coord>,
+    /// what the path cost, with the hazard steps and the penalties on the first step
+    pub cost: u16,
+    /// our health on reaching the goal, before we eat it
+    pub health_at_goal: u8,
+    /// the tiles the search took off its frontier, found or not
+    pub nodes_expanded: u32,
 }
 
 /// # a_star
 /// determines the shortest path to a food
 /// ## Arguments:
-/// * board - battlesnake game board
-/// * game_board - hashmap representation of the board
-/// * you - your battlesnake
-/// * connection_threshold - only go to goal if it passes this connection threshold
-/// * degree_threshold - the minimum number of adjacent tiles that a given tile must have to be considered valid
+/// * ctx - the turn's context
+/// * options - the thresholds the first step and the goal have to clear, and how far to look for longer heads
+///   around the first step
+/// * hazard_cost - the cost of stepping onto a hazard, every other step costs 1
 /// ## Returns:
-/// The shortest path to the goal tile
-pub fn a_star(
-    board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
-    you: &types::Battlesnake,
-    connection_threshold: f32,
-    degree_threshold: u8
-) -> Vec<types::Coord> {
-    let mut frontier: PriorityQueue<types::Coord, OrderedFloat<f32>> = PriorityQueue::new();
-    frontier.push(you.head, OrderedFloat(0.0));
-    let mut visited: HashMap<types::Coord, types::Coord> = HashMap::new();
-    let mut cost_so_far: HashMap<types::Coord, u16> = HashMap::new();
-    let path_found = a_star_logic(
-        board,
-        game_board,
-        you,
-        &mut frontier,
-        &mut visited,
-        &mut cost_so_far,
-        connection_threshold,
-        degree_threshold
-    );
+/// The shortest path to the goal tile, with its cost and our health at the end of it
+pub fn a_star(ctx: &TurnContext, options: &MoveOptions, hazard_cost: u16) -> PathResult {
+    let span = tracing::debug_span!("a_star", hazard_cost, nodes_expanded = Empty, path_len = Empty).entered();
+    let food_distances = food_distances(ctx.board);
+    let found = with_scratch(ctx.board, |scratch| {
+        scratch.queue.push(ctx.you.head, OrderedFloat(0.0));
+        let mut nodes_expanded = 0;
+        let path_found = a_star_logic(
+            ctx,
+            &mut scratch.queue,
+            &mut scratch.nodes,
+            &mut scratch.path,
+            &food_distances,
+            options,
+            hazard_cost,
+            &mut nodes_expanded,
+        );
 
-    return match path_found {
-        Some(goal) => backtrack(goal, &visited),
-        None => vec![],
-    };
+        return match path_found.and_then(|goal| Some((goal, *scratch.nodes.get(&goal)?))) {
+            Some((goal, record)) => PathResult {
+                path: backtrack(goal, &scratch.nodes),
+                cost: record.g,
+                health_at_goal: record.health_after,
+                nodes_expanded,
+            },
+            None => PathResult {
+                health_at_goal: ctx.you.health,
+                nodes_expanded,
+                ..PathResult::default()
+            },
+        };
+    });
+    span.record("nodes_expanded", found.nodes_expanded);
+    span.record("path_len", found.path.len());
+    return found;
 }
 
 /// # a_star_logic
 /// determines the shortest path to a food or specified tile
 /// ## Arguments:
-/// * goal_tile_option - option to find path to tile instead of food
-/// * board - battlesnake game board
-/// * game_board - hashmap representation of the board
-/// * you - your battlesnake
+/// * ctx - the turn's context
 /// * frontier - used to investigate new tiles
-/// * visited - used to mark tiles we've already visited
-/// * cost_so_far - used to remember the current cost of the path
-/// * exclude_tiles - mark specified tiles as blocked, for example the starting tile if it's not a snake body
-/// * connection_threshold - only go to goal if it passes this connection threshold
-/// * degree_threshold - the minimum number of adjacent tiles that a given tile must have to be considered valid
+/// * visited - the record of every tile reached, its parent, its cost and our health on it
+/// * future_snake_positions - where our body would be at each tile, reused from tile to tile
+/// * food_distances - the heuristic, the distance from each tile to its closest food
+/// * options - the thresholds a first step or goal has to clear, and how far to look for longer heads around the
+///   first step
+/// * hazard_cost - the cost of stepping onto a hazard stack, every other step costs 1. the stacks are counted on the
+///   turn we'd step onto the tile, on snail mode a trail we'd only reach once it's gone costs no more than any step
+/// * nodes_expanded - counts the tiles taken off the frontier
 /// ## Returns:
 /// The goal tile if a path is found
 fn a_star_logic(
-    board: &types::Board,
-    game_board: &HashMap<types::Coord, types::Flags>,
-    you: &types::Battlesnake,
+    ctx: &TurnContext,
     frontier: &mut PriorityQueue<types::Coord, OrderedFloat<f32>>,
-    visited: &mut HashMap<types::Coord, types::Coord>,
-    cost_so_far: &mut HashMap<types::Coord, u16>,
-    connection_threshold: f32,
-    degree_threshold: u8,
+    visited: &mut TileMap<NodeRecord>,
+    future_snake_positions: &mut Vec<types::Coord>,
+    food_distances: &TileMap<f32>,
+    options: &MoveOptions,
+    hazard_cost: u16,
+    nodes_expanded: &mut u32,
 ) -> Option<types::Coord> {
-    if frontier.is_empty() {
-        return None;
-    }
+    // a frontier that runs dry is a search that found nothing
+    let (current_tile, _) = frontier.pop()?;
+    let (game_board, you) = (ctx.game_board, ctx.you);
 
-    let (current_tile, _) = frontier.pop().unwrap();
+    *nodes_expanded += 1;
+    #[cfg(test)]
+    A_STAR_COSTS.with(|costs| costs.set((costs.get().0 + 1, costs.get().1)));
+    let current_cost = visited.get(&current_tile).map_or(0, |record| record.g);
+    let on_food = !(get_board_tile!(game_board, current_tile.x, current_tile.y) & types::Flags::FOOD).is_empty();
 
-    // if we've found a food that we can get to with our current health
-    if !(get_board_tile!(game_board, current_tile.x, current_tile.y) & types::Flags::FOOD)
-        .is_empty()
-        && cost_so_far.get(&current_tile).unwrap_or(&0) < &(you.health as u16)
+    // if we've found a food that we can get to with our current health, that we're hungry for, and it leaves us enough
+    // room once we're on it
+    if on_food
+        && current_cost < you.health as u16
+        && ctx.wants_food(&current_tile)
+        && goal_clears(
+            &current_tile,
+            ctx,
+            visited,
+            future_snake_positions,
+            options.connection_threshold(),
+            options.degree_threshold(),
+        )
     {
         return Some(current_tile);
     }
 
-    // get current path so we make sure we don't intersect our own path
-    let current_path = backtrack(current_tile, visited);
-    let path_index =
-        usize::try_from(cmp::max(0, current_path.len() as i32 - you.length as i32)).unwrap_or(0);
-    let future_snake_positions: Vec<types::Coord> = current_path[path_index..].to_vec();
-
-    // get adj tiles if they haven't been visited before and they're not in the current path
-    let adj_tiles: Vec<types::Coord> = logic::get_adj_tiles_connected(
-        &current_tile,
-        board,
-        &game_board,
-        you,
-        connection_threshold,
-        degree_threshold,
-        Some(true),
-        None,
-        None,
-        Some(future_snake_positions),
-    );
+    // get the end of the current path so we make sure we don't intersect our own path
+    recent_path(current_tile, visited, you.length as usize, future_snake_positions);
 
-    let current_cost = *cost_so_far.get(&current_tile).unwrap_or(&0);
-    // mark adj tiles as visited and link the parent node
-    for tile in &adj_tiles {
-        let mut movement_cost: u8 = 1;
-        if !(get_board_tile!(game_board, tile.x, tile.y) & types::Flags::HAZARD).is_empty() {
-            movement_cost = 16;
+    // get adj tiles if they haven't been visited before and they're not in the current path. only the first step has
+    // to clear the connectivity and degree thresholds, the rest of the path only has to be legal
+    let adj_tiles: AdjTiles = if current_tile == you.head {
+        #[cfg(test)]
+        A_STAR_COSTS.with(|costs| costs.set((costs.get().0, costs.get().1 + 1)));
+        logic::get_adj_tiles_connected(&current_tile, ctx, &options.exclude(future_snake_positions))
+    } else {
+        get_adj_tiles(&current_tile, ctx, &MoveOptions::default().exclude(future_snake_positions))
+    };
+
+    // a food passed over on the way is eaten, the steps after it start from full health
+    let current_health = if on_food && current_tile != you.head {
+        100
+    } else {
+        visited.get(&current_tile).map_or(you.health, |record| record.health_after)
+    };
+    let turn = visited.get(&current_tile).map_or(0, |record| record.turn) + 1;
+    // mark adj tiles as visited and link the parent node. the head is where the path starts, on a snake with nothing
+    // behind it the tile is open but stepping back onto it would give it a parent and the backtrack a loop
+    for tile in adj_tiles.iter().filter(|tile| **tile != you.head) {
+        let stacks = ctx.hazards.on(tile, turn) as u16;
+        let mut movement_cost = 1 + hazard_cost.saturating_sub(1).saturating_mul(stacks);
+        let damage = ctx.hazards.damage_on(tile, turn, ctx.rules.hazard_damage);
+        if current_tile == you.head && ctx.herded(tile) {
+            movement_cost += logic::HERDING_PENALTY;
         }
-        let previous_cost_opt = cost_so_far.get(&tile);
-        let new_cost = current_cost + movement_cost as u16;
-        if previous_cost_opt.is_none() || *previous_cost_opt.unwrap() > new_cost {
-            cost_so_far.insert(*tile, new_cost);
-            let heuristic_distance = closest_food(tile, board).unwrap_or(0.0);
+        if current_tile == you.head && logic::cut_off(tile, ctx, options) {
+            movement_cost += logic::CUT_OFF_PENALTY;
+        }
+        let new_cost = current_cost.saturating_add(movement_cost);
+        if visited.get(tile).is_none_or(|record| record.g > new_cost) {
+            let heuristic_distance = food_distances.get(tile).copied().unwrap_or(0.0);
             let priority = new_cost as f32 + heuristic_distance;
             // here we take the negative priority so closest points are at the top
             frontier.push(*tile, OrderedFloat(-priority));
-            visited.insert(*tile, current_tile);
+            visited.insert(
+                *tile,
+                NodeRecord {
+                    parent: current_tile,
+                    g: new_cost,
+                    f: priority,
+                    health_after: current_health.saturating_sub(1).saturating_sub(damage),
+                    turn,
+                },
+            );
         }
     }
 
     return a_star_logic(
-        board,
-        game_board,
-        you,
+        ctx,
         frontier,
         visited,
-        cost_so_far,
-        connection_threshold,
-        degree_threshold
+        future_snake_positions,
+        food_distances,
+        options,
+        hazard_cost,
+        nodes_expanded,
     );
 }
 
@@ -436,6 +675,7 @@ fn a_star_logic(
 mod test {
     use super::*;
     use crate::types;
+    use std::collections::HashMap;
 
     #[test]
     fn test_get_head_adj() {
@@ -481,7 +721,8 @@ mod test {
         let board: types::Board = serde_json::from_str(BOARD_DATA).unwrap();
         let you: types::Battlesnake = board.snakes[0].clone();
         let game_board = board.to_game_board();
-        let adj = logic::get_adj_tiles(&you.head, &board, &game_board, &you, None, None);
+        let ctx = TurnContext::sample(&board, &game_board, &you);
+        let adj = logic::get_adj_tiles(&you.head, &ctx, &MoveOptions::default());
         assert!(
             adj.contains(&(you.head + types::DIRECTIONS["left"]))
                 && adj.contains(&(you.head + types::DIRECTIONS["right"]))
@@ -489,76 +730,106 @@ mod test {
         );
     }
 
-    #[test]
-    fn shortest_to_food() {
-        const FOOD_DATA: &str = r#"
-        {
-            "food": [
+    /// our snake in the middle of the bottom half, a food on a hazard nearby and another in the far corner
+    const FOOD_DATA: &str = r#"
+    {
+        "food": [
+          {
+            "x": 8,
+            "y": 4
+          },
+          {
+            "x": 0,
+            "y": 10
+          }
+        ],
+        "snakes": [
+          {
+            "id": "jt-0Z",
+            "name": "snake jt-0Z",
+            "health": 100,
+            "body": [
               {
-                "x": 8,
+                "x": 4,
                 "y": 4
               },
               {
-                "x": 0,
-                "y": 10
-              }
-            ],
-            "snakes": [
+                "x": 4,
+                "y": 3
+              },
               {
-                "id": "jt-0Z",
-                "name": "snake jt-0Z",
-                "health": 100,
-                "body": [
-                  {
-                    "x": 4,
-                    "y": 4
-                  },
-                  {
-                    "x": 4,
-                    "y": 3
-                  },
-                  {
-                    "x": 4,
-                    "y": 2
-                  },
-                  {
-                    "x": 4,
-                    "y": 1
-                  }
-                ],
-                "latency": 0,
-                "head": {
-                  "x": 4,
-                  "y": 4
-                },
-                "length": 4,
-                "shout": "",
-                "squad": ""
-              }
-            ],
-            "width": 11,
-            "height": 11,
-            "hazards": [
+                "x": 4,
+                "y": 2
+              },
               {
-                "x": 8,
-                "y": 4
+                "x": 4,
+                "y": 1
               }
-            ]
+            ],
+            "latency": 0,
+            "head": {
+              "x": 4,
+              "y": 4
+            },
+            "length": 4,
+            "shout": "",
+            "squad": ""
+          }
+        ],
+        "width": 11,
+        "height": 11,
+        "hazards": [
+          {
+            "x": 8,
+            "y": 4
           }
-        "#;
+        ]
+      }
+    "#;
+
+    #[test]
+    fn shortest_to_food() {
         let board: types::Board = serde_json::from_str(FOOD_DATA).unwrap();
         let mut you = board.snakes[0].clone();
         let game_board = board.to_game_board();
 
-        let a_star_path = a_star(&board, &game_board, &you, 0.5, 0);
+        let options = MoveOptions::default().thresholds(0.5, 0);
+        let found = a_star(&TurnContext::sample(&board, &game_board, &you), &options, 16);
+        let a_star_path = &found.path;
         assert!(
-            a_star_path.len() > 0
+            !a_star_path.is_empty()
                 && a_star_path[a_star_path.len() - 1] == types::Coord { x: 0, y: 10 }
         );
+        // ten steps off the hazards from full health
+        assert_eq!(found.cost, 10);
+        assert_eq!(found.health_at_goal, 90);
+        assert!(found.nodes_expanded as usize > a_star_path.len());
         you.health = 3;
-        let a_star_path_low = a_star(&board, &game_board, &you, 0.5, 0);
-        assert!(a_star_path_low.len() <= 0);
+        let options = MoveOptions::default().thresholds(0.5, 0);
+        let low = a_star(&TurnContext::sample(&board, &game_board, &you), &options, 16);
+        assert!(low.path.is_empty());
+        assert_eq!((low.cost, low.health_at_goal), (0, 3));
     }
+    #[test]
+    fn a_star_asks_about_connectivity_only_at_the_ends() {
+        let board: types::Board = serde_json::from_str(FOOD_DATA).unwrap();
+        let game_board = board.to_game_board();
+        let ctx = TurnContext::sample(&board, &game_board, &board.snakes[0]).with_full_fills();
+        A_STAR_COSTS.with(|costs| costs.set((0, 0)));
+        let path = a_star(&ctx, &MoveOptions::default().thresholds(0.5, 0), 16).path;
+        let (expanded, questions) = A_STAR_COSTS.with(|costs| costs.get());
+        assert_eq!(path.last(), Some(&types::Coord { x: 0, y: 10 }));
+        // the first step and the goal, the hazard food is out of reach of the path's cost
+        assert_eq!(questions, 2, "{} questions for {} tiles expanded", questions, expanded);
+        assert!(expanded > path.len());
+        // every tile expanded used to flood fill its neighbours, now the fills are the first step's and the goal's
+        assert!(
+            ctx.connectivity.visits() < 4 * ctx.free_tiles as u64,
+            "{} tiles visited filling",
+            ctx.connectivity.visits()
+        );
+    }
+
     #[test]
     fn avoid_future_poorly_connected_tiles() {
         const BOARD_DATA: &str = r#"
@@ -639,9 +910,10 @@ mod test {
         let you = &board.snakes[0];
         let game_board = board.to_game_board();
 
-        let a_star_path = a_star(&board, &game_board, you, 0.5, 0);
+        let options = MoveOptions::default().thresholds(0.5, 0);
+        let a_star_path = a_star(&TurnContext::sample(&board, &game_board, you), &options, 16).path;
         // a valid path cannot exist here because approaching the tile disconnects it from the rest of the board
-        assert!(a_star_path.len() <= 0);
+        assert!(a_star_path.is_empty());
     }
 
     #[test]
@@ -896,12 +1168,277 @@ mod test {
         let board: types::Board = serde_json::from_str(BOARD_DATA).unwrap();
         let game_board = board.to_game_board();
         let you: types::Battlesnake = serde_json::from_str(YOU_DATA).unwrap();
-        assert_eq!(
-            find_key_hole(&board, &game_board, &you),
-            Some(types::Coord { x: 6, y: 3 })
-        );
-        assert!(inside_box(&you, &board, &game_board, 0.3));
-        let long_path = dfs_long(&types::Coord { x: 6, y: 3 }, &board, &game_board, &you, 0.0, 0);
+        let ctx = TurnContext::sample(&board, &game_board, &you);
+        assert_eq!(find_key_hole(&ctx), Some(types::Coord { x: 6, y: 3 }));
+        assert_eq!(inside_box(&ctx, 0.3), Some(23));
+        let long_path = dfs_long(&types::Coord { x: 6, y: 3 }, &ctx, 0.0, 0);
         assert_eq!(*long_path.last().unwrap(), types::Coord { x: 6, y: 3 });
     }
+    /// three snakes of 17 segments each, snaking across the board in pairs of rows
+    fn long_snakes() -> types::Board {
+        let snake = |id: &str, row: i16| {
+            let mut body: Vec<types::Coord> = (0..9).map(|x| types::Coord { x, y: row }).collect();
+            body.extend((0..8).rev().map(|x| types::Coord { x, y: row + 1 }));
+            return serde_json::json!({
+                "id": id, "name": id, "health": 90, "head": body[0], "body": body, "length": body.len(),
+            });
+        };
+        return serde_json::from_value(serde_json::json!({
+            "width": 11, "height": 11, "food": [], "hazards": [],
+            "snakes": [snake("a", 0), snake("b", 3), snake("c", 6)],
+        }))
+        .unwrap();
+    }
+
+    /// twenty tiles spread over the three bodies, with two empty tiles among them
+    fn blocking_tiles(board: &types::Board) -> Vec<types::Coord> {
+        let mut tiles: Vec<types::Coord> = board
+            .snakes
+            .iter()
+            .flat_map(|snake| snake.body.iter().step_by(3).copied())
+            .take(18)
+            .collect();
+        tiles.insert(5, types::Coord { x: 10, y: 10 });
+        tiles.insert(12, types::Coord { x: 9, y: 2 });
+        return tiles;
+    }
+
+    #[test]
+    fn blocking_tiles_sort_as_the_body_scan_did() {
+        let board = long_snakes();
+        let mut tiles = blocking_tiles(&board);
+        assert_eq!(tiles.len(), 20);
+        let mut scanned = tiles.clone();
+        scanned.sort_by(|a, b| {
+            let turns_left = |tile: &types::Coord| {
+                for snake in &board.snakes {
+                    if let Some(segment) = snake.body.iter().position(|item| item == tile) {
+                        return snake.body.len() - segment;
+                    }
+                }
+                return 0;
+            };
+            return turns_left(a).cmp(&turns_left(b));
+        });
+
+        sort_blocking_tiles(&mut tiles, &types::TimedOccupancy::new(&board, "a"));
+        assert_eq!(tiles, scanned);
+    }
+
+    #[test]
+    fn blocking_tiles_sort_reads_each_body_once() {
+        let board = long_snakes();
+        let mut tiles = blocking_tiles(&board);
+        types::SEGMENTS_SCANNED.with(|scanned| scanned.set(0));
+        sort_blocking_tiles(&mut tiles, &types::TimedOccupancy::new(&board, "a"));
+        // one pass over the 51 segments, however many comparisons the sort made
+        assert_eq!(types::SEGMENTS_SCANNED.with(|scanned| scanned.get()), 51);
+    }
+
+    #[test]
+    fn recent_path_is_the_end_of_the_backtrack() {
+        // a winding trace from the root at (0, 0)
+        let tiles: Vec<types::Coord> = [(0, 0), (1, 0), (1, 1), (0, 1), (0, 2), (1, 2), (2, 2)]
+            .iter()
+            .map(|&(x, y)| types::Coord { x, y })
+            .collect();
+        let mut trace_tree: TileMap<types::Coord> = TileMap::with_size(3, 3);
+        for pair in tiles.windows(2) {
+            trace_tree.insert(pair[1], pair[0]);
+        }
+        let mut path = vec![types::Coord { x: 9, y: 9 }];
+        for end in &tiles {
+            let full = backtrack(*end, &trace_tree);
+            for length in 0..9 {
+                recent_path(*end, &trace_tree, length, &mut path);
+                let mut expected = full[full.len().saturating_sub(length)..].to_vec();
+                expected.reverse();
+                assert_eq!(path, expected, "{} tiles back from {:?}", length, end);
+            }
+        }
+    }
+
+    #[test]
+    fn backtrack_follows_the_parents_the_hash_map_held() {
+        let mut rng = crate::seed::rng("backtrack_follows_the_parents_the_hash_map_held", 0);
+        for _ in 0..20 {
+            let board = crate::logic::tests::random_board(&mut rng);
+            let game_board = board.to_game_board();
+            let you = &board.snakes[0];
+            let ctx = TurnContext::sample(&board, &game_board, you);
+            // a breadth first search from our head, the parents kept in both
+            let mut parents: TileMap<types::Coord> = TileMap::new(&board);
+            let mut hashed: HashMap<types::Coord, types::Coord> = HashMap::new();
+            let mut frontier = VecDeque::from([you.head]);
+            while let Some(tile) = frontier.pop_front() {
+                for adj in get_adj_tiles(&tile, &ctx, &MoveOptions::default()) {
+                    if parents.get(&adj).is_none() && adj != you.head {
+                        parents.insert(adj, tile);
+                        hashed.insert(adj, tile);
+                        frontier.push_back(adj);
+                    }
+                }
+            }
+            for tile in hashed.keys() {
+                let mut expected = vec![*tile];
+                while let Some(parent) = hashed.get(expected.last().unwrap()) {
+                    expected.push(*parent);
+                }
+                expected.pop();
+                expected.reverse();
+                assert_eq!(backtrack(*tile, &parents), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn food_distances_are_the_closest_food() {
+        for fixture in crate::fixtures::load(std::path::Path::new(crate::fixtures::FIXTURE_DIR)).unwrap() {
+            let board = &fixture.state.board;
+            let distances = food_distances(board);
+            for x in 0..board.width as i16 {
+                for y in 0..board.height as i16 {
+                    let tile = types::Coord { x, y };
+                    // the very same floats, so A* orders its frontier and finds its paths as it did
+                    assert_eq!(
+                        distances.get(&tile).map(|distance| distance.to_bits()),
+                        closest_food(&tile, board).map(|distance| distance.to_bits()),
+                        "{:?} in {}",
+                        tile,
+                        fixture.name
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_star_measures_the_food_once() {
+        // our snake in the bottom left corner, fifteen food along the top two rows
+        let body: Vec<types::Coord> = (0..3).map(|y| types::Coord { x: 0, y: 2 - y }).collect();
+        let board = types::Board {
+            height: 11,
+            width: 11,
+            food: (0..15).map(|i| types::Coord { x: i % 11, y: 10 - i / 11 }).collect(),
+            hazards: vec![],
+            snakes: vec![types::Battlesnake {
+                id: "you".to_string(),
+                name: "you".to_string(),
+                health: 100,
+                head: body[0],
+                length: body.len() as u32,
+                body,
+                shout: None,
+                squad: String::new(),
+                latency: None,
+            }],
+            hazard_odds: vec![],
+            hazard_walls: false,
+        };
+        let game_board = board.to_game_board();
+        let ctx = TurnContext::sample(&board, &game_board, &board.snakes[0]);
+        FOOD_DISTANCES_MEASURED.with(|measured| measured.set(0));
+        let path = a_star(&ctx, &MoveOptions::default().thresholds(0.0, 0), 1).path;
+        assert_eq!(path.len(), 7);
+        // a distance for each tile and food up front, nothing for each tile the search reached
+        assert_eq!(FOOD_DISTANCES_MEASURED.with(|measured| measured.get()), 11 * 11 * 15);
+    }
+
+    /// a snake with a body of tiles, its head first
+    fn snake_of(id: &str, health: u8, body: &[(i16, i16)]) -> serde_json::Value {
+        let body: Vec<types::Coord> = body.iter().map(|&(x, y)| types::Coord { x, y }).collect();
+        return serde_json::json!({
+            "id": id, "name": id, "health": health, "head": body[0], "body": body, "length": body.len(),
+        });
+    }
+
+    /// a board of a size with snakes on it, the first of them ours
+    fn board_with(width: u8, height: u8, snakes: &[serde_json::Value]) -> types::Board {
+        return serde_json::from_value(serde_json::json!({
+            "width": width, "height": height, "food": [], "hazards": [], "snakes": snakes,
+        }))
+        .unwrap();
+    }
+
+    #[test]
+    fn flood_fills_run_off_a_queue() {
+        // the recursive fills took a frame for every tile, far more than this stack holds
+        let filled = std::thread::Builder::new()
+            .stack_size(128 * 1024)
+            .spawn(|| {
+                let board = board_with(200, 200, &[snake_of("me", 100, &[(0, 0), (0, 1), (0, 2)])]);
+                let game_board = board.to_game_board();
+                let ctx = TurnContext::sample(&board, &game_board, &board.snakes[0]);
+                let fill = flood_fill(&ctx, &[types::Coord { x: 1, y: 0 }], &FillOptions::default().count_only(true));
+                return (fill, inside_box(&ctx, 1.0));
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(filled.0.count, 200 * 200 - 3);
+        assert!(filled.0.tiles.is_empty());
+        assert_eq!(filled.1, Some(200 * 200 - 3));
+    }
+
+    #[test]
+    fn flood_fills_reach_every_tile_once() {
+        let board = long_snakes();
+        let game_board = board.to_game_board();
+        let ctx = TurnContext::sample(&board, &game_board, &board.snakes[0]);
+        let distinct = |tiles: &[types::Coord]| {
+            let mut distinct = tiles.to_vec();
+            distinct.sort_by_key(|tile| (tile.x, tile.y));
+            distinct.dedup();
+            return distinct.len() == tiles.len();
+        };
+        // starts next to each other and one of them twice, the old fills reached a start again from its neighbour
+        let (empty, tail) = (types::Coord { x: 9, y: 2 }, types::Coord { x: 0, y: 4 });
+        let starts = [empty, empty + types::DIRECTIONS["up"], empty, tail, tail];
+        let fill = flood_fill(&ctx, &starts, &FillOptions::default());
+        assert!(distinct(&fill.tiles));
+        assert_eq!(fill.count as usize, fill.tiles.len());
+        // the tail moves off, but it's next to a longer head
+        assert!(fill.tiles.contains(&empty) && !fill.tiles.contains(&tail));
+
+        let snake = |tile: &types::Coord| {
+            return !(get_board_tile!(game_board, tile.x, tile.y) & types::Flags::SNAKE).is_empty();
+        };
+        let options = FillOptions::default().passable(Passable::Board).stop(&snake);
+        let fill = flood_fill(&ctx, &starts, &options);
+        assert!(distinct(&fill.boundary) && distinct(&fill.tiles));
+        assert_eq!(fill.boundary.iter().filter(|tile| **tile == tail).count(), 1);
+        assert!(fill.boundary.iter().all(snake) && !fill.tiles.iter().any(snake));
+
+        // the budget is a tile count, the starts count towards it
+        let fill = flood_fill(&ctx, &starts, &FillOptions::default().budget(2));
+        assert_eq!((fill.count, fill.cut_short), (2, true));
+        assert_eq!(fill.tiles, [empty, empty + types::DIRECTIONS["up"]]);
+        let whole = flood_fill(&ctx, &starts, &FillOptions::default().exclude(&[empty]));
+        assert!(!whole.cut_short && !whole.tiles.contains(&empty));
+    }
+
+    #[test]
+    fn decaying_bodies_open_up_as_the_fill_reaches_them() {
+        // a wall down the middle from a snake that just ate, its tail moves off in two turns and its head in four
+        let board = board_with(
+            5,
+            3,
+            &[snake_of("me", 100, &[(0, 0), (0, 1), (0, 2), (1, 2)]), snake_of("wall", 100, &[(2, 2), (2, 1), (2, 0)])],
+        );
+        let game_board = board.to_game_board();
+        let ctx = TurnContext::sample(&board, &game_board, &board.snakes[0]);
+        let timed = types::TimedOccupancy::new(&board, "me");
+        let starts = get_all_adj_tiles(&ctx.you.head, ctx.board);
+        let open = flood_fill(&ctx, &starts, &FillOptions::default());
+        assert_eq!(open.tiles, [types::Coord { x: 1, y: 0 }, types::Coord { x: 1, y: 1 }]);
+
+        let decaying = FillOptions::default().passable(Passable::Decaying(&timed));
+        let fill = flood_fill(&ctx, &starts, &decaying);
+        let reached = |x, y| fill.tiles.iter().position(|tile| *tile == types::Coord { x, y });
+        // through the tail two moves out, the middle of the wall three moves out and its head four
+        assert!(reached(2, 0) < reached(2, 1) && reached(2, 1) < reached(2, 2));
+        assert!((3..5).all(|x| (0..3).all(|y| reached(x, y).is_some())));
+        // the middle of the wall is still there a move out
+        assert_eq!(flood_fill(&ctx, &[types::Coord { x: 2, y: 1 }], &decaying).count, 0);
+    }
 }
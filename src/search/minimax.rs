@@ -0,0 +1,1472 @@
+use crate::logic;
+use crate::metrics;
+use crate::search::eval::{self, MoveScore, Weights, WIN_SCORE};
+use crate::search::opponent::OpponentPolicy;
+use crate::search::ordering::MoveOrdering;
+use crate::search::state::SearchBoard;
+use crate::types::{self, Coord, Ruleset};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+const INFINITY: i32 = i32::MAX / 2;
+
+/// how many nodes are searched between looks at the clock
+const CLOCK_INTERVAL: u64 = 256;
+
+/// the most nodes a profile's search extends past its depth, see `with_quiescence`
+pub const QUIESCENCE_BUDGET: u64 = 10_000;
+
+/// the food spawns a profile's search averages at each chance node, see `with_food_spawn`
+pub const FOOD_SPAWN_SAMPLES: usize = 2;
+/// the seed a profile's search samples the food spawns from, mixed with each position's hash so positions still see
+/// different food
+pub const FOOD_SPAWN_SEED: u64 = 0x5eed;
+
+/// every direction in a fixed order, used to break ties between equally scored root moves
+pub const MOVES: [Coord; 4] = [
+    Coord { x: 0, y: 1 },
+    Coord { x: 0, y: -1 },
+    Coord { x: -1, y: 0 },
+    Coord { x: 1, y: 0 },
+];
+
+fn canonical_index(mv: &Coord) -> usize {
+    return MOVES
+        .iter()
+        .position(|item| item == mv)
+        .unwrap_or(MOVES.len());
+}
+
+/// # record
+/// counts a finished search's nodes, and the deadline if it cut the search short
+fn record(nodes: u64, complete: bool) {
+    metrics::SEARCH_NODES.add("minimax", nodes);
+    if !complete {
+        metrics::DEADLINE_DEGRADATIONS.add("minimax", 1);
+    }
+}
+
+/// # candidate_moves
+/// the moves worth simulating for a snake: every direction except straight back into its own neck
+pub fn candidate_moves(snake: &types::Battlesnake) -> Vec<Coord> {
+    let neck = snake.body.get(1).copied();
+    return MOVES
+        .into_iter()
+        .filter(|dir| neck.is_none() || neck == Some(snake.head) || Some(snake.head + *dir) != neck)
+        .collect();
+}
+
+/// # squadmates
+/// the snakes playing in our squad, none outside of squad games
+fn squadmates(board: &types::Board, you_id: &str, rules: &Ruleset) -> Vec<String> {
+    let you = match board.snakes.iter().find(|snake| snake.id == you_id) {
+        Some(snake) if rules.squad.is_some() => snake,
+        _ => return vec![],
+    };
+    return board.snakes.iter().filter(|snake| you.squadmate(snake)).map(|snake| snake.id.clone()).collect();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchResult {
+    /// the unit direction of the best move, none if we have no snake on the board
+    pub best_move: Option<Coord>,
+    pub score: i32,
+    /// the number of max and min nodes visited
+    pub nodes: u64,
+    /// false if the deadline passed before every root move was searched to full depth
+    pub complete: bool,
+}
+
+/// # better_root_move
+/// equally scored moves are broken by `MOVES` order so neither ordering nor threading changes the result
+fn better_root_move(mv: &Coord, score: i32, best_move: &Option<Coord>, best: i32) -> bool {
+    return match best_move {
+        None => true,
+        Some(best_mv) => {
+            score > best || (score == best && canonical_index(mv) < canonical_index(best_mv))
+        }
+    };
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TableEntry {
+    depth: u8,
+    score: i32,
+    exact: bool,
+    best_move: Option<Coord>,
+}
+
+/// limits on how far the search may extend past its depth when heads are about to meet
+#[derive(Debug, Clone, Copy)]
+struct Quiescence {
+    /// the most plies a single line may be extended by
+    max_plies: u8,
+    /// the most extended nodes per search, keeps a tense position from eating the deadline
+    budget: u64,
+    used: u64,
+}
+
+/// how food spawning is sampled at chance nodes
+#[derive(Debug, Clone, Copy)]
+struct FoodSpawn {
+    samples: usize,
+    seed: u64,
+}
+
+/// how the search expects the opponents to reply to our moves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMode {
+    /// every opponent jointly plays the reply that is worst for us
+    Paranoid,
+    /// the replies are averaged, each weighted by how likely the opponent policy thinks it is, so a move that's only
+    /// punished by a reply the opponent rarely plays isn't written off
+    Expectimax,
+}
+
+/// paranoid alpha-beta search: we pick a move, then every opponent jointly picks the reply that is worst for us. in
+/// `SearchMode::Expectimax` the opponents' replies are averaged instead
+pub struct AlphaBeta {
+    weights: Weights,
+    rules: Ruleset,
+    mode: SearchMode,
+    ordering: Option<MoveOrdering>,
+    /// when set, opponent replies are ordered by how likely the policy thinks they are
+    opponent_policy: Option<Arc<dyn OpponentPolicy>>,
+    quiescence: Option<Quiescence>,
+    /// when set every turn ends in a chance node that averages a few samples of the food the engine might spawn
+    food_spawn: Option<FoodSpawn>,
+    deadline: Option<Instant>,
+    /// when set the only move searched at the root, see `with_first_move`
+    first_move: Option<Coord>,
+    /// each search (and each thread of a parallel search) has its own table
+    table: HashMap<u64, TableEntry>,
+    nodes: u64,
+    timed_out: bool,
+    had_opponents: bool,
+    /// our squadmates, whose moves are chosen alongside ours. losing any of them loses the search, see `squadmates`
+    mates: Vec<String>,
+    root_depth: u8,
+}
+
+impl AlphaBeta {
+    /// # new
+    /// ## Arguments:
+    /// * weights - the evaluation weights
+    /// * rules - the hazard damage and royale settings used during simulation
+    /// * ordered - whether to use move ordering and the transposition table, unordered search tries moves in `MOVES` order
+    pub fn new(weights: Weights, rules: Ruleset, ordered: bool) -> Self {
+        return AlphaBeta {
+            weights,
+            rules,
+            mode: SearchMode::Paranoid,
+            ordering: if ordered {
+                Some(MoveOrdering::new())
+            } else {
+                None
+            },
+            opponent_policy: None,
+            quiescence: None,
+            food_spawn: None,
+            deadline: None,
+            first_move: None,
+            table: HashMap::new(),
+            nodes: 0,
+            timed_out: false,
+            had_opponents: false,
+            mates: vec![],
+            root_depth: 0,
+        };
+    }
+
+    /// # fork
+    /// a searcher with the same settings and its own table and ordering, for searching a root move on another thread
+    fn fork(&self) -> AlphaBeta {
+        return AlphaBeta {
+            weights: self.weights,
+            rules: self.rules,
+            mode: self.mode,
+            ordering: self.ordering.as_ref().map(|_| MoveOrdering::new()),
+            opponent_policy: self.opponent_policy.clone(),
+            quiescence: self.quiescence,
+            food_spawn: self.food_spawn,
+            deadline: self.deadline,
+            first_move: self.first_move,
+            table: HashMap::new(),
+            nodes: 0,
+            timed_out: false,
+            had_opponents: self.had_opponents,
+            mates: self.mates.clone(),
+            root_depth: self.root_depth,
+        };
+    }
+
+    /// # with_opponent_policy
+    /// orders opponent replies by the policy's predictions instead of their static `MoveScore`
+    pub fn with_opponent_policy(mut self, policy: Box<dyn OpponentPolicy>) -> Self {
+        self.opponent_policy = Some(Arc::from(policy));
+        return self;
+    }
+
+    /// # with_expectimax
+    /// averages the opponents' replies by the policy's weights instead of assuming the worst. a position where an
+    /// opponent's weights aren't known is searched paranoid
+    pub fn with_expectimax(mut self, policy: Box<dyn OpponentPolicy>) -> Self {
+        self.mode = SearchMode::Expectimax;
+        return self.with_opponent_policy(policy);
+    }
+
+    /// # with_quiescence
+    /// extends the search past its depth while snake heads are close enough to collide, so a head-to-head one turn
+    /// past the horizon isn't scored as two healthy snakes
+    /// ## Arguments:
+    /// * max_plies - the most extra plies a single line may be searched
+    /// * budget - the most extended nodes across the whole search
+    pub fn with_quiescence(mut self, max_plies: u8, budget: u64) -> Self {
+        self.quiescence = Some(Quiescence {
+            max_plies,
+            budget,
+            used: 0,
+        });
+        return self;
+    }
+
+    /// # with_food_spawn
+    /// models the engine spawning food, without it long lines starve in positions where food would have turned up
+    /// ## Arguments:
+    /// * samples - the number of spawns averaged at each chance node
+    /// * seed - the seed every sample is derived from, together with the position's hash so the same position always
+    ///   sees the same food no matter the order the tree is searched in
+    pub fn with_food_spawn(mut self, samples: usize, seed: u64) -> Self {
+        self.food_spawn = Some(FoodSpawn {
+            samples: samples.max(1),
+            seed,
+        });
+        return self;
+    }
+
+    /// # with_deadline
+    /// stops searching once the deadline passes, the moves searched so far decide the result
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        return self;
+    }
+
+    /// # with_first_move
+    /// searches only one of our moves at the root, the result is that move's score rather than the best move's
+    /// ## Arguments:
+    /// * first_move - the unit direction of the move
+    pub fn with_first_move(mut self, first_move: Coord) -> Self {
+        self.first_move = Some(first_move);
+        return self;
+    }
+
+    /// # root_moves
+    /// our moves at the root, the one `with_first_move` set if there is one
+    fn root_moves(&self, you: &types::Battlesnake) -> Vec<Coord> {
+        let mut moves = candidate_moves(you);
+        if let Some(first_move) = self.first_move {
+            moves.retain(|mv| *mv == first_move);
+        }
+        return moves;
+    }
+
+    /// # begin
+    /// resets the per search state
+    fn begin(&mut self, board: &types::Board, you_id: &str, depth: u8) {
+        self.table.clear();
+        self.nodes = 1;
+        self.timed_out = false;
+        self.mates = squadmates(board, you_id, &self.rules);
+        self.had_opponents = board.snakes.len() > 1 + self.mates.len();
+        self.root_depth = depth.max(1);
+        if let Some(quiescence) = &mut self.quiescence {
+            quiescence.used = 0;
+        }
+    }
+
+    /// # out_of_time
+    /// checks the deadline every `CLOCK_INTERVAL` nodes, once it has passed every node returns its static evaluation
+    fn out_of_time(&mut self) -> bool {
+        if !self.timed_out && self.nodes.is_multiple_of(CLOCK_INTERVAL) {
+            if let Some(deadline) = self.deadline {
+                self.timed_out = Instant::now() >= deadline;
+            }
+        }
+        return self.timed_out;
+    }
+
+    /// # search
+    /// searches the position to a fixed depth
+    /// ## Arguments:
+    /// * board - the position to search
+    /// * you_id - the id of the snake we're choosing a move for
+    /// * turn - the turn number of the position, royale shrinks are simulated from it
+    /// * depth - the number of turns to look ahead
+    /// ## Returns:
+    /// the best move and its score. equally scored moves are broken by `MOVES` order so ordering never changes the result
+    pub fn search(
+        &mut self,
+        board: &types::Board,
+        you_id: &str,
+        turn: u32,
+        depth: u8,
+    ) -> SearchResult {
+        let span = tracing::debug_span!("minimax", depth, nodes = tracing::field::Empty).entered();
+        self.begin(board, you_id, depth);
+        let you = match board.snakes.iter().find(|snake| snake.id == you_id) {
+            Some(snake) => snake,
+            None => {
+                return SearchResult {
+                    best_move: None,
+                    score: -WIN_SCORE,
+                    nodes: self.nodes,
+                    complete: true,
+                }
+            }
+        };
+        let mut state = SearchBoard::new(board.clone(), turn);
+        let mut moves = self.root_moves(you);
+        if let Some(ordering) = &self.ordering {
+            ordering.order_moves(
+                board,
+                &state.game_board,
+                you,
+                &mut moves,
+                0,
+                None,
+                &self.weights,
+            );
+        }
+
+        let mut best_move: Option<Coord> = None;
+        let mut best = -INFINITY;
+        for team_move in self.team_moves(board, moves) {
+            // searching with alpha just below the best score keeps ties exact so we can break them canonically
+            let alpha = if best_move.is_some() {
+                best - 1
+            } else {
+                -INFINITY
+            };
+            let score = self.min_node(
+                &mut state,
+                you_id,
+                &team_move,
+                self.root_depth,
+                0,
+                alpha,
+                INFINITY,
+                None,
+            );
+            // a move cut short by the deadline only counts if it's all we have
+            if self.timed_out && best_move.is_some() {
+                break;
+            }
+            if better_root_move(&team_move[0], score, &best_move, best) {
+                best = score;
+                best_move = Some(team_move[0]);
+            }
+        }
+
+        record(self.nodes, !self.timed_out);
+        span.record("nodes", self.nodes);
+        return SearchResult {
+            best_move,
+            score: best,
+            nodes: self.nodes,
+            complete: !self.timed_out,
+        };
+    }
+
+    /// # search_parallel
+    /// searches each root move on its own thread with its own copy of the board, table and move ordering. falls back
+    /// to `search` when there's only one legal move. the quiescence budget applies to each thread separately
+    /// ## Arguments:
+    /// * board - the position to search
+    /// * you_id - the id of the snake we're choosing a move for
+    /// * turn - the turn number of the position, royale shrinks are simulated from it
+    /// * depth - the number of turns to look ahead
+    /// ## Returns:
+    /// the same move and score `search` finds
+    pub fn search_parallel(
+        &mut self,
+        board: &types::Board,
+        you_id: &str,
+        turn: u32,
+        depth: u8,
+    ) -> SearchResult {
+        let you = match board.snakes.iter().find(|snake| snake.id == you_id) {
+            Some(snake) => snake,
+            None => return self.search(board, you_id, turn, depth),
+        };
+        let game_board = board.to_game_board();
+        let moves = self.root_moves(you);
+        let anywhere = logic::MoveOptions::default().avoid_snake_heads(false);
+        let legal = moves
+            .iter()
+            .filter(|mv| logic::can_move_board(&(you.head + **mv), board, &game_board, you, &anywhere))
+            .count();
+        if legal <= 1 {
+            return self.search(board, you_id, turn, depth);
+        }
+
+        self.begin(board, you_id, depth);
+        let this = &*self;
+        let results: Vec<(Coord, i32, u64, bool)> = self
+            .team_moves(board, moves)
+            .par_iter()
+            .map(|team_move| {
+                let mut worker = this.fork();
+                let mut state = SearchBoard::new(board.clone(), turn);
+                let score = worker.min_node(
+                    &mut state,
+                    you_id,
+                    team_move,
+                    worker.root_depth,
+                    0,
+                    -INFINITY,
+                    INFINITY,
+                    None,
+                );
+                (team_move[0], score, worker.nodes, worker.timed_out)
+            })
+            .collect();
+
+        let complete = results.iter().all(|(_, _, _, timed_out)| !timed_out);
+        let any_complete = results.iter().any(|(_, _, _, timed_out)| !timed_out);
+        let mut best_move: Option<Coord> = None;
+        let mut best = -INFINITY;
+        for (mv, score, nodes, timed_out) in results {
+            self.nodes += nodes;
+            // moves cut short by the deadline only count if they're all we have
+            if timed_out && any_complete {
+                continue;
+            }
+            if better_root_move(&mv, score, &best_move, best) {
+                best = score;
+                best_move = Some(mv);
+            }
+        }
+        record(self.nodes, complete);
+        return SearchResult {
+            best_move,
+            score: best,
+            nodes: self.nodes,
+            complete,
+        };
+    }
+
+    fn max_node(
+        &mut self,
+        state: &mut SearchBoard,
+        you_id: &str,
+        depth: u8,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        self.nodes += 1;
+        let board = &state.board;
+        let you = match board.snakes.iter().find(|snake| snake.id == you_id) {
+            Some(snake) => snake,
+            // dying later is better than dying now
+            None => return -WIN_SCORE + ply as i32,
+        };
+        // the squad survives together or not at all
+        if self.mates.iter().any(|mate| !board.snakes.iter().any(|snake| snake.id == *mate)) {
+            return -WIN_SCORE + ply as i32;
+        }
+        if self.had_opponents && board.snakes.len() == 1 + self.mates.len() {
+            return WIN_SCORE - ply as i32;
+        }
+        if self.out_of_time() {
+            return eval::evaluate(board, &state.game_board, you_id, &self.mates, &self.weights);
+        }
+        // past the horizon only tense positions are searched further, and only by the snakes involved
+        let mut involved: Option<Vec<String>> = None;
+        if depth == 0 {
+            match self.extension(board, ply) {
+                Some(snakes) => involved = Some(snakes),
+                None => return eval::evaluate(board, &state.game_board, you_id, &self.mates, &self.weights),
+            }
+        }
+
+        let hash = state.hash;
+        let mut tt_move = None;
+        if self.ordering.is_some() && involved.is_none() {
+            if let Some(entry) = self.table.get(&hash) {
+                // only reuse scores searched to exactly this depth so the result matches unordered search
+                if entry.exact && entry.depth == depth {
+                    return entry.score;
+                }
+                tt_move = entry.best_move;
+            }
+        }
+
+        let mut moves = candidate_moves(you);
+        if let Some(ordering) = &self.ordering {
+            ordering.order_moves(
+                board,
+                &state.game_board,
+                you,
+                &mut moves,
+                ply,
+                tt_move,
+                &self.weights,
+            );
+        }
+        let head = you.head;
+        let team_moves = self.team_moves(board, moves);
+
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        for team_move in team_moves {
+            let mv = team_move[0];
+            let score = self.min_node(
+                state,
+                you_id,
+                &team_move,
+                depth.max(1),
+                ply,
+                alpha,
+                beta,
+                involved.as_deref(),
+            );
+            if score > best {
+                best = score;
+                best_move = Some(mv);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                if let Some(ordering) = &mut self.ordering {
+                    ordering.record_cutoff(ply, head, mv, depth);
+                }
+                break;
+            }
+        }
+
+        if self.ordering.is_some() && involved.is_none() && !self.timed_out {
+            self.table.insert(
+                hash,
+                TableEntry {
+                    depth,
+                    score: best,
+                    exact: best > original_alpha && best < beta,
+                    best_move,
+                },
+            );
+        }
+        return best;
+    }
+
+    /// # min_node
+    /// the opponents' replies to a move of ours
+    /// ## Arguments:
+    /// * team_move - our move, followed by our squadmates' in board order
+    fn min_node(
+        &mut self,
+        state: &mut SearchBoard,
+        you_id: &str,
+        team_move: &[Coord],
+        depth: u8,
+        ply: usize,
+        alpha: i32,
+        mut beta: i32,
+        involved: Option<&[String]>,
+    ) -> i32 {
+        self.nodes += 1;
+        if self.mode == SearchMode::Expectimax {
+            if let Some(score) = self.expect_node(state, you_id, team_move, depth, ply, involved) {
+                return score;
+            }
+        }
+        let replies = self.joint_replies(state, you_id, ply, involved);
+
+        let mut best = INFINITY;
+        for reply in replies {
+            let moves = self.joint_moves(&state.board, you_id, team_move, &reply);
+            let token = state.apply(&moves, &self.rules);
+            let score = match self.food_spawn {
+                Some(spawn) => self.chance_node(state, you_id, depth - 1, ply + 1, spawn),
+                None => self.max_node(state, you_id, depth - 1, ply + 1, alpha, beta),
+            };
+            state.undo(token);
+            best = best.min(score);
+            beta = beta.min(score);
+            if alpha >= beta {
+                if let Some(ordering) = &mut self.ordering {
+                    ordering.record_reply_cutoff(ply, &reply);
+                }
+                break;
+            }
+        }
+        return best;
+    }
+
+    /// # expect_node
+    /// averages our move over the opponents' joint replies, each weighted by the product of its moves' weights under the
+    /// opponent policy. every reply is searched with a full window since an average can't be cut off by a bound on one
+    /// of its parts
+    /// ## Returns:
+    /// the expected score, none when an opponent's weights aren't known and the reply should be searched paranoid
+    fn expect_node(
+        &mut self,
+        state: &mut SearchBoard,
+        you_id: &str,
+        team_move: &[Coord],
+        depth: u8,
+        ply: usize,
+        involved: Option<&[String]>,
+    ) -> Option<i32> {
+        let policy = self.opponent_policy.clone()?;
+        let board = &state.board;
+        let mut replies: Vec<(Vec<Coord>, f64)> = vec![(vec![], 1.0)];
+        for snake in board.snakes.iter().filter(|snake| self.opponent(snake, you_id)) {
+            let weights: Vec<(Coord, f64)> = if involved.is_some_and(|ids| !ids.contains(&snake.id)) {
+                vec![(self.static_best(state, snake)?, 1.0)]
+            } else {
+                policy
+                    .move_weights(board, &state.game_board, snake)
+                    .into_iter()
+                    .filter(|(_, weight)| *weight > 0.0)
+                    .map(|(mv, weight)| (mv, weight as f64))
+                    .collect()
+            };
+            if weights.is_empty() {
+                return None;
+            }
+            replies = replies
+                .into_iter()
+                .flat_map(|(reply, chance)| {
+                    weights.iter().map(move |(mv, weight)| {
+                        let mut extended = reply.clone();
+                        extended.push(*mv);
+                        (extended, chance * weight)
+                    })
+                })
+                .collect();
+        }
+
+        let mut total = 0.0;
+        let mut weight_sum = 0.0;
+        for (reply, chance) in replies {
+            let moves = self.joint_moves(&state.board, you_id, team_move, &reply);
+            let token = state.apply(&moves, &self.rules);
+            let score = match self.food_spawn {
+                Some(spawn) => self.chance_node(state, you_id, depth - 1, ply + 1, spawn),
+                None => self.max_node(state, you_id, depth - 1, ply + 1, -INFINITY, INFINITY),
+            };
+            state.undo(token);
+            total += chance * score as f64;
+            weight_sum += chance;
+        }
+        return Some((total / weight_sum).round() as i32);
+    }
+
+    /// # chance_node
+    /// averages the position over a few samples of the food the engine might spawn. every sample is searched with a
+    /// full window since an average can't be cut off by a bound on one of its parts
+    fn chance_node(
+        &mut self,
+        state: &mut SearchBoard,
+        you_id: &str,
+        depth: u8,
+        ply: usize,
+        spawn: FoodSpawn,
+    ) -> i32 {
+        let mut total: i64 = 0;
+        for sample in 0..spawn.samples as u64 {
+            let mut rng = StdRng::seed_from_u64(
+                spawn.seed ^ state.hash ^ sample.wrapping_mul(0x9e37_79b9_7f4a_7c15),
+            );
+            let spawned = state.spawn_food(&self.rules, &mut rng);
+            total += self.max_node(state, you_id, depth, ply, -INFINITY, INFINITY) as i64;
+            state.unspawn_food(spawned);
+        }
+        return (total / spawn.samples as i64) as i32;
+    }
+
+    /// # extension
+    /// decides whether a position at the depth limit is too tense to evaluate statically. a position is tense when two
+    /// heads are within 2 tiles of each other, which covers a longer snake being able to enter a tile next to our head
+    /// ## Returns:
+    /// the ids of the snakes whose heads are close, none if the position should be evaluated
+    fn extension(&mut self, board: &types::Board, ply: usize) -> Option<Vec<String>> {
+        let quiescence = self.quiescence.as_mut()?;
+        if ply >= (self.root_depth + quiescence.max_plies) as usize
+            || quiescence.used >= quiescence.budget
+        {
+            return None;
+        }
+        let involved: Vec<String> = board
+            .snakes
+            .iter()
+            .filter(|snake| {
+                board.snakes.iter().any(|other| {
+                    other != *snake
+                        && (snake.head.x - other.head.x).abs() + (snake.head.y - other.head.y).abs()
+                            <= 2
+                })
+            })
+            .map(|snake| snake.id.clone())
+            .collect();
+        if involved.is_empty() {
+            return None;
+        }
+        quiescence.used += 1;
+        return Some(involved);
+    }
+
+    /// # static_best
+    /// a snake's best move by its `MoveScore`, independent of the ordering settings so ordered and unordered search
+    /// extend identically
+    fn static_best(&self, state: &SearchBoard, snake: &types::Battlesnake) -> Option<Coord> {
+        return candidate_moves(snake).into_iter().max_by_key(|mv| {
+            (
+                MoveScore::new(&state.board, &state.game_board, snake, mv).value(&self.weights),
+                -(canonical_index(mv) as i32),
+            )
+        });
+    }
+
+    /// # opponent
+    /// whether a snake replies to our moves, every snake but us and our squadmates
+    fn opponent(&self, snake: &types::Battlesnake, you_id: &str) -> bool {
+        return snake.id != you_id && !self.mates.contains(&snake.id);
+    }
+
+    /// # team_moves
+    /// our moves, each followed by every combination of our squadmates' candidate moves in board order. without
+    /// squadmates it's our moves on their own
+    fn team_moves(&self, board: &types::Board, ours: Vec<Coord>) -> Vec<Vec<Coord>> {
+        let mut team_moves: Vec<Vec<Coord>> = ours.into_iter().map(|mv| vec![mv]).collect();
+        for mate in board.snakes.iter().filter(|snake| self.mates.contains(&snake.id)) {
+            let moves = candidate_moves(mate);
+            team_moves = team_moves
+                .into_iter()
+                .flat_map(|team_move| {
+                    moves.iter().map(move |mv| {
+                        let mut extended = team_move.clone();
+                        extended.push(*mv);
+                        extended
+                    })
+                })
+                .collect();
+        }
+        return team_moves;
+    }
+
+    /// # joint_moves
+    /// every snake's move for a turn in board order, ours and our squadmates' from the team move and the opponents'
+    /// from the reply
+    fn joint_moves(&self, board: &types::Board, you_id: &str, team_move: &[Coord], reply: &[Coord]) -> Vec<Coord> {
+        let mut mate_moves = team_move.iter().skip(1);
+        let mut opponent_moves = reply.iter();
+        return board
+            .snakes
+            .iter()
+            .map(|snake| {
+                if snake.id == you_id {
+                    team_move[0]
+                } else if self.mates.contains(&snake.id) {
+                    *mate_moves.next().unwrap_or(&MOVES[0])
+                } else {
+                    *opponent_moves.next().unwrap_or(&MOVES[0])
+                }
+            })
+            .collect();
+    }
+
+    /// # joint_replies
+    /// every combination of the opponents' candidate moves, one direction per opponent in board order
+    /// ## Arguments:
+    /// * involved - when extending past the depth limit, the snakes allowed to branch. everyone else plays their
+    ///   statically best move
+    fn joint_replies(
+        &self,
+        state: &SearchBoard,
+        you_id: &str,
+        ply: usize,
+        involved: Option<&[String]>,
+    ) -> Vec<Vec<Coord>> {
+        let board = &state.board;
+        let mut replies: Vec<Vec<Coord>> = vec![vec![]];
+        for snake in board.snakes.iter().filter(|snake| self.opponent(snake, you_id)) {
+            let mut moves = candidate_moves(snake);
+            if involved.is_some_and(|ids| !ids.contains(&snake.id)) {
+                moves = self.static_best(state, snake).into_iter().collect();
+            }
+            match (&self.ordering, &self.opponent_policy) {
+                (Some(_), Some(policy)) => {
+                    let weights = policy.move_weights(board, &state.game_board, snake);
+                    let weight_of = |mv: &Coord| {
+                        weights
+                            .iter()
+                            .find(|(item, _)| item == mv)
+                            .map_or(0.0, |(_, weight)| *weight)
+                    };
+                    moves.sort_by(|a, b| weight_of(b).total_cmp(&weight_of(a)));
+                }
+                (Some(ordering), None) => ordering.order_opponent_moves(
+                    board,
+                    &state.game_board,
+                    snake,
+                    &mut moves,
+                    &self.weights,
+                ),
+                _ => {}
+            }
+            replies = replies
+                .into_iter()
+                .flat_map(|reply| {
+                    moves.iter().map(move |mv| {
+                        let mut extended = reply.clone();
+                        extended.push(*mv);
+                        extended
+                    })
+                })
+                .collect();
+        }
+        if let Some(ordering) = &self.ordering {
+            ordering.order_replies(&mut replies, ply);
+        }
+        return replies;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::search::opponent::GreedyPolicy;
+
+    const DUEL_DATA: &str = r#"
+      {
+        "food": [
+          { "x": 2, "y": 8 },
+          { "x": 8, "y": 2 },
+          { "x": 5, "y": 5 }
+        ],
+        "snakes": [
+          {
+            "id": "me",
+            "name": "me",
+            "health": 80,
+            "body": [
+              { "x": 3, "y": 5 },
+              { "x": 3, "y": 4 },
+              { "x": 3, "y": 3 },
+              { "x": 2, "y": 3 }
+            ],
+            "latency": 0,
+            "head": { "x": 3, "y": 5 },
+            "length": 4,
+            "shout": "",
+            "squad": ""
+          },
+          {
+            "id": "them",
+            "name": "them",
+            "health": 90,
+            "body": [
+              { "x": 7, "y": 5 },
+              { "x": 7, "y": 6 },
+              { "x": 7, "y": 7 },
+              { "x": 8, "y": 7 },
+              { "x": 9, "y": 7 }
+            ],
+            "latency": 0,
+            "head": { "x": 7, "y": 5 },
+            "length": 5,
+            "shout": "",
+            "squad": ""
+          }
+        ],
+        "width": 11,
+        "height": 11,
+        "hazards": []
+      }
+    "#;
+
+    const CORNER_DATA: &str = r#"
+      {
+        "food": [
+          { "x": 0, "y": 0 }
+        ],
+        "snakes": [
+          {
+            "id": "me",
+            "name": "me",
+            "health": 50,
+            "body": [
+              { "x": 1, "y": 2 },
+              { "x": 1, "y": 3 },
+              { "x": 1, "y": 4 },
+              { "x": 2, "y": 4 },
+              { "x": 3, "y": 4 },
+              { "x": 3, "y": 3 }
+            ],
+            "latency": 0,
+            "head": { "x": 1, "y": 2 },
+            "length": 6,
+            "shout": "",
+            "squad": ""
+          },
+          {
+            "id": "them",
+            "name": "them",
+            "health": 70,
+            "body": [
+              { "x": 3, "y": 1 },
+              { "x": 4, "y": 1 },
+              { "x": 5, "y": 1 },
+              { "x": 6, "y": 1 },
+              { "x": 6, "y": 2 },
+              { "x": 6, "y": 3 },
+              { "x": 6, "y": 4 }
+            ],
+            "latency": 0,
+            "head": { "x": 3, "y": 1 },
+            "length": 7,
+            "shout": "",
+            "squad": ""
+          }
+        ],
+        "width": 7,
+        "height": 7,
+        "hazards": []
+      }
+    "#;
+
+    fn search(data: &str, depth: u8, ordered: bool) -> SearchResult {
+        let board: types::Board = serde_json::from_str(data).unwrap();
+        let mut alpha_beta = AlphaBeta::new(Weights::default(), Ruleset::default(), ordered);
+        return alpha_beta.search(&board, "me", 0, depth);
+    }
+
+    #[test]
+    fn ordering_reduces_nodes() {
+        for data in [DUEL_DATA, CORNER_DATA] {
+            let unordered = search(data, 3, false);
+            let ordered = search(data, 3, true);
+            // ordering should save at least a fifth of the tree on these positions
+            assert!(
+                ordered.nodes * 5 < unordered.nodes * 4,
+                "unordered: {} ordered: {}",
+                unordered.nodes,
+                ordered.nodes
+            );
+        }
+    }
+
+    #[test]
+    fn opponent_policy_ordering_keeps_chosen_move() {
+        for data in [DUEL_DATA, CORNER_DATA] {
+            let board: types::Board = serde_json::from_str(data).unwrap();
+            let unordered = search(data, 3, false);
+            let mut alpha_beta = AlphaBeta::new(Weights::default(), Ruleset::default(), true)
+                .with_opponent_policy(Box::new(GreedyPolicy::default()));
+            let ordered = alpha_beta.search(&board, "me", 0, 3);
+            assert_eq!(ordered.best_move, unordered.best_move);
+            assert_eq!(ordered.score, unordered.score);
+            assert!(ordered.nodes < unordered.nodes);
+        }
+    }
+
+    #[test]
+    fn ordering_keeps_chosen_move() {
+        for data in [DUEL_DATA, CORNER_DATA] {
+            for depth in 1..=3 {
+                let unordered = search(data, depth, false);
+                let ordered = search(data, depth, true);
+                assert_eq!(ordered.best_move, unordered.best_move);
+                assert_eq!(ordered.score, unordered.score);
+            }
+        }
+    }
+
+    #[test]
+    fn search_avoids_losing_head_to_head() {
+        const BOARD_DATA: &str = r#"
+          {
+            "food": [],
+            "snakes": [
+              {
+                "id": "me",
+                "name": "me",
+                "health": 90,
+                "body": [
+                  { "x": 5, "y": 5 },
+                  { "x": 5, "y": 4 },
+                  { "x": 5, "y": 3 }
+                ],
+                "latency": 0,
+                "head": { "x": 5, "y": 5 },
+                "length": 3,
+                "shout": "",
+                "squad": ""
+              },
+              {
+                "id": "them",
+                "name": "them",
+                "health": 90,
+                "body": [
+                  { "x": 7, "y": 5 },
+                  { "x": 8, "y": 5 },
+                  { "x": 9, "y": 5 },
+                  { "x": 10, "y": 5 },
+                  { "x": 10, "y": 4 }
+                ],
+                "latency": 0,
+                "head": { "x": 7, "y": 5 },
+                "length": 5,
+                "shout": "",
+                "squad": ""
+              }
+            ],
+            "width": 11,
+            "height": 11,
+            "hazards": []
+          }
+        "#;
+        for ordered in [false, true] {
+            let result = search(BOARD_DATA, 2, ordered);
+            assert_ne!(result.best_move, Some(Coord { x: 1, y: 0 }));
+            assert!(result.score > -WIN_SCORE / 2);
+        }
+    }
+
+    #[test]
+    fn quiescence_sees_head_to_head_past_horizon() {
+        // going left into the corner leaves us one tile from a longer head with nowhere to go but next to it
+        const BOARD_DATA: &str = r#"
+          {
+            "food": [],
+            "snakes": [
+              {
+                "id": "me",
+                "name": "me",
+                "health": 90,
+                "body": [
+                  { "x": 1, "y": 0 },
+                  { "x": 1, "y": 1 },
+                  { "x": 1, "y": 2 }
+                ],
+                "latency": 0,
+                "head": { "x": 1, "y": 0 },
+                "length": 3,
+                "shout": "",
+                "squad": ""
+              },
+              {
+                "id": "them",
+                "name": "them",
+                "health": 90,
+                "body": [
+                  { "x": 2, "y": 3 },
+                  { "x": 3, "y": 3 },
+                  { "x": 3, "y": 2 },
+                  { "x": 3, "y": 1 },
+                  { "x": 4, "y": 1 },
+                  { "x": 4, "y": 2 },
+                  { "x": 4, "y": 3 },
+                  { "x": 5, "y": 3 },
+                  { "x": 5, "y": 2 }
+                ],
+                "latency": 0,
+                "head": { "x": 2, "y": 3 },
+                "length": 9,
+                "shout": "",
+                "squad": ""
+              }
+            ],
+            "width": 7,
+            "height": 7,
+            "hazards": []
+          }
+        "#;
+        let board: types::Board = serde_json::from_str(BOARD_DATA).unwrap();
+        let left = Some(types::DIRECTIONS["left"]);
+
+        let horizon = AlphaBeta::new(Weights::default(), Ruleset::default(), false)
+            .search(&board, "me", 0, 2);
+        assert_eq!(horizon.best_move, left);
+        let deeper = AlphaBeta::new(Weights::default(), Ruleset::default(), false)
+            .search(&board, "me", 0, 3);
+        assert_ne!(deeper.best_move, left);
+
+        for ordered in [false, true] {
+            let result = AlphaBeta::new(Weights::default(), Ruleset::default(), ordered)
+                .with_quiescence(2, 10_000)
+                .search(&board, "me", 0, 2);
+            assert_ne!(result.best_move, left);
+            assert!(result.score > -WIN_SCORE / 2);
+        }
+
+        // with no budget the extension never fires and we're back to the horizon's choice
+        let starved = AlphaBeta::new(Weights::default(), Ruleset::default(), false)
+            .with_quiescence(2, 0)
+            .search(&board, "me", 0, 2);
+        assert_eq!(starved.best_move, left);
+    }
+
+    #[test]
+    fn royale_search_avoids_the_incoming_ring() {
+        // the food is down the left edge and our body blocks every other way there. the board shrinks at the end of
+        // next turn, after which a turn on the edge on the way to the food starves us
+        const BOARD_DATA: &str = r#"
+          {
+            "food": [
+              { "x": 0, "y": 3 }
+            ],
+            "snakes": [
+              {
+                "id": "me",
+                "name": "me",
+                "health": 5,
+                "body": [
+                  { "x": 1, "y": 5 },
+                  { "x": 2, "y": 5 },
+                  { "x": 2, "y": 4 },
+                  { "x": 1, "y": 4 },
+                  { "x": 1, "y": 3 },
+                  { "x": 2, "y": 3 },
+                  { "x": 3, "y": 3 }
+                ],
+                "latency": 0,
+                "head": { "x": 1, "y": 5 },
+                "length": 7,
+                "shout": "",
+                "squad": ""
+              }
+            ],
+            "width": 11,
+            "height": 11,
+            "hazards": []
+          }
+        "#;
+        let board: types::Board = serde_json::from_str(BOARD_DATA).unwrap();
+        let left = types::DIRECTIONS["left"];
+
+        let policy = GreedyPolicy::default();
+        assert_eq!(
+            policy.predict(&board, &board.to_game_board(), &board.snakes[0]),
+            Some(left)
+        );
+
+        // without the shrink the food is worth the detour
+        let blind = AlphaBeta::new(Weights::default(), Ruleset::default(), true)
+            .search(&board, "me", 24, 3);
+        assert_eq!(blind.best_move, Some(left));
+
+        for mode in [types::ShrinkMode::Pessimistic, types::ShrinkMode::Expected] {
+            let rules = Ruleset {
+                royale: Some(types::Royale {
+                    shrink_every_n_turns: 25,
+                    mode,
+                }),
+                ..Ruleset::default()
+            };
+            let result =
+                AlphaBeta::new(Weights::default(), rules, true).search(&board, "me", 24, 3);
+            assert_ne!(result.best_move, Some(left));
+            assert!(result.score > -WIN_SCORE / 2);
+        }
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        for data in [DUEL_DATA, CORNER_DATA] {
+            let board: types::Board = serde_json::from_str(data).unwrap();
+            for ordered in [false, true] {
+                for depth in 1..=3 {
+                    let sequential = search(data, depth, ordered);
+                    let parallel = AlphaBeta::new(Weights::default(), Ruleset::default(), ordered)
+                        .with_opponent_policy(Box::new(GreedyPolicy::default()))
+                        .search_parallel(&board, "me", 0, depth);
+                    assert_eq!(parallel.best_move, sequential.best_move);
+                    assert_eq!(parallel.score, sequential.score);
+                    assert!(parallel.complete);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_search_under_load() {
+        // plays out a few turns of the duel on many threads at once, every thread has to agree with the sequential search
+        let board: types::Board = serde_json::from_str(DUEL_DATA).unwrap();
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                let mut board = board.clone();
+                scope.spawn(move || {
+                    for turn in 0..8 {
+                        if board.snakes.len() < 2 {
+                            break;
+                        }
+                        let mut moves: Vec<Coord> = vec![];
+                        for snake in &board.snakes {
+                            let mut alpha_beta =
+                                AlphaBeta::new(Weights::default(), Ruleset::default(), true);
+                            let parallel = alpha_beta.search_parallel(&board, &snake.id, turn, 2);
+                            let sequential = alpha_beta.search(&board, &snake.id, turn, 2);
+                            assert_eq!(parallel.best_move, sequential.best_move);
+                            moves.push(parallel.best_move.unwrap_or(MOVES[0]));
+                        }
+                        board.simulate_turn(&moves, turn + 1, &Ruleset::default());
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn expired_deadline_still_moves() {
+        let board: types::Board = serde_json::from_str(DUEL_DATA).unwrap();
+        for parallel in [false, true] {
+            let mut alpha_beta = AlphaBeta::new(Weights::default(), Ruleset::default(), true)
+                .with_deadline(Instant::now());
+            let result = if parallel {
+                alpha_beta.search_parallel(&board, "me", 0, 6)
+            } else {
+                alpha_beta.search(&board, "me", 0, 6)
+            };
+            assert!(result.best_move.is_some());
+            assert!(!result.complete);
+        }
+    }
+
+    // alone on an empty board with almost no health, without new food we starve whatever we do
+    const STARVING_DATA: &str = r#"
+      {
+        "food": [],
+        "snakes": [
+          {
+            "id": "me",
+            "name": "me",
+            "health": 3,
+            "body": [
+              { "x": 3, "y": 3 },
+              { "x": 3, "y": 2 },
+              { "x": 3, "y": 1 }
+            ],
+            "latency": 0,
+            "head": { "x": 3, "y": 3 },
+            "length": 3,
+            "shout": "",
+            "squad": ""
+          }
+        ],
+        "width": 7,
+        "height": 7,
+        "hazards": []
+      }
+    "#;
+
+    #[test]
+    fn food_spawn_is_reproducible() {
+        for data in [DUEL_DATA, STARVING_DATA] {
+            let board: types::Board = serde_json::from_str(data).unwrap();
+            let results: Vec<SearchResult> = [false, true, true]
+                .iter()
+                .map(|ordered| {
+                    AlphaBeta::new(Weights::default(), Ruleset::default(), *ordered)
+                        .with_food_spawn(3, 1234)
+                        .search(&board, "me", 0, 2)
+                })
+                .collect();
+            for result in &results {
+                assert_eq!(result.best_move, results[0].best_move);
+                assert_eq!(result.score, results[0].score);
+            }
+        }
+    }
+
+    #[test]
+    fn food_spawn_values_starving_but_spacious_position() {
+        let board: types::Board = serde_json::from_str(STARVING_DATA).unwrap();
+        let no_spawn =
+            AlphaBeta::new(Weights::default(), Ruleset::default(), true).search(&board, "me", 0, 3);
+        assert!(no_spawn.score < -WIN_SCORE / 2);
+        let spawn = AlphaBeta::new(Weights::default(), Ruleset::default(), true)
+            .with_food_spawn(4, 1234)
+            .search(&board, "me", 0, 3);
+        assert!(spawn.score > no_spawn.score);
+    }
+
+    // the food at (5, 5) is two moves away for us and one for them, but they only go for it one time in ten
+    const DENIED_FOOD_DATA: &str = r#"
+      {
+        "food": [
+          { "x": 5, "y": 5 }
+        ],
+        "snakes": [
+          {
+            "id": "me",
+            "name": "me",
+            "health": 30,
+            "body": [
+              { "x": 3, "y": 5 },
+              { "x": 2, "y": 5 },
+              { "x": 1, "y": 5 }
+            ],
+            "latency": 0,
+            "head": { "x": 3, "y": 5 },
+            "length": 3,
+            "shout": "",
+            "squad": ""
+          },
+          {
+            "id": "them",
+            "name": "them",
+            "health": 80,
+            "body": [
+              { "x": 6, "y": 5 },
+              { "x": 7, "y": 5 },
+              { "x": 8, "y": 5 }
+            ],
+            "latency": 0,
+            "head": { "x": 6, "y": 5 },
+            "length": 3,
+            "shout": "",
+            "squad": ""
+          }
+        ],
+        "width": 11,
+        "height": 11,
+        "hazards": []
+      }
+    "#;
+
+    /// an opponent we know nothing about
+    struct Unknown;
+
+    impl OpponentPolicy for Unknown {
+        fn move_weights(
+            &self,
+            _board: &types::Board,
+            _game_board: &types::FastMap<Coord, types::Flags>,
+            _snake: &types::Battlesnake,
+        ) -> Vec<(Coord, f32)> {
+            return vec![];
+        }
+    }
+
+    #[test]
+    fn expectimax_takes_food_that_is_rarely_denied() {
+        let board: types::Board = serde_json::from_str(DENIED_FOOD_DATA).unwrap();
+        let rarely = GreedyPolicy {
+            confidence: 0.1,
+            ..GreedyPolicy::default()
+        };
+        let game_board = board.to_game_board();
+        let denial = rarely.move_weights(&board, &game_board, &board.snakes[1]);
+        assert!(denial.contains(&(Coord { x: -1, y: 0 }, 0.1)));
+        for depth in [2, 3] {
+            let paranoid = search(DENIED_FOOD_DATA, depth, true);
+            let mut expectimax = AlphaBeta::new(Weights::default(), Ruleset::default(), true)
+                .with_expectimax(Box::new(GreedyPolicy { confidence: 0.1, ..GreedyPolicy::default() }));
+            let expected = expectimax.search(&board, "me", 0, depth);
+            // paranoid search is sure they'll take it and turns away, expectimax goes for it
+            assert_ne!(paranoid.best_move, Some(Coord { x: 1, y: 0 }), "depth {}", depth);
+            assert_eq!(expected.best_move, Some(Coord { x: 1, y: 0 }), "depth {}", depth);
+            assert!(expected.complete);
+
+            // with nothing known about the opponent expectimax is paranoid search
+            let mut unknown =
+                AlphaBeta::new(Weights::default(), Ruleset::default(), true).with_expectimax(Box::new(Unknown));
+            let degraded = unknown.search(&board, "me", 0, depth);
+            assert_eq!((degraded.best_move, degraded.score), (paranoid.best_move, paranoid.score));
+        }
+    }
+
+    #[test]
+    fn the_profiles_searcher_plays_with_its_settings() {
+        let board: types::Board = serde_json::from_str(DUEL_DATA).unwrap();
+        let profile = logic::StrategyProfile::DEFAULT;
+        let profiled = profile.searcher(Ruleset::default()).search(&board, "me", 0, 3);
+        let expected = AlphaBeta::new(profile.phase_weights.mid, Ruleset::default(), true)
+            .with_opponent_policy(Box::new(GreedyPolicy::default()))
+            .with_quiescence(profile.quiescence_plies, QUIESCENCE_BUDGET)
+            .with_food_spawn(FOOD_SPAWN_SAMPLES, FOOD_SPAWN_SEED)
+            .search(&board, "me", 0, 3);
+        assert_eq!((profiled.best_move, profiled.score), (expected.best_move, expected.score));
+        // the same replies tried in the same order, extended as far and with the same food spawned
+        assert_eq!(profiled.nodes, expected.nodes);
+
+        // without food spawning there's no chance node to average
+        let barren = Ruleset {
+            food_spawn_chance: 0,
+            minimum_food: 0,
+            ..Ruleset::default()
+        };
+        let profiled = profile.searcher(barren).search(&board, "me", 0, 3);
+        let expected = AlphaBeta::new(profile.phase_weights.mid, barren, true)
+            .with_opponent_policy(Box::new(GreedyPolicy::default()))
+            .with_quiescence(profile.quiescence_plies, QUIESCENCE_BUDGET)
+            .search(&board, "me", 0, 3);
+        assert_eq!((profiled.score, profiled.nodes), (expected.score, expected.nodes));
+    }
+
+    #[test]
+    fn expectimax_searches_the_same_in_parallel() {
+        let board: types::Board = serde_json::from_str(DENIED_FOOD_DATA).unwrap();
+        let searcher = || {
+            let mut profile = logic::StrategyProfile::DEFAULT;
+            profile.search = SearchMode::Expectimax;
+            return profile.searcher(Ruleset::default());
+        };
+        let sequential = searcher().search(&board, "me", 0, 2);
+        let parallel = searcher().search_parallel(&board, "me", 0, 2);
+        assert_eq!(parallel.best_move, sequential.best_move);
+        assert_eq!(parallel.score, sequential.score);
+    }
+
+    #[test]
+    fn squad_search_keeps_our_partner_alive() {
+        // our partner's only way out of the corner is the tile below our head. going down wins the head to head there,
+        // but under shared elimination it takes us out with our partner
+        let snake = |id: &str, squad: &str, body: &[(i16, i16)]| {
+            let body: Vec<serde_json::Value> =
+                body.iter().map(|(x, y)| serde_json::json!({ "x": x, "y": y })).collect();
+            serde_json::json!({
+                "id": id, "name": id, "health": 90, "body": body, "head": body[0], "length": body.len(), "squad": squad
+            })
+        };
+        let board: types::Board = serde_json::from_value(serde_json::json!({
+            "width": 7, "height": 7, "food": [], "hazards": [],
+            "snakes": [
+                snake("me", "red", &[(2, 1), (1, 1), (1, 2), (1, 3)]),
+                snake("mate", "red", &[(1, 0), (0, 0), (0, 1)]),
+                snake("them", "blue", &[(5, 5), (5, 6), (6, 6)]),
+            ]
+        }))
+        .unwrap();
+        let down = Coord { x: 0, y: -1 };
+        let squad = Ruleset {
+            squad: Some(types::Squad {
+                shared_elimination: true,
+                ..types::Squad::default()
+            }),
+            ..Ruleset::default()
+        };
+        assert_eq!(squadmates(&board, "me", &squad), ["mate"]);
+        assert!(squadmates(&board, "me", &Ruleset::default()).is_empty());
+
+        for depth in 1..=3 {
+            // on its own the head to head is a free kill
+            let alone = AlphaBeta::new(Weights::default(), Ruleset::default(), true).search(&board, "me", 0, depth);
+            assert_eq!(alone.best_move, Some(down), "depth {}", depth);
+            let together = AlphaBeta::new(Weights::default(), squad, true).search(&board, "me", 0, depth);
+            assert_ne!(together.best_move, Some(down), "depth {}", depth);
+            assert!(together.score > -WIN_SCORE / 2, "depth {}", depth);
+            let parallel = AlphaBeta::new(Weights::default(), squad, true).search_parallel(&board, "me", 0, depth);
+            assert_eq!((parallel.best_move, parallel.score), (together.best_move, together.score));
+        }
+    }
+}
@@ -1 +1,17 @@
-pub mod graph;
\ No newline at end of file
+pub(crate) mod connectivity;
+pub(crate) mod endgame;
+pub(crate) mod escape;
+pub(crate) mod eval;
+// replays recorded games through the simulator, only built for tests
+#[cfg(test)]
+mod fidelity;
+pub(crate) mod graph;
+pub(crate) mod minimax;
+pub(crate) mod opponent;
+pub(crate) mod ordering;
+pub(crate) mod safety;
+pub(crate) mod scratch;
+pub(crate) mod starvation;
+pub(crate) mod state;
+pub(crate) mod tiles;
+pub(crate) mod zobrist;
\ No newline at end of file
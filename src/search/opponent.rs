@@ -0,0 +1,357 @@
+use crate::logic;
+use crate::search::eval::{MoveScore, Weights};
+use crate::search::minimax::candidate_moves;
+use crate::types::{self, Coord};
+use std::collections::{HashSet, VecDeque};
+
+/// predicts how an opponent will move so the search can spend its time on the replies that actually happen. policies
+/// are shared between the threads of a parallel search
+pub trait OpponentPolicy: Send + Sync {
+    /// # move_weights
+    /// weights for each of a snake's candidate moves
+    /// ## Arguments:
+    /// * board - the battlesnake game board
+    /// * game_board - the hashmap representation of the game board
+    /// * snake - the opponent whose move we're predicting
+    /// ## Returns:
+    /// every candidate unit direction with the probability the snake plays it, the weights sum to 1
+    fn move_weights(
+        &self,
+        board: &types::Board,
+        game_board: &types::FastMap<Coord, types::Flags>,
+        snake: &types::Battlesnake,
+    ) -> Vec<(Coord, f32)>;
+
+    /// # predict
+    /// the single most likely move for a snake
+    fn predict(
+        &self,
+        board: &types::Board,
+        game_board: &types::FastMap<Coord, types::Flags>,
+        snake: &types::Battlesnake,
+    ) -> Option<Coord> {
+        return self
+            .move_weights(board, game_board, snake)
+            .into_iter()
+            .fold(
+                None,
+                |best: Option<(Coord, f32)>, (mv, weight)| match best {
+                    Some((_, best_weight)) if best_weight >= weight => best,
+                    _ => Some((mv, weight)),
+                },
+            )
+            .map(|(mv, _)| mv);
+    }
+}
+
+/// models opponents as playing a stripped down version of our own pipeline: stay on safe tiles and take the shortest
+/// path to food. the divergence/connectivity analysis is skipped to keep it cheap
+pub struct GreedyPolicy {
+    /// the maximum number of tiles the food search may expand
+    pub budget: usize,
+    /// the share of the probability given to the move towards food, the rest is spread over the other safe moves
+    pub confidence: f32,
+}
+
+impl Default for GreedyPolicy {
+    fn default() -> Self {
+        return GreedyPolicy {
+            budget: 64,
+            confidence: 0.7,
+        };
+    }
+}
+
+impl GreedyPolicy {
+    /// # is_safe
+    /// a tile is safe for a snake if it's on the board, not a body, and not next to the head of a strictly longer snake.
+    /// ties are treated as safe since most snakes happily contest food against an equal snake
+    fn is_safe(
+        &self,
+        tile: &Coord,
+        board: &types::Board,
+        game_board: &types::FastMap<Coord, types::Flags>,
+        snake: &types::Battlesnake,
+    ) -> bool {
+        let anywhere = logic::MoveOptions::default().avoid_snake_heads(false);
+        return logic::can_move_board(tile, board, game_board, snake, &anywhere)
+            && !board.snakes.iter().any(|other| {
+                other != snake && other.length > snake.length && other.head.distance(tile) <= 1.0
+            });
+    }
+
+    /// # first_step_to_food
+    /// breadth first search from the snake's head to the closest food, giving up once the budget is spent
+    /// ## Returns:
+    /// the first tile of the path to the closest reachable food
+    fn first_step_to_food(
+        &self,
+        safe_moves: &[Coord],
+        board: &types::Board,
+        game_board: &types::FastMap<Coord, types::Flags>,
+        snake: &types::Battlesnake,
+    ) -> Option<Coord> {
+        if board.food.is_empty() {
+            return None;
+        }
+        // every queued tile remembers which first step led to it
+        let mut frontier: VecDeque<(Coord, Coord)> = safe_moves
+            .iter()
+            .map(|dir| (snake.head + *dir, snake.head + *dir))
+            .collect();
+        let mut visited: HashSet<Coord> = frontier.iter().map(|(tile, _)| *tile).collect();
+        visited.insert(snake.head);
+        let anywhere = logic::MoveOptions::default().avoid_snake_heads(false);
+        let mut expanded = 0;
+        while let Some((tile, first)) = frontier.pop_front() {
+            if board.food.contains(&tile) {
+                return Some(first);
+            }
+            expanded += 1;
+            if expanded >= self.budget {
+                return None;
+            }
+            for dir in types::DIRECTIONS.values() {
+                let adj = tile + *dir;
+                if !visited.contains(&adj) && logic::can_move_board(&adj, board, game_board, snake, &anywhere) {
+                    visited.insert(adj);
+                    frontier.push_back((adj, first));
+                }
+            }
+        }
+        return None;
+    }
+}
+
+impl OpponentPolicy for GreedyPolicy {
+    fn move_weights(
+        &self,
+        board: &types::Board,
+        game_board: &types::FastMap<Coord, types::Flags>,
+        snake: &types::Battlesnake,
+    ) -> Vec<(Coord, f32)> {
+        let moves = candidate_moves(snake);
+        let safe_moves: Vec<Coord> = moves
+            .iter()
+            .copied()
+            .filter(|dir| self.is_safe(&(snake.head + *dir), board, game_board, snake))
+            .collect();
+        // a snake with no safe moves is equally likely to pick any of its bad ones
+        if safe_moves.is_empty() {
+            let weight = 1.0 / moves.len().max(1) as f32;
+            return moves.into_iter().map(|mv| (mv, weight)).collect();
+        }
+
+        let food_step = self
+            .first_step_to_food(&safe_moves, board, game_board, snake)
+            .map(|tile| tile - snake.head);
+        let (bonus, share) = match food_step {
+            Some(_) if safe_moves.len() > 1 => (
+                self.confidence,
+                (1.0 - self.confidence) / (safe_moves.len() - 1) as f32,
+            ),
+            Some(_) => (1.0, 0.0),
+            None => (0.0, 1.0 / safe_moves.len() as f32),
+        };
+        return moves
+            .into_iter()
+            .map(|mv| {
+                let weight = if Some(mv) == food_step {
+                    bonus
+                } else if safe_moves.contains(&mv) {
+                    share
+                } else {
+                    0.0
+                };
+                (mv, weight)
+            })
+            .collect();
+    }
+}
+
+/// models opponents as weighing their moves by how well each scores on its own: a softmax over the `MoveScore` of every
+/// move that doesn't run into a wall or a body. it never rules out a plausible move, which is what expectimax needs
+pub struct SoftmaxPolicy {
+    pub weights: Weights,
+    /// the score difference that makes one move e times as likely as another, lower is more decisive
+    pub temperature: f32,
+}
+
+impl Default for SoftmaxPolicy {
+    fn default() -> Self {
+        return SoftmaxPolicy {
+            weights: Weights::DEFAULT,
+            temperature: 10.0,
+        };
+    }
+}
+
+impl OpponentPolicy for SoftmaxPolicy {
+    fn move_weights(
+        &self,
+        board: &types::Board,
+        game_board: &types::FastMap<Coord, types::Flags>,
+        snake: &types::Battlesnake,
+    ) -> Vec<(Coord, f32)> {
+        let moves = candidate_moves(snake);
+        let anywhere = logic::MoveOptions::default().avoid_snake_heads(false);
+        let scores: Vec<Option<f32>> = moves
+            .iter()
+            .map(|dir| {
+                logic::can_move_board(&(snake.head + *dir), board, game_board, snake, &anywhere)
+                    .then(|| MoveScore::new(board, game_board, snake, dir).value(&self.weights) as f32)
+            })
+            .collect();
+        // a snake with no safe moves is equally likely to pick any of its bad ones
+        let Some(best) = scores.iter().flatten().copied().reduce(f32::max) else {
+            let weight = 1.0 / moves.len().max(1) as f32;
+            return moves.into_iter().map(|mv| (mv, weight)).collect();
+        };
+        let exps: Vec<f32> = scores
+            .iter()
+            .map(|score| score.map_or(0.0, |score| ((score - best) / self.temperature).exp()))
+            .collect();
+        let total: f32 = exps.iter().sum();
+        return moves.into_iter().zip(exps).map(|(mv, exp)| (mv, exp / total)).collect();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn predicts_food_grab() {
+        // the avoid_head_to_head fixture: the other snake is one step from food that we're also next to
+        const BOARD_DATA: &str = r#"
+          {
+            "food": [
+              { "x": 5, "y": 5 }
+            ],
+            "snakes": [
+              {
+                "id": "mTOl1",
+                "name": "snake mTOl1",
+                "health": 80,
+                "body": [
+                  { "x": 4, "y": 5 },
+                  { "x": 3, "y": 5 },
+                  { "x": 2, "y": 5 },
+                  { "x": 1, "y": 5 }
+                ],
+                "latency": 0,
+                "head": { "x": 4, "y": 5 },
+                "length": 4,
+                "shout": "",
+                "squad": ""
+              },
+              {
+                "id": "uZejq",
+                "name": "snake uZejq",
+                "health": 80,
+                "body": [
+                  { "x": 5, "y": 4 },
+                  { "x": 5, "y": 3 },
+                  { "x": 5, "y": 2 },
+                  { "x": 5, "y": 1 }
+                ],
+                "latency": 0,
+                "head": { "x": 5, "y": 4 },
+                "length": 4,
+                "shout": "",
+                "squad": ""
+              }
+            ],
+            "width": 11,
+            "height": 11,
+            "hazards": []
+          }
+        "#;
+        let board: types::Board = serde_json::from_str(BOARD_DATA).unwrap();
+        let game_board = board.to_game_board();
+        let policy = GreedyPolicy::default();
+        let opponent = &board.snakes[0];
+        assert_eq!(
+            policy.predict(&board, &game_board, opponent),
+            Some(types::DIRECTIONS["right"])
+        );
+        let weights = policy.move_weights(&board, &game_board, opponent);
+        let total: f32 = weights.iter().map(|(_, weight)| weight).sum();
+        assert!((total - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn prediction_respects_own_body() {
+        // the food is straight behind the snake, the shortest legal route goes around its body
+        const BOARD_DATA: &str = r#"
+          {
+            "food": [
+              { "x": 0, "y": 5 }
+            ],
+            "snakes": [
+              {
+                "id": "them",
+                "name": "them",
+                "health": 50,
+                "body": [
+                  { "x": 4, "y": 5 },
+                  { "x": 3, "y": 5 },
+                  { "x": 2, "y": 5 },
+                  { "x": 1, "y": 5 }
+                ],
+                "latency": 0,
+                "head": { "x": 4, "y": 5 },
+                "length": 4,
+                "shout": "",
+                "squad": ""
+              }
+            ],
+            "width": 11,
+            "height": 11,
+            "hazards": []
+          }
+        "#;
+        let board: types::Board = serde_json::from_str(BOARD_DATA).unwrap();
+        let game_board = board.to_game_board();
+        let policy = GreedyPolicy::default();
+        let opponent = &board.snakes[0];
+        let predicted = policy.predict(&board, &game_board, opponent).unwrap();
+        assert!(predicted == types::DIRECTIONS["up"] || predicted == types::DIRECTIONS["down"]);
+        for (mv, weight) in policy.move_weights(&board, &game_board, opponent) {
+            if weight > 0.0 {
+                assert!(!opponent.body.contains(&(opponent.head + mv)));
+            }
+        }
+
+        // with no budget the search gives up and every safe move is equally likely
+        let blind = GreedyPolicy {
+            budget: 1,
+            ..GreedyPolicy::default()
+        };
+        let weights = blind.move_weights(&board, &game_board, opponent);
+        assert!(weights
+            .iter()
+            .filter(|(mv, _)| *mv != types::DIRECTIONS["left"])
+            .all(|(_, weight)| (*weight - 1.0 / 3.0).abs() < 0.001));
+    }
+
+    #[test]
+    fn softmax_weighs_every_safe_move() {
+        let board: types::Board = serde_json::from_value(serde_json::json!({
+            "width": 11, "height": 11, "hazards": [], "food": [{ "x": 0, "y": 3 }],
+            "snakes": [{
+                "id": "them", "name": "them", "health": 80, "length": 3, "head": { "x": 0, "y": 5 },
+                "body": [{ "x": 0, "y": 5 }, { "x": 1, "y": 5 }, { "x": 2, "y": 5 }],
+            }],
+        }))
+        .unwrap();
+        let game_board = board.to_game_board();
+        let weights = SoftmaxPolicy::default().move_weights(&board, &game_board, &board.snakes[0]);
+        let weight = |dir: Coord| weights.iter().find(|(mv, _)| *mv == dir).unwrap().1;
+        // off the board is never played, toward the food is the likeliest but up still gets its share
+        assert_eq!(weight(Coord { x: -1, y: 0 }), 0.0);
+        assert!(weight(Coord { x: 0, y: -1 }) > weight(Coord { x: 0, y: 1 }));
+        assert!(weight(Coord { x: 0, y: 1 }) > 0.05);
+        assert!((weights.iter().map(|(_, weight)| weight).sum::<f32>() - 1.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::search::eval::{MoveScore, Weights};
+use crate::types::{self, Coord};
+use std::collections::HashMap;
+
+/// # is_capture
+/// the battlesnake equivalent of a capture: a move onto food or a move that can win a head-to-head against a shorter snake
+fn is_capture(board: &types::Board, snake: &types::Battlesnake, dir: &Coord) -> bool {
+    let tile = snake.head + *dir;
+    if board.food.contains(&tile) {
+        return true;
+    }
+    return board.snakes.iter().any(|other| {
+        other.id != snake.id && other.length < snake.length && other.head.distance(&tile) <= 1.0
+    });
+}
+
+/// move ordering for the alpha-beta search, cheap guesses at which moves are best so the search can prune the rest
+#[derive(Debug, Default)]
+pub struct MoveOrdering {
+    /// per ply, up to two of our moves that recently caused a beta cutoff
+    killers: Vec<[Option<Coord>; 2]>,
+    /// per ply, the last opponent reply (one direction per opponent) that caused an alpha cutoff
+    reply_killers: Vec<Option<Vec<Coord>>>,
+    /// how often a move from a given head tile caused a cutoff, weighted by remaining depth
+    history: HashMap<(Coord, Coord), i32>,
+}
+
+impl MoveOrdering {
+    pub fn new() -> Self {
+        return MoveOrdering::default();
+    }
+
+    /// # order_moves
+    /// orders a snake's candidate moves: transposition table move, captures, killers, then history and static `MoveScore`
+    /// ## Arguments:
+    /// * board - the battlesnake game board
+    /// * game_board - the hashmap representation of the game board
+    /// * snake - the snake making the move
+    /// * moves - the candidate unit directions, sorted in place
+    /// * ply - the distance from the root of the search
+    /// * tt_move - the best move stored in the transposition table for this position, if any
+    /// * weights - weights used to compute the static `MoveScore`
+    pub fn order_moves(
+        &self,
+        board: &types::Board,
+        game_board: &types::FastMap<Coord, types::Flags>,
+        snake: &types::Battlesnake,
+        moves: &mut [Coord],
+        ply: usize,
+        tt_move: Option<Coord>,
+        weights: &Weights,
+    ) {
+        let killers = self.killers.get(ply).copied().unwrap_or([None, None]);
+        moves.sort_by_cached_key(|mv| {
+            let tier = if Some(*mv) == tt_move {
+                0
+            } else if is_capture(board, snake, mv) {
+                1
+            } else if killers.contains(&Some(*mv)) {
+                2
+            } else {
+                3
+            };
+            let history = *self.history.get(&(snake.head, *mv)).unwrap_or(&0);
+            let score = MoveScore::new(board, game_board, snake, mv).value(weights);
+            (tier, -history, -score)
+        });
+    }
+
+    /// # order_opponent_moves
+    /// orders an opponent's candidate moves by captures then static `MoveScore`, the moves they're most likely to make come first
+    pub fn order_opponent_moves(
+        &self,
+        board: &types::Board,
+        game_board: &types::FastMap<Coord, types::Flags>,
+        snake: &types::Battlesnake,
+        moves: &mut [Coord],
+        weights: &Weights,
+    ) {
+        moves.sort_by_cached_key(|mv| {
+            let tier = if is_capture(board, snake, mv) { 0 } else { 1 };
+            (
+                tier,
+                -MoveScore::new(board, game_board, snake, mv).value(weights),
+            )
+        });
+    }
+
+    /// # order_replies
+    /// moves the opponents' killer reply for this ply to the front of the joint replies
+    pub fn order_replies(&self, replies: &mut Vec<Vec<Coord>>, ply: usize) {
+        if let Some(Some(killer)) = self.reply_killers.get(ply) {
+            if let Some(index) = replies.iter().position(|reply| reply == killer) {
+                let reply = replies.remove(index);
+                replies.insert(0, reply);
+            }
+        }
+    }
+
+    /// # record_cutoff
+    /// remembers one of our moves that caused a beta cutoff
+    pub fn record_cutoff(&mut self, ply: usize, head: Coord, mv: Coord, depth: u8) {
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None, None]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+        *self.history.entry((head, mv)).or_insert(0) += depth as i32 * depth as i32;
+    }
+
+    /// # record_reply_cutoff
+    /// remembers the opponents' joint reply that caused an alpha cutoff
+    pub fn record_reply_cutoff(&mut self, ply: usize, reply: &[Coord]) {
+        if self.reply_killers.len() <= ply {
+            self.reply_killers.resize(ply + 1, None);
+        }
+        self.reply_killers[ply] = Some(reply.to_vec());
+    }
+}
@@ -0,0 +1,244 @@
+use crate::logic;
+use crate::search::minimax::{candidate_moves, MOVES};
+use crate::types::{self, Coord, Ruleset};
+
+/// # plausible_moves
+/// the moves an opponent could make without killing itself on a wall or a body, every candidate if it has none
+fn plausible_moves(board: &types::Board, snake: &types::Battlesnake) -> Vec<Coord> {
+    let game_board = board.to_game_board();
+    let moves = candidate_moves(snake);
+    let anywhere = logic::MoveOptions::default().avoid_snake_heads(false);
+    let plausible: Vec<Coord> = moves
+        .iter()
+        .copied()
+        .filter(|dir| logic::can_move_board(&(snake.head + *dir), board, &game_board, snake, &anywhere))
+        .collect();
+    if plausible.is_empty() {
+        return moves;
+    }
+    return plausible;
+}
+
+/// # survival_rate
+/// simulates one turn with our move against every combination of the opponents' plausible replies. only opponents
+/// whose head could reach the tile we move onto branch, the rest play their first plausible move
+/// ## Arguments:
+/// * board - the battlesnake game board
+/// * you_id - the id of our snake
+/// * dir - the unit direction of our move
+/// * turn - the turn number the board is at after the move
+/// * rules - the hazard damage and royale settings
+/// ## Returns:
+/// the fraction of replies we survive
+pub fn survival_rate(
+    board: &types::Board,
+    you_id: &str,
+    dir: &Coord,
+    turn: u32,
+    rules: &Ruleset,
+) -> f32 {
+    let you = match board.snakes.iter().find(|snake| snake.id == you_id) {
+        Some(snake) => snake,
+        None => return 0.0,
+    };
+    let target = you.head + *dir;
+    let mut replies: Vec<Vec<Coord>> = vec![vec![]];
+    for snake in &board.snakes {
+        let moves = if snake.id == you_id {
+            vec![*dir]
+        } else {
+            let mut moves = plausible_moves(board, snake);
+            let reach = (snake.head.x - target.x).abs() + (snake.head.y - target.y).abs();
+            if reach > 1 {
+                moves.truncate(1);
+            }
+            moves
+        };
+        replies = replies
+            .into_iter()
+            .flat_map(|reply| {
+                moves.iter().map(move |mv| {
+                    let mut extended = reply.clone();
+                    extended.push(*mv);
+                    extended
+                })
+            })
+            .collect();
+    }
+
+    let _span = tracing::debug_span!("simulate", replies = replies.len()).entered();
+    let mut board = board.clone();
+    // every reply is undone before the next, so they all start from the same bodies
+    let occupied = board.to_occupancy();
+    let mut survived = 0;
+    for reply in &replies {
+        let token = board.simulate_turn_with(reply, turn, rules, &occupied);
+        if board.snakes.iter().any(|snake| snake.id == you_id) {
+            survived += 1;
+        }
+        board.undo(token);
+    }
+    return survived as f32 / replies.len() as f32;
+}
+
+/// # validate_move
+/// a last check on the move the pipeline chose: if it dies against every plausible reply but another move survives
+/// at least one, the move that survives the most replies is played instead
+/// ## Arguments:
+/// * board - the battlesnake game board
+/// * you_id - the id of our snake
+/// * chosen - the unit direction the pipeline chose
+/// * turn - the turn number the board is at after the move
+/// * rules - the hazard damage and royale settings
+/// ## Returns:
+/// the unit direction to play
+pub fn validate_move(
+    board: &types::Board,
+    you_id: &str,
+    chosen: &Coord,
+    turn: u32,
+    rules: &Ruleset,
+) -> Coord {
+    if survival_rate(board, you_id, chosen, turn, rules) > 0.0 {
+        return *chosen;
+    }
+    let mut best = *chosen;
+    let mut best_rate = 0.0;
+    for dir in MOVES {
+        let rate = survival_rate(board, you_id, &dir, turn, rules);
+        if rate > best_rate {
+            best = dir;
+            best_rate = rate;
+        }
+    }
+    return best;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // we're about to step next to a longer snake whose only way out is the tile we step onto
+    const CORNERED_DATA: &str = r#"
+      {
+        "game": {
+          "id": "cornered",
+          "ruleset": { "name": "standard", "version": "v1.0.0" },
+          "timeout": 500
+        },
+        "turn": 40,
+        "board": {
+          "height": 11,
+          "width": 11,
+          "food": [],
+          "snakes": [
+            {
+              "id": "me",
+              "name": "me",
+              "health": 90,
+              "body": [
+                { "x": 4, "y": 5 },
+                { "x": 4, "y": 6 },
+                { "x": 4, "y": 7 },
+                { "x": 4, "y": 8 }
+              ],
+              "latency": 0,
+              "head": { "x": 4, "y": 5 },
+              "length": 4,
+              "shout": ""
+            },
+            {
+              "id": "them",
+              "name": "them",
+              "health": 90,
+              "body": [
+                { "x": 5, "y": 6 },
+                { "x": 5, "y": 7 },
+                { "x": 6, "y": 7 },
+                { "x": 6, "y": 6 },
+                { "x": 6, "y": 5 },
+                { "x": 6, "y": 4 },
+                { "x": 6, "y": 3 }
+              ],
+              "latency": 0,
+              "head": { "x": 5, "y": 6 },
+              "length": 7,
+              "shout": ""
+            }
+          ],
+          "hazards": []
+        },
+        "you": {
+          "id": "me",
+          "name": "me",
+          "health": 90,
+          "body": [
+            { "x": 4, "y": 5 },
+            { "x": 4, "y": 6 },
+            { "x": 4, "y": 7 },
+            { "x": 4, "y": 8 }
+          ],
+          "latency": 0,
+          "head": { "x": 4, "y": 5 },
+          "length": 4,
+          "shout": ""
+        }
+      }
+    "#;
+
+    #[test]
+    fn validator_avoids_cornered_longer_head() {
+        let state: types::GameState = serde_json::from_str(CORNERED_DATA).unwrap();
+        let rules = Ruleset::default();
+        let right = types::DIRECTIONS["right"];
+        assert_eq!(survival_rate(&state.board, "me", &right, 41, &rules), 0.0);
+
+        let validated = validate_move(&state.board, "me", &right, 41, &rules);
+        assert_ne!(validated, right);
+        assert_eq!(
+            survival_rate(&state.board, "me", &validated, 41, &rules),
+            1.0
+        );
+
+        // a move that survives is left alone
+        let left = types::DIRECTIONS["left"];
+        assert_eq!(validate_move(&state.board, "me", &left, 41, &rules), left);
+
+        let response = logic::get_move(&state.game, &state.turn, &state.board, &state.you);
+        assert_ne!(response["move"], "right");
+    }
+
+    #[test]
+    fn validator_keeps_move_when_nothing_survives() {
+        // boxed into the corner by our own body, every move dies
+        const BOARD_DATA: &str = r#"
+          {
+            "food": [],
+            "snakes": [
+              {
+                "id": "me",
+                "name": "me",
+                "health": 90,
+                "body": [
+                  { "x": 0, "y": 0 },
+                  { "x": 1, "y": 0 },
+                  { "x": 1, "y": 1 },
+                  { "x": 0, "y": 1 },
+                  { "x": 0, "y": 2 }
+                ],
+                "latency": 0,
+                "head": { "x": 0, "y": 0 },
+                "length": 5,
+                "shout": ""
+              }
+            ],
+            "width": 11,
+            "height": 11,
+            "hazards": []
+          }
+        "#;
+        let board: types::Board = serde_json::from_str(BOARD_DATA).unwrap();
+        let up = types::DIRECTIONS["up"];
+        assert_eq!(validate_move(&board, "me", &up, 1, &Ruleset::default()), up);
+    }
+}
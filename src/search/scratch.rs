@@ -0,0 +1,158 @@
+//! the frontiers, visited sets and parent maps the searches and flood fills work in, kept by each thread from one
+//! move to the next so a turn's searches empty the buffers the last turn grew instead of allocating their own. a
+//! search takes a whole scratch for itself and hands it back when it's done, a search started inside another one
+//! (a flood fill under A*) takes a second one, so nothing running at once shares a buffer
+
+use crate::search::graph::NodeRecord;
+use crate::search::tiles::{TileMap, TileSet};
+use crate::types::{self, Coord};
+use ordered_float::OrderedFloat;
+use priority_queue::PriorityQueue;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+/// the buffers one search works in, emptied and sized for the board every time it's handed out
+#[derive(Default)]
+pub struct SearchScratch {
+    /// the tiles a breadth first search has yet to expand
+    pub frontier: VecDeque<Coord>,
+    /// the tiles a best first search has yet to expand, the best first
+    pub queue: PriorityQueue<Coord, OrderedFloat<f32>>,
+    pub visited: TileSet,
+    /// the tile each tile was reached from
+    pub parents: TileMap<Coord>,
+    /// what A* knows about each tile it reached
+    pub nodes: TileMap<NodeRecord>,
+    pub path: Vec<Coord>,
+}
+
+impl SearchScratch {
+    /// # reset
+    /// empties every buffer, the tile containers sized for a board
+    fn reset(&mut self, board: &types::Board) {
+        self.frontier.clear();
+        self.queue.clear();
+        self.visited.reset(board);
+        self.parents.reset(board);
+        self.nodes.reset(board);
+        self.path.clear();
+    }
+}
+
+thread_local! {
+    /// the scratches this thread isn't using, as many as its deepest nesting of searches has needed
+    static SCRATCHES: RefCell<Vec<SearchScratch>> = const { RefCell::new(Vec::new()) };
+}
+
+#[cfg(test)]
+thread_local! {
+    /// off for every search on this thread to start from fresh buffers, for the tests to compare
+    static REUSE: std::cell::Cell<bool> = const { std::cell::Cell::new(true) };
+}
+
+/// # with_scratch
+/// runs a search with a scratch of its own, taken from the thread's spares or made if they've run out and handed back
+/// once the search is done. a search that panics loses its scratch and the next one makes another
+/// ## Arguments:
+/// * board - the board the search runs on, the scratch is emptied and sized for it
+/// * search - the search, given the scratch
+pub fn with_scratch<R>(board: &types::Board, search: impl FnOnce(&mut SearchScratch) -> R) -> R {
+    let mut scratch = SCRATCHES.with(|scratches| scratches.borrow_mut().pop()).unwrap_or_default();
+    #[cfg(test)]
+    if !REUSE.with(|reuse| reuse.get()) {
+        scratch = SearchScratch::default();
+    }
+    scratch.reset(board);
+    let result = search(&mut scratch);
+    SCRATCHES.with(|scratches| scratches.borrow_mut().push(scratch));
+    return result;
+}
+
+/// # set_reuse
+/// whether searches on this thread get the spare scratches or fresh ones
+#[cfg(test)]
+pub fn set_reuse(reuse: bool) {
+    REUSE.with(|cell| cell.set(reuse));
+}
+
+/// # spares
+/// the scratches this thread has ready for its next searches
+#[cfg(test)]
+pub fn spares() -> usize {
+    return SCRATCHES.with(|scratches| scratches.borrow().len());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::TurnContext;
+    use crate::fixtures;
+    use crate::logic::tests::random_board;
+    use crate::search::{eval, graph};
+    use crate::seed;
+    use std::path::Path;
+
+    /// the fixtures, an 11x11 board and a 5x5 one among them, with random boards after them. the frames without a snake
+    /// of ours to play are answered before any search runs, see `logic::intact`
+    fn boards() -> Vec<types::Board> {
+        let mut rng = seed::rng("scratch", 0);
+        let mut boards: Vec<types::Board> = fixtures::load(Path::new(fixtures::FIXTURE_DIR))
+            .unwrap()
+            .into_iter()
+            .filter(|fixture| crate::logic::intact(&fixture.state.board, &fixture.state.you))
+            .map(|fixture| fixture.state.board)
+            .collect();
+        boards.extend((0..10).map(|_| random_board(&mut rng)));
+        return boards;
+    }
+
+    /// every search that borrows a scratch, with the flood fills left to run inside A* and the long path
+    fn search(board: &types::Board) -> String {
+        let game_board = board.to_game_board();
+        let you = &board.snakes[0];
+        let ctx = TurnContext::sample(board, &game_board, you).with_full_fills();
+        // the corner furthest from our head, never the head itself
+        let corner = Coord {
+            x: if you.head.x < board.width as i16 / 2 { board.width as i16 - 1 } else { 0 },
+            y: if you.head.y < board.height as i16 / 2 { board.height as i16 - 1 } else { 0 },
+        };
+        return format!(
+            "{:?} {:?} {:?} {:?} {}",
+            graph::a_star(&ctx, &crate::logic::MoveOptions::default().thresholds(0.5, 0), 16),
+            graph::dfs_long(&corner, &ctx, 0.0, 0),
+            graph::inside_box(&ctx, 0.3),
+            graph::find_key_hole(&ctx),
+            eval::reachable_tiles(&you.head, board, &game_board)
+        );
+    }
+
+    #[test]
+    fn reused_scratches_find_what_fresh_ones_do() {
+        let boards = boards();
+        set_reuse(false);
+        let fresh: Vec<String> = boards.iter().map(search).collect();
+        set_reuse(true);
+        // twice over, the second time round every search starts from buffers the boards before it left behind
+        for _ in 0..2 {
+            let reused: Vec<String> = boards.iter().map(search).collect();
+            assert_eq!(reused, fresh);
+        }
+        // A* and its flood fills each had one
+        assert!(spares() >= 2, "{} spare scratches", spares());
+    }
+
+    #[test]
+    fn nested_searches_get_their_own_scratch() {
+        let board = &boards()[0];
+        with_scratch(board, |outer| {
+            outer.path.push(Coord { x: 1, y: 1 });
+            outer.visited.insert(Coord { x: 1, y: 1 });
+            with_scratch(board, |inner| {
+                assert!(inner.path.is_empty());
+                assert_eq!(inner.visited.len(), 0);
+                inner.path.push(Coord { x: 2, y: 2 });
+            });
+            assert_eq!(outer.path, vec![Coord { x: 1, y: 1 }]);
+        });
+    }
+}
@@ -0,0 +1,259 @@
+//! the opponents about to starve. an opponent a few moves from running out of health with no food it can reach before
+//! we do is as good as dead: its head can't be worth a detour, the food next to it isn't its to take, and when a
+//! single food is all that stands between it and starving, taking that food ourselves finishes it off. food that
+//! spawns could still save it, so only the opponents within `DOOMED_HORIZON` moves of starving are written off
+
+use crate::types::{self, Coord, FastMap, HazardStacks, Ruleset};
+use std::collections::HashMap;
+
+/// the most moves an opponent can have left before starving for it to be written off
+pub const DOOMED_HORIZON: u8 = 5;
+
+/// what's left for an opponent close to starving
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outlook {
+    /// the moves it can make before it starves unless it eats, staying off the hazards
+    pub turns_to_live: u8,
+    /// the food it can reach before it starves and before we can
+    pub lifelines: Vec<Coord>,
+    /// the food it can reach before it starves, but that we reach first
+    pub deniable: Vec<Coord>,
+}
+
+impl Outlook {
+    /// # doomed
+    /// whether nothing but food we take first can save it
+    pub fn doomed(&self) -> bool {
+        return self.turns_to_live <= DOOMED_HORIZON && self.lifelines.is_empty();
+    }
+
+    /// # denial
+    /// the food to take when it's the one food that could still save the snake
+    pub fn denial(&self) -> Option<Coord> {
+        if !self.doomed() || self.deniable.len() != 1 {
+            return None;
+        }
+        return self.deniable.first().copied();
+    }
+}
+
+/// # vacated_after
+/// the moves until each body segment leaves its tile, counting from the tail. a stacked segment leaves with the last
+/// one on its tile
+fn vacated_after(board: &types::Board) -> FastMap<Coord, u16> {
+    let mut vacated: FastMap<Coord, u16> = FastMap::default();
+    for snake in &board.snakes {
+        for (i, segment) in snake.body.iter().enumerate() {
+            let moves = (snake.body.len() - i) as u16;
+            let leaves = vacated.entry(*segment).or_insert(0);
+            *leaves = (*leaves).max(moves);
+        }
+    }
+    return vacated;
+}
+
+/// # steps_to_food
+/// how many moves a snake needs to reach each food within a number of moves, through tiles that are free by the time
+/// it gets there. with `hazards` its health runs down on the way and a food only counts if it gets there alive
+/// ## Arguments:
+/// * board - the battlesnake game board
+/// * game_board - the hashmap representation of the game board
+/// * vacated - the moves until each body segment leaves its tile, see `vacated_after`
+/// * snake - the snake moving
+/// * max_moves - the most moves to look
+/// * hazards - the hazard stacks and the game's rules, none to leave health out of it
+fn steps_to_food(
+    board: &types::Board,
+    game_board: &FastMap<Coord, types::Flags>,
+    vacated: &FastMap<Coord, u16>,
+    snake: &types::Battlesnake,
+    max_moves: u16,
+    hazards: Option<(&HazardStacks, &Ruleset)>,
+) -> FastMap<Coord, u16> {
+    let mut found: FastMap<Coord, u16> = FastMap::default();
+    // the most health the snake has left on each tile reached, a tile reached again with less isn't worth going on from
+    let mut best: FastMap<Coord, u16> = FastMap::default();
+    let mut frontier = vec![(snake.head, snake.health as u16)];
+    for step in 1..=max_moves {
+        let mut next = vec![];
+        for (tile, health) in frontier {
+            for dir in types::DIRECTIONS.values() {
+                let to = tile + *dir;
+                let on_board = to.x >= 0 && to.y >= 0 && (to.x as u8) < board.width && (to.y as u8) < board.height;
+                let walled = game_board.get(&to).is_some_and(|flags| flags.contains(types::Flags::WALL));
+                if !on_board || walled || vacated.get(&to).is_some_and(|moves| *moves > step) {
+                    continue;
+                }
+                if board.food.contains(&to) {
+                    found.entry(to).or_insert(step);
+                    continue;
+                }
+                let cost = match hazards {
+                    Some((stacks, rules)) => 1 + stacks.damage_on(&to, step, rules.hazard_damage) as u16,
+                    None => 0,
+                };
+                let left = health.saturating_sub(cost);
+                if (hazards.is_some() && left == 0) || best.get(&to).is_some_and(|seen| *seen >= left) {
+                    continue;
+                }
+                best.insert(to, left);
+                next.push((to, left));
+            }
+        }
+        frontier = next;
+    }
+    return found;
+}
+
+/// # outlooks
+/// works out what's left for every opponent within `DOOMED_HORIZON` moves of starving. food spawning in the meantime
+/// isn't counted on
+/// ## Arguments:
+/// * board - the battlesnake game board, with the hazards as walls where they are
+/// * you - your battlesnake
+/// * rules - the hazard damage and whether the stacks decay
+/// ## Returns:
+/// the outlook of each of those opponents, by snake id
+pub fn outlooks(board: &types::Board, you: &types::Battlesnake, rules: &Ruleset) -> HashMap<String, Outlook> {
+    let starving: Vec<&types::Battlesnake> = board
+        .snakes
+        .iter()
+        .filter(|snake| snake.id != you.id && snake.health <= DOOMED_HORIZON)
+        .collect();
+    if starving.is_empty() {
+        return HashMap::new();
+    }
+    let game_board = board.to_game_board();
+    let vacated = vacated_after(board);
+    let stacks = HazardStacks::new(board, rules);
+    let ours = steps_to_food(board, &game_board, &vacated, you, DOOMED_HORIZON as u16, None);
+    return starving
+        .into_iter()
+        .map(|snake| {
+            let hazards = Some((&stacks, rules));
+            let theirs = steps_to_food(board, &game_board, &vacated, snake, snake.health as u16, hazards);
+            let mut outlook = Outlook {
+                turns_to_live: snake.health,
+                lifelines: vec![],
+                deniable: vec![],
+            };
+            for food in &board.food {
+                let Some(steps) = theirs.get(food) else {
+                    continue;
+                };
+                // a head to head on the food goes to the longer snake
+                let first = ours
+                    .get(food)
+                    .is_some_and(|ours| *ours < *steps || (*ours == *steps && you.length > snake.length));
+                if first {
+                    outlook.deniable.push(*food);
+                } else {
+                    outlook.lifelines.push(*food);
+                }
+            }
+            return (snake.id.clone(), outlook);
+        })
+        .collect();
+}
+
+/// # write_off
+/// shrinks every doomed opponent to a segment shorter than us on the board the heuristics play on, so its head isn't
+/// a threat and the food next to it isn't conceded to it
+/// ## Arguments:
+/// * board - the board the heuristics play on
+/// * you - your battlesnake
+/// * outlooks - what's left for the opponents close to starving, see `outlooks`
+/// ## Returns:
+/// the ids of the opponents written off
+pub fn write_off(
+    board: &mut types::Board,
+    you: &types::Battlesnake,
+    outlooks: &HashMap<String, Outlook>,
+) -> Vec<String> {
+    let mut written_off = vec![];
+    for snake in board.snakes.iter_mut() {
+        if outlooks.get(&snake.id).is_some_and(Outlook::doomed) {
+            snake.length = snake.length.min(you.length.saturating_sub(1));
+            written_off.push(snake.id.clone());
+        }
+    }
+    written_off.sort();
+    return written_off;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    /// an 11x11 board with us along the bottom and an opponent of 5 along the top, heads toward the middle
+    fn position(their_health: u8, food: &[(i16, i16)]) -> (types::Board, types::Battlesnake) {
+        let body = |y: i16, health: u8, id: &str| {
+            let body: Vec<_> = (0..5).map(|x| json!({ "x": 5 - x, "y": y })).collect();
+            return json!({
+                "id": id, "name": id, "health": health, "head": body[0], "body": body, "length": 5,
+            });
+        };
+        let food: Vec<_> = food.iter().map(|(x, y)| json!({ "x": x, "y": y })).collect();
+        let board: types::Board = serde_json::from_value(json!({
+            "width": 11, "height": 11, "food": food, "hazards": [],
+            "snakes": [body(1, 90, "me"), body(9, their_health, "them")],
+        }))
+        .unwrap();
+        let you = board.snakes[0].clone();
+        return (board, you);
+    }
+
+    #[test]
+    fn food_out_of_reach_dooms_a_starving_opponent() {
+        let (board, you) = position(3, &[(5, 5)]);
+        let outlook = &outlooks(&board, &you, &Ruleset::default())["them"];
+        assert_eq!(outlook.turns_to_live, 3);
+        assert!(outlook.lifelines.is_empty() && outlook.deniable.is_empty());
+        assert!(outlook.doomed());
+        assert_eq!(outlook.denial(), None);
+
+        // four moves away it's in reach
+        let (board, you) = position(4, &[(5, 5)]);
+        assert!(!outlooks(&board, &you, &Ruleset::default())["them"].doomed());
+        // and a healthy opponent isn't looked at
+        let (board, you) = position(DOOMED_HORIZON + 1, &[]);
+        assert!(outlooks(&board, &you, &Ruleset::default()).is_empty());
+    }
+
+    #[test]
+    fn food_we_reach_first_only_saves_them_if_we_leave_it() {
+        // five moves for them, three for us
+        let (board, you) = position(5, &[(5, 4), (9, 9)]);
+        let outlook = &outlooks(&board, &you, &Ruleset::default())["them"];
+        assert_eq!(outlook.deniable, [Coord { x: 5, y: 4 }]);
+        assert_eq!(outlook.lifelines, [Coord { x: 9, y: 9 }]);
+        assert!(!outlook.doomed());
+
+        let (board, you) = position(5, &[(5, 4)]);
+        let outlook = &outlooks(&board, &you, &Ruleset::default())["them"];
+        assert!(outlook.doomed());
+        assert_eq!(outlook.denial(), Some(Coord { x: 5, y: 4 }));
+        // level on the food and as long as us, the head to head kills us both and it's still theirs to gamble on
+        let (board, you) = position(5, &[(5, 5)]);
+        assert_eq!(outlooks(&board, &you, &Ruleset::default())["them"].lifelines, [Coord { x: 5, y: 5 }]);
+    }
+
+    #[test]
+    fn hazards_on_the_way_to_food_can_starve_them() {
+        let (mut board, you) = position(4, &[(5, 5)]);
+        let rules = Ruleset::default();
+        assert!(!outlooks(&board, &you, &rules)["them"].doomed());
+        board.hazards = (0..11).map(|x| Coord { x, y: 7 }).collect();
+        assert!(outlooks(&board, &you, &rules)["them"].doomed());
+    }
+
+    #[test]
+    fn written_off_opponents_look_shorter_than_us() {
+        let (mut board, you) = position(2, &[(5, 5)]);
+        let outlooks = outlooks(&board, &you, &Ruleset::default());
+        assert_eq!(write_off(&mut board, &you, &outlooks), ["them"]);
+        assert_eq!(board.snakes[1].length, you.length - 1);
+        assert_eq!(board.snakes[0].length, you.length);
+    }
+}
@@ -0,0 +1,491 @@
+use crate::search::zobrist;
+use crate::types::{self, Coord, Flags, Occupancy, Ruleset, UndoToken};
+use rand::Rng;
+
+/// a board together with its game board, occupancy and zobrist hash, kept in sync as turns are applied and undone so a
+/// search can walk the whole tree with a single mutable board
+pub struct SearchBoard {
+    pub board: types::Board,
+    pub game_board: types::FastMap<Coord, Flags>,
+    /// the tiles the game board flags `SNAKE`, for the checks that only ask whether a body is in the way
+    pub occupancy: Occupancy,
+    pub hash: u64,
+    /// the turn number of the current position
+    pub turn: u32,
+}
+
+/// what a snake looked like before the turn, enough to update the hash incrementally
+struct Before {
+    key: u64,
+    head: Coord,
+    /// the tail segment's own key, none for a snake with a single segment
+    tail_key: Option<u64>,
+    health: u8,
+    length: u32,
+}
+
+impl SearchBoard {
+    pub fn new(board: types::Board, turn: u32) -> Self {
+        let game_board = board.to_game_board();
+        let occupancy = board.to_occupancy();
+        let hash = zobrist::hash_board(&board);
+        return SearchBoard {
+            board,
+            game_board,
+            occupancy,
+            hash,
+            turn,
+        };
+    }
+
+    /// # set_flag
+    /// sets or clears a flag on a game board tile, journaling the previous value. the occupancy follows the `SNAKE`
+    /// flag
+    fn set_flag(&mut self, token: &mut UndoToken, tile: Coord, flag: Flags, on: bool) {
+        let old = self.game_board.get(&tile).copied();
+        let mut value = old.unwrap_or(Flags::empty());
+        value.set(flag, on);
+        if Some(value) == old || (old.is_none() && value.is_empty()) {
+            return;
+        }
+        token.flags.push((tile, old));
+        if flag.contains(Flags::SNAKE) {
+            if on {
+                self.occupancy.set(tile);
+            } else {
+                self.occupancy.clear(tile);
+            }
+        }
+        if value.is_empty() {
+            self.game_board.remove(&tile);
+        } else {
+            self.game_board.insert(tile, value);
+        }
+    }
+
+    /// # apply
+    /// simulates a turn, updating the game board, occupancy and hash incrementally
+    /// ## Arguments:
+    /// * moves - the unit direction each snake moves in, in the same order as `board.snakes`
+    /// * rules - the hazard damage and royale settings
+    /// ## Returns:
+    /// the journal needed to undo the turn
+    pub fn apply(&mut self, moves: &[Coord], rules: &Ruleset) -> UndoToken {
+        let before: Vec<Before> = self
+            .board
+            .snakes
+            .iter()
+            .map(|snake| {
+                let key = zobrist::snake_key(&snake.id);
+                let len = snake.body.len();
+                let tail_key = if len >= 2 {
+                    let tail = snake.body[len - 1];
+                    Some(zobrist::segment_key(
+                        key,
+                        &tail,
+                        &(snake.body[len - 2] - tail),
+                    ))
+                } else {
+                    None
+                };
+                Before {
+                    key,
+                    head: snake.head,
+                    tail_key,
+                    health: snake.health,
+                    length: snake.length,
+                }
+            })
+            .collect();
+
+        self.turn += 1;
+        let mut token = self.board.simulate_turn_with(moves, self.turn, rules, &self.occupancy);
+        token.hash = self.hash;
+
+        // walk every snake that took part in the turn in its pre-turn order
+        let mut survivors = self.board.snakes.iter();
+        let mut eliminated = token.eliminated.iter().peekable();
+        let mut hash = self.hash;
+        let mut new_heads: Vec<Coord> = vec![];
+        for (i, prev) in before.iter().enumerate() {
+            let snake = match eliminated.peek() {
+                Some((index, snake)) if *index == i => {
+                    eliminated.next();
+                    snake
+                }
+                _ => match survivors.next() {
+                    Some(snake) => snake,
+                    None => break,
+                },
+            };
+            let key = prev.key;
+            let no_dir = Coord { x: 0, y: 0 };
+            hash ^= zobrist::head_key(key, &prev.head) ^ zobrist::head_key(key, &snake.head);
+            hash ^= zobrist::health_key(key, prev.health) ^ zobrist::health_key(key, snake.health);
+            hash ^= zobrist::length_key(key, prev.length) ^ zobrist::length_key(key, snake.length);
+            // the old head stops being the head segment, the new head becomes it
+            hash ^= zobrist::segment_key(key, &prev.head, &no_dir);
+            hash ^= zobrist::segment_key(key, &snake.head, &no_dir);
+            if let Some(tail_key) = prev.tail_key {
+                hash ^= zobrist::segment_key(key, &prev.head, &(snake.head - prev.head));
+                hash ^= tail_key;
+            }
+            // every segment stacked on the tail, by eating or by sharing a squadmate's length, is keyed the same
+            let stacked = token.moved[i].grew as u32 + token.moved[i].shared;
+            if stacked % 2 == 1 {
+                if let Some(tail) = snake.body.last() {
+                    hash ^= zobrist::segment_key(key, tail, &no_dir);
+                }
+            }
+            new_heads.push(snake.head);
+        }
+        for (_, snake) in &token.eliminated {
+            hash ^= zobrist::hash_snake(snake);
+        }
+        for (_, food) in &token.eaten {
+            hash ^= zobrist::food_key(food);
+        }
+        for hazard in &self.board.hazards[token.hazards..] {
+            hash ^= zobrist::hazard_key(hazard);
+        }
+        for (tile, odds) in &self.board.hazard_odds[token.hazard_odds..] {
+            hash ^= zobrist::hazard_odds_key(tile, *odds);
+        }
+        self.hash = hash;
+
+        // game board: new heads are occupied, popped tails and eliminated bodies are freed unless a survivor still covers them
+        for head in new_heads {
+            if self.board.in_bounds(&head) {
+                self.set_flag(&mut token, head, Flags::SNAKE, true);
+            }
+        }
+        let mut vacated: Vec<Coord> = token.moved.iter().filter_map(|moved| moved.tail).collect();
+        for (_, snake) in &token.eliminated {
+            vacated.extend(snake.body.iter().filter(|tile| self.board.in_bounds(tile)));
+        }
+        for tile in vacated {
+            let covered = self
+                .board
+                .snakes
+                .iter()
+                .any(|snake| snake.body.contains(&tile));
+            if !covered {
+                self.set_flag(&mut token, tile, Flags::SNAKE, false);
+            }
+        }
+        let eaten: Vec<Coord> = token.eaten.iter().map(|(_, food)| *food).collect();
+        for food in eaten {
+            self.set_flag(&mut token, food, Flags::FOOD, false);
+        }
+        let hazards: Vec<Coord> = self.board.hazards[token.hazards..].to_vec();
+        let hazard_flags = self.board.hazard_flags();
+        for hazard in hazards {
+            self.set_flag(&mut token, hazard, hazard_flags, true);
+        }
+        #[cfg(feature = "verify")]
+        crate::verify::check_search_board(self);
+        return token;
+    }
+
+    /// # spawn_food
+    /// spawns food like the engine at the end of a turn, keeping the game board and hash in sync
+    /// ## Returns:
+    /// the number of food spawned, pass it to `unspawn_food` to remove them
+    pub fn spawn_food(&mut self, rules: &Ruleset, rng: &mut impl Rng) -> usize {
+        let spawned = self.board.spawn_food(rules, rng);
+        let start = self.board.food.len() - spawned;
+        for food in &self.board.food[start..] {
+            self.hash ^= zobrist::food_key(food);
+            *self.game_board.entry(*food).or_insert(Flags::empty()) |= Flags::FOOD;
+        }
+        return spawned;
+    }
+
+    /// # unspawn_food
+    /// removes the food added by the last `spawn_food`
+    pub fn unspawn_food(&mut self, spawned: usize) {
+        let start = self.board.food.len() - spawned;
+        for food in &self.board.food[start..] {
+            self.hash ^= zobrist::food_key(food);
+            if let Some(flags) = self.game_board.get_mut(food) {
+                flags.remove(Flags::FOOD);
+                if flags.is_empty() {
+                    self.game_board.remove(food);
+                }
+            }
+        }
+        self.board.unspawn_food(spawned);
+    }
+
+    /// # undo
+    /// reverts a turn made by `apply`, restoring the board, game board, occupancy and hash exactly
+    pub fn undo(&mut self, mut token: UndoToken) {
+        for (tile, old) in token.flags.drain(..).rev() {
+            if old.is_some_and(|flags| flags.contains(Flags::SNAKE)) {
+                self.occupancy.set(tile);
+            } else {
+                self.occupancy.clear(tile);
+            }
+            match old {
+                Some(value) => self.game_board.insert(tile, value),
+                None => self.game_board.remove(&tile),
+            };
+        }
+        self.hash = token.hash;
+        self.turn -= 1;
+        self.board.undo(token);
+        #[cfg(feature = "verify")]
+        crate::verify::check_search_board(self);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::logic::tests::random_board;
+    use crate::search::minimax::MOVES;
+    use crate::seed;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const BOARD_DATA: &str = r#"
+      {
+        "food": [
+          { "x": 2, "y": 2 },
+          { "x": 4, "y": 5 },
+          { "x": 6, "y": 1 },
+          { "x": 0, "y": 6 }
+        ],
+        "snakes": [
+          {
+            "id": "a",
+            "name": "a",
+            "health": 40,
+            "body": [
+              { "x": 1, "y": 1 },
+              { "x": 1, "y": 0 },
+              { "x": 0, "y": 0 }
+            ],
+            "latency": 0,
+            "head": { "x": 1, "y": 1 },
+            "length": 3,
+            "shout": "",
+            "squad": ""
+          },
+          {
+            "id": "b",
+            "name": "b",
+            "health": 100,
+            "body": [
+              { "x": 5, "y": 5 },
+              { "x": 5, "y": 5 },
+              { "x": 5, "y": 5 }
+            ],
+            "latency": 0,
+            "head": { "x": 5, "y": 5 },
+            "length": 3,
+            "shout": "",
+            "squad": ""
+          },
+          {
+            "id": "c",
+            "name": "c",
+            "health": 8,
+            "body": [
+              { "x": 3, "y": 3 },
+              { "x": 4, "y": 3 },
+              { "x": 4, "y": 2 },
+              { "x": 5, "y": 2 }
+            ],
+            "latency": 0,
+            "head": { "x": 3, "y": 3 },
+            "length": 4,
+            "shout": "",
+            "squad": ""
+          }
+        ],
+        "width": 7,
+        "height": 7,
+        "hazards": [
+          { "x": 3, "y": 4 },
+          { "x": 2, "y": 3 }
+        ]
+      }
+    "#;
+
+    /// the occupancy marks exactly the tiles the game board flags `SNAKE`, and is what it'd be built from scratch
+    fn assert_occupancy_in_sync(state: &SearchBoard) {
+        let mut free = 0;
+        for x in 0..state.board.width as i16 {
+            for y in 0..state.board.height as i16 {
+                let tile = Coord { x, y };
+                let snake = state.game_board.get(&tile).is_some_and(|flags| flags.contains(Flags::SNAKE));
+                assert_eq!(state.occupancy.is_occupied(&tile), snake, "{:?}", tile);
+                free += !snake as u16;
+            }
+        }
+        assert_eq!(state.occupancy.count_free(), free);
+        assert_eq!(state.occupancy, state.board.to_occupancy());
+    }
+
+    #[test]
+    fn apply_keeps_game_board_and_hash_in_sync() {
+        let board: types::Board = serde_json::from_str(BOARD_DATA).unwrap();
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..50 {
+            let mut state = SearchBoard::new(board.clone(), 0);
+            for _ in 0..10 {
+                let moves: Vec<Coord> = state
+                    .board
+                    .snakes
+                    .iter()
+                    .map(|_| MOVES[rng.gen_range(0..MOVES.len())])
+                    .collect();
+                state.apply(&moves, &Ruleset::default());
+                assert_eq!(state.game_board, state.board.to_game_board());
+                assert_eq!(state.hash, zobrist::hash_board(&state.board));
+                assert_occupancy_in_sync(&state);
+            }
+        }
+    }
+
+    #[test]
+    fn occupancy_follows_random_games() {
+        let mut rng = seed::rng("occupancy_follows_random_games", 0);
+        for _ in 0..50 {
+            let board = random_board(&mut rng);
+            let original = board.to_occupancy();
+            let mut state = SearchBoard::new(board, 0);
+            let mut tokens: Vec<UndoToken> = vec![];
+            for _ in 0..rng.gen_range(1..12) {
+                let moves: Vec<Coord> = state
+                    .board
+                    .snakes
+                    .iter()
+                    .map(|_| MOVES[rng.gen_range(0..MOVES.len())])
+                    .collect();
+                tokens.push(state.apply(&moves, &Ruleset::default()));
+                assert_occupancy_in_sync(&state);
+            }
+            while let Some(token) = tokens.pop() {
+                state.undo(token);
+                assert_occupancy_in_sync(&state);
+            }
+            assert_eq!(state.occupancy, original);
+        }
+    }
+
+    #[test]
+    fn undo_restores_everything() {
+        let board: types::Board = serde_json::from_str(BOARD_DATA).unwrap();
+        let original = serde_json::to_value(&board).unwrap();
+        let original_game_board = board.to_game_board();
+        let original_occupancy = board.to_occupancy();
+        let original_hash = zobrist::hash_board(&board);
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..200 {
+            let mut state = SearchBoard::new(board.clone(), 0);
+            let mut tokens: Vec<UndoToken> = vec![];
+            for _ in 0..rng.gen_range(1..12) {
+                let moves: Vec<Coord> = state
+                    .board
+                    .snakes
+                    .iter()
+                    .map(|_| MOVES[rng.gen_range(0..MOVES.len())])
+                    .collect();
+                tokens.push(state.apply(&moves, &Ruleset::default()));
+            }
+            while let Some(token) = tokens.pop() {
+                state.undo(token);
+            }
+            assert_eq!(serde_json::to_value(&state.board).unwrap(), original);
+            assert_eq!(state.game_board, original_game_board);
+            assert_eq!(state.occupancy, original_occupancy);
+            assert_eq!(state.hash, original_hash);
+        }
+    }
+
+    #[test]
+    fn royale_shrink_is_tracked_and_undone() {
+        let board: types::Board = serde_json::from_str(BOARD_DATA).unwrap();
+        let original = serde_json::to_value(&board).unwrap();
+        let original_hash = zobrist::hash_board(&board);
+        for mode in [types::ShrinkMode::Pessimistic, types::ShrinkMode::Expected] {
+            let rules = Ruleset {
+                royale: Some(types::Royale {
+                    shrink_every_n_turns: 3,
+                    mode,
+                }),
+                ..Ruleset::default()
+            };
+            let mut rng = StdRng::seed_from_u64(3);
+            let mut state = SearchBoard::new(board.clone(), 1);
+            let mut tokens: Vec<UndoToken> = vec![];
+            for _ in 0..6 {
+                let moves: Vec<Coord> = state
+                    .board
+                    .snakes
+                    .iter()
+                    .map(|_| MOVES[rng.gen_range(0..MOVES.len())])
+                    .collect();
+                tokens.push(state.apply(&moves, &rules));
+                assert_eq!(state.game_board, state.board.to_game_board());
+                assert_eq!(state.hash, zobrist::hash_board(&state.board));
+            }
+            // the shrinks on turns 3 and 6 covered the edges of the board
+            let corner = Coord { x: 0, y: 0 };
+            match mode {
+                types::ShrinkMode::Pessimistic => assert!(state.board.hazards.contains(&corner)),
+                types::ShrinkMode::Expected => {
+                    assert!(state.board.hazard_odds.contains(&(corner, 0.5)))
+                }
+            }
+            while let Some(token) = tokens.pop() {
+                state.undo(token);
+            }
+            assert_eq!(serde_json::to_value(&state.board).unwrap(), original);
+            assert!(state.board.hazard_odds.is_empty());
+            assert_eq!(state.hash, original_hash);
+            assert_eq!(state.turn, 1);
+        }
+    }
+
+    #[test]
+    fn seeded_playouts_are_reproducible() {
+        let board: types::Board = serde_json::from_str(BOARD_DATA).unwrap();
+        let rules = Ruleset {
+            food_spawn_chance: 50,
+            minimum_food: 5,
+            ..Ruleset::default()
+        };
+        let playout = |seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut state = SearchBoard::new(board.clone(), 0);
+            for _ in 0..10 {
+                let moves: Vec<Coord> = state
+                    .board
+                    .snakes
+                    .iter()
+                    .map(|_| MOVES[rng.gen_range(0..MOVES.len())])
+                    .collect();
+                state.apply(&moves, &rules);
+                state.spawn_food(&rules, &mut rng);
+                assert_eq!(state.game_board, state.board.to_game_board());
+                assert_eq!(state.hash, zobrist::hash_board(&state.board));
+            }
+            return serde_json::to_value(&state.board).unwrap();
+        };
+        assert_eq!(playout(9), playout(9));
+        assert_ne!(playout(9), playout(10));
+
+        let mut rng = StdRng::seed_from_u64(9);
+        let mut state = SearchBoard::new(board.clone(), 0);
+        let original_hash = state.hash;
+        let original_game_board = state.game_board.clone();
+        let spawned = state.spawn_food(&rules, &mut rng);
+        assert_eq!(spawned, 1);
+        state.unspawn_food(spawned);
+        assert_eq!(state.hash, original_hash);
+        assert_eq!(state.game_board, original_game_board);
+    }
+}
@@ -0,0 +1,208 @@
+//! sets and maps of tiles for the flood fills and searches, stored flat by the tile's place on the board instead of
+//! hashed. a board has at most a few hundred tiles, so a search's visited set is a handful of words and its parent map
+//! a single vector. the searches borrow them from the thread's `scratch` and reset them instead of allocating new ones
+
+use crate::types::{self, Coord};
+
+/// a set of tiles on a board, one bit each
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileSet {
+    width: usize,
+    height: usize,
+    bits: Vec<u64>,
+    len: usize,
+}
+
+impl TileSet {
+    /// # with_size
+    /// an empty set for a board of the given dimensions
+    pub fn with_size(width: usize, height: usize) -> Self {
+        return TileSet {
+            width,
+            height,
+            bits: vec![0; (width * height).div_ceil(64)],
+            len: 0,
+        };
+    }
+
+    /// # reset
+    /// empties the set and sizes it for a board, keeping the memory it has
+    pub fn reset(&mut self, board: &types::Board) {
+        (self.width, self.height) = (board.width as usize, board.height as usize);
+        self.bits.clear();
+        self.bits.resize((self.width * self.height).div_ceil(64), 0);
+        self.len = 0;
+    }
+
+    /// # contains
+    /// whether a tile is in the set, a tile off the board never is
+    pub fn contains(&self, tile: &Coord) -> bool {
+        return match index(tile, self.width, self.height) {
+            Some(index) => self.bits[index / 64] & (1 << (index % 64)) != 0,
+            None => false,
+        };
+    }
+
+    /// # insert
+    /// adds a tile to the set
+    /// ## Returns:
+    /// true if the tile wasn't in the set already
+    /// ## Panics:
+    /// if the tile is off the board, nothing the searches reach is
+    pub fn insert(&mut self, tile: Coord) -> bool {
+        let index = index(&tile, self.width, self.height).expect("a tile on the board");
+        let word = &mut self.bits[index / 64];
+        let bit = 1 << (index % 64);
+        if *word & bit != 0 {
+            return false;
+        }
+        *word |= bit;
+        self.len += 1;
+        return true;
+    }
+
+    /// # len
+    /// the number of tiles in the set
+    pub fn len(&self) -> usize {
+        return self.len;
+    }
+}
+
+impl Default for TileSet {
+    /// an empty set for an empty board, to be reset for a real one
+    fn default() -> Self {
+        return TileSet::with_size(0, 0);
+    }
+}
+
+impl Extend<Coord> for TileSet {
+    fn extend<I: IntoIterator<Item = Coord>>(&mut self, tiles: I) {
+        for tile in tiles {
+            self.insert(tile);
+        }
+    }
+}
+
+/// a map from the tiles on a board to values, one slot each
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileMap<T> {
+    width: usize,
+    height: usize,
+    values: Vec<Option<T>>,
+    len: usize,
+}
+
+impl<T: Copy> TileMap<T> {
+    /// # new
+    /// an empty map sized for a board
+    pub fn new(board: &types::Board) -> Self {
+        return TileMap::with_size(board.width as usize, board.height as usize);
+    }
+
+    /// # with_size
+    /// an empty map for a board of the given dimensions
+    pub fn with_size(width: usize, height: usize) -> Self {
+        return TileMap {
+            width,
+            height,
+            values: vec![None; width * height],
+            len: 0,
+        };
+    }
+
+    /// # reset
+    /// empties the map and sizes it for a board, keeping the memory it has
+    pub fn reset(&mut self, board: &types::Board) {
+        (self.width, self.height) = (board.width as usize, board.height as usize);
+        self.values.clear();
+        self.values.resize(self.width * self.height, None);
+        self.len = 0;
+    }
+
+    /// # get
+    /// the value for a tile, none for a tile that hasn't got one or is off the board
+    pub fn get(&self, tile: &Coord) -> Option<&T> {
+        return index(tile, self.width, self.height).and_then(|index| self.values[index].as_ref());
+    }
+
+    /// # insert
+    /// sets the value for a tile
+    /// ## Returns:
+    /// the value the tile had before, if any
+    /// ## Panics:
+    /// if the tile is off the board, nothing the searches reach is
+    pub fn insert(&mut self, tile: Coord, value: T) -> Option<T> {
+        let index = index(&tile, self.width, self.height).expect("a tile on the board");
+        let previous = self.values[index].replace(value);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        return previous;
+    }
+
+    /// # len
+    /// the number of tiles with a value
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        return self.len;
+    }
+}
+
+impl<T: Copy> Default for TileMap<T> {
+    /// an empty map for an empty board, to be reset for a real one
+    fn default() -> Self {
+        return TileMap::with_size(0, 0);
+    }
+}
+
+/// a tile's place on a board, row by row from the bottom
+fn index(tile: &Coord, width: usize, height: usize) -> Option<usize> {
+    if tile.x < 0 || tile.y < 0 || tile.x as usize >= width || tile.y as usize >= height {
+        return None;
+    }
+    return Some(tile.y as usize * width + tile.x as usize);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seed;
+    use rand::Rng;
+    use std::collections::{HashMap, HashSet};
+
+    #[test]
+    fn tiles_behave_as_the_hashed_containers_did() {
+        let mut rng = seed::rng("tiles_behave_as_the_hashed_containers_did", 0);
+        for _ in 0..50 {
+            let (width, height) = (rng.gen_range(1..26), rng.gen_range(1..26));
+            let mut set = TileSet::with_size(width, height);
+            let mut map: TileMap<Coord> = TileMap::with_size(width, height);
+            let mut hash_set: HashSet<Coord> = HashSet::new();
+            let mut hash_map: HashMap<Coord, Coord> = HashMap::new();
+            let random_tile = |rng: &mut rand::rngs::StdRng| Coord {
+                x: rng.gen_range(0..width as i16),
+                y: rng.gen_range(0..height as i16),
+            };
+            for _ in 0..200 {
+                let (tile, parent) = (random_tile(&mut rng), random_tile(&mut rng));
+                assert_eq!(set.insert(tile), hash_set.insert(tile));
+                assert_eq!(map.insert(tile, parent), hash_map.insert(tile, parent));
+                assert_eq!(set.len(), hash_set.len());
+                assert_eq!(map.len(), hash_map.len());
+            }
+            for x in -1..=width as i16 {
+                for y in -1..=height as i16 {
+                    let tile = Coord { x, y };
+                    assert_eq!(set.contains(&tile), hash_set.contains(&tile));
+                    assert_eq!(map.get(&tile), hash_map.get(&tile));
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "a tile on the board")]
+    fn tiles_off_the_board_are_refused() {
+        TileSet::with_size(11, 11).insert(Coord { x: 11, y: 0 });
+    }
+}
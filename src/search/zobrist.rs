@@ -0,0 +1,105 @@
+use crate::types;
+
+const HEAD_TAG: u64 = 0x01;
+const SEGMENT_TAG: u64 = 0x02;
+const HEALTH_TAG: u64 = 0x03;
+const LENGTH_TAG: u64 = 0x04;
+const FOOD_TAG: u64 = 0x05;
+const HAZARD_TAG: u64 = 0x06;
+const HAZARD_ODDS_TAG: u64 = 0x07;
+
+/// # mix
+/// splitmix64 finalizer, turns structured input into a well distributed key
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    return z ^ (z >> 31);
+}
+
+fn pack(tile: &types::Coord) -> u64 {
+    return (tile.x as u16 as u64) | ((tile.y as u16 as u64) << 16);
+}
+
+/// # snake_key
+/// derives a stable key from a snake id so keys don't depend on the snake's position in `board.snakes`
+pub fn snake_key(id: &str) -> u64 {
+    // FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in id.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    return mix(hash);
+}
+
+/// # segment_key
+/// key for a body segment, the direction towards the head is included so the key set describes the body's shape and not just its tiles
+pub fn segment_key(snake: u64, tile: &types::Coord, toward_head: &types::Coord) -> u64 {
+    let dir = (toward_head.x + 1) as u64 | (((toward_head.y + 1) as u64) << 4);
+    return mix(snake ^ mix(SEGMENT_TAG | (pack(tile) << 8) | (dir << 40)));
+}
+
+pub fn head_key(snake: u64, tile: &types::Coord) -> u64 {
+    return mix(snake ^ mix(HEAD_TAG | (pack(tile) << 8)));
+}
+
+pub fn health_key(snake: u64, health: u8) -> u64 {
+    return mix(snake ^ mix(HEALTH_TAG | ((health as u64) << 8)));
+}
+
+pub fn length_key(snake: u64, length: u32) -> u64 {
+    return mix(snake ^ mix(LENGTH_TAG | ((length as u64) << 8)));
+}
+
+pub fn food_key(tile: &types::Coord) -> u64 {
+    return mix(FOOD_TAG | (pack(tile) << 8));
+}
+
+pub fn hazard_key(tile: &types::Coord) -> u64 {
+    return mix(HAZARD_TAG | (pack(tile) << 8));
+}
+
+pub fn hazard_odds_key(tile: &types::Coord, odds: f32) -> u64 {
+    return mix(HAZARD_ODDS_TAG | (pack(tile) << 8) | ((odds.to_bits() as u64) << 32));
+}
+
+/// # hash_snake
+/// hashes every feature of a single snake
+pub fn hash_snake(snake: &types::Battlesnake) -> u64 {
+    let key = snake_key(&snake.id);
+    let mut hash =
+        head_key(key, &snake.head) ^ health_key(key, snake.health) ^ length_key(key, snake.length);
+    for (i, segment) in snake.body.iter().enumerate() {
+        let toward_head = if i == 0 {
+            types::Coord { x: 0, y: 0 }
+        } else {
+            snake.body[i - 1] - *segment
+        };
+        hash ^= segment_key(key, segment, &toward_head);
+    }
+    return hash;
+}
+
+/// # hash_board
+/// computes the zobrist hash of a board from scratch
+/// ## Arguments:
+/// * board - the board to hash
+/// ## Returns:
+/// a 64 bit key identifying the snakes, food and hazards on the board
+pub fn hash_board(board: &types::Board) -> u64 {
+    let mut hash = 0;
+    for snake in &board.snakes {
+        hash ^= hash_snake(snake);
+    }
+    for food in &board.food {
+        hash ^= food_key(food);
+    }
+    for hazard in &board.hazards {
+        hash ^= hazard_key(hazard);
+    }
+    for (tile, odds) in &board.hazard_odds {
+        hash ^= hazard_odds_key(tile, *odds);
+    }
+    return hash;
+}
@@ -0,0 +1,38 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::env;
+
+/// set to a number to use the same seed for every game and turn, handy for reproducing a game locally
+pub(crate) const SEED_ENV: &str = "BATTLESNAKE_SEED";
+
+/// # derive_seed
+/// derives the seed for a single decision, the same game and turn always give the same seed
+/// ## Arguments:
+/// * game_id - the id of the game
+/// * turn - the turn we're deciding
+/// ## Returns:
+/// a 64 bit seed
+pub(crate) fn derive_seed(game_id: &str, turn: u32) -> u64 {
+    // FNV-1a over the game id then the turn
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in game_id.bytes().chain(turn.to_le_bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    return hash;
+}
+
+/// # seed
+/// the seed for a decision, `SEED_ENV` overrides the derived seed when it's set
+pub(crate) fn seed(game_id: &str, turn: u32) -> u64 {
+    return match env::var(SEED_ENV).ok().and_then(|value| value.parse().ok()) {
+        Some(fixed) => fixed,
+        None => derive_seed(game_id, turn),
+    };
+}
+
+/// # rng
+/// the random number generator every randomized part of a decision should draw from, so a decision can be replayed exactly
+pub(crate) fn rng(game_id: &str, turn: u32) -> StdRng {
+    return StdRng::seed_from_u64(seed(game_id, turn));
+}
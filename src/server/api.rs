@@ -0,0 +1,232 @@
+//! the game requests answered the same way whichever transport brings them in, the Rocket routes and the Lambda
+//! adapter parse the body and hand it over here
+
+use crate::capture::Capture;
+use crate::drain::Drain;
+use crate::gamelog::GameLogger;
+use crate::live::LiveFeed;
+use crate::logic::{self, Answer, Decision};
+use crate::metrics;
+use crate::opponents::OpponentTable;
+use crate::payload;
+use crate::replay::ReplayLogger;
+use crate::results::{GameRecord, ResultLog};
+use crate::stats::Stats;
+use crate::store::GameStore;
+use crate::types;
+use crate::server::{MoveConfig, Personality};
+use log::{error, info, warn};
+use rocket::futures::FutureExt;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// how a start request was taken
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Started {
+    Playing,
+    /// the server is draining for a restart and won't take new games
+    Draining,
+}
+
+/// # parse
+/// counts a request and reads its body
+/// ## Arguments:
+/// * endpoint - `start`, `move` or `end`
+/// * body - the body as it was sent
+/// ## Returns:
+/// the request, none if it can't be read even leniently. it still gets an answer, one the engine won't count as a
+/// timeout
+pub fn parse(stats: &Stats, endpoint: &'static str, body: &str) -> Option<types::GameState> {
+    stats.request(endpoint);
+    return payload::parse(endpoint, body);
+}
+
+/// # unreadable_move
+/// the answer to a move request `parse` couldn't read. any legal answer in time beats a timeout, which the engine
+/// counts against us and carries on with our last move anyway
+pub fn unreadable_move() -> serde_json::Value {
+    return serde_json::json!({ "move": "up" });
+}
+
+/// # index
+/// how a personality looks
+pub fn index(personality: &Personality, stats: &Stats) -> types::SnakeInfo {
+    stats.request("index");
+    return logic::info(&personality.appearance);
+}
+
+/// # start
+/// starts remembering a game and recognises the opponents in it
+/// ## Arguments:
+/// * route - the personality the game is for, empty for the root
+/// * state - the start request
+/// ## Returns:
+/// whether we're playing, a draining server refuses new games
+pub fn start(
+    route: &str,
+    state: &types::GameState,
+    store: &GameStore,
+    replays: &ReplayLogger,
+    gamelogs: &GameLogger,
+    stats: &Stats,
+    drain: &Drain,
+    opponents: &OpponentTable,
+) -> Started {
+    if drain.is_draining() {
+        warn!("refused game {}, draining for a restart", state.game.id);
+        return Started::Draining;
+    }
+    stats.game_started();
+    store.start(route, &state.game.id);
+    remember_opponents(route, state, store, opponents);
+    replays.log(route, "start", state, None, None);
+    gamelogs.start(route, state);
+    logic::start(&state.game, &state.turn, &state.board, &state.you);
+    return Started::Playing;
+}
+
+/// # remember_opponents
+/// keeps how to play around the opponents we recognise and how to read their shouts with the game's memory
+fn remember_opponents(
+    route: &str,
+    state: &types::GameState,
+    store: &GameStore,
+    opponents: &OpponentTable,
+) {
+    let stances = opponents.recognize(state);
+    let shout_readers = opponents.shout_readers(state);
+    store.write(route, &state.game.id, |memory| {
+        memory.stances = stances;
+        memory.shout_readers = shout_readers;
+    });
+}
+
+/// # play
+/// answers a move request in time. the fallback move is worked out before anything else and answers a turn that
+/// can't be played. a game we don't remember, because its start went to another server or we restarted since, is
+/// picked up from the move request: the opponents are recognised from it and the memory starts from this turn
+/// ## Arguments:
+/// * route - the personality the game is for, empty for the root
+/// * personality - the snake at that route
+/// * state - the move request
+/// * results - the results kept so far, a game's first move keeps back the latency buffer learned for the region
+/// ## Returns:
+/// our answer and how we got to it
+pub async fn play(
+    route: &str,
+    personality: &Personality,
+    state: types::GameState,
+    config: &MoveConfig,
+    store: &GameStore,
+    replays: &ReplayLogger,
+    gamelogs: &GameLogger,
+    live: &LiveFeed,
+    stats: &Stats,
+    opponents: &OpponentTable,
+    results: &ResultLog,
+) -> Answer {
+    // worked out before anything that could take long or fail, it's the answer when everything after it does
+    let started = Instant::now();
+    let fallback = logic::fallback_move(&state.board, &state.you);
+    let state = Arc::new(state);
+    if store.read(route, &state.game.id, |_| ()).is_none() {
+        info!(
+            "MOVE {}: picking up game {} without its start",
+            state.turn, state.game.id
+        );
+        store.start(route, &state.game.id);
+        remember_opponents(route, &state, store, opponents);
+    }
+    // the buffer the engine's reported latencies call for, the first move of a game starts from the region's
+    let buffer = store
+        .write(route, &state.game.id, |memory| {
+            return memory.budget.buffer_for(&state, || results.learned_buffer().unwrap_or(config.buffer));
+        })
+        .unwrap_or(config.buffer);
+    let strategy = Arc::clone(&personality.strategy);
+    let answer = AssertUnwindSafe(logic::move_in_time(
+        Arc::clone(&state),
+        fallback,
+        store,
+        route,
+        buffer,
+        &config.pool,
+        move |state, deadline| strategy(state, deadline),
+    ))
+    .catch_unwind()
+    .await;
+    let answer = answer.unwrap_or_else(|_| {
+        error!(
+            "MOVE {}: the move handler panicked in game {}, answering {}",
+            state.turn, state.game.id, fallback
+        );
+        metrics::PIPELINE_PANICS.add("handler", 1);
+        let decision = Decision::fallback(&state, fallback);
+        return Answer {
+            response: decision.response,
+            trace: decision.trace,
+            elapsed: started.elapsed(),
+        };
+    });
+
+    store.write(route, &state.game.id, |memory| memory.budget.answered(state.turn, answer.elapsed));
+    stats.moved(answer.elapsed, answer.trace.branch);
+    if gamelogs.logs_globally() {
+        if let Ok(trace) = serde_json::to_string(&answer.trace) {
+            info!("DECISION {}", trace);
+        }
+    }
+    gamelogs.turn(route, &state, &answer.trace);
+    live.publish(route, &state, &answer.trace);
+    replays.log(
+        route,
+        "move",
+        &state,
+        Some(&answer.response),
+        serde_json::to_value(&answer.trace).ok(),
+    );
+    return answer;
+}
+
+/// # end
+/// records how a game went and forgets it. the post-mortem covers the turns we remember, all of them unless the game
+/// was picked up part way through
+/// ## Arguments:
+/// * route - the personality the game was for, empty for the root
+/// * state - the end request
+pub fn end(
+    route: &str,
+    state: &types::GameState,
+    store: &GameStore,
+    replays: &ReplayLogger,
+    gamelogs: &GameLogger,
+    stats: &Stats,
+    opponents: &OpponentTable,
+    results: &ResultLog,
+    captures: &Capture,
+) {
+    // the survivors are still on the final board, a draw leaves nobody
+    let won = state
+        .board
+        .snakes
+        .iter()
+        .any(|snake| snake.id == state.you.id);
+    stats.game_finished(won);
+    replays.log(route, "end", state, None, None);
+    replays.finish(route, &state.game.id);
+    let memory = store.end(route, &state.game.id);
+    if let Some(memory) = &memory {
+        opponents.learn(&memory.habits);
+    }
+    let history = memory.as_ref().map(|memory| memory.history()).unwrap_or_default();
+    let record = logic::end(&state.game, &state.turn, &state.board, &state.you, &history);
+    let starting = history.first().map_or(&state.board, |turn| &turn.board);
+    let mut game = GameRecord::new(route, state, starting, &record);
+    game.buffer_ms = memory.as_ref().and_then(|memory| memory.budget.buffer()).map(|buffer| buffer.as_millis() as u64);
+    results.record(&game);
+    if let Some(memory) = &memory {
+        captures.game_over(route, memory, &record);
+    }
+    gamelogs.end(route, state, &record);
+}
@@ -0,0 +1,356 @@
+//! runs the snake as an AWS Lambda function behind a function URL or an API Gateway HTTP API, so nothing has to be kept
+//! warm between games. `serve` hands over to it when it finds itself in the Lambda runtime and the crate was built with
+//! the `lambda` feature. `lambda_runtime` takes the invocations, an event is one HTTP request to the same four game
+//! routes the server has, at the root and at every personality's route
+//!
+//! an instance answers one request at a time and can be frozen or replaced between any two, so the memory of a game
+//! only covers the turns the instance saw. a move for a game it didn't start is picked up from the move request, see
+//! `api::play`
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::Engine;
+use lambda_runtime::{service_fn, LambdaEvent};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::capture::Capture;
+use crate::config::StrategyConfig;
+use crate::drain::Drain;
+use crate::gamelog::GameLogger;
+use crate::live::LiveFeed;
+use crate::logic;
+use crate::opponents::OpponentTable;
+use crate::pool::ComputePool;
+use crate::replay::ReplayLogger;
+use crate::results::ResultLog;
+use crate::server::{api, personalities, MoveConfig, ServeArgs};
+use crate::stats::Stats;
+use crate::store::{self, GameStore};
+
+/// where the runtime API is, set by Lambda in every instance
+pub const RUNTIME_API_ENV: &str = "AWS_LAMBDA_RUNTIME_API";
+
+/// the HTTP request in an event, in the 2.0 payload format with the 1.0 names as aliases
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct HttpEvent {
+    #[serde(alias = "path", default)]
+    raw_path: String,
+    /// the 1.0 format's method, 2.0 puts it in the request context
+    http_method: Option<String>,
+    #[serde(default)]
+    request_context: RequestContext,
+    body: Option<String>,
+    #[serde(default)]
+    is_base64_encoded: bool,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct RequestContext {
+    http: Option<HttpContext>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HttpContext {
+    method: String,
+}
+
+/// the HTTP response an invocation answers with
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Value,
+    pub body: String,
+}
+
+impl HttpResponse {
+    fn json(status_code: u16, body: &impl Serialize) -> Self {
+        return HttpResponse {
+            status_code,
+            headers: json!({ "content-type": "application/json" }),
+            body: serde_json::to_string(body).unwrap_or_default(),
+        };
+    }
+
+    fn text(status_code: u16, body: &str) -> Self {
+        return HttpResponse {
+            status_code,
+            headers: json!({ "content-type": "text/plain; charset=utf-8" }),
+            body: body.to_string(),
+        };
+    }
+}
+
+/// everything the server keeps between requests, kept by an instance between its invocations
+pub struct Function {
+    config: MoveConfig,
+    store: Arc<GameStore>,
+    replays: ReplayLogger,
+    gamelogs: GameLogger,
+    live: LiveFeed,
+    stats: Stats,
+    drain: Drain,
+    opponents: OpponentTable,
+    results: ResultLog,
+    captures: Capture,
+}
+
+impl Function {
+    /// # new
+    /// a function hosting the personalities of a config, without the live feed and with nothing to drain
+    pub fn new(
+        config: MoveConfig,
+        store: Arc<GameStore>,
+        replays: ReplayLogger,
+        gamelogs: GameLogger,
+        opponents: OpponentTable,
+        results: ResultLog,
+        captures: Capture,
+    ) -> Self {
+        return Function {
+            config,
+            store,
+            replays,
+            gamelogs,
+            live: LiveFeed::disabled(),
+            stats: Stats::new(),
+            drain: Drain::new(Duration::ZERO, None),
+            opponents,
+            results,
+            captures,
+        };
+    }
+
+    /// # invoke
+    /// the handler `lambda_runtime` invokes, every event is answered, even one that isn't an HTTP request
+    /// ## Arguments:
+    /// * event - the invocation, its payload is the HTTP event
+    pub async fn invoke(&self, event: LambdaEvent<Value>) -> Result<HttpResponse, lambda_runtime::Error> {
+        return Ok(self.handle(event.payload).await);
+    }
+
+    /// # handle
+    /// answers an event as the server would answer its request
+    /// ## Arguments:
+    /// * event - the HTTP event the function was invoked with
+    /// ## Returns:
+    /// the response, a 400 when the event isn't an HTTP request and a 404 for anything but the four game routes
+    pub async fn handle(&self, event: Value) -> HttpResponse {
+        let event: HttpEvent = match serde_json::from_value(event) {
+            Ok(event) => event,
+            Err(err) => return HttpResponse::text(400, &format!("not an HTTP event: {}", err)),
+        };
+        let method = event
+            .request_context
+            .http
+            .map(|http| http.method)
+            .or(event.http_method)
+            .unwrap_or_default()
+            .to_uppercase();
+        let body = event.body.unwrap_or_default();
+        let body = if event.is_base64_encoded {
+            let decoded = base64::engine::general_purpose::STANDARD.decode(body.trim());
+            match decoded.ok().and_then(|bytes| String::from_utf8(bytes).ok()) {
+                Some(body) => body,
+                None => return HttpResponse::text(400, "the body isn't base64 encoded text"),
+            }
+        } else {
+            body
+        };
+        let path = event.raw_path.trim_matches('/');
+        let (route, endpoint) = match path.rsplit_once('/') {
+            Some((route, endpoint)) => (route, endpoint),
+            None if matches!(path, "start" | "move" | "end") => ("", path),
+            None => (path, ""),
+        };
+        let Some((route, personality)) = self.config.personalities.get_key_value(route) else {
+            return HttpResponse::text(404, "not found");
+        };
+        let route = route.as_str();
+
+        // the same answers as the Rocket routes, a body that can't be read still gets one
+        return match (method.as_str(), endpoint) {
+            ("GET", "") => HttpResponse::json(200, &api::index(personality, &self.stats)),
+            ("POST", "start") => {
+                let Some(state) = api::parse(&self.stats, "start", &body) else {
+                    return HttpResponse::text(200, "");
+                };
+                let started = api::start(
+                    route,
+                    &state,
+                    &self.store,
+                    &self.replays,
+                    &self.gamelogs,
+                    &self.stats,
+                    &self.drain,
+                    &self.opponents,
+                );
+                match started {
+                    api::Started::Playing => HttpResponse::text(200, ""),
+                    api::Started::Draining => HttpResponse::text(503, "draining for a restart"),
+                }
+            }
+            ("POST", "move") => {
+                let Some(state) = api::parse(&self.stats, "move", &body) else {
+                    return HttpResponse::json(200, &api::unreadable_move());
+                };
+                let answer = api::play(
+                    route,
+                    personality,
+                    state,
+                    &self.config,
+                    &self.store,
+                    &self.replays,
+                    &self.gamelogs,
+                    &self.live,
+                    &self.stats,
+                    &self.opponents,
+                    &self.results,
+                )
+                .await;
+                HttpResponse::json(200, &answer.response)
+            }
+            ("POST", "end") => {
+                if let Some(state) = api::parse(&self.stats, "end", &body) {
+                    api::end(
+                        route,
+                        &state,
+                        &self.store,
+                        &self.replays,
+                        &self.gamelogs,
+                        &self.stats,
+                        &self.opponents,
+                        &self.results,
+                        &self.captures,
+                    );
+                }
+                HttpResponse::text(200, "")
+            }
+            _ => HttpResponse::text(404, "not found"),
+        };
+    }
+}
+
+/// # serve
+/// the `serve` command inside Lambda: hands the function to `lambda_runtime`, which takes invocations until the
+/// instance is shut down
+/// ## Arguments:
+/// * args - the parsed arguments, the port is Lambda's business
+/// ## Returns:
+/// the exit code, 1 when the config is bad or the runtime API can't be reached
+pub fn serve(args: &ServeArgs) -> i32 {
+    // an instance that exits before its first invocation is reported by Lambda as failing to initialise
+    let function = match function(args) {
+        Ok(function) => function,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1;
+        }
+    };
+    let runtime = match rocket::tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("couldn't start the async runtime: {}", err);
+            return 1;
+        }
+    };
+    info!("Serving Lambda invocations");
+    let function = &function;
+    if let Err(err) = runtime.block_on(lambda_runtime::run(service_fn(|event| function.invoke(event)))) {
+        error!("couldn't take invocations from the runtime API: {}", err);
+        return 1;
+    }
+    return 0;
+}
+
+/// # function
+/// the function `serve` would set up as a server
+fn function(args: &ServeArgs) -> Result<Function, String> {
+    let strategy = StrategyConfig::load(&args.config)?;
+    let store = Arc::new(GameStore::new(store::GAME_TTL));
+    let config = MoveConfig {
+        personalities: personalities(&strategy, &args.profile, &store),
+        buffer: logic::latency_buffer(),
+        pool: ComputePool::from_env(),
+    };
+    return Ok(Function::new(
+        config,
+        store,
+        ReplayLogger::from_env(),
+        GameLogger::from_env(store::GAME_TTL),
+        OpponentTable::from_env(),
+        ResultLog::from_env(),
+        Capture::from_env(),
+    ));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    fn event(name: &str) -> Value {
+        let path = format!("testdata/lambda/{}.json", name);
+        return serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    }
+
+    fn sample() -> Function {
+        let store = Arc::new(GameStore::new(store::GAME_TTL));
+        let config = MoveConfig {
+            personalities: personalities(&StrategyConfig::default(), "default", &store),
+            buffer: Duration::from_millis(120),
+            pool: ComputePool::new(2, 2),
+        };
+        return Function::new(
+            config,
+            store,
+            ReplayLogger::disabled(),
+            GameLogger::disabled(),
+            OpponentTable::disabled(),
+            ResultLog::disabled(),
+            Capture::disabled(),
+        );
+    }
+
+    #[rocket::async_test]
+    async fn move_event_is_answered() {
+        let function = sample();
+        // the instance never saw the start, the game is picked up from the move
+        let invocation = LambdaEvent::new(event("move"), lambda_runtime::Context::default());
+        let response = function.invoke(invocation).await.unwrap();
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.headers["content-type"], "application/json");
+        let body: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["move"], "right");
+        assert!(function.store.read("", "corner", |_| ()).is_some());
+    }
+
+    #[rocket::async_test]
+    async fn events_reach_every_route() {
+        let function = sample();
+        let response = function.handle(event("start")).await;
+        assert_eq!(response.status_code, 200);
+        assert!(function.store.read("defensive", "corner", |_| ()).is_some());
+
+        let mut index = event("move");
+        index["rawPath"] = json!("/defensive");
+        index["requestContext"]["http"]["method"] = json!("GET");
+        let response = function.handle(index).await;
+        let info: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(info["apiversion"], "1");
+
+        let mut end = event("move");
+        end["rawPath"] = json!("/defensive/end");
+        assert_eq!(function.handle(end).await.status_code, 200);
+        assert!(function.store.read("defensive", "corner", |_| ()).is_none());
+
+        let mut unknown = event("move");
+        unknown["rawPath"] = json!("/reckless/move");
+        assert_eq!(function.handle(unknown).await.status_code, 404);
+        assert_eq!(function.handle(json!([])).await.status_code, 400);
+    }
+}
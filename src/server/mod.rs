@@ -0,0 +1,1699 @@
+//! the HTTP server and the command line around it, everything `run` needs to take a process from its arguments to its
+//! exit code
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{env, io, vec};
+
+use clap::{Parser, Subcommand};
+use log::info;
+use rocket::fairing::AdHoc;
+use rocket::{Build, Rocket};
+
+use capture::Capture;
+use config::StrategyConfig;
+use drain::Drain;
+use gamelog::GameLogger;
+use live::LiveFeed;
+use opponents::OpponentTable;
+use pool::ComputePool;
+use replay::ReplayLogger;
+use results::ResultLog;
+use stats::Stats;
+use store::GameStore;
+
+mod api;
+#[cfg(feature = "lambda")]
+mod lambda;
+mod routes;
+#[cfg(test)]
+mod rules_cli;
+
+use crate::{
+    arena, bench, capture, config, diff, drain, fetch, gamelog, live, logic, opponents, pool, replay, results, spans,
+    stats, store, tune, types, visualize,
+};
+
+// API and Response Objects
+// See https://docs.battlesnake.com/api
+
+/// computes a move for a request, it should be ready by the deadline it's given. a frame it gives up on is answered
+/// with the fallback move
+type Strategy =
+    Arc<dyn Fn(&types::GameState, Instant) -> Result<logic::Decision, logic::DecisionError> + Send + Sync>;
+
+/// a snake the server hosts, how it plays and how it looks
+struct Personality {
+    strategy: Strategy,
+    appearance: types::SnakeInfo,
+    /// what the strategy plays with, reported by /version
+    profile: logic::StrategyProfile,
+}
+
+/// the snakes the server hosts, how much of the timeout /move keeps back for the network and where the moves are
+/// computed. every personality is mounted at `/<route>`, the one with the empty route at the root where existing
+/// registrations point
+struct MoveConfig {
+    personalities: HashMap<String, Personality>,
+    buffer: Duration,
+    pool: ComputePool,
+}
+
+/// when the server started
+struct Started(Instant);
+
+/// the snake server and the tools around it
+#[derive(Parser, Debug)]
+#[command(name = "battlesnake", version)]
+struct Cli {
+    /// `serve` when it's left out, which is how deployments start the server
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// serves every personality over HTTP
+    Serve(ServeArgs),
+    /// re-runs a logged game through the current code and compares the moves
+    Replay(replay::ReplayArgs),
+    /// lists the turns of the replays and fixtures two profiles, or a profile and the logged moves, decide differently
+    DiffCorpus(diff::DiffArgs),
+    /// plays complete games between policies in process
+    Arena(arena::ArenaArgs),
+    /// tunes the move weights by playing candidates against the defaults in the arena
+    Tune(tune::TuneArgs),
+    /// times the move pipeline on a corpus of positions
+    Bench(bench::BenchArgs),
+    /// downloads a finished game from the engine as a replay
+    FetchGame(fetch::FetchArgs),
+    /// renders a logged game as an HTML page to step through
+    Visualize(visualize::VisualizeArgs),
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "serve")]
+struct ServeArgs {
+    /// the port to listen on, ahead of `PORT` and `ROCKET_PORT`
+    #[arg(long)]
+    port: Option<u16>,
+    /// the strategy profile the snake at the root plays, `default` or one of the personalities
+    #[arg(
+        long,
+        default_value = "default",
+        value_parser = |name: &str| match logic::profile(name) {
+            Some(_) => Ok(name.to_string()),
+            None => Err(format!("unknown profile {}", name)),
+        }
+    )]
+    profile: String,
+    /// the strategy tunables, a file that doesn't exist leaves them at their defaults
+    #[arg(long, env = config::CONFIG_ENV, default_value = config::DEFAULT_CONFIG)]
+    config: PathBuf,
+}
+
+/// # run
+/// the `battlesnake` command: parses the arguments and runs the command they name, the server when there isn't one
+/// ## Returns:
+/// the exit code
+pub fn run() -> i32 {
+    let cli = Cli::parse();
+    let command = cli
+        .command
+        .unwrap_or_else(|| Command::Serve(ServeArgs::parse_from(["serve"])));
+    // the tools print their results, the server logs what it's doing
+    let level = if matches!(command, Command::Serve(_)) { "info" } else { "warn" };
+    init(level);
+    return match command {
+        Command::Serve(args) => serve(&args),
+        Command::Replay(args) => replay::run(&args, &mut io::stdout()),
+        Command::DiffCorpus(args) => diff::run(&args, &mut io::stdout()),
+        Command::Arena(args) => arena::run(&args, &mut io::stdout()),
+        Command::Tune(args) => tune::run(&args, &mut io::stdout()),
+        Command::Bench(args) => bench::run(&args, &mut io::stdout()),
+        Command::FetchGame(args) => fetch::run(&args, &mut io::stdout()),
+        Command::Visualize(args) => visualize::run(&args, &mut io::stdout()),
+    };
+}
+
+/// # init
+/// the setup every command shares, the environment the hosting service gives us and logging
+/// ## Arguments:
+/// * level - the log level when `RUST_LOG` isn't set
+fn init(level: &str) {
+    // Lots of web hosting services expect you to bind to the port specified by the `PORT`
+    // environment variable. However, Rocket looks at the `ROCKET_PORT` environment variable.
+    // If we find a value for `PORT`, we set `ROCKET_PORT` to that value.
+    if let Ok(port) = env::var("PORT") {
+        env::set_var("ROCKET_PORT", &port);
+    }
+
+    // We default to the command's level of logging. But if the `RUST_LOG` environment variable is set,
+    // we keep that value instead.
+    if env::var("RUST_LOG").is_err() {
+        env::set_var("RUST_LOG", level);
+    }
+
+    spans::init();
+}
+
+/// # serve
+/// the `serve` command, runs the server until it's shut down. built with the `lambda` feature and run inside Lambda, it
+/// takes Lambda invocations instead
+/// ## Returns:
+/// the exit code, 1 when the config is bad or the server fails
+fn serve(args: &ServeArgs) -> i32 {
+    #[cfg(feature = "lambda")]
+    if env::var_os(lambda::RUNTIME_API_ENV).is_some() {
+        return lambda::serve(args);
+    }
+    let rocket = match rocket(args) {
+        Ok(rocket) => rocket,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1;
+        }
+    };
+    if let Err(err) = rocket::execute(rocket.launch()) {
+        eprintln!("{}", err);
+        return 1;
+    }
+    return 0;
+}
+
+/// # rocket
+/// the server as `serve` starts it
+/// ## Returns:
+/// the server, or why its config can't be used
+fn rocket(args: &ServeArgs) -> Result<Rocket<Build>, String> {
+    info!("Starting Battlesnake Server...");
+
+    let strategy = StrategyConfig::load(&args.config)?;
+    let store = Arc::new(GameStore::new(store::GAME_TTL));
+    let config = MoveConfig {
+        personalities: personalities(&strategy, &args.profile, &store),
+        buffer: logic::latency_buffer(),
+        pool: ComputePool::from_env(),
+    };
+    let gamelogs = GameLogger::from_env(store::GAME_TTL);
+    let rocket = build(
+        config,
+        store,
+        ReplayLogger::from_env(),
+        gamelogs,
+        Drain::from_env(),
+        OpponentTable::from_env(),
+        ResultLog::from_env(),
+        Capture::from_env(),
+        LiveFeed::from_env(),
+    );
+    let rocket = if env::var(routes::DEBUG_ENV).is_ok_and(|debug| debug == "1") {
+        with_debug(rocket)
+    } else {
+        rocket
+    };
+    return Ok(match args.port {
+        Some(port) => rocket.configure(rocket::Config::figment().merge(("port", port))),
+        None => rocket,
+    });
+}
+
+/// # with_debug
+/// mounts `/debug/move` next to every personality's game routes
+fn with_debug(mut rocket: Rocket<Build>) -> Rocket<Build> {
+    let routes: Vec<String> = rocket
+        .state::<MoveConfig>()
+        .map(|config| config.personalities.keys().cloned().collect())
+        .unwrap_or_default();
+    for route in routes {
+        rocket = rocket.mount(format!("/{}", route), routes::debug_routes());
+    }
+    return rocket;
+}
+
+/// # personalities
+/// a snake at the root and one for each of `logic::PROFILES` at its route, dressed from the environment
+/// ## Arguments:
+/// * strategy - the tunables every personality plays with
+/// * root - the name of the profile the snake at the root plays
+/// * store - where the snakes find how to play around the opponents they recognised, and how to read their shouts
+fn personalities(
+    strategy: &StrategyConfig,
+    root: &str,
+    store: &Arc<GameStore>,
+) -> HashMap<String, Personality> {
+    let mut personalities = HashMap::new();
+    let routes = [("", root)]
+        .into_iter()
+        .chain(logic::PROFILES.map(|(route, _)| (route, route)));
+    for (route, name) in routes {
+        let profile = strategy.profile(name).unwrap_or_default();
+        let appearance = logic::personality_info(route, |key| env::var(key).ok());
+        info!(
+            "Snake at /{}: author {}, color {}, head {}, tail {}",
+            route, appearance.author, appearance.color, appearance.head, appearance.tail
+        );
+        let store = Arc::clone(store);
+        let strategy: Strategy = Arc::new(move |state, deadline| {
+            let (stances, announced, phase, pace) = store
+                .write(route, &state.game.id, |memory| {
+                    let phase = memory.phase.observe(&state.board, state.turn);
+                    let pace = memory.pace.observe(&state.board, &state.you.id, state.turn);
+                    let announced = opponents::announced(&state.board, &memory.shout_readers);
+                    (memory.stances.clone(), announced, Some(phase), pace)
+                })
+                .unwrap_or_default();
+            let profile = profile.paced(pace);
+            let (plan, alongside) = store
+                .read(route, &state.game.id, |memory| {
+                    (memory.cache.escape_for(state.turn), memory.cache.alongside_for(state.turn))
+                })
+                .unwrap_or_default();
+            let decision = logic::get_move_against(
+                &state.game,
+                &state.turn,
+                &state.board,
+                &state.you,
+                &profile,
+                &stances,
+                &announced,
+                phase,
+                &plan,
+                alongside.as_ref(),
+                deadline,
+            )?;
+            // the rest of the way out, to keep to on the next turn, and who's alongside us to see if they still are
+            store.write(route, &state.game.id, |memory| {
+                memory.cache.commit_escape(state.turn, decision.trace.escape.get(1..).unwrap_or_default());
+                memory.cache.commit_alongside(state.turn, decision.trace.alongside.clone());
+            });
+            Ok(decision)
+        });
+        personalities.insert(route.to_string(), Personality { strategy, appearance, profile });
+    }
+    return personalities;
+}
+
+fn build(
+    config: MoveConfig,
+    store: Arc<GameStore>,
+    replays: ReplayLogger,
+    gamelogs: GameLogger,
+    drain: Drain,
+    opponents: OpponentTable,
+    results: ResultLog,
+    captures: Capture,
+    live: LiveFeed,
+) -> Rocket<Build> {
+    let routes: Vec<String> = config.personalities.keys().cloned().collect();
+    let live_routes = if live.enabled() { routes::live_routes() } else { vec![] };
+    let mut rocket = rocket::build()
+        .manage(config)
+        .manage(store)
+        .manage(replays)
+        .manage(gamelogs)
+        .manage(Stats::new())
+        .manage(Started(Instant::now()))
+        .manage(drain)
+        .manage(opponents)
+        .manage(results)
+        .manage(captures)
+        .manage(live)
+        .attach(AdHoc::on_response("Server ID Middleware", |_, res| {
+            Box::pin(async move {
+                res.set_raw_header("Server", "battlesnake/github/starter-snake-rust");
+            })
+        }))
+        .attach(AdHoc::on_shutdown("Flush logs", |rocket| {
+            Box::pin(async move {
+                if let Some(replays) = rocket.state::<ReplayLogger>() {
+                    replays.flush();
+                }
+                if let Some(gamelogs) = rocket.state::<GameLogger>() {
+                    gamelogs.flush();
+                }
+            })
+        }))
+        .mount("/", routes::server_routes());
+    rocket = rocket.mount("/", live_routes);
+    for route in routes {
+        rocket = rocket.mount(format!("/{}", route), routes::game_routes());
+    }
+    return rocket;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{budget, trace};
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::blocking::Client;
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+    use std::process;
+    use std::sync::Mutex;
+    use std::thread;
+
+    // our head is in the top left corner with our neck below it, right is the only legal move
+    const CORNER_DATA: &str = r#"
+      {
+        "game": {
+          "id": "slow",
+          "ruleset": { "name": "standard", "version": "v1.0.0" },
+          "timeout": 300
+        },
+        "turn": 12,
+        "board": {
+          "height": 11,
+          "width": 11,
+          "food": [],
+          "snakes": [
+            {
+              "id": "me",
+              "name": "me",
+              "health": 90,
+              "body": [
+                { "x": 0, "y": 10 },
+                { "x": 0, "y": 9 },
+                { "x": 0, "y": 8 }
+              ],
+              "latency": 0,
+              "head": { "x": 0, "y": 10 },
+              "length": 3,
+              "shout": ""
+            }
+          ],
+          "hazards": []
+        },
+        "you": {
+          "id": "me",
+          "name": "me",
+          "health": 90,
+          "body": [
+            { "x": 0, "y": 10 },
+            { "x": 0, "y": 9 },
+            { "x": 0, "y": 8 }
+          ],
+          "latency": 0,
+          "head": { "x": 0, "y": 10 },
+          "length": 3,
+          "shout": ""
+        }
+      }
+    "#;
+
+    // from an arena game, we're the shorter snake at (5, 9). the aggressive profile heads left for the food at (1, 9)
+    // in front of the other snake, the defensive one turns away from it
+    const PROFILED_DATA: &str = r#"
+      {
+        "game": {
+          "id": "shared",
+          "ruleset": { "name": "standard" },
+          "timeout": 500
+        },
+        "turn": 12,
+        "board": {
+          "height": 11,
+          "width": 11,
+          "food": [
+            { "x": 1, "y": 9 },
+            { "x": 9, "y": 0 }
+          ],
+          "snakes": [
+            {
+              "id": "them",
+              "name": "them",
+              "health": 94,
+              "body": [
+                { "x": 2, "y": 8 },
+                { "x": 3, "y": 8 },
+                { "x": 3, "y": 7 },
+                { "x": 4, "y": 7 },
+                { "x": 4, "y": 6 }
+              ],
+              "head": { "x": 2, "y": 8 },
+              "length": 5,
+              "shout": ""
+            },
+            {
+              "id": "me",
+              "name": "me",
+              "health": 90,
+              "body": [
+                { "x": 5, "y": 9 },
+                { "x": 5, "y": 8 },
+                { "x": 6, "y": 8 },
+                { "x": 6, "y": 7 }
+              ],
+              "head": { "x": 5, "y": 9 },
+              "length": 4,
+              "shout": ""
+            }
+          ],
+          "hazards": []
+        },
+        "you": {
+          "id": "me",
+          "name": "me",
+          "health": 90,
+          "body": [
+            { "x": 5, "y": 9 },
+            { "x": 5, "y": 8 },
+            { "x": 6, "y": 8 },
+            { "x": 6, "y": 7 }
+          ],
+          "head": { "x": 5, "y": 9 },
+          "length": 4,
+          "shout": ""
+        }
+      }
+    "#;
+
+    const ADMIN_TOKEN: &str = "let-me-in";
+
+    fn decision(mv: &str) -> Result<logic::Decision, logic::DecisionError> {
+        let mut trace = trace::DecisionTrace::new("", 0);
+        trace.chosen = mv.to_string();
+        trace.branch = logic::Branch::AStar;
+        return Ok(logic::Decision::new(trace));
+    }
+
+    fn personality(strategy: Strategy, route: &str) -> Personality {
+        return Personality {
+            strategy,
+            appearance: logic::personality_info(route, |_| None),
+            profile: logic::profile(route).unwrap_or_default(),
+        };
+    }
+
+    // a client without replays or game logs
+    fn tracked(config: MoveConfig, store: Arc<GameStore>, drain: Drain) -> Client {
+        let rocket = build(
+            config,
+            store,
+            ReplayLogger::disabled(),
+            GameLogger::disabled(),
+            drain,
+            OpponentTable::disabled(),
+            ResultLog::disabled(),
+            Capture::disabled(),
+            LiveFeed::disabled(),
+        );
+        return Client::tracked(rocket).unwrap();
+    }
+
+    fn client_for(personalities: HashMap<String, Personality>, store: Arc<GameStore>) -> Client {
+        let config = MoveConfig {
+            personalities,
+            buffer: Duration::from_millis(120),
+            pool: ComputePool::new(4, 4),
+        };
+        let drain = Drain::new(Duration::from_secs(60), Some(ADMIN_TOKEN.to_string()));
+        return tracked(config, store, drain);
+    }
+
+    fn client(strategy: Strategy, store: Arc<GameStore>) -> Client {
+        let personalities = HashMap::from([("".to_string(), personality(strategy, ""))]);
+        return client_for(personalities, store);
+    }
+
+    fn post_move(strategy: Strategy) -> (Value, Duration) {
+        let client = client(strategy, Arc::new(GameStore::new(store::GAME_TTL)));
+        let start = Instant::now();
+        let response = client
+            .post("/move")
+            .header(ContentType::JSON)
+            .body(CORNER_DATA)
+            .dispatch();
+        let elapsed = start.elapsed();
+        return (response.into_json().unwrap(), elapsed);
+    }
+
+    #[test]
+    fn slow_strategy_still_answers_in_time() {
+        let (response, elapsed) = post_move(Arc::new(|_, _| {
+            thread::sleep(Duration::from_secs(2));
+            decision("up")
+        }));
+        assert!(elapsed < Duration::from_millis(300), "took {:?}", elapsed);
+        assert_eq!(response["move"], "right");
+    }
+
+    #[test]
+    fn panicking_strategy_answers_the_fallback() {
+        let (response, _) = post_move(Arc::new(|_, _| panic!("a strategy bug")));
+        let state: types::GameState = serde_json::from_str(CORNER_DATA).unwrap();
+        assert_eq!(response["move"], logic::fallback_move(&state.board, &state.you));
+        assert_eq!(response["move"], "right");
+    }
+
+    #[test]
+    fn fast_strategy_is_answered() {
+        let (response, _) = post_move(Arc::new(|_, deadline| {
+            assert!(deadline > Instant::now());
+            decision("down")
+        }));
+        assert_eq!(response["move"], "down");
+    }
+
+    #[test]
+    fn reported_latency_moves_the_deadline_in() {
+        // the time the strategy has left on each turn
+        let budgets = Arc::new(Mutex::new(vec![]));
+        let seen = Arc::clone(&budgets);
+        let client = client(
+            Arc::new(move |_, deadline| {
+                seen.lock().unwrap().push(deadline.saturating_duration_since(Instant::now()));
+                decision("down")
+            }),
+            Arc::new(GameStore::new(store::GAME_TTL)),
+        );
+        let mut state: types::GameState = serde_json::from_str(CORNER_DATA).unwrap();
+        state.game.timeout = 500;
+        for turn in 0..12 {
+            // we answer in a millisecond or two and the engine keeps reporting 450
+            state.turn = turn;
+            state.you.latency = (turn > 0).then_some(450);
+            let path = if turn == 0 { "/start" } else { "/move" };
+            let body = serde_json::to_string(&state).unwrap();
+            assert_eq!(client.post(path).header(ContentType::JSON).body(body).dispatch().status(), Status::Ok);
+        }
+        let budgets = budgets.lock().unwrap();
+        // the first move only knows the configured 120ms buffer, by the last the buffer is up to its cap
+        assert!(budgets[0] > Duration::from_millis(300), "{:?}", budgets);
+        assert!(budgets.windows(2).all(|pair| pair[1] <= pair[0] + Duration::from_millis(5)), "{:?}", budgets);
+        let last = *budgets.last().unwrap();
+        assert!(last <= Duration::from_millis(500) - budget::MAX_BUFFER, "{:?}", budgets);
+    }
+
+    #[test]
+    fn interleaved_games_keep_separate_memories() {
+        let store = Arc::new(GameStore::new(store::GAME_TTL));
+        // each game answers with a move named after it so a mixed up memory shows
+        let client = client(
+            Arc::new(|state, _| decision(if state.game.id == "a" { "right" } else { "down" })),
+            Arc::clone(&store),
+        );
+        let request = |path: &str, game_id: &str, turn: u32| {
+            let body = CORNER_DATA
+                .replace("\"slow\"", &format!("\"{}\"", game_id))
+                .replace("\"turn\": 12", &format!("\"turn\": {}", turn));
+            let status = client
+                .post(path.to_string())
+                .header(ContentType::JSON)
+                .body(body)
+                .dispatch()
+                .status();
+            assert_eq!(status, Status::Ok);
+        };
+        request("/start", "a", 0);
+        request("/start", "b", 0);
+        request("/move", "a", 1);
+        request("/move", "b", 1);
+        request("/move", "a", 2);
+
+        store
+            .read("", "a", |memory| {
+                assert!(memory.states.iter().all(|state| state.game.id == "a"));
+                assert_eq!(memory.moves, ["right", "right"]);
+            })
+            .unwrap();
+        store
+            .read("", "b", |memory| {
+                assert!(memory.states.iter().all(|state| state.game.id == "b"));
+                assert_eq!(memory.moves, ["down"]);
+            })
+            .unwrap();
+
+        request("/end", "a", 3);
+        assert!(store.read("", "a", |_| ()).is_none());
+        assert_eq!(store.read("", "b", |memory| memory.states.len()), Some(1));
+        request("/end", "b", 2);
+        assert!(store.read("", "b", |_| ()).is_none());
+    }
+
+    #[test]
+    fn stats_count_a_won_and_a_lost_game() {
+        let client = client(
+            Arc::new(|_, _| decision("right")),
+            Arc::new(GameStore::new(store::GAME_TTL)),
+        );
+        let request = |path: &str, game_id: &str, body: &str| {
+            let status = client
+                .post(path.to_string())
+                .header(ContentType::JSON)
+                .body(body.replace("\"slow\"", &format!("\"{}\"", game_id)))
+                .dispatch()
+                .status();
+            assert_eq!(status, Status::Ok);
+        };
+        // we're missing from the final board of the lost game
+        let mut lost: Value = serde_json::from_str(CORNER_DATA).unwrap();
+        lost["board"]["snakes"] = json!([]);
+        let lost = lost.to_string();
+        assert_eq!(client.get("/").dispatch().status(), Status::Ok);
+        request("/start", "won", CORNER_DATA);
+        request("/move", "won", CORNER_DATA);
+        request("/move", "won", CORNER_DATA);
+        request("/end", "won", CORNER_DATA);
+        request("/start", "lost", CORNER_DATA);
+        request("/move", "lost", CORNER_DATA);
+        request("/end", "lost", &lost);
+
+        let stats: Value = client.get("/stats").dispatch().into_json().unwrap();
+        assert_eq!(
+            stats["games"],
+            json!({ "started": 2, "finished": 2, "wins": 1, "losses": 1 })
+        );
+        assert_eq!(
+            stats["requests"],
+            json!({ "index": 1, "start": 2, "move": 3, "end": 2, "stats": 1 })
+        );
+        assert_eq!(stats["branches"], json!({ "a_star": 3 }));
+        assert!(stats["move_latency_ms"]["p50"].is_u64());
+        let latency = &stats["move_latency_ms"];
+        assert!(latency["p50"].as_u64() <= latency["p95"].as_u64());
+        assert!(latency["p95"].as_u64() <= latency["max"].as_u64());
+    }
+
+    #[test]
+    fn results_outlive_a_restart() {
+        let path = env::temp_dir().join(format!("battlesnake-restart-{}.jsonl", process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server = || {
+            let personalities = HashMap::from([("".to_string(), personality(Arc::new(|_, _| decision("right")), ""))]);
+            let config = MoveConfig {
+                personalities,
+                buffer: Duration::from_millis(120),
+                pool: ComputePool::new(1, 1),
+            };
+            let rocket = build(
+                config,
+                Arc::new(GameStore::new(store::GAME_TTL)),
+                ReplayLogger::disabled(),
+                GameLogger::disabled(),
+                Drain::new(Duration::from_secs(60), None),
+                OpponentTable::disabled(),
+                ResultLog::load(path.clone()),
+                Capture::disabled(),
+                LiveFeed::disabled(),
+            );
+            return Client::tracked(rocket).unwrap();
+        };
+        let mut lost: Value = serde_json::from_str(CORNER_DATA).unwrap();
+        // someone else is left on the final board of the lost game
+        lost["board"]["snakes"][0]["id"] = json!("them");
+        let lost = lost.to_string();
+        let client = server();
+        for (game_id, end) in [("won", CORNER_DATA), ("lost", lost.as_str())] {
+            for (path, body) in [("/start", CORNER_DATA), ("/move", CORNER_DATA), ("/end", end)] {
+                let body = body.replace("\"slow\"", &format!("\"{}\"", game_id));
+                let status = client.post(path).header(ContentType::JSON).body(body).dispatch().status();
+                assert_eq!(status, Status::Ok);
+            }
+        }
+        drop(client);
+
+        // the counters start over, the results carry on
+        let restarted = server();
+        let stats: Value = restarted.get("/stats").dispatch().into_json().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(stats["games"]["finished"], 0);
+        let results = &stats["results"];
+        assert_eq!(results["total"], json!({ "games": 2, "wins": 1, "draws": 0, "losses": 1, "win_rate": 0.5 }));
+        assert_eq!(results["by_version"][env!("CARGO_PKG_VERSION")]["games"], 2);
+        assert_eq!(results["by_profile"][""]["games"], 2);
+        assert_eq!(results["deaths"].as_object().unwrap().values().filter_map(Value::as_u64).sum::<u64>(), 1);
+    }
+
+    #[test]
+    fn lost_games_are_captured_as_fixtures() {
+        let dir = env::temp_dir().join(format!("battlesnake-captures-{}", process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let personalities = HashMap::from([("".to_string(), personality(Arc::new(|_, _| decision("right")), ""))]);
+        let config = MoveConfig {
+            personalities,
+            buffer: Duration::from_millis(120),
+            pool: ComputePool::new(1, 1),
+        };
+        let rocket = build(
+            config,
+            Arc::new(GameStore::new(store::GAME_TTL)),
+            ReplayLogger::disabled(),
+            GameLogger::disabled(),
+            Drain::new(Duration::from_secs(60), None),
+            OpponentTable::disabled(),
+            ResultLog::disabled(),
+            Capture::new(dir.clone(), 1024 * 1024),
+            LiveFeed::disabled(),
+        );
+        let client = Client::tracked(rocket).unwrap();
+        let mut lost: Value = serde_json::from_str(CORNER_DATA).unwrap();
+        lost["board"]["snakes"][0]["id"] = json!("them");
+        let lost = lost.to_string();
+        for (game_id, end) in [("won", CORNER_DATA), ("lost", lost.as_str())] {
+            let request = |path: &str, turn: u32, body: &str| {
+                let body = body
+                    .replace("\"slow\"", &format!("\"{}\"", game_id))
+                    .replace("\"turn\": 12", &format!("\"turn\": {}", turn));
+                let status = client.post(path).header(ContentType::JSON).body(body).dispatch().status();
+                assert_eq!(status, Status::Ok);
+            };
+            request("/start", 0, CORNER_DATA);
+            for turn in 1..=7 {
+                request("/move", turn, CORNER_DATA);
+            }
+            request("/end", 8, end);
+        }
+
+        let captured: Vec<PathBuf> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+        // only the lost game, with the last five turns we remember
+        assert_eq!(captured.len(), 1);
+        let capture = &captured[0];
+        assert!(capture.file_name().unwrap().to_str().unwrap().ends_with("_lost"));
+        let mut files: Vec<String> = std::fs::read_dir(capture)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["capture.json", "turn_3.json", "turn_4.json", "turn_5.json", "turn_6.json", "turn_7.json"]);
+        for turn in 3..=7 {
+            let state: types::GameState =
+                serde_json::from_str(&std::fs::read_to_string(capture.join(format!("turn_{}.json", turn))).unwrap())
+                    .unwrap();
+            assert_eq!((state.game.id.as_str(), state.turn), ("lost", turn));
+        }
+        let summary: Value = serde_json::from_str(&std::fs::read_to_string(capture.join("capture.json")).unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(summary["moves"]["7"], "right");
+        assert_eq!(summary["postmortem"]["result"], "loss");
+    }
+
+    #[test]
+    fn serve_is_the_default_command() {
+        let cli = Cli::try_parse_from(["battlesnake"]).unwrap();
+        assert!(cli.command.is_none());
+
+        let cli = Cli::try_parse_from(["battlesnake", "serve", "--port", "9000", "--profile", "defensive"]).unwrap();
+        let Some(Command::Serve(args)) = cli.command else {
+            panic!("{:?}", cli.command);
+        };
+        assert_eq!(args.profile, "defensive");
+        let client = Client::tracked(rocket(&args).unwrap()).unwrap();
+        assert_eq!(client.rocket().config().port, 9000);
+        let info: Value = client.get("/").dispatch().into_json().unwrap();
+        assert_eq!(info["apiversion"], "1");
+
+        let version: Value = client.get("/version").dispatch().into_json().unwrap();
+        assert_eq!(version["profiles"][""], version["profiles"]["defensive"]);
+
+        assert!(Cli::try_parse_from(["battlesnake", "serve", "--profile", "reckless"]).is_err());
+        // a bad config stops the server before it starts
+        let bad = env::temp_dir().join(format!("battlesnake-serve-{}.toml", process::id()));
+        std::fs::write(&bad, "box_threshold = 2.0").unwrap();
+        let args = ServeArgs::parse_from(["serve", "--config", bad.to_str().unwrap()]);
+        let err = rocket(&args).err().unwrap();
+        std::fs::remove_file(&bad).unwrap();
+        assert!(err.contains("box_threshold must be between 0 and 1"), "{}", err);
+    }
+
+    #[test]
+    fn recognised_opponents_reach_the_pipeline() {
+        // the other snake in the fixture is known to leave contested food alone
+        let path = env::temp_dir().join(format!("battlesnake-known-{}.json", process::id()));
+        let known = r#"{ "snake mTOl1": { "games": 10, "food_chances": 10, "food_contests": 1 } }"#;
+        std::fs::write(&path, known).unwrap();
+        let opponents = OpponentTable::load(path.clone());
+        std::fs::remove_file(&path).unwrap();
+
+        let store = Arc::new(GameStore::new(store::GAME_TTL));
+        let config = MoveConfig {
+            personalities: personalities(&StrategyConfig::default(), "default", &store),
+            buffer: logic::DEFAULT_LATENCY_BUFFER,
+            pool: ComputePool::new(1, 1),
+        };
+        let rocket = build(
+            config,
+            Arc::clone(&store),
+            ReplayLogger::disabled(),
+            GameLogger::disabled(),
+            Drain::new(Duration::from_secs(60), None),
+            opponents,
+            ResultLog::disabled(),
+            Capture::disabled(),
+            LiveFeed::disabled(),
+        );
+        let client = Client::tracked(rocket).unwrap();
+        let contested = std::fs::read_to_string("testdata/fixtures/contested_food.json").unwrap();
+        let post = |path: &str| {
+            return client
+                .post(path.to_string())
+                .header(ContentType::JSON)
+                .body(&contested)
+                .dispatch();
+        };
+        assert_eq!(post("/start").status(), Status::Ok);
+        let game_id = serde_json::from_str::<Value>(&contested).unwrap()["game"]["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let stances = store.read("", &game_id, |memory| memory.stances.clone()).unwrap();
+        assert_eq!(stances.get("mTOl1"), Some(&opponents::Stance::Passive));
+        let chosen: Value = post("/move").into_json().unwrap();
+        assert_eq!(chosen["move"], "up");
+    }
+
+    #[test]
+    fn shouts_reach_the_trace() {
+        let dir = env::temp_dir().join(format!("battlesnake-shouts-{}", process::id()));
+        let config = MoveConfig {
+            personalities: HashMap::from([(
+                "".to_string(),
+                personality(Arc::new(|_, _| decision("right")), ""),
+            )]),
+            buffer: Duration::from_millis(120),
+            pool: ComputePool::new(1, 1),
+        };
+        let rocket = build(
+            config,
+            Arc::new(GameStore::new(store::GAME_TTL)),
+            ReplayLogger::disabled(),
+            GameLogger::new(dir.clone(), store::GAME_TTL, true),
+            Drain::new(Duration::from_secs(60), None),
+            OpponentTable::disabled(),
+            ResultLog::disabled(),
+            Capture::disabled(),
+            LiveFeed::disabled(),
+        );
+        let client = Client::tracked(rocket).unwrap();
+        // the other snake comes first in the board
+        let shouting = |turn: u32, shout: &str| {
+            let body = PROFILED_DATA
+                .replacen("\"shout\": \"\"", &format!("\"shout\": \"{}\"", shout), 1)
+                .replace("\"turn\": 12", &format!("\"turn\": {}", turn));
+            let status = client.post("/move").header(ContentType::JSON).body(body).dispatch().status();
+            assert_eq!(status, Status::Ok);
+        };
+        shouting(12, "coming for you");
+        shouting(13, "coming for you");
+        shouting(14, "left");
+        client.rocket().state::<GameLogger>().unwrap().flush();
+
+        let log = std::fs::read_to_string(dir.join("shared.log")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let shouts: Vec<Value> = log
+            .lines()
+            .filter_map(|line| line.strip_prefix("DECISION "))
+            .map(|trace| serde_json::from_str::<Value>(trace).unwrap()["shouts"].clone())
+            .collect();
+        let them = |shout: &str| json!([{ "snake": "them", "shout": shout }]);
+        assert_eq!(shouts, [them("coming for you"), json!([]), them("left")]);
+        assert!(log.contains("SHOUT them: left\n"), "{}", log);
+    }
+
+    #[test]
+    fn debug_move_explains_the_decision() {
+        let store = Arc::new(GameStore::new(store::GAME_TTL));
+        let config = MoveConfig {
+            personalities: personalities(&StrategyConfig::default(), "default", &store),
+            buffer: logic::DEFAULT_LATENCY_BUFFER,
+            pool: ComputePool::new(1, 1),
+        };
+        let rocket = build(
+            config,
+            Arc::clone(&store),
+            ReplayLogger::disabled(),
+            GameLogger::disabled(),
+            Drain::new(Duration::from_secs(60), None),
+            OpponentTable::disabled(),
+            ResultLog::disabled(),
+            Capture::disabled(),
+            LiveFeed::disabled(),
+        );
+        let client = Client::tracked(with_debug(rocket)).unwrap();
+        let contested = std::fs::read_to_string("testdata/fixtures/contested_food.json").unwrap();
+        let render = serde_json::from_str::<types::GameState>(&contested).unwrap().board.render();
+        for route in ["/debug/move", "/defensive/debug/move"] {
+            let response = client.post(route).header(ContentType::JSON).body(&contested).dispatch();
+            assert_eq!(response.status(), Status::Ok, "{}", route);
+            let body: Value = response.into_json().unwrap();
+            let chosen = body["move"].as_str().unwrap();
+            assert!(["left", "right"].contains(&chosen), "{}: {}", route, chosen);
+            assert_eq!(body["trace"]["chosen"], chosen);
+            let scored = body["trace"]["candidates"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|candidate| candidate["score"].is_i64() && candidate["flood_fill"].is_i64())
+                .count();
+            assert!(scored >= 2, "{}", body);
+            assert!(!body["trace"]["phases"].as_array().unwrap().is_empty());
+            assert_eq!(body["board"], render);
+        }
+        // nothing was remembered for the game
+        assert_eq!(store.in_flight(), 0);
+        let bad = client.post("/debug/move").header(ContentType::JSON).body("{").dispatch();
+        assert_eq!(bad.status(), Status::BadRequest);
+
+        // it's only there when it's asked for
+        let plain = client_for(
+            HashMap::from([("".to_string(), personality(Arc::new(|_, _| decision("up")), ""))]),
+            Arc::new(GameStore::new(store::GAME_TTL)),
+        );
+        let response = plain.post("/debug/move").header(ContentType::JSON).body(&contested).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn tools_are_subcommands() {
+        let command = |args: &[&str]| {
+            return Cli::try_parse_from(["battlesnake"].iter().chain(args)).map(|cli| cli.command);
+        };
+        assert!(matches!(command(&["replay", "game.jsonl", "--fail-on-diff"]), Ok(Some(Command::Replay(_)))));
+        let arena = command(&["arena", "--games", "3", "--seed", "1", "--profiles", "greedy,safe"]);
+        assert!(matches!(arena, Ok(Some(Command::Arena(_)))));
+        let tune = command(&["tune", "--games", "4", "--iterations", "2", "--budget", "60", "-o", "tuned.toml"]);
+        assert!(matches!(tune, Ok(Some(Command::Tune(_)))));
+        assert!(matches!(command(&["bench", "--corpus", "testdata/fixtures"]), Ok(Some(Command::Bench(_)))));
+        let diff = command(&["diff-corpus", "testdata/replays", "--base", "default", "--against", "defensive"]);
+        assert!(matches!(diff, Ok(Some(Command::DiffCorpus(_)))));
+        let fetch = command(&["fetch-game", "3b9f3a54", "--snake", "our snake"]);
+        assert!(matches!(fetch, Ok(Some(Command::FetchGame(_)))));
+        assert!(command(&["fetch-game", "3b9f3a54"]).is_err());
+        let visualize = command(&["visualize", "game.jsonl", "-o", "game.html"]);
+        assert!(matches!(visualize, Ok(Some(Command::Visualize(_)))));
+        assert!(command(&["replay"]).is_err());
+        assert!(command(&["race"]).is_err());
+    }
+
+    #[test]
+    fn personalities_play_their_own_profiles() {
+        let mut personalities: HashMap<String, Personality> = logic::PROFILES
+            .into_iter()
+            .map(|(route, profile)| {
+                let strategy: Strategy = Arc::new(move |state, deadline| {
+                    Ok(logic::get_move_profiled(
+                        &state.game,
+                        &state.turn,
+                        &state.board,
+                        &state.you,
+                        &profile,
+                        deadline,
+                    ))
+                });
+                (route.to_string(), personality(strategy, route))
+            })
+            .collect();
+        personalities.insert("".to_string(), personality(Arc::new(|_, _| decision("down")), ""));
+        let store = Arc::new(GameStore::new(store::GAME_TTL));
+        let client = client_for(personalities, Arc::clone(&store));
+        let post = |path: &str| {
+            let response = client
+                .post(path.to_string())
+                .header(ContentType::JSON)
+                .body(PROFILED_DATA)
+                .dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            return response.into_json::<Value>();
+        };
+
+        for route in ["", "/aggressive", "/defensive"] {
+            post(&format!("{}/start", route));
+        }
+        assert_eq!(post("/aggressive/move").unwrap()["move"], "left");
+        // left and right tie, the early game's weights break it toward the food and the room on the left
+        assert_eq!(post("/defensive/move").unwrap()["move"], "left");
+        assert_eq!(post("/move").unwrap()["move"], "down");
+
+        // the same game is remembered separately by each personality
+        for (route, chosen) in [("", "down"), ("aggressive", "left"), ("defensive", "left")] {
+            let moves = store.read(route, "shared", |memory| memory.moves.clone());
+            assert_eq!(moves, Some([chosen.to_string()].into()));
+        }
+        post("/aggressive/end");
+        assert!(store.read("aggressive", "shared", |_| ()).is_none());
+        assert!(store.read("defensive", "shared", |_| ()).is_some());
+
+        let info: Value = client.get("/defensive").dispatch().into_json().unwrap();
+        assert_eq!(info["apiversion"], "1");
+        assert_eq!(client.get("/stats").dispatch().status(), Status::Ok);
+        assert_eq!(client.get("/cautious").dispatch().status(), Status::NotFound);
+    }
+
+    #[test]
+    fn malformed_payloads_still_get_a_move() {
+        let client = client(
+            Arc::new(|state, _| decision(logic::fallback_move(&state.board, &state.you))),
+            Arc::new(GameStore::new(store::GAME_TTL)),
+        );
+        let post = |path: &str, body: String| {
+            let response = client.post(path.to_string()).body(body).dispatch();
+            assert_eq!(response.status(), Status::Ok, "{}", path);
+            return response.into_json::<Value>();
+        };
+        let mut wrong_types: Value = serde_json::from_str(CORNER_DATA).unwrap();
+        wrong_types["turn"] = json!("12");
+        wrong_types["you"]["health"] = json!(90.0);
+        wrong_types["board"]["snakes"][0]["length"] = json!("3");
+        let mut unreadable: Value = serde_json::from_str(CORNER_DATA).unwrap();
+        unreadable["board"]["width"] = json!("eleven");
+
+        // the mistakes that can be read past still get a real move out of the corner
+        assert_eq!(post("/move", wrong_types.to_string()).unwrap()["move"], "right");
+        for body in [
+            CORNER_DATA[..CORNER_DATA.len() / 2].to_string(),
+            unreadable.to_string(),
+            String::new(),
+        ] {
+            let response = post("/move", body.clone()).unwrap();
+            assert!(types::DIRECTIONS.contains_key(response["move"].as_str().unwrap()));
+            post("/start", body.clone());
+            post("/end", body);
+        }
+    }
+
+    #[test]
+    fn frames_without_our_snake_get_a_move() {
+        let store = Arc::new(GameStore::new(store::GAME_TTL));
+        let config = MoveConfig {
+            personalities: personalities(&StrategyConfig::default(), "default", &store),
+            buffer: logic::DEFAULT_LATENCY_BUFFER,
+            pool: ComputePool::new(1, 1),
+        };
+        let client = tracked(config, store, Drain::new(Duration::from_secs(60), None));
+        for name in ["missing_you", "dead_you", "bodiless_you", "offboard_you"] {
+            let read = |file: String| std::fs::read_to_string(format!("testdata/fixtures/{}", file)).unwrap();
+            let frame = read(format!("{}.json", name));
+            let expect: Value = serde_json::from_str(&read(format!("{}.expect.json", name))).unwrap();
+            let post = |path: &str| {
+                let response = client.post(path.to_string()).header(ContentType::JSON).body(&frame).dispatch();
+                assert_eq!(response.status(), Status::Ok, "{} {}", name, path);
+                return response;
+            };
+            post("/start");
+            let chosen: Value = post("/move").into_json().unwrap();
+            assert!(expect["allowed"].as_array().unwrap().contains(&chosen["move"]), "{}: {}", name, chosen);
+            post("/end");
+        }
+    }
+
+    #[test]
+    fn pipeline_errors_reach_the_trace() {
+        let dir = env::temp_dir().join(format!("battlesnake-errors-{}", process::id()));
+        let store = Arc::new(GameStore::new(store::GAME_TTL));
+        let config = MoveConfig {
+            personalities: personalities(&StrategyConfig::default(), "default", &store),
+            buffer: logic::DEFAULT_LATENCY_BUFFER,
+            pool: ComputePool::new(1, 1),
+        };
+        let rocket = build(
+            config,
+            Arc::clone(&store),
+            ReplayLogger::disabled(),
+            GameLogger::new(dir.clone(), store::GAME_TTL, true),
+            Drain::new(Duration::from_secs(60), None),
+            OpponentTable::disabled(),
+            ResultLog::disabled(),
+            Capture::disabled(),
+            LiveFeed::disabled(),
+        );
+        let client = Client::tracked(rocket).unwrap();
+        // the corner frame on a board of its own, with our snake's body
+        let frame = |game: &str, size: u8, body: Value| {
+            let mut state: Value = serde_json::from_str(CORNER_DATA).unwrap();
+            state["game"]["id"] = json!(game);
+            state["you"]["head"] = body[0].clone();
+            state["you"]["length"] = json!(body.as_array().unwrap().len());
+            state["you"]["body"] = body;
+            state["board"]["snakes"] = json!([state["you"].clone()]);
+            (state["board"]["width"], state["board"]["height"]) = (json!(size), json!(size));
+            return state.to_string();
+        };
+        let tiles = |tiles: &[(i16, i16)]| {
+            return json!(tiles.iter().map(|&(x, y)| json!({ "x": x, "y": y })).collect::<Vec<_>>());
+        };
+        let post = |path: &str, body: &str| {
+            let response = client.post(path.to_string()).header(ContentType::JSON).body(body).dispatch();
+            assert_eq!(response.status(), Status::Ok, "{}", path);
+            return response;
+        };
+        // every tile taken but our tail's, and our own body walling us in
+        let full = frame("full", 2, tiles(&[(0, 0), (0, 1), (1, 1), (1, 0)]));
+        let boxed = frame("boxed", 3, tiles(&[(0, 0), (1, 0), (1, 1), (0, 1), (0, 2)]));
+        // a way out remembered from the turn before that starts with a step no move makes
+        let stale = frame("stale", 11, tiles(&[(0, 10), (0, 9), (0, 8)]));
+        post("/start", &stale);
+        store.write("", "stale", |memory| memory.cache.commit_escape(11, &[types::Coord { x: 2, y: 0 }]));
+        let moves: Vec<Value> = [&full, &boxed, &stale]
+            .iter()
+            .map(|frame| post("/move", frame).into_json::<Value>().unwrap()["move"].clone())
+            .collect();
+        assert_eq!(moves[0], "right");
+        assert_eq!(moves[2], "right");
+        client.rocket().state::<GameLogger>().unwrap().flush();
+
+        let traces: Vec<Value> = ["full", "boxed", "stale"]
+            .iter()
+            .flat_map(|game| {
+                let log = std::fs::read_to_string(dir.join(format!("{}.log", game))).unwrap();
+                return log
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("DECISION "))
+                    .map(|trace| serde_json::from_str::<Value>(trace).unwrap())
+                    .collect::<Vec<_>>();
+            })
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let errors: Vec<&Value> = traces.iter().map(|trace| &trace["error"]).collect();
+        assert_eq!(
+            errors,
+            [
+                &json!({ "kind": "no_free_tiles" }),
+                &json!({ "kind": "empty_frontier" }),
+                &json!({ "kind": "empty_path", "branch": "pinned" }),
+            ]
+        );
+        assert!(traces.iter().all(|trace| trace["branch"] == "fallback"));
+        assert_eq!(traces.iter().map(|trace| trace["chosen"].clone()).collect::<Vec<_>>(), moves);
+    }
+
+    #[test]
+    fn health_and_version_answer() {
+        let store = Arc::new(GameStore::new(store::GAME_TTL));
+        let client = client(Arc::new(|_, _| decision("right")), Arc::clone(&store));
+        let health = |client: &Client| -> Value {
+            let response = client.get("/health").dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            return response.into_json().unwrap();
+        };
+        assert_eq!(health(&client)["games_in_flight"], 0);
+        assert!(health(&client)["uptime_secs"].is_u64());
+        let status = client
+            .post("/start")
+            .header(ContentType::JSON)
+            .body(CORNER_DATA)
+            .dispatch()
+            .status();
+        assert_eq!(status, Status::Ok);
+        assert_eq!(health(&client)["games_in_flight"], 1);
+
+        // the probe doesn't wait on a store that's busy
+        store.read("", "slow", |_| {
+            assert_eq!(health(&client)["status"], "ok");
+        });
+
+        let response = client.get("/version").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let version: Value = response.into_json().unwrap();
+        assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+        assert!(version["commit"].is_string());
+    }
+
+    #[test]
+    fn draining_refuses_new_games_and_finishes_old_ones() {
+        let store = Arc::new(GameStore::new(store::GAME_TTL));
+        let client = client(Arc::new(|_, _| decision("right")), Arc::clone(&store));
+        let post = |path: &str, game_id: &str| {
+            return client
+                .post(path.to_string())
+                .header(ContentType::JSON)
+                .body(CORNER_DATA.replace("\"slow\"", &format!("\"{}\"", game_id)))
+                .dispatch()
+                .status();
+        };
+        let drain = |token: &str| {
+            return client
+                .post("/admin/drain")
+                .header(Header::new("Authorization", format!("Bearer {}", token)))
+                .dispatch()
+                .status();
+        };
+        assert_eq!(post("/start", "old"), Status::Ok);
+        assert_eq!(drain("guess"), Status::Unauthorized);
+        // a prefix of the token, and a guess as long as it
+        assert_eq!(drain(&ADMIN_TOKEN[..ADMIN_TOKEN.len() - 1]), Status::Unauthorized);
+        assert_eq!(drain(&ADMIN_TOKEN.replace('m', "w")), Status::Unauthorized);
+        assert_eq!(post("/start", "early"), Status::Ok);
+        assert_eq!(post("/end", "early"), Status::Ok);
+
+        assert_eq!(drain(ADMIN_TOKEN), Status::Accepted);
+        let refused = client
+            .post("/start")
+            .header(ContentType::JSON)
+            .body(CORNER_DATA)
+            .dispatch();
+        assert_eq!(refused.status(), Status::ServiceUnavailable);
+        assert!(refused.into_string().unwrap().contains("draining"));
+        for _ in 0..3 {
+            assert_eq!(post("/move", "old"), Status::Ok);
+        }
+        let health: Value = client.get("/health").dispatch().into_json().unwrap();
+        assert_eq!(health["status"], "draining");
+        assert_eq!(health["games_in_flight"], 1);
+        assert_eq!(post("/end", "old"), Status::Ok);
+        assert_eq!(store.in_flight(), 0);
+    }
+
+    #[test]
+    fn drain_is_off_without_a_token() {
+        let config = MoveConfig {
+            personalities: HashMap::from([(
+                "".to_string(),
+                personality(Arc::new(|_, _| decision("up")), ""),
+            )]),
+            buffer: Duration::from_millis(120),
+            pool: ComputePool::new(4, 4),
+        };
+        let store = Arc::new(GameStore::new(store::GAME_TTL));
+        let drain = Drain::new(Duration::from_secs(60), None);
+        let client = tracked(config, store, drain);
+        let status = client
+            .post("/admin/drain")
+            .header(Header::new("Authorization", "Bearer "))
+            .dispatch()
+            .status();
+        assert_eq!(status, Status::NotFound);
+    }
+
+    /// a sample from a scrape: its name, labels and value
+    type Sample = (String, BTreeMap<String, String>, f64);
+
+    /// # parse_exposition
+    /// reads a scrape as Prometheus would, panicking on anything the text format doesn't allow
+    /// ## Returns:
+    /// each family's type and samples
+    fn parse_exposition(text: &str) -> BTreeMap<String, (String, Vec<Sample>)> {
+        let valid_name = |name: &str| {
+            return !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':');
+        };
+        let mut families: BTreeMap<String, (String, Vec<Sample>)> = BTreeMap::new();
+        let mut current = String::new();
+        for line in text.lines() {
+            if let Some(help) = line.strip_prefix("# HELP ") {
+                let (name, _) = help.split_once(' ').unwrap();
+                assert!(valid_name(name), "{}", line);
+                continue;
+            }
+            if let Some(kind) = line.strip_prefix("# TYPE ") {
+                let (name, kind) = kind.split_once(' ').unwrap();
+                assert!(valid_name(name), "{}", line);
+                assert!(["counter", "gauge", "histogram"].contains(&kind), "{}", line);
+                assert!(!families.contains_key(name), "{} is declared twice", name);
+                families.insert(name.to_string(), (kind.to_string(), vec![]));
+                current = name.to_string();
+                continue;
+            }
+            let (series, value) = line.rsplit_once(' ').unwrap();
+            let value: f64 = match value {
+                "+Inf" => f64::INFINITY,
+                value => value.parse().unwrap(),
+            };
+            let (name, labels) = match series.split_once('{') {
+                Some((name, labels)) => (name, labels.strip_suffix('}').unwrap()),
+                None => (series, ""),
+            };
+            assert!(valid_name(name), "{}", line);
+            let mut parsed = BTreeMap::new();
+            for pair in labels.split(',').filter(|pair| !pair.is_empty()) {
+                let (label, quoted) = pair.split_once('=').unwrap();
+                assert!(valid_name(label), "{}", line);
+                let unquoted = quoted.strip_prefix('"').and_then(|q| q.strip_suffix('"'));
+                parsed.insert(label.to_string(), unquoted.unwrap().to_string());
+            }
+            let (kind, samples) = families.get_mut(&current).unwrap();
+            let suffix = name.strip_prefix(current.as_str()).unwrap();
+            match kind.as_str() {
+                "histogram" => assert!(["_bucket", "_sum", "_count"].contains(&suffix), "{}", line),
+                _ => assert_eq!(suffix, "", "{}", line),
+            }
+            samples.push((name.to_string(), parsed, value));
+        }
+        return families;
+    }
+
+    #[test]
+    fn metrics_parse_after_a_game() {
+        let client = client(
+            Arc::new(|_, _| decision("right")),
+            Arc::new(GameStore::new(store::GAME_TTL)),
+        );
+        for path in ["/start", "/move", "/move", "/end"] {
+            let response = client
+                .post(path)
+                .header(ContentType::JSON)
+                .body(CORNER_DATA)
+                .dispatch();
+            assert_eq!(response.status(), Status::Ok);
+        }
+        let response = client.get("/metrics").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.content_type().unwrap().to_string(),
+            "text/plain; version=0.0.4"
+        );
+        let families = parse_exposition(&response.into_string().unwrap());
+
+        let labels = |family: &str, label: &str| -> Vec<String> {
+            return families[family]
+                .1
+                .iter()
+                .filter_map(|(_, labels, _)| labels.get(label).cloned())
+                .collect();
+        };
+        let value = |sample: &str, label: Option<(&str, &str)>| -> f64 {
+            return families
+                .values()
+                .flat_map(|(_, samples)| samples)
+                .find(|(name, labels, _)| {
+                    let labelled = label.is_none_or(|(label, value)| {
+                        labels.get(label).map(String::as_str) == Some(value)
+                    });
+                    return name == sample && labelled;
+                })
+                .unwrap()
+                .2;
+        };
+        let kinds: BTreeMap<&str, &str> = families
+            .iter()
+            .map(|(name, (kind, _))| (name.as_str(), kind.as_str()))
+            .collect();
+        assert_eq!(
+            kinds,
+            BTreeMap::from([
+                ("battlesnake_deadline_degradations_total", "counter"),
+                ("battlesnake_games_finished_total", "counter"),
+                ("battlesnake_games_in_progress", "gauge"),
+                ("battlesnake_games_started_total", "counter"),
+                ("battlesnake_move_latency_seconds", "histogram"),
+                ("battlesnake_moves_total", "counter"),
+                ("battlesnake_pipeline_panics_total", "counter"),
+                ("battlesnake_requests_total", "counter"),
+                ("battlesnake_search_nodes_total", "counter"),
+            ])
+        );
+
+        assert_eq!(
+            labels("battlesnake_requests_total", "endpoint"),
+            ["end", "metrics", "move", "start"]
+        );
+        assert_eq!(
+            value("battlesnake_requests_total", Some(("endpoint", "move"))),
+            2.0
+        );
+        assert_eq!(value("battlesnake_games_in_progress", None), 0.0);
+        assert_eq!(value("battlesnake_games_started_total", None), 1.0);
+        assert_eq!(labels("battlesnake_games_finished_total", "result"), ["win", "loss"]);
+        assert_eq!(
+            value("battlesnake_games_finished_total", Some(("result", "win"))),
+            1.0
+        );
+        let branches: Vec<&str> = logic::Branch::ALL.iter().map(|branch| branch.name()).collect();
+        assert_eq!(labels("battlesnake_moves_total", "branch"), branches);
+        assert_eq!(
+            value("battlesnake_moves_total", Some(("branch", "a_star"))),
+            2.0
+        );
+        assert_eq!(
+            labels("battlesnake_search_nodes_total", "search"),
+            ["endgame", "minimax"]
+        );
+        assert_eq!(
+            labels("battlesnake_deadline_degradations_total", "stage"),
+            ["pipeline", "saturated", "endgame", "minimax"]
+        );
+        assert_eq!(
+            labels("battlesnake_pipeline_panics_total", "stage"),
+            ["pipeline", "handler"]
+        );
+
+        // the buckets are cumulative and end with every move
+        let buckets: Vec<f64> = families["battlesnake_move_latency_seconds"]
+            .1
+            .iter()
+            .filter(|(name, _, _)| name.ends_with("_bucket"))
+            .map(|(_, _, value)| *value)
+            .collect();
+        assert!(buckets.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(buckets.last(), Some(&2.0));
+        assert_eq!(
+            labels("battlesnake_move_latency_seconds", "le").last().unwrap(),
+            "+Inf"
+        );
+        assert_eq!(value("battlesnake_move_latency_seconds_count", None), 2.0);
+    }
+
+    #[rocket::async_test]
+    async fn concurrent_games_stay_apart() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+        use rocket::futures::future::join_all;
+        use rocket::local::asynchronous::Client;
+
+        const GAMES: usize = 10;
+        const TURNS: usize = 12;
+        const MOVES: [&str; 4] = ["up", "right", "down", "left"];
+        // one turn of one game panics, and takes the store's lock down with it
+        const PANICS: (usize, usize) = (3, 5);
+        // every game puts our head in its own column and the strategy answers from the board, so an answer or memory
+        // meant for another game shows
+        let expected = |game: usize, turn: usize| MOVES[(game + turn) % 4];
+        let store = Arc::new(GameStore::new(store::GAME_TTL));
+        let poisoner = Arc::clone(&store);
+        let strategy: Strategy = Arc::new(move |state, _| {
+            let game = state.you.head.x as usize;
+            if (game, state.turn as usize) == PANICS {
+                poisoner.read("", &state.game.id, |_| panic!("a strategy bug"));
+            }
+            decision(expected(game, state.turn as usize))
+        });
+        let config = MoveConfig {
+            personalities: HashMap::from([("".to_string(), personality(strategy, ""))]),
+            buffer: Duration::from_millis(120),
+            pool: ComputePool::new(4, GAMES),
+        };
+        let rocket = build(
+            config,
+            Arc::clone(&store),
+            ReplayLogger::disabled(),
+            GameLogger::disabled(),
+            Drain::new(Duration::from_secs(60), None),
+            OpponentTable::disabled(),
+            ResultLog::disabled(),
+            Capture::disabled(),
+            LiveFeed::disabled(),
+        );
+        let client = Client::tracked(rocket).await.unwrap();
+        let body = |game: usize, turn: usize, alive: bool| {
+            let me = json!({
+                "id": "me",
+                "name": "me",
+                "health": 90,
+                "body": [{ "x": game, "y": 5 }, { "x": game, "y": 4 }, { "x": game, "y": 3 }],
+                "head": { "x": game, "y": 5 },
+                "length": 3,
+                "shout": ""
+            });
+            let snakes = if alive { json!([me]) } else { json!([]) };
+            return json!({
+                "game": { "id": format!("game-{}", game), "ruleset": { "name": "standard" }, "timeout": 500 },
+                "turn": turn,
+                "board": { "height": 11, "width": 11, "food": [], "snakes": snakes, "hazards": [] },
+                "you": me
+            })
+            .to_string();
+        };
+
+        let games = (0..GAMES).map(|game| {
+            let (client, store) = (&client, &store);
+            let mut rng = StdRng::seed_from_u64(game as u64);
+            let mut pause = move || rocket::tokio::time::sleep(Duration::from_micros(rng.gen_range(0..3000)));
+            return async move {
+                pause().await;
+                let start = client.post("/start").header(ContentType::JSON).body(body(game, 0, true));
+                assert_eq!(start.dispatch().await.status(), Status::Ok);
+                let mut answered = vec![];
+                for turn in 0..TURNS {
+                    pause().await;
+                    let request = client.post("/move").header(ContentType::JSON).body(body(game, turn, true));
+                    let response = request.dispatch().await;
+                    assert_eq!(response.status(), Status::Ok);
+                    let chosen: Value = response.into_json().await.unwrap();
+                    let chosen = chosen["move"].as_str().unwrap().to_string();
+                    // the panicking turn gets the fallback, up, the first of the moves with the most room
+                    let wanted = if (game, turn) == PANICS { "up" } else { expected(game, turn) };
+                    assert_eq!(chosen, wanted, "game {} turn {}", game, turn);
+                    answered.push(chosen);
+                }
+                let game_id = format!("game-{}", game);
+                store
+                    .read("", &game_id, |memory| {
+                        let ours =
+                            |state: &types::GameState| state.game.id == game_id && state.you.head.x as usize == game;
+                        assert!(memory.states.iter().all(ours), "game {}", game);
+                        assert!(memory.moves.iter().eq(&answered[TURNS - store::MEMORY_LEN..]), "game {}", game);
+                    })
+                    .unwrap();
+                pause().await;
+                // the even games are won, we're missing from the final board of the odd ones
+                let end = client.post("/end").header(ContentType::JSON).body(body(game, TURNS, game % 2 == 0));
+                assert_eq!(end.dispatch().await.status(), Status::Ok);
+            };
+        });
+        join_all(games).await;
+
+        assert_eq!(store.in_flight(), 0);
+        let stats: Value = client.get("/stats").dispatch().await.into_json().await.unwrap();
+        let games = json!({ "started": GAMES, "finished": GAMES, "wins": GAMES / 2, "losses": GAMES / 2 });
+        assert_eq!(stats["games"], games);
+        let requests = json!({ "start": GAMES, "move": GAMES * TURNS, "end": GAMES, "stats": 1 });
+        assert_eq!(stats["requests"], requests);
+        assert_eq!(stats["branches"], json!({ "a_star": GAMES * TURNS - 1, "fallback": 1 }));
+    }
+
+    #[rocket::async_test]
+    async fn simultaneous_slow_moves_answer_in_time() {
+        use rocket::futures::future::{join, join_all};
+        use rocket::local::asynchronous::Client;
+
+        let slow: Strategy = Arc::new(|_, _| {
+            thread::sleep(Duration::from_secs(2));
+            decision("up")
+        });
+        let config = MoveConfig {
+            personalities: HashMap::from([("".to_string(), personality(slow, ""))]),
+            buffer: Duration::from_millis(120),
+            pool: ComputePool::new(2, 2),
+        };
+        let rocket = build(
+            config,
+            Arc::new(GameStore::new(store::GAME_TTL)),
+            ReplayLogger::disabled(),
+            GameLogger::disabled(),
+            Drain::new(Duration::from_secs(60), None),
+            OpponentTable::disabled(),
+            ResultLog::disabled(),
+            Capture::disabled(),
+            LiveFeed::disabled(),
+        );
+        let client = Client::tracked(rocket).await.unwrap();
+
+        // far more moves than the pool has threads and queue, the ones that don't fit fall back straight away
+        let started = Instant::now();
+        let moves = (0..16).map(|game| {
+            let body = CORNER_DATA.replace("\"slow\"", &format!("\"game-{}\"", game));
+            let client = &client;
+            return async move {
+                let response = client
+                    .post("/move")
+                    .header(ContentType::JSON)
+                    .body(body)
+                    .dispatch()
+                    .await;
+                assert_eq!(response.status(), Status::Ok);
+                return response.into_json::<Value>().await.unwrap();
+            };
+        });
+        let info = async {
+            let response = client.get("/").dispatch().await;
+            assert_eq!(response.status(), Status::Ok);
+            return started.elapsed();
+        };
+        let (answers, info_elapsed) = join(join_all(moves), info).await;
+        let elapsed = started.elapsed();
+
+        // the budget is the 300ms timeout less the 120ms buffer
+        assert!(elapsed < Duration::from_millis(300), "took {:?}", elapsed);
+        assert!(info_elapsed < Duration::from_millis(100), "took {:?}", info_elapsed);
+        assert!(answers.iter().all(|answer| answer["move"] == "right"));
+    }
+
+    /// a client of a live feed: the server with a strategy that always goes down and the feed mounted
+    async fn live_client(capacity: usize) -> rocket::local::asynchronous::Client {
+        let config = MoveConfig {
+            personalities: HashMap::from([("".to_string(), personality(Arc::new(|_, _| decision("down")), ""))]),
+            buffer: Duration::from_millis(120),
+            pool: ComputePool::new(2, 2),
+        };
+        let rocket = build(
+            config,
+            Arc::new(GameStore::new(store::GAME_TTL)),
+            ReplayLogger::disabled(),
+            GameLogger::disabled(),
+            Drain::new(Duration::from_secs(60), None),
+            OpponentTable::disabled(),
+            ResultLog::disabled(),
+            Capture::disabled(),
+            LiveFeed::new(capacity),
+        );
+        return rocket::local::asynchronous::Client::tracked(rocket).await.unwrap();
+    }
+
+    fn live_move(game_id: &str, turn: u32) -> String {
+        return CORNER_DATA
+            .replace("\"slow\"", &format!("\"{}\"", game_id))
+            .replace("\"turn\": 12", &format!("\"turn\": {}", turn));
+    }
+
+    /// the data of the next event on a feed
+    async fn next_turn<R: rocket::tokio::io::AsyncBufRead + Unpin>(feed: &mut R) -> Value {
+        use rocket::tokio::io::AsyncBufReadExt;
+        let read = async {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                assert!(feed.read_line(&mut line).await.unwrap() > 0, "the feed ended");
+                if let Some(data) = line.strip_prefix("data:") {
+                    return serde_json::from_str::<Value>(data.trim()).unwrap();
+                }
+            }
+        };
+        return rocket::tokio::time::timeout(Duration::from_secs(5), read).await.unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn live_feed_streams_each_move() {
+        let client = live_client(live::CAPACITY).await;
+        let feed = client.get("/live?game=watched").dispatch().await;
+        assert_eq!(feed.status(), Status::Ok);
+        assert_eq!(feed.content_type(), Some(ContentType::EventStream));
+        let mut feed = rocket::tokio::io::BufReader::new(feed);
+
+        for game_id in ["other", "watched"] {
+            let request = client.post("/move").header(ContentType::JSON).body(live_move(game_id, 12));
+            assert_eq!(request.dispatch().await.status(), Status::Ok);
+        }
+        // the other game is filtered out
+        let turn = next_turn(&mut feed).await;
+        assert_eq!(turn["game_id"], "watched");
+        assert_eq!(turn["turn"], 12);
+        assert_eq!(turn["trace"]["chosen"], "down");
+        let state: types::GameState = serde_json::from_str(CORNER_DATA).unwrap();
+        assert_eq!(turn["board"], state.board.render());
+    }
+
+    #[rocket::async_test]
+    async fn stalled_live_client_never_blocks_a_move() {
+        const KEPT: usize = 4;
+        let client = live_client(KEPT).await;
+        // connected but never read while the game is played
+        let feed = client.get("/live").dispatch().await;
+        let turns = 20;
+        let started = Instant::now();
+        for turn in 0..turns {
+            let request = client.post("/move").header(ContentType::JSON).body(live_move("stalled", turn));
+            let response = rocket::tokio::time::timeout(Duration::from_secs(2), request.dispatch()).await;
+            assert_eq!(response.expect("a stalled client held up a move").status(), Status::Ok);
+        }
+        assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+
+        // the client lost the oldest turns and picks up from the ones kept
+        let mut feed = rocket::tokio::io::BufReader::new(feed);
+        for turn in turns - KEPT as u32..turns {
+            assert_eq!(next_turn(&mut feed).await["turn"], turn);
+        }
+    }
+}
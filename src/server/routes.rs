@@ -0,0 +1,271 @@
+//! the HTTP routes, each a thin layer over `api` or the state the server manages
+// the route attribute re-exports a `uri!` macro for every route, nothing builds uris with them
+#![allow(unused_imports)]
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use rocket::http::{ContentType, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::stream::{Event, EventStream};
+use rocket::serde::json::Json;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::{Request, Route, Shutdown, State};
+use serde_json::Value;
+
+use super::{api, MoveConfig, Personality, Started};
+use crate::capture::Capture;
+use crate::drain::{Admin, Drain};
+use crate::gamelog::GameLogger;
+use crate::live::LiveFeed;
+use crate::opponents::OpponentTable;
+use crate::payload::{self, RawBody};
+use crate::replay::ReplayLogger;
+use crate::results::ResultLog;
+use crate::stats::Stats;
+use crate::store::GameStore;
+use crate::{logic, metrics, types, version};
+
+/// the personality a request was routed to
+struct Mounted<'r> {
+    route: &'r str,
+    personality: &'r Personality,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Mounted<'r> {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let route = req
+            .route()
+            .map_or("", |route| route.uri.base().trim_matches('/'));
+        let mounted = req
+            .rocket()
+            .state::<MoveConfig>()
+            .and_then(|config| config.personalities.get_key_value(route));
+        return match mounted {
+            Some((route, personality)) => Outcome::Success(Mounted { route, personality }),
+            None => Outcome::Failure((Status::NotFound, ())),
+        };
+    }
+}
+
+#[get("/")]
+fn handle_index(mounted: Mounted, stats: &State<Stats>) -> Json<types::SnakeInfo> {
+    Json(api::index(mounted.personality, stats))
+}
+
+// the game routes take any body and parse it themselves, a request that fails to parse still gets an answer instead of
+// an error the engine would count as a timeout
+
+#[post("/start", data = "<start_req>")]
+fn handle_start(
+    start_req: RawBody,
+    mounted: Mounted,
+    store: &State<Arc<GameStore>>,
+    replays: &State<ReplayLogger>,
+    gamelogs: &State<GameLogger>,
+    stats: &State<Stats>,
+    drain: &State<Drain>,
+    opponents: &State<OpponentTable>,
+) -> (Status, &'static str) {
+    let Some(start_req) = api::parse(stats, "start", &start_req.0) else {
+        return (Status::Ok, "");
+    };
+    match api::start(mounted.route, &start_req, store, replays, gamelogs, stats, drain, opponents) {
+        api::Started::Playing => (Status::Ok, ""),
+        api::Started::Draining => (Status::ServiceUnavailable, "draining for a restart, no new games for now"),
+    }
+}
+
+#[post("/move", data = "<move_req>")]
+async fn handle_move(
+    move_req: RawBody,
+    mounted: Mounted<'_>,
+    config: &State<MoveConfig>,
+    store: &State<Arc<GameStore>>,
+    replays: &State<ReplayLogger>,
+    gamelogs: &State<GameLogger>,
+    live: &State<LiveFeed>,
+    stats: &State<Stats>,
+    opponents: &State<OpponentTable>,
+    results: &State<ResultLog>,
+) -> Json<Value> {
+    let Some(state) = api::parse(stats, "move", &move_req.0) else {
+        return Json(api::unreadable_move());
+    };
+    let answer = api::play(
+        mounted.route,
+        mounted.personality,
+        state,
+        config,
+        store,
+        replays,
+        gamelogs,
+        live,
+        stats,
+        opponents,
+        results,
+    )
+    .await;
+    Json(answer.response)
+}
+
+#[post("/end", data = "<end_req>")]
+fn handle_end(
+    end_req: RawBody,
+    mounted: Mounted,
+    store: &State<Arc<GameStore>>,
+    replays: &State<ReplayLogger>,
+    gamelogs: &State<GameLogger>,
+    stats: &State<Stats>,
+    opponents: &State<OpponentTable>,
+    results: &State<ResultLog>,
+    captures: &State<Capture>,
+) -> Status {
+    if let Some(end_req) = api::parse(stats, "end", &end_req.0) {
+        api::end(mounted.route, &end_req, store, replays, gamelogs, stats, opponents, results, captures);
+    }
+    Status::Ok
+}
+
+/// set to `1` to serve `POST /debug/move` next to every personality's `/move`
+pub(super) const DEBUG_ENV: &str = "BATTLESNAKE_DEBUG";
+
+// answers a move request with the whole decision and the board instead of just the move, for poking at positions with
+// curl. the position isn't remembered and the stances aren't read, so it can't leak into a live game
+#[post("/debug/move", data = "<move_req>")]
+async fn handle_debug_move(
+    move_req: RawBody,
+    mounted: Mounted<'_>,
+    config: &State<MoveConfig>,
+) -> (Status, Json<Value>) {
+    let Some(state) = payload::parse("debug move", &move_req.0) else {
+        return (Status::BadRequest, Json(serde_json::json!({ "error": "not a move request" })));
+    };
+    let profile = mounted.personality.profile;
+    let started = Instant::now();
+    let deadline = started + logic::move_budget(state.game.timeout, config.buffer);
+    let decided = rocket::tokio::task::spawn_blocking(move || {
+        let decision =
+            logic::get_move_profiled(&state.game, &state.turn, &state.board, &state.you, &profile, deadline);
+        return (decision, state.board.render());
+    })
+    .await;
+    let Ok((decision, board)) = decided else {
+        return (Status::InternalServerError, Json(serde_json::json!({ "error": "the pipeline panicked" })));
+    };
+    let mut trace = decision.trace;
+    trace.elapsed_ms = started.elapsed().as_millis() as u64;
+    (
+        Status::Ok,
+        Json(serde_json::json!({ "move": trace.chosen, "trace": trace, "board": board })),
+    )
+}
+
+// streams every move as it's answered while the live feed is on, `?game=<id>` follows a single game
+#[get("/live?<game>")]
+fn handle_live(game: Option<String>, live: &State<LiveFeed>, mut shutdown: Shutdown) -> EventStream![] {
+    // only mounted while the feed is on
+    let mut turns = live.subscribe();
+    EventStream! {
+        let Some(turns) = turns.as_mut() else {
+            return;
+        };
+        loop {
+            let turn = rocket::tokio::select! {
+                turn = turns.recv() => match turn {
+                    Ok(turn) => turn,
+                    // a client too slow to keep up has lost its oldest turns, it carries on from the ones still kept
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            };
+            if game.as_ref().is_none_or(|game| *game == turn.game_id) {
+                yield Event::json(&*turn).event("turn");
+            }
+        }
+    }
+}
+
+// the counters since the server started, with every game's result ever kept under `results` when they're kept
+#[get("/stats")]
+fn handle_stats(stats: &State<Stats>, results: &State<ResultLog>) -> Json<Value> {
+    stats.request("stats");
+    let mut snapshot = stats.snapshot();
+    snapshot["results"] = results.snapshot();
+    Json(snapshot)
+}
+
+// the same counters as `/stats` with the searches' own, as Prometheus scrapes them
+#[get("/metrics")]
+fn handle_metrics(stats: &State<Stats>, store: &State<Arc<GameStore>>) -> (ContentType, String) {
+    stats.request("metrics");
+    let mut metrics = metrics::Exposition::new();
+    stats.export(&mut metrics, store.in_flight());
+    metrics.counters();
+    let format = ContentType::new("text", "plain").with_params(("version", "0.0.4"));
+    (format, metrics.finish())
+}
+
+// the liveness probe and build info stay away from the game handlers, the game count is read without the store's lock
+
+#[get("/health")]
+fn handle_health(
+    started: &State<Started>,
+    store: &State<Arc<GameStore>>,
+    drain: &State<Drain>,
+) -> Json<Value> {
+    Json(version::health(
+        started.0.elapsed(),
+        store.in_flight(),
+        drain.is_draining(),
+    ))
+}
+
+#[get("/version")]
+fn handle_version(config: &State<MoveConfig>) -> Json<Value> {
+    let profiles = config
+        .personalities
+        .iter()
+        .map(|(route, personality)| (route.as_str(), &personality.profile));
+    Json(version::version(profiles))
+}
+
+// refuses new games and shuts down once the games in flight end, call it before a redeploy
+#[post("/admin/drain")]
+fn handle_drain(
+    _admin: Admin,
+    drain: &State<Drain>,
+    store: &State<Arc<GameStore>>,
+    shutdown: Shutdown,
+) -> Status {
+    drain.begin(Arc::clone(store), shutdown);
+    Status::Accepted
+}
+
+/// # game_routes
+/// the routes every personality is mounted with
+pub(super) fn game_routes() -> Vec<Route> {
+    return routes![handle_index, handle_start, handle_move, handle_end];
+}
+
+/// # server_routes
+/// the routes mounted once at the root, whatever the personalities
+pub(super) fn server_routes() -> Vec<Route> {
+    return routes![handle_stats, handle_metrics, handle_health, handle_version, handle_drain];
+}
+
+/// # live_routes
+/// the live feed's stream, only mounted while it's on
+pub(super) fn live_routes() -> Vec<Route> {
+    return routes![handle_live];
+}
+
+/// # debug_routes
+/// `/debug/move`, mounted next to every personality's game routes when `DEBUG_ENV` is set
+pub(super) fn debug_routes() -> Vec<Route> {
+    return routes![handle_debug_move];
+}
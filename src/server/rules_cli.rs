@@ -0,0 +1,202 @@
+//! end to end games against the official rules CLI, https://github.com/BattlesnakeOfficial/rules. they're ignored by
+//! default, run them with `cargo test rules_cli -- --ignored` and the CLI installed as `battlesnake` or pointed to by
+//! `BATTLESNAKE_CLI`. without the CLI they pass without playing
+
+use crate::capture::Capture;
+use crate::config::StrategyConfig;
+use crate::drain::Drain;
+use crate::gamelog::GameLogger;
+use crate::live::LiveFeed;
+use crate::opponents::OpponentTable;
+use crate::pool::ComputePool;
+use crate::replay::ReplayLogger;
+use crate::results::ResultLog;
+use crate::store::{self, GameStore};
+use crate::logic;
+use crate::server::{build, personalities, MoveConfig};
+use rocket::Shutdown;
+use serde_json::Value;
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{env, fs, thread};
+
+/// the rules CLI binary, `battlesnake` on the path when it isn't set
+const CLI_ENV: &str = "BATTLESNAKE_CLI";
+
+/// the fewest turns a game has to last to count as a pass, our snake shouldn't die before its food runs out
+const MIN_TURNS: u64 = 50;
+
+/// how long the server gets to start listening
+const STARTUP: Duration = Duration::from_secs(10);
+
+/// # cli
+/// ## Returns:
+/// the rules CLI, none if it isn't installed
+fn cli() -> Option<String> {
+    let cli = env::var(CLI_ENV).unwrap_or_else(|_| "battlesnake".to_string());
+    // our own binary shares the name, the CLI is the one that can play
+    let help = Command::new(&cli).arg("--help").output().ok()?;
+    let help = String::from_utf8_lossy(&help.stdout);
+    return help.contains("play").then_some(cli);
+}
+
+/// # serve
+/// starts the server with every personality on a free port
+/// ## Returns:
+/// the port and the handle that stops the server
+fn serve() -> (u16, Shutdown) {
+    // the port is free once the listener is dropped, nothing else should take it before the server does
+    let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let store = Arc::new(GameStore::new(store::GAME_TTL));
+    let config = MoveConfig {
+        personalities: personalities(&StrategyConfig::default(), "default", &store),
+        buffer: logic::latency_buffer(),
+        pool: ComputePool::from_env(),
+    };
+    let figment = rocket::Config::figment()
+        .merge(("port", port))
+        .merge(("address", Ipv4Addr::LOCALHOST))
+        .merge(("log_level", "critical"));
+    let server = build(
+        config,
+        store,
+        ReplayLogger::disabled(),
+        GameLogger::disabled(),
+        Drain::new(Duration::from_secs(1), None),
+        OpponentTable::disabled(),
+        ResultLog::disabled(),
+        Capture::disabled(),
+        LiveFeed::disabled(),
+    )
+    .configure(figment);
+    let server = rocket::execute(server.ignite()).unwrap();
+    let shutdown = server.shutdown();
+    thread::spawn(move || {
+        let _ = rocket::execute(server.launch());
+    });
+
+    let started = Instant::now();
+    while TcpStream::connect((Ipv4Addr::LOCALHOST, port)).is_err() {
+        assert!(started.elapsed() < STARTUP, "the server didn't start");
+        thread::sleep(Duration::from_millis(50));
+    }
+    return (port, shutdown);
+}
+
+/// # play
+/// plays a game with the CLI
+/// ## Arguments:
+/// * cli - the rules CLI
+/// * game_type - `solo` or `standard`
+/// * snakes - each snake's name and url
+/// ## Returns:
+/// every line of the game the CLI wrote out, and everything it logged
+fn play(cli: &str, game_type: &str, snakes: &[(&str, String)]) -> (Vec<Value>, String) {
+    let output = env::temp_dir().join(format!(
+        "battlesnake-rules-cli-{}-{}.jsonl",
+        game_type,
+        std::process::id()
+    ));
+    let mut command = Command::new(cli);
+    command.args([
+        "play", "-W", "11", "-H", "11", "-g", game_type, "--seed", "1",
+    ]);
+    command.args(["--timeout", "500", "--output"]).arg(&output);
+    for (name, url) in snakes {
+        command.args(["--name", name, "--url", url]);
+    }
+    let result = command.output().unwrap();
+    let log = format!(
+        "{}{}",
+        String::from_utf8_lossy(&result.stdout),
+        String::from_utf8_lossy(&result.stderr)
+    );
+    assert!(result.status.success(), "the CLI failed: {}", log);
+    let lines = fs::read_to_string(&output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let _ = fs::remove_file(output);
+    return (lines, log);
+}
+
+/// # check
+/// asserts we lasted `MIN_TURNS`, or won before then, and the CLI never had to correct a move of ours
+/// ## Arguments:
+/// * lines - the game the CLI wrote out
+/// * log - what the CLI logged
+/// * name - our snake's name
+fn check(lines: &[Value], log: &str, name: &str) {
+    let last = lines
+        .iter()
+        .filter_map(|line| line["turn"].as_u64())
+        .max()
+        .unwrap_or(0);
+    let alive_until = lines
+        .iter()
+        .filter(|line| {
+            return line["board"]["snakes"]
+                .as_array()
+                .is_some_and(|snakes| snakes.iter().any(|snake| snake["name"] == name));
+        })
+        .filter_map(|line| line["turn"].as_u64())
+        .max()
+        .unwrap_or(0);
+    assert!(
+        alive_until >= MIN_TURNS || alive_until == last,
+        "{} died on turn {}\n{}",
+        name,
+        alive_until,
+        log
+    );
+    // the CLI warns when a snake's answer is late, unreadable or not a move, and plays a default for it
+    for line in log.lines() {
+        let lower = line.to_lowercase();
+        assert!(
+            !["illegal", "invalid", "failed", "timed out"]
+                .iter()
+                .any(|warning| lower.contains(warning)),
+            "{}",
+            line
+        );
+    }
+}
+
+#[test]
+#[ignore]
+fn solo_game_against_the_rules_cli() {
+    let Some(cli) = cli() else {
+        eprintln!("the rules CLI isn't installed, set {} to play", CLI_ENV);
+        return;
+    };
+    let (port, shutdown) = serve();
+    let url = format!("http://localhost:{}", port);
+    let (lines, log) = play(&cli, "solo", &[("me", url)]);
+    shutdown.notify();
+    check(&lines, &log, "me");
+}
+
+#[test]
+#[ignore]
+fn duel_against_the_rules_cli() {
+    let Some(cli) = cli() else {
+        eprintln!("the rules CLI isn't installed, set {} to play", CLI_ENV);
+        return;
+    };
+    // the CLI brings no snake of its own, the defensive personality plays the other side
+    let (port, shutdown) = serve();
+    let snakes = [
+        ("me", format!("http://localhost:{}", port)),
+        ("them", format!("http://localhost:{}/defensive", port)),
+    ];
+    let (lines, log) = play(&cli, "standard", &snakes);
+    shutdown.notify();
+    check(&lines, &log, "me");
+}
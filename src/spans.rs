@@ -0,0 +1,258 @@
+//! where the time goes inside a turn. the pipeline opens a `turn` span around each move and a span around each of its
+//! expensive steps, and `SpanTimings` times every span under a turn, with the fields it recorded, into the decision
+//! trace. the `log` macros keep working through the subscriber's log bridge, filtered by `RUST_LOG` as before
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
+
+/// set to a file to append every turn's spans to it as folded stacks, ready for `inferno-flamegraph` or
+/// `flamegraph.pl`
+pub const FLAME_FILE_ENV: &str = "BATTLESNAKE_FLAME_FILE";
+
+/// the name of the span the pipeline opens around a move, the spans under it are the ones timed
+pub const TURN_SPAN: &str = "turn";
+
+/// a span closed during a turn
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SpanTiming {
+    /// the span's name after the names of the spans it was opened in, from the turn down, separated by `;`
+    pub path: String,
+    /// from the span opening to it closing
+    pub micros: u64,
+    /// `micros` less the time spent in the spans opened inside it
+    pub self_micros: u64,
+    /// what the span recorded, `nodes_expanded` for A* for example
+    pub fields: BTreeMap<String, String>,
+}
+
+/// a span being timed
+struct Timing {
+    opened: Instant,
+    /// the time spent in the spans opened inside it that have closed
+    children: u64,
+    fields: BTreeMap<String, String>,
+}
+
+/// the spans closed so far under a turn
+#[derive(Default)]
+struct Turn {
+    spans: Vec<SpanTiming>,
+}
+
+struct Fields<'a>(&'a mut BTreeMap<String, String>);
+
+impl Visit for Fields<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+/// times the spans the crate opens under a turn, other crates' spans are left alone
+#[derive(Default)]
+pub struct SpanTimings {
+    /// where to append the folded stacks of each turn as it closes
+    flame: Option<Mutex<File>>,
+}
+
+impl SpanTimings {
+    /// # from_env
+    /// the timings, writing folded stacks to `FLAME_FILE_ENV` when it's set
+    pub fn from_env() -> Self {
+        let flame = env::var(FLAME_FILE_ENV).ok().and_then(|path| {
+            return match OpenOptions::new().create(true).append(true).open(&path) {
+                Ok(file) => Some(Mutex::new(file)),
+                Err(err) => {
+                    eprintln!("couldn't open {} for the flame graph: {}", path, err);
+                    None
+                }
+            };
+        });
+        return SpanTimings { flame };
+    }
+
+    /// # write_flame
+    /// appends a closed turn's spans as folded stacks, each line a stack and the microseconds spent in its last frame
+    fn write_flame(&self, turn: &Turn, self_micros: u64) {
+        let Some(flame) = &self.flame else {
+            return;
+        };
+        let mut folded = format!("{} {}\n", TURN_SPAN, self_micros);
+        for span in &turn.spans {
+            folded += &format!("{} {}\n", span.path, span.self_micros);
+        }
+        if let Ok(mut file) = flame.lock() {
+            let _ = file.write_all(folded.as_bytes());
+        }
+    }
+}
+
+impl<S> Layer<S> for SpanTimings
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if !attrs.metadata().target().starts_with(env!("CARGO_CRATE_NAME")) {
+            return;
+        }
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = BTreeMap::new();
+        attrs.record(&mut Fields(&mut fields));
+        let mut extensions = span.extensions_mut();
+        extensions.insert(Timing {
+            opened: Instant::now(),
+            children: 0,
+            fields,
+        });
+        if span.name() == TURN_SPAN {
+            extensions.insert(Turn::default());
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(timing) = extensions.get_mut::<Timing>() {
+            values.record(&mut Fields(&mut timing.fields));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(timing) = span.extensions_mut().remove::<Timing>() else {
+            return;
+        };
+        let micros = timing.opened.elapsed().as_micros() as u64;
+        let self_micros = micros.saturating_sub(timing.children);
+        if let Some(parent) = span.parent() {
+            if let Some(parent) = parent.extensions_mut().get_mut::<Timing>() {
+                parent.children += micros;
+            }
+        }
+        if span.name() == TURN_SPAN {
+            if let Some(turn) = span.extensions_mut().remove::<Turn>() {
+                self.write_flame(&turn, self_micros);
+            }
+            return;
+        }
+        let Some(turn) = span.scope().skip(1).find(|ancestor| ancestor.name() == TURN_SPAN) else {
+            return;
+        };
+        let path: Vec<&str> = span
+            .scope()
+            .from_root()
+            .map(|ancestor| ancestor.name())
+            .skip_while(|name| *name != TURN_SPAN)
+            .collect();
+        let mut extensions = turn.extensions_mut();
+        if let Some(turn) = extensions.get_mut::<Turn>() {
+            turn.spans.push(SpanTiming {
+                path: path.join(";"),
+                micros,
+                self_micros,
+                fields: timing.fields,
+            });
+        }
+    }
+}
+
+/// # closed_in
+/// the spans closed so far under a turn span, none when no subscriber is timing them
+/// ## Arguments:
+/// * turn - the turn's span
+pub fn closed_in(turn: &tracing::Span) -> Vec<SpanTiming> {
+    let mut spans = vec![];
+    turn.with_subscriber(|(id, dispatch)| {
+        let Some(span) = dispatch.downcast_ref::<Registry>().and_then(|registry| registry.span(id)) else {
+            return;
+        };
+        let extensions = span.extensions();
+        if let Some(turn) = extensions.get::<Turn>() {
+            spans = turn.spans.clone();
+        }
+    });
+    return spans;
+}
+
+/// # init
+/// installs the process's subscriber: `SpanTimings`, and log lines filtered by `RUST_LOG` with the `log` macros'
+/// records bridged in
+pub fn init() {
+    // on stderr like env_logger had them, the commands write their output to stdout
+    let logs = tracing_subscriber::fmt::layer()
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .with_filter(EnvFilter::from_default_env());
+    // other crates' spans aren't opened at all unless their log lines are wanted
+    let timings = SpanTimings::from_env().with_filter(filter_fn(|metadata| {
+        return metadata.target().starts_with(env!("CARGO_CRATE_NAME"));
+    }));
+    if let Err(err) = tracing_subscriber::registry().with(timings).with(logs).try_init() {
+        eprintln!("couldn't install the subscriber: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{self, FIXTURE_DIR};
+    use crate::StrategyConfig;
+    use std::path::Path;
+    use std::time::Duration;
+
+    #[test]
+    fn a_fixture_turn_times_its_spans_into_the_trace() {
+        let fixtures = fixtures::load(Path::new(FIXTURE_DIR)).unwrap();
+        let fixture = fixtures.iter().find(|fixture| fixture.name == "behind_by_two").unwrap();
+        let subscriber = Registry::default().with(SpanTimings::default());
+        let decision = tracing::subscriber::with_default(subscriber, || {
+            return crate::decide(&fixture.state, &StrategyConfig::default(), Instant::now() + Duration::from_secs(1));
+        });
+        let spans = &decision.trace.spans;
+        let paths: Vec<&str> = spans.iter().map(|span| span.path.as_str()).collect();
+        for path in ["turn;to_game_board", "turn;connectivity", "turn;a_star", "turn;select;simulate"] {
+            assert!(paths.contains(&path), "no {} in {:?}", path, paths);
+        }
+        assert!(paths.iter().all(|path| path.starts_with("turn;")), "{:?}", paths);
+        let a_star = spans.iter().find(|span| span.path == "turn;a_star").unwrap();
+        assert!(a_star.fields["nodes_expanded"].parse::<u32>().unwrap() > 0, "{:?}", a_star);
+        let connectivity = spans.iter().find(|span| span.path == "turn;connectivity").unwrap();
+        assert!(connectivity.fields.contains_key("candidates") && connectivity.fields.contains_key("tiles_filled"));
+        // a span's own time is what's left of it once the spans inside it are taken out
+        let select = spans.iter().find(|span| span.path == "turn;select").unwrap();
+        let inside: u64 = spans
+            .iter()
+            .filter(|span| span.path.starts_with("turn;select;") && span.path.matches(';').count() == 2)
+            .map(|span| span.micros)
+            .sum();
+        assert!(select.micros > 0 && select.self_micros <= select.micros);
+        assert_eq!(select.self_micros, select.micros.saturating_sub(inside));
+
+        // without a subscriber timing them the trace has none
+        let decision = crate::decide(&fixture.state, &StrategyConfig::default(), Instant::now() + Duration::from_secs(1));
+        assert!(decision.trace.spans.is_empty());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::logic::Branch;
+use crate::metrics::Exposition;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// move latencies are bucketed by the millisecond up to this, anything slower shares the last bucket
+const LATENCY_BUCKETS: usize = 2000;
+
+/// the upper bounds, in milliseconds, of the move latency histogram `/metrics` exports
+const HISTOGRAM_BOUNDS_MS: [usize; 9] = [5, 10, 25, 50, 100, 200, 300, 500, 1000];
+
+/// counters for the `/stats` route, they start over whenever the server restarts
+pub struct Stats {
+    counters: Mutex<Counters>,
+}
+
+struct Counters {
+    games_started: u64,
+    games_finished: u64,
+    wins: u64,
+    losses: u64,
+    requests: BTreeMap<&'static str, u64>,
+    /// how many moves took each whole number of milliseconds
+    latencies: Vec<u64>,
+    slowest: Duration,
+    total_latency: Duration,
+    branches: BTreeMap<&'static str, u64>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        return Stats::new();
+    }
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        return Stats {
+            counters: Mutex::new(Counters {
+                games_started: 0,
+                games_finished: 0,
+                wins: 0,
+                losses: 0,
+                requests: BTreeMap::new(),
+                latencies: vec![0; LATENCY_BUCKETS + 1],
+                slowest: Duration::ZERO,
+                total_latency: Duration::ZERO,
+                branches: BTreeMap::new(),
+            }),
+        };
+    }
+
+    fn counters(&self) -> MutexGuard<'_, Counters> {
+        return self
+            .counters
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+
+    /// # request
+    /// counts a request to an endpoint
+    pub fn request(&self, endpoint: &'static str) {
+        *self.counters().requests.entry(endpoint).or_default() += 1;
+    }
+
+    pub fn game_started(&self) {
+        self.counters().games_started += 1;
+    }
+
+    /// # game_finished
+    /// counts a finished game
+    /// ## Arguments:
+    /// * won - whether our snake was among the survivors
+    pub fn game_finished(&self, won: bool) {
+        let mut counters = self.counters();
+        counters.games_finished += 1;
+        if won {
+            counters.wins += 1;
+        } else {
+            counters.losses += 1;
+        }
+    }
+
+    /// # moved
+    /// counts a move, how long it took and the branch of the pipeline that chose it
+    pub fn moved(&self, elapsed: Duration, branch: Branch) {
+        let mut counters = self.counters();
+        let bucket = (elapsed.as_millis() as usize).min(LATENCY_BUCKETS);
+        counters.latencies[bucket] += 1;
+        counters.slowest = counters.slowest.max(elapsed);
+        counters.total_latency += elapsed;
+        *counters.branches.entry(branch.name()).or_default() += 1;
+    }
+
+    /// # snapshot
+    /// the counters as the `/stats` route reports them
+    pub fn snapshot(&self) -> Value {
+        let counters = self.counters();
+        return json!({
+            "games": {
+                "started": counters.games_started,
+                "finished": counters.games_finished,
+                "wins": counters.wins,
+                "losses": counters.losses,
+            },
+            "requests": counters.requests,
+            "move_latency_ms": {
+                "p50": percentile(&counters.latencies, 0.5),
+                "p95": percentile(&counters.latencies, 0.95),
+                "max": counters.slowest.as_millis() as u64,
+            },
+            "branches": counters.branches,
+        });
+    }
+
+    /// # export
+    /// adds the counters to a `/metrics` scrape
+    /// ## Arguments:
+    /// * metrics - the scrape
+    /// * in_flight - the games being played
+    pub fn export(&self, metrics: &mut Exposition, in_flight: usize) {
+        let counters = self.counters();
+        let requests = "battlesnake_requests_total";
+        metrics.family(requests, "counter", "Requests answered, by endpoint.");
+        for (endpoint, count) in counters.requests.iter() {
+            metrics.sample(requests, &[("endpoint", endpoint)], count);
+        }
+
+        let latency = "battlesnake_move_latency_seconds";
+        metrics.family(latency, "histogram", "Time taken to answer a move request.");
+        let bucket = format!("{}_bucket", latency);
+        let mut seen = 0;
+        let mut counted = 0;
+        for bound in HISTOGRAM_BOUNDS_MS {
+            // a move in the bucket for n milliseconds took less than n + 1
+            seen += counters.latencies[counted..bound].iter().sum::<u64>();
+            counted = bound;
+            let le = format!("{}", bound as f64 / 1000.0);
+            metrics.sample(&bucket, &[("le", &le)], seen);
+        }
+        let moves: u64 = counters.latencies.iter().sum();
+        metrics.sample(&bucket, &[("le", "+Inf")], moves);
+        let sum = counters.total_latency.as_secs_f64();
+        metrics.sample(&format!("{}_sum", latency), &[], sum);
+        metrics.sample(&format!("{}_count", latency), &[], moves);
+
+        let in_progress = "battlesnake_games_in_progress";
+        metrics.family(in_progress, "gauge", "Games started and not yet ended.");
+        metrics.sample(in_progress, &[], in_flight);
+
+        let started = "battlesnake_games_started_total";
+        metrics.family(started, "counter", "Games started.");
+        metrics.sample(started, &[], counters.games_started);
+
+        let finished = "battlesnake_games_finished_total";
+        metrics.family(finished, "counter", "Games finished, by whether we won.");
+        metrics.sample(finished, &[("result", "win")], counters.wins);
+        metrics.sample(finished, &[("result", "loss")], counters.losses);
+
+        let branches = "battlesnake_moves_total";
+        metrics.family(
+            branches,
+            "counter",
+            "Moves, by the pipeline branch that chose them.",
+        );
+        for branch in Branch::ALL {
+            let count = counters.branches.get(branch.name()).copied().unwrap_or(0);
+            metrics.sample(branches, &[("branch", branch.name())], count);
+        }
+    }
+}
+
+/// # percentile
+/// the bucket a share of the counted moves fall at or below, none before the first move
+fn percentile(buckets: &[u64], share: f64) -> Option<u64> {
+    let total: u64 = buckets.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let rank = ((total as f64 * share).ceil() as u64).max(1);
+    let mut seen = 0;
+    for (millis, count) in buckets.iter().enumerate() {
+        seen += count;
+        if seen >= rank {
+            return Some(millis as u64);
+        }
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn latency_percentiles() {
+        let stats = Stats::new();
+        assert_eq!(stats.snapshot()["move_latency_ms"]["p50"], Value::Null);
+        for millis in 1..=100 {
+            stats.moved(Duration::from_millis(millis), Branch::AStar);
+        }
+        stats.moved(Duration::from_secs(5), Branch::Fallback);
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot["move_latency_ms"]["p50"], 51);
+        assert_eq!(snapshot["move_latency_ms"]["p95"], 96);
+        assert_eq!(snapshot["move_latency_ms"]["max"], 5000);
+        assert_eq!(snapshot["branches"]["a_star"], 100);
+        assert_eq!(snapshot["branches"]["fallback"], 1);
+    }
+}
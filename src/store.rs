@@ -0,0 +1,374 @@
+use crate::budget::BudgetTracker;
+use crate::cache::GameCache;
+use crate::opponents::{self, OpponentProfile, ShoutReaders, Stances};
+use crate::pace::PaceTracker;
+use crate::phase::PhaseTracker;
+use crate::postmortem;
+use crate::trace::Shout;
+use crate::types;
+use log::info;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// how many of the latest turns of a game are remembered
+pub const MEMORY_LEN: usize = 8;
+
+/// games that haven't been touched for this long are assumed to have ended without an `end()`
+pub const GAME_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// what we remember about a game between requests
+#[derive(Debug)]
+pub struct GameMemory {
+    /// the latest requests, oldest first
+    pub states: VecDeque<types::GameState>,
+    /// the moves we answered the requests in `states` with
+    pub moves: VecDeque<String>,
+    /// every length each opponent has been seen at and the turn it was first seen at
+    pub opponent_lengths: HashMap<String, Vec<(u32, u32)>>,
+    /// what the strategies carry from one turn to the next, it goes with the rest of the memory
+    pub cache: GameCache,
+    /// how we play around the opponents we recognised at the start
+    pub stances: Stances,
+    /// what each opponent has done this game, by name
+    pub habits: HashMap<String, OpponentProfile>,
+    /// how to read the shouts of the opponents that announce their moves
+    pub shout_readers: ShoutReaders,
+    /// the phase the game is in, moved on by the move pipeline before it plays each turn
+    pub phase: PhaseTracker,
+    /// the opponents' latencies, moved on alongside `phase`
+    pub pace: PaceTracker,
+    /// the time kept back from the timeout for the network, learned from the latency the engine reports
+    pub budget: BudgetTracker,
+    /// the latest shout of each opponent, by snake id
+    shouts: HashMap<String, String>,
+    touched: Instant,
+}
+
+impl GameMemory {
+    fn new() -> Self {
+        return GameMemory {
+            states: VecDeque::new(),
+            moves: VecDeque::new(),
+            opponent_lengths: HashMap::new(),
+            cache: GameCache::default(),
+            stances: Stances::new(),
+            habits: HashMap::new(),
+            shout_readers: ShoutReaders::new(),
+            phase: PhaseTracker::default(),
+            pace: PaceTracker::default(),
+            budget: BudgetTracker::default(),
+            shouts: HashMap::new(),
+            touched: Instant::now(),
+        };
+    }
+
+    /// # history
+    /// the remembered turns in the form the post-mortem reads them
+    pub fn history(&self) -> Vec<postmortem::Turn> {
+        return self
+            .states
+            .iter()
+            .zip(&self.moves)
+            .map(|(state, chosen)| postmortem::Turn {
+                turn: state.turn,
+                board: state.board.clone(),
+                you: state.you.clone(),
+                chosen: chosen.clone(),
+            })
+            .collect();
+    }
+}
+
+/// the memory of every game in progress. games run concurrently so it's shared between the request handlers.
+/// games are kept by the route of the personality playing them along with their id, two of our personalities can
+/// be in the same game
+pub struct GameStore {
+    games: Mutex<HashMap<(String, String), GameMemory>>,
+    /// the number of games remembered, kept outside the lock so it can be read without waiting on it
+    in_flight: AtomicUsize,
+    ttl: Duration,
+}
+
+impl GameStore {
+    pub fn new(ttl: Duration) -> Self {
+        return GameStore {
+            games: Mutex::new(HashMap::new()),
+            in_flight: AtomicUsize::new(0),
+            ttl,
+        };
+    }
+
+    fn games(&self) -> std::sync::MutexGuard<'_, HashMap<(String, String), GameMemory>> {
+        // a handler panicking mid update leaves at worst one game's memory a turn behind
+        return self
+            .games
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+
+    /// # start
+    /// starts remembering a game, forgetting any game that's gone quiet for longer than the ttl
+    pub fn start(&self, route: &str, game_id: &str) {
+        self.sweep();
+        let mut games = self.games();
+        games.insert(key(route, game_id), GameMemory::new());
+        self.in_flight.store(games.len(), Ordering::Relaxed);
+    }
+
+    /// # record
+    /// remembers a request and the move we answered it with, a game we missed the start of is remembered from here
+    /// ## Arguments:
+    /// * route - the personality that answered
+    /// * state - the move request
+    /// * chosen - the move we answered with
+    /// ## Returns:
+    /// the opponents' shouts that changed since the request before, a snake that's gone quiet isn't listed
+    pub fn record(&self, route: &str, state: &types::GameState, chosen: &str) -> Vec<Shout> {
+        let mut games = self.games();
+        let memory = games
+            .entry(key(route, &state.game.id))
+            .or_insert_with(GameMemory::new);
+        memory.touched = Instant::now();
+        memory.cache.advance(state.turn);
+        let mut shouts = vec![];
+        for snake in state
+            .board
+            .snakes
+            .iter()
+            .filter(|snake| snake.id != state.you.id)
+        {
+            let shout = snake.shout.clone().unwrap_or_default();
+            if memory.shouts.get(&snake.id) != Some(&shout) {
+                if !shout.is_empty() {
+                    shouts.push(Shout {
+                        snake: snake.name.clone(),
+                        shout: shout.clone(),
+                    });
+                }
+                memory.shouts.insert(snake.id.clone(), shout);
+            }
+            let lengths = memory.opponent_lengths.entry(snake.id.clone()).or_default();
+            if lengths
+                .last()
+                .is_none_or(|(_, length)| *length != snake.length)
+            {
+                lengths.push((state.turn, snake.length));
+            }
+        }
+        if let Some(before) = memory.states.back() {
+            opponents::observe(before, state, &mut memory.habits);
+        }
+        if memory.states.len() >= MEMORY_LEN {
+            memory.states.pop_front();
+            memory.moves.pop_front();
+        }
+        memory.states.push_back(state.clone());
+        memory.moves.push_back(chosen.to_string());
+        self.in_flight.store(games.len(), Ordering::Relaxed);
+        return shouts;
+    }
+
+    /// # read
+    /// looks at a game's memory
+    /// ## Returns:
+    /// what `read` returns, none if the game isn't remembered
+    pub fn read<R>(
+        &self,
+        route: &str,
+        game_id: &str,
+        read: impl FnOnce(&GameMemory) -> R,
+    ) -> Option<R> {
+        return self.games().get(&key(route, game_id)).map(read);
+    }
+
+    /// # write
+    /// changes a game's memory, a game that isn't remembered is left alone
+    /// ## Returns:
+    /// what `write` returns, none if the game isn't remembered
+    pub fn write<R>(
+        &self,
+        route: &str,
+        game_id: &str,
+        write: impl FnOnce(&mut GameMemory) -> R,
+    ) -> Option<R> {
+        return self.games().get_mut(&key(route, game_id)).map(write);
+    }
+
+    /// # end
+    /// stops remembering a game
+    /// ## Returns:
+    /// the game's memory, none if it wasn't remembered
+    pub fn end(&self, route: &str, game_id: &str) -> Option<GameMemory> {
+        let mut games = self.games();
+        let memory = games.remove(&key(route, game_id));
+        self.in_flight.store(games.len(), Ordering::Relaxed);
+        return memory;
+    }
+
+    /// # in_flight
+    /// the number of games being remembered, read without taking the lock
+    pub fn in_flight(&self) -> usize {
+        return self.in_flight.load(Ordering::Relaxed);
+    }
+
+    /// # sweep
+    /// forgets the games that haven't been touched for longer than the ttl
+    /// ## Returns:
+    /// the number of games forgotten
+    pub fn sweep(&self) -> usize {
+        let mut games = self.games();
+        let before = games.len();
+        games.retain(|_, memory| memory.touched.elapsed() < self.ttl);
+        self.in_flight.store(games.len(), Ordering::Relaxed);
+        let swept = before - games.len();
+        if swept > 0 {
+            info!("forgot {} games that never ended", swept);
+        }
+        return swept;
+    }
+}
+
+fn key(route: &str, game_id: &str) -> (String, String) {
+    return (route.to_string(), game_id.to_string());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cache::Cached;
+    use serde_json::json;
+
+    fn state(game_id: &str, turn: u32, them_length: usize) -> types::GameState {
+        let body = |x: i64, length: usize| {
+            (0..length)
+                .map(|y| json!({ "x": x, "y": y }))
+                .collect::<Vec<_>>()
+        };
+        let snake = |id: &str, x: i64, length: usize| {
+            json!({
+                "id": id,
+                "name": id,
+                "health": 90,
+                "body": body(x, length),
+                "head": { "x": x, "y": 0 },
+                "length": length,
+                "shout": ""
+            })
+        };
+        return serde_json::from_value(json!({
+            "game": { "id": game_id, "ruleset": { "name": "standard" }, "timeout": 500 },
+            "turn": turn,
+            "board": {
+                "height": 11,
+                "width": 11,
+                "food": [],
+                "snakes": [snake("me", 1, 3), snake("them", 5, them_length)],
+                "hazards": []
+            },
+            "you": snake("me", 1, 3)
+        }))
+        .unwrap();
+    }
+
+    #[test]
+    fn memory_is_bounded_and_tracks_growth() {
+        let store = GameStore::new(GAME_TTL);
+        store.start("", "bounded");
+        for turn in 0..MEMORY_LEN as u32 + 2 {
+            store.record("", &state("bounded", turn, 3 + turn as usize / 4), "up");
+        }
+        store
+            .read("", "bounded", |memory| {
+                assert_eq!(memory.states.len(), MEMORY_LEN);
+                assert_eq!(memory.moves.len(), MEMORY_LEN);
+                assert_eq!(memory.states[0].turn, 2);
+                assert_eq!(
+                    memory.opponent_lengths["them"],
+                    vec![(0, 3), (4, 4), (8, 5)]
+                );
+            })
+            .unwrap();
+        assert_eq!(store.in_flight(), 1);
+        assert!(store.end("", "bounded").is_some());
+        assert_eq!(store.in_flight(), 0);
+        assert!(store.read("", "bounded", |_| ()).is_none());
+    }
+
+    #[test]
+    fn shouts_are_reported_when_they_change() {
+        let store = GameStore::new(GAME_TTL);
+        store.start("", "shouting");
+        let shouts = ["", "hi", "hi", "", "hi", "bye"];
+        let heard: Vec<Vec<String>> = shouts
+            .iter()
+            .enumerate()
+            .map(|(turn, shout)| {
+                let mut state = state("shouting", turn as u32, 3);
+                state.board.snakes[1].shout = Some(shout.to_string());
+                // our own shout is never reported
+                state.board.snakes[0].shout = Some("me".to_string());
+                return store
+                    .record("", &state, "up")
+                    .into_iter()
+                    .map(|shout| format!("{}: {}", shout.snake, shout.shout))
+                    .collect();
+            })
+            .collect();
+        let expected: [&[&str]; 6] = [&[], &["them: hi"], &[], &[], &["them: hi"], &["them: bye"]];
+        assert_eq!(heard, expected);
+    }
+
+    #[test]
+    fn stale_games_are_swept() {
+        let store = GameStore::new(Duration::from_millis(20));
+        store.start("", "abandoned");
+        std::thread::sleep(Duration::from_millis(40));
+        store.start("", "fresh");
+        assert!(store.read("", "abandoned", |_| ()).is_none());
+        assert!(store.read("", "fresh", |_| ()).is_some());
+    }
+
+    #[test]
+    fn cache_keeps_the_previous_turn() {
+        let store = GameStore::new(Duration::from_millis(20));
+        store.start("", "cached");
+        store.record("", &state("cached", 10, 3), "up");
+        store.write("", "cached", |memory| {
+            memory.cache.insert(10, "plan", vec!["up", "left"]);
+            memory.cache.planned_path = Some(Cached {
+                turn: 10,
+                value: vec![types::Coord { x: 1, y: 1 }],
+            });
+        });
+
+        store.record("", &state("cached", 11, 3), "left");
+        store
+            .read("", "cached", |memory| {
+                assert_eq!(memory.cache.get(10, "plan"), Some(&vec!["up", "left"]));
+                // the right name with the wrong type isn't found
+                assert_eq!(memory.cache.get::<String>(10, "plan"), None);
+                assert!(memory.cache.planned_path.is_some());
+            })
+            .unwrap();
+
+        store.record("", &state("cached", 13, 3), "left");
+        store
+            .read("", "cached", |memory| {
+                assert_eq!(memory.cache.get::<Vec<&str>>(10, "plan"), None);
+                assert!(memory.cache.planned_path.is_none());
+                assert!(memory.cache.is_empty());
+            })
+            .unwrap();
+
+        // one game ends, the other is abandoned with its cache full and swept
+        store.start("", "abandoned");
+        store.write("", "abandoned", |memory| memory.cache.insert(0, "plan", 1));
+        assert!(store.end("", "cached").is_some());
+        std::thread::sleep(Duration::from_millis(40));
+        assert_eq!(store.sweep(), 1);
+        assert!(store.games().is_empty());
+        assert_eq!(store.in_flight(), 0);
+    }
+}
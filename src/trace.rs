@@ -0,0 +1,285 @@
+use crate::logic::{self, Branch};
+use crate::phase;
+use crate::search::eval::{self, MoveScore, Weights};
+use crate::spans::SpanTiming;
+use crate::types::{self, Coord};
+use serde::Serialize;
+use std::time::Instant;
+
+/// why the move pipeline chose a move. the pipeline fills it in as it goes and it's logged with every answer
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DecisionTrace {
+    pub game_id: String,
+    pub turn: u32,
+    /// the move we answered with
+    pub chosen: String,
+    /// the part of the pipeline that settled on `chosen`
+    pub branch: Branch,
+    /// every direction with the static features the pipeline could have judged it by
+    pub candidates: Vec<Candidate>,
+    /// where A* was heading, none if it didn't run or found nothing
+    pub a_star: Option<PathTrace>,
+    /// how long each phase of the pipeline took, in the order they ran
+    pub phases: Vec<Phase>,
+    /// the move the validator replaced, if it did
+    pub safety_override: Option<String>,
+    /// why the pipeline gave up on the move, the answer is the fallback when it did
+    pub error: Option<logic::DecisionError>,
+    /// whether a longer snake was running us down a lane against a wall, see `logic::sandwich_lane`
+    pub sandwiched: bool,
+    /// the longer snake running alongside us and for how many turns, see `logic::running_alongside`
+    pub alongside: Option<logic::Alongside>,
+    /// the way out of a pin we're following, from the move we answered with on, empty unless we're following one
+    pub escape: Vec<Coord>,
+    /// the hazard crossing A* weighed against the way around, none unless crossing would have cost us extra damage
+    pub crossing: Option<CrossingTrace>,
+    /// the length we were playing for, see `logic::LengthPolicy::length_target`
+    pub length_target: u32,
+    /// how hungry that length left us
+    pub hunger: logic::Hunger,
+    /// the phase of the game we played the move in
+    pub game_phase: phase::Phase,
+    /// the opponents starving with no food they can reach before us, by id, see `search::starvation`
+    pub written_off: Vec<String>,
+    /// the food we made for to keep it from a starving opponent
+    pub denials: Vec<Coord>,
+    /// what the other snakes shouted, a snake repeating its last shout isn't listed again
+    pub shouts: Vec<Shout>,
+    /// how long each span under the turn took, empty unless a subscriber is timing them, see `spans::SpanTimings`
+    pub spans: Vec<SpanTiming>,
+    /// from the request arriving to the answer being ready, set once the answer is
+    pub elapsed_ms: u64,
+}
+
+/// one of the four moves we could make
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Candidate {
+    #[serde(rename = "move")]
+    pub mv: String,
+    /// whether the move stays on the board and off every body
+    pub legal: bool,
+    /// the tiles reachable from where the move lands
+    pub flood_fill: i32,
+    pub degree: i32,
+    pub food_distance: i32,
+    pub center_distance: i32,
+    /// the move's static score with the weights of the phase we played it in
+    pub score: i32,
+    /// the share of the free tiles the move is connected to, when the moves diverged and it was ordered by it
+    pub connectivity: Option<f32>,
+    /// whether the move went through the divergence analysis and cleared its thresholds
+    pub diverged: bool,
+}
+
+/// a shout from another snake
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Shout {
+    /// the snake's name
+    pub snake: String,
+    pub shout: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PathTrace {
+    pub goal: Coord,
+    pub length: usize,
+    /// what A* costed the path at, hazards and first step penalties included
+    pub cost: u16,
+    /// our health on reaching the goal
+    pub health_at_goal: u8,
+    /// the tiles A* expanded to find it
+    pub nodes_expanded: u32,
+}
+
+/// a path across the hazards against the way around them, see `logic::HazardPolicy`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CrossingTrace {
+    /// the turns the crossing saves, `i32::MAX` when there's no way around
+    pub turns_saved: i32,
+    /// the damage the crossing deals
+    pub damage: i32,
+    pub crossed: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Phase {
+    pub name: &'static str,
+    pub micros: u64,
+}
+
+impl DecisionTrace {
+    pub(crate) fn new(game_id: &str, turn: u32) -> Self {
+        return DecisionTrace {
+            game_id: game_id.to_string(),
+            turn,
+            chosen: String::new(),
+            branch: Branch::Survival,
+            candidates: vec![],
+            a_star: None,
+            phases: vec![],
+            safety_override: None,
+            error: None,
+            sandwiched: false,
+            alongside: None,
+            escape: vec![],
+            crossing: None,
+            length_target: 0,
+            hunger: logic::Hunger::Normal,
+            game_phase: phase::Phase::Mid,
+            written_off: vec![],
+            denials: vec![],
+            shouts: vec![],
+            spans: vec![],
+            elapsed_ms: 0,
+        };
+    }
+
+    /// # score_candidates
+    /// records the static features of every move we could make
+    /// ## Arguments:
+    /// * board - the battlesnake game board
+    /// * game_board - the hashmap representation of the game board
+    /// * you - your battlesnake
+    /// * weights - the weights to score the moves with
+    pub(crate) fn score_candidates(
+        &mut self,
+        board: &types::Board,
+        game_board: &types::FastMap<Coord, types::Flags>,
+        you: &types::Battlesnake,
+        weights: &Weights,
+    ) {
+        let anywhere = logic::MoveOptions::default().avoid_snake_heads(false);
+        self.candidates = ["up", "right", "down", "left"]
+            .into_iter()
+            .map(|mv| {
+                let dir = types::DIRECTIONS[mv];
+                let tile = you.head + dir;
+                let score = MoveScore::new(board, game_board, you, &dir);
+                let legal = logic::can_move_board(&tile, board, game_board, you, &anywhere);
+                return Candidate {
+                    mv: mv.to_string(),
+                    legal,
+                    flood_fill: if legal {
+                        eval::reachable_tiles(&tile, board, game_board)
+                    } else {
+                        0
+                    },
+                    degree: score.degree,
+                    food_distance: score.food_distance,
+                    center_distance: score.center_distance,
+                    score: score.value(weights),
+                    connectivity: None,
+                    diverged: false,
+                };
+            })
+            .collect();
+    }
+
+    /// # score_connectivity
+    /// records what ordering the moves that clear the pipeline's thresholds found out about them
+    /// ## Arguments:
+    /// * head - our head
+    /// * scored - the moves from our head, see `logic::get_scored_adj_tiles`
+    pub(crate) fn score_connectivity(&mut self, head: Coord, scored: &[logic::ScoredTile]) {
+        for tile in scored {
+            let dir = tile.coord - head;
+            let candidate = self.candidates.iter_mut().find(|candidate| types::DIRECTIONS[candidate.mv.as_str()] == dir);
+            if let Some(candidate) = candidate {
+                candidate.connectivity = tile.connectivity;
+                candidate.diverged = tile.diverged;
+            }
+        }
+    }
+
+    /// # phase
+    /// records how long a phase took
+    /// ## Arguments:
+    /// * name - the phase
+    /// * started - when it started, it ends now
+    pub(crate) fn phase(&mut self, name: &'static str, started: Instant) {
+        self.phases.push(Phase {
+            name,
+            micros: started.elapsed().as_micros() as u64,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::Value;
+    use std::time::Instant;
+
+    // our head is at (1, 1) on a 5x5 board with our neck below it and food two tiles to the right
+    const STATE: &str = r#"{
+        "game": { "id": "traced", "ruleset": { "name": "standard" }, "timeout": 500 },
+        "turn": 4,
+        "board": {
+            "height": 5,
+            "width": 5,
+            "food": [{ "x": 3, "y": 1 }],
+            "snakes": [{
+                "id": "me", "name": "me", "health": 80, "length": 3, "shout": "",
+                "head": { "x": 1, "y": 1 },
+                "body": [{ "x": 1, "y": 1 }, { "x": 1, "y": 0 }, { "x": 0, "y": 0 }]
+            }],
+            "hazards": []
+        },
+        "you": {
+            "id": "me", "name": "me", "health": 80, "length": 3, "shout": "",
+            "head": { "x": 1, "y": 1 },
+            "body": [{ "x": 1, "y": 1 }, { "x": 1, "y": 0 }, { "x": 0, "y": 0 }]
+        }
+    }"#;
+
+    #[test]
+    fn trace_matches_the_move() {
+        let state: types::GameState = serde_json::from_str(STATE).unwrap();
+        let deadline = Instant::now() + std::time::Duration::from_secs(1);
+        let decision =
+            logic::get_move_until(&state.game, &state.turn, &state.board, &state.you, deadline);
+        let trace: Value = serde_json::to_value(&decision.trace).unwrap();
+
+        assert_eq!(trace["game_id"], "traced");
+        assert_eq!(trace["turn"], 4);
+        assert_eq!(trace["chosen"], decision.response["move"]);
+        assert_eq!(trace["branch"], decision.trace.branch.name());
+        assert_eq!(trace["branch"], "a_star");
+        assert_eq!(
+            trace["a_star"]["goal"],
+            serde_json::json!({ "x": 3, "y": 1 })
+        );
+        assert_eq!(trace["a_star"]["length"], 2);
+        assert_eq!(trace["a_star"]["cost"], 2);
+        assert_eq!(trace["a_star"]["health_at_goal"], 78);
+        assert!(trace["a_star"]["nodes_expanded"].as_u64().unwrap() >= 3);
+        assert_eq!(trace["safety_override"], Value::Null);
+        assert_eq!(trace["sandwiched"], false);
+        assert_eq!(trace["length_target"], decision.trace.length_target);
+        assert_eq!(trace["hunger"], "normal");
+        assert_eq!(trace["game_phase"], "early");
+        assert_eq!(trace["escape"], serde_json::json!([]));
+
+        let candidates = trace["candidates"].as_array().unwrap();
+        assert_eq!(candidates.len(), 4);
+        let chosen = candidates
+            .iter()
+            .find(|candidate| candidate["move"] == trace["chosen"])
+            .unwrap();
+        assert_eq!(chosen["legal"], true);
+        assert!(chosen["diverged"].is_boolean() && chosen.get("connectivity").is_some());
+        assert!(chosen["flood_fill"].as_i64().unwrap() > 0);
+        // our neck is below us
+        let down = candidates.iter().find(|c| c["move"] == "down").unwrap();
+        assert_eq!(down["legal"], false);
+        assert_eq!(down["flood_fill"], 0);
+
+        let phases: Vec<&str> = trace["phases"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|phase| phase["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(phases, ["escape", "pinned", "a_star", "lookahead", "endgame", "validator"]);
+    }
+}
@@ -0,0 +1,382 @@
+//! the `tune` command: coordinate descent over the phase weights. every candidate plays the same seeded arena games
+//! against the default pipeline, side by side on the rayon pool, and is judged by its winrate over them. game `n` of
+//! every evaluation is seeded the same so candidates are compared on the same starts and food. the endgame solver is
+//! the one part of the pipeline that reads the clock, a game it decides can still go another way on a slower run
+
+use crate::arena::{self, ArenaConfig, Policy};
+use crate::phase::PhaseWeights;
+use clap::Parser;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// the weights of a phase times the phases, the coordinates the descent steps along
+const COORDINATES: usize = 18;
+
+/// the largest a weight can be, the config refuses anything above it
+const MAX_WEIGHT: i32 = 1000;
+
+/// how to run a tuning session
+#[derive(Debug, Clone)]
+pub struct TuneConfig {
+    /// the games every candidate plays
+    pub games: u32,
+    pub size: u8,
+    /// games are drawn once this many turns have been played
+    pub turn_cap: u32,
+    /// game `n` of every evaluation is seeded with `seed + n`
+    pub seed: u64,
+    /// the most iterations to run, each one steps a single weight both ways
+    pub iterations: u32,
+    /// how far a weight is stepped as a share of its value, at least 1. halved after a sweep of every weight finds
+    /// nothing better
+    pub step: f32,
+    /// no game is started after this long, the iteration it would have been part of is dropped
+    pub budget: Duration,
+    /// where the descent starts
+    pub start: PhaseWeights,
+}
+
+impl Default for TuneConfig {
+    fn default() -> Self {
+        return TuneConfig {
+            games: 20,
+            size: 11,
+            turn_cap: 300,
+            seed: 0,
+            iterations: 36,
+            step: 0.5,
+            budget: Duration::from_secs(600),
+            start: PhaseWeights::DEFAULT,
+        };
+    }
+}
+
+/// one candidate the descent played
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trial {
+    pub iteration: u32,
+    /// the weight that was stepped, as `phase.name`
+    pub weight: String,
+    /// what it was stepped to
+    pub value: i32,
+    pub winrate: f32,
+    /// whether it beat the best weights so far and took their place
+    pub kept: bool,
+}
+
+/// how a tuning session went
+#[derive(Debug, Clone)]
+pub struct Tuned {
+    /// the best weights found
+    pub weights: PhaseWeights,
+    pub winrate: f32,
+    /// the starting weights' winrate
+    pub baseline: f32,
+    /// every candidate in the order it was played
+    pub trials: Vec<Trial>,
+    /// the iterations played in full, fewer than asked for when the budget ran out
+    pub iterations: u32,
+}
+
+/// # step
+/// a copy of the weights with one of them moved
+/// ## Arguments:
+/// * weights - the weights to step from
+/// * coordinate - the weight, `0..COORDINATES`, early to late and in declaration order within a phase
+/// * delta - how far to move it
+/// ## Returns:
+/// the stepped weights, the weight's name and where it was moved to, none when the step leaves the weight's range
+fn step(weights: &PhaseWeights, coordinate: usize, delta: i32) -> Option<(PhaseWeights, String, i32)> {
+    let mut stepped = *weights;
+    let (phase, phase_weights) = stepped.phases_mut().into_iter().nth(coordinate / 6)?;
+    let (name, value) = phase_weights.named_mut().into_iter().nth(coordinate % 6)?;
+    let moved = *value + delta;
+    if !(0..=MAX_WEIGHT).contains(&moved) {
+        return None;
+    }
+    *value = moved;
+    let name = format!("{}.{}", phase, name);
+    return Some((stepped, name, moved));
+}
+
+/// # value
+/// the current value of one of the weights, see `step` for the coordinates
+fn value(weights: &PhaseWeights, coordinate: usize) -> i32 {
+    let mut weights = *weights;
+    let (_, phase_weights) = weights.phases_mut().into_iter().nth(coordinate / 6).unwrap();
+    let (_, value) = phase_weights.named_mut().into_iter().nth(coordinate % 6).unwrap();
+    return *value;
+}
+
+/// # evaluate
+/// plays every candidate's games against the default pipeline, all of them side by side. the candidate and the
+/// default swap seats every game
+/// ## Arguments:
+/// * candidates - the weights to play
+/// * config - the games to play them in
+/// * deadline - no game is started after it
+/// ## Returns:
+/// each candidate's winrate, its wins and half its draws over its games, none when the deadline cut the games short
+fn evaluate(candidates: &[PhaseWeights], config: &TuneConfig, deadline: Instant) -> Option<Vec<f32>> {
+    let jobs: Vec<(usize, u32)> = (0..candidates.len())
+        .flat_map(|candidate| (0..config.games).map(move |game| (candidate, game)))
+        .collect();
+    let scores: Option<Vec<(usize, f32)>> = jobs
+        .par_iter()
+        .map(|(candidate, game)| {
+            if Instant::now() >= deadline {
+                return None;
+            }
+            let weighted = Policy::Weighted(candidates[*candidate]);
+            let seat = (*game % 2) as usize;
+            let policies = if seat == 0 { vec![weighted, Policy::Greedy] } else { vec![Policy::Greedy, weighted] };
+            let result = arena::run_games(&ArenaConfig {
+                games: 1,
+                size: config.size,
+                turn_cap: config.turn_cap,
+                seed: config.seed + *game as u64,
+                policies,
+            });
+            let record = &result.records[seat];
+            return Some((*candidate, record.wins as f32 + record.draws as f32 / 2.0));
+        })
+        .collect();
+    let mut winrates = vec![0.0; candidates.len()];
+    for (candidate, score) in scores? {
+        winrates[candidate] += score / config.games.max(1) as f32;
+    }
+    return Some(winrates);
+}
+
+/// # tune
+/// runs the coordinate descent: each iteration steps the next weight up and down, and keeps whichever of the two
+/// beats the best winrate so far
+/// ## Arguments:
+/// * config - the session's settings
+/// ## Returns:
+/// the session, or an error when the budget ran out before the starting weights had played
+pub fn tune(config: &TuneConfig) -> Result<Tuned, String> {
+    let deadline = Instant::now() + config.budget;
+    let baseline = evaluate(&[config.start], config, deadline)
+        .ok_or("the budget ran out before the starting weights had played their games")?[0];
+    let mut tuned = Tuned {
+        weights: config.start,
+        winrate: baseline,
+        baseline,
+        trials: vec![],
+        iterations: 0,
+    };
+    let mut share = config.step;
+    let mut improved = false;
+    for iteration in 0..config.iterations {
+        let coordinate = iteration as usize % COORDINATES;
+        if coordinate == 0 && iteration > 0 {
+            if !improved {
+                share /= 2.0;
+            }
+            improved = false;
+        }
+        let delta = ((value(&tuned.weights, coordinate) as f32 * share).round() as i32).max(1);
+        let steps: Vec<(PhaseWeights, String, i32)> = [delta, -delta]
+            .into_iter()
+            .filter_map(|delta| step(&tuned.weights, coordinate, delta))
+            .collect();
+        let candidates: Vec<PhaseWeights> = steps.iter().map(|(weights, _, _)| *weights).collect();
+        let Some(winrates) = evaluate(&candidates, config, deadline) else {
+            break;
+        };
+        let best = winrates
+            .iter()
+            .enumerate()
+            .filter(|(_, winrate)| **winrate > tuned.winrate)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index);
+        for (index, ((_, weight, value), winrate)) in steps.iter().zip(&winrates).enumerate() {
+            tuned.trials.push(Trial {
+                iteration: iteration + 1,
+                weight: weight.clone(),
+                value: *value,
+                winrate: *winrate,
+                kept: best == Some(index),
+            });
+        }
+        if let Some(index) = best {
+            tuned.weights = candidates[index];
+            tuned.winrate = winrates[index];
+            improved = true;
+        }
+        tuned.iterations += 1;
+    }
+    return Ok(tuned);
+}
+
+/// the part of the config the tuner writes
+#[derive(Serialize)]
+struct TunedConfig {
+    phase_weights: PhaseWeights,
+}
+
+/// # write_profile
+/// writes the tuned weights as a strategy config, ready for `--config` or to paste into `config.toml`
+/// ## Arguments:
+/// * tuned - the session
+/// * config - its settings, noted in the file's header
+/// * path - where to write it
+pub fn write_profile(tuned: &Tuned, config: &TuneConfig, path: &Path) -> Result<(), String> {
+    let tables = toml::to_string(&TunedConfig {
+        phase_weights: tuned.weights,
+    })
+    .map_err(|err| err.to_string())?;
+    let header = format!(
+        "# tuned over {} games a candidate on {}x{}, seed {}: won {:.3} against the defaults, from {:.3}\n\n",
+        config.games, config.size, config.size, config.seed, tuned.winrate, tuned.baseline
+    );
+    return fs::write(path, header + &tables).map_err(|err| format!("couldn't write {}: {}", path.display(), err));
+}
+
+/// the `tune` command's arguments
+#[derive(Parser, Debug)]
+#[command(name = "tune")]
+pub struct TuneArgs {
+    /// the games every candidate plays
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u32).range(1..))]
+    games: u32,
+    /// the width and height of the board
+    #[arg(long, default_value_t = 11, value_parser = clap::value_parser!(u8).range(5..))]
+    size: u8,
+    /// games are drawn once this many turns have been played
+    #[arg(long, default_value_t = 300)]
+    turns: u32,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// the most iterations to run, each steps one weight up and down
+    #[arg(long, default_value_t = 36)]
+    iterations: u32,
+    /// how far a weight is stepped as a share of its value
+    #[arg(long, default_value_t = 0.5)]
+    step: f32,
+    /// the wall clock budget in seconds, no game is started past it
+    #[arg(long, default_value_t = 600)]
+    budget: u64,
+    /// where the tuned weights are written, as a strategy config
+    #[arg(long, short, default_value = "tuned.toml")]
+    out: PathBuf,
+}
+
+/// # run
+/// the `tune` command: tunes the phase weights, prints every candidate it played and writes the best as a config
+/// ## Arguments:
+/// * args - the parsed arguments
+/// * out - where the history is written
+/// ## Returns:
+/// the exit code, 1 when the budget ran out before anything was played or the config couldn't be written
+pub fn run(args: &TuneArgs, out: &mut impl Write) -> i32 {
+    let config = TuneConfig {
+        games: args.games,
+        size: args.size,
+        turn_cap: args.turns,
+        seed: args.seed,
+        iterations: args.iterations,
+        step: args.step,
+        budget: Duration::from_secs(args.budget),
+        ..TuneConfig::default()
+    };
+    let written = tune(&config).and_then(|tuned| {
+        write_profile(&tuned, &config, &args.out)?;
+        return report(&tuned, &args.out, out).map_err(|err| err.to_string());
+    });
+    return match written {
+        Ok(_) => 0,
+        Err(err) => {
+            let _ = writeln!(out, "{}", err);
+            1
+        }
+    };
+}
+
+/// # report
+/// prints the starting winrate, a line per candidate and where the best weights went
+fn report(tuned: &Tuned, path: &Path, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "starting weights won {:.3}", tuned.baseline)?;
+    for trial in &tuned.trials {
+        writeln!(
+            out,
+            "iteration {}: {} = {} won {:.3}{}",
+            trial.iteration,
+            trial.weight,
+            trial.value,
+            trial.winrate,
+            if trial.kept { ", kept" } else { "" }
+        )?;
+    }
+    writeln!(
+        out,
+        "{} iterations, best won {:.3}, written to {}",
+        tuned.iterations,
+        tuned.winrate,
+        path.display()
+    )?;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::StrategyConfig;
+    use std::env;
+
+    #[test]
+    fn weights_step_within_their_range() {
+        let (stepped, name, moved) = step(&PhaseWeights::DEFAULT, 10, 2).unwrap();
+        assert_eq!((name.as_str(), moved), ("mid.food", 5));
+        assert_eq!(stepped.mid.food, 5);
+        assert_eq!(stepped.early, PhaseWeights::DEFAULT.early);
+        // the late game doesn't weigh food at all
+        assert_eq!(step(&PhaseWeights::DEFAULT, 16, -1), None);
+        assert_eq!(value(&PhaseWeights::DEFAULT, 15), 20);
+    }
+
+    #[test]
+    fn two_iterations_write_a_config() {
+        let config = TuneConfig {
+            games: 2,
+            size: 7,
+            turn_cap: 30,
+            seed: 5,
+            iterations: 2,
+            ..TuneConfig::default()
+        };
+        let tuned = tune(&config).unwrap();
+        assert_eq!(tuned.iterations, 2);
+        // the first two weights, early space and early length, each stepped up and down
+        let stepped: Vec<(u32, &str, i32)> =
+            tuned.trials.iter().map(|trial| (trial.iteration, trial.weight.as_str(), trial.value)).collect();
+        let expected = [(1, "early.space", 15), (1, "early.space", 5), (2, "early.length", 45), (2, "early.length", 15)];
+        assert_eq!(stepped, expected);
+        for trial in &tuned.trials {
+            assert!((0.0..=1.0).contains(&trial.winrate), "{:?}", trial);
+        }
+        assert!(tuned.winrate >= tuned.baseline);
+        let kept: Vec<&Trial> = tuned.trials.iter().filter(|trial| trial.kept).collect();
+        assert_eq!(kept.last().map_or(tuned.baseline, |trial| trial.winrate), tuned.winrate);
+        assert_eq!(kept.is_empty(), tuned.weights == PhaseWeights::DEFAULT);
+
+        let path = env::temp_dir().join(format!("battlesnake-tuned-{}.toml", std::process::id()));
+        write_profile(&tuned, &config, &path).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        let loaded = StrategyConfig::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(written.starts_with("# tuned over 2 games a candidate on 7x7, seed 5"), "{}", written);
+        assert_eq!(loaded.unwrap().phase_weights, tuned.weights);
+
+        let mut out: Vec<u8> = vec![];
+        report(&tuned, &path, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 6, "{}", out);
+        assert!(out.contains("iteration 2: early.length = 45 won"), "{}", out);
+    }
+}
@@ -1,11 +1,25 @@
 use bitflags::bitflags;
 use phf::phf_map;
+use rand::seq::SliceRandom;
+use rand::Rng;
 use rocket::serde::Deserialize;
+use rustc_hash::{FxHashMap, FxHashSet};
 use serde::Serialize;
 use serde_json::Value;
+use smallvec::{smallvec, SmallVec};
+use std::borrow::Cow;
+#[cfg(test)]
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher};
 use std::ops;
 
+/// a map keyed by small values like tiles, hashed with a hasher far cheaper than the default one. nothing here is
+/// keyed by what a client sends, so there's nobody to flood the map with collisions
+pub(crate) type FastMap<K, V> = FxHashMap<K, V>;
+/// a set of small values like tiles, see `FastMap`
+pub(crate) type FastSet<T> = FxHashSet<T>;
+
 pub static DIRECTIONS: phf::Map<&'static str, Coord> = phf_map! {
     "up" => Coord{y: 1, x:0},
     "right" => Coord{y:0, x:1},
@@ -20,53 +34,204 @@ bitflags! {
         const ALLY = 0x04;
         const SNAKE = 0x08;
         const HAZARD = 0x10;
-        const BOARD_TILE_OCCUPIED_MASK = 0x8;
+        /// a hazard that kills whatever moves onto it, see `Ruleset::hazard_walls`
+        const WALL = 0x20;
+        const BOARD_TILE_OCCUPIED_MASK = 0x28;
     }
 }
 
-#[macro_export]
 macro_rules! board_tile_is_free {
     ($tile:ident) => {
-        ($tile & types::Flags::BOARD_TILE_OCCUPIED_MASK).is_empty()
+        ($tile & $crate::types::Flags::BOARD_TILE_OCCUPIED_MASK).is_empty()
     };
 }
 
-#[macro_export]
 macro_rules! get_board_tile {
     ($board:ident, $x:expr, $y:expr) => {
-        *$board.get(&types::Coord{x:$x, y:$y}).unwrap_or(&types::Flags::EMPTY)
+        *$board.get(&$crate::types::Coord{x:$x, y:$y}).unwrap_or(&$crate::types::Flags::EMPTY)
     };
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+pub(crate) use {board_tile_is_free, get_board_tile};
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Game {
     pub id: String,
     pub ruleset: HashMap<String, Value>,
     pub timeout: u32,
+    /// the map the game was set up with, `standard`, `arcade_maze` and so on. older requests leave it out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map: Option<String>,
+}
+
+/// the map whose hazards are the walls of a maze
+pub const ARCADE_MAZE: &str = "arcade_maze";
+/// the map where every snake leaves a trail of hazards behind it, stacked on each tile it leaves and losing a stack a
+/// turn
+pub const SNAIL_MODE: &str = "snail_mode";
+/// hazard damage a snake at full health can't survive a single step of
+pub const LETHAL_HAZARD_DAMAGE: u8 = 100;
+
+/// the damage the engine deals per turn on a hazard tile unless the ruleset says otherwise
+pub const DEFAULT_HAZARD_DAMAGE: u8 = 14;
+/// the engine's default percent chance of spawning food each turn
+pub const DEFAULT_FOOD_SPAWN_CHANCE: u8 = 15;
+pub const DEFAULT_MINIMUM_FOOD: u8 = 1;
+
+/// how simulation models the royale shrink. the engine picks the side that shrinks at random so lookahead can't know which
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShrinkMode {
+    /// every side of the safe area shrinks at once
+    Pessimistic,
+    /// every side is covered with the odds the engine picks it, and deals that share of the hazard damage
+    Expected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Royale {
+    pub shrink_every_n_turns: u32,
+    pub mode: ShrinkMode,
+}
+
+/// the rules squadmates play by on the squad ruleset, a snake's squad is the one the engine sends with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Squad {
+    /// squadmates move through each other's bodies, a head to head between them still counts
+    pub allow_body_collisions: bool,
+    /// a squad is eliminated together, a squadmate out is every squadmate out
+    pub shared_elimination: bool,
+    /// squadmates all have the health of the healthiest of them
+    pub shared_health: bool,
+    /// squadmates all grow to the length of the longest of them
+    pub shared_length: bool,
+}
+
+/// the parts of the game's ruleset that simulation needs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ruleset {
+    pub hazard_damage: u8,
+    /// set when the hazards close in over the game
+    pub royale: Option<Royale>,
+    /// the percent chance a piece of food spawns at the end of a turn
+    pub food_spawn_chance: u8,
+    /// food always spawns when there's less than this on the board
+    pub minimum_food: u8,
+    /// whether the hazards are walls rather than costly tiles, on the arcade maze or wherever they deal lethal damage
+    pub hazard_walls: bool,
+    /// whether the hazard stacks are trails that lose a stack a turn, on snail mode
+    pub snail: bool,
+    /// set on the squad ruleset
+    pub squad: Option<Squad>,
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        return Ruleset {
+            hazard_damage: DEFAULT_HAZARD_DAMAGE,
+            royale: None,
+            food_spawn_chance: DEFAULT_FOOD_SPAWN_CHANCE,
+            minimum_food: DEFAULT_MINIMUM_FOOD,
+            hazard_walls: false,
+            snail: false,
+            squad: None,
+        };
+    }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+impl Ruleset {
+    /// # with_shrink
+    /// the ruleset with the royale shrink modelled another way, a game that doesn't shrink is left as it is
+    /// ## Arguments:
+    /// * mode - how simulation models the shrink
+    pub fn with_shrink(self, mode: ShrinkMode) -> Ruleset {
+        let royale = self.royale.map(|royale| Royale { mode, ..royale });
+        return Ruleset { royale, ..self };
+    }
+}
+
+impl From<&Game> for Ruleset {
+    /// reads the ruleset sent with every request, the royale shrink is modelled pessimistically, see `with_shrink`
+    fn from(game: &Game) -> Self {
+        let settings = game.ruleset.get("settings");
+        let setting = |name: &str, default: u8| {
+            settings
+                .and_then(|settings| settings.get(name))
+                .and_then(Value::as_u64)
+                .map_or(default, |value| value.min(u8::MAX as u64) as u8)
+        };
+        let name = game.ruleset.get("name").and_then(Value::as_str);
+        let is_royale = name == Some("royale");
+        let royale = settings
+            .and_then(|settings| settings.get("royale"))
+            .and_then(|royale| royale.get("shrinkEveryNTurns"))
+            .and_then(Value::as_u64)
+            .filter(|turns| is_royale && *turns > 0)
+            .map(|turns| Royale {
+                shrink_every_n_turns: turns as u32,
+                mode: ShrinkMode::Pessimistic,
+            });
+        let squad = settings.and_then(|settings| settings.get("squad"));
+        let squad_setting = |name: &str| squad.and_then(|squad| squad.get(name)).and_then(Value::as_bool) == Some(true);
+        let squad = (name == Some("squad")).then(|| Squad {
+            allow_body_collisions: squad_setting("allowBodyCollisions"),
+            shared_elimination: squad_setting("sharedElimination"),
+            shared_health: squad_setting("sharedHealth"),
+            shared_length: squad_setting("sharedLength"),
+        });
+        let hazard_damage = setting("hazardDamagePerTurn", DEFAULT_HAZARD_DAMAGE);
+        return Ruleset {
+            hazard_damage,
+            royale,
+            food_spawn_chance: setting("foodSpawnChance", DEFAULT_FOOD_SPAWN_CHANCE),
+            minimum_food: setting("minimumFood", DEFAULT_MINIMUM_FOOD),
+            hazard_walls: game.map.as_deref() == Some(ARCADE_MAZE) || hazard_damage >= LETHAL_HAZARD_DAMAGE,
+            snail: game.map.as_deref() == Some(SNAIL_MODE) || name == Some(SNAIL_MODE),
+            squad,
+        };
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Board {
     pub height: u8,
     pub width: u8,
     pub food: Vec<Coord>,
     pub snakes: Vec<Battlesnake>,
     pub hazards: Vec<Coord>,
+    /// tiles that might have become hazards during simulation and the odds they did, only used by `ShrinkMode::Expected`
+    #[serde(skip)]
+    pub hazard_odds: Vec<(Coord, f32)>,
+    /// whether the hazards are walls, flagged `WALL` on the game board so nothing moves or fills onto them. the
+    /// pipeline sets it from `Ruleset::hazard_walls`, see `with_rules`
+    #[serde(skip)]
+    pub hazard_walls: bool,
 }
-fn add_coords_to_board(board: &mut HashMap<Coord, Flags>, points: &Vec<Coord>, value: Flags) {
+fn add_coords_to_board<S: BuildHasher>(
+    board: &mut HashMap<Coord, Flags, S>,
+    points: &Vec<Coord>,
+    value: Flags,
+) {
     for point in points {
         let mut tile_val = value;
         let old_val = board.get(point);
         // some tiles can be occupied by multiple board entities
-        if old_val.is_some(){
-            tile_val = tile_val | *old_val.unwrap();
+        if let Some(old_val) = old_val {
+            tile_val |= *old_val;
         }
         board.insert(*point, tile_val);
     }
 }
 impl Board {
-    pub fn to_game_board(&self) -> HashMap<Coord, Flags> {
-        let mut board = HashMap::new();
+    pub(crate) fn to_game_board(&self) -> FastMap<Coord, Flags> {
+        let _span = tracing::trace_span!("to_game_board").entered();
+        return self.game_board_with();
+    }
+
+    /// # game_board_with
+    /// the game board in a map hashed by any hasher, to compare the hashers
+    fn game_board_with<S: BuildHasher + Default>(&self) -> HashMap<Coord, Flags, S> {
+        let mut board = HashMap::default();
 
         // populate food
         add_coords_to_board(&mut board, &self.food, Flags::FOOD);
@@ -78,9 +243,641 @@ impl Board {
         }
 
         // populate hazards
-        add_coords_to_board(&mut board, &self.hazards, Flags::HAZARD);
+        add_coords_to_board(&mut board, &self.hazards, self.hazard_flags());
         return board;
     }
+
+    /// # hazard_flags
+    /// the flags a hazard tile gets on the game board, walls are occupied for good
+    pub fn hazard_flags(&self) -> Flags {
+        if self.hazard_walls {
+            return Flags::HAZARD | Flags::WALL;
+        }
+        return Flags::HAZARD;
+    }
+
+    /// # with_rules
+    /// the board as the rules have it: a copy with its hazards made walls when the rules say they are, the board
+    /// itself otherwise
+    pub fn with_rules(&self, rules: &Ruleset) -> Cow<'_, Board> {
+        if rules.hazard_walls && !self.hazard_walls {
+            return Cow::Owned(Board {
+                hazard_walls: true,
+                ..self.clone()
+            });
+        }
+        return Cow::Borrowed(self);
+    }
+
+    /// # to_occupancy
+    /// the tiles under every snake's body, the same tiles the game board flags `SNAKE`
+    pub(crate) fn to_occupancy(&self) -> Occupancy {
+        let mut occupancy = Occupancy::new(self.width, self.height);
+        for snake in &self.snakes {
+            for seg in &snake.body {
+                occupancy.set(*seg);
+            }
+        }
+        return occupancy;
+    }
+
+    /// # in_bounds
+    /// determines if a tile lies on the board
+    pub fn in_bounds(&self, tile: &Coord) -> bool {
+        return tile.x >= 0 && tile.y >= 0 && tile.x < self.width as i16 && tile.y < self.height as i16;
+    }
+
+    /// # render
+    /// draws the board as text with the top row first. snakes are lettered in board order, the head in upper case and
+    /// the body in lower case, food is `*`, hazards are `~`, or `#` when they're walls, and empty tiles are `.`
+    pub fn render(&self) -> String {
+        let mut rows: Vec<String> = vec![];
+        for y in (0..self.height as i16).rev() {
+            let mut row = String::new();
+            for x in 0..self.width as i16 {
+                let tile = Coord { x, y };
+                let snake = self.snakes.iter().enumerate().find(|(_, snake)| snake.body.contains(&tile));
+                let symbol = match snake {
+                    Some((i, snake)) => {
+                        let letter = (b'a' + (i % 26) as u8) as char;
+                        if snake.head == tile {
+                            letter.to_ascii_uppercase()
+                        } else {
+                            letter
+                        }
+                    }
+                    None if self.food.contains(&tile) => '*',
+                    None if self.hazards.contains(&tile) && self.hazard_walls => '#',
+                    None if self.hazards.contains(&tile) => '~',
+                    None => '.',
+                };
+                row.push(symbol);
+            }
+            rows.push(row);
+        }
+        return rows.join("\n");
+    }
+
+    /// # hazard_damage
+    /// the damage a snake takes for ending its move on a tile, once for every hazard stacked on it
+    fn hazard_damage(&self, tile: &Coord, damage: u8) -> u8 {
+        let stacks = self.hazards.iter().filter(|hazard| *hazard == tile).count();
+        if stacks > 0 {
+            return (damage as usize * stacks).min(u8::MAX as usize) as u8;
+        }
+        let odds: f32 = self
+            .hazard_odds
+            .iter()
+            .filter(|(hazard, _)| hazard == tile)
+            .map(|(_, odds)| odds)
+            .sum();
+        return (damage as f32 * odds.min(1.0)).round() as u8;
+    }
+
+    /// # shrink
+    /// covers the outermost ring of the royale safe area, the bounding box of the tiles that aren't hazards yet.
+    /// expected mode only ever models the next shrink, the safe area doesn't move until a side is known to be covered
+    fn shrink(&mut self, mode: ShrinkMode) {
+        let mut bounds: Option<(i16, i16, i16, i16)> = None;
+        for x in 0..self.width as i16 {
+            for y in 0..self.height as i16 {
+                if self.hazards.contains(&Coord { x, y }) {
+                    continue;
+                }
+                bounds = Some(match bounds {
+                    Some((min_x, max_x, min_y, max_y)) => {
+                        (min_x.min(x), max_x.max(x), min_y.min(y), max_y.max(y))
+                    }
+                    None => (x, x, y, y),
+                });
+            }
+        }
+        let (min_x, max_x, min_y, max_y) = match bounds {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                let tile = Coord { x, y };
+                let sides = [x == min_x, x == max_x, y == min_y, y == max_y]
+                    .iter()
+                    .filter(|side| **side)
+                    .count();
+                if sides == 0 || self.hazards.contains(&tile) {
+                    continue;
+                }
+                match mode {
+                    ShrinkMode::Pessimistic => self.hazards.push(tile),
+                    ShrinkMode::Expected => {
+                        if !self.hazard_odds.iter().any(|(hazard, _)| *hazard == tile) {
+                            self.hazard_odds.push((tile, sides as f32 / 4.0));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// # play_turn
+    /// plays a turn for good, `simulate_turn` without the journal, for a referee that never takes a turn back
+    /// ## Arguments:
+    /// * moves - the unit direction each snake moves in, in the same order as `snakes`
+    /// * turn - the turn number the board is at once the moves are made
+    /// * rules - the hazard damage, squad and royale settings
+    pub fn play_turn(&mut self, moves: &[Coord], turn: u32, rules: &Ruleset) {
+        self.simulate_turn(moves, turn, rules);
+    }
+
+    /// # simulate_turn
+    /// advances the board by one turn using the standard ruleset: snakes move, lose health, take hazard damage, eat, and are eliminated.
+    /// in squad games the squadmates then share what the squad settings have them share, and in royale the hazards
+    /// close in on every turn that's a multiple of the shrink interval
+    /// ## Arguments:
+    /// * moves - the unit direction each snake moves in, in the same order as `snakes`
+    /// * turn - the turn number the board is at once the moves are made
+    /// * rules - the hazard damage, squad and royale settings
+    /// ## Returns:
+    /// a journal of the changes made, pass it to `undo` to restore the board. eliminated snakes are removed from the board and kept in the journal
+    pub(crate) fn simulate_turn(&mut self, moves: &[Coord], turn: u32, rules: &Ruleset) -> UndoToken {
+        let occupied = self.to_occupancy();
+        return self.simulate_turn_with(moves, turn, rules, &occupied);
+    }
+
+    /// # simulate_turn_with
+    /// `simulate_turn` for a caller that keeps the board's occupancy already, so the collision checks needn't build it
+    /// ## Arguments:
+    /// * occupied - the tiles under the bodies before the turn, as `to_occupancy` has them
+    pub(crate) fn simulate_turn_with(
+        &mut self,
+        moves: &[Coord],
+        turn: u32,
+        rules: &Ruleset,
+        occupied: &Occupancy,
+    ) -> UndoToken {
+        #[cfg(feature = "verify")]
+        let before = self.clone();
+        let mut token = UndoToken {
+            hazards: self.hazards.len(),
+            hazard_odds: self.hazard_odds.len(),
+            ..UndoToken::default()
+        };
+        for (snake, dir) in self.snakes.iter_mut().zip(moves) {
+            token.moved.push(snake.move_snake(dir));
+        }
+
+        for i in 0..self.snakes.len() {
+            let head = self.snakes[i].head;
+            if !self.food.contains(&head) {
+                let damage = self.hazard_damage(&head, rules.hazard_damage);
+                self.snakes[i].health = self.snakes[i].health.saturating_sub(damage);
+            }
+        }
+
+        for (i, snake) in self.snakes.iter_mut().enumerate() {
+            if self.food.contains(&snake.head) {
+                snake.grow();
+                token.moved[i].grew = true;
+            }
+        }
+        let mut index = 0;
+        while index < self.food.len() {
+            let food = self.food[index];
+            if self.snakes.iter().any(|snake| snake.head == food) {
+                // indices are recorded after earlier removals so undo can insert in reverse order
+                token.eaten.push((index, food));
+                self.food.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+
+        // starvation and walls are resolved before collisions so a snake leaving the board can't take anyone with it
+        let mut out: Vec<bool> = self
+            .snakes
+            .iter()
+            .map(|snake| snake.health == 0 || !self.in_bounds(&snake.head))
+            .collect();
+        let collided = self.collisions(&out, occupied, rules);
+        for (i, hit) in collided.into_iter().enumerate() {
+            out[i] |= hit;
+        }
+        if let Some(squad) = rules.squad {
+            self.share(&squad, &mut out, &mut token);
+        }
+
+        let mut survivors: Vec<Battlesnake> = vec![];
+        for (i, (snake, dead)) in self.snakes.drain(..).zip(out).enumerate() {
+            if dead {
+                token.eliminated.push((i, snake));
+            } else {
+                survivors.push(snake);
+            }
+        }
+        self.snakes = survivors;
+
+        if let Some(royale) = rules.royale {
+            if royale.shrink_every_n_turns > 0 && turn.is_multiple_of(royale.shrink_every_n_turns) {
+                self.shrink(royale.mode);
+            }
+        }
+        #[cfg(feature = "verify")]
+        crate::verify::check_turn(&before, moves, rules, self, &token);
+        return token;
+    }
+
+    /// # collisions
+    /// which snakes ran into a body or lost a head to head, the snakes already out neither collide nor are run into
+    /// ## Arguments:
+    /// * out - the snakes that starved or left the board, in the same order as `snakes`
+    /// * occupied - the tiles under the bodies before the turn. every segment behind a head was under a body then, so
+    ///   only a head that moved onto one of those tiles needs checking against the bodies
+    /// * rules - whether squadmates move through each other's bodies
+    fn collisions(&self, out: &[bool], occupied: &Occupancy, rules: &Ruleset) -> Vec<bool> {
+        let live = || self.snakes.iter().zip(out).filter(|(_, out)| !**out).map(|(snake, _)| snake);
+        let passes_through = rules.squad.is_some_and(|squad| squad.allow_body_collisions);
+        return self
+            .snakes
+            .iter()
+            .zip(out)
+            .map(|(snake, out)| {
+                let into_body = || {
+                    live()
+                        .filter(|other| !(passes_through && snake.squadmate(other)))
+                        .any(|other| other.body.iter().skip(1).any(|seg| *seg == snake.head))
+                };
+                let head_to_head = || {
+                    live().any(|other| other != snake && other.head == snake.head && other.length >= snake.length)
+                };
+                !out && ((occupied.is_occupied(&snake.head) && into_body()) || head_to_head())
+            })
+            .collect();
+    }
+
+    /// # share
+    /// what squadmates share once the turn's eliminations are known, the way the engine does it: the snakes still in
+    /// take the health and length of the healthiest and longest of their squad, eliminated squadmates included, and a
+    /// squad with one of its own out is out with it
+    /// ## Arguments:
+    /// * squad - what the squadmates share
+    /// * out - the snakes eliminated this turn, in the same order as `snakes`
+    /// * token - the turn's journal, the segments stacked on to share a length are recorded in it
+    fn share(&mut self, squad: &Squad, out: &mut [bool], token: &mut UndoToken) {
+        let eliminated = out.to_vec();
+        for i in 0..self.snakes.len() {
+            if eliminated[i] {
+                continue;
+            }
+            let mates = || (0..self.snakes.len()).filter(|j| self.snakes[i].squadmate(&self.snakes[*j]));
+            let health = mates().map(|j| self.snakes[j].health).max().unwrap_or(0);
+            let length = mates().map(|j| self.snakes[j].length).max().unwrap_or(0);
+            if squad.shared_elimination && mates().any(|j| eliminated[j]) {
+                out[i] = true;
+            }
+            let snake = &mut self.snakes[i];
+            if squad.shared_health {
+                snake.health = snake.health.max(health);
+            }
+            if squad.shared_length && length > snake.length {
+                let tail = *snake.body.last().unwrap_or(&snake.head);
+                let grown = length - snake.length;
+                snake.body.extend(std::iter::repeat_n(tail, grown as usize));
+                snake.length = length;
+                token.moved[i].shared = grown;
+            }
+        }
+    }
+
+    /// # spawn_food
+    /// spawns food the way the engine does at the end of a turn: enough to reach the minimum, otherwise a single piece
+    /// with the ruleset's chance. food lands on a uniformly random tile without a snake or food on it
+    /// ## Arguments:
+    /// * rules - the spawn chance and minimum food
+    /// * rng - the random number generator, seed it for reproducible simulations
+    /// ## Returns:
+    /// the number of food spawned, they're appended to `food` so `unspawn_food` can remove them
+    pub fn spawn_food(&mut self, rules: &Ruleset, rng: &mut impl Rng) -> usize {
+        let wanted = if self.food.len() < rules.minimum_food as usize {
+            rules.minimum_food as usize - self.food.len()
+        } else if rng.gen_range(0..100) < rules.food_spawn_chance {
+            1
+        } else {
+            0
+        };
+        if wanted == 0 {
+            return 0;
+        }
+        let occupancy = self.to_occupancy();
+        let mut free: Vec<Coord> = vec![];
+        for x in 0..self.width as i16 {
+            for y in 0..self.height as i16 {
+                let tile = Coord { x, y };
+                if !self.food.contains(&tile) && !occupancy.is_occupied(&tile) {
+                    free.push(tile);
+                }
+            }
+        }
+        let spawned: Vec<Coord> = free.choose_multiple(rng, wanted).copied().collect();
+        self.food.extend(&spawned);
+        return spawned.len();
+    }
+
+    /// # unspawn_food
+    /// removes the food added by the last `spawn_food`
+    pub(crate) fn unspawn_food(&mut self, spawned: usize) {
+        self.food.truncate(self.food.len() - spawned);
+    }
+
+    /// # undo
+    /// reverts a turn made by `simulate_turn`, turns must be undone in the reverse order they were simulated
+    /// ## Arguments:
+    /// * token - the journal returned by `simulate_turn`
+    pub(crate) fn undo(&mut self, token: UndoToken) {
+        self.hazards.truncate(token.hazards);
+        self.hazard_odds.truncate(token.hazard_odds);
+        for (index, snake) in token.eliminated {
+            self.snakes.insert(index, snake);
+        }
+        for (index, food) in token.eaten.into_iter().rev() {
+            self.food.insert(index, food);
+        }
+        for (snake, moved) in self.snakes.iter_mut().zip(token.moved) {
+            snake.undo_move(moved);
+        }
+    }
+}
+
+/// the changes a single snake went through in one simulated turn
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SnakeUndo {
+    /// the tail segment that was popped by moving
+    pub tail: Option<Coord>,
+    pub health: u8,
+    pub length: u32,
+    pub grew: bool,
+    /// the segments stacked on the tail to share a squadmate's length
+    pub shared: u32,
+}
+
+/// journal of a simulated turn, returned by `Board::simulate_turn` and consumed by `Board::undo`
+#[derive(Debug, Default)]
+pub(crate) struct UndoToken {
+    /// one entry per snake, in board order before the turn
+    pub moved: Vec<SnakeUndo>,
+    /// food that was eaten and its index in `food` at the time it was removed
+    pub eaten: Vec<(usize, Coord)>,
+    /// eliminated snakes and their index in `snakes` before the turn
+    pub eliminated: Vec<(usize, Battlesnake)>,
+    /// previous values of every game board tile changed, filled in by whoever maintains the game board
+    pub flags: Vec<(Coord, Option<Flags>)>,
+    /// the board hash before the turn, filled in by whoever maintains the hash
+    pub hash: u64,
+    /// the number of hazards before the turn, the shrink only ever appends
+    pub hazards: usize,
+    pub hazard_odds: usize,
+}
+
+#[cfg(test)]
+thread_local! {
+    /// the body segments read building tile owners on this thread, for the tests to count
+    pub static SEGMENTS_SCANNED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// where a body tile is: the snake's index on the board and the segment's index in its body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TileOwner {
+    pub snake: usize,
+    pub segment: usize,
+}
+
+/// which snake each body tile belongs to, read off the bodies in one pass. where bodies overlap the tile goes to the
+/// first snake in board order and the segment nearest its head, the one a scan of the bodies would find first
+#[derive(Debug, Default)]
+pub(crate) struct TileOwners {
+    owners: FastMap<Coord, TileOwner>,
+}
+
+impl TileOwners {
+    pub fn new(snakes: &[Battlesnake]) -> Self {
+        let mut owners = FastMap::default();
+        for (snake, battlesnake) in snakes.iter().enumerate() {
+            for (segment, tile) in battlesnake.body.iter().enumerate() {
+                owners.entry(*tile).or_insert(TileOwner { snake, segment });
+            }
+            #[cfg(test)]
+            SEGMENTS_SCANNED.with(|scanned| scanned.set(scanned.get() + battlesnake.body.len()));
+        }
+        return TileOwners { owners };
+    }
+
+    /// # get
+    /// ## Returns:
+    /// the owner of a tile, none when no snake is on it
+    pub fn get(&self, tile: &Coord) -> Option<TileOwner> {
+        return self.owners.get(tile).copied();
+    }
+}
+
+/// how long a body tile stays taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Clearance {
+    /// the turns until the tail has moved off the tile
+    turns: u16,
+    /// the turn its snake could eat by, from when on its body may stop moving up
+    eats_in: u16,
+}
+
+/// the tiles under the bodies and how many turns each stays under one, every tail moving up a tile a turn. a snake that
+/// just ate keeps its tail for a turn, and a snake that could reach food is taken as frozen from the turn it could eat
+/// it, since it could grow any turn from then on
+#[derive(Debug, Default)]
+pub(crate) struct TimedOccupancy {
+    tiles: FastMap<Coord, Clearance>,
+}
+
+impl TimedOccupancy {
+    /// # new
+    /// ## Arguments:
+    /// * board - the battlesnake game board
+    /// * you_id - the id of our snake, we decide whether we eat so our body is never frozen
+    pub fn new(board: &Board, you_id: &str) -> Self {
+        let mut tiles: FastMap<Coord, Clearance> = FastMap::default();
+        for snake in &board.snakes {
+            let length = snake.body.len();
+            // the engine stacks the tail of a snake that just ate, one sent without it keeps its tail a turn all the same
+            let stacked = length >= 2 && snake.body[length - 1] == snake.body[length - 2];
+            let grown = (snake.health == 100 && !stacked) as usize;
+            let eats_in = match snake.id == you_id {
+                true => u16::MAX,
+                false => board.food.iter().map(|food| snake.head.steps(food) as u16).min().unwrap_or(u16::MAX),
+            };
+            for (segment, tile) in snake.body.iter().enumerate() {
+                let turns = (length - segment + grown) as u16;
+                let clearance = tiles.entry(*tile).or_insert(Clearance { turns, eats_in });
+                clearance.turns = clearance.turns.max(turns);
+                clearance.eats_in = clearance.eats_in.min(eats_in);
+            }
+            #[cfg(test)]
+            SEGMENTS_SCANNED.with(|scanned| scanned.set(scanned.get() + length));
+        }
+        return TimedOccupancy { tiles };
+    }
+
+    /// # clears_in
+    /// the turns until the tails have moved off a tile, whether or not its snake eats, 0 for a free tile
+    pub fn clears_in(&self, tile: &Coord) -> u16 {
+        return self.tiles.get(tile).map_or(0, |clearance| clearance.turns);
+    }
+
+    /// # occupied_at
+    /// whether a tile is still under a body `k` turns from now, 0 for now. a snake that could eat stops moving its tail
+    /// up from that turn on, the tiles it had already left stay free
+    pub fn occupied_at(&self, tile: &Coord, k: u16) -> bool {
+        return self.tiles.get(tile).is_some_and(|clearance| clearance.turns > k.min(clearance.eats_in));
+    }
+
+    /// # occupancy_at
+    /// the tiles still under a body `k` turns from now
+    pub fn occupancy_at(&self, width: u8, height: u8, k: u16) -> Occupancy {
+        let mut occupancy = Occupancy::new(width, height);
+        for tile in self.tiles.keys().filter(|tile| self.occupied_at(tile, k)) {
+            occupancy.set(*tile);
+        }
+        return occupancy;
+    }
+}
+
+/// how many hazards are stacked on each tile, the engine lists a tile once for every stack and deals its damage once
+/// for each. on snail mode the stacks are the trails the snakes leave and lose a stack a turn, everywhere else they
+/// stay as deep as they are
+#[derive(Debug, Default)]
+pub(crate) struct HazardStacks {
+    stacks: FastMap<Coord, u8>,
+    decays: bool,
+}
+
+impl HazardStacks {
+    /// # new
+    /// ## Arguments:
+    /// * board - the battlesnake game board
+    /// * rules - the game's rules, whether the stacks decay
+    pub fn new(board: &Board, rules: &Ruleset) -> Self {
+        let mut stacks: FastMap<Coord, u8> = FastMap::default();
+        for hazard in &board.hazards {
+            let stack = stacks.entry(*hazard).or_insert(0);
+            *stack = stack.saturating_add(1);
+        }
+        return HazardStacks {
+            stacks,
+            decays: rules.snail,
+        };
+    }
+
+    /// # at
+    /// the hazards stacked on a tile `k` turns from now, 0 for now
+    pub fn at(&self, tile: &Coord, k: u16) -> u8 {
+        let stacks = self.stacks.get(tile).copied().unwrap_or(0);
+        if self.decays {
+            return stacks.saturating_sub(k.min(u8::MAX as u16) as u8);
+        }
+        return stacks;
+    }
+
+    /// # on
+    /// the hazards that deal their damage to a snake ending its `turn`th move from now on a tile, 1 for the next move.
+    /// the engine deals the damage before it takes the turn's stack off
+    pub fn on(&self, tile: &Coord, turn: u16) -> u8 {
+        return self.at(tile, turn.saturating_sub(1));
+    }
+
+    /// # damage_on
+    /// the damage for ending our `turn`th move from now on a tile
+    /// ## Arguments:
+    /// * tile - the tile we'd end the move on
+    /// * turn - the move, 1 for the next one
+    /// * damage - the damage a single stack deals
+    pub fn damage_on(&self, tile: &Coord, turn: u16, damage: u8) -> u8 {
+        return (self.on(tile, turn) as u16 * damage as u16).min(u8::MAX as u16) as u8;
+    }
+}
+
+/// the tiles taken, by a snake's body wherever the simulator uses it, one bitmask a row, so asking about a tile is a
+/// shift and copying or snapshotting the whole board is a few words. a tile off the board, or past the 128th column, is
+/// never taken and setting it does nothing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Occupancy {
+    width: u8,
+    /// kept inline for boards up to the largest the engine hosts, so a simulated turn building one doesn't allocate
+    rows: SmallVec<[u128; 25]>,
+}
+
+impl Occupancy {
+    /// # new
+    /// an empty board of the given dimensions
+    pub fn new(width: u8, height: u8) -> Self {
+        return Occupancy {
+            width,
+            rows: smallvec![0; height as usize],
+        };
+    }
+
+    /// the row a tile is in and its bit in the row
+    fn bit(&self, tile: &Coord) -> Option<(usize, u128)> {
+        if tile.x < 0 || tile.y < 0 || tile.x >= self.width.min(128) as i16 || tile.y as usize >= self.rows.len() {
+            return None;
+        }
+        return Some((tile.y as usize, 1 << tile.x));
+    }
+
+    /// # is_occupied
+    /// whether a tile is taken
+    pub fn is_occupied(&self, tile: &Coord) -> bool {
+        return match self.bit(tile) {
+            Some((row, bit)) => self.rows[row] & bit != 0,
+            None => false,
+        };
+    }
+
+    /// # set
+    /// marks a tile occupied
+    pub fn set(&mut self, tile: Coord) {
+        if let Some((row, bit)) = self.bit(&tile) {
+            self.rows[row] |= bit;
+        }
+    }
+
+    /// # clear
+    /// marks a tile free
+    pub fn clear(&mut self, tile: Coord) {
+        if let Some((row, bit)) = self.bit(&tile) {
+            self.rows[row] &= !bit;
+        }
+    }
+
+    /// # count_free
+    /// the number of tiles on the board that aren't taken
+    pub fn count_free(&self) -> u16 {
+        let occupied: u32 = self.rows.iter().map(|row| row.count_ones()).sum();
+        return (self.width as u16 * self.rows.len() as u16).saturating_sub(occupied as u16);
+    }
+}
+
+/// # reported_latency
+/// reads a snake's latency, which the engine sends as a string of milliseconds but tests and our own replays write as a
+/// number. an empty string or zero is what the engine sends before the snake has answered anything
+/// ## Arguments:
+/// * deserializer - the latency field's deserializer
+/// ## Returns:
+/// the latency in milliseconds, or none when there isn't one
+pub(crate) fn reported_latency<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let ms = match Value::deserialize(deserializer)? {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.trim().parse::<f64>().ok(),
+        _ => None,
+    };
+    return Ok(ms.filter(|ms| *ms >= 1.0).map(|ms| ms.round() as u32));
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -91,41 +888,94 @@ pub struct Battlesnake {
     pub body: Vec<Coord>,
     pub head: Coord,
     pub length: u32,
-    // latency: String,
+    /// how many milliseconds the snake took to answer the last turn, none before its first answer or when the engine
+    /// didn't say
+    #[serde(default, deserialize_with = "reported_latency")]
+    pub latency: Option<u32>,
     pub shout: Option<String>,
+    /// the squad the snake plays in, empty outside of squad games
+    #[serde(default)]
+    pub squad: String,
 }
 impl PartialEq for Battlesnake {
     fn eq(&self, other: &Self) -> bool {
         return self.id == other.id;
     }
 }
-// this will be useful for the minimax approach
-// impl Battlesnake {
-//     pub fn move_snake(&mut self, game_board: &mut Vec<Vec<Flags>>, move_to: &Coord) {
-//         self.head = *move_to;
-//         self.body.insert(0, *move_to);
-//         if game_board[move_to.x as usize][move_to.y as usize] != Flags::FOOD {
-//             if self.health > 0 {
-//                 self.health -= 1;
-//             }
-//             self.body.pop();
-//             game_board[move_to.x as usize][move_to.y as usize] = Flags::EMPTY
-//         } else {
-//             self.health = 100;
-//         }
-//     }
-// }
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub struct IndexedCoord{
-    pub coord: Coord,
-    pub connected_index: f32
-}
-
-#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Copy, Clone, Hash)]
+impl Battlesnake {
+    /// # move_snake
+    /// moves the snake one tile in the given direction, the tail follows the head and the snake loses one health
+    /// ## Arguments:
+    /// * dir - the unit direction to move in
+    /// ## Returns:
+    /// the state needed to undo the move
+    pub(crate) fn move_snake(&mut self, dir: &Coord) -> SnakeUndo {
+        let undo = SnakeUndo {
+            tail: None,
+            health: self.health,
+            length: self.length,
+            grew: false,
+            shared: 0,
+        };
+        self.head = self.head + *dir;
+        self.body.insert(0, self.head);
+        let tail = self.body.pop();
+        self.health = self.health.saturating_sub(1);
+        return SnakeUndo { tail, ..undo };
+    }
+
+    /// # undo_move
+    /// reverts `move_snake`, and `grow` if the snake ate
+    pub(crate) fn undo_move(&mut self, undo: SnakeUndo) {
+        let stacked = undo.grew as usize + undo.shared as usize;
+        self.body.truncate(self.body.len().saturating_sub(stacked));
+        if !self.body.is_empty() {
+            self.body.remove(0);
+        }
+        if let Some(tail) = undo.tail {
+            self.body.push(tail);
+        }
+        if let Some(&head) = self.body.first() {
+            self.head = head;
+        }
+        self.health = undo.health;
+        self.length = undo.length;
+    }
+
+    /// # squadmate
+    /// whether another snake plays in the same squad as this one
+    pub fn squadmate(&self, other: &Battlesnake) -> bool {
+        return !self.squad.is_empty() && self.squad == other.squad && self != other;
+    }
+
+    /// # heading
+    /// the direction the snake last moved in, its head minus its neck. none while its body is still stacked on its head
+    pub fn heading(&self) -> Option<Coord> {
+        let neck = self.body.get(1)?;
+        return (*neck != self.head).then(|| self.head - *neck);
+    }
+
+    /// # grow
+    /// feeds the snake, restoring its health and stacking a new segment on its tail
+    pub fn grow(&mut self) {
+        if let Some(&tail) = self.body.last() {
+            self.body.push(tail);
+        }
+        self.length += 1;
+        self.health = 100;
+    }
+}
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Copy, Clone)]
 pub struct Coord {
     pub x: i16,
     pub y: i16,
 }
+impl Hash for Coord {
+    // both halves packed into one word, the hasher is fed once instead of once a field
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u32(self.x as u16 as u32 | (self.y as u16 as u32) << 16);
+    }
+}
 impl ops::Add<Coord> for Coord {
     type Output = Coord;
     fn add(self, c: Coord) -> Self::Output {
@@ -149,12 +999,488 @@ impl Coord {
         let vec = *self - *c;
         return ((vec.x.pow(2) + vec.y.pow(2)) as f32).sqrt();
     }
+
+    /// # steps
+    /// the number of moves between two tiles on an empty board
+    pub fn steps(&self, c: &Coord) -> i16 {
+        let vec = *self - *c;
+        return vec.x.abs() + vec.y.abs();
+    }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct GameState {
     pub game: Game,
     pub turn: u32,
     pub board: Board,
     pub you: Battlesnake,
 }
+
+/// a `#rrggbb` color, the only form the engine accepts
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Color(String);
+
+impl Color {
+    /// # parse
+    /// ## Returns:
+    /// the color, none if it isn't a `#` followed by six hex digits
+    pub fn parse(value: &str) -> Option<Color> {
+        let digits = value.strip_prefix('#')?;
+        if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        return Some(Color(value.to_string()));
+    }
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return write!(f, "{}", self.0);
+    }
+}
+
+/// the answer to `GET /`, how the snake looks and who wrote it
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SnakeInfo {
+    pub apiversion: String,
+    pub author: String,
+    pub color: Color,
+    pub head: String,
+    pub tail: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::TurnContext;
+    use crate::fixtures;
+    use crate::logic::tests::random_board;
+    use crate::search::graph;
+    use crate::search::state::SearchBoard;
+    use crate::seed;
+    use std::collections::hash_map::RandomState;
+    use std::path::Path;
+    use std::time::Instant;
+
+    fn boards() -> Vec<Board> {
+        return fixtures::load(Path::new(fixtures::FIXTURE_DIR))
+            .unwrap()
+            .into_iter()
+            .map(|fixture| fixture.state.board)
+            .collect();
+    }
+
+    #[test]
+    fn lethal_hazards_are_walls() {
+        let rules = |map: Option<&str>, damage: u8| {
+            let game: Game = serde_json::from_value(serde_json::json!({
+                "id": "maze",
+                "ruleset": { "name": "standard", "settings": { "hazardDamagePerTurn": damage } },
+                "map": map,
+                "timeout": 500
+            }))
+            .unwrap();
+            return Ruleset::from(&game);
+        };
+        assert!(rules(Some(ARCADE_MAZE), 14).hazard_walls);
+        assert!(rules(None, 100).hazard_walls);
+        assert!(!rules(Some("standard"), 14).hazard_walls);
+
+        let state: GameState =
+            serde_json::from_str(&std::fs::read_to_string("testdata/fixtures/maze_corridor.json").unwrap()).unwrap();
+        let walled = state.board.with_rules(&Ruleset::from(&state.game));
+        let wall = Coord { x: 1, y: 3 };
+        assert_eq!(walled.to_game_board()[&wall], Flags::HAZARD | Flags::WALL);
+        assert_eq!(state.board.to_game_board()[&wall], Flags::HAZARD);
+        assert!(walled.render().contains("##########."));
+        assert!(matches!(state.board.with_rules(&Ruleset::default()), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn snail_stacks_decay_a_stack_a_turn() {
+        let state: GameState =
+            serde_json::from_str(&std::fs::read_to_string("testdata/fixtures/snail_trails.json").unwrap()).unwrap();
+        let rules = Ruleset::from(&state.game);
+        assert!(rules.snail && !rules.hazard_walls);
+        assert!(!Ruleset::default().snail);
+
+        let fresh = Coord { x: 5, y: 4 };
+        let old = Coord { x: 4, y: 4 };
+        let stacks = HazardStacks::new(&state.board, &rules);
+        assert_eq!((stacks.at(&fresh, 0), stacks.at(&old, 0)), (5, 3));
+        assert_eq!((stacks.on(&fresh, 1), stacks.on(&old, 2)), (5, 2));
+        assert_eq!(stacks.at(&old, 7), 0);
+        assert_eq!(stacks.damage_on(&fresh, 3, 14), 42);
+        assert_eq!(stacks.at(&Coord { x: 5, y: 3 }, 0), 0);
+        // elsewhere a stack stays as deep as it is
+        let standing = HazardStacks::new(&state.board, &Ruleset::default());
+        assert_eq!(standing.on(&fresh, 4), 5);
+
+        // the simulator deals a stack's damage once for each hazard on it
+        assert_eq!(state.board.hazard_damage(&fresh, 14), 70);
+        assert_eq!(state.board.hazard_damage(&fresh, 60), u8::MAX);
+    }
+
+    /// squadmates `a` and `b` and a snake of its own `c`. `a` moves into `b`'s body and `b` eats as it moves on
+    fn squad_turn() -> (Board, [Coord; 3]) {
+        let snake = |id: &str, squad: &str, health: u8, body: &[(i16, i16)]| {
+            let body: Vec<serde_json::Value> =
+                body.iter().map(|(x, y)| serde_json::json!({ "x": x, "y": y })).collect();
+            serde_json::json!({
+                "id": id, "name": id, "health": health, "body": body, "head": body[0], "length": body.len(),
+                "squad": squad
+            })
+        };
+        let board: Board = serde_json::from_value(serde_json::json!({
+            "width": 7, "height": 7, "food": [{ "x": 4, "y": 4 }], "hazards": [],
+            "snakes": [
+                snake("a", "red", 50, &[(2, 2), (1, 2), (0, 2)]),
+                snake("b", "red", 90, &[(3, 4), (3, 3), (3, 2), (3, 1)]),
+                snake("c", "blue", 70, &[(6, 6), (6, 5), (6, 4)]),
+            ]
+        }))
+        .unwrap();
+        let (left, right) = (Coord { x: -1, y: 0 }, Coord { x: 1, y: 0 });
+        return (board, [right, right, left]);
+    }
+
+    /// # simulate_squad
+    /// plays the squad turn under the settings, checks undoing it restores the board, and hands back the board after
+    /// the turn
+    fn simulate_squad(squad: Squad) -> Board {
+        let (mut board, moves) = squad_turn();
+        let before = serde_json::to_value(&board).unwrap();
+        let rules = Ruleset {
+            squad: Some(squad),
+            ..Ruleset::default()
+        };
+        let token = board.simulate_turn(&moves, 1, &rules);
+        let after = board.clone();
+        board.undo(token);
+        assert_eq!(serde_json::to_value(&board).unwrap(), before);
+        return after;
+    }
+
+    fn ids(board: &Board) -> Vec<&str> {
+        return board.snakes.iter().map(|snake| snake.id.as_str()).collect();
+    }
+
+    #[test]
+    fn squadmates_move_through_each_other_when_allowed() {
+        assert_eq!(ids(&simulate_squad(Squad::default())), ["b", "c"]);
+        let through = Squad {
+            allow_body_collisions: true,
+            ..Squad::default()
+        };
+        assert_eq!(ids(&simulate_squad(through)), ["a", "b", "c"]);
+        // not through anyone else's
+        let (mut board, moves) = squad_turn();
+        board.snakes[1].squad = "blue".to_string();
+        let rules = Ruleset {
+            squad: Some(through),
+            ..Ruleset::default()
+        };
+        board.simulate_turn(&moves, 1, &rules);
+        assert_eq!(ids(&board), ["b", "c"]);
+    }
+
+    #[test]
+    fn squads_are_eliminated_together_when_shared() {
+        let shared = Squad {
+            shared_elimination: true,
+            ..Squad::default()
+        };
+        assert_eq!(ids(&simulate_squad(shared)), ["c"]);
+    }
+
+    #[test]
+    fn squadmates_share_the_best_health() {
+        let shared = Squad {
+            allow_body_collisions: true,
+            shared_health: true,
+            ..Squad::default()
+        };
+        let health = |board: &Board| board.snakes.iter().map(|snake| snake.health).collect::<Vec<u8>>();
+        // `b` ate, `a` takes its full health and `c` keeps its own
+        assert_eq!(health(&simulate_squad(shared)), [100, 100, 69]);
+        let apart = Squad {
+            allow_body_collisions: true,
+            ..Squad::default()
+        };
+        assert_eq!(health(&simulate_squad(apart)), [49, 100, 69]);
+    }
+
+    #[test]
+    fn squadmates_share_the_longest_length() {
+        let shared = Squad {
+            allow_body_collisions: true,
+            shared_length: true,
+            ..Squad::default()
+        };
+        let board = simulate_squad(shared);
+        let a = &board.snakes[0];
+        assert_eq!((a.length, board.snakes[1].length, board.snakes[2].length), (5, 5, 3));
+        let tail = Coord { x: 1, y: 2 };
+        assert_eq!(a.body, [Coord { x: 3, y: 2 }, Coord { x: 2, y: 2 }, tail, tail, tail]);
+
+        // a search keeps its hash and game board in step with the stacked segments
+        let (board, moves) = squad_turn();
+        let mut state = SearchBoard::new(board, 0);
+        let rules = Ruleset {
+            squad: Some(Squad {
+                shared_health: true,
+                ..shared
+            }),
+            ..Ruleset::default()
+        };
+        let (hash, game_board) = (state.hash, state.game_board.clone());
+        let token = state.apply(&moves, &rules);
+        assert_eq!(state.hash, crate::search::zobrist::hash_board(&state.board));
+        assert_eq!(state.game_board, state.board.to_game_board());
+        state.undo(token);
+        assert_eq!((state.hash, &state.game_board), (hash, &game_board));
+    }
+
+    #[test]
+    fn squad_settings_are_read_on_the_squad_ruleset() {
+        let rules = |name: &str| {
+            let game: Game = serde_json::from_value(serde_json::json!({
+                "id": "squad",
+                "ruleset": {
+                    "name": name,
+                    "settings": { "squad": { "allowBodyCollisions": true, "sharedElimination": true } }
+                },
+                "timeout": 500
+            }))
+            .unwrap();
+            return Ruleset::from(&game);
+        };
+        let squad = Squad {
+            allow_body_collisions: true,
+            shared_elimination: true,
+            ..Squad::default()
+        };
+        assert_eq!(rules("squad").squad, Some(squad));
+        assert_eq!(rules("standard").squad, None);
+    }
+
+    #[test]
+    fn shrink_mode_is_picked_for_royale_games() {
+        let rules = |name: &str| {
+            let game: Game = serde_json::from_value(serde_json::json!({
+                "id": "royale",
+                "ruleset": { "name": name, "settings": { "royale": { "shrinkEveryNTurns": 25 } } },
+                "timeout": 500
+            }))
+            .unwrap();
+            return Ruleset::from(&game);
+        };
+        let royale = rules("royale");
+        assert_eq!(royale.royale.map(|royale| royale.mode), Some(ShrinkMode::Pessimistic));
+        let expected = royale.with_shrink(ShrinkMode::Expected).royale.unwrap();
+        assert_eq!((expected.mode, expected.shrink_every_n_turns), (ShrinkMode::Expected, 25));
+        assert_eq!(rules("standard").with_shrink(ShrinkMode::Expected), rules("standard"));
+    }
+
+    #[test]
+    fn game_boards_agree_whatever_the_hasher() {
+        for board in boards() {
+            let fast = board.to_game_board();
+            let sip: HashMap<Coord, Flags> = board.game_board_with::<RandomState>();
+            assert_eq!(fast.len(), sip.len());
+            for (tile, flags) in &sip {
+                assert_eq!(fast.get(tile), Some(flags));
+            }
+        }
+    }
+
+    /// building the game board and looking every tile up, with the default hasher and with `FastMap`'s, then
+    /// building it and running A* the way a move does. run with
+    /// `cargo test --release hashing_bench -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn hashing_bench() {
+        const RUNS: u32 = 2000;
+        let boards = boards();
+        fn lookups<S: BuildHasher>(board: &Board, game_board: &HashMap<Coord, Flags, S>) -> u32 {
+            let mut found = 0;
+            for x in 0..board.width as i16 {
+                for y in 0..board.height as i16 {
+                    found += game_board.get(&Coord { x, y }).is_some() as u32;
+                }
+            }
+            return found;
+        }
+        let started = Instant::now();
+        for _ in 0..RUNS {
+            for board in &boards {
+                let game_board = board.game_board_with::<RandomState>();
+                std::hint::black_box(lookups(board, &game_board));
+            }
+        }
+        let sip = started.elapsed() / RUNS;
+        let started = Instant::now();
+        for _ in 0..RUNS {
+            for board in &boards {
+                let game_board = board.to_game_board();
+                std::hint::black_box(lookups(board, &game_board));
+            }
+        }
+        let fast = started.elapsed() / RUNS;
+        let started = Instant::now();
+        for _ in 0..RUNS / 10 {
+            for board in &boards {
+                let game_board = board.to_game_board();
+                let ctx = TurnContext::sample(board, &game_board, &board.snakes[0]);
+                let options = crate::logic::MoveOptions::default();
+                std::hint::black_box(graph::a_star(&ctx, &options, 16));
+            }
+        }
+        let a_star = started.elapsed() / (RUNS / 10);
+        println!(
+            "{} boards: built and looked up in {:?} hashed by default, {:?} fast; built and searched in {:?}",
+            boards.len(),
+            sip,
+            fast,
+            a_star
+        );
+    }
+
+    /// the collision check the simulator made before it had the occupancy: every head against every body
+    fn scanned_collisions(board: &Board, out: &[bool]) -> Vec<bool> {
+        let mut collided: Vec<bool> = vec![false; board.snakes.len()];
+        for (i, snake) in board.snakes.iter().enumerate() {
+            if out[i] {
+                continue;
+            }
+            for (j, other) in board.snakes.iter().enumerate() {
+                if out[j] {
+                    continue;
+                }
+                if other.body.iter().skip(1).any(|seg| *seg == snake.head)
+                    || (i != j && other.head == snake.head && other.length >= snake.length)
+                {
+                    collided[i] = true;
+                    break;
+                }
+            }
+        }
+        return collided;
+    }
+
+    /// the fixtures and random boards with every snake moved a few times at random, the way the simulator sees them
+    /// before it checks for collisions, along with the snakes it would have put out already
+    fn moved_boards() -> Vec<(Board, Vec<bool>, Occupancy)> {
+        let mut rng = seed::rng("moved_boards", 0);
+        let mut boards = boards();
+        boards.extend((0..20).map(|_| random_board(&mut rng)));
+        let mut moved = vec![];
+        for mut board in boards {
+            for _ in 0..4 {
+                let occupied = board.to_occupancy();
+                for snake in board.snakes.iter_mut() {
+                    let dir = *DIRECTIONS.values().nth(rng.gen_range(0..4)).unwrap();
+                    snake.move_snake(&dir);
+                }
+                let out: Vec<bool> = board
+                    .snakes
+                    .iter()
+                    .map(|snake| {
+                        snake.health == 0 || !board.in_bounds(&snake.head) || rng.gen_bool(0.1)
+                    })
+                    .collect();
+                moved.push((board.clone(), out, occupied));
+            }
+        }
+        return moved;
+    }
+
+    #[test]
+    fn occupancy_matches_the_game_board() {
+        for (board, _, _) in moved_boards() {
+            let game_board = board.to_game_board();
+            let occupancy = board.to_occupancy();
+            let mut free = 0;
+            for x in -1..=board.width as i16 {
+                for y in -1..=board.height as i16 {
+                    let tile = Coord { x, y };
+                    let snake = game_board.get(&tile).is_some_and(|flags| flags.contains(Flags::SNAKE));
+                    assert_eq!(occupancy.is_occupied(&tile), snake && board.in_bounds(&tile), "{:?}", tile);
+                    free += (board.in_bounds(&tile) && !snake) as u16;
+                }
+            }
+            assert_eq!(occupancy.count_free(), free);
+        }
+    }
+
+    #[test]
+    fn occupancy_sets_and_clears_tiles() {
+        let mut occupancy = Occupancy::new(19, 7);
+        let tile = Coord { x: 18, y: 6 };
+        occupancy.set(tile);
+        occupancy.set(tile);
+        assert!(occupancy.is_occupied(&tile));
+        assert_eq!(occupancy.count_free(), 19 * 7 - 1);
+        // nothing off the board is taken, whatever's set there
+        occupancy.set(Coord { x: 19, y: 0 });
+        occupancy.set(Coord { x: 0, y: -1 });
+        assert!(!occupancy.is_occupied(&Coord { x: 19, y: 0 }));
+        assert_eq!(occupancy.count_free(), 19 * 7 - 1);
+        occupancy.clear(tile);
+        assert!(!occupancy.is_occupied(&tile));
+        assert_eq!(occupancy, Occupancy::new(19, 7));
+    }
+
+    #[test]
+    fn collisions_agree_with_a_scan() {
+        for (board, out, occupied) in moved_boards() {
+            let collided = board.collisions(&out, &occupied, &Ruleset::default());
+            assert_eq!(collided, scanned_collisions(&board, &out), "{}", board.render());
+        }
+    }
+
+    /// the simulator's collision check with the occupancy and by scanning the bodies, then whole turns simulated and
+    /// undone the way a search does, with the occupancy kept up to date instead of built every turn.
+    /// run with `cargo test --release simulator_bench -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn simulator_bench() {
+        const RUNS: u32 = 2000;
+        let moved = moved_boards();
+        let started = Instant::now();
+        for _ in 0..RUNS {
+            for (board, out, _) in &moved {
+                std::hint::black_box(scanned_collisions(board, out));
+            }
+        }
+        let scanned = started.elapsed() / RUNS;
+        let started = Instant::now();
+        for _ in 0..RUNS {
+            for (board, out, occupied) in &moved {
+                std::hint::black_box(board.collisions(out, occupied, &Ruleset::default()));
+            }
+        }
+        let occupied = started.elapsed() / RUNS;
+        let mut rng = seed::rng("simulator_bench", 0);
+        let mut boards: Vec<SearchBoard> = moved.into_iter().map(|(board, _, _)| SearchBoard::new(board, 0)).collect();
+        let started = Instant::now();
+        for _ in 0..RUNS {
+            for state in boards.iter_mut() {
+                let moves: Vec<Coord> = (0..state.board.snakes.len())
+                    .map(|_| *DIRECTIONS.values().nth(rng.gen_range(0..4)).unwrap())
+                    .collect();
+                let token = state.apply(&moves, &Ruleset::default());
+                state.undo(token);
+            }
+        }
+        let simulated = started.elapsed() / RUNS;
+        println!(
+            "{} boards: collisions found in {:?} scanning, {:?} by occupancy; turns simulated and undone in {:?}",
+            boards.len(),
+            scanned,
+            occupied,
+            simulated
+        );
+    }
+}
@@ -0,0 +1,334 @@
+//! slow but plainly correct references for the fast paths: which tiles are open, the flood fill and a simulated turn,
+//! each worked out straight from the board's snakes, food and hazards with no game board, occupancy, connectivity
+//! labels or journal in between. built with the `verify` feature, `get_move` and the simulator run them alongside the
+//! fast paths and panic with the board when the two disagree, so a bug in the bitsets or the incremental bookkeeping
+//! fails the fixtures and the property tests instead of quietly losing games. without the feature none of it is built
+//! outside the tests
+
+use crate::context::TurnContext;
+use crate::logic::MoveOptions;
+use crate::search::state::SearchBoard;
+use crate::search::zobrist;
+use crate::types::{self, Coord, Ruleset};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+
+/// a snake as the comparisons see it: its id, health, length, head and body
+type Snapshot = (String, u8, u32, Coord, Vec<Coord>);
+
+/// what a turn leaves on the board: the snakes still in, the ids of the snakes eliminated and the food left
+type Outcome = (Vec<Snapshot>, Vec<String>, Vec<Coord>);
+
+fn snapshot(snake: &types::Battlesnake) -> Snapshot {
+    return (snake.id.clone(), snake.health, snake.length, snake.head, snake.body.clone());
+}
+
+/// # diverged
+/// panics with what diverged, both answers and the board, drawn and as json to paste into a fixture
+fn diverged(what: &str, fast: impl Debug, reference: impl Debug, board: &types::Board) -> ! {
+    panic!(
+        "{} diverged from the reference\nfast: {:?}\nreference: {:?}\n{}\n{}",
+        what,
+        fast,
+        reference,
+        board.render(),
+        serde_json::to_string(board).unwrap_or_default()
+    );
+}
+
+/// # is_open
+/// whether a snake can move onto a tile: it's on the board, isn't a wall, and no body is on it but the tail tip of a
+/// snake that hasn't just eaten. with `avoid_heads` the heads of the other snakes at least as long as `you` and the
+/// tiles next to them are closed too
+pub(crate) fn is_open(tile: &Coord, board: &types::Board, you: &types::Battlesnake, avoid_heads: bool) -> bool {
+    if !board.in_bounds(tile) || (board.hazard_walls && board.hazards.contains(tile)) {
+        return false;
+    }
+    for snake in &board.snakes {
+        for (i, segment) in snake.body.iter().enumerate() {
+            let moving_tail = i == snake.body.len() - 1 && snake.health < 100;
+            if segment == tile && !moving_tail {
+                return false;
+            }
+        }
+        // a tail tip on a hazard or food is held up by the game board's flags, the fast path has never let it go
+        if snake.body.last() == Some(tile) && (board.hazards.contains(tile) || board.food.contains(tile)) {
+            return false;
+        }
+        let longer = snake.id != you.id && snake.length >= you.length;
+        if avoid_heads && longer && (snake.head.x - tile.x).abs() + (snake.head.y - tile.y).abs() <= 1 {
+            return false;
+        }
+    }
+    return true;
+}
+
+/// # connected_tiles
+/// a breadth first fill from the open tiles next to a tile, counted the way `logic`'s fill counts: the tiles reached,
+/// and the tile itself and the fill's empty frontier on top
+/// ## Arguments:
+/// * tile - the tile the fill starts from
+/// * board - the board the heuristics play on
+/// * you - your battlesnake
+/// * exclude_tiles - the tiles the fill can't go through
+pub(crate) fn connected_tiles(
+    tile: &Coord,
+    board: &types::Board,
+    you: &types::Battlesnake,
+    exclude_tiles: &[Coord],
+) -> u32 {
+    let open = |tile: &Coord| is_open(tile, board, you, true) && !exclude_tiles.contains(tile);
+    let mut reached: Vec<Coord> = vec![];
+    let mut frontier = VecDeque::from([*tile]);
+    while let Some(next) = frontier.pop_front() {
+        for dir in types::DIRECTIONS.values() {
+            let adj = next + *dir;
+            if open(&adj) && !reached.contains(&adj) {
+                reached.push(adj);
+                frontier.push_back(adj);
+            }
+        }
+    }
+    return 2 + reached.len() as u32;
+}
+
+/// # simulate
+/// plays a turn on a copy of the board by the standard rules: every snake moves and loses a health, the snakes off
+/// food take the hazard damage, the snakes on food eat it, then the starved and the snakes off the board are out, and
+/// the snakes that ran into a body or into a head at least as long as them
+/// ## Returns:
+/// what the turn leaves, none for the squad and royale rules and for moves that don't cover every snake
+fn simulate(board: &types::Board, moves: &[Coord], rules: &Ruleset) -> Option<Outcome> {
+    if rules.squad.is_some() || rules.royale.is_some() || moves.len() != board.snakes.len() {
+        return None;
+    }
+    let mut snakes = board.snakes.clone();
+    for (snake, dir) in snakes.iter_mut().zip(moves) {
+        snake.head = snake.head + *dir;
+        snake.body.insert(0, snake.head);
+        snake.body.pop();
+        snake.health = snake.health.saturating_sub(1);
+    }
+    for snake in snakes.iter_mut() {
+        if board.food.contains(&snake.head) {
+            continue;
+        }
+        let stacks = board.hazards.iter().filter(|hazard| **hazard == snake.head).count();
+        let odds: f32 = board.hazard_odds.iter().filter(|(tile, _)| *tile == snake.head).map(|(_, odds)| odds).sum();
+        let damage = if stacks > 0 {
+            (rules.hazard_damage as usize * stacks).min(u8::MAX as usize) as u8
+        } else {
+            (rules.hazard_damage as f32 * odds.min(1.0)).round() as u8
+        };
+        snake.health = snake.health.saturating_sub(damage);
+    }
+    for snake in snakes.iter_mut() {
+        if board.food.contains(&snake.head) {
+            snake.health = 100;
+            snake.length += 1;
+            snake.body.push(*snake.body.last().unwrap_or(&snake.head));
+        }
+    }
+    let eaten = |food: &&Coord| snakes.iter().any(|snake| snake.head == **food);
+    let food: Vec<Coord> = board.food.iter().filter(|food| !eaten(food)).copied().collect();
+    let starved: Vec<bool> = snakes.iter().map(|snake| snake.health == 0 || !board.in_bounds(&snake.head)).collect();
+    let out: Vec<bool> = snakes
+        .iter()
+        .enumerate()
+        .map(|(i, snake)| {
+            if starved[i] {
+                return true;
+            }
+            let others = snakes.iter().enumerate().filter(|(j, _)| !starved[*j]).map(|(_, other)| other);
+            let hit_body = others.clone().any(|other| other.body[1..].contains(&snake.head));
+            let lost_head = others
+                .clone()
+                .any(|other| other.id != snake.id && other.head == snake.head && other.length >= snake.length);
+            return hit_body || lost_head;
+        })
+        .collect();
+    let survivors = snakes.iter().zip(&out).filter(|(_, out)| !**out).map(|(snake, _)| snapshot(snake)).collect();
+    let eliminated = snakes.iter().zip(&out).filter(|(_, out)| **out).map(|(snake, _)| snake.id.clone()).collect();
+    return Some((survivors, eliminated, food));
+}
+
+/// # check_open_tiles
+/// checks which tiles the turn's context lets us move onto against `is_open`, with and without the longer heads
+/// avoided, for every tile on the board and one off each edge
+pub(crate) fn check_open_tiles(ctx: &TurnContext) {
+    for x in -1..=ctx.board.width as i16 {
+        for y in -1..=ctx.board.height as i16 {
+            let tile = Coord { x, y };
+            for avoid_heads in [true, false] {
+                let fast = ctx.can_move(&tile, &MoveOptions::default().avoid_snake_heads(avoid_heads));
+                let reference = is_open(&tile, ctx.board, ctx.you, avoid_heads);
+                if fast != reference {
+                    let what = format!("whether {:?} is open, avoiding heads {}", tile, avoid_heads);
+                    diverged(&what, fast, reference, ctx.board);
+                }
+            }
+        }
+    }
+}
+
+/// # check_connected
+/// checks a connected tile count against `connected_tiles`
+/// ## Arguments:
+/// * ctx - the turn's context
+/// * tile - the tile the count is from
+/// * exclude_tiles - the tiles the count left out
+/// * fast - the count, by the connectivity or the fill
+/// * limit - the count the fill stopped at, `u32::MAX` for none
+pub(crate) fn check_connected(ctx: &TurnContext, tile: &Coord, exclude_tiles: &[Coord], fast: u32, limit: u32) {
+    let reference = connected_tiles(tile, ctx.board, ctx.you, exclude_tiles).min(limit);
+    if fast != reference {
+        let what = format!("the tiles connected to {:?} without {:?}", tile, exclude_tiles);
+        diverged(&what, fast, reference, ctx.board);
+    }
+}
+
+/// # check_turn
+/// checks a simulated turn against `simulate`
+/// ## Arguments:
+/// * before - the board before the turn
+/// * moves - the moves made, in the same order as `before.snakes`
+/// * rules - the rules the turn was played by
+/// * after - the board after the turn
+/// * token - the turn's journal
+pub(crate) fn check_turn(
+    before: &types::Board,
+    moves: &[Coord],
+    rules: &Ruleset,
+    after: &types::Board,
+    token: &types::UndoToken,
+) {
+    let Some(reference) = simulate(before, moves, rules) else {
+        return;
+    };
+    let mut eliminated: Vec<(usize, String)> =
+        token.eliminated.iter().map(|(i, snake)| (*i, snake.id.clone())).collect();
+    eliminated.sort();
+    let fast: Outcome = (
+        after.snakes.iter().map(snapshot).collect(),
+        eliminated.into_iter().map(|(_, id)| id).collect(),
+        after.food.clone(),
+    );
+    if fast != reference {
+        diverged(&format!("the turn moving {:?}", moves), fast, reference, before);
+    }
+}
+
+/// # check_search_board
+/// checks the game board, occupancy and hash a search board keeps in step against the ones built from its board
+pub(crate) fn check_search_board(search: &SearchBoard) {
+    let board = &search.board;
+    let game_board = board.to_game_board();
+    if search.game_board != game_board {
+        diverged("the incremental game board", &search.game_board, game_board, board);
+    }
+    let occupancy = board.to_occupancy();
+    if search.occupancy != occupancy {
+        diverged("the incremental occupancy", &search.occupancy, occupancy, board);
+    }
+    let hash = zobrist::hash_board(board);
+    if search.hash != hash {
+        diverged("the incremental hash", search.hash, hash, board);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{self, FIXTURE_DIR};
+    use crate::logic::tests::random_board;
+    use crate::search::minimax::MOVES;
+    use crate::seed;
+    use rand::Rng;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> types::GameState {
+        let fixtures = fixtures::load(Path::new(FIXTURE_DIR)).unwrap();
+        return fixtures.into_iter().find(|fixture| fixture.name == name).unwrap().state;
+    }
+
+    #[test]
+    fn the_references_agree_with_the_fast_paths_on_the_fixtures() {
+        for fixture in fixtures::load(Path::new(FIXTURE_DIR)).unwrap() {
+            let state = &fixture.state;
+            let board = state.board.with_rules(&Ruleset::from(&state.game));
+            let game_board = board.to_game_board();
+            let ctx = TurnContext::sample(&board, &game_board, &state.you);
+            check_open_tiles(&ctx);
+            for dir in types::DIRECTIONS.values() {
+                let tile = state.you.head + *dir;
+                let connected = ctx.connectivity.connected_tiles(&tile, &[]).unwrap();
+                check_connected(&ctx, &tile, &[], connected, u32::MAX);
+            }
+        }
+    }
+
+    #[test]
+    fn the_references_agree_with_the_simulator_on_random_games() {
+        let mut rng = seed::rng("the_references_agree_with_the_simulator_on_random_games", 0);
+        for _ in 0..20 {
+            let mut search = SearchBoard::new(random_board(&mut rng), 0);
+            let mut tokens = vec![];
+            while !search.board.snakes.is_empty() && tokens.len() < 30 {
+                let before = search.board.clone();
+                let moves: Vec<Coord> = before.snakes.iter().map(|_| MOVES[rng.gen_range(0..4)]).collect();
+                let token = search.apply(&moves, &Ruleset::default());
+                check_turn(&before, &moves, &Ruleset::default(), &search.board, &token);
+                check_search_board(&search);
+                tokens.push(token);
+            }
+            while let Some(token) = tokens.pop() {
+                search.undo(token);
+                check_search_board(&search);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "the tiles connected to")]
+    fn a_miscounted_fill_is_caught() {
+        let state = fixture("behind_by_two");
+        let game_board = state.board.to_game_board();
+        let ctx = TurnContext::sample(&state.board, &game_board, &state.you);
+        let tile = state.you.head + types::DIRECTIONS["up"];
+        let connected = ctx.connectivity.connected_tiles(&tile, &[]).unwrap();
+        // a region one tile short, as a labelling that lost a tile would have it
+        check_connected(&ctx, &tile, &[], connected - 1, u32::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "is open")]
+    fn a_tile_opened_by_mistake_is_caught() {
+        let state = fixture("behind_by_two");
+        let mut game_board = state.board.to_game_board();
+        // a neck segment dropped from the game board, the way a missed flag in an incremental update would
+        game_board.remove(&state.you.body[1]);
+        let ctx = TurnContext::sample(&state.board, &game_board, &state.you);
+        check_open_tiles(&ctx);
+    }
+
+    #[test]
+    #[should_panic(expected = "the turn moving")]
+    fn a_wrong_turn_is_caught() {
+        let state = fixture("behind_by_two");
+        let mut board = state.board.clone();
+        let moves: Vec<Coord> = board.snakes.iter().map(|snake| snake.heading().unwrap_or(MOVES[0])).collect();
+        let token = board.simulate_turn(&moves, 1, &Ruleset::default());
+        // a health the simulator forgot to take off
+        board.snakes[0].health += 1;
+        check_turn(&state.board, &moves, &Ruleset::default(), &board, &token);
+    }
+
+    #[test]
+    #[should_panic(expected = "the incremental occupancy")]
+    fn a_stale_occupancy_is_caught() {
+        let mut search = SearchBoard::new(fixture("behind_by_two").board, 0);
+        let tail = *search.board.snakes[0].body.last().unwrap();
+        search.occupancy.clear(tail);
+        check_search_board(&search);
+    }
+}
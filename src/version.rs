@@ -0,0 +1,72 @@
+use crate::logic::StrategyProfile;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// the commit the binary was built from, embedded by build.rs when it could find one
+const COMMIT: Option<&str> = option_env!("BATTLESNAKE_COMMIT");
+
+/// # commit
+/// ## Returns:
+/// the embedded commit, `unknown` when the build had no git metadata
+fn commit(embedded: Option<&'static str>) -> &'static str {
+    return embedded
+        .filter(|commit| !commit.is_empty())
+        .unwrap_or("unknown");
+}
+
+/// # version
+/// which build is running and the profiles it plays with, for the `/version` route
+/// ## Arguments:
+/// * profiles - the profile of every personality, by route
+pub fn version<'a>(profiles: impl Iterator<Item = (&'a str, &'a StrategyProfile)>) -> Value {
+    let profiles: serde_json::Map<String, Value> = profiles
+        .map(|(route, profile)| (route.to_string(), json!(profile)))
+        .collect();
+    return json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "commit": commit(COMMIT),
+        "profiles": profiles,
+    });
+}
+
+/// # health
+/// the liveness report for the `/health` route
+/// ## Arguments:
+/// * uptime - how long the server has been running
+/// * in_flight - the games being played
+/// * draining - whether we're refusing new games before shutting down
+pub fn health(uptime: Duration, in_flight: usize, draining: bool) -> Value {
+    return json!({
+        "status": if draining { "draining" } else { "ok" },
+        "uptime_secs": uptime.as_secs(),
+        "games_in_flight": in_flight,
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::logic;
+
+    #[test]
+    fn missing_commit_is_unknown() {
+        assert_eq!(commit(None), "unknown");
+        assert_eq!(commit(Some("")), "unknown");
+        assert_eq!(commit(Some("0da361a4c2e1")), "0da361a4c2e1");
+    }
+
+    #[test]
+    fn version_lists_every_profile() {
+        let default = StrategyProfile::default();
+        let profiles = logic::PROFILES
+            .iter()
+            .map(|(route, profile)| (*route, profile));
+        let version = version([("", &default)].into_iter().chain(profiles));
+        assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+        assert!(version["commit"].is_string());
+        assert_eq!(version["profiles"][""]["degree_threshold"], 2);
+        for (route, _) in logic::PROFILES {
+            assert!(version["profiles"][route].is_object(), "{}", route);
+        }
+    }
+}
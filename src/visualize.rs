@@ -0,0 +1,225 @@
+//! renders a replay as a single HTML page with a turn slider: the board of every turn, our move and the trace that
+//! chose it. everything it needs is inlined so the page can be attached to an issue or opened offline
+
+use crate::replay::{self, ReplayEntry};
+use crate::types::Coord;
+use clap::Parser;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// the page, the frames go where the placeholder is
+const TEMPLATE: &str = include_str!("visualize.html");
+const FRAMES_PLACEHOLDER: &str = "/*FRAMES*/";
+
+/// the `visualize` command's arguments
+#[derive(Parser, Debug)]
+#[command(name = "visualize")]
+pub struct VisualizeArgs {
+    /// the replay log of a game
+    file: PathBuf,
+    /// where the page goes, next to the replay with an `.html` extension by default
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+    /// the most turns put on the page, the last ones are kept since that's where a game is lost
+    #[arg(long, default_value_t = 1000, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    max_turns: usize,
+}
+
+/// a snake as the page draws it
+#[derive(Serialize, Debug)]
+struct SnakeFrame {
+    name: String,
+    health: u8,
+    body: Vec<Coord>,
+    ours: bool,
+}
+
+/// a turn as the page draws it
+#[derive(Serialize, Debug)]
+struct Frame {
+    turn: u32,
+    width: u8,
+    height: u8,
+    food: Vec<Coord>,
+    hazards: Vec<Coord>,
+    snakes: Vec<SnakeFrame>,
+    /// our move, none on the final board and on turns we weren't asked about
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chosen: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace: Option<Value>,
+    /// we're no longer on the board
+    dead: bool,
+}
+
+/// the data embedded in the page
+#[derive(Serialize, Debug)]
+struct Page<'a> {
+    game_id: &'a str,
+    /// the turns left out from the start of a game too long for the page
+    skipped: usize,
+    frames: Vec<Frame>,
+}
+
+/// # frames
+/// a frame per move request and one for the final board. a game we died early in keeps going without our snake
+fn frames(entries: &[ReplayEntry]) -> Vec<Frame> {
+    return entries
+        .iter()
+        .filter(|entry| entry.request == "move" || entry.request == "end")
+        .map(|entry| {
+            let state = &entry.state;
+            let snakes: Vec<SnakeFrame> = state
+                .board
+                .snakes
+                .iter()
+                .map(|snake| SnakeFrame {
+                    name: snake.name.clone(),
+                    health: snake.health,
+                    body: snake.body.clone(),
+                    ours: snake.id == state.you.id,
+                })
+                .collect();
+            let chosen = entry
+                .response
+                .as_ref()
+                .and_then(|response| response["move"].as_str())
+                .map(str::to_string);
+            return Frame {
+                turn: state.turn,
+                width: state.board.width,
+                height: state.board.height,
+                food: state.board.food.clone(),
+                hazards: state.board.hazards.clone(),
+                dead: !snakes.iter().any(|snake| snake.ours),
+                snakes,
+                chosen,
+                trace: entry.trace.clone(),
+            };
+        })
+        .collect();
+}
+
+/// # render
+/// the page for a game
+/// ## Arguments:
+/// * entries - the game's replay
+/// * max_turns - the most turns put on the page, the earliest are left out past it
+/// ## Returns:
+/// the page, or why its data couldn't be written
+pub fn render(entries: &[ReplayEntry], max_turns: usize) -> serde_json::Result<String> {
+    let mut frames = frames(entries);
+    let skipped = frames.len().saturating_sub(max_turns);
+    frames.drain(..skipped);
+    let page = Page {
+        game_id: entries.first().map_or("", |entry| &entry.state.game.id),
+        skipped,
+        frames,
+    };
+    // a `<` can only be inside a string, escaping it keeps a name like `</script>` from closing the data early
+    let data = serde_json::to_string(&page)?.replace('<', "\\u003c");
+    return Ok(TEMPLATE.replacen(FRAMES_PLACEHOLDER, &data, 1));
+}
+
+/// # run
+/// the `visualize` command: writes the page for a replay
+/// ## Arguments:
+/// * args - the parsed arguments
+/// * out - where the outcome is written
+/// ## Returns:
+/// the exit code, 2 when the replay can't be read or the page written
+pub fn run(args: &VisualizeArgs, out: &mut impl Write) -> i32 {
+    let page = fs::read_to_string(&args.file)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| replay::load(&contents).map_err(|err| err.to_string()))
+        .and_then(|entries| render(&entries, args.max_turns).map_err(|err| err.to_string()));
+    let page = match page {
+        Ok(page) => page,
+        Err(err) => {
+            let _ = writeln!(out, "couldn't read {}: {}", args.file.display(), err);
+            return 2;
+        }
+    };
+    let path = args
+        .out
+        .clone()
+        .unwrap_or_else(|| args.file.with_extension("html"));
+    if let Err(err) = fs::write(&path, page) {
+        let _ = writeln!(out, "couldn't write {}: {}", path.display(), err);
+        return 2;
+    }
+    let _ = writeln!(out, "wrote {}", path.display());
+    return 0;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    /// the data embedded in a page
+    fn embedded(page: &str) -> Value {
+        let start = page.find("type=\"application/json\">").unwrap();
+        let data = &page[start..];
+        let data = &data[data.find('>').unwrap() + 1..data.find("</script>").unwrap()];
+        return serde_json::from_str(data).unwrap();
+    }
+
+    #[test]
+    fn replay_becomes_a_page() {
+        let dir = env::temp_dir().join(format!("battlesnake-visualize-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("game.html");
+        let file = "testdata/replays/standard.jsonl";
+        let args = VisualizeArgs::try_parse_from(["visualize", file, "-o", path.to_str().unwrap()])
+            .unwrap();
+        let mut out: Vec<u8> = vec![];
+        assert_eq!(run(&args, &mut out), 0);
+        let page = String::from_utf8(fs::read(&path).unwrap()).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+
+        let entries = replay::load(&fs::read_to_string(file).unwrap()).unwrap();
+        let moves = entries
+            .iter()
+            .filter(|entry| entry.request == "move")
+            .count();
+        let data = embedded(&page);
+        let frames = data["frames"].as_array().unwrap();
+        // every move and the final board
+        assert_eq!(frames.len(), moves + 1);
+        assert_eq!(data["skipped"], 0);
+        assert!(frames[..moves]
+            .iter()
+            .all(|frame| frame["chosen"].is_string()));
+        assert!(frames[moves].get("chosen").is_none());
+        assert!(!page.contains(FRAMES_PLACEHOLDER));
+        assert!(!page.contains("src=\"http"));
+    }
+
+    #[test]
+    fn long_and_lost_games_fit_the_page() {
+        let mut entries =
+            replay::load(&fs::read_to_string("testdata/replays/standard.jsonl").unwrap()).unwrap();
+        // we died and the game went on without us
+        let last = entries.last_mut().unwrap();
+        let you = last.state.you.id.clone();
+        last.state.board.snakes.retain(|snake| snake.id != you);
+        let count = entries.len();
+        let named = entries[count - 2].state.board.snakes.last_mut().unwrap();
+        named.name = "</script><script>alert(1)".to_string();
+
+        let page = render(&entries, 2).unwrap();
+        assert_eq!(page.matches("</script>").count(), 2);
+        let data = embedded(&page);
+        let frames = data["frames"].as_array().unwrap();
+        assert_eq!(frames.len(), 2);
+        assert!(data["skipped"].as_u64().unwrap() > 0);
+        assert_eq!(frames[0]["dead"], false);
+        assert_eq!(frames[1]["dead"], true);
+        let names = frames[0]["snakes"].as_array().unwrap();
+        assert_eq!(names.last().unwrap()["name"], "</script><script>alert(1)");
+    }
+}
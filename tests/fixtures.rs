@@ -0,0 +1,47 @@
+//! the recorded positions, played through the library the way any other crate would
+
+#![allow(clippy::needless_return)]
+
+use battlesnake::fixtures::{self, FIXTURE_DIR, GOLDEN_MOVES, UPDATE_GOLDEN_ENV};
+use battlesnake::{decide, StrategyConfig};
+use std::env;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[test]
+fn every_fixture_holds() {
+    let fixtures = fixtures::load(Path::new(FIXTURE_DIR)).unwrap();
+    assert!(!fixtures.is_empty());
+    let failures: Vec<String> = fixtures
+        .iter()
+        .filter_map(|fixture| {
+            let outcome = fixture.check();
+            return (!outcome.passed).then(|| fixture.report(&outcome));
+        })
+        .collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
+
+#[test]
+fn decide_answers_with_the_trace_it_logs() {
+    let fixtures = fixtures::load(Path::new(FIXTURE_DIR)).unwrap();
+    let config = StrategyConfig::default();
+    for fixture in &fixtures {
+        let decision = decide(&fixture.state, &config, Instant::now() + Duration::from_secs(1));
+        assert_eq!(decision.response["move"], decision.trace.chosen.as_str(), "{}", fixture.name);
+        assert_eq!(decision.trace.game_id, fixture.state.game.id, "{}", fixture.name);
+    }
+}
+
+#[test]
+fn every_fixture_answers_its_golden_move() {
+    let fixtures = fixtures::load(Path::new(FIXTURE_DIR)).unwrap();
+    let path = Path::new(GOLDEN_MOVES);
+    let mut golden = fixtures::load_golden(path).unwrap();
+    let update = env::var(UPDATE_GOLDEN_ENV).is_ok_and(|value| value == "1");
+    let failures = fixtures::check_golden(&fixtures, &mut golden, update);
+    if update {
+        fixtures::write_golden(path, &golden).unwrap();
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
@@ -0,0 +1,124 @@
+//! whole games between `decide` and a scripted opponent with `play_turn` as the referee, the simulator, the
+//! pipeline and the seeded randomness all playing together the way they never do in a single fixture
+
+#![allow(clippy::needless_return)]
+
+use battlesnake::types::{Battlesnake, Board, Coord, Game, Ruleset, DIRECTIONS};
+use battlesnake::{decide, GameState, StrategyConfig};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::time::{Duration, Instant};
+
+/// the seeded games, each one draws its own food
+const GAMES: u64 = 4;
+/// a game still going this many turns in is a draw
+const TURN_CAP: u32 = 150;
+/// the turns we have to last in a game we don't win
+const SURVIVE: u32 = 50;
+/// the games of `GAMES` we have to win
+const WINS: u32 = 3;
+
+/// a snake of three stacked on its spawn, the way every game starts
+fn spawn(id: &str, at: Coord) -> Battlesnake {
+    return Battlesnake {
+        id: id.to_string(),
+        name: id.to_string(),
+        health: 100,
+        body: vec![at; 3],
+        head: at,
+        length: 3,
+        shout: None,
+        squad: String::new(),
+        latency: None,
+    };
+}
+
+/// an 11x11 standard start: us in the bottom left, them in the top right, food next to each of us and in the center
+fn start() -> Board {
+    return Board {
+        height: 11,
+        width: 11,
+        food: vec![Coord { x: 5, y: 5 }, Coord { x: 2, y: 2 }, Coord { x: 8, y: 8 }],
+        snakes: vec![spawn("us", Coord { x: 1, y: 1 }), spawn("them", Coord { x: 9, y: 9 })],
+        hazards: vec![],
+        hazard_odds: vec![],
+        hazard_walls: false,
+    };
+}
+
+/// whether a move stays on the board and off every body, a tail that's about to move out of the way is fair game
+fn legal(board: &Board, snake: &Battlesnake, dir: Coord) -> bool {
+    let tile = snake.head + dir;
+    return board.in_bounds(&tile)
+        && !board.snakes.iter().any(|other| {
+            let stacked = other.body.len() > 1 && other.body[other.body.len() - 1] == other.body[other.body.len() - 2];
+            let moving = if stacked { other.body.len() } else { other.body.len() - 1 };
+            return other.body[..moving].contains(&tile);
+        });
+}
+
+/// the opponent: the legal move closest to the nearest food, straight up when it has nothing legal left
+fn scripted(board: &Board, snake: &Battlesnake) -> Coord {
+    let nearest = |tile: Coord| board.food.iter().map(|food| food.steps(&tile)).min().unwrap_or(0);
+    return ["up", "right", "down", "left"]
+        .into_iter()
+        .map(|name| DIRECTIONS[name])
+        .filter(|dir| legal(board, snake, *dir))
+        .min_by_key(|dir| nearest(snake.head + *dir))
+        .unwrap_or(DIRECTIONS["up"]);
+}
+
+/// # play
+/// plays one game to a winner or the turn cap
+/// ## Returns:
+/// whether we won and the turns we lasted
+fn play(seed: u64) -> (bool, u32) {
+    let game: Game = serde_json::from_value(serde_json::json!({
+        "id": format!("full-game-{}", seed),
+        "ruleset": { "name": "standard" },
+        "timeout": 500
+    }))
+    .unwrap();
+    let rules = Ruleset::from(&game);
+    let config = StrategyConfig::default();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut board = start();
+    let mut turn = 0;
+    while turn < TURN_CAP {
+        let Some(us) = board.snakes.iter().find(|snake| snake.id == "us").cloned() else {
+            return (false, turn);
+        };
+        if board.snakes.len() == 1 {
+            return (true, turn);
+        }
+        let state = GameState {
+            game: game.clone(),
+            turn,
+            board: board.clone(),
+            you: us.clone(),
+        };
+        let decision = decide(&state, &config, Instant::now() + Duration::from_millis(300));
+        let ours = DIRECTIONS[decision.response["move"].as_str().unwrap()];
+        assert!(legal(&board, &us, ours), "game {} turn {}: {:?}\n{}", seed, turn, ours, board.render());
+        let moves: Vec<Coord> = board
+            .snakes
+            .iter()
+            .map(|snake| if snake.id == "us" { ours } else { scripted(&board, snake) })
+            .collect();
+        turn += 1;
+        board.play_turn(&moves, turn, &rules);
+        board.spawn_food(&rules, &mut rng);
+    }
+    return (false, turn);
+}
+
+#[test]
+fn whole_games_against_a_scripted_opponent() {
+    let mut wins = 0;
+    for seed in 0..GAMES {
+        let (won, turns) = play(seed);
+        assert!(won || turns >= SURVIVE, "game {} lost after {} turns", seed, turns);
+        wins += won as u32;
+    }
+    assert!(wins >= WINS, "won {} of {}", wins, GAMES);
+}